@@ -214,6 +214,12 @@ fn parse_and_normalize_grammar(session: &Session, file_text: &FileText) -> io::R
                          &format!("extra token at end of input: `{}`", text));
         }
 
+        Err(ParseError::NestingTooDeep { location }) => {
+            report_error(&file_text,
+                         pt::Span(location, location),
+                         "grammar nested too deeply");
+        }
+
         Err(ParseError::User { error }) => {
             let string = match error.code {
                 tok::ErrorCode::UnrecognizedToken => "unrecognized token",
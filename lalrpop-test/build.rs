@@ -3,6 +3,14 @@ extern crate lalrpop;
 fn main() {
     lalrpop::Configuration::new()
         .emit_comments(true)
+        .emit_introspection(true)
+        .emit_observer(true)
+        .stack_capacity(1000)
+        .error_on_stack_overflow()
+        .reductions_per_fn(8)
+        .pub_crate_entry_points()
+        .set_variable("KEYWORD", "\"answer\"")
+        .set_variable("ANSWER", "42")
         .force_build(true)
         .unit_test()
         .process_current_dir()
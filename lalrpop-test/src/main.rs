@@ -1,9 +1,13 @@
 extern crate diff;
 extern crate lalrpop_util;
+extern crate proc_macro2;
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::rc::Rc;
 
-use lalrpop_util::{ErrorRecovery, ParseError};
+use lalrpop_util::{ErrorRecovery, ParseError, Span};
+use lalrpop_util::location::LineCol;
 
 use util::tok::Tok;
 
@@ -21,9 +25,21 @@ mod expr;
 /// more interesting demonstration of parsing full expressions, using LALR not LR
 mod expr_lalr;
 
+/// more interesting demonstration of parsing full expressions, using IELR not LR
+mod expr_ielr;
+
 /// more interesting demonstration of parsing full expressions, using intern tok
 mod expr_intern_tok;
 
+/// the same, with terminals written as the token variants they match
+mod expr_variant;
+
+/// test the wildcard terminal `_`
+mod wildcard;
+
+/// the same, with the precedence levels declared in an `operators` table
+mod expr_operators;
+
 /// test that passes in lifetime/type/formal parameters and threads
 /// them through, building an AST from the result
 mod expr_arena;
@@ -37,12 +53,18 @@ mod expr_generic;
 mod generics_issue_104;
 mod generics_issue_104_lib;
 
+/// test of bounds like `grammar<T: Generator>`
+mod generic_bounds;
+
 /// test of inlining
 mod inline;
 
 /// test that exercises internal token generation, as well as locations and spans
 mod intern_tok;
 
+/// the same, with lines and columns as locations
+mod intern_tok_line_col;
+
 /// test that exercises using a lifetime parameter in the token type
 mod lifetime_tok;
 
@@ -56,6 +78,45 @@ mod loc;
 mod loc_issue_90;
 mod loc_issue_90_lib;
 
+/// test that exercises access to the lookahead token with `@T`
+mod lookahead_token;
+
+/// test for a grammar that needs two tokens of lookahead
+mod lookahead2;
+
+/// test for a grammar whose shift/reduce conflicts are resolved by
+/// shifting
+mod dangling_else;
+
+/// test for a grammar that is parsed a character at a time
+mod scannerless;
+
+/// test for a grammar whose shift/reduce conflicts are resolved at
+/// parse time
+mod conflict_hook;
+
+/// test for a grammar whose actions can be overridden by its parameter
+mod reduce_hooks;
+
+/// test that exercises the `parse_Foo_items` iterator for `Foo = Item*`
+/// (and its checkpoints) and the `parse_Foo_prefix` entry point
+mod items;
+
+/// test that exercises `parse_Foo_incremental` and its checkpoints
+mod incremental;
+
+/// test of `${NAME}` substitutions
+mod variables;
+
+/// test of `use "file.lalrpop";` imports
+mod import;
+
+/// test of the `PushParser_Foo` of `#[push]` grammars
+mod push;
+
+/// test of the macros of the prelude and of macros taking macros
+mod prelude;
+
 /// test that uses `super` in paths in various places
 mod use_super;
 
@@ -70,6 +131,7 @@ mod error_issue_113;
 /// Test error recovery
 mod error_recovery;
 mod error_recovery_pull_182;
+mod error_recovery_span;
 
 /// test for inlining expansion issue #55
 mod issue_55;
@@ -84,6 +146,15 @@ mod match_section;
 #[allow(unused)]
 mod no_clone_tok;
 
+/// test for nonterminal and binding names that are not ASCII
+mod unicode_ids;
+
+/// test for parsing a `proc_macro2::TokenStream`
+mod token_stream;
+
+/// library for token_stream test
+mod token_stream_lib;
+
 mod util;
 
 /// This constant is here so that some of the generator parsers can
@@ -115,6 +186,18 @@ fn expr_test5() {
     util::test(|v| expr::parse_Expr(11, v), "22 * 3 - 6", 22*11 * 3*11 - 6*11);
 }
 
+#[test]
+fn expr_variant_test() {
+    util::test(|v| expr_variant::parse_Expr(2, v), "22 * 3 - 6", 22*2 * 3*2 - 6*2);
+}
+
+#[test]
+fn wildcard_test() {
+    // the `,` is matched by its own terminal even where `_` could
+    // match it, and `+`, which the grammar never mentions, by `_`
+    util::test(|v| wildcard::parse_List(v), "1, + 2 *, 3", vec![Some(1), None, Some(3)]);
+}
+
 #[test]
 fn expr_intern_tok_test1() {
     assert_eq!(expr_intern_tok::parse_Expr(1, "22 - 3").unwrap(), 22 - 3);
@@ -125,6 +208,19 @@ fn expr_intern_tok_test2() {
     assert_eq!(expr_intern_tok::parse_Expr(1, "22 - (3 - 5) - 13").unwrap(), 22 - (3 - 5) - 13);
 }
 
+#[test]
+fn expr_intern_tok_threads() {
+    // the parsers on all the threads share the same tables
+    let threads: Vec<_> = (0..4).map(|i| {
+        ::std::thread::spawn(move || {
+            let input = format!("22 - {}", i);
+            expr_intern_tok::parse_Expr(1, &input).unwrap()
+        })
+    }).collect();
+    let results: Vec<_> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+    assert_eq!(results, vec![22, 21, 20, 19]);
+}
+
 #[test]
 fn expr_intern_tok_test_err() {
     match expr_intern_tok::parse_Expr(1, "22 - (3 - 5) - X") {
@@ -138,6 +234,131 @@ fn expr_intern_tok_test_err() {
     }
 }
 
+#[test]
+fn expr_intern_tok_expected_tokens() {
+    // in the start state, only a number or a `(` can come next
+    let expected = expr_intern_tok::expected_tokens_Expr(&[0]);
+    assert_eq!(expected, vec![r#""(""#.to_string(), r##"r#"[0-9]+"#"##.to_string()]);
+}
+
+#[test]
+fn expr_intern_tok_complete() {
+    let completion = expr_intern_tok::complete_Expr(1, "22 - ").unwrap();
+    assert_eq!(completion.terminals,
+               vec![r#""(""#.to_string(), r##"r#"[0-9]+"#"##.to_string()]);
+    assert_eq!(completion.nonterminals, vec!["Factor", "Num", "Term"]);
+    assert!(!completion.can_end);
+
+    let completion = expr_intern_tok::complete_Expr(1, "22").unwrap();
+    assert!(completion.terminals.contains(&r#""*""#.to_string()));
+    assert!(completion.nonterminals.is_empty());
+    assert!(completion.can_end);
+
+    match expr_intern_tok::complete_Expr(1, "22 )") {
+        Err(ParseError::UnrecognizedToken { token: Some((3, _, 4)), .. }) => { }
+        r => panic!("unexpected result {:?}", r),
+    }
+}
+
+#[test]
+fn expr_intern_tok_observe() {
+    let steps = Rc::new(RefCell::new(vec![]));
+    let observed = steps.clone();
+    expr_intern_tok::observe_Expr(Some(Box::new(move |step: &expr_intern_tok::Introspection_Expr| {
+        assert!(!step.items().is_empty());
+        observed.borrow_mut().push((step.states().to_vec(),
+                                    step.lookahead(),
+                                    step.pending_reductions()));
+    })));
+    assert_eq!(expr_intern_tok::parse_Expr(1, "22").unwrap(), 22);
+    expr_intern_tok::observe_Expr(None);
+
+    // the number is shifted right away; then, at EOF, it is reduced
+    // all the way up to `Expr`, one step at a time
+    let steps = steps.borrow();
+    assert_eq!(steps.len(), 6);
+    assert_eq!(steps[0], (vec![0], Some(r##"r#"[0-9]+"#"##), vec![]));
+    assert_eq!(steps[1].1, None);
+    assert_eq!(steps[1].2.len(), 5);
+    assert_eq!(steps[1].2[0], r##"Num = r#"[0-9]+"#"##);
+    assert_eq!(steps[1].2[1], "Term = Num");
+    assert_eq!(steps[5].2.len(), 1);
+}
+
+/// Records what the parser of `expr_intern_tok` does, as text.
+struct Events(Rc<RefCell<Vec<String>>>);
+
+impl expr_intern_tok::Observer_Expr for Events {
+    fn shift(&mut self, terminal: &'static str, _state: i32) {
+        self.0.borrow_mut().push(format!("shift {}", terminal));
+    }
+
+    fn reduce(&mut self, production: &'static str) {
+        self.0.borrow_mut().push(format!("reduce {}", production));
+    }
+
+    fn error(&mut self, lookahead: Option<&'static str>, expected: &[String]) {
+        assert!(!expected.is_empty());
+        self.0.borrow_mut().push(format!("error {:?}", lookahead));
+    }
+}
+
+#[test]
+fn expr_intern_tok_observer() {
+    let events = Rc::new(RefCell::new(vec![]));
+    expr_intern_tok::set_observer_Expr(Some(Box::new(Events(events.clone()))));
+    assert_eq!(expr_intern_tok::parse_Expr(1, "22").unwrap(), 22);
+    assert!(expr_intern_tok::parse_Expr(1, "22 )").is_err());
+    expr_intern_tok::set_observer_Expr(None);
+
+    let events = events.borrow();
+    assert_eq!(events[0], r##"shift r#"[0-9]+"#"##);
+    assert_eq!(events[1], r##"reduce Num = r#"[0-9]+"#"##);
+    assert_eq!(events[2], "reduce Term = Num");
+    assert_eq!(events[6], r##"shift r#"[0-9]+"#"##);
+    assert_eq!(events.last().unwrap(), r#"error Some("\")\"")"#);
+}
+
+#[test]
+fn expr_intern_tok_display_error() {
+    let error = expr_intern_tok::parse_Expr(1, "22 - (3 - 5) 13").unwrap_err();
+    //                                          012345678901234
+    let message = expr_intern_tok::display_error(&error).to_string();
+    assert!(message.starts_with("Unrecognized token \"13\" found at 13:15\nExpected one of "),
+            "unexpected message {:?}", message);
+}
+
+#[test]
+fn expr_operators_test() {
+    assert_eq!(expr_operators::parse_Expr("22 - 3 - 5").unwrap(), 22 - 3 - 5);
+    assert_eq!(expr_operators::parse_Expr("1 + 2 * 3").unwrap(), 1 + 2 * 3);
+    assert_eq!(expr_operators::parse_Expr("(1 + 2) * 3").unwrap(), (1 + 2) * 3);
+    assert_eq!(expr_operators::parse_Expr("2 ^ 3 ^ 2").unwrap(), 512);
+    assert_eq!(expr_operators::parse_Expr("-2 ^ 2").unwrap(), -4);
+    assert_eq!(expr_operators::parse_Expr("2 * -3 - -1").unwrap(), -5);
+}
+
+#[test]
+fn expr_operators_mixfix_test() {
+    assert_eq!(expr_operators::parse_Expr("1 ? 2 : 3").unwrap(), 2);
+    assert_eq!(expr_operators::parse_Expr("0 ? 1 : 0 ? 2 : 3 + 1").unwrap(), 4);
+    assert_eq!(expr_operators::parse_Expr("1 - 1 ? 1 ? 2 : 3 : 4").unwrap(), 4);
+    assert_eq!(expr_operators::parse_Expr("|2 - 5| * 2").unwrap(), 6);
+}
+
+#[test]
+fn expr_operators_nonassoc_test() {
+    assert_eq!(expr_operators::parse_Expr("1 + 1 == 2").unwrap(), 1);
+    assert_eq!(expr_operators::parse_Expr("(1 < 2) < 3").unwrap(), 1);
+
+    // chaining a non-associative operator is a parse error at the
+    // second operator
+    match expr_operators::parse_Expr("1 < 2 < 3") {
+        Err(ParseError::UnrecognizedToken { token: Some((6, _, 7)), .. }) => { }
+        r => panic!("unexpected result {:?}", r),
+    }
+}
+
 #[test]
 fn expr_lifetime_tok1() {
     // the problem here was that we were improperly pruning the 'input from the
@@ -171,6 +392,16 @@ fn expr_lalr_test5() {
     util::test(|v| expr_lalr::parse_Expr(11, v), "22 * 3 - 6", 22*11 * 3*11 - 6*11);
 }
 
+#[test]
+fn expr_ielr_test1() {
+    util::test(|v| expr_ielr::parse_Expr(1, v), "22 - (3 - 5) - 13", 22 - (3 - 5) - 13);
+}
+
+#[test]
+fn expr_ielr_test2() {
+    util::test(|v| expr_ielr::parse_Expr(11, v), "22 * 3 - 6", 22*11 * 3*11 - 6*11);
+}
+
 #[test]
 fn inline_test1() {
     assert_eq!(inline::parse_E("& L L").unwrap(), "& L L");
@@ -201,6 +432,20 @@ fn sub_table_test1() {
     util::test(sub_table::parse_S, "22 - 3", 22 - 3);
 }
 
+#[test]
+fn sub_table_nesting_too_deep() {
+    // the stacks hold at most 1000 symbols (see `build.rs`)
+    let input = format!("{}22{}", "(".repeat(900), ")".repeat(900));
+    util::test(sub_table::parse_S, &input, 22);
+
+    let input = format!("{}22{}", "(".repeat(1100), ")".repeat(1100));
+    let tokens: Vec<_> = util::tok::tokenize(&input).into_iter().map(|(_, tok, _)| tok).collect();
+    match sub_table::parse_S(tokens) {
+        Err(ParseError::NestingTooDeep { location: () }) => { }
+        r => panic!("unexpected result {:?}", r),
+    }
+}
+
 #[test]
 fn expr_arena_test1() {
     use expr_arena_ast::*;
@@ -261,6 +506,16 @@ fn intern_tok_test1() {
     assert_eq!(actual, expected);
 }
 
+#[test]
+fn intern_tok_line_col_test() {
+    let line_col = |line, column| LineCol { line: line, column: column };
+    let expected = vec![(line_col(0, 0), line_col(0, 0)),
+                        (line_col(0, 1), line_col(0, 2)),
+                        (line_col(1, 2), line_col(1, 3))];
+    let actual = intern_tok_line_col::parse_Items("-+\n -+").unwrap();
+    assert_eq!(actual, expected);
+}
+
 #[test]
 fn loc_test1() {
     let expected = vec![(0, 0), // note that tok.rs generates odd spans, measured in 2*chars
@@ -284,6 +539,161 @@ fn loc_empty() {
     util::test_loc(|v| loc::parse_Items(v), "", vec![(0, 0)]);
 }
 
+#[test]
+fn lookahead_token_test1() {
+    let actual = lookahead_token::parse_Items("+-++").unwrap();
+    assert_eq!(actual, vec![Some("-"), Some("+"), None]);
+}
+
+#[test]
+fn items_test1() {
+    let actual: Vec<_> = items::parse_Items_items("1; { 2; 3; } 4;").map(|r| r.unwrap()).collect();
+    assert_eq!(actual, vec![1, 5, 4]);
+    assert_eq!(items::parse_Items("1; { 2; 3; } 4;").unwrap(), vec![1, 5, 4]);
+}
+
+#[test]
+fn items_test_err() {
+    // items before the error are still produced
+    let mut iter = items::parse_Items_items("1; 2 3;");
+    assert_eq!(iter.next(), Some(Ok(1)));
+    match iter.next() {
+        Some(Err(ParseError::UnrecognizedToken { token: Some((5, _, 6)), .. })) => { }
+        r => panic!("unexpected result {:?}", r),
+    }
+    assert_eq!(iter.next(), None);
+
+    // the same goes for errors from the tokenizer
+    let actual: Vec<_> = items::parse_Items_items("1; 2; $").collect();
+    assert_eq!(actual, vec![Ok(1), Ok(2), Err(ParseError::InvalidToken { location: 6 })]);
+}
+
+#[test]
+fn items_prefix_test() {
+    // the longest prefix ends before the first token that cannot
+    // continue the parse...
+    assert_eq!(items::parse_Items_prefix("1; 2; } 3;").unwrap(), (vec![1, 2], 5));
+    // ...or before input that the tokenizer does not recognize...
+    assert_eq!(items::parse_Items_prefix("1; $ 3;").unwrap(), (vec![1], 2));
+    // ...or at the end of the input
+    assert_eq!(items::parse_Items_prefix("1; 2;").unwrap(), (vec![1, 2], 5));
+
+    // an incomplete item after it is left alone...
+    assert_eq!(items::parse_Items_prefix("1; 2 }").unwrap(), (vec![1], 2));
+    assert_eq!(items::parse_Items_prefix("1; { 2; 3;").unwrap(), (vec![1], 2));
+    // ...even if it is all there is
+    assert_eq!(items::parse_Items_prefix("2 }").unwrap(), (vec![], 0));
+}
+
+#[test]
+fn incremental_test() {
+    let mut checkpoints = incremental::Checkpoints_Sum::new(4);
+    assert_eq!(incremental::parse_Sum_incremental("1 + 2 + 3 + 4", &mut checkpoints), Ok(10));
+    //                                             0123456789012
+    assert_eq!(checkpoints.offsets(), vec![4, 8, 12]);
+
+    // parsing the same input again only reparses the `4`
+    assert_eq!(incremental::parse_Sum_incremental("1 + 2 + 3 + 4", &mut checkpoints), Ok(10));
+    assert_eq!(checkpoints.offsets(), vec![4, 8, 12]);
+
+    // inserting a `0` after the `3` throws away the checkpoints after it
+    checkpoints.edit(9);
+    assert_eq!(checkpoints.offsets(), vec![4, 8]);
+    assert_eq!(incremental::parse_Sum_incremental("1 + 2 + 30 + 4", &mut checkpoints), Ok(37));
+    //                                             01234567890123
+    assert_eq!(checkpoints.offsets(), vec![4, 8, 13]);
+}
+
+#[test]
+fn variables_test() {
+    assert_eq!(variables::parse_Answer("answer"), Ok(42));
+    // the literal `"${KEYWORD}"`, and the string in its action code,
+    // are left as written
+    assert_eq!(variables::parse_Answer("${KEYWORD}"), Ok(2));
+    assert!(variables::parse_Answer("${ANSWER}").is_err());
+}
+
+#[test]
+fn import_test() {
+    assert_eq!(import::parse_Expr("1+2*3"), Ok(7));
+    assert_eq!(import::parse_Expr("2*3+4*5"), Ok(26));
+}
+
+#[test]
+fn push_test() {
+    let mut parser = push::PushParser_Expr::new(2);
+    for token in util::tok::tokenize("(1 + 2) * 3") {
+        parser.push(token).unwrap();
+    }
+    assert_eq!(parser.finish(), Ok(36));
+
+    // the input may end too early, which only `finish` can tell
+    let mut parser = push::PushParser_Expr::new(1);
+    for token in util::tok::tokenize("1 +") {
+        parser.push(token).unwrap();
+    }
+    assert!(parser.expected_tokens().contains(&"Num".to_string()));
+    assert!(parser.finish().is_err());
+
+    // but a token that cannot follow is rejected as it is pushed
+    let mut parser = push::PushParser_Expr::new(1);
+    parser.push((0, Tok::Num(1), 1)).unwrap();
+    match parser.push((2, Tok::LParen, 3)) {
+        Err(ParseError::UnrecognizedToken { token: Some((2, Tok::LParen, 3)), .. }) => { }
+        r => panic!("unexpected result {:?}", r),
+    }
+}
+
+#[test]
+fn prelude_test() {
+    assert_eq!(prelude::parse_List("[1, 2, 3,]"), Ok(vec![1, 2, 3]));
+    assert_eq!(prelude::parse_List("[]"), Ok(vec![]));
+    assert_eq!(prelude::parse_Items("1; 2"), Ok(vec![1, 2]));
+    assert!(prelude::parse_Items("1, 2").is_err());
+}
+
+#[test]
+fn unicode_ids_test() {
+    assert_eq!(unicode_ids::parse_Größe("1 + 2 + 3"), Ok(6));
+    assert_eq!(unicode_ids::parse_Σa("a"), Ok(1));
+    assert_eq!(unicode_ids::parse_㨺("b"), Ok(2));
+}
+
+#[test]
+fn items_rewind_test() {
+    let mut iter = items::parse_Items_items("1; 2; 3;");
+    assert_eq!(iter.next(), Some(Ok(1)));
+    let checkpoint = iter.checkpoint();
+    assert_eq!(checkpoint, 3);
+    assert_eq!(iter.next(), Some(Ok(2)));
+    assert_eq!(iter.next(), Some(Ok(3)));
+    assert_eq!(iter.next(), None);
+    iter.rewind(checkpoint);
+    assert_eq!(iter.collect::<Vec<_>>(), vec![Ok(2), Ok(3)]);
+
+    // an error does not stop the iterator from going back
+    let mut iter = items::parse_Items_items("1; 2 3;");
+    assert_eq!(iter.next(), Some(Ok(1)));
+    let checkpoint = iter.checkpoint();
+    assert!(iter.next().unwrap().is_err());
+    assert_eq!(iter.next(), None);
+    iter.rewind(checkpoint);
+    assert!(iter.next().unwrap().is_err());
+}
+
+#[test]
+fn items_derivations_test() {
+    // parsing the items one at a time gives the same result as
+    // parsing them all at once
+    items::derivations_Items().check(100, |input| {
+        let one_by_one: Result<Vec<_>, _> = items::parse_Items_items(input).collect();
+        match (items::parse_Items(input), one_by_one) {
+            (Ok(all), Ok(one_by_one)) => all == one_by_one,
+            _ => false,
+        }
+    });
+}
+
 #[test]
 fn use_super_test1() {
     util::test(|v| use_super::parse_S(v), "()", 0);
@@ -370,6 +780,20 @@ fn error_recovery_multiple_extra_tokens() {
     });
 }
 
+#[test]
+fn error_recovery_span() {
+    let recovery = error_recovery_span::parse_Item("(a b c)").unwrap().unwrap_err();
+    //                                              0123456
+    assert_eq!(recovery.span(), Some(Span::new(3, 6)));
+    let dropped: Vec<_> = recovery.dropped_tokens.iter().map(|&(_, (_, text), _)| text).collect();
+    assert_eq!(dropped, vec!["b", "c"]);
+
+    // nothing was skipped, so the span is empty
+    let recovery = error_recovery_span::parse_Item("()").unwrap().unwrap_err();
+    assert_eq!(recovery.span(), Some(Span::new(1, 1)));
+    assert!(recovery.dropped_tokens.is_empty());
+}
+
 #[test]
 fn error_recovery_dont_panic_on_reduce_normal() {
     let mut errors = vec![];
@@ -421,6 +845,12 @@ fn generics_issue_104_test1() {
     assert!(generics_issue_104::parse_Schema::<()>("grammar { foo }").is_ok());
 }
 
+#[test]
+fn generic_bounds_test() {
+    assert_eq!(generic_bounds::parse_Schema::<(), _>(&1, "grammar { foo }"),
+               Ok("foo1".to_string()));
+}
+
 #[test]
 fn test_match_section() {
     assert!(match_section::parse_Query("SELECT foo").is_ok());
@@ -430,6 +860,102 @@ fn test_match_section() {
     assert!(match_section::parse_Query("UPDATE update").is_err());
 }
 
+#[test]
+fn match_section_derivations_test() {
+    // the derived sentences use the shortest text for each terminal,
+    // which must still make it through the match section
+    match_section::derivations_Query().check(100, |input| {
+        match_section::parse_Query(input).is_ok()
+    });
+}
+
+#[test]
+fn lookahead2_test() {
+    assert_eq!(lookahead2::parse_S("a x y").unwrap(), "A");
+    assert_eq!(lookahead2::parse_S("a x z").unwrap(), "B");
+    assert_eq!(lookahead2::parse_S("a x w").unwrap(), "shift");
+    assert!(lookahead2::parse_S("a x x").is_err());
+    assert!(lookahead2::parse_S("a x").is_err());
+}
+
+impl<'input, 't> conflict_hook::__ConflictHook<'input, 't> for RefCell<HashSet<String>> {
+    fn should_shift(&self, nonterminal: &'static str, lookahead: &(usize, &'input str)) -> bool {
+        // the lookahead starts another declaration only if it names a type
+        assert_eq!(nonterminal, "Stmts");
+        self.borrow().contains(lookahead.1)
+    }
+}
+
+#[test]
+fn conflict_hook_test() {
+    let types = RefCell::new(HashSet::new());
+    assert_eq!(conflict_hook::parse_Block(&types, "{ typedef t; t x; f x; }").unwrap(),
+               (vec!["typedef t".to_string(), "t x".to_string()], vec!["f(x)".to_string()]));
+    assert_eq!(conflict_hook::parse_Block(&types, "{ x; t y; }").unwrap(),
+               (vec![], vec!["x".to_string(), "t(y)".to_string()]));
+}
+
+/// What the parser of `reduce_hooks` has reduced: the additions are
+/// counted by the grammar, and the parentheses by the override below.
+pub struct Metrics {
+    additions: Cell<u32>,
+    parentheses: Cell<u32>,
+}
+
+impl<'input, 'm> reduce_hooks::__ReduceHooks<'input, 'm> for Metrics {
+    fn reduce_Term_1(&'m self, _input: &'input str, e: i32) -> i32 {
+        self.parentheses.set(self.parentheses.get() + 1);
+        e
+    }
+}
+
+#[test]
+fn reduce_hooks_test() {
+    let metrics = Metrics {
+        additions: Cell::new(0),
+        parentheses: Cell::new(0),
+    };
+    assert_eq!(reduce_hooks::parse_Expr(&metrics, "(1+2)+((3))").unwrap(), 6);
+    assert_eq!(metrics.additions.get(), 2);
+    assert_eq!(metrics.parentheses.get(), 3);
+}
+
+#[test]
+fn dangling_else_test() {
+    assert_eq!(dangling_else::parse_Stmt("if a 1 else 2").unwrap(), "(if a 1 2)");
+    assert_eq!(dangling_else::parse_Stmt("if a if b 1 else 2").unwrap(), "(if a (if b 1 2))");
+    assert_eq!(dangling_else::parse_Stmt("if a if b 1 else 2 else 3").unwrap(),
+               "(if a (if b 1 2) 3)");
+}
+
+#[test]
+fn scannerless_test() {
+    assert_eq!(scannerless::parse_Items("ab,0x1f,12,x").unwrap(),
+               vec!["ab", "0x1f", "12", "x"]);
+    assert!(scannerless::parse_Items("ab, 12").is_err());
+    assert_eq!(scannerless::parse_Words("abc").unwrap(), vec!["abc"]);
+}
+
+#[test]
+fn token_stream_test() {
+    use lalrpop_util::token_stream::Tokens;
+    use token_stream_lib::Keyword;
+
+    let parse = |text: &str| {
+        let tokens: Tokens<_, ()> = Tokens::new(text.parse().unwrap()).keyword("fn", Keyword::Fn);
+        token_stream::parse_Item(tokens)
+    };
+    assert_eq!(parse("fn f(a, b)").unwrap(), "f(a, b)");
+    assert_eq!(parse("a + b").unwrap(), "a + b");
+    assert_eq!(parse("a += b").unwrap(), "a += b");
+    match parse("fn (a)") {
+        Err(ParseError::UnrecognizedToken { token: Some((start, _, _)), .. }) => {
+            assert_eq!(start.index, 1);
+        }
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
+
 #[test]
 fn issue_113() {
     assert!(error_issue_113::parse_Items("+").is_err());
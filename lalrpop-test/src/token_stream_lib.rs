@@ -0,0 +1,15 @@
+use std::fmt;
+
+/// The keywords of the `token_stream` grammar.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Keyword {
+    Fn,
+}
+
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Keyword::Fn => write!(f, "fn"),
+        }
+    }
+}
@@ -0,0 +1,408 @@
+//! Random derivations of a grammar, for property-based testing.
+//!
+//! For every grammar that uses the built-in lexer, LALRPOP generates
+//! a function `derivations_Foo()` for each public nonterminal `Foo`,
+//! which returns a `Derivations` strategy. It derives random
+//! sentences of `Foo`, and when one of them falsifies a property it
+//! shrinks it by pruning the derivation tree, so that the input
+//! reported is as small as possible:
+//!
+//! ```ignore
+//! #[test]
+//! fn reparse() {
+//!     calculator::derivations_Expr().check(1000, |input| {
+//!         let expr = calculator::parse_Expr(input).unwrap();
+//!         calculator::parse_Expr(&expr.to_string()).unwrap() == expr
+//!     });
+//! }
+//! ```
+
+use std::fmt;
+
+/// A description of a grammar that is just detailed enough to derive
+/// sentences from it.
+pub struct Grammar {
+    /// For each nonterminal, its name and the right-hand sides of its
+    /// productions.
+    pub nonterminals: &'static [(&'static str, &'static [&'static [Symbol]])],
+
+    /// For each nonterminal, the cost of each of its productions, as
+    /// given by `#[cost(N)]`. Random derivations pick a production of
+    /// cost `N` a factor of `N + 1` less often than one of cost 0.
+    pub costs: &'static [&'static [u32]],
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Symbol {
+    /// A terminal, given by a piece of text that the lexer recognizes
+    /// as that terminal.
+    Terminal(&'static str),
+
+    /// A nonterminal, given by its index in `Grammar::nonterminals`.
+    Nonterminal(usize),
+}
+
+/// A derivation tree for some nonterminal.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Derivation {
+    nonterminal: usize,
+    children: Vec<Child>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Child {
+    Terminal(&'static str),
+    Nonterminal(Derivation),
+}
+
+impl Derivation {
+    /// The number of nodes in the tree.
+    pub fn size(&self) -> usize {
+        1 + self.children
+                .iter()
+                .map(|child| match *child {
+                    Child::Terminal(_) => 1,
+                    Child::Nonterminal(ref derivation) => derivation.size(),
+                })
+                .sum::<usize>()
+    }
+
+    /// The derived sentence, with a space between each pair of
+    /// terminals.
+    pub fn text(&self) -> String {
+        let mut terminals = vec![];
+        self.push_terminals(&mut terminals);
+        terminals.join(" ")
+    }
+
+    fn push_terminals(&self, terminals: &mut Vec<&'static str>) {
+        for child in &self.children {
+            match *child {
+                Child::Terminal(text) => terminals.push(text),
+                Child::Nonterminal(ref derivation) => derivation.push_terminals(terminals),
+            }
+        }
+    }
+
+    /// Every derivation nested within this one, excluding this one.
+    fn push_nested(&self, nested: &mut Vec<Derivation>) {
+        for child in &self.children {
+            if let Child::Nonterminal(ref derivation) = *child {
+                nested.push(derivation.clone());
+                derivation.push_nested(nested);
+            }
+        }
+    }
+}
+
+impl fmt::Display for Derivation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text())
+    }
+}
+
+/// A small xorshift random number generator, so that derivations can
+/// be reproduced from a seed without any further dependencies.
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        let state = seed ^ 0x9E37_79B9_7F4A_7C15;
+        Rng { state: if state == 0 { 1 } else { state } }
+    }
+
+    /// A number in `0..n`.
+    pub fn below(&mut self, n: usize) -> usize {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        (x.wrapping_mul(0x2545_F491_4F6C_DD1D) % (n as u64)) as usize
+    }
+}
+
+/// A strategy for property-based testing that generates random
+/// derivations of one nonterminal and shrinks them by pruning.
+pub struct Derivations {
+    grammar: &'static Grammar,
+    start: usize,
+    max_depth: usize,
+
+    // for each nonterminal, the height of its shallowest derivation,
+    // or `None` if it does not derive any sentence at all
+    heights: Vec<Option<usize>>,
+}
+
+impl Derivations {
+    pub fn new(grammar: &'static Grammar, start: usize) -> Derivations {
+        let mut heights = vec![None; grammar.nonterminals.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (nonterminal, &(_, productions)) in grammar.nonterminals.iter().enumerate() {
+                let height =
+                    productions.iter().filter_map(|p| production_height(&heights, p)).min();
+                if height.is_some() &&
+                   (heights[nonterminal].is_none() || height < heights[nonterminal]) {
+                    heights[nonterminal] = height;
+                    changed = true;
+                }
+            }
+        }
+
+        assert!(heights[start].is_some(),
+                "`{}` does not derive any sentence",
+                grammar.nonterminals[start].0);
+
+        Derivations {
+            grammar: grammar,
+            start: start,
+            max_depth: 8,
+            heights: heights,
+        }
+    }
+
+    /// Limits how deeply derivations are nested; the default is 8.
+    /// Derivations may still be deeper than this if the nonterminal
+    /// cannot be derived otherwise.
+    pub fn max_depth(mut self, max_depth: usize) -> Derivations {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// A random derivation of the nonterminal.
+    pub fn generate(&self, rng: &mut Rng) -> Derivation {
+        self.derive(self.start, self.max_depth, &mut |costs| {
+            // rejection sampling: draw uniformly, then keep a
+            // production of cost `N` with probability `1 / (N + 1)`
+            loop {
+                let index = rng.below(costs.len());
+                let cost = costs[index] as usize;
+                if cost == 0 || rng.below(cost.saturating_add(1)) == 0 {
+                    return index;
+                }
+            }
+        })
+    }
+
+    /// The smallest derivation of the nonterminal.
+    pub fn minimal(&self) -> Derivation {
+        self.derive(self.start, 0, &mut |_| 0)
+    }
+
+    fn derive(&self,
+              nonterminal: usize,
+              depth: usize,
+              choose: &mut FnMut(&[u32]) -> usize)
+              -> Derivation {
+        let productions = self.grammar.nonterminals[nonterminal].1;
+        let heights: Vec<_> =
+            productions.iter().map(|p| production_height(&self.heights, p)).collect();
+
+        // stay within `depth` if possible; otherwise, take the
+        // shallowest productions, so that we do not go any deeper
+        // than we have to
+        let limit = ::std::cmp::max(depth, self.heights[nonterminal].unwrap());
+        let candidates: Vec<_> =
+            (0..productions.len())
+                .filter(|&i| heights[i].map(|h| h <= limit).unwrap_or(false))
+                .collect();
+        let costs: Vec<u32> =
+            candidates.iter().map(|&i| self.grammar.costs[nonterminal][i]).collect();
+        let production = productions[candidates[choose(&costs)]];
+
+        Derivation {
+            nonterminal: nonterminal,
+            children: production.iter()
+                                .map(|&symbol| match symbol {
+                                    Symbol::Terminal(text) => Child::Terminal(text),
+                                    Symbol::Nonterminal(nt) => {
+                                        Child::Nonterminal(self.derive(nt, limit - 1, choose))
+                                    }
+                                })
+                                .collect(),
+        }
+    }
+
+    /// Smaller variants of `derivation`. These are the derivations of
+    /// the same nonterminal nested within it; those that can be
+    /// wrapped in a single production of that nonterminal, with the
+    /// rest of the production derived minimally; and `derivation`
+    /// with one of its subtrees shrunk in turn.
+    pub fn shrink(&self, derivation: &Derivation) -> Vec<Derivation> {
+        let size = derivation.size();
+        let mut nested = vec![];
+        derivation.push_nested(&mut nested);
+
+        let mut candidates = vec![];
+        for inner in &nested {
+            if inner.nonterminal == derivation.nonterminal {
+                candidates.push(inner.clone());
+            }
+        }
+
+        let productions = self.grammar.nonterminals[derivation.nonterminal].1;
+        for &production in productions {
+            if production_height(&self.heights, production).is_none() {
+                continue;
+            }
+            for (position, &symbol) in production.iter().enumerate() {
+                if let Symbol::Nonterminal(nt) = symbol {
+                    for inner in nested.iter().filter(|inner| inner.nonterminal == nt) {
+                        let candidate = Derivation {
+                            nonterminal: derivation.nonterminal,
+                            children: production.iter()
+                                                .enumerate()
+                                                .map(|(i, &symbol)| if i == position {
+                                                    Child::Nonterminal(inner.clone())
+                                                } else {
+                                                    self.minimal_child(symbol)
+                                                })
+                                                .collect(),
+                        };
+                        if candidate.size() < size {
+                            candidates.push(candidate);
+                        }
+                    }
+                }
+            }
+        }
+
+        let minimal = self.derive(derivation.nonterminal, 0, &mut |_| 0);
+        if minimal.size() < size {
+            candidates.push(minimal);
+        }
+
+        for (index, child) in derivation.children.iter().enumerate() {
+            if let Child::Nonterminal(ref inner) = *child {
+                for smaller in self.shrink(inner) {
+                    let mut candidate = derivation.clone();
+                    candidate.children[index] = Child::Nonterminal(smaller);
+                    candidates.push(candidate);
+                }
+            }
+        }
+
+        candidates
+    }
+
+    fn minimal_child(&self, symbol: Symbol) -> Child {
+        match symbol {
+            Symbol::Terminal(text) => Child::Terminal(text),
+            Symbol::Nonterminal(nt) => Child::Nonterminal(self.derive(nt, 0, &mut |_| 0)),
+        }
+    }
+
+    /// Checks `property` against the text of `cases` random
+    /// derivations. If it fails, the derivation is shrunk as far as
+    /// possible while still failing, and we panic with its text.
+    pub fn check<F>(&self, cases: u64, mut property: F)
+        where F: FnMut(&str) -> bool
+    {
+        for seed in 0..cases {
+            let derivation = self.generate(&mut Rng::new(seed));
+            if property(&derivation.text()) {
+                continue;
+            }
+
+            let mut smallest = derivation.clone();
+            'shrink: loop {
+                for candidate in self.shrink(&smallest) {
+                    if !property(&candidate.text()) {
+                        smallest = candidate;
+                        continue 'shrink;
+                    }
+                }
+                break;
+            }
+
+            panic!("property failed for {:?} (seed {}, shrunk from {:?})",
+                   smallest.text(),
+                   seed,
+                   derivation.text());
+        }
+    }
+}
+
+fn production_height(heights: &[Option<usize>], production: &[Symbol]) -> Option<usize> {
+    let mut height = 1;
+    for &symbol in production {
+        if let Symbol::Nonterminal(nt) = symbol {
+            match heights[nt] {
+                Some(h) => height = ::std::cmp::max(height, h + 1),
+                None => return None,
+            }
+        }
+    }
+    Some(height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Expr = Expr "+" Term | Term;  Term = "x" | "y" | "(" Expr ")";
+    const NONTERMINALS: &'static [(&'static str, &'static [&'static [Symbol]])] = &[
+        ("Expr", &[&[Symbol::Nonterminal(0), Symbol::Terminal("+"), Symbol::Nonterminal(1)],
+                   &[Symbol::Nonterminal(1)]]),
+        ("Term", &[&[Symbol::Terminal("x")],
+                   &[Symbol::Terminal("y")],
+                   &[Symbol::Terminal("("), Symbol::Nonterminal(0), Symbol::Terminal(")")]]),
+    ];
+
+    static GRAMMAR: Grammar = Grammar {
+        nonterminals: NONTERMINALS,
+        costs: &[&[0, 0], &[0, 0, 0]],
+    };
+
+    // the same, but with `#[cost(9)]` on `Term = "(" Expr ")"`
+    static WEIGHTED: Grammar = Grammar {
+        nonterminals: NONTERMINALS,
+        costs: &[&[0, 0], &[0, 0, 9]],
+    };
+
+    #[test]
+    fn minimal() {
+        assert_eq!(Derivations::new(&GRAMMAR, 0).minimal().text(), "x");
+    }
+
+    #[test]
+    fn generate_is_reproducible() {
+        let derivations = Derivations::new(&GRAMMAR, 0);
+        for seed in 0..100 {
+            let text = derivations.generate(&mut Rng::new(seed)).text();
+            assert_eq!(derivations.generate(&mut Rng::new(seed)).text(), text);
+        }
+    }
+
+    #[test]
+    fn check_passes() {
+        Derivations::new(&GRAMMAR, 0).check(100, |input| {
+            input.matches('(').count() == input.matches(')').count()
+        });
+    }
+
+    #[test]
+    fn check_shrinks() {
+        let result = ::std::panic::catch_unwind(|| {
+            Derivations::new(&GRAMMAR, 0).check(100, |input| !input.contains('y'))
+        });
+        let message = result.unwrap_err();
+        let message = message.downcast_ref::<String>().unwrap();
+        assert!(message.starts_with("property failed for \"y\""), "{}", message);
+    }
+
+    #[test]
+    fn generate_prefers_cheap_productions() {
+        let parens = |grammar: &'static Grammar| -> usize {
+            let derivations = Derivations::new(grammar, 0);
+            (0..200)
+                .map(|seed| derivations.generate(&mut Rng::new(seed)).text().matches('(').count())
+                .sum()
+        };
+        assert!(parens(&WEIGHTED) * 2 < parens(&GRAMMAR));
+    }
+}
@@ -1,10 +1,59 @@
+//! The runtime support that the parsers generated by LALRPOP share:
+//! their errors, the spans of the input they refer to, and the
+//! tokenizers they read from. Every parser in a project uses the same
+//! types, so the errors of different grammars can be handled alike.
+
+#[cfg(feature = "proc-macro2")]
+extern crate proc_macro2;
+
 use std::error::Error;
 use std::fmt;
 
+pub mod derivation;
+pub mod location;
+#[cfg(feature = "proc-macro2")]
+pub mod token_stream;
+
+/// The input from `start` to `end`, such as the locations that `@L`
+/// and `@R` give for a nonterminal, or those around a token.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Span<L> {
+    pub start: L,
+    pub end: L,
+}
+
+impl<L> Span<L> {
+    pub fn new(start: L, end: L) -> Span<L> {
+        Span { start: start, end: end }
+    }
+}
+
+impl<L> From<(L, L)> for Span<L> {
+    fn from((start, end): (L, L)) -> Span<L> {
+        Span::new(start, end)
+    }
+}
+
+impl<L: fmt::Display> fmt::Display for Span<L> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.start, self.end)
+    }
+}
+
+/// A tokenizer that a generated parser can read from: an iterator
+/// over tokens `T`, each with the locations `L` where it starts and
+/// ends, or errors `E`. Every such iterator is one, including the
+/// tokenizers that LALRPOP generates; the trait only gives the bound
+/// a name, for code that handles the tokenizers of several grammars.
+pub trait Lexer<L, T, E>: Iterator<Item = Result<(L, T, L), E>> {}
+
+impl<I, L, T, E> Lexer<L, T, E> for I where I: Iterator<Item = Result<(L, T, L), E>> {}
+
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ParseError<L,T,E> {
-    /// Generated by the parser when it encounters a token (or EOF) it did not
-    /// expect.
+    /// Generated by the tokenizer when it encounters input that does not
+    /// match any token; with the built-in lexer, `location` is the offset
+    /// where the bad input starts.
     InvalidToken {
         location: L
     },
@@ -30,50 +79,127 @@ pub enum ParseError<L,T,E> {
         token: (L, T, L),
     },
 
+    /// Generated by a table-driven parser that was built to stop when
+    /// its stacks are full, rather than grow them, so that deeply
+    /// nested input cannot use up all the memory; `location` is the
+    /// start of the token it could not shift.
+    NestingTooDeep {
+        location: L,
+    },
+
     /// Custom error type.
     User {
         error: E,
     },
 }
 
+impl<L: Clone, T, E> ParseError<L, T, E> {
+    /// The span of the input where the error occurred, if known: none
+    /// for an unexpected EOF or a user error.
+    pub fn span(&self) -> Option<Span<L>> {
+        use self::ParseError::*;
+        match *self {
+            InvalidToken { ref location } |
+            NestingTooDeep { ref location } =>
+                Some(Span::new(location.clone(), location.clone())),
+            UnrecognizedToken { token: Some((ref start, _, ref end)), .. } |
+            ExtraToken { token: (ref start, _, ref end) } =>
+                Some(Span::new(start.clone(), end.clone())),
+            UnrecognizedToken { token: None, .. } |
+            User { .. } =>
+                None,
+        }
+    }
+}
+
 impl<L, T, E> fmt::Display for ParseError<L, T, E>
 where L: fmt::Display,
       T: fmt::Display,
       E: fmt::Display
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use self::ParseError::*;
-        match *self {
-            InvalidToken { ref location } =>
-                write!(f, "Invalid token at {}", location),
-            UnrecognizedToken { ref token, ref expected } => {
-                match *token {
-                    Some((ref start, ref token, ref end)) =>
-                        try!(write!(f, "Unrecognized token {} found at {}:{}", token, start, end)),
-                    None =>
-                        try!(write!(f, "Unrecognized EOF")),
-                }
-                if !expected.is_empty() {
-                    try!(writeln!(f, ""));
-                    for (i, e) in expected.iter().enumerate() {
-                        let sep = match i {
-                            0 => "Expected one of",
-                            _ if i < expected.len() - 1 => ",",
-                            // Last expected message to be written
-                            _ => " or",
-                        };
-                        try!(write!(f, "{} {}", sep, e));
-                    }
-                }
-                Ok(())
+        fmt_error(self, f, &|token| token.to_string(), &|error| error.to_string())
+    }
+}
+
+/// A `ParseError` displayed with its tokens described by `describe`,
+/// for token types that do not implement `Display`. Every generated
+/// parser has a `display_error` function that returns one, describing
+/// tokens by their text if the grammar uses the built-in lexer, or by
+/// the terminal they match otherwise. User errors are displayed with
+/// `Debug`, which unlike `Display` the default error type `()` has.
+pub struct ErrorDisplay<'error, L: 'error, T: 'error, E: 'error> {
+    pub error: &'error ParseError<L, T, E>,
+    pub describe: fn(&T) -> String,
+}
+
+impl<'error, L, T, E> fmt::Display for ErrorDisplay<'error, L, T, E>
+where L: fmt::Display,
+      E: fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_error(self.error, f, &self.describe, &|error| format!("{:?}", error))
+    }
+}
+
+/// The most expected tokens that an error lists; the rest are only
+/// counted.
+const MAX_EXPECTED: usize = 10;
+
+fn fmt_error<L, T, E>(error: &ParseError<L, T, E>,
+                      f: &mut fmt::Formatter,
+                      describe: &Fn(&T) -> String,
+                      describe_error: &Fn(&E) -> String)
+                      -> fmt::Result
+where L: fmt::Display
+{
+    use self::ParseError::*;
+    match *error {
+        InvalidToken { ref location } =>
+            write!(f, "Invalid token at {}", location),
+        UnrecognizedToken { ref token, ref expected } => {
+            match *token {
+                Some((ref start, ref token, ref end)) =>
+                    try!(write!(f, "Unrecognized token {} found at {}:{}",
+                                describe(token), start, end)),
+                None =>
+                    try!(write!(f, "Unrecognized EOF")),
             }
-            ExtraToken { token: (ref start, ref token, ref end) } => {
-                write!(f, "Extra token {} found at {}:{}", token, start, end)
+            if !expected.is_empty() {
+                try!(writeln!(f, ""));
+                try!(fmt_expected(f, expected));
             }
-            User { ref error } =>
-                write!(f, "{}", error)
+            Ok(())
+        }
+        ExtraToken { token: (ref start, ref token, ref end) } => {
+            write!(f, "Extra token {} found at {}:{}", describe(token), start, end)
         }
+        NestingTooDeep { ref location } =>
+            write!(f, "Input nested too deeply at {}", location),
+        User { ref error } =>
+            write!(f, "{}", describe_error(error))
+    }
+}
+
+fn fmt_expected(f: &mut fmt::Formatter, expected: &[String]) -> fmt::Result {
+    let (shown, others) = if expected.len() > MAX_EXPECTED {
+        (&expected[..MAX_EXPECTED - 1], expected.len() - (MAX_EXPECTED - 1))
+    } else {
+        (expected, 0)
+    };
+    for (i, e) in shown.iter().enumerate() {
+        let sep = match i {
+            0 => "Expected one of",
+            _ if i < shown.len() - 1 || others > 0 => ",",
+            // Last expected message to be written
+            _ => " or",
+        };
+        try!(write!(f, "{} {}", sep, e));
+    }
+    if others > 0 {
+        try!(write!(f, " or {} others", others));
     }
+    Ok(())
 }
 
 impl<L, T, E> Error for ParseError<L, T, E>
@@ -86,12 +212,45 @@ where L: fmt::Debug + fmt::Display,
     }
 }
 
+/// What could come next after a prefix of the input, as the generated
+/// `complete_Foo` functions find, e.g. for code completion: the
+/// terminals that the parser would accept there, the nonterminals of
+/// the grammar that it could begin there, and whether the input could
+/// also end there.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Completion {
+    pub terminals: Vec<String>,
+    pub nonterminals: Vec<String>,
+    pub can_end: bool,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ErrorRecovery<L, T, E> {
     pub error: ParseError<L, T, E>,
     pub dropped_tokens: Vec<(L, T, L)>,
 }
 
+impl<L: Clone, T, E> ErrorRecovery<L, T, E> {
+    /// The span of the input that the recovery skipped: from the start
+    /// of the token that caused the error to the end of the last
+    /// dropped token, or empty if none were dropped. None if the error
+    /// was an unexpected EOF and nothing was dropped.
+    pub fn span(&self) -> Option<Span<L>> {
+        let start = match self.error.span() {
+            Some(span) => span.start,
+            None => match self.dropped_tokens.first() {
+                Some(&(ref start, _, _)) => start.clone(),
+                None => return None,
+            },
+        };
+        let end = match self.dropped_tokens.last() {
+            Some(&(_, _, ref end)) => end.clone(),
+            None => start.clone(),
+        };
+        Some(Span::new(start, end))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -107,5 +266,63 @@ mod tests {
         };
         assert_eq!(format!("{}", err), "Unrecognized token t0 found at 1:2\n\
                                         Expected one of t1, t2 or t3");
+        assert_eq!(err.span(), Some(Span::new(1, 2)));
+    }
+
+    #[test]
+    fn truncated_expected() {
+        let err = ParseError::UnrecognizedToken::<i32, &str, &str> {
+            token: None,
+            expected: (0..12).map(|i| format!("t{}", i)).collect(),
+        };
+        assert_eq!(format!("{}", err), "Unrecognized EOF\n\
+                                        Expected one of t0, t1, t2, t3, t4, t5, t6, t7, t8 \
+                                        or 3 others");
+    }
+
+    #[test]
+    fn error_display() {
+        let err = ParseError::ExtraToken::<i32, (usize, &str), &str> {
+            token: (1, (0, "t0"), 2),
+        };
+        let display = ErrorDisplay { error: &err, describe: |token| format!("{:?}", token.1) };
+        assert_eq!(format!("{}", display), "Extra token \"t0\" found at 1:2");
+    }
+
+    #[test]
+    fn nesting_too_deep() {
+        let err = ParseError::NestingTooDeep::<i32, &str, &str> { location: 7 };
+        assert_eq!(format!("{}", err), "Input nested too deeply at 7");
+        assert_eq!(err.span(), Some(Span::new(7, 7)));
+    }
+
+    #[test]
+    fn span_of_eof() {
+        let err = ParseError::UnrecognizedToken::<i32, &str, &str> {
+            token: None,
+            expected: vec![],
+        };
+        assert_eq!(err.span(), None);
+    }
+
+    #[test]
+    fn span_of_recovery() {
+        let recovery = ErrorRecovery::<i32, &str, &str> {
+            error: ParseError::UnrecognizedToken {
+                token: Some((3, "b", 4)),
+                expected: vec![],
+            },
+            dropped_tokens: vec![(3, "b", 4), (5, "c", 6)],
+        };
+        assert_eq!(recovery.span(), Some(Span::new(3, 6)));
+
+        let recovery = ErrorRecovery::<i32, &str, &str> {
+            error: ParseError::UnrecognizedToken {
+                token: None,
+                expected: vec![],
+            },
+            dropped_tokens: vec![],
+        };
+        assert_eq!(recovery.span(), None);
     }
 }
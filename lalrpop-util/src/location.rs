@@ -0,0 +1,68 @@
+//! Locations in the input of the tokenizer that LALRPOP generates.
+//!
+//! By default, that tokenizer reports the byte offsets where tokens
+//! start and end, but a grammar can ask for any other type that
+//! implements `Location`, by declaring it in an `extern` block:
+//!
+//! ```ignore
+//! extern {
+//!     type Location = lalrpop_util::location::LineCol;
+//! }
+//! ```
+
+use std::fmt;
+
+/// A location in the input. The tokenizer starts from the default
+/// location and moves along with `advance`.
+pub trait Location: Clone + Default {
+    /// Returns the location just past `text`, which starts at `self`.
+    fn advance(&self, text: &str) -> Self;
+}
+
+impl Location for usize {
+    fn advance(&self, text: &str) -> usize {
+        *self + text.len()
+    }
+}
+
+/// A line and column, both counted from zero. Columns count
+/// characters, not bytes.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LineCol {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location for LineCol {
+    fn advance(&self, text: &str) -> LineCol {
+        let mut location = *self;
+        for c in text.chars() {
+            if c == '\n' {
+                location.line += 1;
+                location.column = 0;
+            } else {
+                location.column += 1;
+            }
+        }
+        location
+    }
+}
+
+impl fmt::Display for LineCol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line + 1, self.column + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_col() {
+        let start = LineCol::default().advance("ab\n  ");
+        assert_eq!(start, LineCol { line: 1, column: 2 });
+        assert_eq!(start.advance("cé"), LineCol { line: 1, column: 4 });
+        assert_eq!(format!("{}", start), "2:3");
+    }
+}
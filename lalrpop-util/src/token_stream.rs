@@ -0,0 +1,239 @@
+//! A tokenizer over a `proc_macro2::TokenStream`, so that a procedural
+//! macro can parse its input with a LALRPOP grammar. This module is
+//! only there with the `proc-macro2` feature.
+//!
+//! `Tokens` turns the stream into Rust tokens -- identifiers, keywords,
+//! punctuation, literals, and the delimiters of groups -- which the
+//! grammar maps to its terminals in an `extern` block:
+//!
+//! ```ignore
+//! use lalrpop_util::token_stream::{Location, Token};
+//! use proc_macro2::{Delimiter, Ident, Literal, Spacing};
+//! use Keyword;
+//!
+//! extern {
+//!     type Location = Location;
+//!
+//!     enum Token<Keyword> {
+//!         "fn" => Token::Keyword(Keyword::Fn),
+//!         Ident => Token::Ident(<Ident>),
+//!         Literal => Token::Literal(<Literal>),
+//!         "(" => Token::Open(Delimiter::Parenthesis),
+//!         ")" => Token::Close(Delimiter::Parenthesis),
+//!         "," => Token::Punct(',', _),
+//!         "+" => Token::Punct('+', Spacing::Alone),
+//!         JointPlus => Token::Punct('+', Spacing::Joint),
+//!         "=" => Token::Punct('=', _),
+//!     }
+//! }
+//! ```
+//!
+//! where `Keyword` is an enum of the grammar's keywords, which are
+//! given to `Tokens::keyword` and displayed as they are written. A
+//! punctuation character is `Joint` when the next token is one that it
+//! makes up an operator with, like the `+` of `+=`, which the grammar
+//! above sees as `JointPlus "="`.
+
+use proc_macro2::{token_stream, Delimiter, Ident, Literal, Spacing, Span, TokenStream};
+use proc_macro2::TokenTree;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// A Rust token, with keywords of type `K`.
+#[derive(Clone, Debug)]
+pub enum Token<K> {
+    /// An identifier that is not one of the keywords.
+    Ident(Ident),
+    Keyword(K),
+    Punct(char, Spacing),
+    Literal(Literal),
+
+    /// The start of a group, like `(` or `{`.
+    Open(Delimiter),
+
+    /// The end of a group, like `)` or `}`.
+    Close(Delimiter),
+}
+
+impl<K: fmt::Display> fmt::Display for Token<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Token::Ident(ref ident) => write!(f, "{}", ident),
+            Token::Keyword(ref keyword) => write!(f, "{}", keyword),
+            Token::Punct(c, _) => write!(f, "{}", c),
+            Token::Literal(ref literal) => write!(f, "{}", literal),
+            Token::Open(delimiter) => write!(f, "{}", delimiters(delimiter).0),
+            Token::Close(delimiter) => write!(f, "{}", delimiters(delimiter).1),
+        }
+    }
+}
+
+fn delimiters(delimiter: Delimiter) -> (&'static str, &'static str) {
+    match delimiter {
+        Delimiter::Parenthesis => ("(", ")"),
+        Delimiter::Brace => ("{", "}"),
+        Delimiter::Bracket => ("[", "]"),
+        Delimiter::None => ("", ""),
+    }
+}
+
+/// Where a token starts or ends: the number of tokens before that
+/// point, and the span of the token, for errors that the macro
+/// reports with `compile_error!`.
+#[derive(Copy, Clone, Debug)]
+pub struct Location {
+    pub index: usize,
+    pub span: Span,
+}
+
+impl Default for Location {
+    fn default() -> Location {
+        Location {
+            index: 0,
+            span: Span::call_site(),
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.index)
+    }
+}
+
+/// The tokens of a `TokenStream`, with keywords of type `K`, for a
+/// grammar whose errors are of type `E`; `Tokens` itself never fails.
+/// Groups without delimiters, as `macro_rules!` makes of the
+/// fragments that it substitutes, are not seen: their tokens come as
+/// if they were not grouped.
+pub struct Tokens<K, E> {
+    stack: Vec<(token_stream::IntoIter, Option<(Delimiter, Span)>)>,
+    keywords: Vec<(&'static str, K)>,
+    index: usize,
+    phantom: PhantomData<E>,
+}
+
+impl<K: Clone, E> Tokens<K, E> {
+    pub fn new(stream: TokenStream) -> Tokens<K, E> {
+        Tokens {
+            stack: vec![(stream.into_iter(), None)],
+            keywords: vec![],
+            index: 0,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Makes the identifier `text` the keyword `keyword`.
+    pub fn keyword(mut self, text: &'static str, keyword: K) -> Tokens<K, E> {
+        self.keywords.push((text, keyword));
+        self
+    }
+
+    fn token(&mut self,
+             token: Token<K>,
+             span: Span)
+             -> Option<Result<(Location, Token<K>, Location), E>> {
+        let start = Location { index: self.index, span: span };
+        self.index += 1;
+        let end = Location { index: self.index, span: span };
+        Some(Ok((start, token, end)))
+    }
+}
+
+impl<K: Clone, E> Iterator for Tokens<K, E> {
+    type Item = Result<(Location, Token<K>, Location), E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let tree = match self.stack.last_mut() {
+                Some(&mut (ref mut trees, _)) => trees.next(),
+                None => return None,
+            };
+            match tree {
+                None => {
+                    if let Some((_, Some((delimiter, span)))) = self.stack.pop() {
+                        return self.token(Token::Close(delimiter), span);
+                    }
+                }
+                Some(TokenTree::Group(group)) => {
+                    let delimiter = group.delimiter();
+                    if delimiter == Delimiter::None {
+                        self.stack.push((group.stream().into_iter(), None));
+                        continue;
+                    }
+                    self.stack.push((group.stream().into_iter(),
+                                     Some((delimiter, group.span_close()))));
+                    return self.token(Token::Open(delimiter), group.span_open());
+                }
+                Some(TokenTree::Ident(ident)) => {
+                    let span = ident.span();
+                    let token = match self.keywords.iter().find(|&&(text, _)| ident == text) {
+                        Some(&(_, ref keyword)) => Token::Keyword(keyword.clone()),
+                        None => Token::Ident(ident),
+                    };
+                    return self.token(token, span);
+                }
+                Some(TokenTree::Punct(punct)) => {
+                    return self.token(Token::Punct(punct.as_char(), punct.spacing()),
+                                      punct.span());
+                }
+                Some(TokenTree::Literal(literal)) => {
+                    let span = literal.span();
+                    return self.token(Token::Literal(literal), span);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum Keyword {
+        Fn,
+    }
+
+    impl fmt::Display for Keyword {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "fn")
+        }
+    }
+
+    #[test]
+    fn tokens() {
+        let stream: TokenStream = "fn f(a) { a += 1 }".parse().unwrap();
+        let tokens: Vec<_> = Tokens::<Keyword, ()>::new(stream)
+            .keyword("fn", Keyword::Fn)
+            .map(|t| t.unwrap())
+            .collect();
+
+        let texts: Vec<String> = tokens.iter().map(|t| t.1.to_string()).collect();
+        assert_eq!(texts, vec!["fn", "f", "(", "a", ")", "{", "a", "+", "=", "1", "}"]);
+        match tokens[0].1 {
+            Token::Keyword(Keyword::Fn) => {}
+            ref token => panic!("expected `fn`, found {:?}", token),
+        }
+        match tokens[7].1 {
+            Token::Punct('+', Spacing::Joint) => {}
+            ref token => panic!("expected a joint `+`, found {:?}", token),
+        }
+        match tokens[10].1 {
+            Token::Close(Delimiter::Brace) => {}
+            ref token => panic!("expected `}}`, found {:?}", token),
+        }
+        assert_eq!((tokens[3].0.index, tokens[3].2.index), (3, 4));
+    }
+
+    #[test]
+    fn invisible_groups() {
+        let inner: TokenStream = "a b".parse().unwrap();
+        let group = TokenTree::Group(::proc_macro2::Group::new(Delimiter::None, inner));
+        let stream: TokenStream = vec![group].into_iter().collect();
+        let tokens: Vec<String> = Tokens::<Keyword, ()>::new(stream)
+            .map(|t| t.unwrap().1.to_string())
+            .collect();
+        assert_eq!(tokens, vec!["a", "b"]);
+    }
+}
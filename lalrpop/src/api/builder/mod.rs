@@ -0,0 +1,376 @@
+//! Builders for grammars made in Rust code rather than written in a
+//! `.lalrpop` file, for tools that generate grammars from something
+//! else (a protocol schema, say).
+//!
+//! The builders construct the same parse tree that the parser would
+//! produce for the grammar, so they go through the same normalization
+//! and code generation. They also render the grammar as text, which
+//! is what error messages point into.
+
+use file_text::FileText;
+use grammar::parse_tree as pt;
+use intern::intern;
+use parser;
+use std::path::PathBuf;
+
+#[cfg(test)]
+mod test;
+
+/// A grammar that uses the built-in lexer: a list of nonterminals,
+/// along with `use` declarations and annotations like
+/// `table_driven`. To generate a parser from it, hand it to
+/// `Configuration::process_grammar`.
+///
+/// ```ignore
+/// let grammar = GrammarBuilder::new()
+///     .nonterminal(NonterminalBuilder::new("Sum")
+///         .public()
+///         .type_decl("i32")
+///         .alternative(vec![SymbolBuilder::nonterminal("Sum").named("l"),
+///                           SymbolBuilder::literal("+"),
+///                           SymbolBuilder::nonterminal("Num").named("r")],
+///                      Some("l + r"))
+///         .alternative(vec![SymbolBuilder::nonterminal("Num")], None))
+///     .nonterminal(NonterminalBuilder::new("Num")
+///         .type_decl("i32")
+///         .alternative(vec![SymbolBuilder::regex("[0-9]+").named("n")],
+///                      Some("n.parse().unwrap()")));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct GrammarBuilder {
+    uses: Vec<String>,
+    annotations: Vec<String>,
+    nonterminals: Vec<NonterminalBuilder>,
+}
+
+/// A nonterminal of a `GrammarBuilder`.
+#[derive(Clone, Debug)]
+pub struct NonterminalBuilder {
+    public: bool,
+    annotations: Vec<String>,
+    name: String,
+    type_decl: Option<String>,
+    alternatives: Vec<(Vec<SymbolBuilder>, Option<String>)>,
+}
+
+/// A symbol in an alternative of a `NonterminalBuilder`.
+#[derive(Clone, Debug)]
+pub struct SymbolBuilder {
+    kind: SymbolBuilderKind,
+}
+
+#[derive(Clone, Debug)]
+enum SymbolBuilderKind {
+    Literal(String),
+    Regex(String),
+    Nonterminal(String),
+    Sequence(Vec<SymbolBuilder>),
+    Repeat(pt::RepeatOp, Box<SymbolBuilder>),
+    Choose(Box<SymbolBuilder>),
+    Name(String, Box<SymbolBuilder>),
+}
+
+impl GrammarBuilder {
+    pub fn new() -> GrammarBuilder {
+        GrammarBuilder::default()
+    }
+
+    /// Adds `use path;` to the grammar, for the action code.
+    pub fn use_path(mut self, path: &str) -> GrammarBuilder {
+        self.uses.push(path.to_string());
+        self
+    }
+
+    /// Adds `#[name]` to the grammar, e.g. `table_driven`.
+    pub fn annotation(mut self, name: &str) -> GrammarBuilder {
+        self.annotations.push(name.to_string());
+        self
+    }
+
+    pub fn nonterminal(mut self, nonterminal: NonterminalBuilder) -> GrammarBuilder {
+        self.nonterminals.push(nonterminal);
+        self
+    }
+
+    fn render(&self, text: &mut String) -> Result<pt::Grammar, (pt::Span, String)> {
+        let mut items = vec![];
+        for path in &self.uses {
+            text.push_str(&format!("use {};\n", path));
+            items.push(pt::GrammarItem::Use(path.clone()));
+        }
+
+        let annotations = render_annotations(&self.annotations, text);
+
+        let lo = text.len();
+        text.push_str("grammar");
+        let span = pt::Span(lo, text.len());
+        text.push_str(";\n");
+
+        for nonterminal in &self.nonterminals {
+            text.push_str("\n");
+            items.push(pt::GrammarItem::Nonterminal(try!(nonterminal.render(text))));
+        }
+
+        // as `parser::parse_grammar` does, find a prefix that does not
+        // appear anywhere in the grammar
+        let mut prefix = format!("__");
+        while text.contains(&prefix) {
+            prefix.push('_');
+        }
+
+        Ok(pt::Grammar {
+            prefix: prefix,
+            span: span,
+            type_parameters: vec![],
+            parameters: vec![],
+            where_clauses: vec![],
+            items: items,
+            annotations: annotations,
+        })
+    }
+}
+
+/// Renders `grammar` as the text of a `.lalrpop` file at `path`, and
+/// constructs the parse tree that parsing that text would give. Fails
+/// with the span of the first type that does not parse, and a message
+/// about it.
+pub fn to_parse_tree(grammar: &GrammarBuilder, path: PathBuf)
+                     -> (FileText, Result<pt::Grammar, (pt::Span, String)>) {
+    let mut text = String::new();
+    let parse_tree = grammar.render(&mut text);
+    (FileText::new(path, text), parse_tree)
+}
+
+impl NonterminalBuilder {
+    pub fn new(name: &str) -> NonterminalBuilder {
+        NonterminalBuilder {
+            public: false,
+            annotations: vec![],
+            name: name.to_string(),
+            type_decl: None,
+            alternatives: vec![],
+        }
+    }
+
+    /// Makes the nonterminal public, so that the parser gets a
+    /// `parse_Name` function for it.
+    pub fn public(mut self) -> NonterminalBuilder {
+        self.public = true;
+        self
+    }
+
+    /// Adds `#[name]` to the nonterminal, e.g. `inline`.
+    pub fn annotation(mut self, name: &str) -> NonterminalBuilder {
+        self.annotations.push(name.to_string());
+        self
+    }
+
+    /// Declares the type of the nonterminal, as it would be written
+    /// in a `.lalrpop` file (e.g., `Vec<i32>`).
+    pub fn type_decl(mut self, type_decl: &str) -> NonterminalBuilder {
+        self.type_decl = Some(type_decl.to_string());
+        self
+    }
+
+    /// Adds an alternative that matches `symbols`, with the action
+    /// code `action`, or the default action if there is none. An
+    /// alternative that matches nothing needs action code.
+    pub fn alternative(mut self, symbols: Vec<SymbolBuilder>, action: Option<&str>)
+                       -> NonterminalBuilder {
+        self.alternatives.push((symbols, action.map(|a| a.to_string())));
+        self
+    }
+
+    fn render(&self, text: &mut String) -> Result<pt::NonterminalData, (pt::Span, String)> {
+        let annotations = render_annotations(&self.annotations, text);
+        if self.public {
+            text.push_str("pub ");
+        }
+
+        let lo = text.len();
+        text.push_str(&self.name);
+        let span = pt::Span(lo, text.len());
+
+        let type_decl = match self.type_decl {
+            Some(ref type_decl) => {
+                text.push_str(": ");
+                let lo = text.len();
+                text.push_str(type_decl);
+                match parser::parse_type_ref_at(type_decl, lo) {
+                    Ok(type_ref) => Some(type_ref),
+                    Err(_) => {
+                        return Err((pt::Span(lo, text.len()),
+                                    format!("invalid type `{}`", type_decl)));
+                    }
+                }
+            }
+            None => None,
+        };
+
+        text.push_str(" = {\n");
+        let alternatives = self.alternatives
+                               .iter()
+                               .map(|&(ref symbols, ref action)| {
+                                   text.push_str("    ");
+                                   let alternative = render_alternative(symbols, action, text);
+                                   text.push_str(",\n");
+                                   alternative
+                               })
+                               .collect();
+        text.push_str("};\n");
+
+        Ok(pt::NonterminalData {
+            public: self.public,
+            name: pt::NonterminalString(intern(&self.name)),
+            annotations: annotations,
+            span: span,
+            args: vec![],
+            type_decl: type_decl,
+            alternatives: alternatives,
+        })
+    }
+}
+
+impl SymbolBuilder {
+    fn new(kind: SymbolBuilderKind) -> SymbolBuilder {
+        SymbolBuilder { kind: kind }
+    }
+
+    /// A terminal written `"text"`: `text` is what would go between
+    /// the quotes.
+    pub fn literal(text: &str) -> SymbolBuilder {
+        SymbolBuilder::new(SymbolBuilderKind::Literal(text.to_string()))
+    }
+
+    /// A terminal written `r"regex"`.
+    pub fn regex(regex: &str) -> SymbolBuilder {
+        SymbolBuilder::new(SymbolBuilderKind::Regex(regex.to_string()))
+    }
+
+    pub fn nonterminal(name: &str) -> SymbolBuilder {
+        SymbolBuilder::new(SymbolBuilderKind::Nonterminal(name.to_string()))
+    }
+
+    /// `(X Y Z)`, for `symbols` `X`, `Y` and `Z`.
+    pub fn sequence(symbols: Vec<SymbolBuilder>) -> SymbolBuilder {
+        SymbolBuilder::new(SymbolBuilderKind::Sequence(symbols))
+    }
+
+    /// `X?`, for `self` `X`.
+    pub fn optional(self) -> SymbolBuilder {
+        SymbolBuilder::new(SymbolBuilderKind::Repeat(pt::RepeatOp::Question, Box::new(self)))
+    }
+
+    /// `X*`, for `self` `X`.
+    pub fn zero_or_more(self) -> SymbolBuilder {
+        SymbolBuilder::new(SymbolBuilderKind::Repeat(pt::RepeatOp::Star, Box::new(self)))
+    }
+
+    /// `X+`, for `self` `X`.
+    pub fn one_or_more(self) -> SymbolBuilder {
+        SymbolBuilder::new(SymbolBuilderKind::Repeat(pt::RepeatOp::Plus, Box::new(self)))
+    }
+
+    /// `<X>`, for `self` `X`: the value of the alternative, if it has
+    /// no action code.
+    pub fn chosen(self) -> SymbolBuilder {
+        SymbolBuilder::new(SymbolBuilderKind::Choose(Box::new(self)))
+    }
+
+    /// `<name:X>`, for `self` `X`: its value is called `name` in the
+    /// action code.
+    pub fn named(self, name: &str) -> SymbolBuilder {
+        SymbolBuilder::new(SymbolBuilderKind::Name(name.to_string(), Box::new(self)))
+    }
+
+    fn render(&self, text: &mut String) -> pt::Symbol {
+        let lo = text.len();
+        let kind = match self.kind {
+            SymbolBuilderKind::Literal(ref literal) => {
+                text.push_str(&format!("\"{}\"", literal));
+                pt::SymbolKind::Terminal(pt::TerminalString::quoted(intern(literal)))
+            }
+            SymbolBuilderKind::Regex(ref regex) => {
+                if regex.contains('"') {
+                    text.push_str(&format!("r#\"{}\"#", regex));
+                } else {
+                    text.push_str(&format!("r\"{}\"", regex));
+                }
+                pt::SymbolKind::Terminal(pt::TerminalString::regex(intern(regex)))
+            }
+            SymbolBuilderKind::Nonterminal(ref name) => {
+                text.push_str(name);
+                pt::SymbolKind::AmbiguousId(intern(name))
+            }
+            SymbolBuilderKind::Sequence(ref symbols) => {
+                text.push_str("(");
+                let symbols = render_symbols(symbols, text);
+                text.push_str(")");
+                pt::SymbolKind::Expr(pt::ExprSymbol { symbols: symbols })
+            }
+            SymbolBuilderKind::Repeat(op, ref symbol) => {
+                let symbol = symbol.render(text);
+                text.push_str(&format!("{}", op));
+                pt::SymbolKind::Repeat(Box::new(pt::RepeatSymbol { op: op, symbol: symbol }))
+            }
+            SymbolBuilderKind::Choose(ref symbol) => {
+                text.push_str("<");
+                let symbol = symbol.render(text);
+                text.push_str(">");
+                pt::SymbolKind::Choose(Box::new(symbol))
+            }
+            SymbolBuilderKind::Name(ref name, ref symbol) => {
+                text.push_str(&format!("<{}:", name));
+                let symbol = symbol.render(text);
+                text.push_str(">");
+                pt::SymbolKind::Name(intern(name), Box::new(symbol))
+            }
+        };
+        pt::Symbol::new(pt::Span(lo, text.len()), kind)
+    }
+}
+
+fn render_annotations(names: &[String], text: &mut String) -> Vec<pt::Annotation> {
+    names.iter()
+         .map(|name| {
+             text.push_str("#[");
+             let lo = text.len();
+             text.push_str(name);
+             let id_span = pt::Span(lo, text.len());
+             text.push_str("]\n");
+             pt::Annotation { id_span: id_span, id: intern(name), arg: None }
+         })
+         .collect()
+}
+
+fn render_alternative(symbols: &[SymbolBuilder], action: &Option<String>, text: &mut String)
+                      -> pt::Alternative {
+    let lo = text.len();
+    let symbols = render_symbols(symbols, text);
+    let action = action.as_ref().map(|action| {
+        if !symbols.is_empty() {
+            text.push_str(" ");
+        }
+        text.push_str(&format!("=> {}", action));
+        pt::ActionKind::User(action.trim().to_string())
+    });
+    pt::Alternative {
+        span: pt::Span(lo, text.len()),
+        annotations: vec![],
+        expr: pt::ExprSymbol { symbols: symbols },
+        condition: None,
+        action: action,
+    }
+}
+
+fn render_symbols(symbols: &[SymbolBuilder], text: &mut String) -> Vec<pt::Symbol> {
+    symbols.iter()
+           .enumerate()
+           .map(|(index, symbol)| {
+               if index > 0 {
+                   text.push_str(" ");
+               }
+               symbol.render(text)
+           })
+           .collect()
+}
@@ -0,0 +1,47 @@
+use parser;
+use std::path::PathBuf;
+
+use super::{to_parse_tree, GrammarBuilder, NonterminalBuilder, SymbolBuilder};
+
+fn sum_grammar() -> GrammarBuilder {
+    GrammarBuilder::new()
+        .use_path("std::str::FromStr")
+        .annotation("table_driven")
+        .nonterminal(NonterminalBuilder::new("Sum")
+            .public()
+            .type_decl("Vec<i32>")
+            .alternative(vec![SymbolBuilder::nonterminal("Num").named("n"),
+                              SymbolBuilder::sequence(vec![SymbolBuilder::literal("+"),
+                                                           SymbolBuilder::nonterminal("Num")])
+                                  .zero_or_more()
+                                  .named("rest")],
+                         Some("{ let mut v = rest; v.insert(0, n); v }"))
+            .alternative(vec![], Some("vec![]")))
+        .nonterminal(NonterminalBuilder::new("Num")
+            .annotation("inline")
+            .type_decl("i32")
+            .alternative(vec![SymbolBuilder::regex("[0-9]+").named("n")],
+                         Some("i32::from_str(n).unwrap()"))
+            .alternative(vec![SymbolBuilder::literal("(").optional(),
+                              SymbolBuilder::regex("\"[0-9]+\"").one_or_more().chosen()],
+                         None))
+}
+
+#[test]
+fn same_as_parsed_text() {
+    let (file_text, grammar) = to_parse_tree(&sum_grammar(), PathBuf::from("sum.lalrpop"));
+    let expected = parser::parse_grammar(file_text.text()).unwrap();
+    assert_eq!(grammar.unwrap(), expected);
+}
+
+#[test]
+fn invalid_type() {
+    let grammar = GrammarBuilder::new()
+        .nonterminal(NonterminalBuilder::new("Num")
+            .type_decl("Vec<")
+            .alternative(vec![SymbolBuilder::regex("[0-9]+")], None));
+    let (file_text, grammar) = to_parse_tree(&grammar, PathBuf::from("num.lalrpop"));
+    let (span, message) = grammar.unwrap_err();
+    assert_eq!(&file_text.text()[span.0..span.1], "Vec<");
+    assert_eq!(message, "invalid type `Vec<`");
+}
@@ -1,6 +1,11 @@
+use backend::Backend;
 use build;
+use diagnostics::{Diagnostic, Diagnostics};
+use grammar::repr::LrConstruction;
+use ir;
 use log::Level;
-use session::{ColorConfig, Session};
+use pass::Pass;
+use session::{ColorConfig, ErrorFormat, Optimize, Session, StackOverflow, Visibility};
 use std::default::Default;
 use std::env;
 use std::env::current_dir;
@@ -8,6 +13,13 @@ use std::error::Error;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
+pub mod builder;
+
+#[cfg(test)]
+mod test;
+
+pub use self::builder::{GrammarBuilder, NonterminalBuilder, SymbolBuilder};
+
 /// Configure various aspects of how LALRPOP works.
 /// Intended for use within a `build.rs` script.
 /// To get the default configuration, use `Configuration::new`.
@@ -41,6 +53,14 @@ impl Configuration {
         self
     }
 
+    /// Report errors in the `error[E0001]: ... --> file:line:col`
+    /// format used by `rustc`, so that editors and other tools that
+    /// understand `rustc`'s output can pick them up.
+    pub fn use_rustc_error_format(&mut self) -> &mut Configuration {
+        self.session.error_format = ErrorFormat::Rustc;
+        self
+    }
+
     /// Specify a custom directory to search for input files.  This
     /// directory is recursively searched for `.lalrpop` files to be
     /// considered as input files.  This configuration setting also
@@ -76,6 +96,18 @@ impl Configuration {
         self
     }
 
+    /// If true, print a `cargo:rerun-if-changed` line for each
+    /// `.lalrpop` file processed, so that cargo only runs the build
+    /// script again when one of them (or the script itself) changes.
+    /// Note that cargo then does not notice new `.lalrpop` files, and
+    /// stops running the rest of the build script when other inputs
+    /// change, unless the script prints directives of its own for
+    /// them. Default is false.
+    pub fn emit_rerun_directives(&mut self, val: bool) -> &mut Configuration {
+        self.session.emit_rerun_directives = val;
+        self
+    }
+
     /// If true, emit comments into the generated code. This makes the
     /// generated code significantly larger. Default is false.
     pub fn emit_comments(&mut self, val: bool) -> &mut Configuration {
@@ -83,12 +115,323 @@ impl Configuration {
         self
     }
 
-    /// If true, emit report file about generated code.
+    /// If true, let the table-driven parsers be watched as they parse:
+    /// the generated `observe_Foo` function installs a callback that
+    /// sees the stack of states, the items of the top state, and the
+    /// reductions pending before the lookahead is shifted, at each
+    /// step. This makes the generated code larger. Default is false.
+    pub fn emit_introspection(&mut self, val: bool) -> &mut Configuration {
+        self.session.emit_introspection = val;
+        self
+    }
+
+    /// If true, let an observer follow the table-driven parsers as
+    /// they parse: it implements the generated `Observer_Foo` trait,
+    /// whose methods are called on each shift, reduction and error,
+    /// and is installed with `set_observer_Foo`. This is meant for
+    /// profilers, tracers and visualizers. Default is false.
+    pub fn emit_observer(&mut self, val: bool) -> &mut Configuration {
+        self.session.emit_observer = val;
+        self
+    }
+
+    /// Make room for `capacity` entries in the stacks of the
+    /// table-driven parsers when they start, rather than growing them
+    /// from empty. After `error_on_stack_overflow()` or
+    /// `abort_on_stack_overflow()`, this is also as deep as they may
+    /// get (10000 entries by default).
+    pub fn stack_capacity(&mut self, capacity: usize) -> &mut Configuration {
+        self.session.stack_capacity = Some(capacity);
+        self
+    }
+
+    /// Have the table-driven parsers fail with
+    /// `ParseError::NestingTooDeep` when their stacks are full, so
+    /// that deeply nested input cannot use up all the memory. By
+    /// default, they grow the stacks.
+    pub fn error_on_stack_overflow(&mut self) -> &mut Configuration {
+        self.session.stack_overflow = StackOverflow::Error;
+        self
+    }
+
+    /// Have the table-driven parsers abort the process when their
+    /// stacks are full.
+    pub fn abort_on_stack_overflow(&mut self) -> &mut Configuration {
+        self.session.stack_overflow = StackOverflow::Abort;
+        self
+    }
+
+    /// Split the reductions of table-driven parsers for grammars with
+    /// more than `count` productions among several functions, each of
+    /// which handles at most `count` of them, because rustc takes a
+    /// long time to compile a single huge `match`. Default is 500.
+    pub fn reductions_per_fn(&mut self, count: usize) -> &mut Configuration {
+        assert!(count > 0);
+        self.session.reductions_per_fn = count;
+        self
+    }
+
+    /// Give the table-driven parser for each public nonterminal only
+    /// the goto columns and reductions of the nonterminals that it can
+    /// reach, so that a grammar with both a small `pub Expr` and a big
+    /// `pub Program` does not generate the whole grammar's tables
+    /// twice. `lalrpop metrics` shows how much each would keep.
+    pub fn prune_tables(&mut self, val: bool) -> &mut Configuration {
+        self.session.prune_tables = val;
+        self
+    }
+
+    /// Give up on a grammar whose parser for some public nonterminal
+    /// needs more than `count` LR states, reporting the nonterminals
+    /// that take part in the most of them, rather than spending a long
+    /// time on an automaton too big to compile. There is no limit by
+    /// default.
+    pub fn max_states(&mut self, count: usize) -> &mut Configuration {
+        self.session.max_states = Some(count);
+        self
+    }
+
+    /// Give up on a grammar whose parser for some public nonterminal
+    /// would have tables of more than `count` entries: a row for each
+    /// state, with a column for each terminal and each nonterminal.
+    /// There is no limit by default.
+    pub fn max_table_entries(&mut self, count: usize) -> &mut Configuration {
+        self.session.max_table_entries = Some(count);
+        self
+    }
+
+    /// If true, write the action and goto tables of each table-driven
+    /// parser to a binary file next to the generated `.rs` file, named
+    /// after it and the parser (`foo.__parse__Expr.tables`), and embed
+    /// it with `include_bytes!`; the tables are decoded the first time
+    /// the parser runs. For huge grammars, this leaves rustc much less
+    /// source to get through. Default is false.
+    pub fn binary_tables(&mut self, val: bool) -> &mut Configuration {
+        self.session.binary_tables = val;
+        self
+    }
+
+    /// If true, write the action and goto tables of each table-driven
+    /// parser in full, with an entry for each state and each terminal
+    /// or nonterminal, which is easier to read when debugging the
+    /// generated code. By default, the entry that fills most of a
+    /// state's row is only stored once, and the others are packed
+    /// together with those of other states, which shrinks the tables
+    /// of real grammars many times over at the cost of a few steps per
+    /// lookup. The tables are always written in full with
+    /// `binary_tables` and `optimize_for_speed`. Default is false.
+    pub fn dense_tables(&mut self, val: bool) -> &mut Configuration {
+        self.session.dense_tables = val;
+        self
+    }
+
+    /// Make the generated code as small as it can be, at some cost in
+    /// speed: the parsers are table-driven, the tables are pruned (see
+    /// `prune_tables`) and hold the narrowest integers that fit, and
+    /// the functions that pop the symbols of a reduction are never
+    /// inlined into the many places that call them.
+    pub fn optimize_for_size(&mut self) -> &mut Configuration {
+        self.session.optimize = Optimize::Size;
+        self.session.prune_tables = true;
+        self
+    }
+
+    /// Make the parsers as fast as they can be, at the cost of more
+    /// code, which also takes longer to compile: a grammar that does
+    /// not choose how its parsers are generated gets recursive ascent,
+    /// which dispatches on the state with `match`es instead of reading
+    /// tables, unless it uses something only table-driven parsers can
+    /// do, like `#[lookahead2]`, `#[items]`, `#[prefix]` or error
+    /// recovery. Recursive ascent parsers have no `expected_tokens_Foo`
+    /// or `complete_Foo`. The table-driven parsers that remain have
+    /// uncompressed tables of `i32`s, which are read without being
+    /// widened, and have their reductions inlined.
+    pub fn optimize_for_speed(&mut self) -> &mut Configuration {
+        self.session.optimize = Optimize::Speed;
+        self
+    }
+
+    /// Build the states of grammars that do not choose a construction
+    /// with an annotation as LALR(1) does, as `#[LALR]` would: states
+    /// with the same items are merged, which keeps the tables of
+    /// medium-sized grammars small, but can bring in reduce/reduce
+    /// conflicts that canonical LR(1) does not have. These are
+    /// reported like any other conflict.
+    pub fn use_lalr(&mut self) -> &mut Configuration {
+        self.session.construction = Some(LrConstruction::Lalr);
+        self
+    }
+
+    /// Like `use_lalr`, but for IELR(1), as `#[IELR]` would: states are
+    /// only merged where that brings in no conflict.
+    pub fn use_ielr(&mut self) -> &mut Configuration {
+        self.session.construction = Some(LrConstruction::Ielr);
+        self
+    }
+
+    /// Like `use_lalr`, but for canonical LR(1) with states merged by
+    /// lane tables, as `#[lane_table]` would.
+    pub fn use_lane_table(&mut self) -> &mut Configuration {
+        self.session.construction = Some(LrConstruction::LaneTable);
+        self
+    }
+
+    /// Like `use_lalr`, but for SLR(1), as `#[SLR]` would.
+    pub fn use_slr(&mut self) -> &mut Configuration {
+        self.session.construction = Some(LrConstruction::Slr);
+        self
+    }
+
+    /// Substitute `value` for each `${name}` in the grammars, outside
+    /// of string literals and comments (write `$${` for a literal
+    /// `${`), e.g. to give a grammar the keywords of a language that
+    /// are only known when the crate is built. Once any variable is
+    /// set, using one that was not given a value is an error; grammars
+    /// are left alone if none are set.
+    pub fn set_variable<N, V>(&mut self, name: N, value: V) -> &mut Configuration
+        where N: Into<String>, V: Into<String>
+    {
+        self.session.variables.insert(name.into(), value.into());
+        self
+    }
+
+    /// Use `text` for the diagnostics with the code `code` (such as
+    /// `E0012`) instead of the default English text, e.g. to
+    /// translate them. `{0}`, `{1}` and so on in `text` stand for the
+    /// details of each diagnostic, such as the offending token; see
+    /// `CATALOG` for the codes and their default texts.
+    pub fn set_message<C, T>(&mut self, code: C, text: T) -> &mut Configuration
+        where C: Into<String>, T: Into<String>
+    {
+        self.session.messages.insert(code.into(), text.into());
+        self
+    }
+
+    /// Declare the entry points of the generated parsers (`parse_Foo`
+    /// and so forth) and `display_error` as `pub(crate)` rather than
+    /// `pub`, so that they do not become part of the crate's API.
+    pub fn pub_crate_entry_points(&mut self) -> &mut Configuration {
+        self.session.visibility = Visibility::PubCrate;
+        self
+    }
+
+    /// Write the scaffolding that the generated parsers need but that
+    /// does not depend on their grammar (the `ToTriple` trait through
+    /// which they accept tokens) once, into the file for the module
+    /// `module_path` in the output directory (see `process_file_as`),
+    /// and have every parser use it from `::module_path` rather than
+    /// carry a copy of its own. The crate must declare this module.
+    pub fn use_support_module(&mut self, module_path: &str) -> &mut Configuration {
+        self.session.support_module = Some(module_path.to_string());
+        self
+    }
+
+    /// Declare the entry points of the generated parsers without any
+    /// visibility qualifier, for parsers that are `include!`d into an
+    /// existing module rather than declared as modules of their own.
+    pub fn private_entry_points(&mut self) -> &mut Configuration {
+        self.session.visibility = Visibility::Private;
+        self
+    }
+
+    /// If true, emit report file about generated code: the items,
+    /// reductions, shifts and gotos of each state, followed by the
+    /// conflicts, if any, with the same examples as their errors.
     pub fn emit_report(&mut self, val: bool) -> &mut Configuration {
         self.session.emit_report = val;
         self
     }
 
+    /// If true, write the LR automaton of each public nonterminal as a
+    /// Graphviz `.dot` file next to the generated parser, named after
+    /// the grammar file and the nonterminal, e.g. `calc-Expr.dot`. The
+    /// states list their items and reductions, shifts and gotos are
+    /// the edges, and states with conflicts are drawn in red.
+    pub fn emit_dot(&mut self, val: bool) -> &mut Configuration {
+        self.session.emit_dot = val;
+        self
+    }
+
+    /// If true, the snapshots written by `write_snapshots` also record
+    /// the whitespace that the lexer skips, as trivia on the terminals
+    /// next to it: what follows a terminal up to the end of its line
+    /// trails it, and the rest leads the next terminal. The input can
+    /// then be rebuilt from the snapshot, as a formatter would need.
+    pub fn snapshot_trivia(&mut self, val: bool) -> &mut Configuration {
+        self.session.snapshot_trivia = val;
+        self
+    }
+
+    /// Write an SVG picture of each example in the conflict reports
+    /// into `dir`, for embedding in documentation or bug reports. The
+    /// pictures of a conflict are named after the grammar file, the
+    /// public nonterminal and the number of the conflict, e.g.
+    /// `calc-Expr-conflict1a.svg` and `calc-Expr-conflict1b.svg`.
+    pub fn write_conflict_svgs<P>(&mut self, dir: P) -> &mut Configuration
+        where P: Into<PathBuf>
+    {
+        self.session.conflict_svg_dir = Some(dir.into());
+        self
+    }
+
+    /// Write an HTML report on the states of each public nonterminal
+    /// into `dir`, as a single page that can be shared with people
+    /// who do not run LALRPOP: it lists the conflicts with pictures of
+    /// their examples, and the states, which can be searched and whose
+    /// items can be unfolded. The report is named after the grammar
+    /// file and the public nonterminal, e.g. `calc-Expr.html`.
+    pub fn write_html_reports<P>(&mut self, dir: P) -> &mut Configuration
+        where P: Into<PathBuf>
+    {
+        self.session.html_report_dir = Some(dir.into());
+        self
+    }
+
+    /// Show at most `count` reductions in each example of a conflict
+    /// report. In examples with more, the innermost and outermost
+    /// reductions are shown, and those in the middle are collapsed
+    /// into a single `…` row. Default is 12.
+    pub fn max_example_reductions(&mut self, count: usize) -> &mut Configuration {
+        self.session.max_example_reductions = Some(count);
+        self
+    }
+
+    /// Show every reduction in the examples of conflict reports, no
+    /// matter how deep the derivation (see `max_example_reductions`).
+    pub fn expand_examples(&mut self) -> &mut Configuration {
+        self.session.max_example_reductions = None;
+        self
+    }
+
+    /// Send the errors, warnings, conflict reports and log messages
+    /// to `diagnostics` rather than printing them, so that they can be
+    /// captured, filtered or shown some other way.
+    pub fn set_diagnostics<D>(&mut self, diagnostics: D) -> &mut Configuration
+        where D: Diagnostics + 'static
+    {
+        self.session.diagnostics = Some(Rc::new(diagnostics));
+        self
+    }
+
+    /// Generate the files that `backend` makes of each grammar rather
+    /// than the Rust code of its parsers. The files go where the `.rs`
+    /// file would.
+    pub fn set_backend<B>(&mut self, backend: B) -> &mut Configuration
+        where B: Backend + 'static
+    {
+        self.session.backend = Some(Rc::new(backend));
+        self
+    }
+
+    /// Run `pass` on each grammar once it is normalized, after the
+    /// passes added before it (see `Pass`).
+    pub fn add_pass<P>(&mut self, pass: P) -> &mut Configuration
+        where P: Pass + 'static
+    {
+        self.session.passes.push(Rc::new(pass));
+        self
+    }
+
     /// Minimal logs: only for errors that halt progress.
     pub fn log_quiet(&mut self) -> &mut Configuration {
         self.session.log.set_level(Level::Taciturn);
@@ -153,6 +496,282 @@ impl Configuration {
         try!(build::process_file(session, path));
         Ok(())
     }
+
+    /// Process the given `.lalrpop` file, writing the parser to where
+    /// the module `module_path` (e.g., `parser::calc`) is found
+    /// relative to the output directory (`parser/calc.rs`), so that
+    /// it can be declared with `mod calc;` in `parser.rs` wherever the
+    /// grammar itself is kept.
+    pub fn process_file_as<P>(&self, path: P, module_path: &str) -> Result<(), Box<Error>>
+        where P: AsRef<Path>
+    {
+        let session = Rc::new(self.session.clone());
+        try!(build::process_file_as(session, path, module_path));
+        Ok(())
+    }
+
+    /// Generates a parser for a grammar built with `GrammarBuilder`
+    /// rather than read from a `.lalrpop` file, writing it to where
+    /// the module `module_path` is found, as `process_file_as` does.
+    pub fn process_grammar(&self, grammar: &GrammarBuilder, module_path: &str)
+                           -> Result<(), Box<Error>>
+    {
+        let session = Rc::new(self.session.clone());
+        try!(build::process_grammar(session, grammar, module_path));
+        Ok(())
+    }
+
+    /// Generates a parser for the grammar `grammar_src`, the text of a
+    /// `.lalrpop` file, and returns its code rather than writing it
+    /// anywhere, for tools like playgrounds and procedural macros that
+    /// drive LALRPOP themselves. Nothing is read from or written to
+    /// disk: reports, pictures and binary tables are not written, and
+    /// neither is the module given to `use_support_module`. If there are
+    /// errors, they are returned along with the warnings before them.
+    pub fn generate(&self, grammar_src: &str) -> Result<String, Vec<Diagnostic>> {
+        build::generate(&self.session, grammar_src)
+    }
+
+    /// Checks the given `.lalrpop` file for errors and conflicts, as
+    /// `process_file` would, but without generating any code. The
+    /// action code is not type-checked, since that is up to `rustc`.
+    pub fn check_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<Error>> {
+        let session = Rc::new(self.session.clone());
+        try!(build::check_file(session, path));
+        Ok(())
+    }
+
+    /// Parses every file in `corpus_dir` with the grammar in `path`,
+    /// starting from the public nonterminal `start` (which may be
+    /// omitted if the grammar has only one), and saves the parse tree
+    /// for each file `foo.txt` as `foo.txt.snap`. See also
+    /// `snapshot_trivia`.
+    pub fn write_snapshots<P, Q>(&self, path: P, corpus_dir: Q, start: Option<&str>)
+                                 -> Result<(), Box<Error>>
+        where P: AsRef<Path>, Q: AsRef<Path>
+    {
+        let session = Rc::new(self.session.clone());
+        try!(build::snapshot_corpus(session, path.as_ref(), corpus_dir.as_ref(), start, false));
+        Ok(())
+    }
+
+    /// Like `write_snapshots`, but compares the parse trees against
+    /// the saved snapshots instead, printing a diff and returning an
+    /// error if any of them is missing or differs.
+    pub fn verify_snapshots<P, Q>(&self, path: P, corpus_dir: Q, start: Option<&str>)
+                                  -> Result<(), Box<Error>>
+        where P: AsRef<Path>, Q: AsRef<Path>
+    {
+        let session = Rc::new(self.session.clone());
+        try!(build::snapshot_corpus(session, path.as_ref(), corpus_dir.as_ref(), start, true));
+        Ok(())
+    }
+
+    /// Parses every file in `corpus_dir` with the grammar in `path`,
+    /// as for `write_snapshots`, and prints the productions that
+    /// none of them used.
+    pub fn measure_coverage<P, Q>(&self, path: P, corpus_dir: Q, start: Option<&str>)
+                                  -> Result<(), Box<Error>>
+        where P: AsRef<Path>, Q: AsRef<Path>
+    {
+        let session = Rc::new(self.session.clone());
+        try!(build::measure_coverage(session, path.as_ref(), corpus_dir.as_ref(), start));
+        Ok(())
+    }
+
+    /// Writes a `cargo fuzz` target for the grammar in `path` into
+    /// `fuzz_dir/fuzz_targets`, along with a dictionary of the
+    /// grammar's string literals in `fuzz_dir`. `module` is the path
+    /// to the generated parser, starting with the crate name (e.g.,
+    /// `calculator::parser`).
+    pub fn generate_fuzz_target<P, Q>(&self, path: P, module: &str, fuzz_dir: Q)
+                                      -> Result<(), Box<Error>>
+        where P: AsRef<Path>, Q: AsRef<Path>
+    {
+        let session = Rc::new(self.session.clone());
+        try!(build::generate_fuzz_target(session, path.as_ref(), module, fuzz_dir.as_ref()));
+        Ok(())
+    }
+
+    /// Searches for an input of at most `max_length` tokens that has
+    /// two different parse trees under the grammar in `path`, and
+    /// fails, printing the input and both trees, if there is one.
+    pub fn check_ambiguity<P>(&self, path: P, start: Option<&str>, max_length: usize)
+                              -> Result<(), Box<Error>>
+        where P: AsRef<Path>
+    {
+        let session = Rc::new(self.session.clone());
+        try!(build::check_ambiguity(session, path.as_ref(), start, max_length));
+        Ok(())
+    }
+
+    /// Prints complexity metrics for each nonterminal of the grammar
+    /// in `path`: its number of productions and symbols after macro
+    /// expansion, how deeply nonterminals are nested beneath it, and
+    /// how many LR states it takes part in.
+    pub fn report_metrics<P>(&self, path: P) -> Result<(), Box<Error>>
+        where P: AsRef<Path>
+    {
+        let session = Rc::new(self.session.clone());
+        try!(build::report_metrics(session, path.as_ref()));
+        Ok(())
+    }
+
+    /// Compares two revisions of a grammar, printing the productions
+    /// that were added, removed, or given new action code, and for
+    /// each public nonterminal, the states and conflicts that only
+    /// the automaton for `new_path` has.
+    pub fn diff_grammars<P, Q>(&self, old_path: P, new_path: Q) -> Result<(), Box<Error>>
+        where P: AsRef<Path>, Q: AsRef<Path>
+    {
+        let session = Rc::new(self.session.clone());
+        try!(build::diff_grammars(session, old_path.as_ref(), new_path.as_ref()));
+        Ok(())
+    }
+
+    /// Prints a reference for the grammar in `path`, in Markdown: each
+    /// nonterminal with its doc comment and its alternatives in BNF,
+    /// without names or action code, linked to the nonterminals that
+    /// it refers to and that refer to it. The `//!` comment at the
+    /// top of the file, if any, introduces the whole.
+    pub fn document_grammar<P>(&self, path: P) -> Result<(), Box<Error>>
+        where P: AsRef<Path>
+    {
+        let session = Rc::new(self.session.clone());
+        try!(build::document_grammar(session, path.as_ref()));
+        Ok(())
+    }
+
+    /// Prints the literal terminals of the grammar in `path` as a JSON
+    /// array, for generating syntax highlighters and the like. Each
+    /// element has the `text` of the token, its `kind` (`keyword`,
+    /// `punctuation` or, for a regular expression, `regex`), the
+    /// `terminal` it is written as in the grammar (which a `match`
+    /// block may have renamed), and whether it is `reserved`.
+    pub fn list_tokens<P>(&self, path: P) -> Result<(), Box<Error>>
+        where P: AsRef<Path>
+    {
+        let session = Rc::new(self.session.clone());
+        try!(build::list_tokens(session, path.as_ref()));
+        Ok(())
+    }
+
+    /// The grammar in `path` as LR(1) construction sees it, after
+    /// macros are expanded, `#[inline]` nonterminals are inlined and
+    /// types are inferred, for tools that analyze grammars (see the
+    /// `ir` module).
+    pub fn normalized_grammar<P>(&self, path: P) -> Result<ir::Grammar, Box<Error>>
+        where P: AsRef<Path>
+    {
+        let session = Rc::new(self.session.clone());
+        Ok(try!(build::normalized_grammar(session, path.as_ref())))
+    }
+
+    /// Prints `normalized_grammar(path)` as JSON, which needs the
+    /// `serde` feature.
+    pub fn print_normalized_grammar<P>(&self, path: P) -> Result<(), Box<Error>>
+        where P: AsRef<Path>
+    {
+        let session = Rc::new(self.session.clone());
+        try!(build::print_normalized_grammar(session, path.as_ref()));
+        Ok(())
+    }
+
+    /// Prints the grammar in `path` as an ANTLR4 grammar, to be saved
+    /// as a `.g4` file named after it, for parsing the same language
+    /// with ANTLR's targets. The grammar is exported after macros are
+    /// expanded, without its action code; the regular expressions of
+    /// the internal tokenizer become lexer rules. ANTLR only accepts
+    /// direct left recursion, so a grammar whose nonterminals are
+    /// left-recursive through one another needs adjusting by hand.
+    pub fn export_antlr<P>(&self, path: P) -> Result<(), Box<Error>>
+        where P: AsRef<Path>
+    {
+        let session = Rc::new(self.session.clone());
+        try!(build::export_antlr(session, path.as_ref()));
+        Ok(())
+    }
+
+    /// Translates the ABNF grammar (as in RFC 5234) in `path` into a
+    /// LALRPOP grammar and prints it, to start a parser for a protocol
+    /// from the rules in its specification. The first rule becomes the
+    /// public nonterminal, and each rule a nonterminal of type `()`.
+    /// Core rules like `ALPHA` and `DIGIT` become regular expressions;
+    /// whitespace, which the tokenizer skips, is left out. Prose
+    /// values (`<...>`) cannot be translated and are reported as
+    /// errors.
+    pub fn import_abnf<P>(&self, path: P) -> Result<(), Box<Error>>
+        where P: AsRef<Path>
+    {
+        let session = Rc::new(self.session.clone());
+        try!(build::import_abnf(session, path.as_ref()));
+        Ok(())
+    }
+
+    /// Parses each file in `samples` (`-` standing for stdin) with the
+    /// grammar in `path`, starting from the public nonterminal
+    /// `start` (which may be omitted if the grammar has only one),
+    /// and prints whether it was accepted or, if not, where and why
+    /// the parser gave up. The parser is not generated but
+    /// interpreted from its tables, so this takes no longer than
+    /// `check_file`. For `names`, see `explain_input`. Returns an
+    /// error if any sample was rejected.
+    pub fn parse_samples<P, Q>(&self,
+                               path: P,
+                               samples: &[Q],
+                               start: Option<&str>,
+                               names: bool)
+                               -> Result<(), Box<Error>>
+        where P: AsRef<Path>, Q: AsRef<Path>
+    {
+        let session = Rc::new(self.session.clone());
+        let samples: Vec<&Path> = samples.iter().map(|sample| sample.as_ref()).collect();
+        try!(build::parse_samples(session, path.as_ref(), &samples, start, names));
+        Ok(())
+    }
+
+    /// Parses `input` with the grammar in `path`, starting from the
+    /// public nonterminal `start` (which may be omitted if the grammar
+    /// has only one), and prints the derivation tree, with the
+    /// production used at each node; with `show_steps`, it is preceded
+    /// by the shifts and reductions that the parser made. If `names`
+    /// is set, `input` is not lexed but read as the names of terminals
+    /// separated by whitespace (e.g., `"(" Num ")"`), which also works
+    /// for grammars with an external lexer. Returns an error if the
+    /// input does not parse, after printing how far the parser got.
+    pub fn explain_input<P>(&self,
+                            path: P,
+                            input: &str,
+                            start: Option<&str>,
+                            names: bool,
+                            show_steps: bool)
+                            -> Result<(), Box<Error>>
+        where P: AsRef<Path>
+    {
+        let session = Rc::new(self.session.clone());
+        try!(build::explain_input(session, path.as_ref(), input, start, names, show_steps));
+        Ok(())
+    }
+
+    /// Parses `input` with the grammar in `path` as `explain_input`
+    /// does, but prints each move of the parser in turn: the shift or
+    /// reduction, the states left on the stack, and the trees built so
+    /// far, drawn like the examples in conflict reports, before the
+    /// tokens yet to be shifted. When stdout is a terminal, it waits
+    /// for Enter between moves (or `q` to stop). Returns an error if
+    /// the input does not parse.
+    pub fn simulate_input<P>(&self,
+                             path: P,
+                             input: &str,
+                             start: Option<&str>,
+                             names: bool)
+                             -> Result<(), Box<Error>>
+        where P: AsRef<Path>
+    {
+        let session = Rc::new(self.session.clone());
+        try!(build::simulate_input(session, path.as_ref(), input, start, names));
+        Ok(())
+    }
 }
 
 /// Process all files in the current directory, which -- unless you
@@ -0,0 +1,29 @@
+use diagnostics::Severity;
+
+use super::Configuration;
+
+#[test]
+fn generate_parser() {
+    let code = Configuration::new()
+        .generate(r#"grammar; pub Num: u32 = <r"[0-9]+"> => <>.parse().unwrap();"#)
+        .unwrap();
+    assert!(code.contains("fn parse_Num"), "{}", code);
+}
+
+#[test]
+fn generate_with_errors() {
+    let grammar = "grammar; pub Num: u32 = Digits;";
+    let errors = Configuration::new().generate(grammar).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].severity, Severity::Error);
+    assert_eq!(errors[0].code, Some("E0002"));
+    assert_eq!(&grammar[errors[0].span.0..errors[0].span.1], "Digits");
+}
+
+#[test]
+fn generate_without_public_nonterminals() {
+    let errors = Configuration::new().generate(r#"grammar; Num = "1";"#).unwrap_err();
+    let error = errors.last().unwrap();
+    assert_eq!(error.code, None);
+    assert_eq!(error.text, "no public symbols declared in grammar");
+}
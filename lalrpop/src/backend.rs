@@ -0,0 +1,88 @@
+//! A way to generate something other than the Rust code of the
+//! parsers -- parsers in other languages, dumps of the tables, pictures
+//! of the automata -- from what LALRPOP computes, without forking it.
+//! The Rust code itself is generated by a backend like any other.
+
+use build;
+use grammar::repr as r;
+use ir;
+use session::Session;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Turns a grammar into files, once given to
+/// `Configuration::set_backend`.
+pub trait Backend {
+    /// The files to write for the grammar of `input`. Errors and
+    /// warnings about the grammar have been reported by the time this
+    /// is called, except for the conflicts that `input.automata()`
+    /// runs into.
+    fn generate(&self, input: &BackendInput) -> io::Result<Vec<OutputFile>>;
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputFile {
+    /// Where to write the file, relative to the directory where the
+    /// `.rs` file of the parser would go.
+    pub path: PathBuf,
+
+    pub contents: Vec<u8>,
+}
+
+/// What a backend has to go on: the grammar after normalization and,
+/// on demand, the automata of its parsers.
+pub struct BackendInput<'input> {
+    session: &'input Session,
+    grammar: &'input r::Grammar,
+    ir: ir::Grammar,
+    rs_file: &'input Path,
+    report_file: &'input Path,
+}
+
+impl<'input> BackendInput<'input> {
+    #[doc(hidden)]
+    pub fn new(session: &'input Session,
+               grammar: &'input r::Grammar,
+               rs_file: &'input Path,
+               report_file: &'input Path)
+               -> BackendInput<'input> {
+        BackendInput {
+            session: session,
+            grammar: grammar,
+            ir: build::to_ir(grammar),
+            rs_file: rs_file,
+            report_file: report_file,
+        }
+    }
+
+    /// The grammar as LR(1) construction sees it.
+    pub fn grammar(&self) -> &ir::Grammar {
+        &self.ir
+    }
+
+    /// The name of the `.rs` file of the parser, without its
+    /// extension, after which the output files are usually named.
+    pub fn file_stem(&self) -> String {
+        self.rs_file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Builds the automaton of the parser for each public nonterminal,
+    /// in the order of `grammar().start_symbols`. As when the Rust code
+    /// is generated, conflicts are reported, and make this fail.
+    pub fn automata(&self) -> io::Result<Vec<ir::Automaton>> {
+        build::automata(self.session, self.grammar, self.report_file)
+    }
+}
+
+/// The backend that generates the Rust code of the parsers, which is
+/// used unless the session names another.
+pub struct RustBackend;
+
+impl Backend for RustBackend {
+    fn generate(&self, input: &BackendInput) -> io::Result<Vec<OutputFile>> {
+        build::emit_rust(input.session, input.grammar, input.rs_file, input.report_file)
+    }
+}
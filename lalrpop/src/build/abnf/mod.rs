@@ -0,0 +1,580 @@
+//! Translates an ABNF grammar (RFC 5234, with the case-sensitive
+//! strings of RFC 7405) into a LALRPOP grammar that recognizes the
+//! same language, as a starting point for a parser of a protocol
+//! defined in an RFC. Each rule becomes a nonterminal of type `()`;
+//! alternatives nested in groups get nonterminals of their own. The
+//! core rules, like `ALPHA` and `DIGIT`, become regular expressions,
+//! except for the whitespace ones, like `SP` and `CRLF`: the
+//! tokenizer skips whitespace, so those are left out altogether.
+
+use collections::{map, set, Map, Set};
+use session::Session;
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::rc::Rc;
+
+use super::corpus::other_error;
+
+#[cfg(test)]
+mod test;
+
+pub fn process_file(session: Rc<Session>, abnf_file: &Path) -> io::Result<()> {
+    let mut text = String::new();
+    try!(try!(File::open(abnf_file)).read_to_string(&mut text));
+    let title = abnf_file.file_name()
+                         .map(|name| name.to_string_lossy().into_owned())
+                         .unwrap_or_default();
+    let grammar = try!(translate(&title, &text).map_err(|message| {
+        other_error(format!("{}: {}", abnf_file.display(), message))
+    }));
+    log!(session, Verbose, "Translated `{}`", abnf_file.display());
+    print!("{}", grammar);
+    Ok(())
+}
+
+/// An alternation: a choice between concatenations of repetitions.
+type Alternation = Vec<Vec<Repetition>>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Repetition {
+    min: u32,
+    max: Option<u32>,
+    element: Element,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Element {
+    Rule(String),
+    Group(Alternation),
+    Chars(String, bool), // the text, and whether it is case-sensitive
+    Range(u32, u32),
+    Prose(String),
+}
+
+struct Rule {
+    name: String,
+    line: usize,
+    alternation: Alternation,
+}
+
+/// The LALRPOP grammar equivalent to the ABNF `text`, read from the
+/// file `title`, or a message saying why there is none.
+pub fn translate(title: &str, text: &str) -> Result<String, String> {
+    let rules = try!(parse_rules(text));
+    if rules.is_empty() {
+        return Err("no rules found".to_string());
+    }
+
+    let mut translator = Translator {
+        names: map(),
+        used_names: set(),
+        nonterminals: vec![],
+    };
+    for rule in &rules {
+        let name = translator.fresh_name(&camel_case(&rule.name));
+        translator.names.insert(rule.name.to_lowercase(), name);
+    }
+    for (index, rule) in rules.iter().enumerate() {
+        let name = translator.names[&rule.name.to_lowercase()].clone();
+        try!(translator.nonterminal(&name, index == 0, &rule.alternation)
+                       .map_err(|message| format!("line {}: {}", rule.line, message)));
+    }
+
+    let mut out = format!("// Translated from `{}` by `lalrpop abnf`. The nonterminals only\n\
+                           // recognize their input; whitespace, such as `SP` and `CRLF`, is\n\
+                           // skipped by the tokenizer, so it has been left out.\n\
+                           grammar;\n",
+                          title);
+    for nonterminal in &translator.nonterminals {
+        out.push_str("\n");
+        out.push_str(nonterminal);
+    }
+    Ok(out)
+}
+
+struct Translator {
+    /// The nonterminal for each rule, by its name in lowercase (as
+    /// the names of ABNF rules are case-insensitive).
+    names: Map<String, String>,
+    used_names: Set<String>,
+
+    /// The text of each nonterminal, in order.
+    nonterminals: Vec<String>,
+}
+
+impl Translator {
+    fn fresh_name(&mut self, base: &str) -> String {
+        let mut name = base.to_string();
+        let mut suffix = 2;
+        while self.used_names.contains(&name) {
+            name = format!("{}{}", base, suffix);
+            suffix += 1;
+        }
+        self.used_names.insert(name.clone());
+        name
+    }
+
+    /// Adds the nonterminal `name` for `alternation`, followed by the
+    /// nonterminals for the groups within it.
+    fn nonterminal(&mut self,
+                   name: &str,
+                   public: bool,
+                   alternation: &Alternation)
+                   -> Result<(), String> {
+        let index = self.nonterminals.len();
+        self.nonterminals.push(String::new());
+        let mut text = format!("{}{}: () = {{\n", if public { "pub " } else { "" }, name);
+        for concatenation in alternation {
+            let symbols = try!(self.concatenation(name, concatenation));
+            if symbols.is_empty() {
+                text.push_str("    => (),\n");
+            } else {
+                text.push_str(&format!("    {} => (),\n", symbols.join(" ")));
+            }
+        }
+        text.push_str("};\n");
+        self.nonterminals[index] = text;
+        Ok(())
+    }
+
+    fn concatenation(&mut self,
+                     parent: &str,
+                     concatenation: &[Repetition])
+                     -> Result<Vec<String>, String> {
+        let mut symbols = vec![];
+        for repetition in concatenation {
+            symbols.extend(try!(self.repetition(parent, repetition)));
+        }
+        Ok(symbols)
+    }
+
+    fn repetition(&mut self,
+                  parent: &str,
+                  repetition: &Repetition)
+                  -> Result<Vec<String>, String> {
+        let symbol = match try!(self.element(parent, &repetition.element)) {
+            Some(symbol) => symbol,
+            None => return Ok(vec![]),
+        };
+        let min = repetition.min as usize;
+        match repetition.max {
+            None if min == 0 => Ok(vec![format!("{}*", symbol)]),
+            None => {
+                let mut symbols = vec![symbol.clone(); min - 1];
+                symbols.push(format!("{}+", symbol));
+                Ok(symbols)
+            }
+            Some(max) if (max as usize) < min => {
+                Err(format!("the repetition `{}*{}` has a maximum below its minimum",
+                            min,
+                            max))
+            }
+            Some(max) => {
+                // `2*4x` becomes `x x (x x?)?`, which, unlike
+                // `x x x? x?`, is not ambiguous
+                let mut symbols = vec![symbol.clone(); min];
+                let mut optional: Option<String> = None;
+                for _ in min..(max as usize) {
+                    optional = Some(match optional {
+                        None => format!("{}?", symbol),
+                        Some(rest) => format!("({} {})?", symbol, rest),
+                    });
+                }
+                symbols.extend(optional);
+                Ok(symbols)
+            }
+        }
+    }
+
+    /// The symbol for `element`, or `None` if it only matches
+    /// whitespace.
+    fn element(&mut self, parent: &str, element: &Element) -> Result<Option<String>, String> {
+        match *element {
+            Element::Rule(ref name) => {
+                if let Some(nonterminal) = self.names.get(&name.to_lowercase()) {
+                    return Ok(Some(nonterminal.clone()));
+                }
+                match core_rule(name) {
+                    Some(symbol) => Ok(symbol.map(|s| s.to_string())),
+                    None => Err(format!("the rule `{}` is not defined", name)),
+                }
+            }
+            Element::Group(ref alternation) => {
+                if alternation.len() == 1 {
+                    let symbols = try!(self.concatenation(parent, &alternation[0]));
+                    Ok(match symbols.len() {
+                        0 => None,
+                        1 => symbols.into_iter().next(),
+                        _ => Some(format!("({})", symbols.join(" "))),
+                    })
+                } else {
+                    let name = self.fresh_name(&format!("{}Group", parent));
+                    try!(self.nonterminal(&name, false, alternation));
+                    Ok(Some(name))
+                }
+            }
+            Element::Chars(ref text, case_sensitive) => {
+                if text.chars().all(|c| c.is_whitespace()) {
+                    Ok(None)
+                } else {
+                    Ok(Some(terminal(text, case_sensitive)))
+                }
+            }
+            Element::Range(low, high) => {
+                Ok(Some(format!("r\"[{}-{}]\"", hex_escape(low), hex_escape(high))))
+            }
+            Element::Prose(ref prose) => {
+                Err(format!("the prose value `<{}>` has to be translated by hand", prose))
+            }
+        }
+    }
+}
+
+/// The core rules of RFC 5234, Appendix B.1, as terminals; `None`
+/// for those that only match whitespace.
+fn core_rule(name: &str) -> Option<Option<&'static str>> {
+    match &name.to_uppercase()[..] {
+        "ALPHA" => Some(Some(r#"r"[A-Za-z]""#)),
+        "BIT" => Some(Some(r#"r"[01]""#)),
+        "CHAR" => Some(Some(r#"r"[\x01-\x7F]""#)),
+        "CTL" => Some(Some(r#"r"[\x00-\x1F\x7F]""#)),
+        "DIGIT" => Some(Some(r#"r"[0-9]""#)),
+        "DQUOTE" => Some(Some(r#"r"\x22""#)),
+        "HEXDIG" => Some(Some(r#"r"[0-9A-Fa-f]""#)),
+        "OCTET" => Some(Some(r#"r"[\x00-\xFF]""#)),
+        "VCHAR" => Some(Some(r#"r"[!-~]""#)),
+        "CR" | "CRLF" | "HTAB" | "LF" | "LWSP" | "SP" | "WSP" => Some(None),
+        _ => None,
+    }
+}
+
+/// The terminal for the string `text`: quoted if it can be, and
+/// otherwise a regular expression.
+fn terminal(text: &str, case_sensitive: bool) -> String {
+    let case_sensitive = case_sensitive || !text.chars().any(|c| c.is_alphabetic());
+    let plain = text.chars().all(|c| c >= ' ' && c <= '~' && c != '"' && c != '\\');
+    if case_sensitive && plain {
+        return format!("\"{}\"", text);
+    }
+
+    let mut regex = String::new();
+    if !case_sensitive {
+        regex.push_str("(?i)");
+    }
+    for c in text.chars() {
+        if "\\.+*?()|[]{}^$#".contains(c) {
+            regex.push('\\');
+            regex.push(c);
+        } else if c >= ' ' && c <= '~' && c != '"' {
+            regex.push(c);
+        } else {
+            regex.push_str(&hex_escape(c as u32));
+        }
+    }
+    format!("r\"{}\"", regex)
+}
+
+fn hex_escape(c: u32) -> String {
+    format!("\\x{{{:X}}}", c)
+}
+
+/// `hier-part` becomes `HierPart`.
+fn camel_case(name: &str) -> String {
+    let mut out = String::new();
+    for word in name.split('-') {
+        let mut chars = word.chars();
+        if let Some(c) = chars.next() {
+            out.extend(c.to_uppercase());
+            out.push_str(chars.as_str());
+        }
+    }
+    out
+}
+
+///////////////////////////////////////////////////////////////////////////
+// Parsing
+
+/// The rules of `text`, with the alternatives added by `=/` merged
+/// into the rules that they extend.
+fn parse_rules(text: &str) -> Result<Vec<Rule>, String> {
+    // a rule starts at the beginning of a line, and goes on over the
+    // lines that start with whitespace
+    let mut chunks: Vec<(usize, String)> = vec![];
+    for (index, line) in text.lines().enumerate() {
+        let line = strip_comment(line);
+        if line.trim().is_empty() {
+            continue;
+        }
+        if line.starts_with(|c: char| c.is_whitespace()) {
+            match chunks.last_mut() {
+                Some(&mut (_, ref mut chunk)) => {
+                    chunk.push(' ');
+                    chunk.push_str(line);
+                }
+                None => return Err(format!("line {}: expected a rule name", index + 1)),
+            }
+        } else {
+            chunks.push((index + 1, line.to_string()));
+        }
+    }
+
+    let mut rules: Vec<Rule> = vec![];
+    for (line, chunk) in chunks {
+        let mut parser = Parser { chars: chunk.chars().collect(), position: 0 };
+        let (name, incremental, alternation) =
+            try!(parser.rule().map_err(|message| format!("line {}: {}", line, message)));
+        let existing = rules.iter()
+                            .position(|rule| rule.name.to_lowercase() == name.to_lowercase());
+        match (existing, incremental) {
+            (Some(index), true) => rules[index].alternation.extend(alternation),
+            (None, true) => {
+                return Err(format!("line {}: `{} =/` extends a rule that is not defined",
+                                   line,
+                                   name));
+            }
+            (Some(_), false) => {
+                return Err(format!("line {}: the rule `{}` is defined twice", line, name));
+            }
+            (None, false) => {
+                rules.push(Rule {
+                    name: name,
+                    line: line,
+                    alternation: alternation,
+                })
+            }
+        }
+    }
+    Ok(rules)
+}
+
+/// `line` without its comment, which starts with a `;` that is not
+/// inside a string or a prose value.
+fn strip_comment(line: &str) -> &str {
+    let mut closing = None;
+    for (index, c) in line.char_indices() {
+        match closing {
+            Some(end) if c == end => closing = None,
+            Some(_) => {}
+            None if c == '"' => closing = Some('"'),
+            None if c == '<' => closing = Some('>'),
+            None if c == ';' => return &line[..index],
+            None => {}
+        }
+    }
+    line
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&mut self) -> Option<char> {
+        while self.position < self.chars.len() && self.chars[self.position].is_whitespace() {
+            self.position += 1;
+        }
+        self.chars.get(self.position).cloned()
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.peek() == Some(c) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("`{}`", c)))
+        }
+    }
+
+    fn unexpected(&mut self, expected: &str) -> String {
+        match self.peek() {
+            Some(c) => format!("expected {}, found `{}`", expected, c),
+            None => format!("expected {}, found the end of the rule", expected),
+        }
+    }
+
+    /// Takes the characters for which `f` holds, without skipping
+    /// whitespace first.
+    fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> String {
+        let start = self.position;
+        while self.position < self.chars.len() && f(self.chars[self.position]) {
+            self.position += 1;
+        }
+        self.chars[start..self.position].iter().cloned().collect()
+    }
+
+    fn rule(&mut self) -> Result<(String, bool, Alternation), String> {
+        let name = try!(self.rule_name());
+        try!(self.expect('='));
+        let incremental = self.chars.get(self.position) == Some(&'/');
+        if incremental {
+            self.position += 1;
+        }
+        let alternation = try!(self.alternation());
+        if self.peek().is_some() {
+            return Err(self.unexpected("`/` or an element"));
+        }
+        Ok((name, incremental, alternation))
+    }
+
+    fn rule_name(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some(c) if is_alpha(c) => {
+                Ok(self.take_while(|c| is_alpha(c) || c.is_digit(10) || c == '-'))
+            }
+            _ => Err(self.unexpected("a rule name")),
+        }
+    }
+
+    fn alternation(&mut self) -> Result<Alternation, String> {
+        let mut alternation = vec![try!(self.concatenation())];
+        while self.peek() == Some('/') {
+            self.position += 1;
+            alternation.push(try!(self.concatenation()));
+        }
+        Ok(alternation)
+    }
+
+    fn concatenation(&mut self) -> Result<Vec<Repetition>, String> {
+        let mut concatenation = vec![try!(self.repetition())];
+        loop {
+            match self.peek() {
+                None | Some('/') | Some(')') | Some(']') => return Ok(concatenation),
+                Some(_) => concatenation.push(try!(self.repetition())),
+            }
+        }
+    }
+
+    fn repetition(&mut self) -> Result<Repetition, String> {
+        self.peek();
+        let min = self.take_while(|c| c.is_digit(10));
+        let (min, max) = if self.chars.get(self.position) == Some(&'*') {
+            self.position += 1;
+            let max = self.take_while(|c| c.is_digit(10));
+            (if min.is_empty() { 0 } else { try!(number(&min, 10)) },
+             if max.is_empty() { None } else { Some(try!(number(&max, 10))) })
+        } else if min.is_empty() {
+            (1, Some(1))
+        } else {
+            let count = try!(number(&min, 10));
+            (count, Some(count))
+        };
+        let element = try!(self.element());
+        Ok(Repetition {
+            min: min,
+            max: max,
+            element: element,
+        })
+    }
+
+    fn element(&mut self) -> Result<Element, String> {
+        match self.peek() {
+            Some('(') => {
+                self.position += 1;
+                let alternation = try!(self.alternation());
+                try!(self.expect(')'));
+                Ok(Element::Group(alternation))
+            }
+            Some('[') => {
+                self.position += 1;
+                let alternation = try!(self.alternation());
+                try!(self.expect(']'));
+                Ok(Element::Group(vec![vec![Repetition {
+                                                min: 0,
+                                                max: Some(1),
+                                                element: Element::Group(alternation),
+                                            }]]))
+            }
+            Some('"') => self.char_val(false),
+            Some('<') => {
+                self.position += 1;
+                let prose = self.take_while(|c| c != '>');
+                try!(self.expect('>'));
+                Ok(Element::Prose(prose))
+            }
+            Some('%') => {
+                self.position += 1;
+                match self.chars.get(self.position).cloned() {
+                    Some('s') | Some('S') => {
+                        self.position += 1;
+                        self.char_val(true)
+                    }
+                    Some('i') | Some('I') => {
+                        self.position += 1;
+                        self.char_val(false)
+                    }
+                    _ => self.num_val(),
+                }
+            }
+            Some(c) if is_alpha(c) => Ok(Element::Rule(try!(self.rule_name()))),
+            _ => Err(self.unexpected("an element")),
+        }
+    }
+
+    fn char_val(&mut self, case_sensitive: bool) -> Result<Element, String> {
+        if self.chars.get(self.position) != Some(&'"') {
+            return Err(self.unexpected("`\"`"));
+        }
+        self.position += 1;
+        let text = self.take_while(|c| c != '"');
+        if self.chars.get(self.position) != Some(&'"') {
+            return Err("unterminated string".to_string());
+        }
+        self.position += 1;
+        Ok(Element::Chars(text, case_sensitive))
+    }
+
+    /// `%x41`, `%x41.42` or `%x41-5A`, after the `%`.
+    fn num_val(&mut self) -> Result<Element, String> {
+        let radix = match self.chars.get(self.position).cloned() {
+            Some('b') | Some('B') => 2,
+            Some('d') | Some('D') => 10,
+            Some('x') | Some('X') => 16,
+            _ => return Err(self.unexpected("`b`, `d` or `x` after `%`")),
+        };
+        self.position += 1;
+        let first = try!(self.digits(radix));
+        match self.chars.get(self.position).cloned() {
+            Some('-') => {
+                self.position += 1;
+                let last = try!(self.digits(radix));
+                Ok(Element::Range(first, last))
+            }
+            Some('.') => {
+                let mut text = try!(char_from(first)).to_string();
+                while self.chars.get(self.position) == Some(&'.') {
+                    self.position += 1;
+                    text.push(try!(char_from(try!(self.digits(radix)))));
+                }
+                Ok(Element::Chars(text, true))
+            }
+            _ => Ok(Element::Chars(try!(char_from(first)).to_string(), true)),
+        }
+    }
+
+    fn digits(&mut self, radix: u32) -> Result<u32, String> {
+        let digits = self.take_while(|c| c.is_digit(radix));
+        if digits.is_empty() {
+            Err(self.unexpected("a digit"))
+        } else {
+            number(&digits, radix)
+        }
+    }
+}
+
+fn is_alpha(c: char) -> bool {
+    match c {
+        'a'...'z' | 'A'...'Z' => true,
+        _ => false,
+    }
+}
+
+fn number(digits: &str, radix: u32) -> Result<u32, String> {
+    u32::from_str_radix(digits, radix).map_err(|_| format!("`{}` is too large", digits))
+}
+
+fn char_from(value: u32) -> Result<char, String> {
+    ::std::char::from_u32(value).ok_or_else(|| format!("`{:X}` is not a character", value))
+}
@@ -0,0 +1,75 @@
+use parser;
+
+use super::translate;
+
+#[test]
+fn rules_groups_and_repetitions() {
+    let abnf = r#"
+; a tiny language of URIs
+uri     = scheme ":" [ "//" host ]
+          *( "/" segment )
+scheme  = ALPHA *( ALPHA / DIGIT / "+" )
+host    = 1*( %x61-7A / DIGIT ) SP
+segment = 0*2%s"ab" ";" ; a semicolon
+scheme  =/ "urn"
+"#;
+    let expected = r#"// Translated from `uri.abnf` by `lalrpop abnf`. The nonterminals only
+// recognize their input; whitespace, such as `SP` and `CRLF`, is
+// skipped by the tokenizer, so it has been left out.
+grammar;
+
+pub Uri: () = {
+    Scheme ":" ("//" Host)? ("/" Segment)* => (),
+};
+
+Scheme: () = {
+    r"[A-Za-z]" SchemeGroup* => (),
+    r"(?i)urn" => (),
+};
+
+SchemeGroup: () = {
+    r"[A-Za-z]" => (),
+    r"[0-9]" => (),
+    "+" => (),
+};
+
+Host: () = {
+    HostGroup+ => (),
+};
+
+HostGroup: () = {
+    r"[\x{61}-\x{7A}]" => (),
+    r"[0-9]" => (),
+};
+
+Segment: () = {
+    ("ab" "ab"?)? ";" => (),
+};
+"#;
+    let actual = translate("uri.abnf", abnf).unwrap();
+    assert_eq!(actual, expected, "\n{}", actual);
+    assert!(parser::parse_grammar(&actual).is_ok());
+}
+
+#[test]
+fn terminals() {
+    let actual = translate("t.abnf", "t = \"a\\b\" %x0D.0A %d65 %b1000010 %s\"Q\" \"42\"\n")
+        .unwrap();
+    assert!(actual.contains(r#"    r"(?i)a\\b" "A" "B" "Q" "42" => (),"#), "{}", actual);
+    assert!(parser::parse_grammar(&actual).is_ok());
+}
+
+#[test]
+fn errors() {
+    let check = |abnf: &str, expected: &str| {
+        let message = translate("e.abnf", abnf).unwrap_err();
+        assert_eq!(message, expected);
+    };
+    check("a = b\n", "line 1: the rule `b` is not defined");
+    check("a = \"x\"\nb = <a prose value>\n",
+          "line 2: the prose value `<a prose value>` has to be translated by hand");
+    check("a =/ \"x\"\n", "line 1: `a =/` extends a rule that is not defined");
+    check("a = \"x\"\nA = \"y\"\n", "line 2: the rule `A` is defined twice");
+    check("a = 3*2\"x\"\n", "line 1: the repetition `3*2` has a maximum below its minimum");
+    check("a = (\"x\"\n", "line 1: expected `)`, found the end of the rule");
+}
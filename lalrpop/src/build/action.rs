@@ -27,10 +27,18 @@
 //! ) -> Box<Expr<'input>>
 //! ```
 //!
+//! Action fns that implement `@T`, or that inline such an action,
+//! additionally take the lookahead token as their final argument:
+//!
+//! ```
+//!    __lookahead_token: Option<&(usize, Tok, usize)>,
+//! ```
+//!
 //! * -- in this case, those "user-declared" parameters are inserted by
 //! the "internal tokenizer".
 
 use grammar::repr as r;
+use intern::{intern, InternedString};
 use rust::RustWrite;
 use std::io::{self, Write};
 
@@ -58,6 +66,10 @@ pub fn emit_action_code<W: Write>(grammar: &r::Grammar, rust: &mut RustWrite<W>)
         }
     }
 
+    if grammar.uses_reduce_hooks() {
+        try!(emit_reduce_hooks_trait(grammar, rust));
+    }
+
     Ok(())
 }
 
@@ -115,7 +127,109 @@ fn emit_user_action_code<W: Write>(grammar: &r::Grammar,
                               ret_type,
                               vec![]));
     rust!(rust, "{{");
-    rust!(rust, "{}", data.code);
+    match data.hook {
+        Some(hook) => {
+            // the code lives in the hook, which the first parameter
+            // may have overridden
+            let type_parameters: Vec<String> =
+                grammar.type_parameters.iter().map(|p| p.to_string()).collect();
+            let arguments: Vec<String> =
+                grammar.parameters
+                       .iter()
+                       .map(|p| p.name.to_string())
+                       .chain(hook_arguments(data).into_iter().map(|(p, _)| p.to_string()))
+                       .collect();
+            rust!(rust,
+                  "{}ReduceHooks::<{}>::{}({})",
+                  grammar.prefix,
+                  type_parameters.join(", "),
+                  hook,
+                  arguments.join(", "));
+        }
+        None => rust!(rust, "{}", data.code),
+    }
+    rust!(rust, "}}");
+    Ok(())
+}
+
+/// The values that a hook is given: those of the symbols that the
+/// user gave a name, without their locations.
+fn hook_arguments(data: &r::UserActionFnDefn) -> Vec<(InternedString, &r::TypeRepr)> {
+    data.arg_patterns
+        .iter()
+        .cloned()
+        .zip(&data.arg_types)
+        .filter(|&(p, _)| p != intern("_"))
+        .collect()
+}
+
+/// The trait whose default methods hold the code of the user's
+/// actions in a `#[reduce_hooks]` grammar. It is implemented by the
+/// referent of the first parameter, which is given to the code under
+/// its own name, and the other parameters are passed along. The
+/// default methods know nothing of `Self` but that it can be borrowed
+/// as that referent, which every type can be as itself.
+fn emit_reduce_hooks_trait<W: Write>(grammar: &r::Grammar,
+                                     rust: &mut RustWrite<W>)
+                                     -> io::Result<()> {
+    let type_parameters: Vec<String> =
+        grammar.type_parameters.iter().map(|p| p.to_string()).collect();
+    let (receiver, referent) = match grammar.parameters[0].ty {
+        r::TypeRepr::Ref { lifetime: Some(lifetime), ref referent, .. } => {
+            (format!("&{} self", lifetime), referent)
+        }
+        r::TypeRepr::Ref { lifetime: None, ref referent, .. } => (format!("&self"), referent),
+        _ => panic!("`prevalidate` permitted a `#[reduce_hooks]` grammar without a reference"),
+    };
+
+    rust!(rust, "");
+    rust!(rust, "#[allow(non_snake_case)]");
+    rust!(rust,
+          "pub trait {}ReduceHooks<{}>: ::std::borrow::Borrow<{}>",
+          grammar.prefix,
+          type_parameters.join(", "),
+          referent);
+    if !grammar.where_clauses.is_empty() {
+        rust!(rust, "where");
+        for where_clause in &grammar.where_clauses {
+            rust!(rust, "  {},", where_clause);
+        }
+    }
+    rust!(rust, "{{");
+
+    for defn in &grammar.action_fn_defns {
+        let data = match defn.kind {
+            r::ActionFnDefnKind::User(ref data) => data,
+            _ => continue,
+        };
+        let hook = match data.hook {
+            Some(hook) => hook,
+            None => continue,
+        };
+
+        let parameters: Vec<String> =
+            Some(receiver.clone())
+                .into_iter()
+                .chain(grammar.parameters[1..]
+                           .iter()
+                           .map(|p| format!("{}: {}", p.name, p.ty)))
+                .chain(hook_arguments(data).into_iter().map(|(p, t)| format!("{}: {}", p, t)))
+                .collect();
+
+        rust!(rust, "#[allow(unused_variables)]");
+        rust!(rust,
+              "fn {}({}) -> {} {{",
+              hook,
+              parameters.join(", "),
+              ret_type_string(grammar, defn));
+        rust!(rust,
+              "let {} = ::std::borrow::Borrow::<{}>::borrow(self);",
+              grammar.parameters[0].name,
+              referent);
+        rust!(rust, "{}", data.code);
+        rust!(rust, "}}");
+    }
+
     rust!(rust, "}}");
     Ok(())
 }
@@ -123,19 +237,24 @@ fn emit_user_action_code<W: Write>(grammar: &r::Grammar,
 fn emit_lookaround_action_code<W: Write>(grammar: &r::Grammar,
                                          rust: &mut RustWrite<W>,
                                          index: usize,
-                                         _defn: &r::ActionFnDefn,
+                                         defn: &r::ActionFnDefn,
                                          data: &r::LookaroundActionFnDefn)
                                          -> io::Result<()> {
+    let mut arguments = vec![format!("{}lookbehind: &{}",
+                                     grammar.prefix,
+                                     grammar.types.terminal_loc_type()),
+                             format!("{}lookahead: &{}",
+                                     grammar.prefix,
+                                     grammar.types.terminal_loc_type())];
+    if *data == r::LookaroundActionFnDefn::LookaheadToken {
+        arguments.push(lookahead_token_argument(grammar));
+    }
+
     try!(rust.write_fn_header(grammar,
                               format!("{}action{}", grammar.prefix, index),
                               vec![],
-                              vec![format!("{}lookbehind: &{}",
-                                           grammar.prefix,
-                                           grammar.types.terminal_loc_type()),
-                                   format!("{}lookahead: &{}",
-                                           grammar.prefix,
-                                           grammar.types.terminal_loc_type())],
-                              format!("{}", grammar.types.terminal_loc_type()),
+                              arguments,
+                              format!("{}", defn.ret_type),
                               vec![]));
 
     rust!(rust, "{{");
@@ -151,11 +270,21 @@ fn emit_lookaround_action_code<W: Write>(grammar: &r::Grammar,
             // take lookbehind or supply default
             rust!(rust, "{}lookbehind.clone()", grammar.prefix);
         }
+        r::LookaroundActionFnDefn::LookaheadToken => {
+            // take the lookahead token, or `None` if we are at EOF
+            rust!(rust, "{}lookahead_token.cloned()", grammar.prefix);
+        }
     }
     rust!(rust, "}}");
     Ok(())
 }
 
+fn lookahead_token_argument(grammar: &r::Grammar) -> String {
+    format!("{}lookahead_token: Option<&{}>",
+            grammar.prefix,
+            grammar.types.triple_type())
+}
+
 fn emit_inline_action_code<W: Write>(grammar: &r::Grammar,
                                      rust: &mut RustWrite<W>,
                                      index: usize,
@@ -197,6 +326,10 @@ fn emit_inline_action_code<W: Write>(grammar: &r::Grammar,
                                       grammar.types.terminal_loc_type())]);
     }
 
+    if grammar.action_takes_lookahead_token(r::ActionFn::new(index)) {
+        arguments.push(lookahead_token_argument(grammar));
+    }
+
     try!(rust.write_fn_header(grammar,
                               format!("{}action{}", grammar.prefix, index),
                               vec![],
@@ -319,6 +452,9 @@ fn emit_inline_action_code<W: Write>(grammar: &r::Grammar,
                     rust!(rust, "&{}start{},", grammar.prefix, temp_counter);
                     rust!(rust, "&{}end{},", grammar.prefix, temp_counter);
                 }
+                if grammar.action_takes_lookahead_token(inlined_action) {
+                    rust!(rust, "{}lookahead_token,", grammar.prefix);
+                }
                 rust!(rust, ");");
 
                 // wrap up the inlined value along with its span
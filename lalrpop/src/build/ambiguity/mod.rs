@@ -0,0 +1,158 @@
+//! Searches for inputs that have more than one parse tree. The LR(1)
+//! construction already reports the ambiguities that cause conflicts,
+//! but only as the conflict; this finds a concrete input, up to a
+//! given number of tokens, that actually parses in two ways.
+//!
+//! The search works bottom-up: for each nonterminal, we collect every
+//! sentence of at most `max_length` terminals that it derives, along
+//! with up to two distinct parse trees for it, and repeat until no
+//! more can be found. Since each table is bounded, this terminates
+//! even for grammars with cycles like `A = A`.
+
+use collections::{map, Map};
+use grammar::repr::*;
+use lr1::interpret::ParseTree;
+use session::Session;
+use tls::Tls;
+use util::Sep;
+
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+use super::{corpus, load_file_text, parse_and_normalize_grammar};
+
+#[cfg(test)]
+mod test;
+
+pub fn process_file(session: Rc<Session>,
+                    lalrpop_file: &Path,
+                    start: Option<&str>,
+                    max_length: usize)
+                    -> io::Result<()> {
+    let file_text = Rc::new(try!(load_file_text(&session, lalrpop_file)));
+    let _tls = Tls::install(session.clone(), file_text.clone());
+
+    let grammar = try!(parse_and_normalize_grammar(&session, &file_text));
+    let start_nt = try!(corpus::start_nonterminal(&grammar, start));
+    let user_nt = grammar.start_nonterminals
+                         .iter()
+                         .find(|&(_, &nt)| nt == start_nt)
+                         .map(|(&user_nt, _)| user_nt)
+                         .unwrap();
+
+    match find_ambiguity(&grammar, user_nt, max_length) {
+        Some(ambiguity) => {
+            println!("`{}` is ambiguous: the input `{}` has more than one parse tree:",
+                     user_nt,
+                     Sep(" ", &ambiguity.sentence));
+            println!("  {}", ambiguity.trees.0);
+            println!("  {}", ambiguity.trees.1);
+            Err(corpus::other_error(format!("found an ambiguous input for `{}`", user_nt)))
+        }
+        None => {
+            log!(session,
+                 Informative,
+                 "no ambiguous input for `{}` with at most {} tokens",
+                 user_nt,
+                 max_length);
+            Ok(())
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Ambiguity {
+    pub sentence: Vec<TerminalString>,
+    pub trees: (ParseTree, ParseTree),
+}
+
+/// The shortest sentence of at most `max_length` terminals that
+/// `nonterminal` derives in two different ways, if any.
+pub fn find_ambiguity(grammar: &Grammar,
+                      nonterminal: NonterminalString,
+                      max_length: usize)
+                      -> Option<Ambiguity> {
+    let table = derivations(grammar, max_length);
+    table[&nonterminal]
+        .iter()
+        .filter(|&(_, trees)| trees.len() > 1)
+        .min_by_key(|&(sentence, _)| sentence.len())
+        .map(|(sentence, trees)| {
+            Ambiguity {
+                sentence: sentence.clone(),
+                trees: (trees[0].clone(), trees[1].clone()),
+            }
+        })
+}
+
+type Sentence = Vec<TerminalString>;
+
+/// For each nonterminal, the sentences of at most `max_length`
+/// terminals that it derives, each with one or two parse trees.
+fn derivations(grammar: &Grammar,
+               max_length: usize)
+               -> Map<NonterminalString, Map<Sentence, Vec<ParseTree>>> {
+    let mut table: Map<_, Map<Sentence, Vec<ParseTree>>> =
+        grammar.nonterminals.keys().map(|&nt| (nt, map())).collect();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (&nt, data) in &grammar.nonterminals {
+            for production in &data.productions {
+                for (sentence, children) in expansions(&table, &production.symbols, max_length) {
+                    let tree = ParseTree::Nonterminal(nt, children);
+                    let trees = table.get_mut(&nt).unwrap().entry(sentence).or_insert(vec![]);
+                    if trees.len() < 2 && !trees.contains(&tree) {
+                        trees.push(tree);
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+
+    table
+}
+
+/// Each way of deriving `symbols` from what is in `table`, as the
+/// sentence and the parse trees for each symbol.
+fn expansions(table: &Map<NonterminalString, Map<Sentence, Vec<ParseTree>>>,
+              symbols: &[Symbol],
+              max_length: usize)
+              -> Vec<(Sentence, Vec<ParseTree>)> {
+    let mut partials = vec![(vec![], vec![])];
+    for &symbol in symbols {
+        let mut extended = vec![];
+        for (sentence, trees) in partials {
+            match symbol {
+                Symbol::Terminal(t) => {
+                    if sentence.len() < max_length {
+                        let mut sentence = sentence;
+                        let mut trees = trees;
+                        sentence.push(t);
+                        trees.push(ParseTree::Terminal(t));
+                        extended.push((sentence, trees));
+                    }
+                }
+                Symbol::Nonterminal(nt) => {
+                    for (suffix, suffix_trees) in &table[&nt] {
+                        if sentence.len() + suffix.len() > max_length {
+                            continue;
+                        }
+                        for tree in suffix_trees {
+                            let mut sentence = sentence.clone();
+                            let mut trees = trees.clone();
+                            sentence.extend(suffix.iter().cloned());
+                            trees.push(tree.clone());
+                            extended.push((sentence, trees));
+                        }
+                    }
+                }
+            }
+        }
+        partials = extended;
+    }
+    partials
+}
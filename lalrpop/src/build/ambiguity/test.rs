@@ -0,0 +1,54 @@
+use grammar::repr::*;
+use intern::intern;
+use test_util::normalized_grammar;
+use util::Sep;
+
+use super::find_ambiguity;
+
+fn check(grammar: &str, max_length: usize, expected: Option<(&str, &str, &str)>) {
+    let grammar = normalized_grammar(grammar);
+    let ambiguity = find_ambiguity(&grammar, NonterminalString(intern("S")), max_length);
+    let actual = ambiguity.map(|a| {
+        (format!("{}", Sep(" ", &a.sentence)), format!("{}", a.trees.0), format!("{}", a.trees.1))
+    });
+    let expected = expected.map(|(s, t0, t1)| (s.to_string(), t0.to_string(), t1.to_string()));
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn unambiguous() {
+    check(r#"
+grammar;
+    pub S = { S "+" T, T };
+    T = { "x", "(" S ")" };
+"#, 7, None);
+}
+
+#[test]
+fn binary_operator() {
+    check(r#"
+grammar;
+    pub S = { S "+" S, "x" };
+"#, 7, Some((r#""x" "+" "x" "+" "x""#,
+             r#"[S: [S: "x"], "+", [S: [S: "x"], "+", [S: "x"]]]"#,
+             r#"[S: [S: [S: "x"], "+", [S: "x"]], "+", [S: "x"]]"#)));
+}
+
+#[test]
+fn too_short() {
+    // the shortest ambiguous input has five tokens
+    check(r#"
+grammar;
+    pub S = { S "+" S, "x" };
+"#, 4, None);
+}
+
+#[test]
+fn empty_input() {
+    check(r#"
+grammar;
+    pub S = { A, B };
+    A: () = => ();
+    B: () = => ();
+"#, 3, Some(("", "[S: [A: ]]", "[S: [B: ]]")));
+}
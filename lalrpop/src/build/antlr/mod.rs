@@ -0,0 +1,406 @@
+//! Exports a grammar to ANTLR4, for toolchains that need to parse the
+//! same language from other languages. We export the normalized
+//! grammar, so macros, `*` and `?` have been expanded and the operator
+//! tables turned into tiers, and we leave out the action code. The
+//! regular expressions of the internal tokenizer become lexer rules;
+//! for a grammar with an external tokenizer, we emit a parser grammar
+//! that declares its terminals as tokens.
+
+use collections::{map, set, Map, Set};
+use grammar::parse_tree::TerminalLiteral;
+use grammar::repr::*;
+use intern;
+use lexer::re::{self, RegexError};
+use regex_syntax::{ClassRange, Expr, Repeater};
+use session::Session;
+use tls::Tls;
+
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+use super::{load_file_text, parse_and_normalize_grammar};
+
+#[cfg(test)]
+mod test;
+
+/// Words that ANTLR does not accept as the names of rules.
+const RESERVED: &'static [&'static str] = &["catch", "channels", "finally", "fragment",
+                                            "grammar", "import", "lexer", "locals", "mode",
+                                            "options", "parser", "returns", "throws",
+                                            "tokens"];
+
+pub fn process_file(session: Rc<Session>, lalrpop_file: &Path) -> io::Result<()> {
+    let file_text = Rc::new(try!(load_file_text(&session, lalrpop_file)));
+    let _tls = Tls::install(session.clone(), file_text.clone());
+
+    let grammar = try!(parse_and_normalize_grammar(&session, &file_text));
+    let name = lalrpop_file.file_stem().unwrap().to_string_lossy();
+    let g4 = try!(antlr(&name, &grammar).map_err(|err| {
+        io::Error::new(io::ErrorKind::Other,
+                       format!("cannot translate regular expression: {}", err))
+    }));
+    print!("{}", g4);
+    Ok(())
+}
+
+/// How a terminal is written in the ANTLR grammar: as a string
+/// literal, or as the name of a lexer rule (or token).
+enum Token {
+    Literal(String),
+    Rule(String),
+}
+
+/// The ANTLR4 grammar named `name` (which should be the stem of the
+/// `.g4` file it is saved as) equivalent to `grammar`.
+pub fn antlr(name: &str, grammar: &Grammar) -> Result<String, RegexError> {
+    let mut used_names = set();
+    let mut out = String::new();
+    let name = identifier(name);
+    match grammar.intern_token {
+        Some(_) => out.push_str(&format!("grammar {};\n", name)),
+        None => out.push_str(&format!("parser grammar {};\n", name)),
+    }
+
+    // the artificial start symbols are left out; the public
+    // nonterminals can be used as entry points directly
+    let start_nonterminals: Set<NonterminalString> =
+        grammar.start_nonterminals
+               .values()
+               .chain(grammar.item_start_nonterminals.values())
+               .cloned()
+               .collect();
+    let mut rule_names: Map<NonterminalString, String> = map();
+    for &nonterminal in grammar.nonterminals.keys() {
+        if !start_nonterminals.contains(&nonterminal) {
+            let name = rule_name(&nonterminal.to_string(), false, &mut used_names);
+            rule_names.insert(nonterminal, name);
+        }
+    }
+
+    let (tokens, lexer_rules) = try!(tokens(grammar, &mut used_names));
+
+    if grammar.intern_token.is_none() {
+        let names: Vec<&str> = tokens.values()
+                                     .map(|token| match *token {
+                                         Token::Literal(ref s) | Token::Rule(ref s) => &s[..],
+                                     })
+                                     .collect();
+        if !names.is_empty() {
+            out.push_str(&format!("\ntokens {{ {} }}\n", names.join(", ")));
+        }
+    }
+
+    for (nonterminal, rule_name) in &rule_names {
+        let data = &grammar.nonterminals[nonterminal];
+        let alternatives: Vec<String> =
+            data.productions
+                .iter()
+                .filter(|production| {
+                    // ANTLR recovers from errors on its own
+                    !production.symbols.contains(&Symbol::Terminal(TerminalString::Error))
+                })
+                .map(|production| {
+                    let symbols: Vec<&str> =
+                        production.symbols
+                                  .iter()
+                                  .map(|symbol| match *symbol {
+                                      Symbol::Nonterminal(ref nt) => &rule_names[nt][..],
+                                      Symbol::Terminal(ref t) => match tokens[t] {
+                                          Token::Literal(ref s) | Token::Rule(ref s) => &s[..],
+                                      },
+                                  })
+                                  .collect();
+                    symbols.join(" ")
+                })
+                .collect();
+        out.push_str(&format!("\n{}\n", rule_name));
+        for (index, alternative) in alternatives.iter().enumerate() {
+            let bar = if index == 0 { ':' } else { '|' };
+            if alternative.is_empty() {
+                out.push_str(&format!("    {}\n", bar));
+            } else {
+                out.push_str(&format!("    {} {}\n", bar, alternative));
+            }
+        }
+        out.push_str("    ;\n");
+    }
+
+    if grammar.intern_token.is_some() {
+        out.push_str("\n");
+        for lexer_rule in &lexer_rules {
+            out.push_str(lexer_rule);
+        }
+
+        // the internal tokenizer skips whitespace between tokens
+        out.push_str("WS : [\\p{White_Space}]+ -> skip ;\n");
+    }
+    Ok(out)
+}
+
+/// Decides how each terminal of `grammar` is written, and returns the
+/// lexer rules that this needs, in order of decreasing precedence.
+fn tokens(grammar: &Grammar,
+          used_names: &mut Set<String>)
+          -> Result<(Map<TerminalString, Token>, Vec<String>), RegexError> {
+    let mut tokens = map();
+    let mut lexer_rules = vec![];
+    match grammar.intern_token {
+        Some(ref intern_token) => {
+            for entry in intern_token.match_entries.iter().rev() {
+                let token = match (entry.match_literal, entry.user_name) {
+                    // a literal standing for itself can be written inline
+                    (TerminalLiteral::Quoted(s), TerminalString::Literal(literal))
+                        if entry.match_literal == literal => {
+                        Token::Literal(intern::read(|interner| quote(interner.data(s))))
+                    }
+                    (match_literal, user_name) => {
+                        let name = token_name(user_name, lexer_rules.len() + 1, used_names);
+                        let regex = try!(intern::read(|interner| match match_literal {
+                            TerminalLiteral::Quoted(s) => Ok(re::parse_literal(interner.data(s))),
+                            TerminalLiteral::Regex(s) => re::parse_regex(interner.data(s)),
+                        }));
+                        lexer_rules.push(format!("{} : {} ; // {}\n",
+                                                 name,
+                                                 regex_text(&regex),
+                                                 match_literal));
+                        Token::Rule(name)
+                    }
+                };
+                tokens.insert(entry.user_name, token);
+            }
+        }
+        None => {
+            let terminals = grammar.terminals
+                                   .all
+                                   .iter()
+                                   .filter(|&&t| t != TerminalString::Error);
+            for (index, &terminal) in terminals.enumerate() {
+                let name = token_name(terminal, index + 1, used_names);
+                tokens.insert(terminal, Token::Rule(name));
+            }
+        }
+    }
+    Ok((tokens, lexer_rules))
+}
+
+/// The name of the lexer rule (or token) for `terminal`: its own name
+/// if it has one that can be used, and otherwise `TOKEN` followed by
+/// `number`.
+fn token_name(terminal: TerminalString, number: usize, used_names: &mut Set<String>) -> String {
+    match terminal {
+        TerminalString::Bare(name) => rule_name(&name.to_string(), true, used_names),
+        TerminalString::Literal(TerminalLiteral::Quoted(s))
+            if intern::read(|interner| {
+                let s = interner.data(s);
+                !s.is_empty() && s.chars().all(is_identifier_char)
+            }) => {
+            rule_name(&s.to_string(), true, used_names)
+        }
+        _ => rule_name(&format!("TOKEN{}", number), true, used_names),
+    }
+}
+
+/// Turns `name` into a name that ANTLR accepts for a lexer rule (if
+/// `lexer` is set) or a parser rule, which no other rule has: for
+/// example, `Comma<Expr>` becomes `comma_Expr`, `Expr[2]` becomes
+/// `expr_2`, and `(<ID> ",")*` becomes `id_star`.
+fn rule_name(name: &str, lexer: bool, used_names: &mut Set<String>) -> String {
+    let name = identifier(name);
+    let mut base = if lexer {
+        let mut chars = name.chars();
+        let first: String = chars.next().unwrap().to_uppercase().collect();
+        format!("{}{}", first, chars.as_str())
+    } else {
+        // a parser rule starts with a lowercase letter; if the first
+        // word is in capitals, like `ID`, it is lowercased as a whole
+        let end = name.find('_').unwrap_or(name.len());
+        let first_word = &name[..end];
+        if first_word.chars().all(|c| !c.is_lowercase()) {
+            format!("{}{}", first_word.to_lowercase(), &name[end..])
+        } else {
+            let mut chars = name.chars();
+            let first: String = chars.next().unwrap().to_lowercase().collect();
+            format!("{}{}", first, chars.as_str())
+        }
+    };
+    if RESERVED.contains(&&base[..]) {
+        base.push('_');
+    }
+
+    let mut name = base.clone();
+    let mut suffix = 2;
+    while used_names.contains(&name) {
+        name = format!("{}_{}", base, suffix);
+        suffix += 1;
+    }
+    used_names.insert(name.clone());
+    name
+}
+
+/// Turns `name` into an ANTLR identifier, which starts with an ASCII
+/// letter and goes on with ASCII letters, digits and underscores; the
+/// repetitions of expanded macros are spelled out.
+fn identifier(name: &str) -> String {
+    let name = name.replace("*", "_star").replace("+", "_plus").replace("?", "_opt");
+    let words: Vec<&str> = name.split(|c: char| !is_identifier_char(c))
+                               .map(|word| word.trim_matches('_'))
+                               .filter(|word| !word.is_empty())
+                               .collect();
+    let name = words.join("_");
+    match name.chars().next() {
+        Some('a'...'z') | Some('A'...'Z') => name,
+        _ => format!("X{}", name),
+    }
+}
+
+fn is_identifier_char(c: char) -> bool {
+    match c {
+        'a'...'z' | 'A'...'Z' | '0'...'9' | '_' => true,
+        _ => false,
+    }
+}
+
+/// The ANTLR string literal for `s`.
+fn quote(s: &str) -> String {
+    let mut out = String::from("'");
+    for c in s.chars() {
+        match c {
+            '\'' => out.push_str("\\'"),
+            _ => push_char(c, &mut out),
+        }
+    }
+    out.push('\'');
+    out
+}
+
+fn push_char(c: char, out: &mut String) {
+    match c {
+        '\\' => out.push_str("\\\\"),
+        '\n' => out.push_str("\\n"),
+        '\r' => out.push_str("\\r"),
+        '\t' => out.push_str("\\t"),
+        c if c.is_control() || c == '\u{feff}' => {
+            if (c as u32) > 0xffff {
+                out.push_str(&format!("\\u{{{:X}}}", c as u32))
+            } else {
+                out.push_str(&format!("\\u{:04X}", c as u32))
+            }
+        }
+        c => out.push(c),
+    }
+}
+
+/// The ANTLR lexer syntax for `regex`. The tokenizer only accepts
+/// regular expressions that it can build a DFA for, so there are no
+/// anchors, word boundaries, named groups or bytes to translate.
+fn regex_text(regex: &Expr) -> String {
+    match *regex {
+        Expr::Empty => String::new(),
+        Expr::Literal { ref chars, casei: false } => {
+            let s: String = chars.iter().cloned().collect();
+            quote(&s)
+        }
+        Expr::Literal { ref chars, casei: true } => {
+            let parts: Vec<String> =
+                chars.iter()
+                     .map(|&c| {
+                         let mut out = String::from("[");
+                         for c in c.to_lowercase().chain(c.to_uppercase()) {
+                             push_class_char(c, &mut out);
+                         }
+                         out.push(']');
+                         out
+                     })
+                     .collect();
+            parts.join(" ")
+        }
+        Expr::AnyCharNoNL => "~[\\r\\n]".to_string(),
+        Expr::AnyChar => ".".to_string(),
+        Expr::Class(ref class) => {
+            let mut out = String::from("[");
+            for range in class {
+                push_class_range(range, &mut out);
+            }
+            out.push(']');
+            out
+        }
+        Expr::Group { ref e, .. } => {
+            match **e {
+                // which has parentheses already
+                Expr::Alternate(_) => regex_text(e),
+                _ => format!("({})", regex_text(e)),
+            }
+        }
+        Expr::Repeat { ref e, r, greedy } => {
+            let e = operand(e);
+            let lazy = if greedy { "" } else { "?" };
+            match r {
+                Repeater::ZeroOrOne => format!("{}?{}", e, lazy),
+                Repeater::ZeroOrMore => format!("{}*{}", e, lazy),
+                Repeater::OneOrMore => format!("{}+{}", e, lazy),
+                Repeater::Range { min, max } => {
+                    // ANTLR has no counted repetition, so we spell it out
+                    let mut parts = vec![e.clone(); min as usize];
+                    match max {
+                        Some(max) => {
+                            for _ in min..max {
+                                parts.push(format!("{}?{}", e, lazy));
+                            }
+                        }
+                        None => parts.push(format!("{}*{}", e, lazy)),
+                    }
+                    format!("({})", parts.join(" "))
+                }
+            }
+        }
+        Expr::Concat(ref exprs) => {
+            let parts: Vec<String> = exprs.iter().map(operand).collect();
+            parts.join(" ")
+        }
+        Expr::Alternate(ref exprs) => {
+            let parts: Vec<String> = exprs.iter().map(regex_text).collect();
+            format!("({})", parts.join(" | "))
+        }
+        Expr::StartLine |
+        Expr::EndLine |
+        Expr::StartText |
+        Expr::EndText |
+        Expr::WordBoundary |
+        Expr::NotWordBoundary |
+        Expr::WordBoundaryAscii |
+        Expr::NotWordBoundaryAscii |
+        Expr::AnyByte |
+        Expr::AnyByteNoNL |
+        Expr::ClassBytes(_) |
+        Expr::LiteralBytes { .. } => panic!("unexpected regular expression `{}`", regex),
+    }
+}
+
+/// The ANTLR lexer syntax for `regex` as the operand of a repetition
+/// or in a sequence, with parentheses if it needs them.
+fn operand(regex: &Expr) -> String {
+    match *regex {
+        Expr::Concat(_) => format!("({})", regex_text(regex)),
+        Expr::Literal { ref chars, casei: true } if chars.len() > 1 => {
+            format!("({})", regex_text(regex))
+        }
+        _ => regex_text(regex),
+    }
+}
+
+fn push_class_range(range: &ClassRange, out: &mut String) {
+    push_class_char(range.start, out);
+    if range.end != range.start {
+        out.push('-');
+        push_class_char(range.end, out);
+    }
+}
+
+fn push_class_char(c: char, out: &mut String) {
+    match c {
+        ']' => out.push_str("\\]"),
+        '-' => out.push_str("\\-"),
+        _ => push_char(c, out),
+    }
+}
@@ -0,0 +1,82 @@
+use collections::set;
+use lexer::re;
+use test_util::normalized_grammar;
+use tls::Tls;
+
+use super::{antlr, regex_text, rule_name};
+
+#[test]
+fn parser_and_lexer_rules() {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(r#"
+grammar;
+    match {
+        "fn" => FN,
+        r"[a-z]+" => ID,
+    } else {
+        _
+    }
+    pub Item = { FN ID "(" Args ")", FN ID "(" ")" };
+    Args = { Args "," Arg, Arg };
+    Arg = { ID, r"[0-9]+" };
+"#);
+    let g4 = antlr("items", &grammar).unwrap();
+    assert!(g4.starts_with("grammar items;\n"), "{}", g4);
+    assert!(g4.contains("\nitem\n    : FN ID '(' args ')'\n    | FN ID '(' ')'\n    ;\n"),
+            "{}",
+            g4);
+    assert!(g4.contains("\nargs\n    : args ',' arg\n    | arg\n    ;\n"), "{}", g4);
+    assert!(g4.contains("\narg\n    : ID\n    | TOKEN3\n    ;\n"), "{}", g4);
+    assert!(g4.contains("\nFN : 'fn' ; // \"fn\"\n"), "{}", g4);
+    assert!(g4.contains("\nID : [a-z]+ ; // r#\"[a-z]+\"#\n"), "{}", g4);
+    assert!(g4.contains("\nTOKEN3 : [0-9]+ ; // r#\"[0-9]+\"#\n"), "{}", g4);
+    assert!(g4.ends_with("\nWS : [\\p{White_Space}]+ -> skip ;\n"), "{}", g4);
+
+    // the artificial start symbol is left out
+    assert!(!g4.contains("__Item"), "{}", g4);
+}
+
+#[test]
+fn external_tokenizer() {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(r#"
+grammar;
+    extern {
+        enum Tok {
+            "+" => Tok::Plus,
+            "N" => Tok::Num(<i32>),
+        }
+    }
+    pub Expr = { Expr "+" "N", "N" };
+"#);
+    let g4 = antlr("expr", &grammar).unwrap();
+    assert!(g4.starts_with("parser grammar expr;\n"), "{}", g4);
+    assert!(g4.contains("\ntokens { "), "{}", g4);
+    assert!(g4.contains("\nexpr\n    : expr TOKEN"), "{}", g4);
+    assert!(g4.contains(" N\n    | N\n    ;\n"), "{}", g4);
+    assert!(!g4.contains("WS :"), "{}", g4);
+}
+
+#[test]
+fn rule_names() {
+    let mut used_names = set();
+    assert_eq!(rule_name("Comma<Expr>", false, &mut used_names), "comma_Expr");
+    assert_eq!(rule_name("Expr[2]", false, &mut used_names), "expr_2");
+    assert_eq!(rule_name("(<ID> \",\")*", false, &mut used_names), "id_star");
+    assert_eq!(rule_name("(<ID> \",\")+", false, &mut used_names), "id_plus");
+    assert_eq!(rule_name("Import", false, &mut used_names), "import_");
+    assert_eq!(rule_name("Expr", false, &mut used_names), "expr");
+    assert_eq!(rule_name("expr", false, &mut used_names), "expr_3");
+    assert_eq!(rule_name("id", true, &mut used_names), "Id");
+}
+
+#[test]
+fn regexes() {
+    let check = |regex: &str, expected: &str| {
+        assert_eq!(regex_text(&re::parse_regex(regex).unwrap()), expected);
+    };
+    check("[a-c_]+", "[_a-c]+");
+    check("x{2,3}", "('x' 'x' 'x'?)");
+    check("(ab|c)+", "('ab' | 'c')+");
+    check("a.", "'a' ~[\\r\\n]");
+}
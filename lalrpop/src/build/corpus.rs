@@ -0,0 +1,196 @@
+//! Shared support for the tools that run a grammar over a corpus of
+//! sample inputs, like `lalrpop snapshot` and `lalrpop coverage`.
+//!
+//! Since the generated parser cannot be loaded from here, the inputs
+//! are tokenized with the DFA that backs the built-in lexer and fed
+//! to the LR(1) interpreter, which yields a parse tree rather than the
+//! values produced by the action code.
+
+use file_text::FileText;
+use grammar::repr::*;
+use intern::intern;
+use lexer::dfa::interpret;
+use lr1::{self, Token};
+use lr1::interpret::ParseTree;
+use session::Session;
+use util::Sep;
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use super::{parse_and_normalize_grammar, report_messages};
+
+/// Loads the grammar and checks that it can be interpreted; `tool`
+/// names the caller for use in error messages.
+pub fn load_grammar(session: &Session, file_text: &FileText, tool: &str) -> io::Result<Grammar> {
+    let grammar = try!(parse_and_normalize_grammar(session, file_text));
+    if grammar.intern_token.is_none() {
+        return Err(other_error(format!("cannot {} `{}`: this requires a grammar that uses the \
+                                        built-in lexer",
+                                       tool,
+                                       file_text.path().display())));
+    }
+    Ok(grammar)
+}
+
+/// Finds the artificial start nonterminal for the public nonterminal
+/// named `start`; this may be omitted if the grammar only has one.
+pub fn start_nonterminal(grammar: &Grammar, start: Option<&str>) -> io::Result<NonterminalString> {
+    match start {
+        Some(name) => {
+            grammar.start_nonterminals
+                   .get(&NonterminalString(intern(name)))
+                   .cloned()
+                   .ok_or_else(|| {
+                       other_error(format!("no public nonterminal named `{}`", name))
+                   })
+        }
+        None => {
+            if grammar.start_nonterminals.len() != 1 {
+                let names: Vec<_> = grammar.start_nonterminals.keys().collect();
+                return Err(other_error(format!("grammar has {} public nonterminals ({}); \
+                                                please specify the one to start from",
+                                               names.len(),
+                                               Sep(", ", &names))));
+            }
+            Ok(*grammar.start_nonterminals.values().next().unwrap())
+        }
+    }
+}
+
+/// Like `super::build_states`, but without writing a report.
+pub fn build_states(grammar: &Grammar, start_nt: NonterminalString) -> Vec<lr1::LR1State> {
+    match super::build_lr1_states(grammar, start_nt) {
+        Ok(states) => states,
+        Err(error) => {
+            let messages = lr1::report_error(grammar, &error);
+            let _ = report_messages(messages);
+            exit(1) // FIXME -- propagate up instead of calling `exit`
+        }
+    }
+}
+
+/// Every file below `dir`, other than the snapshots, in a stable order.
+pub fn corpus_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut result = vec![];
+    for entry in try!(fs::read_dir(dir)) {
+        let entry = try!(entry);
+        let file_type = try!(entry.file_type());
+
+        let path = entry.path();
+
+        if file_type.is_dir() {
+            result.extend(try!(corpus_files(&path)));
+        }
+
+        if file_type.is_file() && !is_snap_file(&path) {
+            result.push(path);
+        }
+    }
+    result.sort();
+    Ok(result)
+}
+
+fn is_snap_file(path: &Path) -> bool {
+    path.extension().map(|ext| ext == "snap").unwrap_or(false)
+}
+
+pub fn read_file(path: &Path) -> io::Result<String> {
+    let mut input = String::new();
+    try!(try!(fs::File::open(path)).read_to_string(&mut input));
+    Ok(input)
+}
+
+pub fn other_error(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, message)
+}
+
+/// Parses `input`, returning the parse tree (without the artificial
+/// start production) along with the text matched by each terminal,
+/// in order; or else a description of the error.
+pub fn parse<'input>(grammar: &Grammar,
+                     states: &[lr1::LR1State],
+                     input: &'input str)
+                     -> Result<(ParseTree, Vec<&'input str>), String> {
+    let tokens = match lex(grammar, input, false) {
+        Ok(tokens) => tokens,
+        Err((offset, message)) => return Err(format!("{} at offset {}", message, offset)),
+    };
+    let terminals = tokens.iter().map(|&(terminal, _)| terminal).collect();
+    match lr1::interpret::interpret(states, terminals) {
+        Ok(ParseTree::Nonterminal(_, mut children)) => {
+            assert_eq!(children.len(), 1);
+            let lexemes = tokens.iter().map(|&(_, lexeme)| lexeme).collect();
+            Ok((children.pop().unwrap(), lexemes))
+        }
+        Ok(ParseTree::Terminal(_)) => unreachable!(),
+        Err((_, Token::Terminal(terminal))) => Err(format!("unexpected token {}", terminal)),
+        Err((_, Token::EOF)) => Err("unexpected end of input".to_string()),
+        Err((_, Token::Error)) => unreachable!(),
+    }
+}
+
+/// Splits `input` into terminals, each with the text it matched: with
+/// the built-in lexer, or, if `names` is set, by reading `input` as
+/// the names of terminals as written in the grammar (e.g., `"(" Num
+/// ")"`), separated by whitespace. Returns the offset at which this
+/// fails and a description of the error if it does.
+pub fn lex<'input>(grammar: &Grammar,
+                   input: &'input str,
+                   names: bool)
+                   -> Result<Vec<(TerminalString, &'input str)>, (usize, String)> {
+    if names {
+        return input.split_whitespace()
+                    .map(|name| {
+                        grammar.terminals
+                               .all
+                               .iter()
+                               .find(|terminal| terminal.to_string() == name)
+                               .map(|&terminal| (terminal, name))
+                               .ok_or_else(|| {
+                                   (offset_in(input, name), format!("no terminal named {}", name))
+                               })
+                    })
+                    .collect();
+    }
+
+    match tokenize(grammar.intern_token.as_ref().unwrap(),
+                   input,
+                   grammar.algorithm.scannerless) {
+        Ok(tokens) => Ok(tokens),
+        Err(offset) => Err((offset, "invalid token".to_string())),
+    }
+}
+
+/// The offset of `lexeme`, which must be a slice of `input`, within
+/// `input`.
+pub fn offset_in(input: &str, lexeme: &str) -> usize {
+    lexeme.as_ptr() as usize - input.as_ptr() as usize
+}
+
+fn tokenize<'input>(intern_token: &InternToken,
+                    input: &'input str,
+                    scannerless: bool)
+                    -> Result<Vec<(TerminalString, &'input str)>, usize> {
+    let mut tokens = vec![];
+    let mut rest = input;
+    loop {
+        // skip whitespace, as the generated lexer does
+        if !scannerless {
+            rest = rest.trim_left();
+        }
+        if rest.is_empty() {
+            return Ok(tokens);
+        }
+
+        match interpret::interpret(&intern_token.dfa, rest) {
+            Some((index, lexeme)) => {
+                tokens.push((intern_token.match_entries[index.index()].user_name, lexeme));
+                rest = &rest[lexeme.len()..];
+            }
+            None => return Err(input.len() - rest.len()),
+        }
+    }
+}
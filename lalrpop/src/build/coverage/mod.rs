@@ -0,0 +1,135 @@
+//! Rule coverage for grammars. We run the grammar over every file in
+//! a corpus directory and report the productions that none of the
+//! parse trees use, which are either untested or no longer needed.
+//!
+//! Coverage is measured on the normalized grammar, so macros are
+//! reported once per instantiation (e.g., `Comma<Expr>`), and the
+//! productions of `#[inline]` nonterminals are folded into the
+//! productions of their callers.
+
+use grammar::repr::*;
+use collections::{set, Set};
+use lr1::Lr1Tls;
+use lr1::interpret::ParseTree;
+use session::Session;
+use tls::Tls;
+use util::Sep;
+
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+use super::{corpus, load_file_text};
+
+#[cfg(test)]
+mod test;
+
+pub fn process_corpus(session: Rc<Session>,
+                      lalrpop_file: &Path,
+                      corpus_dir: &Path,
+                      start: Option<&str>)
+                      -> io::Result<()> {
+    let file_text = Rc::new(try!(load_file_text(&session, lalrpop_file)));
+    let _tls = Tls::install(session.clone(), file_text.clone());
+
+    let grammar = try!(corpus::load_grammar(&session, &file_text, "measure coverage of"));
+    let start_nt = try!(corpus::start_nonterminal(&grammar, start));
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let states = corpus::build_states(&grammar, start_nt);
+
+    let mut trees = vec![];
+    for input_file in try!(corpus::corpus_files(corpus_dir)) {
+        let input = try!(corpus::read_file(&input_file));
+        match corpus::parse(&grammar, &states, &input) {
+            Ok((tree, _)) => trees.push(tree),
+            Err(message) => {
+                println!("{}: skipped, as it does not parse: {}",
+                         input_file.display(),
+                         message)
+            }
+        }
+    }
+
+    let reachable = reachable_productions(&grammar, start_nt);
+    let unused = unused_productions(&grammar, start_nt, &trees);
+    println!("{} of {} productions exercised by {} input(s)",
+             reachable.len() - unused.len(),
+             reachable.len(),
+             trees.len());
+    for production in unused {
+        println!("{}: never used: {} = {}",
+                 file_text.span_str(production.span),
+                 production.nonterminal,
+                 Sep(" ", &production.symbols));
+    }
+
+    Ok(())
+}
+
+/// The productions that can appear in a parse tree for `start_nt`,
+/// apart from the artificial start production itself, in a stable
+/// order.
+fn reachable_productions(grammar: &Grammar, start_nt: NonterminalString) -> Vec<&Production> {
+    let mut visited = set();
+    let mut stack = vec![start_nt];
+    let mut result = vec![];
+    while let Some(nt) = stack.pop() {
+        if !visited.insert(nt) {
+            continue;
+        }
+        for production in grammar.productions_for(nt) {
+            if nt != start_nt {
+                result.push(production);
+            }
+            for &symbol in &production.symbols {
+                if let Symbol::Nonterminal(nt) = symbol {
+                    stack.push(nt);
+                }
+            }
+        }
+    }
+    result.sort_by_key(|production| production.span);
+    result
+}
+
+/// The productions reachable from `start_nt` that do not occur in
+/// any of `trees`.
+pub fn unused_productions<'grammar>(grammar: &'grammar Grammar,
+                                    start_nt: NonterminalString,
+                                    trees: &[ParseTree])
+                                    -> Vec<&'grammar Production> {
+    let mut used = set();
+    for tree in trees {
+        mark_used(grammar, tree, &mut used);
+    }
+
+    reachable_productions(grammar, start_nt)
+        .into_iter()
+        .filter(|production| !used.contains(production))
+        .collect()
+}
+
+fn mark_used<'grammar>(grammar: &'grammar Grammar,
+                       tree: &ParseTree,
+                       used: &mut Set<&'grammar Production>) {
+    if let ParseTree::Nonterminal(nt, ref children) = *tree {
+        // the tree does not record which production was reduced, but
+        // it can be recovered from the symbols that were
+        let symbols: Vec<Symbol> =
+            children.iter()
+                    .map(|child| match *child {
+                        ParseTree::Nonterminal(nt, _) => Symbol::Nonterminal(nt),
+                        ParseTree::Terminal(t) => Symbol::Terminal(t),
+                    })
+                    .collect();
+        for production in grammar.productions_for(nt) {
+            if production.symbols == symbols {
+                used.insert(production);
+            }
+        }
+
+        for child in children {
+            mark_used(grammar, child, used);
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use grammar::repr::*;
+use intern::intern;
+use lr1::{self, Lr1Tls};
+use test_util::normalized_grammar;
+use tls::Tls;
+
+use super::super::corpus;
+use super::unused_productions;
+
+fn check_unused(grammar: &str, inputs: &[&str], expected: &[&str]) {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(grammar);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let start_nt = grammar.start_nonterminals[&NonterminalString(intern("Expr"))];
+    let states = lr1::build_states(&grammar, start_nt).unwrap();
+    let trees: Vec<_> =
+        inputs.iter()
+              .map(|input| corpus::parse(&grammar, &states, input).unwrap().0)
+              .collect();
+    let actual: Vec<String> =
+        unused_productions(&grammar, start_nt, &trees)
+            .iter()
+            .map(|production| format!("{:?}", production.symbols))
+            .collect();
+    assert_eq!(actual, expected);
+}
+
+const GRAMMAR: &'static str = r#"
+grammar;
+    pub Expr = {
+        Expr "+" Term,
+        Expr "-" Term,
+        Term,
+    };
+
+    Term = {
+        r"[0-9]+",
+        "(" Expr ")",
+    };
+
+    Unreachable = "!";
+"#;
+
+#[test]
+fn no_inputs() {
+    check_unused(GRAMMAR, &[], &[
+        r#"[Expr, "+", Term]"#,
+        r#"[Expr, "-", Term]"#,
+        r#"[Term]"#,
+        r##"[r#"[0-9]+"#]"##,
+        r#"["(", Expr, ")"]"#,
+    ]);
+}
+
+#[test]
+fn some_inputs() {
+    check_unused(GRAMMAR, &["1 + 2", "3"], &[
+        r#"[Expr, "-", Term]"#,
+        r#"["(", Expr, ")"]"#,
+    ]);
+}
+
+#[test]
+fn all_inputs() {
+    check_unused(GRAMMAR, &["1 + (2 - 3)"], &[]);
+}
@@ -0,0 +1,92 @@
+//! Emits the `derivations_Foo()` functions, which describe the grammar
+//! to `lalrpop_util::derivation` so that it can derive random
+//! sentences for property-based tests. Terminals are represented by
+//! the shortest text the built-in lexer recognizes as that terminal.
+
+use collections::Map;
+use grammar::repr::*;
+use lexer::dfa::sample;
+use rust::RustWrite;
+use std::io::{self, Write};
+
+pub fn emit_derivations<W: Write>(grammar: &Grammar, rust: &mut RustWrite<W>) -> io::Result<()> {
+    let intern_token = match grammar.intern_token {
+        Some(ref intern_token) => intern_token,
+        None => return Ok(()),
+    };
+
+    let samples = sample::shortest_matches(&intern_token.dfa, intern_token.match_entries.len());
+    let texts: Map<TerminalString, String> =
+        intern_token.match_entries
+                    .iter()
+                    .zip(samples)
+                    .filter_map(|(entry, sample)| sample.map(|s| (entry.user_name, s)))
+                    .collect();
+
+    let indices: Map<NonterminalString, usize> =
+        grammar.nonterminals
+               .keys()
+               .enumerate()
+               .map(|(index, &nt)| (nt, index))
+               .collect();
+
+    let util = format!("{}lalrpop_util::derivation", grammar.prefix);
+    let symbol_text = |symbol: &Symbol| match *symbol {
+        Symbol::Terminal(t) => {
+            texts.get(&t).map(|text| format!("{}::Symbol::Terminal({:?})", util, text))
+        }
+        Symbol::Nonterminal(nt) => {
+            Some(format!("{}::Symbol::Nonterminal({})", util, indices[&nt]))
+        }
+    };
+
+    // for each nonterminal, the symbols and cost of its productions,
+    // leaving out those with terminals that the lexer can never
+    // produce, like `!` for error recovery
+    let productions: Vec<Vec<(Vec<String>, u32)>> =
+        grammar.nonterminals
+               .values()
+               .map(|data| {
+                   data.productions
+                       .iter()
+                       .filter_map(|production| {
+                           let symbols: Option<Vec<String>> =
+                               production.symbols.iter().map(&symbol_text).collect();
+                           symbols.map(|symbols| (symbols, production.cost))
+                       })
+                       .collect()
+               })
+               .collect();
+
+    rust!(rust, "");
+    rust!(rust, "#[allow(dead_code)]");
+    rust!(rust, "static {}DERIVATION_GRAMMAR: {}::Grammar = {}::Grammar {{",
+          grammar.prefix, util, util);
+    rust!(rust, "nonterminals: &[");
+    for (&nt, productions) in grammar.nonterminals.keys().zip(&productions) {
+        rust!(rust, "({:?}, &[", nt.to_string());
+        for &(ref symbols, _) in productions {
+            rust!(rust, "&[{}],", symbols.join(", "));
+        }
+        rust!(rust, "]),");
+    }
+    rust!(rust, "],");
+    rust!(rust, "costs: &[");
+    for productions in &productions {
+        let costs: Vec<String> = productions.iter().map(|&(_, cost)| cost.to_string()).collect();
+        rust!(rust, "&[{}],", costs.join(", "));
+    }
+    rust!(rust, "],");
+    rust!(rust, "}};");
+
+    for (&user_nt, &start_nt) in &grammar.start_nonterminals {
+        rust!(rust, "");
+        rust!(rust, "#[allow(dead_code, non_snake_case)]");
+        rust!(rust, "pub fn derivations_{}() -> {}::Derivations {{", user_nt, util);
+        rust!(rust, "{}::Derivations::new(&{}DERIVATION_GRAMMAR, {})",
+              util, grammar.prefix, indices[&start_nt]);
+        rust!(rust, "}}");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,231 @@
+//! Compares two revisions of a grammar. Both are normalized first, so
+//! the comparison is between productions as LALRPOP sees them, after
+//! macros are expanded and `#[inline]` nonterminals are folded in.
+//! For each public nonterminal, we also build both automata and
+//! report the states and conflicts that only the new one has.
+//!
+//! States are identified by their kernel items (including lookahead)
+//! rather than their indices, which shift around whenever anything
+//! changes.
+
+use collections::{map, Map, Set};
+use file_text::FileText;
+use grammar::repr::*;
+use lr1::{self, Lr1Tls};
+use session::Session;
+use tls::Tls;
+use util::Sep;
+
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+use super::{load_file_text, parse_and_normalize_grammar};
+
+#[cfg(test)]
+mod test;
+
+pub fn process_files(session: Rc<Session>, old_file: &Path, new_file: &Path) -> io::Result<()> {
+    let (old_text, old_grammar) = try!(load(&session, old_file));
+    let (new_text, new_grammar) = try!(load(&session, new_file));
+
+    let diff = diff_productions(&old_grammar, &new_grammar);
+    for production in &diff.removed {
+        println!("{}: removed: {}", old_text.span_str(production.span), display(production));
+    }
+    for production in &diff.added {
+        println!("{}: added: {}", new_text.span_str(production.span), display(production));
+    }
+    for &(_, production) in &diff.changed {
+        println!("{}: action changed: {}",
+                 new_text.span_str(production.span),
+                 display(production));
+    }
+
+    let public: Set<_> = old_grammar.start_nonterminals
+                                    .keys()
+                                    .chain(new_grammar.start_nonterminals.keys())
+                                    .cloned()
+                                    .collect();
+    for user_nt in public {
+        let old = {
+            let _tls = Tls::install(session.clone(), old_text.clone());
+            automaton(&old_grammar, user_nt)
+        };
+        let new = {
+            let _tls = Tls::install(session.clone(), new_text.clone());
+            automaton(&new_grammar, user_nt)
+        };
+        match (old, new) {
+            (Some(old), Some(new)) => {
+                let new_states: Vec<_> =
+                    new.states.iter().filter(|s| !old.states.contains(s)).collect();
+                println!("`{}`: {} states before, {} after, {} of them new",
+                         user_nt,
+                         old.states.len(),
+                         new.states.len(),
+                         new_states.len());
+                for state in new_states {
+                    println!("  new state: {}", state);
+                }
+                for conflict in new.conflicts.difference(&old.conflicts) {
+                    println!("  new conflict: {}", conflict);
+                }
+                for conflict in old.conflicts.difference(&new.conflicts) {
+                    println!("  resolved conflict: {}", conflict);
+                }
+            }
+            (Some(_), None) => println!("`{}`: no longer public", user_nt),
+            (None, Some(new)) => {
+                println!("`{}`: newly public, with {} states and {} conflicts",
+                         user_nt,
+                         new.states.len(),
+                         new.conflicts.len())
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+
+    Ok(())
+}
+
+fn load(session: &Rc<Session>, lalrpop_file: &Path) -> io::Result<(Rc<FileText>, Grammar)> {
+    let file_text = Rc::new(try!(load_file_text(session, lalrpop_file)));
+    let _tls = Tls::install(session.clone(), file_text.clone());
+    let grammar = try!(parse_and_normalize_grammar(session, &file_text));
+    Ok((file_text, grammar))
+}
+
+pub struct ProductionDiff<'old, 'new> {
+    pub removed: Vec<&'old Production>,
+    pub added: Vec<&'new Production>,
+
+    /// Productions with the same symbols in both revisions, but
+    /// different action code.
+    pub changed: Vec<(&'old Production, &'new Production)>,
+}
+
+/// Matches up the productions of the two grammars by nonterminal and
+/// symbols, leaving out the artificial start productions.
+pub fn diff_productions<'old, 'new>(old: &'old Grammar,
+                                    new: &'new Grammar)
+                                    -> ProductionDiff<'old, 'new> {
+    let old_productions = productions(old);
+    let new_productions = productions(new);
+
+    let mut diff = ProductionDiff {
+        removed: vec![],
+        added: vec![],
+        changed: vec![],
+    };
+    for (key, &old_production) in &old_productions {
+        match new_productions.get(key) {
+            Some(&new_production) => {
+                if action(old, old_production) != action(new, new_production) {
+                    diff.changed.push((old_production, new_production));
+                }
+            }
+            None => diff.removed.push(old_production),
+        }
+    }
+    for (key, &new_production) in &new_productions {
+        if !old_productions.contains_key(key) {
+            diff.added.push(new_production);
+        }
+    }
+
+    diff.removed.sort_by_key(|production| production.span);
+    diff.added.sort_by_key(|production| production.span);
+    diff.changed.sort_by_key(|&(_, production)| production.span);
+    diff
+}
+
+fn productions(grammar: &Grammar) -> Map<(NonterminalString, &[Symbol]), &Production> {
+    let start_nonterminals: Set<_> = grammar.start_nonterminals.values().cloned().collect();
+    let mut result = map();
+    for (&nt, data) in &grammar.nonterminals {
+        if start_nonterminals.contains(&nt) {
+            continue;
+        }
+        for production in &data.productions {
+            result.entry((nt, &production.symbols[..])).or_insert(production);
+        }
+    }
+    result
+}
+
+/// The action code of a production, if the user wrote it (or it was
+/// generated for them), along with whether it is fallible.
+fn action<'grammar>(grammar: &'grammar Grammar,
+                    production: &Production)
+                    -> Option<(bool, &'grammar str)> {
+    let defn = &grammar.action_fn_defns[production.action.index()];
+    match defn.kind {
+        ActionFnDefnKind::User(ref user) => Some((defn.fallible, &user.code[..])),
+        ActionFnDefnKind::Inline(_) | ActionFnDefnKind::Lookaround(_) => None,
+    }
+}
+
+fn display(production: &Production) -> String {
+    format!("{} = {}", production.nonterminal, Sep(" ", &production.symbols))
+}
+
+pub struct Automaton {
+    /// The kernel items of each state, in order.
+    pub states: Vec<String>,
+
+    pub conflicts: Set<String>,
+}
+
+/// Builds the automaton for the public nonterminal `user_nt`, if the
+/// grammar has one; a grammar with conflicts still yields the states
+/// that were constructed, along with the conflicts.
+pub fn automaton(grammar: &Grammar, user_nt: NonterminalString) -> Option<Automaton> {
+    let start_nt = match grammar.start_nonterminals.get(&user_nt) {
+        Some(&start_nt) => start_nt,
+        None => return None,
+    };
+
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let (states, conflicts) = match lr1::build_states(grammar, start_nt) {
+        Ok(states) => (states, vec![]),
+        Err(error) => (error.states, error.conflicts),
+    };
+
+    let states = states.iter()
+                       .map(|state| {
+                           let kernel: Vec<_> =
+                               state.items
+                                    .vec
+                                    .iter()
+                                    .filter(|item| {
+                                        item.index > 0 || item.production.nonterminal == start_nt
+                                    })
+                                    .map(|item| format!("{:?}", item))
+                                    .collect();
+                           kernel.join("; ")
+                       })
+                       .collect();
+
+    let conflicts = conflicts.iter()
+                             .map(|conflict| match conflict.action {
+                                 lr1::Action::Shift(terminal, _) => {
+                                     format!("shift/reduce conflict on {}: reduce `{}` or shift",
+                                             terminal,
+                                             display(conflict.production))
+                                 }
+                                 lr1::Action::Reduce(other) => {
+                                     format!("reduce/reduce conflict on {:?}: reduce `{}` or \
+                                              reduce `{}`",
+                                             conflict.lookahead,
+                                             display(conflict.production),
+                                             display(other))
+                                 }
+                             })
+                             .collect();
+
+    Some(Automaton {
+        states: states,
+        conflicts: conflicts,
+    })
+}
@@ -0,0 +1,79 @@
+use intern::intern;
+use grammar::repr::*;
+use test_util::normalized_grammar;
+use tls::Tls;
+
+use super::{automaton, diff_productions, display};
+
+const OLD: &'static str = r#"
+grammar;
+    pub Expr = {
+        Expr "+" Term,
+        Expr "-" Term,
+        Term,
+    };
+
+    Term: String = {
+        r"[0-9]+" => <>.to_string(),
+        "(" <Expr> ")",
+    };
+"#;
+
+const NEW: &'static str = r#"
+grammar;
+    pub Expr = {
+        Expr "+" Term,
+        Expr "*" Term,
+        Term,
+    };
+
+    Term: String = {
+        r"[0-9]+" => "0".to_string(),
+        "(" <Expr> ")",
+    };
+"#;
+
+#[test]
+fn productions() {
+    let old = normalized_grammar(OLD);
+    let new = normalized_grammar(NEW);
+    let diff = diff_productions(&old, &new);
+
+    let removed: Vec<_> = diff.removed.iter().map(|p| display(p)).collect();
+    let added: Vec<_> = diff.added.iter().map(|p| display(p)).collect();
+    let changed: Vec<_> = diff.changed.iter().map(|&(_, p)| display(p)).collect();
+    assert_eq!(removed, vec![r#"Expr = Expr "-" Term"#]);
+    assert_eq!(added, vec![r#"Expr = Expr "*" Term"#]);
+    assert_eq!(changed, vec![r##"Term = r#"[0-9]+"#"##]);
+}
+
+#[test]
+fn identical() {
+    let old = normalized_grammar(OLD);
+    let new = normalized_grammar(OLD);
+    let diff = diff_productions(&old, &new);
+    assert!(diff.removed.is_empty());
+    assert!(diff.added.is_empty());
+    assert!(diff.changed.is_empty());
+}
+
+#[test]
+fn new_conflict() {
+    let _tls = Tls::test();
+    let expr = NonterminalString(intern("Expr"));
+
+    let old = automaton(&normalized_grammar(OLD), expr).unwrap();
+    assert!(old.conflicts.is_empty());
+
+    let new = automaton(&normalized_grammar(r#"
+grammar;
+    pub Expr = {
+        Expr "+" Expr,
+        r"[0-9]+",
+    };
+"#), expr).unwrap();
+    assert_eq!(new.conflicts.into_iter().collect::<Vec<_>>(), vec![
+        r#"shift/reduce conflict on "+": reduce `Expr = Expr "+" Expr` or shift"#
+    ]);
+    assert!(new.states.iter().any(|state| !old.states.contains(state)));
+}
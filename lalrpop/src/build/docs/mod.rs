@@ -0,0 +1,338 @@
+//! A reference for a grammar in Markdown, to be checked in with the
+//! documentation of the language that it parses: each nonterminal,
+//! with its doc comment and its alternatives in BNF -- stripped of
+//! names, action code and location markers -- and links to the
+//! nonterminals that it refers to and that refer to it.
+
+use grammar::parse_tree::*;
+use session::Session;
+use tls::Tls;
+
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+use super::{load_file_text, parse_grammar};
+
+#[cfg(test)]
+mod test;
+
+const OPERATORS_NOTE: &'static str =
+    "The operators are listed from the loosest binding to the tightest.";
+
+pub fn process_file(session: Rc<Session>, lalrpop_file: &Path) -> io::Result<()> {
+    let file_text = Rc::new(try!(load_file_text(&session, lalrpop_file)));
+    let _tls = Tls::install(session.clone(), file_text.clone());
+
+    // the grammar is documented as written, before macros are
+    // expanded, so it need not be normalized
+    let grammar = try!(parse_grammar(&file_text));
+    let title = lalrpop_file.file_name()
+                            .map(|name| name.to_string_lossy().into_owned())
+                            .unwrap_or_default();
+    print!("{}", markdown(&title, file_text.text(), &grammar));
+    Ok(())
+}
+
+/// A nonterminal, as it is documented.
+struct Rule {
+    name: NonterminalString,
+    args: Vec<NonterminalString>,
+    public: bool,
+    span: Span,
+
+    /// The symbols of each alternative, with a note to go beside it
+    /// (the associativity of an operator).
+    alternatives: Vec<(Vec<String>, Option<String>)>,
+
+    /// A note to go below the alternatives.
+    note: Option<&'static str>,
+
+    /// The nonterminals that the alternatives refer to, in order of
+    /// first appearance.
+    references: Vec<NonterminalString>,
+}
+
+/// Documents the nonterminals of `grammar`, whose source is `text`, in
+/// the order in which they are declared, under the heading `title`.
+pub fn markdown(title: &str, text: &str, grammar: &Grammar) -> String {
+    let names: Vec<NonterminalString> =
+        grammar.items
+               .iter()
+               .filter_map(|item| match *item {
+                   GrammarItem::Nonterminal(ref data) => Some(data.name),
+                   GrammarItem::Operators(ref operators) => Some(operators.name),
+                   _ => None,
+               })
+               .collect();
+    let rules: Vec<Rule> =
+        grammar.items
+               .iter()
+               .filter_map(|item| match *item {
+                   GrammarItem::Nonterminal(ref data) => Some(nonterminal_rule(data, &names)),
+                   GrammarItem::Operators(ref operators) => {
+                       Some(operators_rule(operators, &names))
+                   }
+                   _ => None,
+               })
+               .collect();
+
+    let mut out = format!("# `{}`\n", title);
+    let doc = inner_doc_comment(text);
+    if !doc.is_empty() {
+        out.push_str("\n");
+        out.push_str(&doc);
+    }
+    for rule in &rules {
+        out.push_str("\n");
+        write_rule(rule, &rules, text, &mut out);
+    }
+    out
+}
+
+fn nonterminal_rule(data: &NonterminalData, names: &[NonterminalString]) -> Rule {
+    let mut references = vec![];
+    let alternatives =
+        data.alternatives
+            .iter()
+            .map(|alternative| {
+                for symbol in &alternative.expr.symbols {
+                    find_references(symbol, &data.args, names, &mut references);
+                }
+                (alternative.expr.symbols.iter().filter_map(bnf).collect(), None)
+            })
+            .collect();
+
+    Rule {
+        name: data.name,
+        args: data.args.clone(),
+        public: data.public,
+        span: data.span,
+        alternatives: alternatives,
+        note: None,
+        references: references,
+    }
+}
+
+/// Documents a nonterminal defined by an operator table with one
+/// alternative per operator, as if each operand could be any
+/// expression, and leaves the precedence to a note.
+fn operators_rule(operators: &Operators, names: &[NonterminalString]) -> Rule {
+    let name = operators.name.to_string();
+    let mut references = vec![];
+    let mut alternatives = vec![];
+    for level in &operators.levels {
+        let associativity = level.associativity.to_string();
+        for operator in &level.operators {
+            let mut symbols = vec![];
+            for part in &operator.parts {
+                if part.is_hole() {
+                    symbols.push(name.clone());
+                } else {
+                    find_references(part, &operators.args, names, &mut references);
+                    symbols.extend(bnf(part));
+                }
+            }
+
+            // without holes, the associativity says where the operands go
+            if !operator.parts.iter().any(|part| part.is_hole()) {
+                match &associativity[..] {
+                    "prefix" => symbols.push(name.clone()),
+                    "postfix" => symbols.insert(0, name.clone()),
+                    _ => {
+                        symbols.insert(0, name.clone());
+                        symbols.push(name.clone());
+                    }
+                }
+            }
+            alternatives.push((symbols, Some(associativity.clone())));
+        }
+    }
+    find_references(&operators.atom, &operators.args, names, &mut references);
+    alternatives.push((bnf(&operators.atom).into_iter().collect(), None));
+
+    Rule {
+        name: operators.name,
+        args: operators.args.clone(),
+        public: operators.public,
+        span: operators.span,
+        alternatives: alternatives,
+        note: Some(OPERATORS_NOTE),
+        references: references,
+    }
+}
+
+fn write_rule(rule: &Rule, rules: &[Rule], text: &str, out: &mut String) {
+    let heading = if rule.args.is_empty() {
+        rule.name.to_string()
+    } else {
+        let args: Vec<String> = rule.args.iter().map(|arg| arg.to_string()).collect();
+        format!("{}<{}>", rule.name, args.join(", "))
+    };
+    out.push_str(&format!("<a name=\"{}\"></a>\n", rule.name));
+    out.push_str(&format!("## `{}`{}\n", heading, if rule.public { " (public)" } else { "" }));
+
+    let doc = doc_comment(text, rule.span.0);
+    if !doc.is_empty() {
+        out.push_str("\n");
+        out.push_str(&doc);
+    }
+
+    // the alternatives, with the notes beside them lined up
+    let alternatives: Vec<(String, &Option<String>)> =
+        rule.alternatives
+            .iter()
+            .map(|&(ref symbols, ref note)| {
+                if symbols.is_empty() {
+                    ("ε".to_string(), note)
+                } else {
+                    (symbols.join(" "), note)
+                }
+            })
+            .collect();
+    let width = alternatives.iter().map(|&(ref s, _)| s.chars().count()).max().unwrap_or(0);
+    let lhs = format!("{} ::= ", heading);
+    let bar = format!("{:width$}| ", "", width = lhs.chars().count() - 2);
+    out.push_str("\n```text\n");
+    for (index, &(ref symbols, note)) in alternatives.iter().enumerate() {
+        out.push_str(if index == 0 { &lhs } else { &bar });
+        match *note {
+            Some(ref note) => {
+                out.push_str(&format!("{:width$}  ({})\n", symbols, note, width = width))
+            }
+            None => out.push_str(&format!("{}\n", symbols)),
+        }
+    }
+    out.push_str("```\n");
+
+    if let Some(note) = rule.note {
+        out.push_str("\n");
+        out.push_str(note);
+        out.push_str("\n");
+    }
+
+    let refers_to: Vec<NonterminalString> =
+        rule.references.iter().cloned().filter(|&name| name != rule.name).collect();
+    let used_by: Vec<NonterminalString> =
+        rules.iter()
+             .filter(|other| other.name != rule.name && other.references.contains(&rule.name))
+             .map(|other| other.name)
+             .collect();
+    let mut sentences = vec![];
+    if !refers_to.is_empty() {
+        sentences.push(format!("Refers to {}.", links(&refers_to)));
+    }
+    if !used_by.is_empty() {
+        sentences.push(format!("Used by {}.", links(&used_by)));
+    }
+    if !sentences.is_empty() {
+        out.push_str("\n");
+        out.push_str(&sentences.join(" "));
+        out.push_str("\n");
+    }
+}
+
+fn links(names: &[NonterminalString]) -> String {
+    let links: Vec<String> = names.iter().map(|name| format!("[`{0}`](#{0})", name)).collect();
+    links.join(", ")
+}
+
+/// The symbol as it would be written in BNF, or `None` for location
+/// markers like `@L`, which match nothing.
+fn bnf(symbol: &Symbol) -> Option<String> {
+    match symbol.kind {
+        SymbolKind::Expr(ref expr) => {
+            let symbols: Vec<String> = expr.symbols.iter().filter_map(bnf).collect();
+            Some(format!("({})", symbols.join(" ")))
+        }
+        SymbolKind::Macro(ref m) => {
+            let args: Vec<String> = m.args.iter().filter_map(bnf).collect();
+            Some(format!("{}<{}>", m.name, args.join(", ")))
+        }
+        SymbolKind::Repeat(ref repeat) => {
+            bnf(&repeat.symbol).map(|symbol| format!("{}{}", symbol, repeat.op))
+        }
+        SymbolKind::Choose(ref symbol) |
+        SymbolKind::Name(_, ref symbol) => bnf(symbol),
+        SymbolKind::Lookahead |
+        SymbolKind::Lookbehind |
+        SymbolKind::LookaheadToken => None,
+        SymbolKind::AmbiguousId(_) |
+        SymbolKind::Variant(_) |
+        SymbolKind::Terminal(_) |
+        SymbolKind::Nonterminal(_) |
+        SymbolKind::Error => Some(symbol.to_string()),
+    }
+}
+
+/// Adds the nonterminals among `names` that `symbol` refers to, other
+/// than the arguments `args` of the macro it appears in, to
+/// `references`, unless they are there already.
+fn find_references(symbol: &Symbol,
+                   args: &[NonterminalString],
+                   names: &[NonterminalString],
+                   references: &mut Vec<NonterminalString>) {
+    let name = match symbol.kind {
+        SymbolKind::Expr(ref expr) => {
+            for symbol in &expr.symbols {
+                find_references(symbol, args, names, references);
+            }
+            return;
+        }
+        SymbolKind::Macro(ref m) => {
+            for symbol in &m.args {
+                find_references(symbol, args, names, references);
+            }
+            m.name
+        }
+        SymbolKind::Repeat(ref repeat) => {
+            return find_references(&repeat.symbol, args, names, references);
+        }
+        SymbolKind::Choose(ref symbol) |
+        SymbolKind::Name(_, ref symbol) => {
+            return find_references(symbol, args, names, references);
+        }
+        SymbolKind::AmbiguousId(id) => NonterminalString(id),
+        SymbolKind::Nonterminal(name) => name,
+        SymbolKind::Variant(_) |
+        SymbolKind::Terminal(_) |
+        SymbolKind::Lookahead |
+        SymbolKind::Lookbehind |
+        SymbolKind::LookaheadToken |
+        SymbolKind::Error => return,
+    };
+    if names.contains(&name) && !args.contains(&name) && !references.contains(&name) {
+        references.push(name);
+    }
+}
+
+/// The `//!` comment at the start of the grammar, as Markdown.
+fn inner_doc_comment(text: &str) -> String {
+    text.lines()
+        .map(|line| line.trim())
+        .skip_while(|line| line.is_empty())
+        .take_while(|line| line.starts_with("//!"))
+        .map(|line| format!("{}\n", strip_comment(line, "//!")))
+        .collect()
+}
+
+/// The `///` comment before the declaration whose name starts at
+/// `start`, skipping the annotations in between, as Markdown.
+fn doc_comment(text: &str, start: usize) -> String {
+    let line_start = text[..start].rfind('\n').map_or(0, |index| index + 1);
+    let mut lines: Vec<&str> =
+        text[..line_start].lines()
+                          .rev()
+                          .map(|line| line.trim())
+                          .take_while(|line| line.starts_with("///") || line.starts_with("#["))
+                          .filter(|line| line.starts_with("///"))
+                          .map(|line| strip_comment(line, "///"))
+                          .collect();
+    lines.reverse();
+    lines.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+fn strip_comment<'a>(line: &'a str, marker: &str) -> &'a str {
+    let rest = &line[marker.len()..];
+    if rest.starts_with(' ') { &rest[1..] } else { rest }
+}
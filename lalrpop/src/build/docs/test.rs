@@ -0,0 +1,131 @@
+use parser;
+
+use super::markdown;
+
+fn check_markdown(text: &str, expected: &str) {
+    let grammar = parser::parse_grammar(text).unwrap();
+    let actual = markdown("sum.lalrpop", text, &grammar);
+    assert_eq!(actual.trim(), expected.trim(), "\n{}", actual);
+}
+
+#[test]
+fn alternatives_docs_and_links() {
+    check_markdown(r#"
+//! The grammar of sums.
+
+grammar;
+
+/// A sum of terms.
+pub Expr = {
+    <l:Expr> "+" <r:Term> => l + r,
+    Term,
+};
+
+/// A number, or a sum in parentheses.
+#[inline]
+Term: i32 = {
+    <n:Num> => n,
+    "(" <Expr> ")",
+    @L "x"? @R => 0,
+};
+
+// not a doc comment
+Num: i32 = r"[0-9]+" => i32::from_str(<>).unwrap();
+
+Comma<T> = (<T> ",")* T?;
+
+Nothing = { => () };
+"#, r##"
+# `sum.lalrpop`
+
+The grammar of sums.
+
+<a name="Expr"></a>
+## `Expr` (public)
+
+A sum of terms.
+
+```text
+Expr ::= Expr "+" Term
+       | Term
+```
+
+Refers to [`Term`](#Term). Used by [`Term`](#Term).
+
+<a name="Term"></a>
+## `Term`
+
+A number, or a sum in parentheses.
+
+```text
+Term ::= Num
+       | "(" Expr ")"
+       | "x"?
+```
+
+Refers to [`Num`](#Num), [`Expr`](#Expr). Used by [`Expr`](#Expr).
+
+<a name="Num"></a>
+## `Num`
+
+```text
+Num ::= r#"[0-9]+"#
+```
+
+Used by [`Term`](#Term).
+
+<a name="Comma"></a>
+## `Comma<T>`
+
+```text
+Comma<T> ::= (T ",")* T?
+```
+
+<a name="Nothing"></a>
+## `Nothing`
+
+```text
+Nothing ::= ε
+```
+"##);
+}
+
+#[test]
+fn operators() {
+    check_markdown(r#"
+grammar;
+
+pub Prod = operators(Atom) {
+    left { "*" => l * r },
+    prefix { "-" => -e },
+    right { _ "?" Atom ":" _ => 0 },
+};
+
+Atom = r"[0-9]+";
+"#, r##"
+# `sum.lalrpop`
+
+<a name="Prod"></a>
+## `Prod` (public)
+
+```text
+Prod ::= Prod "*" Prod           (left)
+       | "-" Prod                (prefix)
+       | Prod "?" Atom ":" Prod  (right)
+       | Atom
+```
+
+The operators are listed from the loosest binding to the tightest.
+
+Refers to [`Atom`](#Atom).
+
+<a name="Atom"></a>
+## `Atom`
+
+```text
+Atom ::= r#"[0-9]+"#
+```
+
+Used by [`Prod`](#Prod).
+"##);
+}
@@ -0,0 +1,181 @@
+//! Explains how a grammar parses a single input, for when it accepts
+//! something that its author did not expect: prints the derivation
+//! tree, with the production used at each node, and optionally the
+//! moves that the LR(1) parser made to get there.
+
+use grammar::repr::*;
+use intern;
+use lr1::{self, Lr1Tls, StateIndex, Token};
+use lr1::interpret::{self, ParseTree, Step};
+use session::Session;
+use tls::Tls;
+
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+use super::{corpus, load_file_text, parse_and_normalize_grammar};
+
+#[cfg(test)]
+mod test;
+
+pub fn process_file(session: Rc<Session>,
+                    lalrpop_file: &Path,
+                    input: &str,
+                    start: Option<&str>,
+                    names: bool,
+                    show_steps: bool)
+                    -> io::Result<()> {
+    let file_text = Rc::new(try!(load_file_text(&session, lalrpop_file)));
+    let _tls = Tls::install(session.clone(), file_text.clone());
+
+    // with the names of the terminals given, the grammar may use any
+    // lexer
+    let grammar = if names {
+        try!(parse_and_normalize_grammar(&session, &file_text))
+    } else {
+        try!(corpus::load_grammar(&session, &file_text, "explain inputs to"))
+    };
+    let start_nt = try!(corpus::start_nonterminal(&grammar, start));
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let states = corpus::build_states(&grammar, start_nt);
+
+    match explain(&grammar, &states, input, names, show_steps) {
+        Ok(explanation) => {
+            print!("{}", explanation);
+            Ok(())
+        }
+        Err(explanation) => {
+            print!("{}", explanation);
+            Err(corpus::other_error("the input does not parse".to_string()))
+        }
+    }
+}
+
+/// Parses `input` (see `corpus::lex` for `names`), and describes the
+/// moves of the parser, if `show_steps` is set, and then the
+/// derivation tree. If the input does not parse, the description ends
+/// with the error instead, and is returned as an `Err`.
+pub fn explain(grammar: &Grammar,
+               states: &[lr1::LR1State],
+               input: &str,
+               names: bool,
+               show_steps: bool)
+               -> Result<String, String> {
+    let tokens = try!(corpus::lex(grammar, input, names).map_err(|(offset, message)| {
+        format!("error: {} at offset {}\n", message, offset)
+    }));
+    let terminals = tokens.iter().map(|&(terminal, _)| terminal).collect();
+    let (result, steps) = interpret::interpret_steps(states, terminals);
+
+    let mut out = String::new();
+    if show_steps {
+        let mut lexemes = tokens.iter().map(|&(_, lexeme)| lexeme);
+        for step in steps {
+            out.push_str(&step_text(step, &mut lexemes));
+            out.push_str("\n");
+        }
+    }
+
+    match result {
+        Ok(ParseTree::Nonterminal(_, children)) => {
+            // skip the artificial start production
+            assert_eq!(children.len(), 1);
+            if show_steps {
+                out.push_str("\n");
+            }
+            let mut lexemes = tokens.iter().map(|&(_, lexeme)| lexeme);
+            write_derivation(&children[0], &mut lexemes, 0, &mut out);
+            Ok(out)
+        }
+        Ok(ParseTree::Terminal(_)) => unreachable!(),
+        Err((state, token)) => {
+            out.push_str(&error_text(state.index, token));
+            out.push_str("\n");
+            Err(out)
+        }
+    }
+}
+
+/// Describes a move of the parser, taking the text of the token from
+/// `lexemes` for a shift.
+pub fn step_text<'input, I>(step: Step, lexemes: &mut I) -> String
+    where I: Iterator<Item = &'input str>
+{
+    match step {
+        Step::Shift(from, terminal, to) => {
+            format!("{:?}: shift {}, go to {:?}",
+                    from,
+                    lexeme_text(terminal, lexemes.next().unwrap()),
+                    to)
+        }
+        Step::Reduce(from, production, to) => {
+            let production = production_text(production.nonterminal, &production.symbols);
+            match to {
+                Some(to) => format!("{:?}: reduce {}, go to {:?}", from, production, to),
+                None => format!("{:?}: reduce {}, accept", from, production),
+            }
+        }
+    }
+}
+
+/// Describes the parser giving up in `state` at `token`.
+pub fn error_text(state: StateIndex, token: Token) -> String {
+    let token = match token {
+        Token::Terminal(terminal) => format!("unexpected token {}", terminal),
+        Token::EOF => "unexpected end of input".to_string(),
+        Token::Error => unreachable!(),
+    };
+    format!("{:?}: error: {}", state, token)
+}
+
+fn write_derivation<'input, I>(tree: &ParseTree, lexemes: &mut I, depth: usize, out: &mut String)
+    where I: Iterator<Item = &'input str>
+{
+    for _ in 0..depth {
+        out.push_str("  ");
+    }
+
+    match *tree {
+        ParseTree::Nonterminal(nt, ref children) => {
+            // the tree does not record which production was reduced,
+            // but it can be recovered from the symbols that were
+            let symbols: Vec<Symbol> =
+                children.iter()
+                        .map(|child| match *child {
+                            ParseTree::Nonterminal(nt, _) => Symbol::Nonterminal(nt),
+                            ParseTree::Terminal(terminal) => Symbol::Terminal(terminal),
+                        })
+                        .collect();
+            out.push_str(&production_text(nt, &symbols));
+            out.push_str("\n");
+            for child in children {
+                write_derivation(child, lexemes, depth + 1, out);
+            }
+        }
+        ParseTree::Terminal(terminal) => {
+            out.push_str(&lexeme_text(terminal, lexemes.next().unwrap()));
+            out.push_str("\n");
+        }
+    }
+}
+
+pub fn production_text(nt: NonterminalString, symbols: &[Symbol]) -> String {
+    if symbols.is_empty() {
+        format!("{} = (empty)", nt)
+    } else {
+        let symbols: Vec<String> = symbols.iter().map(|s| s.to_string()).collect();
+        format!("{} = {}", nt, symbols.join(" "))
+    }
+}
+
+/// A terminal and the text it matched, unless that is just the
+/// literal itself.
+pub fn lexeme_text(terminal: TerminalString, lexeme: &str) -> String {
+    match terminal {
+        TerminalString::Literal(TerminalLiteral::Quoted(s))
+            if intern::read(|interner| interner.data(s) == lexeme) => terminal.to_string(),
+        _ if terminal.to_string() == lexeme => terminal.to_string(),
+        _ => format!("{} {:?}", terminal, lexeme),
+    }
+}
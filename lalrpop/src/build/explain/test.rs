@@ -0,0 +1,84 @@
+use grammar::repr::*;
+use intern::intern;
+use lr1::{self, Lr1Tls};
+use test_util::normalized_grammar;
+use tls::Tls;
+
+use super::explain;
+
+fn check_explain(grammar: &str, input: &str, names: bool, show_steps: bool, expected: &str) {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(grammar);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let start_nt = grammar.start_nonterminals[&NonterminalString(intern("Expr"))];
+    let states = lr1::build_states(&grammar, start_nt).unwrap();
+    let actual = match explain(&grammar, &states, input, names, show_steps) {
+        Ok(actual) => actual,
+        Err(actual) => actual,
+    };
+    assert_eq!(actual.trim(), expected.trim(), "\n{}", actual);
+}
+
+const GRAMMAR: &'static str = r#"
+grammar;
+    pub Expr = {
+        Expr "+" Term,
+        Term,
+    };
+
+    Term = {
+        r"[0-9]+",
+        "(" Expr ")",
+    };
+"#;
+
+#[test]
+fn derivation() {
+    check_explain(GRAMMAR, "1 + (22)", false, false, r##"
+Expr = Expr "+" Term
+  Expr = Term
+    Term = r#"[0-9]+"#
+      r#"[0-9]+"# "1"
+  "+"
+  Term = "(" Expr ")"
+    "("
+    Expr = Term
+      Term = r#"[0-9]+"#
+        r#"[0-9]+"# "22"
+    ")"
+"##);
+}
+
+#[test]
+fn derivation_from_names() {
+    check_explain(GRAMMAR, r##""(" r#"[0-9]+"# ")""##, true, false, r##"
+Expr = Term
+  Term = "(" Expr ")"
+    "("
+    Expr = Term
+      Term = r#"[0-9]+"#
+        r#"[0-9]+"#
+    ")"
+"##);
+}
+
+#[test]
+fn unknown_name() {
+    check_explain(GRAMMAR, r#""(" Num ")""#, true, false, r#"
+error: no terminal named Num at offset 4
+"#);
+}
+
+#[test]
+fn steps_to_error() {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(GRAMMAR);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let start_nt = grammar.start_nonterminals[&NonterminalString(intern("Expr"))];
+    let states = lr1::build_states(&grammar, start_nt).unwrap();
+    let explanation = explain(&grammar, &states, "1 + )", false, true).unwrap_err();
+    let lines: Vec<&str> = explanation.lines().collect();
+    assert!(lines[0].starts_with("S0: shift r#\"[0-9]+\"# \"1\", go to S"), "{}", explanation);
+    assert!(lines.iter().any(|line| line.contains("reduce Term = r#\"[0-9]+\"#, go to S")));
+    assert!(lines.last().unwrap().ends_with(": error: unexpected token \")\""));
+}
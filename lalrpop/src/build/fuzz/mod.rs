@@ -0,0 +1,96 @@
+//! Generates a `cargo fuzz` target for a grammar. The target feeds
+//! arbitrary input to the `parse_Foo` function of every public
+//! nonterminal, so any panic -- in the generated parser or in the
+//! user's action code -- gets reported as a crash. Alongside it we
+//! write a libFuzzer dictionary with the grammar's fixed strings,
+//! which lets the fuzzer assemble inputs that make it past the lexer.
+
+use grammar::repr::*;
+use intern;
+use rust::RustWrite;
+use session::Session;
+use tls::Tls;
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use super::{corpus, load_file_text};
+
+#[cfg(test)]
+mod test;
+
+pub fn process_file(session: Rc<Session>,
+                    lalrpop_file: &Path,
+                    module: &str,
+                    fuzz_dir: &Path)
+                    -> io::Result<()> {
+    let file_text = Rc::new(try!(load_file_text(&session, lalrpop_file)));
+    let _tls = Tls::install(session.clone(), file_text.clone());
+
+    let grammar = try!(corpus::load_grammar(&session, &file_text, "generate a fuzz target for"));
+    if !grammar.type_parameters.is_empty() || !grammar.parameters.is_empty() {
+        return Err(corpus::other_error(format!("cannot generate a fuzz target for `{}`: \
+                                                the grammar has parameters",
+                                               lalrpop_file.display())));
+    }
+
+    let name = lalrpop_file.file_stem().unwrap().to_string_lossy();
+    let targets_dir = fuzz_dir.join("fuzz_targets");
+    try!(fs::create_dir_all(&targets_dir));
+
+    let target_file = targets_dir.join(format!("{}.rs", name));
+    log!(session, Informative, "writing fuzz target `{}`", target_file.display());
+    let target = try!(emit_fuzz_target(&grammar, module));
+    try!(try!(fs::File::create(&target_file)).write_all(&target));
+
+    let dict_file = fuzz_dir.join(format!("{}.dict", name));
+    log!(session, Informative, "writing fuzzing dictionary `{}`", dict_file.display());
+    try!(try!(fs::File::create(&dict_file)).write_all(dictionary(&grammar).as_bytes()));
+
+    Ok(())
+}
+
+/// The fuzz target, where `module` is the path of the generated
+/// parser, starting with the name of the crate.
+pub fn emit_fuzz_target(grammar: &Grammar, module: &str) -> io::Result<Vec<u8>> {
+    let krate = module.split("::").next().unwrap();
+
+    let mut rust = RustWrite::new(vec![]);
+    rust!(rust, "#![no_main]");
+    rust!(rust, "#[macro_use]");
+    rust!(rust, "extern crate libfuzzer_sys;");
+    rust!(rust, "extern crate {};", krate);
+    rust!(rust, "");
+    rust!(rust, "fuzz_target!(|data: &[u8]| {{");
+    rust!(rust, "if let Ok(input) = ::std::str::from_utf8(data) {{");
+    for &nt in grammar.start_nonterminals.keys() {
+        rust!(rust, "let _ = {}::parse_{}(input);", module, nt);
+    }
+    rust!(rust, "}}");
+    rust!(rust, "}});");
+    Ok(rust.into_inner())
+}
+
+/// A dictionary in the format used by libFuzzer and AFL, with one
+/// entry for each string literal in the grammar.
+pub fn dictionary(grammar: &Grammar) -> String {
+    let mut out = String::new();
+    for entry in &grammar.intern_token.as_ref().unwrap().match_entries {
+        if let TerminalLiteral::Quoted(s) = entry.match_literal {
+            out.push('"');
+            intern::read(|interner| {
+                for &byte in interner.data(s).as_bytes() {
+                    match byte {
+                        b'"' | b'\\' => out.push_str(&format!("\\{}", byte as char)),
+                        _ if b' ' <= byte && byte <= b'~' => out.push(byte as char),
+                        _ => out.push_str(&format!("\\x{:02X}", byte)),
+                    }
+                }
+            });
+            out.push_str("\"\n");
+        }
+    }
+    out
+}
@@ -0,0 +1,42 @@
+use test_util::normalized_grammar;
+
+use super::{dictionary, emit_fuzz_target};
+
+const GRAMMAR: &'static str = r#"
+grammar;
+    pub Expr = {
+        Expr "+" Term,
+        Term,
+    };
+
+    pub Term = {
+        r"[0-9]+",
+        "(" Expr ")",
+    };
+"#;
+
+#[test]
+fn fuzz_target() {
+    let grammar = normalized_grammar(GRAMMAR);
+    let target = emit_fuzz_target(&grammar, "calculator::parser").unwrap();
+    assert_eq!(String::from_utf8(target).unwrap(), r#"#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate calculator;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(input) = ::std::str::from_utf8(data) {
+        let _ = calculator::parser::parse_Expr(input);
+        let _ = calculator::parser::parse_Term(input);
+    }
+});
+"#);
+}
+
+#[test]
+fn fuzz_dictionary() {
+    let grammar = normalized_grammar(GRAMMAR);
+    let mut entries: Vec<_> = dictionary(&grammar).lines().map(String::from).collect();
+    entries.sort();
+    assert_eq!(entries, vec![r#""(""#, r#"")""#, r#""+""#]);
+}
@@ -0,0 +1,188 @@
+//! Complexity metrics for grammars. For each nonterminal, we report
+//! how many productions it has and how long they are once macros are
+//! expanded and `#[inline]` nonterminals are folded in, how deeply
+//! other nonterminals are nested beneath it, and how many LR states
+//! it takes part in. The last is usually the interesting one: it
+//! points at the rules that blow up the automaton. We also report how
+//! much of the grammar the parser for each public nonterminal needs.
+
+use collections::{map, Map, Set};
+use grammar::repr::*;
+use lr1::{self, LR1State, Lr1Tls};
+use session::Session;
+use tls::Tls;
+use util::Prefix;
+
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+use super::{load_file_text, parse_and_normalize_grammar};
+
+#[cfg(test)]
+mod test;
+
+pub fn process_file(session: Rc<Session>, lalrpop_file: &Path) -> io::Result<()> {
+    let file_text = Rc::new(try!(load_file_text(&session, lalrpop_file)));
+    let _tls = Tls::install(session.clone(), file_text.clone());
+
+    let grammar = try!(parse_and_normalize_grammar(&session, &file_text));
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+
+    // each public nonterminal gets its own automaton; a grammar with
+    // conflicts still has states worth counting, so we keep those too
+    let mut states = vec![];
+    let mut reaches = vec![];
+    for (&user_nt, &start_nt) in &grammar.start_nonterminals {
+        let start_states = match lr1::build_states(&grammar, start_nt) {
+            Ok(s) => s,
+            Err(error) => error.states,
+        };
+        reaches.push((user_nt, reachable_productions(&grammar, start_nt), start_states.len()));
+        states.extend(start_states);
+    }
+
+    let rows = metrics(&grammar, &states);
+    let width = rows.iter()
+                    .map(|row| row.nonterminal.len())
+                    .chain(Some("Nonterminal".len()))
+                    .max()
+                    .unwrap();
+    println!("{} states in total", states.len());
+    println!("{:width$}  Productions  Symbols  Depth  Recursive  States",
+             "Nonterminal",
+             width = width);
+    for row in rows {
+        println!("{:width$}  {:>11}  {:>7}  {:>5}  {:>9}  {:>6}",
+                 row.nonterminal.to_string(),
+                 row.productions,
+                 row.symbols,
+                 row.depth,
+                 if row.recursive { "yes" } else { "no" },
+                 row.states,
+                 width = width);
+    }
+
+    let total = grammar.nonterminals
+                       .iter()
+                       .filter(|&(nt, _)| !grammar.start_nonterminals.values().any(|s| s == nt))
+                       .map(|(_, data)| data.productions.len())
+                       .sum::<usize>();
+    println!("");
+    println!("Reachable from each public nonterminal:");
+    for (user_nt, productions, state_count) in reaches {
+        println!("{}: {} of {} productions, {} states",
+                 user_nt,
+                 productions.len(),
+                 total,
+                 state_count);
+        for production in productions {
+            log!(session,
+                 Verbose,
+                 "    {} ={}",
+                 production.nonterminal,
+                 Prefix(" ", &production.symbols[..]));
+        }
+    }
+
+    Ok(())
+}
+
+/// The productions that the parser for the public nonterminal whose
+/// artificial start nonterminal is `start_nt` can use, other than the
+/// artificial start production itself. The rest are dead weight in its
+/// tables, which `Configuration::prune_tables` leaves out.
+pub fn reachable_productions(grammar: &Grammar, start_nt: NonterminalString) -> Vec<&Production> {
+    grammar.reachable_from(start_nt)
+           .into_iter()
+           .filter(|&nt| nt != start_nt)
+           .flat_map(|nt| grammar.productions_for(nt))
+           .collect()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Metrics {
+    pub nonterminal: NonterminalString,
+
+    /// Number of productions, after macro expansion and inlining.
+    pub productions: usize,
+
+    /// Total number of symbols across those productions.
+    pub symbols: usize,
+
+    /// Length of the longest chain of nonterminals that this one
+    /// refers to, counting itself, where nonterminals that are
+    /// mutually recursive count as one.
+    pub depth: usize,
+
+    /// Whether the nonterminal can refer back to itself.
+    pub recursive: bool,
+
+    /// Number of states with an item that is partway through one of
+    /// the nonterminal's productions.
+    pub states: usize,
+}
+
+/// Metrics for each nonterminal other than the artificial start
+/// nonterminals, with those that take part in the most states first.
+pub fn metrics(grammar: &Grammar, states: &[LR1State]) -> Vec<Metrics> {
+    let reachable: Map<NonterminalString, Set<NonterminalString>> =
+        grammar.nonterminals
+               .keys()
+               .map(|&nt| (nt, grammar.reachable_from(nt)))
+               .collect();
+
+    let mut state_counts: Map<NonterminalString, usize> = map();
+    for state in states {
+        let nonterminals: Set<NonterminalString> =
+            state.items
+                 .vec
+                 .iter()
+                 .filter(|item| item.index > 0)
+                 .map(|item| item.production.nonterminal)
+                 .collect();
+        for nt in nonterminals {
+            *state_counts.entry(nt).or_insert(0) += 1;
+        }
+    }
+
+    let start_nonterminals: Set<_> = grammar.start_nonterminals.values().cloned().collect();
+    let mut depths = map();
+    let mut rows: Vec<_> =
+        grammar.nonterminals
+               .iter()
+               .filter(|&(nt, _)| !start_nonterminals.contains(nt))
+               .map(|(&nt, data)| {
+                   Metrics {
+                       nonterminal: nt,
+                       productions: data.productions.len(),
+                       symbols: data.productions.iter().map(|p| p.symbols.len()).sum(),
+                       depth: depth(&reachable, nt, &mut depths),
+                       recursive: reachable[&nt].contains(&nt),
+                       states: state_counts.get(&nt).cloned().unwrap_or(0),
+                   }
+               })
+               .collect();
+    rows.sort_by(|a, b| (b.states, a.nonterminal).cmp(&(a.states, b.nonterminal)));
+    rows
+}
+
+fn depth(reachable: &Map<NonterminalString, Set<NonterminalString>>,
+         nt: NonterminalString,
+         depths: &mut Map<NonterminalString, usize>)
+         -> usize {
+    if let Some(&depth) = depths.get(&nt) {
+        return depth;
+    }
+
+    // only descend into nonterminals that cannot refer back to `nt`,
+    // so that each recursive cycle is counted once
+    let below = reachable[&nt]
+        .iter()
+        .filter(|&referent| !reachable[referent].contains(&nt))
+        .map(|&referent| depth(reachable, referent, depths))
+        .max()
+        .unwrap_or(0);
+    depths.insert(nt, below + 1);
+    below + 1
+}
@@ -0,0 +1,76 @@
+use lr1::{self, Lr1Tls};
+use test_util::normalized_grammar;
+use tls::Tls;
+
+use super::{metrics, reachable_productions};
+
+const GRAMMAR: &'static str = r#"
+grammar;
+    pub S = Expr;
+    Expr = { Expr "+" Term, Term };
+    Term = { Num, "(" Expr ")" };
+    Num = r"[0-9]+";
+    Unused = "y";
+"#;
+
+#[test]
+fn nonterminal_metrics() {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(GRAMMAR);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let start_nt = *grammar.start_nonterminals.values().next().unwrap();
+    let states = lr1::build_states(&grammar, start_nt).unwrap();
+
+    let rows = metrics(&grammar, &states);
+    let mut actual: Vec<_> =
+        rows.iter()
+            .map(|row| {
+                (row.nonterminal.to_string(), row.productions, row.symbols, row.depth,
+                 row.recursive)
+            })
+            .collect();
+    actual.sort();
+    assert_eq!(actual, vec![
+        ("Expr".to_string(), 2, 4, 2, true),
+        ("Num".to_string(), 1, 1, 1, false),
+        ("S".to_string(), 1, 1, 3, false),
+        ("Term".to_string(), 2, 4, 2, true),
+        ("Unused".to_string(), 1, 1, 1, false),
+    ]);
+
+    // `S = Expr` is only ever completed right after the start state,
+    // and `Unused` is not reachable at all
+    let states_of = |name: &str| {
+        rows.iter().find(|row| row.nonterminal.to_string() == name).unwrap().states
+    };
+    assert_eq!(states_of("S"), 1);
+    assert_eq!(states_of("Unused"), 0);
+    assert!(states_of("Expr") > states_of("S"));
+
+    // the rows come with those in the most states first
+    assert!(rows.windows(2).all(|pair| pair[0].states >= pair[1].states));
+}
+
+#[test]
+fn productions_reachable_from_each_start() {
+    let grammar = normalized_grammar(r#"
+grammar;
+    pub S = Expr;
+    pub T = Term;
+    Expr = { Expr "+" Term, Term };
+    Term = { Num, "(" ")" };
+    Num = r"[0-9]+";
+"#);
+    let reachable = |name: &str| -> Vec<String> {
+        let (_, &start_nt) = grammar.start_nonterminals
+                                    .iter()
+                                    .find(|&(nt, _)| nt.to_string() == name)
+                                    .unwrap();
+        reachable_productions(&grammar, start_nt)
+            .iter()
+            .map(|production| production.nonterminal.to_string())
+            .collect()
+    };
+    assert_eq!(reachable("S"), vec!["Expr", "Expr", "Num", "S", "Term", "Term"]);
+    assert_eq!(reachable("T"), vec!["Num", "T", "Term", "Term"]);
+}
@@ -1,48 +1,369 @@
 //! Utilies for running in a build script.
 
+use api::{builder, GrammarBuilder};
 use atty;
+use collections::{set, Set};
+use backend::{Backend, BackendInput, OutputFile, RustBackend};
+use diagnostics::{Diagnostic, Diagnostics, Severity};
 use file_text::FileText;
 use grammar::parse_tree as pt;
 use grammar::repr as r;
 use lalrpop_util::ParseError;
 use lexer::intern_token;
+use lint;
 use lr1;
+use log::Level;
+use lr1::codegen::parse_table::TableFiles;
 use message::{Content, Message};
+use message::catalog::{self, CatalogEntry};
 use message::builder::InlineBuilder;
 use normalize;
 use parser;
 use rust::RustWrite;
-use session::{ColorConfig, Session};
-use term;
+use session::{ColorConfig, ErrorFormat, Session, Visibility};
+use term::{self, Terminal};
 use tls::Tls;
 use tok;
+use util::{Escape, Sep};
 
+use std::cell::RefCell;
+use std::fmt::Display;
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::exit;
 use std::rc::Rc;
 
+mod abnf;
 mod action;
+mod ambiguity;
+mod antlr;
+mod corpus;
+mod coverage;
+mod derivations;
+mod diff;
+mod docs;
+mod explain;
 mod fake_term;
+mod fuzz;
+mod metrics;
+mod normalized;
+mod passes;
+mod sample;
+mod simulate;
+mod snapshot;
+mod tokens;
 
 use self::fake_term::FakeTerminal;
 
+pub use self::abnf::process_file as import_abnf;
+pub use self::ambiguity::process_file as check_ambiguity;
+pub use self::antlr::process_file as export_antlr;
+pub use self::coverage::process_corpus as measure_coverage;
+pub use self::diff::process_files as diff_grammars;
+pub use self::docs::process_file as document_grammar;
+pub use self::explain::process_file as explain_input;
+pub use self::fuzz::process_file as generate_fuzz_target;
+pub use self::metrics::process_file as report_metrics;
+pub use self::normalized::{automata, normalized_grammar, to_ir};
+pub use self::normalized::process_file as print_normalized_grammar;
+pub use self::sample::process_files as parse_samples;
+pub use self::simulate::process_file as simulate_input;
+pub use self::snapshot::process_corpus as snapshot_corpus;
+pub use self::tokens::process_file as list_tokens;
+
 pub fn process_dir<P: AsRef<Path>>(session: Rc<Session>, root_dir: P) -> io::Result<()> {
+    try!(write_support_module(&session));
     let lalrpop_files = try!(lalrpop_files(root_dir));
+    let imported = imported_files(&lalrpop_files);
     for lalrpop_file in lalrpop_files {
+        // a file that another grammar imports is part of that grammar
+        if imported.contains(&canonical_path(&lalrpop_file)) {
+            continue;
+        }
         try!(process_file(session.clone(), lalrpop_file));
     }
     Ok(())
 }
 
+/// The files that the grammars among `lalrpop_files` import with
+/// `use "file.lalrpop";`, directly or through the files they import,
+/// other than the grammars themselves, which may be imported back.
+/// Files that cannot be read are skipped; processing them will report
+/// the problem.
+fn imported_files(lalrpop_files: &[PathBuf]) -> Set<PathBuf> {
+    let mut grammars = set();
+    let mut stack = vec![];
+    for lalrpop_file in lalrpop_files {
+        if let Ok(file_text) = FileText::from_path(lalrpop_file.clone()) {
+            let declares_grammar = tok::Tokenizer::new(file_text.text(), 0)
+                .take_while(|token| token.is_ok())
+                .any(|token| token.unwrap().1 == tok::Tok::Grammar);
+            if declares_grammar {
+                grammars.insert(canonical_path(lalrpop_file));
+                stack.extend(imports_of(lalrpop_file, &file_text));
+            }
+        }
+    }
+
+    let mut imported = set();
+    while let Some(path) = stack.pop() {
+        if !grammars.contains(&path) && imported.insert(path.clone()) {
+            if let Ok(file_text) = FileText::from_path(path.clone()) {
+                stack.extend(imports_of(&path, &file_text));
+            }
+        }
+    }
+    imported
+}
+
+fn imports_of(lalrpop_file: &Path, file_text: &FileText) -> Vec<PathBuf> {
+    let dir = lalrpop_file.parent().unwrap_or(Path::new(""));
+    find_imports(file_text.text(), 0)
+        .into_iter()
+        .map(|(_, import)| canonical_path(&dir.join(import)))
+        .collect()
+}
+
 pub fn process_file<P: AsRef<Path>>(session: Rc<Session>, lalrpop_file: P) -> io::Result<()> {
+    try!(write_support_module(&session));
     let lalrpop_file = lalrpop_file.as_ref();
     let rs_file = try!(resolve_rs_file(&session, lalrpop_file));
     let report_file = try!(resolve_report_file(&session, lalrpop_file));
     process_file_into(session, lalrpop_file, &rs_file, &report_file)
 }
 
+/// Like `process_file`, but writes the generated parser where the
+/// module `module_path` (e.g., `parser::calc`) of a crate whose root
+/// is the output directory would be found (e.g., `parser/calc.rs`),
+/// rather than next to the `.lalrpop` file.
+pub fn process_file_as<P: AsRef<Path>>(session: Rc<Session>,
+                                       lalrpop_file: P,
+                                       module_path: &str)
+                                       -> io::Result<()> {
+    try!(write_support_module(&session));
+    let lalrpop_file = lalrpop_file.as_ref();
+    let rs_file = resolve_module_file(&session, module_path, "rs");
+    let report_file = resolve_module_file(&session, module_path, "report");
+    process_file_into(session, lalrpop_file, &rs_file, &report_file)
+}
+
+/// Generates a parser for a grammar built in code, writing it where
+/// the module `module_path` would be found, as `process_file_as`
+/// does. Errors point into the text of the grammar, which has no file
+/// of its own; it is given the path of the parser, with a `.lalrpop`
+/// extension.
+pub fn process_grammar(session: Rc<Session>,
+                       grammar: &GrammarBuilder,
+                       module_path: &str)
+                       -> io::Result<()> {
+    try!(write_support_module(&session));
+    let rs_file = resolve_module_file(&session, module_path, "rs");
+    let report_file = resolve_module_file(&session, module_path, "report");
+    log!(session,
+         Informative,
+         "processing grammar for `{}`",
+         module_path);
+    if let Some(parent) = rs_file.parent() {
+        try!(fs::create_dir_all(parent));
+    }
+    try!(make_read_only(&rs_file, false));
+    try!(remove_old_file(&rs_file));
+
+    let (file_text, grammar) = builder::to_parse_tree(grammar, rs_file.with_extension("lalrpop"));
+    let file_text = Rc::new(file_text);
+    let _tls = Tls::install(session.clone(), file_text.clone());
+    {
+        let grammar = match grammar {
+            Ok(grammar) => grammar,
+            Err((span, message)) => {
+                return Err(report_error(&file_text,
+                                        &catalog::INVALID_GRAMMAR,
+                                        span,
+                                        &[&message]));
+            }
+        };
+        let grammar = try!(normalize_grammar(&session, &file_text, grammar));
+        try!(run_backend(&session, &grammar, &rs_file, &report_file));
+    }
+
+    make_read_only(&rs_file, true)
+}
+
+/// Runs everything that `process_file` does on the grammar `text`,
+/// which has no file of its own (it is called `grammar.lalrpop` in
+/// diagnostics), and returns the generated code rather than writing
+/// it. Nothing is read from or written to disk, so the reports,
+/// pictures and table files that the session may ask for are left
+/// out, and the grammar cannot import other files. The errors and
+/// warnings still go to the session's `Diagnostics`, if it has one,
+/// and are returned along with any error.
+pub fn generate(session: &Session, text: &str) -> Result<String, Vec<Diagnostic>> {
+    let collected = Rc::new(CollectedDiagnostics {
+        inner: session.diagnostics.clone(),
+        diagnostics: RefCell::new(vec![]),
+    });
+    let mut session = session.clone();
+    session.diagnostics = Some(collected.clone());
+    session.emit_report = false;
+    session.emit_dot = false;
+    session.html_report_dir = None;
+    session.conflict_svg_dir = None;
+    session.binary_tables = false;
+    let session = Rc::new(session);
+
+    let path = PathBuf::from("grammar.lalrpop");
+    match generate_code(&session, FileText::new(path.clone(), text.to_string())) {
+        Ok(buffer) => Ok(String::from_utf8(buffer).unwrap()),
+        Err(error) => {
+            let mut diagnostics = collected.diagnostics.borrow().clone();
+            // some errors, such as a grammar with no public
+            // nonterminals, are not about any part of it
+            if !diagnostics.iter().any(|d| d.severity == Severity::Error) {
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Error,
+                    code: None,
+                    file: path,
+                    span: (0, 0),
+                    text: error.to_string(),
+                });
+            }
+            Err(diagnostics)
+        }
+    }
+}
+
+fn generate_code(session: &Rc<Session>, file_text: FileText) -> io::Result<Vec<u8>> {
+    let file_text = Rc::new(try!(substitute_variables(session, file_text)));
+    let _tls = Tls::install(session.clone(), file_text.clone());
+    let grammar = try!(parse_grammar(&file_text));
+    for item in &grammar.items {
+        if let pt::GrammarItem::Import(ref import) = *item {
+            return Err(report_error(&file_text,
+                                    &catalog::IMPORT_WITHOUT_FILE,
+                                    import.span,
+                                    &[&import.path]));
+        }
+    }
+    let grammar = try!(normalize_grammar(session, &file_text, grammar));
+    let mut tables = TableFiles::new(Path::new(""));
+    emit_recursive_ascent(session, &grammar, Path::new(""), &mut tables)
+}
+
+/// The `Diagnostics` of `generate`, which keeps everything that is
+/// reported and passes it on to those of the caller, if any.
+struct CollectedDiagnostics {
+    inner: Option<Rc<Diagnostics>>,
+    diagnostics: RefCell<Vec<Diagnostic>>,
+}
+
+impl Diagnostics for CollectedDiagnostics {
+    fn report(&self, diagnostic: Diagnostic) {
+        if let Some(ref inner) = self.inner {
+            inner.report(diagnostic.clone());
+        }
+        self.diagnostics.borrow_mut().push(diagnostic);
+    }
+
+    fn log(&self, level: Level, message: &str) {
+        if let Some(ref inner) = self.inner {
+            inner.log(level, message);
+        }
+    }
+}
+
+/// Generates the files for `grammar` with the session's backend, or
+/// with `RustBackend` if it has none, and writes them next to
+/// `rs_file`.
+fn run_backend(session: &Session,
+               grammar: &r::Grammar,
+               rs_file: &Path,
+               report_file: &Path)
+               -> io::Result<()> {
+    let input = BackendInput::new(session, grammar, rs_file, report_file);
+    let files = match session.backend {
+        Some(ref backend) => try!(backend.generate(&input)),
+        None => try!(RustBackend.generate(&input)),
+    };
+    let dir = rs_file.parent().unwrap_or(Path::new(""));
+    for file in files {
+        let path = dir.join(&file.path);
+        if let Some(parent) = path.parent() {
+            try!(fs::create_dir_all(parent));
+        }
+        let mut output_file = try!(fs::File::create(&path));
+        try!(output_file.write_all(&file.contents));
+    }
+    Ok(())
+}
+
+/// The files of `RustBackend`: the `.rs` file of the parsers, named
+/// after `rs_file`, and their table files (see
+/// `Session::binary_tables`).
+pub fn emit_rust(session: &Session,
+                 grammar: &r::Grammar,
+                 rs_file: &Path,
+                 report_file: &Path)
+                 -> io::Result<Vec<OutputFile>> {
+    let mut tables = TableFiles::new(rs_file);
+    let mut buffer = version_header().into_bytes();
+    buffer.extend(try!(emit_recursive_ascent(session, grammar, report_file, &mut tables)));
+    let mut files = vec![OutputFile {
+                             path: PathBuf::from(rs_file.file_name().unwrap_or_default()),
+                             contents: buffer,
+                         }];
+    for (name, bytes) in tables.files {
+        files.push(OutputFile {
+            path: PathBuf::from(name),
+            contents: bytes,
+        });
+    }
+    Ok(files)
+}
+
+/// Writes the module of scaffolding that the parsers share, if the
+/// session asks for one (see `Session::support_module`). The file is
+/// left alone if it is already up to date, so that cargo does not
+/// rebuild the crate for nothing.
+fn write_support_module(session: &Session) -> io::Result<()> {
+    let module_path = match session.support_module {
+        Some(ref module_path) => module_path,
+        None => return Ok(()),
+    };
+    let rs_file = resolve_module_file(session, module_path, "rs");
+
+    let buffer = try!(emit_support_module());
+    let mut old_buffer = vec![];
+    if let Ok(mut file) = fs::File::open(&rs_file) {
+        try!(file.read_to_end(&mut old_buffer));
+        if old_buffer == buffer {
+            return Ok(());
+        }
+    }
+
+    log!(session,
+         Informative,
+         "writing support module `{}`",
+         rs_file.to_string_lossy());
+    if let Some(parent) = rs_file.parent() {
+        try!(fs::create_dir_all(parent));
+    }
+    try!(make_read_only(&rs_file, false));
+    try!(remove_old_file(&rs_file));
+    {
+        let mut output_file = try!(fs::File::create(&rs_file));
+        try!(output_file.write_all(&buffer));
+    }
+    make_read_only(&rs_file, true)
+}
+
+fn resolve_module_file(session: &Session, module_path: &str, ext: &str) -> PathBuf {
+    let mut file = out_dir(session).to_path_buf();
+    for name in module_path.split("::") {
+        file.push(name.trim());
+    }
+    file.with_extension(ext)
+}
+
 fn resolve_rs_file(session: &Session, lalrpop_file: &Path) -> io::Result<PathBuf> {
     gen_resolve_file(session, lalrpop_file, "rs")
 }
@@ -52,26 +373,51 @@ fn resolve_report_file(session: &Session, lalrpop_file: &Path) -> io::Result<Pat
 }
 
 fn gen_resolve_file(session: &Session, lalrpop_file: &Path, ext: &str) -> io::Result<PathBuf> {
-    let in_dir = if let Some(ref d) = session.in_dir {
+    // If the lalrpop file is not in in_dir, the result is that the
+    // .rs file is created in the same directory as the lalrpop file
+    // for compatibility reasons
+    let relative = lalrpop_file.strip_prefix(in_dir(session)).unwrap_or(lalrpop_file);
+    Ok(out_dir(session).join(relative).with_extension(ext))
+}
+
+fn in_dir(session: &Session) -> &Path {
+    if let Some(ref d) = session.in_dir {
         d.as_path()
     } else {
         Path::new(".")
-    };
-    let out_dir = if let Some(ref d) = session.out_dir {
+    }
+}
+
+fn out_dir(session: &Session) -> &Path {
+    if let Some(ref d) = session.out_dir {
         d.as_path()
     } else {
-        in_dir
-    };
+        in_dir(session)
+    }
+}
 
-    // If the lalrpop file is not in in_dir, the result is that the
-    // .rs file is created in the same directory as the lalrpop file
-    // for compatibility reasons
-    Ok(out_dir.join(lalrpop_file.strip_prefix(&in_dir).unwrap_or(lalrpop_file))
-              .with_extension(ext))
+/// Tells cargo to run the build script again if `lalrpop_file`
+/// changes, if the session asks for it. We do not do the same for the
+/// directories that we search, since cargo would look at everything in
+/// them, including the `.rs` files that we write.
+fn emit_rerun_directive(session: &Session, lalrpop_file: &Path) {
+    if session.emit_rerun_directives {
+        println!("cargo:rerun-if-changed={}", lalrpop_file.display());
+    }
 }
 
 fn process_file_into(session: Rc<Session>, lalrpop_file: &Path, rs_file: &Path, report_file: &Path) -> io::Result<()> {
-    if session.force_build || try!(needs_rebuild(&lalrpop_file, &rs_file)) {
+    // Load the LALRPOP source text for this file, and for those it
+    // imports, which are needed to tell whether it is out of date:
+    let file_text = Rc::new(try!(load_file_text(&session, lalrpop_file)));
+    let lalrpop_files: Vec<&Path> = file_text.files().into_iter().map(|(_, path)| path).collect();
+
+    // cargo only keeps the directives of the last run, so they are
+    // needed whether or not the file is rebuilt
+    for path in &lalrpop_files {
+        emit_rerun_directive(&session, path);
+    }
+    if session.force_build || try!(needs_rebuild(&session, &lalrpop_files, &rs_file)) {
         log!(session,
              Informative,
              "processing file `{}`",
@@ -82,9 +428,6 @@ fn process_file_into(session: Rc<Session>, lalrpop_file: &Path, rs_file: &Path,
         try!(make_read_only(&rs_file, false));
         try!(remove_old_file(&rs_file));
 
-        // Load the LALRPOP source text for this file:
-        let file_text = Rc::new(try!(FileText::from_path(lalrpop_file.to_path_buf())));
-
         // Store the session and file-text in TLS -- this is not
         // intended to be used in this high-level code, but it gives
         // easy access to this information pervasively in the
@@ -98,9 +441,7 @@ fn process_file_into(session: Rc<Session>, lalrpop_file: &Path, rs_file: &Path,
         // file behind.
         {
             let grammar = try!(parse_and_normalize_grammar(&session, &file_text));
-            let buffer = try!(emit_recursive_ascent(&session, &grammar, &report_file));
-            let mut output_file = try!(fs::File::create(&rs_file));
-            try!(output_file.write_all(&buffer));
+            try!(run_backend(&session, &grammar, &rs_file, &report_file));
         }
 
         try!(make_read_only(&rs_file, true));
@@ -108,6 +449,46 @@ fn process_file_into(session: Rc<Session>, lalrpop_file: &Path, rs_file: &Path,
     Ok(())
 }
 
+/// Runs everything that `process_file` does short of generating code
+/// -- parsing, normalization (including type inference), and LR table
+/// construction for each public nonterminal -- and reports any errors
+/// and conflicts. Nothing is written to disk.
+pub fn check_file<P: AsRef<Path>>(session: Rc<Session>, lalrpop_file: P) -> io::Result<()> {
+    let lalrpop_file = lalrpop_file.as_ref();
+    log!(session,
+         Informative,
+         "checking file `{}`",
+         lalrpop_file.to_string_lossy());
+
+    let file_text = Rc::new(try!(load_file_text(&session, lalrpop_file)));
+    let _tls = Tls::install(session.clone(), file_text.clone());
+
+    let grammar = try!(parse_and_normalize_grammar(&session, &file_text));
+
+    // unlike `build_states`, keep going after a conflict, so that the
+    // conflicts for every public nonterminal get reported at once
+    let mut conflicted = vec![];
+    for (&user_nt, &start_nt) in &grammar.start_nonterminals {
+        let _lr1_tls = lr1::Lr1Tls::install(grammar.terminals.clone());
+        let lr1result = build_lr1_states(&grammar, start_nt);
+        try!(write_html_report(&session, &grammar, user_nt, &lr1result));
+        if let Err(ref error) = lr1result {
+            let messages = lr1::report_error(&grammar, error);
+            let _ = report_messages(messages);
+            try!(write_conflict_svgs(&session, &grammar, user_nt, error));
+            conflicted.push(user_nt);
+        }
+    }
+
+    if !conflicted.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::Other,
+                                  format!("conflicts in the states for {}",
+                                          Sep(", ", &conflicted))));
+    }
+
+    Ok(())
+}
+
 fn remove_old_file(rs_file: &Path) -> io::Result<()> {
     match fs::remove_file(rs_file) {
         Ok(()) => Ok(()),
@@ -121,11 +502,37 @@ fn remove_old_file(rs_file: &Path) -> io::Result<()> {
     }
 }
 
-fn needs_rebuild(lalrpop_file: &Path, rs_file: &Path) -> io::Result<bool> {
+/// The first line of the `.rs` files that `RustBackend` writes, which
+/// names the version of LALRPOP that wrote them.
+fn version_header() -> String {
+    format!("// auto-generated: \"lalrpop {}\"\n", env!("CARGO_PKG_VERSION"))
+}
+
+/// True if `rs_file` is missing, older than one of `lalrpop_files`
+/// (the grammar and the files it imports), or, unless the session has
+/// a backend of its own, was written by another version of LALRPOP
+/// (or before the version was recorded), whose generated code may not
+/// work with this version's `lalrpop_util`.
+fn needs_rebuild(session: &Session, lalrpop_files: &[&Path], rs_file: &Path) -> io::Result<bool> {
     return match fs::metadata(&rs_file) {
         Ok(rs_metadata) => {
-            let lalrpop_metadata = try!(fs::metadata(&lalrpop_file));
-            Ok(compare_modification_times(&lalrpop_metadata, &rs_metadata))
+            for lalrpop_file in lalrpop_files {
+                let lalrpop_metadata = try!(fs::metadata(lalrpop_file));
+                if compare_modification_times(&lalrpop_metadata, &rs_metadata) {
+                    return Ok(true);
+                }
+            }
+            if session.backend.is_some() {
+                return Ok(false);
+            }
+            let header = version_header();
+            let mut start = vec![0; header.len()];
+            let mut file = try!(fs::File::open(&rs_file));
+            match file.read_exact(&mut start) {
+                Ok(()) => Ok(start != header.as_bytes()),
+                Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(true),
+                Err(e) => Err(e),
+            }
         }
         Err(e) => {
             match e.kind() {
@@ -190,78 +597,322 @@ fn lalrpop_files<P: AsRef<Path>>(root_dir: P) -> io::Result<Vec<PathBuf>> {
     Ok(result)
 }
 
+/// Reads `lalrpop_file`, replacing each `${NAME}` in it with the
+/// value of the session's variable `NAME`, and reports an error if
+/// there is no such variable. The files that it imports are added to
+/// the text; see `load_imports`.
+fn load_file_text(session: &Rc<Session>, lalrpop_file: &Path) -> io::Result<FileText> {
+    let file_text = try!(FileText::from_path(lalrpop_file.to_path_buf()));
+    let file_text = try!(substitute_variables(session, file_text));
+    load_imports(session, file_text)
+}
+
+/// Adds the text of each file imported with `use "file.lalrpop";` to
+/// `file_text`, as well as the files that those import in turn. The
+/// path is relative to the directory of the importing file. A file is
+/// only added once, however many times it is imported, so files may
+/// import each other.
+fn load_imports(session: &Rc<Session>, mut file_text: FileText) -> io::Result<FileText> {
+    let mut loaded = vec![canonical_path(file_text.path())];
+    let mut index = 0;
+    while index < file_text.files().len() {
+        let (span, importing_dir) = {
+            let (span, importing_path) = file_text.files()[index];
+            (span, importing_path.parent().unwrap_or(Path::new("")).to_path_buf())
+        };
+        let imports = find_imports(&file_text.text()[span.0..span.1], span.0);
+        for (import_span, import) in imports {
+            let path = importing_dir.join(import);
+            if loaded.contains(&canonical_path(&path)) {
+                continue;
+            }
+            let imported = match FileText::from_path(path.clone()) {
+                Ok(imported) => imported,
+                Err(error) => {
+                    let file_text = Rc::new(file_text);
+                    let _tls = Tls::install(session.clone(), file_text.clone());
+                    return Err(report_error(&file_text,
+                                            &catalog::UNREADABLE_IMPORT,
+                                            import_span,
+                                            &[&path.display(), &error]));
+                }
+            };
+            let imported = try!(substitute_variables(session, imported));
+            loaded.push(canonical_path(&path));
+            file_text.append(path, imported.text());
+        }
+        index += 1;
+    }
+    Ok(file_text)
+}
+
+/// The span and the path of each `use "file.lalrpop";` in `text`,
+/// which starts at `offset` in the text of its grammar. Only the
+/// tokens are looked at; if there is an error, the imports after it
+/// are left out, and parsing the grammar will report it.
+fn find_imports(text: &str, offset: usize) -> Vec<(pt::Span, String)> {
+    let mut tokens = tok::Tokenizer::new(text, offset);
+    let mut imports = vec![];
+    while let Some(Ok((start, token, _))) = tokens.next() {
+        if token == tok::Tok::Import {
+            if let Some(Ok((_, tok::Tok::StringLiteral(path), end))) = tokens.next() {
+                imports.push((pt::Span(start, end), path.to_string()));
+            }
+        }
+    }
+    imports
+}
+
+/// `path` with symbolic links and `..` resolved, so that a file
+/// imported under two names is recognized, or `path` itself if the
+/// file does not exist.
+fn canonical_path(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or(path.to_path_buf())
+}
+
+fn substitute_variables(session: &Rc<Session>, file_text: FileText) -> io::Result<FileText> {
+    if session.variables.is_empty() {
+        return Ok(file_text);
+    }
+    match file_text.substitute(&session.variables) {
+        Ok(file_text) => Ok(file_text),
+        Err(span) => {
+            let name = file_text.text()[span.0 + 2..span.1 - 1].to_string();
+            let file_text = Rc::new(file_text);
+            let _tls = Tls::install(session.clone(), file_text.clone());
+            Err(report_error(&file_text, &catalog::UNDEFINED_VARIABLE, span, &[&name]))
+        }
+    }
+}
+
 fn parse_and_normalize_grammar(session: &Session, file_text: &FileText) -> io::Result<r::Grammar> {
-    let grammar = match parser::parse_grammar(file_text.text()) {
+    let grammar = try!(parse_grammar(file_text));
+    normalize_grammar(session, file_text, grammar)
+}
+
+/// Parses the grammar in `file_text`, reporting a syntax error if
+/// there is one. The items of the files that it imports, whose text
+/// follows its own, are added to those of the grammar.
+fn parse_grammar(file_text: &FileText) -> io::Result<pt::Grammar> {
+    let files = file_text.files();
+    let text = file_text.text();
+    let (span, _) = files[0];
+    let mut grammar = match parser::parse_grammar(&text[span.0..span.1]) {
         Ok(grammar) => grammar,
+        Err(error) => return Err(report_parse_error(file_text, span, error)),
+    };
+    for &(span, _) in &files[1..] {
+        match parser::parse_grammar_items(&text[span.0..span.1], span.0) {
+            Ok(items) => grammar.items.extend(items),
+            Err(error) => return Err(report_parse_error(file_text, span, error)),
+        }
+    }
 
-        Err(ParseError::InvalidToken { location }) => {
+    // the prefix must not appear in the imported files either
+    while text.contains(&grammar.prefix) {
+        grammar.prefix.push('_');
+    }
+
+    Ok(grammar)
+}
+
+/// Reports a syntax error in the file whose text is at `file_span` in
+/// `file_text`.
+fn report_parse_error(file_text: &FileText,
+                      file_span: pt::Span,
+                      error: parser::ParseError)
+                      -> io::Error {
+    match error {
+        ParseError::InvalidToken { location } => {
             let ch = file_text.text()[location..].chars().next().unwrap();
             report_error(&file_text,
+                         &catalog::INVALID_CHARACTER,
                          pt::Span(location, location),
-                         &format!("invalid character `{}`", ch));
+                         &[&ch])
         }
 
-        Err(ParseError::UnrecognizedToken { token: None, expected: _ }) => {
-            let len = file_text.text().len();
+        ParseError::UnrecognizedToken { token: None, expected: _ } => {
+            let end = file_span.1;
             report_error(&file_text,
-                         pt::Span(len, len),
-                         &format!("unexpected end of file"));
+                         &catalog::UNEXPECTED_EOF,
+                         pt::Span(end, end),
+                         &[])
         }
 
-        Err(ParseError::UnrecognizedToken { token: Some((lo, _, hi)), expected }) => {
+        ParseError::UnrecognizedToken { token: Some((lo, _, hi)), expected } => {
             assert!(expected.is_empty()); // didn't implement this yet :)
             let text = &file_text.text()[lo..hi];
             report_error(&file_text,
+                         &catalog::UNEXPECTED_TOKEN,
                          pt::Span(lo, hi),
-                         &format!("unexpected token: `{}`", text));
+                         &[&text])
         }
 
-        Err(ParseError::ExtraToken { token: (lo, _, hi) }) => {
+        ParseError::ExtraToken { token: (lo, _, hi) } => {
             let text = &file_text.text()[lo..hi];
             report_error(&file_text,
+                         &catalog::EXTRA_TOKEN,
                          pt::Span(lo, hi),
-                         &format!("extra token at end of input: `{}`", text));
+                         &[&text])
         }
 
-        Err(ParseError::User { error }) => {
-            let string = match error.code {
-                tok::ErrorCode::UnrecognizedToken => "unrecognized token",
-                tok::ErrorCode::UnterminatedEscape => "unterminated escape; missing '`'?",
-                tok::ErrorCode::UnterminatedStringLiteral => {
-                    "unterminated string literal; missing `\"`?"
-                }
+        ParseError::NestingTooDeep { location } => {
+            report_error(&file_text,
+                         &catalog::NESTING_TOO_DEEP,
+                         pt::Span(location, location),
+                         &[])
+        }
+
+        ParseError::User { error } => {
+            let entry = match error.code {
+                tok::ErrorCode::UnrecognizedToken => &catalog::UNRECOGNIZED_TOKEN,
+                tok::ErrorCode::UnterminatedEscape => &catalog::UNTERMINATED_ESCAPE,
+                tok::ErrorCode::UnterminatedStringLiteral => &catalog::UNTERMINATED_STRING_LITERAL,
                 tok::ErrorCode::UnterminatedCharacterLiteral => {
-                    "unterminated character literal; missing `'`?"
-                }
-                tok::ErrorCode::ExpectedStringLiteral => "expected string literal; missing `\"`?",
-                tok::ErrorCode::UnterminatedCode => {
-                    "unterminated code block; perhaps a missing `;`, `)`, `]` or `}`?"
+                    &catalog::UNTERMINATED_CHARACTER_LITERAL
                 }
+                tok::ErrorCode::ExpectedStringLiteral => &catalog::EXPECTED_STRING_LITERAL,
+                tok::ErrorCode::ExpectedSingleCharacter => &catalog::EXPECTED_SINGLE_CHARACTER,
+                tok::ErrorCode::UnterminatedCode => &catalog::UNTERMINATED_CODE,
+                tok::ErrorCode::UnterminatedComment => &catalog::UNTERMINATED_COMMENT,
             };
 
             report_error(&file_text,
+                         entry,
                          pt::Span(error.location, error.location + 1),
-                         string)
+                         &[])
+        }
+    }
+}
+
+fn normalize_grammar(session: &Session,
+                     file_text: &FileText,
+                     grammar: pt::Grammar)
+                     -> io::Result<r::Grammar> {
+    let span = grammar.span;
+    let grammar = match normalize::normalize(session, grammar) {
+        Ok(grammar) => grammar,
+        Err(error) => {
+            return Err(report_error(&file_text,
+                                    &catalog::INVALID_GRAMMAR,
+                                    error.span,
+                                    &[&error.message]))
+        }
+    };
+
+    // the passes are about the grammar as a whole, so their errors
+    // point at the `grammar` declaration
+    let grammar = match passes::run_passes(session, grammar) {
+        Ok(grammar) => grammar,
+        Err(message) => {
+            return Err(report_error(&file_text, &catalog::INVALID_GRAMMAR, span, &[&message]))
         }
     };
 
-    match normalize::normalize(session, grammar) {
-        Ok(grammar) => Ok(grammar),
-        Err(error) => report_error(&file_text, error.span, &error.message),
+    for warning in lint::lint(&grammar) {
+        report_warning(&file_text, warning.span, &warning.message);
     }
+
+    Ok(grammar)
+}
+
+/// Reports an error of the kind `entry`, with `args` for the details
+/// that its text leaves open, and returns an `io::Error` for the
+/// caller to give up with.
+fn report_error(file_text: &FileText,
+                entry: &'static CatalogEntry,
+                span: pt::Span,
+                args: &[&Display])
+                -> io::Error {
+    report_span(file_text, Severity::Error, Some(entry.code), span, &entry.format(args));
+    io::Error::new(io::ErrorKind::Other, format!("{} ({})", entry.name, entry.code))
 }
 
-fn report_error(file_text: &FileText, span: pt::Span, message: &str) -> ! {
-    println!("{} error: {}", file_text.span_str(span), message);
+fn report_warning(file_text: &FileText, span: pt::Span, message: &str) {
+    report_span(file_text, Severity::Warning, None, span, message);
+}
 
-    let out = io::stderr();
-    let mut out = out.lock();
-    file_text.highlight(span, &mut out).unwrap();
+/// Reports an error or warning about `span`, to the `Diagnostics` of
+/// the session if it has one, and otherwise by printing it.
+fn report_span(file_text: &FileText,
+               severity: Severity,
+               code: Option<&'static str>,
+               span: pt::Span,
+               message: &str) {
+    let session = Tls::session();
+    let severity_name = match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+    };
 
-    exit(1);
+    // with `ErrorFormat::Human`, the heading is printed to stdout but
+    // the highlighted text to stderr
+    let (heading, details) = match session.error_format {
+        ErrorFormat::Human => {
+            let mut highlight = vec![];
+            file_text.highlight(span, &mut highlight).unwrap();
+            (format!("{} {}: {}", file_text.span_str(span), severity_name, message),
+             String::from_utf8_lossy(&highlight).into_owned())
+        }
+        ErrorFormat::Rustc => {
+            let heading = match code {
+                Some(code) => format!("{}[{}]: {}", severity_name, code, message),
+                None => format!("{}: {}", severity_name, message),
+            };
+            let mut snippet = String::new();
+            for line in file_text.rustc_snippet(span, "") {
+                snippet.push_str(&line);
+                snippet.push('\n');
+            }
+            snippet.push('\n');
+            (heading, snippet)
+        }
+    };
+
+    match session.diagnostics {
+        Some(ref diagnostics) => {
+            diagnostics.report(Diagnostic {
+                severity: severity,
+                code: code,
+                file: file_text.path_at(span.0).to_path_buf(),
+                span: (span.0, span.1),
+                text: format!("{}\n{}", heading, details),
+            })
+        }
+        None => {
+            println!("{}", heading);
+            match session.error_format {
+                ErrorFormat::Human => {
+                    let _ = io::stderr().write_all(details.as_bytes());
+                }
+                ErrorFormat::Rustc => print!("{}", details),
+            }
+        }
+    }
 }
 
 fn report_messages(messages: Vec<Message>) -> term::Result<()> {
+    let session = Tls::session();
+    if let Some(ref diagnostics) = session.diagnostics {
+        let file_text = Tls::file_text();
+        for message in messages {
+            let severity = if message.is_warning() { Severity::Warning } else { Severity::Error };
+            let span = message.span();
+            let code = message.code();
+            let canvas = message.emit_to_canvas(80);
+            let mut text = FakeTerminal::new(vec![]);
+            try!(canvas.write_to(&mut text));
+            diagnostics.report(Diagnostic {
+                severity: severity,
+                code: code,
+                file: file_text.path_at(span.0).to_path_buf(),
+                span: (span.0, span.1),
+                text: String::from_utf8_lossy(&text.into_inner()).into_owned(),
+            });
+        }
+        return Ok(());
+    }
+
     let builder = InlineBuilder::new().begin_paragraphs();
     let builder = messages.into_iter().fold(builder, |b, m| b.push(Box::new(m)));
     let content = builder.end().end();
@@ -294,7 +945,11 @@ fn emit_uses<W: Write>(grammar: &r::Grammar, rust: &mut RustWrite<W>) -> io::Res
 }
 
 
-fn emit_recursive_ascent(session: &Session, grammar: &r::Grammar, report_file : &Path) -> io::Result<Vec<u8>> {
+fn emit_recursive_ascent(session: &Session,
+                         grammar: &r::Grammar,
+                         report_file: &Path,
+                         tables: &mut TableFiles)
+                         -> io::Result<Vec<u8>> {
     let mut rust = RustWrite::new(vec![]);
 
     // We generate a module structure like this:
@@ -302,8 +957,14 @@ fn emit_recursive_ascent(session: &Session, grammar: &r::Grammar, report_file :
     // ```
     // mod <output-file> {
     //     // For each public symbol:
-    //     pub fn parse_XYZ();
-    //     mod __XYZ { ... }
+    //     pub use self::__generated::__parseXYZ::parse_XYZ;
+    //
+    //     mod __generated {
+    //         // For each public symbol:
+    //         pub mod __parseXYZ { pub fn parse_XYZ(); ... }
+    //
+    //         // The tokenizer, `__ToTriple`, ...
+    //     }
     //
     //     // For each bit of action code:
     //     <action-code>
@@ -315,14 +976,28 @@ fn emit_recursive_ascent(session: &Session, grammar: &r::Grammar, report_file :
     // as a module in the rust hierarchy, so if the action code
     // includes things like `super::` it will resolve in the natural
     // way.
+    //
+    // Everything else that we generate goes in the private
+    // `__generated` module, of which the outer module re-exports only
+    // the public entry points, so that none of our helper items can
+    // clash with (or be reached from) the user's items.
 
     try!(emit_uses(grammar, &mut rust));
 
     if grammar.start_nonterminals.is_empty() {
-        println!("Error: no public symbols declared in grammar");
-        exit(1);
+        return Err(io::Error::new(io::ErrorKind::Other,
+                                  "no public symbols declared in grammar"));
     }
 
+    rust!(rust, "");
+    rust!(rust, "mod {}generated {{", grammar.prefix);
+    rust!(rust, "#![allow(unused_imports)]");
+    try!(rust.write_uses("super::", grammar));
+
+    // the paths, relative to `__generated`, of the entry points to
+    // re-export from the outer module
+    let mut entry_points = vec![];
+
     for (&user_nt, &start_nt) in &grammar.start_nonterminals {
         // We generate these, so there should always be exactly 1
         // production. Otherwise the LR(1) algorithm doesn't know
@@ -336,20 +1011,7 @@ fn emit_recursive_ascent(session: &Session, grammar: &r::Grammar, report_file :
 
         let _lr1_tls = lr1::Lr1Tls::install(grammar.terminals.clone());
 
-        let lr1result = lr1::build_states(&grammar, start_nt);
-        if session.emit_report {
-            let mut output_report_file = try!(fs::File::create(&report_file));
-            try!(lr1::generate_report(&mut output_report_file, &lr1result));
-        }
-
-        let states = match lr1result {
-            Ok(states) => states,
-            Err(error) => {
-                let messages = lr1::report_error(&grammar, &error);
-                let _ = report_messages(messages);
-                exit(1) // FIXME -- propagate up instead of calling `exit`
-            }
-        };
+        let states = try!(build_states(session, grammar, user_nt, start_nt, report_file));
 
         match grammar.algorithm.codegen {
             r::LrCodeGeneration::RecursiveAscent =>
@@ -357,38 +1019,397 @@ fn emit_recursive_ascent(session: &Session, grammar: &r::Grammar, report_file :
                                                    user_nt,
                                                    start_nt,
                                                    &states,
-                                                   "super",
+                                                   "super::super",
                                                    &mut rust)),
             r::LrCodeGeneration::TableDriven =>
                 try!(lr1::codegen::parse_table::compile(&grammar,
                                                         user_nt,
                                                         start_nt,
                                                         &states,
-                                                        "super",
+                                                        "super::super",
+                                                        tables,
                                                         &mut rust)),
 
             r::LrCodeGeneration::TestAll =>
-                try!(lr1::codegen::test_all::compile(&grammar, user_nt, start_nt, &states, &mut rust)),
+                try!(lr1::codegen::test_all::compile(&grammar,
+                                                     user_nt,
+                                                     start_nt,
+                                                     &states,
+                                                     "super::super",
+                                                     tables,
+                                                     &mut rust)),
         }
 
-        rust!(rust,
-              "pub use self::{}parse{}::parse_{};",
-              grammar.prefix,
-              start_nt,
-              user_nt);
+        let parse_mod = format!("{}parse{}", grammar.prefix, start_nt);
+
+        entry_points.push(format!("{}::parse_{}", parse_mod, user_nt));
+
+        if grammar.algorithm.prefix {
+            entry_points.push(format!("{}::parse_{}_prefix", parse_mod, user_nt));
+        }
+
+        if grammar.algorithm.incremental {
+            entry_points.push(format!("{}::{{parse_{}_incremental, \
+                                       {}Checkpoints as Checkpoints_{}}}",
+                                      parse_mod,
+                                      user_nt,
+                                      grammar.prefix,
+                                      user_nt));
+        }
+
+        if grammar.algorithm.push {
+            entry_points.push(format!("{}::{}PushParser as PushParser_{}",
+                                      parse_mod,
+                                      grammar.prefix,
+                                      user_nt));
+        }
+
+        if grammar.generates_completion_fns() {
+            entry_points.push(format!("{}::complete_{}", parse_mod, user_nt));
+        }
+
+        if grammar.algorithm.codegen != r::LrCodeGeneration::RecursiveAscent {
+            entry_points.push(format!("{}::{}expected_tokens as expected_tokens_{}",
+                                      parse_mod,
+                                      grammar.prefix,
+                                      user_nt));
+            if session.emit_introspection {
+                entry_points.push(format!("{}::{{{}observe as observe_{}, \
+                                           {}Introspection as Introspection_{}}}",
+                                          parse_mod,
+                                          grammar.prefix,
+                                          user_nt,
+                                          grammar.prefix,
+                                          user_nt));
+            }
+            if session.emit_observer {
+                entry_points.push(format!("{}::{{{}Observer as Observer_{}, \
+                                           {}set_observer as set_observer_{}}}",
+                                          parse_mod,
+                                          grammar.prefix,
+                                          user_nt,
+                                          grammar.prefix,
+                                          user_nt));
+            }
+        }
+    }
+
+    for (&user_nt, &start_nt) in &grammar.item_start_nonterminals {
+        assert_eq!(grammar.productions_for(start_nt).len(), 1);
+
+        log!(session,
+             Verbose,
+             "Building states for the items of public nonterminal `{}`",
+             user_nt);
+
+        let _lr1_tls = lr1::Lr1Tls::install(grammar.terminals.clone());
+
+        let states = try!(build_states(session, grammar, user_nt, start_nt, report_file));
+
+        try!(lr1::codegen::parse_table::compile_items(&grammar,
+                                                      user_nt,
+                                                      start_nt,
+                                                      &states,
+                                                      "super::super",
+                                                      tables,
+                                                      &mut rust));
+
+        entry_points.push(format!("{}parse{}::parse_{}_items",
+                                  grammar.prefix,
+                                  start_nt,
+                                  user_nt));
     }
 
     if let Some(ref intern_token) = grammar.intern_token {
         try!(intern_token::compile(&grammar, intern_token, &mut rust));
+
+        for &user_nt in grammar.start_nonterminals.keys() {
+            entry_points.push(format!("derivations_{}", user_nt));
+        }
+    }
+
+    try!(derivations::emit_derivations(grammar, &mut rust));
+
+    if session.support_module.is_none() {
+        try!(emit_to_triple_trait(grammar, &mut rust));
+    }
+
+    rust!(rust, "}}");
+
+    let visibility = session.visibility;
+    for entry_point in entry_points {
+        if visibility == Visibility::Private {
+            rust!(rust, "#[allow(unused_imports)]");
+        }
+        rust!(rust,
+              "{}use self::{}generated::{};",
+              visibility.qualifier(),
+              grammar.prefix,
+              entry_point);
     }
 
     try!(action::emit_action_code(grammar, &mut rust));
 
-    try!(emit_to_triple_trait(grammar, &mut rust));
+    try!(emit_rust_items(grammar, &mut rust));
+
+    try!(emit_inline_tests(grammar, &mut rust));
+
+    try!(emit_display_error_fn(session, grammar, &mut rust));
+
+    if grammar.uses_conflict_hooks() {
+        try!(emit_conflict_hook_trait(grammar, &mut rust));
+    }
 
     Ok(rust.into_inner())
 }
 
+/// Builds the LR(1) states for `start_nt`, resolving any shift/reduce
+/// conflicts by shifting if the grammar asks for that. The conflicts
+/// are only reported for `#[prefer_shift]`: in a `#[scannerless]`
+/// grammar, they are how the longest match is found.
+fn build_lr1_states<'grammar>(grammar: &'grammar r::Grammar,
+                              start_nt: r::NonterminalString)
+                              -> lr1::LR1Result<'grammar> {
+    match lr1::build_states(grammar, start_nt) {
+        Err(error) if grammar.prefers_shift() && !lr1::exceeds_max_states(&error.states) => {
+            if grammar.algorithm.prefer_shift {
+                let _ = report_messages(lr1::report_shift_warnings(grammar, &error));
+            }
+            lr1::prefer_shift(error)
+        }
+        lr1result => lr1result,
+    }
+}
+
+fn build_states<'grammar>(session: &Session,
+                          grammar: &'grammar r::Grammar,
+                          user_nt: r::NonterminalString,
+                          start_nt: r::NonterminalString,
+                          report_file: &Path)
+                          -> io::Result<Vec<lr1::LR1State<'grammar>>> {
+    let lr1result = build_lr1_states(grammar, start_nt);
+    try!(check_automaton_size(session, grammar, user_nt, &lr1result));
+    let messages = match lr1result {
+        Ok(_) => vec![],
+        Err(ref error) => lr1::report_error(&grammar, error),
+    };
+    if session.emit_report {
+        let mut output_report_file = try!(fs::File::create(&report_file));
+        try!(lr1::generate_report(&mut output_report_file, &lr1result));
+        try!(write_conflict_examples(&mut output_report_file, &messages));
+    }
+    try!(write_dot(session, user_nt, start_nt, report_file, &lr1result));
+    try!(write_html_report(session, grammar, user_nt, &lr1result));
+
+    match lr1result {
+        Ok(states) => Ok(states),
+        Err(error) => {
+            let _ = report_messages(messages);
+            try!(write_conflict_svgs(session, grammar, user_nt, &error));
+            Err(io::Error::new(io::ErrorKind::Other,
+                               format!("conflicts in the states for {}", user_nt)))
+        }
+    }
+}
+
+/// Reports an error if the parser for `user_nt` needs more states, or
+/// bigger tables, than the session allows (see `Session::max_states`
+/// and `Session::max_table_entries`).
+fn check_automaton_size(session: &Session,
+                        grammar: &r::Grammar,
+                        user_nt: r::NonterminalString,
+                        lr1result: &lr1::LR1Result)
+                        -> io::Result<()> {
+    let states = match *lr1result {
+        Ok(ref states) => states,
+        Err(ref error) => &error.states,
+    };
+    let file_text = Tls::file_text();
+    let span = grammar.nonterminals[&user_nt].span;
+
+    if let Some(max) = session.max_states {
+        if lr1::exceeds_max_states(states) {
+            return Err(report_error(&file_text,
+                                    &catalog::TOO_MANY_STATES,
+                                    span,
+                                    &[&user_nt, &max, &busiest_nonterminals(grammar, states)]));
+        }
+    }
+
+    if let Some(max) = session.max_table_entries {
+        let columns = grammar.terminals.all.len() + grammar.nonterminals.len();
+        let entries = states.len() * columns;
+        if entries > max {
+            return Err(report_error(&file_text,
+                                    &catalog::TABLES_TOO_LARGE,
+                                    span,
+                                    &[&user_nt,
+                                      &entries,
+                                      &max,
+                                      &busiest_nonterminals(grammar, states)]));
+        }
+    }
+
+    Ok(())
+}
+
+/// The nonterminals that take part in the most of `states`, with how
+/// many, one per line, for the errors about automata that are too big.
+fn busiest_nonterminals(grammar: &r::Grammar, states: &[lr1::LR1State]) -> String {
+    metrics::metrics(grammar, states)
+        .into_iter()
+        .filter(|row| row.states > 0)
+        .take(10)
+        .map(|row| format!("\n    `{}`: {} states", row.nonterminal, row.states))
+        .collect()
+}
+
+/// Writes SVG pictures of the examples of the conflicts in `error`
+/// into the directory that the session asks for, if any (see
+/// `Session::conflict_svg_dir`).
+fn write_conflict_svgs(session: &Session,
+                       grammar: &r::Grammar,
+                       user_nt: r::NonterminalString,
+                       error: &lr1::LR1TableConstructionError)
+                       -> io::Result<()> {
+    let dir = match session.conflict_svg_dir {
+        Some(ref dir) => dir,
+        None => return Ok(()),
+    };
+    try!(fs::create_dir_all(dir));
+
+    let file_text = Tls::file_text();
+    let stem = file_text.path()
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+    for (index, (action, reduce)) in lr1::conflict_svgs(grammar, error).into_iter().enumerate() {
+        for &(suffix, ref svg) in &[("a", action), ("b", reduce)] {
+            let svg_file = dir.join(format!("{}-{}-conflict{}{}.svg",
+                                            stem,
+                                            user_nt,
+                                            index + 1,
+                                            suffix));
+            log!(session,
+                 Informative,
+                 "writing conflict picture `{}`",
+                 svg_file.to_string_lossy());
+            let mut output_file = try!(fs::File::create(&svg_file));
+            try!(output_file.write_all(svg.as_bytes()));
+        }
+    }
+    Ok(())
+}
+
+/// Ends a report file with the conflicts as they are reported, each
+/// with its example derivations, in plain text.
+fn write_conflict_examples<W: Write>(out: &mut W, messages: &[Message]) -> io::Result<()> {
+    if messages.is_empty() {
+        return Ok(());
+    }
+
+    try!(writeln!(out, "\nConflict Examples"));
+    try!(writeln!(out, "----------------------------------------"));
+    for message in messages {
+        try!(writeln!(out, ""));
+        let canvas = message.emit_to_canvas(80);
+        try!(canvas.write_to(&mut FakeTerminal::new(&mut *out)));
+    }
+    Ok(())
+}
+
+/// Writes the automaton of `lr1result` as a `.dot` file next to
+/// `report_file`, if the session asks for it (see `Session::emit_dot`).
+fn write_dot<'grammar>(session: &Session,
+                       user_nt: r::NonterminalString,
+                       start_nt: r::NonterminalString,
+                       report_file: &Path,
+                       lr1result: &lr1::LR1Result<'grammar>)
+                       -> io::Result<()> {
+    if !session.emit_dot {
+        return Ok(());
+    }
+
+    let stem = report_file.file_stem()
+                          .map(|stem| stem.to_string_lossy().into_owned())
+                          .unwrap_or_default();
+    let dot_file = report_file.with_file_name(format!("{}-{}.dot", stem, user_nt));
+    log!(session,
+         Informative,
+         "writing automaton `{}`",
+         dot_file.to_string_lossy());
+    let mut output_file = try!(fs::File::create(&dot_file));
+    lr1::generate_dot(&mut output_file, start_nt, lr1result)
+}
+
+/// Writes the HTML report on `lr1result` into the directory that the
+/// session asks for, if any (see `Session::html_report_dir`).
+fn write_html_report<'grammar>(session: &Session,
+                               grammar: &'grammar r::Grammar,
+                               user_nt: r::NonterminalString,
+                               lr1result: &lr1::LR1Result<'grammar>)
+                               -> io::Result<()> {
+    let dir = match session.html_report_dir {
+        Some(ref dir) => dir,
+        None => return Ok(()),
+    };
+    try!(fs::create_dir_all(dir));
+
+    let file_text = Tls::file_text();
+    let stem = file_text.path()
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+    let html_file = dir.join(format!("{}-{}.html", stem, user_nt));
+    log!(session,
+         Informative,
+         "writing report `{}`",
+         html_file.to_string_lossy());
+    let title = format!("{}: {}", file_text.path().display(), user_nt);
+    let mut output_file = try!(fs::File::create(&html_file));
+    lr1::generate_html_report(&mut output_file, grammar, &title, lr1result)
+}
+
+fn emit_rust_items<W: Write>(grammar: &r::Grammar, rust: &mut RustWrite<W>) -> io::Result<()> {
+    for item in &grammar.rust_items {
+        rust!(rust, "");
+        rust!(rust, "{}", item.code);
+    }
+    Ok(())
+}
+
+fn emit_inline_tests<W: Write>(grammar: &r::Grammar, rust: &mut RustWrite<W>) -> io::Result<()> {
+    if grammar.tests.is_empty() {
+        return Ok(());
+    }
+
+    rust!(rust, "");
+    rust!(rust, "#[cfg(test)]");
+    rust!(rust, "mod {}test {{", grammar.prefix);
+    for (index, test) in grammar.tests.iter().enumerate() {
+        let (method, verb) = if test.should_parse {
+            ("is_ok", "accept")
+        } else {
+            ("is_err", "reject")
+        };
+        rust!(rust, "");
+        rust!(rust, "#[test]");
+        rust!(rust, "#[allow(non_snake_case)]");
+        rust!(rust, "fn {}_{}() {{", Escape(test.nonterminal), index);
+        rust!(rust,
+              "assert!(super::parse_{}(\"{}\").{}(), \"`{}` should {} {{:?}}\", \"{}\");",
+              test.nonterminal,
+              test.input,
+              method,
+              test.nonterminal,
+              verb,
+              test.input);
+        rust!(rust, "}}");
+    }
+    rust!(rust, "}}");
+    Ok(())
+}
+
 fn emit_to_triple_trait<W: Write>(grammar: &r::Grammar, rust: &mut RustWrite<W>) -> io::Result<()> {
     #![allow(non_snake_case)]
 
@@ -489,3 +1510,138 @@ fn emit_to_triple_trait<W: Write>(grammar: &r::Grammar, rust: &mut RustWrite<W>)
 
     Ok(())
 }
+
+/// Writes `display_error`, which wraps a parse error so that it can be
+/// displayed even if the token type does not implement `Display`. The
+/// tokens of the built-in lexer are described by their text, and
+/// those of an external one by the name of the terminal they match.
+/// The scaffolding that the parsers share when the session has a
+/// `support_module`: a `ToTriple` trait like the one that
+/// `emit_to_triple_trait` writes, but which, rather than being tied to
+/// the types of one grammar, takes them as type parameters.
+fn emit_support_module() -> io::Result<Vec<u8>> {
+    let mut rust = RustWrite::new(vec![]);
+
+    rust!(rust, "//! Scaffolding shared by the parsers that LALRPOP generates.");
+    rust!(rust, "");
+    rust!(rust, "pub trait ToTriple<L, T, E> {{");
+    rust!(rust, "fn to_triple(value: Self) -> Result<(L, T, L), E>;");
+    rust!(rust, "}}");
+
+    // tokens with locations
+    rust!(rust, "");
+    rust!(rust, "impl<L, T, E> ToTriple<L, T, E> for (L, T, L) {{");
+    rust!(rust, "fn to_triple(value: Self) -> Result<(L, T, L), E> {{");
+    rust!(rust, "Ok(value)");
+    rust!(rust, "}}");
+    rust!(rust, "}}");
+    rust!(rust, "");
+    rust!(rust, "impl<L, T, E> ToTriple<L, T, E> for Result<(L, T, L), E> {{");
+    rust!(rust, "fn to_triple(value: Self) -> Result<(L, T, L), E> {{");
+    rust!(rust, "value");
+    rust!(rust, "}}");
+    rust!(rust, "}}");
+
+    // tokens without locations (these do not overlap with the
+    // impls above, since `T` would have to contain itself)
+    rust!(rust, "");
+    rust!(rust, "impl<T, E> ToTriple<(), T, E> for T {{");
+    rust!(rust, "fn to_triple(value: Self) -> Result<((), T, ()), E> {{");
+    rust!(rust, "Ok(((), value, ()))");
+    rust!(rust, "}}");
+    rust!(rust, "}}");
+    rust!(rust, "");
+    rust!(rust, "impl<T, E> ToTriple<(), T, E> for Result<T, E> {{");
+    rust!(rust, "fn to_triple(value: Self) -> Result<((), T, ()), E> {{");
+    rust!(rust, "value.map(|v| ((), v, ()))");
+    rust!(rust, "}}");
+    rust!(rust, "}}");
+
+    Ok(rust.into_inner())
+}
+
+fn emit_display_error_fn<W: Write>(session: &Session,
+                                   grammar: &r::Grammar,
+                                   rust: &mut RustWrite<W>)
+                                   -> io::Result<()> {
+    let parse_error_type = grammar.types.parse_error_type();
+    let referenced = parse_error_type.referenced();
+    let type_parameters: Vec<_> = grammar.type_parameters
+                                         .iter()
+                                         .filter(|t| referenced.contains(t))
+                                         .cloned()
+                                         .collect();
+
+    rust!(rust, "");
+    if session.visibility == Visibility::Private {
+        rust!(rust, "#[allow(dead_code)]");
+    }
+    rust!(rust,
+          "{}fn display_error<'{}error, {}>(error: &'{}error {}) \
+           -> {}lalrpop_util::ErrorDisplay<'{}error, {}, {}, {}> {{",
+          session.visibility.qualifier(),
+          grammar.prefix,
+          Sep(", ", &type_parameters),
+          grammar.prefix,
+          parse_error_type,
+          grammar.prefix,
+          grammar.prefix,
+          grammar.types.terminal_loc_type(),
+          grammar.types.terminal_token_type(),
+          grammar.types.error_type());
+    rust!(rust,
+          "fn {}describe<{}>({}token: &{}) -> ::std::string::String {{",
+          grammar.prefix,
+          Sep(", ", &type_parameters),
+          grammar.prefix,
+          grammar.types.terminal_token_type());
+    if grammar.intern_token.is_some() {
+        rust!(rust, "format!(\"{{:?}}\", {}token.1)", grammar.prefix);
+    } else {
+        rust!(rust, "match *{}token {{", grammar.prefix);
+        for (&terminal, pattern) in &grammar.conversions {
+            if terminal == r::TerminalString::Error || terminal == r::TerminalString::Any {
+                continue;
+            }
+            // Three # should hopefully be enough to prevent any
+            // reasonable terminal from escaping the literal
+            rust!(rust,
+                  "{} if true => r###\"{}\"###.to_string(),",
+                  pattern.map(&mut |_| "_"),
+                  terminal);
+        }
+        rust!(rust, "_ => \"_\".to_string(),");
+        rust!(rust, "}}");
+    }
+    rust!(rust, "}}");
+    rust!(rust,
+          "{}lalrpop_util::ErrorDisplay {{ error: error, describe: {}describe }}",
+          grammar.prefix,
+          grammar.prefix);
+    rust!(rust, "}}");
+    Ok(())
+}
+
+/// The trait through which the parser asks the grammar's first
+/// parameter about the conflicts of `#[conflict_hook]` nonterminals:
+/// `should_shift` is given the name of the nonterminal that could be
+/// reduced and the lookahead token, and says whether to shift it.
+fn emit_conflict_hook_trait<W: Write>(grammar: &r::Grammar,
+                                      rust: &mut RustWrite<W>)
+                                      -> io::Result<()> {
+    let mut user_type_parameters = String::new();
+    for type_parameter in &grammar.type_parameters {
+        user_type_parameters.push_str(&format!("{}, ", type_parameter));
+    }
+
+    rust!(rust, "");
+    rust!(rust,
+          "pub trait {}ConflictHook<{}> {{",
+          grammar.prefix,
+          user_type_parameters);
+    rust!(rust,
+          "fn should_shift(&self, nonterminal: &'static str, lookahead: &{}) -> bool;",
+          grammar.types.terminal_token_type());
+    rust!(rust, "}}");
+    Ok(())
+}
@@ -0,0 +1,221 @@
+//! The normalized grammar, and the automata built from it, in the
+//! public form of the `ir` module, for tools that analyze grammars and
+//! for other backends (see `Backend`). `lalrpop ir` prints the grammar
+//! as JSON.
+
+use grammar::repr as r;
+use ir;
+use lr1::{LR1State, Lr1Tls, Token};
+#[cfg(feature = "serde")]
+use serde_json;
+use session::Session;
+use tls::Tls;
+
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+use super::{build_states, load_file_text, parse_and_normalize_grammar};
+
+#[cfg(test)]
+mod test;
+
+pub fn process_file(session: Rc<Session>, lalrpop_file: &Path) -> io::Result<()> {
+    let grammar = try!(normalized_grammar(session, lalrpop_file));
+    print_json(&grammar)
+}
+
+#[cfg(feature = "serde")]
+fn print_json(grammar: &ir::Grammar) -> io::Result<()> {
+    let text = try!(serde_json::to_string_pretty(grammar));
+    println!("{}", text);
+    Ok(())
+}
+
+#[cfg(not(feature = "serde"))]
+fn print_json(_grammar: &ir::Grammar) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Other,
+                       "printing the normalized grammar as JSON needs the `serde` feature"))
+}
+
+pub fn normalized_grammar(session: Rc<Session>, lalrpop_file: &Path) -> io::Result<ir::Grammar> {
+    let file_text = Rc::new(try!(load_file_text(&session, lalrpop_file)));
+    let _tls = Tls::install(session.clone(), file_text.clone());
+
+    let grammar = try!(parse_and_normalize_grammar(&session, &file_text));
+    Ok(to_ir(&grammar))
+}
+
+pub fn to_ir(grammar: &r::Grammar) -> ir::Grammar {
+    ir::Grammar {
+        version: ir::VERSION,
+        type_parameters: grammar.type_parameters.iter().map(|t| t.to_string()).collect(),
+        where_clauses: grammar.where_clauses.clone(),
+        parameters: grammar.parameters
+                           .iter()
+                           .map(|p| {
+                               ir::Parameter {
+                                   name: p.name.to_string(),
+                                   ty: p.ty.to_string(),
+                               }
+                           })
+                           .collect(),
+        token_type: grammar.types.terminal_token_type().to_string(),
+        location_type: grammar.types.terminal_loc_type().to_string(),
+        error_type: grammar.types.error_type().to_string(),
+        terminals: grammar.terminals
+                          .all
+                          .iter()
+                          .map(|&t| {
+                              ir::Terminal {
+                                  name: t.to_string(),
+                                  ty: grammar.types.terminal_type(t).to_string(),
+                              }
+                          })
+                          .collect(),
+        nonterminals: grammar.nonterminals
+                             .values()
+                             .map(|data| {
+                                 ir::Nonterminal {
+                                     name: data.name.to_string(),
+                                     ty: grammar.types.nonterminal_type(data.name).to_string(),
+                                     span: (data.span.0, data.span.1),
+                                     productions: data.productions.iter().map(production).collect(),
+                                 }
+                             })
+                             .collect(),
+        start_symbols: grammar.start_nonterminals
+                              .iter()
+                              .map(|(public, start)| {
+                                  ir::StartSymbol {
+                                      public: public.to_string(),
+                                      start: start.to_string(),
+                                  }
+                              })
+                              .collect(),
+        actions: grammar.action_fn_defns.iter().map(action).collect(),
+    }
+}
+
+fn production(production: &r::Production) -> ir::Production {
+    ir::Production {
+        symbols: production.symbols.iter().map(symbol).collect(),
+        action: production.action.index(),
+        span: (production.span.0, production.span.1),
+        cost: production.cost,
+    }
+}
+
+fn symbol(symbol: &r::Symbol) -> ir::Symbol {
+    match *symbol {
+        r::Symbol::Terminal(t) => ir::Symbol::Terminal(t.to_string()),
+        r::Symbol::Nonterminal(nt) => ir::Symbol::Nonterminal(nt.to_string()),
+    }
+}
+
+fn action(defn: &r::ActionFnDefn) -> ir::Action {
+    let kind = match defn.kind {
+        r::ActionFnDefnKind::User(ref data) => {
+            ir::ActionKind::User(ir::UserAction {
+                arguments: data.arg_patterns
+                               .iter()
+                               .zip(&data.arg_types)
+                               .map(|(pattern, ty)| {
+                                   ir::Argument {
+                                       pattern: pattern.to_string(),
+                                       ty: ty.to_string(),
+                                   }
+                               })
+                               .collect(),
+                code: data.code.clone(),
+            })
+        }
+        r::ActionFnDefnKind::Inline(ref data) => {
+            ir::ActionKind::Inline(ir::InlineAction {
+                action: data.action.index(),
+                symbols: data.symbols
+                             .iter()
+                             .map(|s| match *s {
+                                 r::InlinedSymbol::Original(ref s) => {
+                                     ir::InlinedSymbol::Original(symbol(s))
+                                 }
+                                 r::InlinedSymbol::Inlined(action, ref symbols) => {
+                                     ir::InlinedSymbol::Inlined(action.index(),
+                                                                symbols.iter()
+                                                                       .map(symbol)
+                                                                       .collect())
+                                 }
+                             })
+                             .collect(),
+            })
+        }
+        r::ActionFnDefnKind::Lookaround(r::LookaroundActionFnDefn::Lookahead) => {
+            ir::ActionKind::Lookahead
+        }
+        r::ActionFnDefnKind::Lookaround(r::LookaroundActionFnDefn::Lookbehind) => {
+            ir::ActionKind::Lookbehind
+        }
+        r::ActionFnDefnKind::Lookaround(r::LookaroundActionFnDefn::LookaheadToken) => {
+            ir::ActionKind::LookaheadToken
+        }
+    };
+    ir::Action {
+        ret_type: defn.ret_type.to_string(),
+        fallible: defn.fallible,
+        kind: kind,
+    }
+}
+
+/// The automaton of the parser for each public nonterminal of
+/// `grammar`, built as `process_file` builds it.
+pub fn automata(session: &Session,
+                grammar: &r::Grammar,
+                report_file: &Path)
+                -> io::Result<Vec<ir::Automaton>> {
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let mut automata = vec![];
+    for (&user_nt, &start_nt) in &grammar.start_nonterminals {
+        let states = try!(build_states(session, grammar, user_nt, start_nt, report_file));
+        automata.push(ir::Automaton {
+            start_symbol: ir::StartSymbol {
+                public: user_nt.to_string(),
+                start: start_nt.to_string(),
+            },
+            states: states.iter().map(|state| to_ir_state(grammar, state)).collect(),
+        });
+    }
+    Ok(automata)
+}
+
+fn to_ir_state(grammar: &r::Grammar, state: &LR1State) -> ir::State {
+    ir::State {
+        shifts: state.shifts.iter().map(|(t, s)| (t.to_string(), s.0)).collect(),
+        reductions: state.reductions
+                         .iter()
+                         .map(|&(ref lookahead, production)| {
+                             let productions = grammar.productions_for(production.nonterminal);
+                             ir::Reduction {
+                                 nonterminal: production.nonterminal.to_string(),
+                                 production: productions.iter()
+                                                        .position(|p| p == production)
+                                                        .unwrap(),
+                                 lookahead: lookahead.iter().map(to_ir_lookahead).collect(),
+                             }
+                         })
+                         .collect(),
+        gotos: state.gotos.iter().map(|(nt, s)| (nt.to_string(), s.0)).collect(),
+        undecided: state.lookahead2
+                        .keys()
+                        .chain(state.conflict_hooks.keys())
+                        .map(|t| t.to_string())
+                        .collect(),
+    }
+}
+
+fn to_ir_lookahead(token: Token) -> ir::Lookahead {
+    match token {
+        Token::Terminal(t) => ir::Lookahead::Terminal(t.to_string()),
+        Token::EOF => ir::Lookahead::Eof,
+        Token::Error => ir::Lookahead::Error,
+    }
+}
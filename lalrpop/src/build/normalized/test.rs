@@ -0,0 +1,96 @@
+use ir;
+#[cfg(feature = "serde")]
+use serde_json;
+use std::path::Path;
+use test_util::normalized_grammar;
+use tls::Tls;
+
+use super::{automata, to_ir};
+
+fn sum_grammar() -> ir::Grammar {
+    let _tls = Tls::test();
+    to_ir(&normalized_grammar(r#"
+grammar;
+    pub Sum: u32 = {
+        <l:Sum> "+" <r:Num> => l + r,
+        Num,
+    };
+    Num: u32 = <n:r"[0-9]+"> => n.parse().unwrap();
+"#))
+}
+
+#[test]
+fn symbols_and_productions() {
+    let grammar = sum_grammar();
+    assert_eq!(grammar.version, ir::VERSION);
+    assert_eq!(grammar.start_symbols,
+               vec![ir::StartSymbol {
+                        public: "Sum".to_string(),
+                        start: "__Sum".to_string(),
+                    }]);
+
+    let terminals: Vec<&str> = grammar.terminals.iter().map(|t| &t.name[..]).collect();
+    assert_eq!(terminals, vec![r#""+""#, r##"r#"[0-9]+"#"##]);
+
+    let sum = grammar.nonterminals.iter().find(|nt| nt.name == "Sum").unwrap();
+    assert_eq!(sum.ty, "u32");
+    assert_eq!(sum.productions.len(), 2);
+    assert_eq!(sum.productions[0].symbols,
+               vec![ir::Symbol::Nonterminal("Sum".to_string()),
+                    ir::Symbol::Terminal(r#""+""#.to_string()),
+                    ir::Symbol::Nonterminal("Num".to_string())]);
+
+    match grammar.actions[sum.productions[0].action].kind {
+        ir::ActionKind::User(ref action) => {
+            assert_eq!(action.code, "l + r");
+            assert_eq!(action.arguments.len(), 3);
+            assert_eq!(action.arguments[0].pattern, "l");
+            assert_eq!(action.arguments[0].ty, "u32");
+        }
+        ref kind => panic!("expected a user action, found {:?}", kind),
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json_round_trip() {
+    let grammar = sum_grammar();
+    let text = serde_json::to_string(&grammar).unwrap();
+    let decoded: ir::Grammar = serde_json::from_str(&text).unwrap();
+    assert_eq!(decoded, grammar);
+}
+
+#[test]
+fn automaton() {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(r#"
+grammar;
+    pub Sum: u32 = {
+        <l:Sum> "+" <r:Num> => l + r,
+        Num,
+    };
+    Num: u32 = <n:r"[0-9]+"> => n.parse().unwrap();
+"#);
+    let automata = automata(&Tls::session(), &grammar, Path::new("")).unwrap();
+    assert_eq!(automata.len(), 1);
+    assert_eq!(automata[0].start_symbol.public, "Sum");
+
+    // the initial state shifts a number and goes to `Sum` or `Num`
+    let initial = &automata[0].states[0];
+    assert_eq!(initial.shifts.len(), 1);
+    assert_eq!(initial.shifts[0].0, r##"r#"[0-9]+"#"##);
+    let mut gotos: Vec<&str> = initial.gotos.iter().map(|g| &g.0[..]).collect();
+    gotos.sort();
+    assert_eq!(gotos, vec!["Num", "Sum"]);
+    assert!(initial.reductions.is_empty());
+
+    // after a number, `Num` is reduced on `+` and at the end
+    let number = &automata[0].states[initial.shifts[0].1];
+    assert_eq!(number.reductions.len(), 1);
+    assert_eq!(number.reductions[0].nonterminal, "Num");
+    assert_eq!(number.reductions[0].production, 0);
+    assert!(number.reductions[0].lookahead.contains(&ir::Lookahead::Eof));
+    assert!(number.reductions[0]
+                .lookahead
+                .contains(&ir::Lookahead::Terminal(r#""+""#.to_string())));
+}
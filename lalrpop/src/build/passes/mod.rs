@@ -0,0 +1,88 @@
+//! Runs the passes that embedders add (see `Pass`) on the normalized
+//! grammar: each gets the grammar as `ir::Grammar`, and what it changes
+//! there is carried back into the grammar that the parsers are built
+//! from.
+
+use collections::{map, set, Map};
+use grammar::repr as r;
+use intern::intern;
+use ir;
+use session::Session;
+
+use super::to_ir;
+
+#[cfg(test)]
+mod test;
+
+const UNSUPPORTED: &'static str = "a normalization pass can only rename nonterminals and change \
+                                   the code of actions";
+
+pub fn run_passes(session: &Session, mut grammar: r::Grammar) -> Result<r::Grammar, String> {
+    for pass in &session.passes {
+        let before = to_ir(&grammar);
+        let mut after = before.clone();
+        try!(pass.run(&mut after));
+        try!(apply(&mut grammar, &before, &after));
+    }
+    Ok(grammar)
+}
+
+/// Carries the changes from `before`, the IR of `grammar`, to `after`
+/// into `grammar`.
+fn apply(grammar: &mut r::Grammar,
+         before: &ir::Grammar,
+         after: &ir::Grammar)
+         -> Result<(), String> {
+    if after.nonterminals.len() != before.nonterminals.len() ||
+       after.actions.len() != before.actions.len() {
+        return Err(UNSUPPORTED.to_string());
+    }
+
+    let mut renames: Map<String, String> = map();
+    for (old, new) in before.nonterminals.iter().zip(&after.nonterminals) {
+        if old.name != new.name {
+            renames.insert(old.name.clone(), new.name.clone());
+        }
+    }
+    let mut names = set();
+    for nonterminal in &after.nonterminals {
+        if nonterminal.name.is_empty() {
+            return Err("a normalization pass gave a nonterminal an empty name".to_string());
+        }
+        if !names.insert(&nonterminal.name[..]) {
+            return Err(format!("a normalization pass gave two nonterminals the name `{}`",
+                               nonterminal.name));
+        }
+    }
+
+    // apart from the names and the code, `after` should be `before`;
+    // the symbols of the productions still have the old names, which
+    // `rename_nonterminals` replaces below
+    let mut expected = before.clone();
+    for (nonterminal, new) in expected.nonterminals.iter_mut().zip(&after.nonterminals) {
+        nonterminal.name = new.name.clone();
+    }
+    for (action, new) in expected.actions.iter_mut().zip(&after.actions) {
+        if let (&mut ir::ActionKind::User(ref mut action), &ir::ActionKind::User(ref new)) =
+               (&mut action.kind, &new.kind) {
+            action.code = new.code.clone();
+        }
+    }
+    if expected != *after {
+        return Err(UNSUPPORTED.to_string());
+    }
+
+    for (defn, action) in grammar.action_fn_defns.iter_mut().zip(&after.actions) {
+        if let (&mut r::ActionFnDefnKind::User(ref mut data), &ir::ActionKind::User(ref action)) =
+               (&mut defn.kind, &action.kind) {
+            data.code = action.code.clone();
+        }
+    }
+    let renames = renames.iter()
+                         .map(|(old, new)| {
+                             (r::NonterminalString(intern(old)), r::NonterminalString(intern(new)))
+                         })
+                         .collect();
+    grammar.rename_nonterminals(&renames);
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use grammar::repr as r;
+use intern::intern;
+use ir;
+use pass::Pass;
+use session::Session;
+use std::rc::Rc;
+use test_util::normalized_grammar;
+use tls::Tls;
+
+use super::run_passes;
+
+struct RenameAndTrace;
+
+impl Pass for RenameAndTrace {
+    fn run(&self, grammar: &mut ir::Grammar) -> Result<(), String> {
+        for nonterminal in &mut grammar.nonterminals {
+            if nonterminal.name == "Num" {
+                nonterminal.name = "Number".to_string();
+            }
+        }
+        for action in &mut grammar.actions {
+            if let ir::ActionKind::User(ref mut action) = action.kind {
+                action.code = format!("{{ trace(); {} }}", action.code);
+            }
+        }
+        Ok(())
+    }
+}
+
+struct DropProductions;
+
+impl Pass for DropProductions {
+    fn run(&self, grammar: &mut ir::Grammar) -> Result<(), String> {
+        for nonterminal in &mut grammar.nonterminals {
+            nonterminal.productions.truncate(1);
+        }
+        Ok(())
+    }
+}
+
+struct Fail;
+
+impl Pass for Fail {
+    fn run(&self, _: &mut ir::Grammar) -> Result<(), String> {
+        Err("no tracing for you".to_string())
+    }
+}
+
+fn run(passes: Vec<Rc<Pass>>) -> Result<r::Grammar, String> {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(r#"
+grammar;
+    pub Sum: u32 = {
+        <l:Sum> "+" <r:Num> => l + r,
+        Num,
+    };
+    Num: u32 = <n:r"[0-9]+"> => n.parse().unwrap();
+"#);
+    let mut session = Session::test();
+    session.passes = passes;
+    run_passes(&session, grammar)
+}
+
+#[test]
+fn rename_and_change_code() {
+    let grammar = run(vec![Rc::new(RenameAndTrace)]).unwrap();
+    let num = r::NonterminalString(intern("Num"));
+    let number = r::NonterminalString(intern("Number"));
+    assert!(!grammar.nonterminals.contains_key(&num));
+    assert_eq!(grammar.nonterminals[&number].name, number);
+    assert_eq!(grammar.types.nonterminal_type(number).to_string(), "u32");
+
+    let sum = &grammar.nonterminals[&r::NonterminalString(intern("Sum"))];
+    assert_eq!(sum.productions[0].symbols[2], r::Symbol::Nonterminal(number));
+    match grammar.action_fn_defns[sum.productions[0].action.index()].kind {
+        r::ActionFnDefnKind::User(ref data) => assert_eq!(data.code, "{ trace(); l + r }"),
+        _ => panic!("expected a user action"),
+    }
+}
+
+#[test]
+fn other_changes() {
+    let message = run(vec![Rc::new(DropProductions)]).unwrap_err();
+    assert_eq!(message,
+               "a normalization pass can only rename nonterminals and change the code of actions");
+}
+
+#[test]
+fn pass_error() {
+    let message = run(vec![Rc::new(RenameAndTrace), Rc::new(Fail)]).unwrap_err();
+    assert_eq!(message, "no tracing for you");
+}
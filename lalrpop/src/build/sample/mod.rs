@@ -0,0 +1,105 @@
+//! Checks whether a grammar accepts sample inputs, without generating
+//! and compiling a parser: the LR(1) tables are run by the interpreter
+//! instead, so that the author of a grammar can try a change in
+//! seconds.
+
+use grammar::repr::*;
+use lr1::{self, Lr1Tls, Token};
+use lr1::interpret::{self, Step};
+use session::Session;
+use tls::Tls;
+
+use std::io::{self, Read};
+use std::path::Path;
+use std::rc::Rc;
+
+use super::{corpus, load_file_text, parse_and_normalize_grammar};
+
+#[cfg(test)]
+mod test;
+
+/// Parses each of `samples`, a path or `-` for stdin, printing whether
+/// it was accepted or else where it was rejected and why.
+pub fn process_files(session: Rc<Session>,
+                     lalrpop_file: &Path,
+                     samples: &[&Path],
+                     start: Option<&str>,
+                     names: bool)
+                     -> io::Result<()> {
+    let file_text = Rc::new(try!(load_file_text(&session, lalrpop_file)));
+    let _tls = Tls::install(session.clone(), file_text.clone());
+
+    // with the names of the terminals given, the grammar may use any
+    // lexer
+    let grammar = if names {
+        try!(parse_and_normalize_grammar(&session, &file_text))
+    } else {
+        try!(corpus::load_grammar(&session, &file_text, "parse samples with"))
+    };
+    let start_nt = try!(corpus::start_nonterminal(&grammar, start));
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let states = corpus::build_states(&grammar, start_nt);
+
+    let mut rejected = 0;
+    for &sample in samples {
+        let (name, input) = if sample == Path::new("-") {
+            let mut input = String::new();
+            try!(io::stdin().read_to_string(&mut input));
+            ("<stdin>".to_string(), input)
+        } else {
+            (sample.display().to_string(), try!(corpus::read_file(sample)))
+        };
+
+        match parse_sample(&grammar, &states, &input, names) {
+            Ok(()) => println!("{}: accepted", name),
+            Err((offset, message)) => {
+                let (line, column) = line_column(&input, offset);
+                println!("{}:{}:{}: {}", name, line, column, message);
+                rejected += 1;
+            }
+        }
+    }
+
+    if rejected > 0 {
+        return Err(corpus::other_error(format!("{} of {} sample(s) rejected",
+                                               rejected,
+                                               samples.len())));
+    }
+    Ok(())
+}
+
+/// Parses `input` (see `corpus::lex` for `names`). If it is rejected,
+/// returns the offset of the token at which the parser gave up (the
+/// end of the input for an unexpected end) and why.
+pub fn parse_sample(grammar: &Grammar,
+                    states: &[lr1::LR1State],
+                    input: &str,
+                    names: bool)
+                    -> Result<(), (usize, String)> {
+    let tokens = try!(corpus::lex(grammar, input, names));
+    let terminals = tokens.iter().map(|&(terminal, _)| terminal).collect();
+    let (result, steps) = interpret::interpret_steps(states, terminals);
+    match result {
+        Ok(_) => Ok(()),
+        Err((_, Token::Terminal(terminal))) => {
+            // every token before this one was shifted
+            let shifted = steps.iter()
+                               .filter(|step| match **step {
+                                   Step::Shift(..) => true,
+                                   Step::Reduce(..) => false,
+                               })
+                               .count();
+            Err((corpus::offset_in(input, tokens[shifted].1),
+                 format!("unexpected token {}", terminal)))
+        }
+        Err((_, Token::EOF)) => Err((input.len(), "unexpected end of input".to_string())),
+        Err((_, Token::Error)) => unreachable!(),
+    }
+}
+
+/// The line and column, both counted from 1, of `offset` in `input`.
+fn line_column(input: &str, offset: usize) -> (usize, usize) {
+    let before = &input[..offset];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
@@ -0,0 +1,54 @@
+use grammar::repr::*;
+use intern::intern;
+use lr1::{self, Lr1Tls};
+use test_util::normalized_grammar;
+use tls::Tls;
+
+use super::{line_column, parse_sample};
+
+const GRAMMAR: &'static str = r#"
+grammar;
+    pub Expr = {
+        Expr "+" Term,
+        Term,
+    };
+
+    Term = {
+        r"[0-9]+",
+        "(" Expr ")",
+    };
+"#;
+
+fn check_samples(inputs: &[(&str, bool, Result<(), (usize, &str)>)]) {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(GRAMMAR);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let start_nt = grammar.start_nonterminals[&NonterminalString(intern("Expr"))];
+    let states = lr1::build_states(&grammar, start_nt).unwrap();
+    for &(input, names, expected) in inputs {
+        let expected = expected.map_err(|(offset, message)| (offset, message.to_string()));
+        assert_eq!(parse_sample(&grammar, &states, input, names), expected, "{}", input);
+    }
+}
+
+#[test]
+fn accepted() {
+    check_samples(&[("1 + (2 + 3)", false, Ok(())),
+                    (r##""(" r#"[0-9]+"# ")""##, true, Ok(()))]);
+}
+
+#[test]
+fn rejected() {
+    check_samples(&[("1 + )", false, Err((4, r#"unexpected token ")""#))),
+                    ("(1 +", false, Err((4, "unexpected end of input"))),
+                    ("1 ? 2", false, Err((2, "invalid token"))),
+                    (r#""(" Num"#, true, Err((4, "no terminal named Num")))]);
+}
+
+#[test]
+fn line_columns() {
+    let input = "1 +\n  (2\nü)";
+    assert_eq!(line_column(input, 0), (1, 1));
+    assert_eq!(line_column(input, 6), (2, 3));
+    assert_eq!(line_column(input, input.len() - 1), (3, 2));
+}
@@ -0,0 +1,143 @@
+//! Steps through the parse of a single input, a move of the LR(1)
+//! parser at a time: after each shift or reduction, shows the states
+//! on the stack and the part of the derivation built so far, drawn
+//! like the examples in conflict reports, so that the author of a
+//! grammar can watch where the parser goes wrong.
+
+use grammar::repr::*;
+use lr1::{self, Lr1Tls, StateIndex};
+use lr1::interpret::{self, ParseTree, Step};
+use message::Content;
+use message::builder::InlineBuilder;
+use session::Session;
+use tls::Tls;
+use atty;
+
+use std::io::{self, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use super::{corpus, load_file_text, parse_and_normalize_grammar, report_content};
+use super::explain::{error_text, step_text};
+
+#[cfg(test)]
+mod test;
+
+/// Prints the moves of the parser for `input` one at a time. On a
+/// terminal, waits for a line of input between them, stopping early
+/// if it is `q`.
+pub fn process_file(session: Rc<Session>,
+                    lalrpop_file: &Path,
+                    input: &str,
+                    start: Option<&str>,
+                    names: bool)
+                    -> io::Result<()> {
+    let file_text = Rc::new(try!(load_file_text(&session, lalrpop_file)));
+    let _tls = Tls::install(session.clone(), file_text.clone());
+
+    // with the names of the terminals given, the grammar may use any
+    // lexer
+    let grammar = if names {
+        try!(parse_and_normalize_grammar(&session, &file_text))
+    } else {
+        try!(corpus::load_grammar(&session, &file_text, "simulate inputs with"))
+    };
+    let start_nt = try!(corpus::start_nonterminal(&grammar, start));
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let states = corpus::build_states(&grammar, start_nt);
+
+    let (frames, accepted) = match simulate(&grammar, &states, input, names) {
+        Ok(frames) => (frames, true),
+        Err(frames) => (frames, false),
+    };
+
+    let interactive = atty::is();
+    let stdin = io::stdin();
+    for (index, frame) in frames.iter().enumerate() {
+        if index > 0 {
+            println!("");
+        }
+        let _ = report_content(&**frame);
+        if interactive && index + 1 < frames.len() {
+            print!("(press Enter for the next move, or q and Enter to stop) ");
+            try!(io::stdout().flush());
+            let mut line = String::new();
+            if try!(stdin.read_line(&mut line)) == 0 || line.trim() == "q" {
+                break;
+            }
+        }
+    }
+
+    if !accepted {
+        return Err(corpus::other_error("the input does not parse".to_string()));
+    }
+    Ok(())
+}
+
+/// Parses `input` (see `corpus::lex` for `names`), and describes each
+/// move of the parser: the move itself, as `explain` describes it,
+/// the states on the stack after it, and the trees on the stack
+/// followed by the tokens yet to be shifted. If the input does not
+/// parse, the last description is of the error instead, and they are
+/// returned as an `Err`.
+pub fn simulate(grammar: &Grammar,
+                states: &[lr1::LR1State],
+                input: &str,
+                names: bool)
+                -> Result<Vec<Box<Content>>, Vec<Box<Content>>> {
+    let tokens = try!(corpus::lex(grammar, input, names).map_err(|(offset, message)| {
+        vec![InlineBuilder::new().text(format!("error: {} at offset {}", message, offset)).end()]
+    }));
+    let terminals: Vec<TerminalString> = tokens.iter().map(|&(terminal, _)| terminal).collect();
+    let (result, frames) = interpret::interpret_frames(states, terminals.clone());
+
+    let mut contents = vec![];
+    let mut lexemes = tokens.iter().map(|&(_, lexeme)| lexeme);
+    let mut shifted = 0;
+    for (index, frame) in frames.iter().enumerate() {
+        if let Step::Shift(..) = frame.step {
+            shifted += 1;
+        }
+        let heading = format!("move {} of {}: {}",
+                              index + 1,
+                              frames.len(),
+                              step_text(frame.step, &mut lexemes));
+        contents.push(frame_content(heading,
+                                    &frame.states,
+                                    &frame.trees,
+                                    &terminals[shifted..]));
+    }
+
+    match result {
+        Ok(_) => Ok(contents),
+        Err((state, token)) => {
+            // the stacks are as the last move left them
+            let (states, trees) = match frames.last() {
+                Some(frame) => (frame.states.clone(), frame.trees.clone()),
+                None => (vec![state.index], vec![]),
+            };
+            contents.push(frame_content(error_text(state.index, token),
+                                        &states,
+                                        &trees,
+                                        &terminals[shifted..]));
+            Err(contents)
+        }
+    }
+}
+
+fn frame_content(heading: String,
+                 states: &[StateIndex],
+                 trees: &[ParseTree],
+                 remaining: &[TerminalString])
+                 -> Box<Content> {
+    let states: Vec<String> = states.iter().map(|state| format!("{:?}", state)).collect();
+    let builder = InlineBuilder::new()
+        .begin_lines()
+        .text(heading)
+        .text(format!("stack: {}", states.join(" ")));
+    if trees.is_empty() && remaining.is_empty() {
+        builder.end().end()
+    } else {
+        builder.push(interpret::picture(trees, remaining)).end().end()
+    }
+}
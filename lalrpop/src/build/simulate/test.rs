@@ -0,0 +1,84 @@
+use grammar::repr::*;
+use intern::intern;
+use lr1::{self, Lr1Tls};
+use test_util::normalized_grammar;
+use tls::Tls;
+
+use super::simulate;
+
+const GRAMMAR: &'static str = r#"
+grammar;
+    pub Expr = {
+        Expr "+" Term,
+        Term,
+    };
+
+    Term = {
+        r"[0-9]+",
+        "(" Expr ")",
+    };
+"#;
+
+/// The lines of each move, and whether the input was accepted.
+fn simulate_lines(input: &str) -> (Vec<Vec<String>>, bool) {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(GRAMMAR);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let start_nt = grammar.start_nonterminals[&NonterminalString(intern("Expr"))];
+    let states = lr1::build_states(&grammar, start_nt).unwrap();
+    let (contents, accepted) = match simulate(&grammar, &states, input, false) {
+        Ok(contents) => (contents, true),
+        Err(contents) => (contents, false),
+    };
+    let moves = contents.iter()
+                        .map(|content| {
+                            content.emit_to_canvas(80)
+                                   .to_strings()
+                                   .iter()
+                                   .map(|row| row.to_string().trim_right().to_string())
+                                   .collect()
+                        })
+                        .collect();
+    (moves, accepted)
+}
+
+#[test]
+fn moves_of_accepted_input() {
+    let (moves, accepted) = simulate_lines("1 + 2");
+    assert!(accepted);
+
+    // the number is shifted, with the rest of the input still to come
+    let first = &moves[0];
+    assert!(first[0].starts_with("move 1 of "), "{:#?}", first);
+    assert!(first[0].contains(": S0: shift r#\"[0-9]+\"# \"1\", go to S"), "{:#?}", first);
+    assert!(first[1].starts_with("stack: S0 S"), "{:#?}", first);
+    assert_eq!(first[2].trim(), r##"r#"[0-9]+"# "+" r#"[0-9]+"#"##);
+
+    // and then reduced to a `Term`, drawn below it
+    let second = &moves[1];
+    assert!(second[0].contains("reduce Term = r#\"[0-9]+\"#, go to S"), "{:#?}", second);
+    assert!(second[3..].iter().any(|line| line.contains("Term")), "{:#?}", second);
+
+    let last = moves.last().unwrap();
+    assert!(last[0].ends_with(", accept"), "{:#?}", last);
+    assert_eq!(last[1], "stack: S0");
+}
+
+#[test]
+fn moves_to_error() {
+    let (moves, accepted) = simulate_lines("1 + )");
+    assert!(!accepted);
+
+    let last = moves.last().unwrap();
+    assert!(last[0].ends_with(": error: unexpected token \")\""), "{:#?}", last);
+    assert!(last[2].trim().ends_with("\"+\" \")\""), "{:#?}", last);
+}
+
+#[test]
+fn lexing_error() {
+    let (moves, accepted) = simulate_lines("1 + ?");
+    assert!(!accepted);
+    assert_eq!(moves.len(), 1);
+    assert!(moves[0][0].starts_with("error: "), "{:#?}", moves[0]);
+    assert!(moves[0][0].ends_with(" at offset 4"), "{:#?}", moves[0]);
+}
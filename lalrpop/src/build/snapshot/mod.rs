@@ -0,0 +1,182 @@
+//! Snapshot testing for grammars. We run the grammar over every file
+//! in a corpus directory and record the parse tree (or the error) for
+//! `foo.txt` in `foo.txt.snap`. Later runs can then verify that a
+//! change to the grammar did not alter how any of the inputs parse.
+
+use diff;
+use grammar::repr::*;
+use intern;
+use lr1::{self, Lr1Tls};
+use lr1::interpret::ParseTree;
+use session::Session;
+use tls::Tls;
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use super::{corpus, load_file_text};
+
+#[cfg(test)]
+mod test;
+
+pub fn process_corpus(session: Rc<Session>,
+                      lalrpop_file: &Path,
+                      corpus_dir: &Path,
+                      start: Option<&str>,
+                      verify: bool)
+                      -> io::Result<()> {
+    let file_text = Rc::new(try!(load_file_text(&session, lalrpop_file)));
+    let _tls = Tls::install(session.clone(), file_text.clone());
+
+    let grammar = try!(corpus::load_grammar(&session, &file_text, "snapshot"));
+    let start_nt = try!(corpus::start_nonterminal(&grammar, start));
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let states = corpus::build_states(&grammar, start_nt);
+
+    let mut mismatches = 0;
+    for input_file in try!(corpus::corpus_files(corpus_dir)) {
+        let input = try!(corpus::read_file(&input_file));
+        let actual = snapshot(&grammar, &states, &input, session.snapshot_trivia);
+
+        let snap_file = snap_file(&input_file);
+        if !verify {
+            log!(session, Informative, "writing snapshot `{}`", snap_file.display());
+            try!(try!(fs::File::create(&snap_file)).write_all(actual.as_bytes()));
+            continue;
+        }
+
+        log!(session, Verbose, "verifying snapshot `{}`", snap_file.display());
+        let expected = match corpus::read_file(&snap_file) {
+            Ok(expected) => expected,
+            Err(_) => {
+                println!("missing snapshot `{}`", snap_file.display());
+                mismatches += 1;
+                continue;
+            }
+        };
+
+        if actual != expected {
+            println!("snapshot `{}` differs:", snap_file.display());
+            for diff in diff::lines(&expected, &actual) {
+                match diff {
+                    diff::Result::Left(l) => println!("- {}", l),
+                    diff::Result::Right(r) => println!("+ {}", r),
+                    diff::Result::Both(l, _) => println!("  {}", l),
+                }
+            }
+            mismatches += 1;
+        }
+    }
+
+    if mismatches > 0 {
+        return Err(corpus::other_error(format!("{} snapshot(s) did not match", mismatches)));
+    }
+    Ok(())
+}
+
+fn snap_file(input_file: &Path) -> PathBuf {
+    let mut name = input_file.file_name().unwrap().to_os_string();
+    name.push(".snap");
+    input_file.with_file_name(name)
+}
+
+/// Parses `input` and renders the outcome, one node of the parse tree
+/// per line. Terminals are followed by the text they matched, unless
+/// that is just the literal itself, and, with `trivia`, by the
+/// whitespace around it, so that the input can be rebuilt from the
+/// snapshot.
+pub fn snapshot(grammar: &Grammar,
+                states: &[lr1::LR1State],
+                input: &str,
+                trivia: bool)
+                -> String {
+    match corpus::parse(grammar, states, input) {
+        Ok((tree, lexemes)) => {
+            let tokens: Vec<Token> = if trivia {
+                attach_trivia(input, &lexemes)
+            } else {
+                lexemes.into_iter()
+                       .map(|lexeme| Token { leading: "", lexeme: lexeme, trailing: "" })
+                       .collect()
+            };
+            let mut out = String::new();
+            write_tree(&tree, &mut tokens.into_iter(), 0, &mut out);
+            out
+        }
+        Err(message) => format!("error: {}\n", message),
+    }
+}
+
+/// The text matched by a terminal, and the whitespace before and
+/// after it that the lexer skipped.
+struct Token<'input> {
+    leading: &'input str,
+    lexeme: &'input str,
+    trailing: &'input str,
+}
+
+/// Splits the whitespace between `lexemes`, which are slices of
+/// `input`, among them: each lexeme gets the whitespace after it up to
+/// the end of its line as its trailing trivia, and the rest goes to
+/// the next lexeme as its leading trivia (or to the last lexeme, at
+/// the end of the input).
+fn attach_trivia<'input>(input: &'input str, lexemes: &[&'input str]) -> Vec<Token<'input>> {
+    let mut tokens = vec![];
+    let mut leading_start = 0;
+    for (index, &lexeme) in lexemes.iter().enumerate() {
+        let start = corpus::offset_in(input, lexeme);
+        let end = start + lexeme.len();
+        let trailing_end = match lexemes.get(index + 1) {
+            Some(&next) => {
+                let gap = &input[end..corpus::offset_in(input, next)];
+                end + gap.find('\n').map(|newline| newline + 1).unwrap_or(gap.len())
+            }
+            None => input.len(),
+        };
+        tokens.push(Token {
+            leading: &input[leading_start..start],
+            lexeme: lexeme,
+            trailing: &input[end..trailing_end],
+        });
+        leading_start = trailing_end;
+    }
+    tokens
+}
+
+fn write_tree<'input, I>(tree: &ParseTree, tokens: &mut I, depth: usize, out: &mut String)
+    where I: Iterator<Item = Token<'input>>
+{
+    for _ in 0..depth {
+        out.push_str("  ");
+    }
+
+    match *tree {
+        ParseTree::Nonterminal(nt, ref children) => {
+            out.push_str(&format!("{}\n", nt));
+            for child in children {
+                write_tree(child, tokens, depth + 1, out);
+            }
+        }
+        ParseTree::Terminal(terminal) => {
+            let token = tokens.next().unwrap();
+            match terminal {
+                TerminalString::Literal(TerminalLiteral::Quoted(s))
+                    if intern::read(|interner| interner.data(s) == token.lexeme) => {
+                    out.push_str(&format!("{}", terminal));
+                }
+                _ => {
+                    out.push_str(&format!("{} {:?}", terminal, token.lexeme));
+                }
+            }
+            if !token.leading.is_empty() {
+                out.push_str(&format!(" leading {:?}", token.leading));
+            }
+            if !token.trailing.is_empty() {
+                out.push_str(&format!(" trailing {:?}", token.trailing));
+            }
+            out.push_str("\n");
+        }
+    }
+}
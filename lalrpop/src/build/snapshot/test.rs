@@ -0,0 +1,79 @@
+use grammar::repr::*;
+use intern::intern;
+use lr1::{self, Lr1Tls};
+use test_util::normalized_grammar;
+use tls::Tls;
+
+use super::snapshot;
+
+fn check_snapshot(grammar: &str, input: &str, trivia: bool, expected: &str) {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(grammar);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let start_nt = grammar.start_nonterminals[&NonterminalString(intern("Expr"))];
+    let states = lr1::build_states(&grammar, start_nt).unwrap();
+    let actual = snapshot(&grammar, &states, input, trivia);
+    assert_eq!(actual.trim(), expected.trim(), "\n{}", actual);
+}
+
+const GRAMMAR: &'static str = r#"
+grammar;
+    pub Expr = {
+        Expr "+" Term,
+        Term,
+    };
+
+    Term = {
+        r"[0-9]+",
+        "(" Expr ")",
+    };
+"#;
+
+#[test]
+fn parse_tree() {
+    check_snapshot(GRAMMAR, "1 + (22)", false, r##"
+Expr
+  Expr
+    Term
+      r#"[0-9]+"# "1"
+  "+"
+  Term
+    "("
+    Expr
+      Term
+        r#"[0-9]+"# "22"
+    ")"
+"##);
+}
+
+#[test]
+fn trivia() {
+    check_snapshot(GRAMMAR, "  1 +\n  (22)  \n", true, r##"
+Expr
+  Expr
+    Term
+      r#"[0-9]+"# "1" leading "  " trailing " "
+  "+" trailing "\n"
+  Term
+    "(" leading "  "
+    Expr
+      Term
+        r#"[0-9]+"# "22"
+    ")" trailing "  \n"
+"##);
+}
+
+#[test]
+fn invalid_token() {
+    check_snapshot(GRAMMAR, "1 + ?", false, "error: invalid token at offset 4");
+}
+
+#[test]
+fn unexpected_token() {
+    check_snapshot(GRAMMAR, "1 + + 2", false, r#"error: unexpected token "+""#);
+}
+
+#[test]
+fn unexpected_end_of_input() {
+    check_snapshot(GRAMMAR, "(1 + 2", false, "error: unexpected end of input");
+}
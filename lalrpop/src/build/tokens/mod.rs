@@ -0,0 +1,155 @@
+//! An inventory of the literal terminals of a grammar -- its keywords,
+//! operators and punctuation, and the regular expressions of its
+//! other tokens -- as JSON, from which syntax highlighters and
+//! documentation can be generated instead of being kept in sync with
+//! the grammar by hand.
+
+use grammar::parse_tree::TerminalLiteral;
+use grammar::repr::*;
+use rustc_serialize::json::Json;
+use session::Session;
+use tls::Tls;
+use unicode_xid::UnicodeXID;
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+use super::{load_file_text, parse_and_normalize_grammar};
+
+#[cfg(test)]
+mod test;
+
+pub fn process_file(session: Rc<Session>, lalrpop_file: &Path) -> io::Result<()> {
+    let file_text = Rc::new(try!(load_file_text(&session, lalrpop_file)));
+    let _tls = Tls::install(session.clone(), file_text.clone());
+
+    let grammar = try!(parse_and_normalize_grammar(&session, &file_text));
+    println!("{}", Json::Array(inventory(&grammar)).pretty());
+    Ok(())
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TokenKind {
+    /// A literal that could be mistaken for an identifier, like `"if"`.
+    Keyword,
+
+    /// Any other literal, like `"+"` or `"("`.
+    Punctuation,
+
+    /// A regular expression, like `r"[0-9]+"`.
+    Regex,
+}
+
+impl TokenKind {
+    fn name(self) -> &'static str {
+        match self {
+            TokenKind::Keyword => "keyword",
+            TokenKind::Punctuation => "punctuation",
+            TokenKind::Regex => "regex",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Token {
+    pub kind: TokenKind,
+
+    /// The text of the literal, or the regular expression.
+    pub text: String,
+
+    /// The terminal as it is written in the grammar, which differs
+    /// from the literal if a `match` block gave it another name.
+    pub terminal: String,
+
+    /// Whether the literal was declared in a `reserved` block.
+    pub reserved: bool,
+}
+
+impl Token {
+    fn to_json(&self) -> Json {
+        let mut object = BTreeMap::new();
+        object.insert("kind".to_string(), Json::String(self.kind.name().to_string()));
+        object.insert("text".to_string(), Json::String(self.text.clone()));
+        object.insert("terminal".to_string(), Json::String(self.terminal.clone()));
+        object.insert("reserved".to_string(), Json::Boolean(self.reserved));
+        Json::Object(object)
+    }
+}
+
+/// The tokens of `grammar`: keywords first, then punctuation, then
+/// regular expressions, each sorted by their text.
+pub fn tokens(grammar: &Grammar) -> Vec<Token> {
+    let mut tokens: Vec<Token> = match grammar.intern_token {
+        // with the internal tokenizer, the match entries tell us the
+        // literal behind each terminal, including reserved words that
+        // the grammar does not use yet
+        Some(ref intern_token) => {
+            intern_token.match_entries
+                        .iter()
+                        .map(|entry| {
+                            let reserved = match entry.match_literal {
+                                TerminalLiteral::Quoted(s) => {
+                                    intern_token.reserved_words
+                                                .iter()
+                                                .any(|word| word.word == s)
+                                }
+                                TerminalLiteral::Regex(_) => false,
+                            };
+                            token(entry.match_literal, entry.user_name.to_string(), reserved)
+                        })
+                        .collect()
+        }
+
+        // with an external tokenizer, the quoted terminals are the
+        // best guess at the text of the tokens
+        None => {
+            grammar.terminals
+                   .all
+                   .iter()
+                   .filter_map(|terminal| match *terminal {
+                       TerminalString::Literal(literal) => {
+                           Some(token(literal, terminal.to_string(), false))
+                       }
+                       // `_` stands for whatever other tokens there are,
+                       // which have no text of their own
+                       TerminalString::Bare(_) |
+                       TerminalString::Error |
+                       TerminalString::Any => None,
+                   })
+                   .collect()
+        }
+    };
+    tokens.sort();
+    tokens.dedup();
+    tokens
+}
+
+fn inventory(grammar: &Grammar) -> Vec<Json> {
+    tokens(grammar).iter().map(|token| token.to_json()).collect()
+}
+
+fn token(literal: TerminalLiteral, terminal: String, reserved: bool) -> Token {
+    let (kind, text) = match literal {
+        TerminalLiteral::Quoted(s) => {
+            let text = s.to_string();
+            let mut chars = text.chars();
+            let word = match chars.next() {
+                Some(c) => {
+                    (UnicodeXID::is_xid_start(c) || c == '_') &&
+                    chars.all(|c| UnicodeXID::is_xid_continue(c) || c == '_')
+                }
+                None => false,
+            };
+            (if word { TokenKind::Keyword } else { TokenKind::Punctuation }, text)
+        }
+        TerminalLiteral::Regex(s) => (TokenKind::Regex, s.to_string()),
+    };
+    Token {
+        kind: kind,
+        text: text,
+        terminal: terminal,
+        reserved: reserved,
+    }
+}
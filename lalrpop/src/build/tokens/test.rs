@@ -0,0 +1,82 @@
+use test_util::normalized_grammar;
+use tls::Tls;
+
+use super::{tokens, TokenKind};
+
+fn check_tokens(grammar: &str, expected: Vec<(TokenKind, &str, &str, bool)>) {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(grammar);
+    let actual: Vec<(TokenKind, String, String, bool)> =
+        tokens(&grammar)
+            .into_iter()
+            .map(|token| (token.kind, token.text, token.terminal, token.reserved))
+            .collect();
+    let expected: Vec<(TokenKind, String, String, bool)> =
+        expected.into_iter()
+                .map(|(kind, text, terminal, reserved)| {
+                    (kind, text.to_string(), terminal.to_string(), reserved)
+                })
+                .collect();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn keywords_punctuation_and_regexes() {
+    check_tokens(r#"
+grammar;
+    reserved { "await" }
+    pub Item = "fn" Id "(" ")" "->"?;
+    Id = r"[a-z]+";
+"#,
+                 vec![(TokenKind::Keyword, "await", r#""await""#, true),
+                      (TokenKind::Keyword, "fn", r#""fn""#, false),
+                      (TokenKind::Punctuation, "(", r#""(""#, false),
+                      (TokenKind::Punctuation, ")", r#"")""#, false),
+                      (TokenKind::Punctuation, "->", r#""->""#, false),
+                      (TokenKind::Regex, "[a-z]+", r##"r#"[a-z]+"#"##, false)]);
+}
+
+#[test]
+fn renamed_terminals() {
+    check_tokens(r#"
+grammar;
+    match {
+        "fn" => FN,
+        r"[a-z]+" => ID,
+        "(",
+        ")",
+    }
+    pub Item = FN ID "(" ")";
+"#,
+                 vec![(TokenKind::Keyword, "fn", "FN", false),
+                      (TokenKind::Punctuation, "(", r#""(""#, false),
+                      (TokenKind::Punctuation, ")", r#"")""#, false),
+                      (TokenKind::Regex, "[a-z]+", "ID", false)]);
+}
+
+#[test]
+fn wildcard_terminal() {
+    check_tokens(r#"
+grammar;
+    extern {
+        enum Tok {
+            "," => Tok::Comma,
+            Num => Tok::Num(<i32>),
+        }
+    }
+    pub Items = (Item ",")*;
+    Item = { Num, _ };
+"#,
+                 vec![(TokenKind::Punctuation, ",", r#"",""#, false)]);
+}
+
+#[test]
+fn wildcard_terminal_internal_tokenizer() {
+    check_tokens(r#"
+grammar;
+    pub Items = (Item ",")*;
+    Item = { r"[0-9]+", _ };
+"#,
+                 vec![(TokenKind::Punctuation, ",", r#"",""#, false),
+                      (TokenKind::Regex, "[0-9]+", r##"r#"[0-9]+"#"##, false)]);
+}
@@ -0,0 +1,49 @@
+//! A way for embedders -- build scripts, IDE plugins, test harnesses
+//! and so forth -- to receive the errors, warnings, conflict reports
+//! and progress messages of LALRPOP, rather than have them printed.
+
+use log::Level;
+use std::path::PathBuf;
+
+/// Whether a diagnostic is an error, which stops the grammar from
+/// being processed, or just a warning.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// An error or warning about a grammar, including the report of a
+/// conflict in its LR(1) tables.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+
+    /// The code of the kind of error (e.g., `E0001` for syntax
+    /// errors), if it has one.
+    pub code: Option<&'static str>,
+
+    /// The grammar file that the diagnostic is about.
+    pub file: PathBuf,
+
+    /// The byte offsets in `file` of the start and end of the part
+    /// that the diagnostic is about.
+    pub span: (usize, usize),
+
+    /// The diagnostic as LALRPOP would print it, in the configured
+    /// error format but without colors.
+    pub text: String,
+}
+
+/// Receives everything that LALRPOP has to say while it processes
+/// grammars, once given to `Configuration::set_diagnostics`.
+pub trait Diagnostics {
+    /// Called with each error or warning. Once an error that stops
+    /// processing has been reported, the call that was processing the
+    /// grammar returns an error.
+    fn report(&self, diagnostic: Diagnostic);
+
+    /// Called with each progress message at or below the configured
+    /// log level (see `Configuration::log_verbose` and so forth).
+    fn log(&self, level: Level, message: &str);
+}
@@ -1,13 +1,19 @@
+use collections::Map;
 use grammar::parse_tree as pt;
+use std::cmp;
 use std::fmt::{Display, Formatter, Error};
 use std::fs::File;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::io::{self, Read, Write};
 
 pub struct FileText {
     path: PathBuf,
     input_str: String,
     newlines: Vec<usize>,
+
+    /// The files added with `append`, each with the offset in
+    /// `input_str` where its text starts.
+    appended: Vec<(usize, PathBuf)>,
 }
 
 impl FileText {
@@ -30,7 +36,39 @@ impl FileText {
                    .collect()
         };
 
-        FileText { path: path, input_str: input_str, newlines: newline_indices }
+        FileText { path: path, input_str: input_str, newlines: newline_indices, appended: vec![] }
+    }
+
+    /// Adds the text of the file `path` after everything else, on a
+    /// line of its own, and returns the offset where it starts. Spans
+    /// from there on are shown as being in `path`.
+    pub fn append(&mut self, path: PathBuf, text: &str) -> usize {
+        if !self.input_str.is_empty() && !self.input_str.ends_with('\n') {
+            self.input_str.push('\n');
+            self.newlines.push(self.input_str.len());
+        }
+        let start = self.input_str.len();
+        self.input_str.push_str(text);
+        self.newlines.extend(text.as_bytes()
+                                 .iter()
+                                 .enumerate()
+                                 .filter(|&(_, &b)| b == ('\n' as u8))
+                                 .map(|(i, _)| start + i + 1));
+        self.appended.push((start, path));
+        start
+    }
+
+    /// The span of the text of each file, starting with the one that
+    /// the `FileText` was created with, and the path of the file.
+    pub fn files(&self) -> Vec<(pt::Span, &Path)> {
+        let starts = Some((0, &self.path)).into_iter()
+                                           .chain(self.appended.iter().map(|&(s, ref p)| (s, p)));
+        let ends = self.appended.iter()
+                                .map(|&(start, _)| start)
+                                .chain(Some(self.input_str.len()));
+        starts.zip(ends)
+              .map(|((start, path), end)| (pt::Span(start, end), path.as_path()))
+              .collect()
     }
 
     #[cfg(test)]
@@ -38,18 +76,84 @@ impl FileText {
         Self::new(PathBuf::from("test.lalrpop"), String::from(""))
     }
 
+    /// Replaces each `${NAME}`, where `NAME` is made of letters,
+    /// digits and underscores, with the value of the variable `NAME`,
+    /// and each `$${` with `${`, except in string and character
+    /// literals and in comments. Gives the span of the first `${NAME}`
+    /// for which there is no variable.
+    pub fn substitute(&self, variables: &Map<String, String>) -> Result<FileText, pt::Span> {
+        let text = &self.input_str;
+        let mut result = String::with_capacity(text.len());
+        let mut copied = 0; // everything before this is in `result`
+        let mut position = 0; // where to look for the next `${`
+        while let Some(c) = text[position..].chars().next() {
+            let rest = &text[position..];
+            if let Some(len) = literal_or_comment_len(&text[..position], rest) {
+                position += len;
+            } else if rest.starts_with("$${") {
+                result.push_str(&text[copied..position]);
+                copied = position + 1;
+                position += 3;
+            } else if rest.starts_with("${") {
+                let start = position;
+                position += 2;
+
+                let rest = &text[position..];
+                let name_len = rest.find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                                   .unwrap_or(rest.len());
+                if name_len == 0 || !rest[name_len..].starts_with('}') {
+                    continue;
+                }
+
+                let end = position + name_len + 1;
+                match variables.get(&rest[..name_len]) {
+                    Some(value) => {
+                        result.push_str(&text[copied..start]);
+                        result.push_str(value);
+                        copied = end;
+                        position = end;
+                    }
+                    None => return Err(pt::Span(start, end)),
+                }
+            } else {
+                position += c.len_utf8();
+            }
+        }
+        result.push_str(&text[copied..]);
+        Ok(FileText::new(self.path.clone(), result))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The path of the file that the text at `pos` comes from.
+    pub fn path_at(&self, pos: usize) -> &Path {
+        self.location(pos).0
+    }
+
     pub fn text(&self) -> &String {
         &self.input_str
     }
 
     pub fn span_str(&self, span: pt::Span) -> String {
-        let (start_line, start_col) = self.line_col(span.0);
-        let (end_line, end_col) = self.line_col(span.1);
+        let (path, start_line, start_col) = self.location(span.0);
+        let (_, end_line, end_col) = self.location(span.1);
         format!("{}:{}:{}: {}:{}",
-                self.path.display(),
+                path.display(),
                 start_line+1, start_col+1, end_line+1, end_col)
     }
 
+    /// The file that the text at `pos` comes from, and the line and
+    /// column of `pos` within that file.
+    fn location(&self, pos: usize) -> (&Path, usize, usize) {
+        let (line, col) = self.line_col(pos);
+        match self.appended.iter().rev().find(|&&(start, _)| start <= pos) {
+            Some(&(start, ref path)) => (path.as_path(), line - self.line_col(start).0, col),
+            None => (self.path.as_path(), line, col),
+        }
+    }
+
     fn line_col(&self, pos: usize) -> (usize, usize) {
         let num_lines = self.newlines.len();
         let line =
@@ -79,6 +183,36 @@ impl FileText {
         }
     }
 
+    /// The lines that `rustc` prints below an error heading: the
+    /// location of `span`, then its first line of text with the
+    /// spanned part underlined and followed by `label` (if any).
+    pub fn rustc_snippet(&self, span: pt::Span, label: &str) -> Vec<String> {
+        let (start_line, start_col) = self.line_col(span.0);
+        let (end_line, end_col) = self.line_col(span.1);
+        let text = self.line_text(start_line);
+        let (path, file_line, _) = self.location(span.0);
+
+        // a span across several lines is underlined to the end of the first
+        let end_col = if start_line == end_line { end_col } else { text.len() };
+        let width = cmp::max(end_col.saturating_sub(start_col), 1);
+
+        let line_num = (file_line + 1).to_string();
+        let gutter = Repeat(' ', line_num.len());
+        let mut underline = format!("{} | {}{}",
+                                    gutter,
+                                    Repeat(' ', start_col),
+                                    Repeat('^', width));
+        if !label.is_empty() {
+            underline.push(' ');
+            underline.push_str(label);
+        }
+
+        vec![format!("{}--> {}:{}:{}", gutter, path.display(), file_line + 1, start_col + 1),
+             format!("{} |", gutter),
+             format!("{} | {}", line_num, text),
+             underline]
+    }
+
     pub fn highlight(&self, span: pt::Span, out: &mut Write) -> io::Result<()> {
         let (start_line, start_col) = self.line_col(span.0);
         let (end_line, end_col) = self.line_col(span.1);
@@ -117,6 +251,51 @@ impl FileText {
     }
 }
 
+/// If `rest`, which follows `before`, starts with a string, regex or
+/// character literal, or a comment, its length; `None` otherwise.
+/// Unterminated ones run to the end.
+fn literal_or_comment_len(before: &str, rest: &str) -> Option<usize> {
+    // the length up to and including the first `end` after `start`
+    let until = |start: usize, end: &str| {
+        rest[start..].find(end).map_or(rest.len(), |i| start + i + end.len())
+    };
+    let prev = before.chars().next_back();
+    if rest.starts_with("//") {
+        Some(rest.find('\n').unwrap_or(rest.len()))
+    } else if rest.starts_with("/*") {
+        Some(until(2, "*/"))
+    } else if rest.starts_with('"') {
+        let mut chars = rest.char_indices().skip(1);
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => { chars.next(); }
+                '"' => return Some(i + 1),
+                _ => { }
+            }
+        }
+        Some(rest.len())
+    } else if rest.starts_with('r') && !prev.map_or(false, |c| c.is_alphanumeric() || c == '_') {
+        let hashes = rest[1..].len() - rest[1..].trim_left_matches('#').len();
+        if !rest[1 + hashes..].starts_with('"') {
+            return None;
+        }
+        let end = format!("\"{}", &rest[1..1 + hashes]);
+        Some(until(2 + hashes, &end))
+    } else if rest.starts_with('\'') {
+        // `'a'` or `'\n'`, but not the lifetime `'a`
+        let mut chars = rest[1..].chars();
+        match chars.next() {
+            Some('\\') => {
+                rest[2..].char_indices().skip(1).find(|&(_, c)| c == '\'').map(|(i, _)| i + 3)
+            }
+            Some(c) if chars.next() == Some('\'') => Some(c.len_utf8() + 2),
+            _ => None,
+        }
+    } else {
+        None
+    }
+}
+
 struct Repeat(char, usize);
 
 impl Display for Repeat {
@@ -10,12 +10,82 @@ pub const INPUT_LIFETIME: &'static str = "'input";
 /// The parameter injected when we do not have an external token enum
 pub const INPUT_PARAMETER: &'static str = "input";
 
+/// The built-in macro that wraps a value in a `Box`.
+pub const BOX: &'static str = "Box";
+
+/// The built-in macro that wraps a value in an `Rc`.
+pub const RC: &'static str = "Rc";
+
 /// The annotation to request inlining.
 pub const INLINE: &'static str = "inline";
 
+/// The annotation on an alternative that makes it less likely to be
+/// used in conflict examples and random derivations, like `#[cost(5)]`.
+pub const COST: &'static str = "cost";
+
+/// The annotation that gives an alternative a precedence level, like
+/// `#[precedence(2)]`, which resolves shift/reduce conflicts as yacc
+/// does; a greater level binds tighter.
+pub const PRECEDENCE: &'static str = "precedence";
+
+/// The annotation that says how an alternative with a precedence
+/// associates: `#[assoc(left)]`, `#[assoc(right)]` or `#[assoc(none)]`.
+pub const ASSOC: &'static str = "assoc";
+
+/// The annotation on a nonterminal whose shift/reduce conflicts are
+/// decided at parse time, by the grammar's conflict hook.
+pub const CONFLICT_HOOK: &'static str = "conflict_hook";
+
+/// Annotation to request that the user's actions be generated as the
+/// default methods of a trait, which the grammar's first parameter
+/// implements and can override.
+pub const REDUCE_HOOKS: &'static str = "reduce_hooks";
+
+/// The annotation that marks an inline test.
+pub const TEST: &'static str = "test";
+
+/// Inline test expecting the input to be accepted.
+pub const PARSES: &'static str = "parses";
+
+/// Inline test expecting the input to be refused.
+pub const REJECTS: &'static str = "rejects";
+
+/// Annotation to request canonical LR(1).
+pub const LR1: &'static str = "LR1";
+
+/// Annotation to request LR(1) via lane tables.
+pub const LANE_TABLE: &'static str = "lane_table";
+
 /// Annotation to request LALR.
 pub const LALR: &'static str = "LALR";
 
+/// Annotation to request SLR, for quick prototyping.
+pub const SLR: &'static str = "SLR";
+
+/// Annotation to request IELR: LALR-sized tables, but only the
+/// conflicts of canonical LR(1).
+pub const IELR: &'static str = "IELR";
+
+/// Annotation to request that conflicts be resolved with a second
+/// token of lookahead where possible.
+pub const LOOKAHEAD2: &'static str = "lookahead2";
+
+/// Annotation to request that shift/reduce conflicts be resolved in
+/// favor of shifting, with a warning.
+pub const PREFER_SHIFT: &'static str = "prefer_shift";
+
+/// Annotation to request `parse_Foo_incremental` functions, which
+/// resume from checkpoints of the parser's stacks.
+pub const INCREMENTAL: &'static str = "incremental";
+
+/// Annotation to request `PushParser_Foo` types, which are given the
+/// tokens one at a time rather than pulling them from an iterator.
+pub const PUSH: &'static str = "push";
+
+/// Annotation to request that the grammar be parsed without a
+/// separate lexer, one character at a time.
+pub const SCANNERLESS: &'static str = "scannerless";
+
 /// Annotation to request recursive-ascent-style code generation.
 pub const TABLE_DRIVEN: &'static str = "table_driven";
 
@@ -25,3 +95,11 @@ pub const RECURSIVE_ASCENT: &'static str = "recursive_ascent";
 /// Annotation to request test-all-style code generation.
 pub const TEST_ALL: &'static str = "test_all";
 
+/// Annotation to request `parse_Foo_items` iterators, which parse
+/// the items of a public nonterminal `Foo = Item*` one at a time.
+pub const ITEMS: &'static str = "items";
+
+/// Annotation to request `parse_Foo_prefix` entry points, which parse
+/// the longest prefix of the input that is a `Foo`.
+pub const PREFIX: &'static str = "prefix";
+
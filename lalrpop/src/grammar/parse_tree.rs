@@ -7,7 +7,9 @@ some pre-expansion and so forth before creating the proper AST.
 
 use intern::{intern, InternedString};
 use lexer::dfa::DFA;
-use grammar::consts::{LALR, RECURSIVE_ASCENT, TABLE_DRIVEN, TEST_ALL};
+use grammar::consts::{ASSOC, BOX, COST, IELR, INCREMENTAL, ITEMS, LALR, LANE_TABLE, LOOKAHEAD2,
+                      LR1, PRECEDENCE, PREFER_SHIFT, PREFIX, PUSH, RC, RECURSIVE_ASCENT,
+                      REDUCE_HOOKS, SCANNERLESS, SLR, TABLE_DRIVEN, TEST_ALL};
 use grammar::repr::{self as r, NominalTypeRepr, TypeRepr};
 use grammar::pattern::Pattern;
 use message::Content;
@@ -47,8 +49,15 @@ pub enum GrammarItem {
     MatchToken(MatchToken),
     ExternToken(ExternToken),
     InternToken(InternToken),
+    InlineTest(InlineTest),
     Nonterminal(NonterminalData),
+    Operators(Operators),
+    Reserved(Reserved),
+    TypeAlias(TypeAlias),
+    TerminalConst(TerminalConst),
+    RustItem(RustItem),
     Use(String),
+    Import(Import),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -110,6 +119,77 @@ impl MatchItem {
 pub type MatchSymbol = TerminalLiteral;
 pub type MatchMapping = TerminalString;
 
+/// A `reserved { "async", "await" }` declaration. Reserved words are
+/// always lexed as keywords by the internal tokenizer, even if the
+/// grammar does not (yet) use them, so that they can never be
+/// mistaken for identifiers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reserved {
+    pub span: Span,
+    pub words: Vec<ReservedWord>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReservedWord {
+    pub word: InternedString,
+    pub span: Span,
+}
+
+/// A `type Spanned<T> = (usize, T, usize);` declaration. Type aliases
+/// are scoped to the grammar: they are expanded in the type
+/// annotations of nonterminals and not emitted in the generated code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypeAlias {
+    pub span: Span,
+    pub name: InternedString,
+    pub type_parameters: Vec<TypeParameter>,
+    pub type_ref: TypeRef,
+}
+
+/// A `const LBRACE = "{";` declaration, which gives a name to a
+/// terminal literal. Uses of the name are replaced by the literal
+/// when identifiers are resolved.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TerminalConst {
+    pub span: Span,
+    pub name: InternedString,
+    pub literal: TerminalLiteral,
+}
+
+/// A `use "tokens.lalrpop";` declaration. The items of the named
+/// file, which is relative to the file that imports it, are added to
+/// those of the grammar; see `build::load_imports`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Import {
+    pub span: Span,
+    pub path: InternedString,
+}
+
+/// A `#[test] Expr parses "1+2*3";` declaration, which checks that
+/// the generated `parse_Expr` accepts the given input (or, with
+/// `rejects` in place of `parses`, that it does not).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InlineTest {
+    pub span: Span,
+    pub annotations: Vec<Annotation>,
+    pub public: bool,
+    pub nonterminal: NonterminalString,
+    pub expectation_span: Span,
+    pub expectation: InternedString,
+    pub input: InternedString,
+}
+
+/// A free-standing Rust item, like `fn foo() { ... }`, `const FOO: u32
+/// = 22;` or `struct Foo;`, which is copied verbatim into the
+/// generated module so that it can be used from action code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RustItem {
+    pub span: Span,
+    pub annotations: Vec<Annotation>,
+    pub name: InternedString,
+    pub code: String, // includes the `pub`, if any
+}
+
 /// Intern tokens are not typed by the user: they are synthesized in
 /// the absence of an "extern" declaration with information about the
 /// string literals etc that appear in the grammar.
@@ -118,7 +198,11 @@ pub struct InternToken {
     /// Set of `r"foo"` and `"foo"` literals extracted from the
     /// grammar. Sorted by order of increasing precedence.
     pub match_entries: Vec<MatchEntry>,
-    pub dfa: DFA
+    pub dfa: DFA,
+
+    /// Words declared in `reserved { ... }` sections; each of these
+    /// also has an entry in `match_entries`.
+    pub reserved_words: Vec<ReservedWord>,
 }
 
 /// In `token_check`, as we prepare to generate a tokenizer, we
@@ -254,16 +338,67 @@ pub struct NonterminalData {
     pub alternatives: Vec<Alternative>
 }
 
+/// A nonterminal defined by an operator table, like
+///
+/// ```
+/// Expr: i32 = operators(Term) {
+///     left { "+" => l + r, "-" => l - r },
+///     left { "*" => l * r, "/" => l / r },
+///     prefix { "-" => -e },
+/// };
+/// ```
+///
+/// The levels are listed from the loosest binding to the tightest;
+/// `Term` is what the operators ultimately apply to. These are
+/// expanded into ordinary nonterminals by `normalize::operators`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Operators {
+    pub public: bool,
+    pub name: NonterminalString,
+    pub annotations: Vec<Annotation>,
+    pub span: Span,
+    pub args: Vec<NonterminalString>, // rejected by `normalize::operators`
+    pub type_decl: Option<TypeRef>,
+    pub atom: Symbol,
+    pub levels: Vec<OperatorLevel>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OperatorLevel {
+    pub span: Span,
+
+    // `left`, `right`, `nonassoc`, `prefix` or `postfix`
+    pub associativity: InternedString,
+
+    pub operators: Vec<Operator>,
+}
+
+/// Operators without holes, like `"+"`, get their operands from the
+/// associativity of their level; mixfix operators, like
+/// `_ "?" _ ":" _`, say where the operands go (see `Symbol::as_hole`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Operator {
+    pub span: Span,
+    pub parts: Vec<Symbol>,
+    pub action: ActionKind,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Annotation {
     pub id_span: Span,
     pub id: InternedString,
+
+    // the `2` in `#[cost(2)]`, only legal on alternatives
+    pub arg: Option<(Span, InternedString)>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Alternative {
     pub span: Span,
 
+    // #[cost(N)]
+    pub annotations: Vec<Annotation>,
+
     pub expr: ExprSymbol,
 
     // if C, only legal in macros
@@ -279,6 +414,7 @@ pub enum ActionKind {
     Fallible(String),
     Lookahead,
     Lookbehind,
+    LookaheadToken,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -318,6 +454,9 @@ pub enum SymbolKind {
     // foo, before name resolution
     AmbiguousId(InternedString),
 
+    // Tok::Num(n), before name resolution
+    Variant(VariantSymbol),
+
     // "foo" and foo (after name resolution)
     Terminal(TerminalString),
 
@@ -341,6 +480,9 @@ pub enum SymbolKind {
 
     // @R
     Lookbehind,
+
+    // @T
+    LookaheadToken,
     
     Error
 }
@@ -350,6 +492,10 @@ pub enum TerminalString {
     Literal(TerminalLiteral),
     Bare(InternedString),
     Error,
+
+    // `_`, which matches any token that no other terminal matches in
+    // the same state; declared last, so that it sorts after the rest
+    Any,
 }
 
 impl TerminalString {
@@ -364,7 +510,8 @@ impl TerminalString {
         match *self {
             TerminalString::Literal(x) => x.display_len(),
             TerminalString::Bare(x) => x.len(),
-            TerminalString::Error => "error".len()
+            TerminalString::Error => "error".len(),
+            TerminalString::Any => 1,
         }
     }
 }
@@ -434,6 +581,37 @@ pub struct MacroSymbol {
     pub args: Vec<Symbol>,
 }
 
+/// A terminal written as the pattern of the token it matches, like
+/// `Tok::Num(n)`. Name resolution replaces it with the terminal whose
+/// conversion has the same shape.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VariantSymbol {
+    pub path: Path,
+
+    /// for each field, the name it is bound to, or `None` for `_`
+    pub fields: Vec<Option<InternedString>>,
+}
+
+impl VariantSymbol {
+    /// Makes the symbol for `self`, named after the fields it binds,
+    /// if any: `Tok::Num(n)` is short for `<n:Tok::Num(_)>` and
+    /// `Tok::Pair(a, b)` for `<(a, b):Tok::Pair(_, _)>`.
+    pub fn into_symbol(self, span: Span) -> Symbol {
+        let names: Vec<_> = self.fields.iter().filter_map(|&field| field).collect();
+        let symbol = Symbol::new(span, SymbolKind::Variant(self));
+        let name = match names.len() {
+            0 => return symbol,
+            1 => names[0],
+            _ => intern(&format!("({})", Sep(", ", &names))),
+        };
+        Symbol::new(span, SymbolKind::Name(name, Box::new(symbol)))
+    }
+
+    pub fn binds_fields(&self) -> bool {
+        self.fields.iter().any(|field| field.is_some())
+    }
+}
+
 impl TerminalString {
     pub fn quoted(i: InternedString) -> TerminalString {
         TerminalString::Literal(TerminalLiteral::Quoted(i))
@@ -496,6 +674,13 @@ impl GrammarItem {
             GrammarItem::MatchToken(..) => None,
             GrammarItem::ExternToken(..) => None,
             GrammarItem::InternToken(..) => None,
+            GrammarItem::InlineTest(..) => None,
+            GrammarItem::Operators(..) => None,
+            GrammarItem::Reserved(..) => None,
+            GrammarItem::TypeAlias(..) => None,
+            GrammarItem::TerminalConst(..) => None,
+            GrammarItem::RustItem(..) => None,
+            GrammarItem::Import(..) => None,
         }
     }
 
@@ -506,6 +691,13 @@ impl GrammarItem {
             GrammarItem::MatchToken(ref d) => Some(d),
             GrammarItem::ExternToken(..) => None,
             GrammarItem::InternToken(..) => None,
+            GrammarItem::InlineTest(..) => None,
+            GrammarItem::Operators(..) => None,
+            GrammarItem::Reserved(..) => None,
+            GrammarItem::TypeAlias(..) => None,
+            GrammarItem::TerminalConst(..) => None,
+            GrammarItem::RustItem(..) => None,
+            GrammarItem::Import(..) => None,
         }
     }
 
@@ -516,6 +708,13 @@ impl GrammarItem {
             GrammarItem::MatchToken(..) => None,
             GrammarItem::ExternToken(ref d) => Some(d),
             GrammarItem::InternToken(..) => None,
+            GrammarItem::InlineTest(..) => None,
+            GrammarItem::Operators(..) => None,
+            GrammarItem::Reserved(..) => None,
+            GrammarItem::TypeAlias(..) => None,
+            GrammarItem::TerminalConst(..) => None,
+            GrammarItem::RustItem(..) => None,
+            GrammarItem::Import(..) => None,
         }
     }
 
@@ -526,10 +725,155 @@ impl GrammarItem {
             GrammarItem::MatchToken(..) => None,
             GrammarItem::ExternToken(..) => None,
             GrammarItem::InternToken(ref d) => Some(d),
+            GrammarItem::InlineTest(..) => None,
+            GrammarItem::Operators(..) => None,
+            GrammarItem::Reserved(..) => None,
+            GrammarItem::TypeAlias(..) => None,
+            GrammarItem::TerminalConst(..) => None,
+            GrammarItem::RustItem(..) => None,
+            GrammarItem::Import(..) => None,
+        }
+    }
+
+    pub fn as_operators(&self) -> Option<&Operators> {
+        match *self {
+            GrammarItem::Nonterminal(..) => None,
+            GrammarItem::Use(..) => None,
+            GrammarItem::MatchToken(..) => None,
+            GrammarItem::ExternToken(..) => None,
+            GrammarItem::InternToken(..) => None,
+            GrammarItem::InlineTest(..) => None,
+            GrammarItem::Operators(ref d) => Some(d),
+            GrammarItem::Reserved(..) => None,
+            GrammarItem::TypeAlias(..) => None,
+            GrammarItem::TerminalConst(..) => None,
+            GrammarItem::RustItem(..) => None,
+            GrammarItem::Import(..) => None,
+        }
+    }
+
+    pub fn as_inline_test(&self) -> Option<&InlineTest> {
+        match *self {
+            GrammarItem::Nonterminal(..) => None,
+            GrammarItem::Use(..) => None,
+            GrammarItem::MatchToken(..) => None,
+            GrammarItem::ExternToken(..) => None,
+            GrammarItem::InternToken(..) => None,
+            GrammarItem::InlineTest(ref d) => Some(d),
+            GrammarItem::Operators(..) => None,
+            GrammarItem::Reserved(..) => None,
+            GrammarItem::TypeAlias(..) => None,
+            GrammarItem::TerminalConst(..) => None,
+            GrammarItem::RustItem(..) => None,
+            GrammarItem::Import(..) => None,
+        }
+    }
+
+    pub fn as_reserved(&self) -> Option<&Reserved> {
+        match *self {
+            GrammarItem::Nonterminal(..) => None,
+            GrammarItem::Use(..) => None,
+            GrammarItem::MatchToken(..) => None,
+            GrammarItem::ExternToken(..) => None,
+            GrammarItem::InternToken(..) => None,
+            GrammarItem::InlineTest(..) => None,
+            GrammarItem::Operators(..) => None,
+            GrammarItem::Reserved(ref d) => Some(d),
+            GrammarItem::TypeAlias(..) => None,
+            GrammarItem::TerminalConst(..) => None,
+            GrammarItem::RustItem(..) => None,
+            GrammarItem::Import(..) => None,
+        }
+    }
+
+    pub fn as_type_alias(&self) -> Option<&TypeAlias> {
+        match *self {
+            GrammarItem::Nonterminal(..) => None,
+            GrammarItem::Use(..) => None,
+            GrammarItem::MatchToken(..) => None,
+            GrammarItem::ExternToken(..) => None,
+            GrammarItem::InternToken(..) => None,
+            GrammarItem::InlineTest(..) => None,
+            GrammarItem::Operators(..) => None,
+            GrammarItem::Reserved(..) => None,
+            GrammarItem::TypeAlias(ref d) => Some(d),
+            GrammarItem::TerminalConst(..) => None,
+            GrammarItem::RustItem(..) => None,
+            GrammarItem::Import(..) => None,
+        }
+    }
+
+    pub fn as_rust_item(&self) -> Option<&RustItem> {
+        match *self {
+            GrammarItem::Nonterminal(..) => None,
+            GrammarItem::Use(..) => None,
+            GrammarItem::MatchToken(..) => None,
+            GrammarItem::ExternToken(..) => None,
+            GrammarItem::InternToken(..) => None,
+            GrammarItem::InlineTest(..) => None,
+            GrammarItem::Operators(..) => None,
+            GrammarItem::Reserved(..) => None,
+            GrammarItem::TypeAlias(..) => None,
+            GrammarItem::TerminalConst(..) => None,
+            GrammarItem::RustItem(ref d) => Some(d),
+            GrammarItem::Import(..) => None,
+        }
+    }
+
+    pub fn as_terminal_const(&self) -> Option<&TerminalConst> {
+        match *self {
+            GrammarItem::Nonterminal(..) => None,
+            GrammarItem::Use(..) => None,
+            GrammarItem::MatchToken(..) => None,
+            GrammarItem::ExternToken(..) => None,
+            GrammarItem::InternToken(..) => None,
+            GrammarItem::InlineTest(..) => None,
+            GrammarItem::Operators(..) => None,
+            GrammarItem::Reserved(..) => None,
+            GrammarItem::TypeAlias(..) => None,
+            GrammarItem::TerminalConst(ref d) => Some(d),
+            GrammarItem::RustItem(..) => None,
+            GrammarItem::Import(..) => None,
         }
     }
 }
 
+impl Alternative {
+    /// The cost given by a `#[cost(N)]` annotation, or 0; `prevalidate`
+    /// has checked that `N` fits.
+    pub fn cost(&self) -> u32 {
+        self.annotations
+            .iter()
+            .filter(|a| a.id == intern(COST))
+            .filter_map(|a| a.arg.and_then(|(_, n)| n.to_string().parse().ok()))
+            .next()
+            .unwrap_or(0)
+    }
+
+    /// The precedence given by `#[precedence(N)]` and `#[assoc(A)]`, if
+    /// any; `prevalidate` has checked both arguments.
+    pub fn precedence(&self) -> Option<r::Precedence> {
+        let arg = |id: &str| {
+            self.annotations
+                .iter()
+                .filter(|a| a.id == intern(id))
+                .filter_map(|a| a.arg.map(|(_, arg)| arg.to_string()))
+                .next()
+        };
+        let level = match arg(PRECEDENCE).and_then(|n| n.parse().ok()) {
+            Some(level) => level,
+            None => return None,
+        };
+        let assoc = arg(ASSOC).and_then(|a| match &a[..] {
+            "left" => Some(r::Assoc::Left),
+            "right" => Some(r::Assoc::Right),
+            "none" => Some(r::Assoc::NonAssoc),
+            _ => None,
+        });
+        Some(r::Precedence { level: level, assoc: assoc })
+    }
+}
+
 impl NonterminalData {
     pub fn is_macro_def(&self) -> bool {
         !self.args.is_empty()
@@ -544,6 +888,24 @@ impl Symbol {
     pub fn canonical_form(&self) -> String {
         format!("{}", self)
     }
+
+    /// In an operator, `_` and `<name:_>` are not wildcard terminals
+    /// but holes for operands. If this is a hole, gives the name
+    /// it binds, if any.
+    pub fn as_hole(&self) -> Option<Option<InternedString>> {
+        match self.kind {
+            SymbolKind::Terminal(TerminalString::Any) => Some(None),
+            SymbolKind::Name(name, ref symbol) => match symbol.kind {
+                SymbolKind::Terminal(TerminalString::Any) => Some(Some(name)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub fn is_hole(&self) -> bool {
+        self.as_hole().is_some()
+    }
 }
 
 impl Display for TerminalString {
@@ -555,6 +917,8 @@ impl Display for TerminalString {
                 write!(fmt, "{}", s),
             TerminalString::Error => 
                 write!(fmt, "error"),
+            TerminalString::Any =>
+                write!(fmt, "_"),
         }
     }
 }
@@ -621,6 +985,8 @@ impl Display for SymbolKind {
                 write!(fmt, "{}", s),
             SymbolKind::Macro(ref m) =>
                 write!(fmt, "{}", m),
+            SymbolKind::Variant(ref v) =>
+                write!(fmt, "{}", v),
             SymbolKind::Repeat(ref r) =>
                 write!(fmt, "{}", r),
             SymbolKind::Choose(ref s) =>
@@ -631,6 +997,8 @@ impl Display for SymbolKind {
                 write!(fmt, "@L"),
             SymbolKind::Lookbehind =>
                 write!(fmt, "@R"),
+            SymbolKind::LookaheadToken =>
+                write!(fmt, "@T"),
             SymbolKind::Error =>
                 write!(fmt, "error"),
         }
@@ -677,6 +1045,13 @@ impl MacroSymbol {
     pub fn canonical_form(&self) -> String {
         format!("{}", self)
     }
+
+    /// If this is a use of one of the built-in wrapper macros, like
+    /// `Box<Expr>`, returns the path of the wrapper type. Only
+    /// consulted when the grammar has no macro of the same name.
+    pub fn builtin_wrapper(&self) -> Option<Path> {
+        Path::builtin_wrapper(self.name)
+    }
 }
 
 impl RepeatSymbol {
@@ -691,6 +1066,19 @@ impl Display for MacroSymbol {
     }
 }
 
+impl Display for VariantSymbol {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        let fields: Vec<_> = self.fields
+                                 .iter()
+                                 .map(|field| match *field {
+                                     Some(name) => name.to_string(),
+                                     None => "_".to_string(),
+                                 })
+                                 .collect();
+        write!(fmt, "{}({})", self.path, fields.join(", "))
+    }
+}
+
 impl Display for TypeParameter {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
         match *self {
@@ -793,6 +1181,30 @@ impl Path {
         }
     }
 
+    pub fn boxed() -> Path {
+        Path {
+            absolute: true,
+            ids: vec![intern("std"), intern("boxed"), intern("Box")]
+        }
+    }
+
+    pub fn rc() -> Path {
+        Path {
+            absolute: true,
+            ids: vec![intern("std"), intern("rc"), intern("Rc")]
+        }
+    }
+
+    pub fn builtin_wrapper(name: NonterminalString) -> Option<Path> {
+        if name == NonterminalString(intern(BOX)) {
+            Some(Path::boxed())
+        } else if name == NonterminalString(intern(RC)) {
+            Some(Path::rc())
+        } else {
+            None
+        }
+    }
+
     pub fn as_id(&self) -> Option<InternedString> {
         if !self.absolute && self.ids.len() == 1 {
             Some(self.ids[0])
@@ -804,14 +1216,38 @@ impl Path {
 
 pub fn read_algorithm(annotations: &[Annotation], algorithm: &mut r::Algorithm) {
     for annotation in annotations {
-        if annotation.id == intern(LALR) {
-            algorithm.lalr = true;
+        if annotation.id == intern(LR1) {
+            algorithm.construction = Some(r::LrConstruction::Lr1);
+        } else if annotation.id == intern(LANE_TABLE) {
+            algorithm.construction = Some(r::LrConstruction::LaneTable);
+        } else if annotation.id == intern(LALR) {
+            algorithm.construction = Some(r::LrConstruction::Lalr);
+        } else if annotation.id == intern(IELR) {
+            algorithm.construction = Some(r::LrConstruction::Ielr);
+        } else if annotation.id == intern(SLR) {
+            algorithm.construction = Some(r::LrConstruction::Slr);
+        } else if annotation.id == intern(LOOKAHEAD2) {
+            algorithm.lookahead2 = true;
+        } else if annotation.id == intern(PREFER_SHIFT) {
+            algorithm.prefer_shift = true;
+        } else if annotation.id == intern(INCREMENTAL) {
+            algorithm.incremental = true;
+        } else if annotation.id == intern(PUSH) {
+            algorithm.push = true;
+        } else if annotation.id == intern(SCANNERLESS) {
+            algorithm.scannerless = true;
         } else if annotation.id == intern(TABLE_DRIVEN) {
             algorithm.codegen = r::LrCodeGeneration::TableDriven;
         } else if annotation.id == intern(RECURSIVE_ASCENT) {
             algorithm.codegen = r::LrCodeGeneration::RecursiveAscent;
         } else if annotation.id == intern(TEST_ALL) {
             algorithm.codegen = r::LrCodeGeneration::TestAll;
+        } else if annotation.id == intern(ITEMS) {
+            algorithm.items = true;
+        } else if annotation.id == intern(PREFIX) {
+            algorithm.prefix = true;
+        } else if annotation.id == intern(REDUCE_HOOKS) {
+            // not about the algorithm; `lower` names the hooks
         } else {
             panic!("validation permitted unknown annotation: {:?}",
                     annotation.id);
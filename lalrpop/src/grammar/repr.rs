@@ -5,10 +5,11 @@
  */
 
 use intern::{intern, InternedString};
+use grammar::consts::CONFLICT_HOOK;
 use grammar::pattern::{Pattern};
 use message::Content;
 use std::fmt::{Debug, Display, Formatter, Error};
-use collections::{map, Map};
+use collections::{map, set, Map, Set};
 use util::Sep;
 
 // These concepts we re-use wholesale
@@ -16,6 +17,7 @@ pub use grammar::parse_tree::{Annotation,
                               InternToken,
                               NonterminalString,
                               Path,
+                              RustItem,
                               Span,
                               TerminalLiteral, TerminalString, TypeParameter};
 
@@ -37,9 +39,23 @@ pub struct Grammar {
     // production like `Foo' = Foo`.
     pub start_nonterminals: Map<NonterminalString, NonterminalString>,
 
+    // for public nonterminals of the form `File = Item*`, the key is
+    // the user's name `File` and the value is an artificial start
+    // symbol with the single production `File' = Item`; it is used to
+    // generate the `parse_File_items` iterator.
+    pub item_start_nonterminals: Map<NonterminalString, NonterminalString>,
+
     // the "use foo;" statements that the user declared
     pub uses: Vec<String>,
 
+    // the free-standing Rust items, like `fn foo() { ... }`, that the
+    // user declared
+    pub rust_items: Vec<RustItem>,
+
+    // the `#[test] Expr parses "...";` declarations, from which we
+    // generate a `#[cfg(test)]` module
+    pub tests: Vec<InlineTest>,
+
     // type parameters declared on the grammar, like `grammar<T>;`
     pub type_parameters: Vec<TypeParameter>,
 
@@ -81,8 +97,56 @@ pub struct NonterminalData {
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Algorithm {
-    pub lalr: bool,
+    /// How to construct the states; `None` if the grammar does not
+    /// say, in which case it is up to the environment.
+    pub construction: Option<LrConstruction>,
     pub codegen: LrCodeGeneration,
+
+    /// Whether to resolve conflicts with a second token of lookahead
+    /// where that suffices.
+    pub lookahead2: bool,
+
+    /// Whether to resolve the remaining shift/reduce conflicts by
+    /// shifting, reporting them as warnings instead of errors.
+    pub prefer_shift: bool,
+
+    /// Whether to generate `parse_Foo_incremental`, which records
+    /// checkpoints of the stacks as it goes and resumes from the last
+    /// one before an edit.
+    pub incremental: bool,
+
+    /// Whether to generate `PushParser_Foo`, which keeps its stacks
+    /// between calls and is handed one token at a time.
+    pub push: bool,
+
+    /// Whether each character of the input is a token of its own, with
+    /// no whitespace skipped, and shift/reduce conflicts are resolved
+    /// by shifting, so that repetitions match as much as they can.
+    pub scannerless: bool,
+
+    /// Whether to generate `parse_Foo_items` for each public
+    /// nonterminal of the form `Foo = Item*`, which parses one `Item`
+    /// at a time.
+    pub items: bool,
+
+    /// Whether to generate `parse_Foo_prefix` for each public
+    /// nonterminal `Foo`, which parses the longest prefix of the input
+    /// that it can.
+    pub prefix: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LrConstruction {
+    /// Canonical LR(1), in the traditional way.
+    Lr1,
+    /// Canonical LR(1), merging states via lane tables as in Pager's
+    /// algorithm.
+    LaneTable,
+    Lalr,
+    Ielr,
+    /// LR(0) states with FOLLOW sets as lookahead: quick to build, but
+    /// may have conflicts that the others would not.
+    Slr,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -92,6 +156,15 @@ pub enum LrCodeGeneration {
     TestAll,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InlineTest {
+    pub nonterminal: NonterminalString,
+    pub input: InternedString,
+
+    // true for `parses`, false for `rejects`
+    pub should_parse: bool,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Parameter {
     pub name: InternedString,
@@ -106,6 +179,37 @@ pub struct Production {
     pub symbols: Vec<Symbol>,
     pub action: ActionFn,
     pub span: Span,
+
+    // from `#[cost(N)]` on the alternative, or 0; biases conflict
+    // examples and random derivations away from this production
+    pub cost: u32,
+
+    // from `#[precedence(N)]` and `#[assoc(A)]` on the alternative;
+    // resolves shift/reduce conflicts that involve this production
+    pub precedence: Option<Precedence>,
+}
+
+/// The precedence of a production, compared with that of the
+/// productions that shift a terminal when they conflict.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Precedence {
+    /// A greater level binds tighter.
+    pub level: u32,
+
+    /// How to decide between productions of the same level, if at all.
+    pub assoc: Option<Assoc>,
+}
+
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Assoc {
+    /// Reduce, so that `a - b - c` is `(a - b) - c`.
+    Left,
+
+    /// Shift, so that `a ^ b ^ c` is `a ^ (b ^ c)`.
+    Right,
+
+    /// Neither: `a == b == c` is a syntax error.
+    NonAssoc,
 }
 
 #[derive(Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
@@ -134,6 +238,11 @@ pub struct UserActionFnDefn {
     pub arg_patterns: Vec<InternedString>,
     pub arg_types: Vec<TypeRepr>,
     pub code: String,
+
+    /// In a `#[reduce_hooks]` grammar, the name of the method of the
+    /// generated `ReduceHooks` trait that holds `code`, like
+    /// `reduce_Expr_1` for the second alternative of `Expr`.
+    pub hook: Option<InternedString>,
 }
 
 /// An action fn generated by the inlining pass.  If we were
@@ -158,6 +267,7 @@ pub struct InlineActionFnDefn {
 pub enum LookaroundActionFnDefn {
     Lookahead,
     Lookbehind,
+    LookaheadToken,
 }
 
 #[derive(Clone, PartialEq, Eq)]
@@ -338,12 +448,30 @@ impl Types {
         &self.parse_error_type
     }
 
+    /// Gives the nonterminals that are keys of `renames` the names
+    /// that they map to (see `Grammar::rename_nonterminals`).
+    pub fn rename_nonterminals(&mut self, renames: &Map<NonterminalString, NonterminalString>) {
+        let types = ::std::mem::replace(&mut self.nonterminal_types, map());
+        self.nonterminal_types = types.into_iter()
+                                      .map(|(nt, ty)| (*renames.get(&nt).unwrap_or(&nt), ty))
+                                      .collect();
+    }
+
     /// Returns a type `(L, T, L)` where L is the location type and T
     /// is the token type.
     pub fn triple_type(&self) -> TypeRepr {
         self.spanned_type(self.terminal_token_type().clone())
     }
 
+    /// Returns a type `Option<(L, T, L)>`, the type of the lookahead
+    /// token as seen by `@T`.
+    pub fn opt_triple_type(&self) -> TypeRepr {
+        TypeRepr::Nominal(NominalTypeRepr {
+            path: Path::option(),
+            types: vec![self.triple_type()]
+        })
+    }
+
     /// Returns a type `(L, T, L)` where L is the location type and T
     /// is the argument.
     pub fn spanned_type(&self, ty: TypeRepr) -> TypeRepr {
@@ -516,6 +644,52 @@ impl Grammar {
         &self.conversions[&t]
     }
 
+    /// Gives the nonterminals that are keys of `renames` the names
+    /// that they map to, wherever they appear. The new names must not
+    /// be taken already.
+    pub fn rename_nonterminals(&mut self, renames: &Map<NonterminalString, NonterminalString>) {
+        let rename = |nt: NonterminalString| *renames.get(&nt).unwrap_or(&nt);
+        let rename_symbol = |symbol: &mut Symbol| {
+            if let Symbol::Nonterminal(ref mut nt) = *symbol {
+                *nt = rename(*nt);
+            }
+        };
+
+        let nonterminals = ::std::mem::replace(&mut self.nonterminals, map());
+        for (_, mut data) in nonterminals {
+            data.name = rename(data.name);
+            for production in &mut data.productions {
+                production.nonterminal = rename(production.nonterminal);
+                for symbol in &mut production.symbols {
+                    rename_symbol(symbol);
+                }
+            }
+            self.nonterminals.insert(data.name, data);
+        }
+        for starts in vec![&mut self.start_nonterminals, &mut self.item_start_nonterminals] {
+            let old = ::std::mem::replace(starts, map());
+            starts.extend(old.into_iter().map(|(public, start)| (rename(public), rename(start))));
+        }
+        for defn in &mut self.action_fn_defns {
+            if let ActionFnDefnKind::Inline(ref mut data) = defn.kind {
+                for inlined in &mut data.symbols {
+                    match *inlined {
+                        InlinedSymbol::Original(ref mut symbol) => rename_symbol(symbol),
+                        InlinedSymbol::Inlined(_, ref mut symbols) => {
+                            for symbol in symbols {
+                                rename_symbol(symbol);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        for test in &mut self.tests {
+            test.nonterminal = rename(test.nonterminal);
+        }
+        self.types.rename_nonterminals(renames);
+    }
+
     pub fn productions_for(&self, nonterminal: NonterminalString) -> &[Production] {
         match self.nonterminals.get(&nonterminal) {
             Some(v) => &v.productions[..],
@@ -523,6 +697,26 @@ impl Grammar {
         }
     }
 
+    /// The nonterminals that `nonterminal` refers to, directly or
+    /// indirectly. This includes `nonterminal` itself only if it is
+    /// recursive.
+    pub fn reachable_from(&self, nonterminal: NonterminalString) -> Set<NonterminalString> {
+        let mut reachable = set();
+        let mut stack = vec![nonterminal];
+        while let Some(nt) = stack.pop() {
+            for production in self.productions_for(nt) {
+                for &symbol in &production.symbols {
+                    if let Symbol::Nonterminal(referent) = symbol {
+                        if reachable.insert(referent) {
+                            stack.push(referent);
+                        }
+                    }
+                }
+            }
+        }
+        reachable
+    }
+
     pub fn user_parameter_refs(&self) -> String {
         let mut result = String::new();
         for parameter in &self.parameters {
@@ -531,10 +725,79 @@ impl Grammar {
         result
     }
 
+    /// True if the `parse_Foo_items` iterators have `checkpoint` and
+    /// `rewind` methods, which deal in byte offsets into the input.
+    pub fn generates_item_checkpoints(&self) -> bool {
+        !self.item_start_nonterminals.is_empty() &&
+            self.types.terminal_loc_type() == TypeRepr::usize()
+    }
+
+    /// True if we generate a `complete_Foo` function for each public
+    /// nonterminal `Foo`. It runs the tables of the table-driven
+    /// parser without the action code, so it cannot leave a conflict
+    /// to a second token or to the conflict hook.
+    pub fn generates_completion_fns(&self) -> bool {
+        self.algorithm.codegen != LrCodeGeneration::RecursiveAscent &&
+            !self.algorithm.lookahead2 &&
+            !self.uses_conflict_hooks()
+    }
+
+    /// True if `nt` is marked `#[conflict_hook]`, so the shift/reduce
+    /// conflicts on its productions are left for the generated parser
+    /// to ask the grammar's first parameter about.
+    pub fn has_conflict_hook(&self, nt: NonterminalString) -> bool {
+        self.nonterminals[&nt].annotations.iter().any(|a| a.id == intern(CONFLICT_HOOK))
+    }
+
+    pub fn uses_conflict_hooks(&self) -> bool {
+        self.nonterminals.keys().any(|&nt| self.has_conflict_hook(nt))
+    }
+
+    pub fn uses_precedence(&self) -> bool {
+        self.nonterminals
+            .values()
+            .any(|data| data.productions.iter().any(|p| p.precedence.is_some()))
+    }
+
+    pub fn uses_reduce_hooks(&self) -> bool {
+        self.action_fn_defns.iter().any(|defn| match defn.kind {
+            ActionFnDefnKind::User(ref data) => data.hook.is_some(),
+            _ => false,
+        })
+    }
+
+    /// True if shift/reduce conflicts are resolved by shifting: on
+    /// request, with `#[prefer_shift]`, or to get the longest match in
+    /// a `#[scannerless]` grammar.
+    pub fn prefers_shift(&self) -> bool {
+        self.algorithm.prefer_shift || self.algorithm.scannerless
+    }
+
+    /// True if some production uses the wildcard terminal `_`.
+    pub fn uses_wildcard(&self) -> bool {
+        self.terminals.all.contains(&TerminalString::Any)
+    }
+
     pub fn action_is_fallible(&self, f: ActionFn) -> bool {
         self.action_fn_defns[f.index()].fallible
     }
 
+    /// True if the action fn `f` expects to be given the lookahead
+    /// token, because it implements `@T` or inlines something that
+    /// does.
+    pub fn action_takes_lookahead_token(&self, f: ActionFn) -> bool {
+        match self.action_fn_defns[f.index()].kind {
+            ActionFnDefnKind::User(_) => false,
+            ActionFnDefnKind::Lookaround(ref data) => *data == LookaroundActionFnDefn::LookaheadToken,
+            ActionFnDefnKind::Inline(ref data) => {
+                data.symbols.iter().any(|sym| match *sym {
+                    InlinedSymbol::Original(_) => false,
+                    InlinedSymbol::Inlined(a, _) => self.action_takes_lookahead_token(a),
+                })
+            }
+        }
+    }
+
     pub fn non_lifetime_type_parameters(&self) -> Vec<&TypeParameter> {
         self.type_parameters
             .iter()
@@ -549,8 +812,15 @@ impl Grammar {
 impl Default for Algorithm {
     fn default() -> Self {
         Algorithm {
-            lalr: false,
+            construction: None,
             codegen: LrCodeGeneration::TableDriven,
+            lookahead2: false,
+            prefer_shift: false,
+            incremental: false,
+            push: false,
+            scannerless: false,
+            items: false,
+            prefix: false,
         }
     }
 }
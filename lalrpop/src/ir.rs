@@ -0,0 +1,260 @@
+//! The grammar as LR(1) construction sees it, after normalization:
+//! macros are expanded, `?`, `*` and `+` are turned into nonterminals
+//! of their own, `#[inline]` nonterminals are inlined, and the types
+//! of all the symbols have been inferred. This is meant for tools that
+//! analyze grammars, which can get it from
+//! `Configuration::normalized_grammar`, or as JSON from `lalrpop ir`.
+//!
+//! Names and types are given as they would be written in the grammar
+//! or in Rust, so that these types depend on nothing else in LALRPOP.
+//! With the `serde` feature, they implement serde's `Serialize` and
+//! `Deserialize`. Fields may be added in later versions; when one is
+//! removed, renamed or changes meaning, `VERSION` goes up.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The version of this representation, as found in `Grammar::version`.
+pub const VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Grammar {
+    /// The `VERSION` of the representation.
+    pub version: u32,
+
+    /// The type parameters of the grammar, like `'input` or `T`.
+    pub type_parameters: Vec<String>,
+
+    /// The where clauses on the type parameters, like `T: Clone`.
+    pub where_clauses: Vec<String>,
+
+    /// The parameters of the grammar, which its parsers take.
+    pub parameters: Vec<Parameter>,
+
+    /// The type of the tokens.
+    pub token_type: String,
+
+    /// The type of the locations of the tokens, `()` if they have none.
+    pub location_type: String,
+
+    /// The type of the errors that the action code and the tokenizer
+    /// can report, `()` if they report none.
+    pub error_type: String,
+
+    /// Every terminal, in the order of the columns of the action
+    /// table.
+    pub terminals: Vec<Terminal>,
+
+    /// Every nonterminal, in the order of the columns of the goto
+    /// table, with its productions.
+    pub nonterminals: Vec<Nonterminal>,
+
+    /// A start symbol for each public nonterminal, in alphabetical
+    /// order.
+    pub start_symbols: Vec<StartSymbol>,
+
+    /// The action of each production, which `Production::action` is
+    /// an index into.
+    pub actions: Vec<Action>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Parameter {
+    pub name: String,
+    pub ty: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Terminal {
+    /// The terminal as written in the grammar: `"+"`, `r#"[0-9]+"#`,
+    /// `Num` for one declared in an `extern` block, or `error`.
+    pub name: String,
+
+    /// The type of its value.
+    pub ty: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Nonterminal {
+    /// The name of the nonterminal; those that normalization made up
+    /// start with `__` or contain `<`, like `Comma<Expr>`.
+    pub name: String,
+
+    /// The type of its value.
+    pub ty: String,
+
+    /// The byte offsets in the grammar of its definition.
+    pub span: (usize, usize),
+
+    pub productions: Vec<Production>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Production {
+    pub symbols: Vec<Symbol>,
+
+    /// The index in `Grammar::actions` of the code that builds the
+    /// value of the nonterminal from those of the symbols.
+    pub action: usize,
+
+    /// The byte offsets in the grammar of the alternative that it
+    /// came from.
+    pub span: (usize, usize),
+
+    /// The cost given to the alternative with `#[cost(N)]`, or 0.
+    pub cost: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Symbol {
+    Terminal(String),
+    Nonterminal(String),
+}
+
+/// The start symbol that LALRPOP adds for a public nonterminal, whose
+/// one production derives just that nonterminal.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StartSymbol {
+    /// The public nonterminal, like `Expr`.
+    pub public: String,
+
+    /// The start symbol, like `__Expr`.
+    pub start: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Action {
+    /// The type of the value that the action returns, or, if it is
+    /// fallible, the type of its `Ok` value.
+    pub ret_type: String,
+
+    /// Whether the action code can fail, as in `=>? { ... }`.
+    pub fallible: bool,
+
+    pub kind: ActionKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ActionKind {
+    /// Code written in the grammar.
+    User(UserAction),
+
+    /// An action that the inlining of an `#[inline]` nonterminal made
+    /// out of others.
+    Inline(InlineAction),
+
+    /// The location after the previous symbol (`@L`).
+    Lookahead,
+
+    /// The location before the next symbol (`@R`).
+    Lookbehind,
+
+    /// The token after the production, if any (`@T`).
+    LookaheadToken,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UserAction {
+    /// The value of each symbol of the production, which the code
+    /// is given along with the parameters of the grammar.
+    pub arguments: Vec<Argument>,
+
+    /// The code itself, as a Rust expression.
+    pub code: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Argument {
+    /// The pattern that binds the value, like `e` or `(a, b)`; one
+    /// that the grammar does not name is made up, like `__0`.
+    pub pattern: String,
+
+    pub ty: String,
+}
+
+/// If `A = B C D` (with action 44) is inlined into `X = Y A Z` (with
+/// action 22), the production `X = Y B C D Z` gets an inline action
+/// whose `action` is 22 and whose `symbols` are `Y`, `B C D` with
+/// action 44, and `Z`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct InlineAction {
+    pub action: usize,
+    pub symbols: Vec<InlinedSymbol>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum InlinedSymbol {
+    Original(Symbol),
+
+    /// The symbols of an inlined production, whose action turns their
+    /// values into one.
+    Inlined(usize, Vec<Symbol>),
+}
+
+/// The LR automaton of the parser for one public nonterminal, from
+/// which its action and goto tables are made.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Automaton {
+    /// The `StartSymbol` that the parser starts from.
+    pub start_symbol: StartSymbol,
+
+    /// The states, the first of which is the initial one.
+    pub states: Vec<State>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct State {
+    /// Each terminal that the state shifts, and the index of the
+    /// state that it goes to.
+    pub shifts: Vec<(String, usize)>,
+
+    pub reductions: Vec<Reduction>,
+
+    /// Each nonterminal, and the index of the state that the parser
+    /// goes to once it has reduced that nonterminal in this state.
+    pub gotos: Vec<(String, usize)>,
+
+    /// The terminals on which the state has a conflict that is left to
+    /// the token after them (with `#[lookahead2]`) or to a conflict
+    /// hook, at parse time.
+    pub undecided: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Reduction {
+    /// The nonterminal of the production, and the index of the
+    /// production among its `productions`.
+    pub nonterminal: String,
+    pub production: usize,
+
+    /// The tokens after the production on which it is reduced.
+    pub lookahead: Vec<Lookahead>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Lookahead {
+    Terminal(String),
+
+    /// The end of the input.
+    Eof,
+
+    /// An error token, as the parser pushes to recover from an error.
+    Error,
+}
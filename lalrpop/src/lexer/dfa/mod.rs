@@ -11,11 +11,12 @@ use lexer::nfa::{self, NFA, NFAConstructionError, NFAStateIndex, Test};
 #[cfg(test)]
 mod test;
 
-#[cfg(test)]
 pub mod interpret;
 
 mod overlap;
 
+pub mod sample;
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct DFA {
     pub states: Vec<State>
@@ -69,6 +70,11 @@ struct DFABuilder<'nfa> {
 pub struct State {
     item_set: DFAItemSet,
     pub kind: Kind,
+
+    // the regexs that also match here, but lose out to the one in
+    // `kind` on precedence
+    shadowed: Vec<NFAIndex>,
+
     pub test_edges: Vec<(Test, DFAStateIndex)>,
     pub other_edge: DFAStateIndex,
 }
@@ -143,6 +149,7 @@ impl<'nfa> DFABuilder<'nfa> {
                         .iter()
                         .all(|&item| self.nfa(item).is_rejecting_state(item.nfa_state));
 
+            let mut shadowed = vec![];
             let kind = if all_rejects || item_set.items.is_empty() {
                 Kind::Reject
             } else if all_accepts.len() == 0 {
@@ -160,6 +167,7 @@ impl<'nfa> DFABuilder<'nfa> {
                         match1: next_nfa
                     });
                 }
+                shadowed.extend(all_accepts[..all_accepts.len() - 1].iter().map(|&(_, nfa)| nfa));
                 Kind::Accepts(best_nfa)
             };
 
@@ -200,6 +208,7 @@ impl<'nfa> DFABuilder<'nfa> {
             let state = State {
                 item_set: item_set,
                 kind: kind,
+                shadowed: shadowed,
                 test_edges: test_edges,
                 other_edge: other_edge,
             };
@@ -0,0 +1,98 @@
+//! Finds a sample of the text matched by each regular expression in
+//! a DFA, as the generated lexer would tokenize it.
+
+use collections::set;
+use lexer::dfa::{DFA, Kind, NFAIndex, State, START};
+use std::char;
+use std::collections::VecDeque;
+
+/// For each of the `count` regular expressions in `dfa`, the shortest
+/// string that the lexer would tokenize as a match for it, if any.
+pub fn shortest_matches(dfa: &DFA, count: usize) -> Vec<Option<String>> {
+    let mut matches = vec![None; count];
+    visit_states(dfa, |state, text| {
+        if let Kind::Accepts(nfa) = state.kind {
+            if matches[nfa.index()].is_none() {
+                matches[nfa.index()] = Some(text.to_string());
+            }
+        }
+    });
+    matches
+}
+
+/// A regular expression that the lexer never produces, because one of
+/// higher precedence matches every string that it matches.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Shadowing {
+    pub shadowed: NFAIndex,
+    pub by: NFAIndex,
+
+    /// The shortest string that both match, which the lexer
+    /// tokenizes as a match for `by`.
+    pub example: String,
+}
+
+/// The regular expressions among the `count` in `dfa` that are
+/// shadowed, in order.
+pub fn shadowings(dfa: &DFA, count: usize) -> Vec<Shadowing> {
+    let matches = shortest_matches(dfa, count);
+    let mut shadowings: Vec<Option<Shadowing>> = vec![None; count];
+    visit_states(dfa, |state, text| {
+        if let Kind::Accepts(by) = state.kind {
+            for &nfa in &state.shadowed {
+                if matches[nfa.index()].is_none() && shadowings[nfa.index()].is_none() {
+                    shadowings[nfa.index()] = Some(Shadowing {
+                        shadowed: nfa,
+                        by: by,
+                        example: text.to_string(),
+                    });
+                }
+            }
+        }
+    });
+    shadowings.into_iter().filter_map(|shadowing| shadowing).collect()
+}
+
+/// Calls `f` with each state of `dfa` that can be reached without
+/// the lexer giving up, along with the shortest string that leads
+/// there from the start.
+fn visit_states<F>(dfa: &DFA, mut f: F)
+    where F: FnMut(&State, &str)
+{
+    let mut visited = set();
+    let mut queue = VecDeque::new();
+    queue.push_back((START, String::new()));
+
+    // breadth-first, so the first time we reach a state is via the
+    // shortest string
+    while let Some((state_index, text)) = queue.pop_front() {
+        if !visited.insert(state_index) {
+            continue;
+        }
+
+        let state = dfa.state(state_index);
+        if let Kind::Reject = state.kind {
+            continue;
+        }
+        f(state, &text);
+
+        for &(test, target) in &state.test_edges {
+            // prefer visible characters, so that samples do not get
+            // mixed up with the whitespace between tokens
+            let mut chars = (test.start..test.end).take(128).filter_map(char::from_u32);
+            let ch = chars.clone().find(|ch| !ch.is_whitespace()).or_else(|| chars.next());
+            if let Some(ch) = ch {
+                queue.push_back((target, format!("{}{}", text, ch)));
+            }
+        }
+
+        // the "other" edge is taken by any character not covered by a
+        // test edge; try to find a printable one
+        let other = (b'!'..b'~' + 1)
+            .map(|b| b as char)
+            .find(|&ch| !state.test_edges.iter().any(|&(test, _)| test.contains_char(ch)));
+        if let Some(ch) = other {
+            queue.push_back((state.other_edge, format!("{}{}", text, ch)));
+        }
+    }
+}
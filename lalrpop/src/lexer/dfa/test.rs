@@ -1,5 +1,6 @@
 use lexer::dfa::{self, DFA, DFAConstructionError, NFAIndex, Precedence};
 use lexer::dfa::interpret::interpret;
+use lexer::dfa::sample;
 use lexer::re;
 
 pub fn dfa(inputs: &[(&str, Precedence)]) -> Result<DFA, DFAConstructionError> {
@@ -79,3 +80,35 @@ fn alternatives_contraction() {
     assert_eq!(interpret(&dfa, "abcd"), Some((NFAIndex(0), "abcd")));
     assert_eq!(interpret(&dfa, "123"), None);
 }
+
+#[test]
+fn shortest_matches() {
+    let dfa = dfa(&[
+        /* 0 */ (r#"class"#, P1),
+        /* 1 */ (r#"[a-zA-Z_][a-zA-Z0-9_]*"#, P0),
+        /* 2 */ (r#"[0-9]+"#, P0),
+        /* 3 */ (r#">>"#, P0),
+        /* 4 */ (r#">"#, P0),
+        ]).unwrap();
+
+    let samples = sample::shortest_matches(&dfa, 5);
+    assert_eq!(samples, vec![Some("class".to_string()),
+                             Some("A".to_string()),
+                             Some("0".to_string()),
+                             Some(">>".to_string()),
+                             Some(">".to_string())]);
+}
+
+#[test]
+fn shadowings() {
+    let dfa = dfa(&[
+        /* 0 */ (r#"[a-z]+"#, P1),
+        /* 1 */ (r#"if"#, P0),
+        /* 2 */ (r#"[a-z0-9]+"#, P0),
+        ]).unwrap();
+
+    assert_eq!(sample::shadowings(&dfa, 3),
+               vec![sample::Shadowing { shadowed: NFAIndex(1),
+                                        by: NFAIndex(0),
+                                        example: "if".to_string() }]);
+}
@@ -8,7 +8,7 @@ mod __intern_token {
 
     pub struct __Matcher<'input> {
         text: &'input str,
-        consumed: usize,
+        location: usize,
         regex_set: __regex::RegexSet,
         regex_vec: Vec<__regex::Regex>,
     }
@@ -26,6 +26,11 @@ mod __intern_token {
 }
 ```
 
+The locations are byte offsets (`usize`) unless the grammar declares
+another `type Location` in an `extern` block, which must implement
+`lalrpop_util::location::Location`; the matcher then works them out
+as it goes with `Location::advance`.
+
  */
 
 use intern;
@@ -42,14 +47,15 @@ pub fn compile<W: Write>(
     -> io::Result<()>
 {
     let prefix = &grammar.prefix;
+    let loc_type = grammar.types.terminal_loc_type();
 
     rust!(out, "mod {}intern_token {{", prefix);
     rust!(out, "#![allow(unused_imports)]");
-    try!(out.write_uses("", &grammar));
+    try!(out.write_uses("super::super::", &grammar));
     rust!(out, "extern crate regex as {}regex;", prefix);
     rust!(out, "pub struct {}Matcher<'input> {{", prefix);
     rust!(out, "text: &'input str,"); // remaining input
-    rust!(out, "consumed: usize,"); // number of chars consumed thus far
+    rust!(out, "location: {},", loc_type); // location of the remaining input
     rust!(out, "regex_set: {}regex::RegexSet,", prefix);
     rust!(out, "regex_vec: Vec<{}regex::Regex>,", prefix);
     rust!(out, "}}");
@@ -94,29 +100,60 @@ pub fn compile<W: Write>(
 
     rust!(out, "{}Matcher {{", prefix);
     rust!(out, "text: s,");
-    rust!(out, "consumed: 0,");
+    rust!(out, "location: ::std::default::Default::default(),");
     rust!(out, "regex_set: {}regex_set,", prefix);
     rust!(out, "regex_vec: {}regex_vec,", prefix);
     rust!(out, "}}"); // struct literal
     rust!(out, "}}"); // fn new()
+
+    // the `parse_Foo_items` iterators can go back to an earlier item
+    if grammar.generates_item_checkpoints() {
+        rust!(out, "");
+        rust!(out, "pub fn offset(&self) -> usize {{");
+        rust!(out, "self.location");
+        rust!(out, "}}"); // fn offset()
+        rust!(out, "");
+        rust!(out, "pub fn seek(&mut self, s: &'input str, offset: usize) {{");
+        rust!(out, "self.text = &s[offset..];");
+        rust!(out, "self.location = offset;");
+        rust!(out, "}}"); // fn seek()
+    }
+
+    // `parse_Foo_incremental` picks up the input where a checkpoint
+    // was taken
+    if grammar.algorithm.incremental {
+        rust!(out, "");
+        rust!(out, "pub fn resume(s: &'input str, offset: usize) -> {}Matcher<'input> {{", prefix);
+        rust!(out, "let mut {}matcher = {}Matcher::new(&s[offset..]);", prefix, prefix);
+        rust!(out, "{}matcher.location = offset;", prefix);
+        rust!(out, "{}matcher", prefix);
+        rust!(out, "}}"); // fn resume()
+    }
     rust!(out, "}}"); // impl Matcher<'input>
     rust!(out, "");
     rust!(out, "impl<'input> Iterator for {}Matcher<'input> {{", prefix);
-    rust!(out, "type Item = Result<(usize, (usize, &'input str), usize), \
-                {}lalrpop_util::ParseError<usize,(usize, &'input str),{}>>;",
-          prefix, grammar.types.error_type());
+    rust!(out, "type Item = Result<({}, (usize, &'input str), {}), \
+                {}lalrpop_util::ParseError<{},(usize, &'input str),{}>>;",
+          loc_type, loc_type, prefix, loc_type, grammar.types.error_type());
     rust!(out, "");
     rust!(out, "fn next(&mut self) -> Option<Self::Item> {{");
 
-    // start by trimming whitespace from left
-    rust!(out, "let {}text = self.text.trim_left();", prefix);
+    // start by trimming whitespace from left, unless every character,
+    // whitespace included, is a token of its own
+    if grammar.algorithm.scannerless {
+        rust!(out, "let {}text = self.text;", prefix);
+    } else {
+        rust!(out, "let {}text = self.text.trim_left();", prefix);
+    }
     rust!(out, "let {}whitespace = self.text.len() - {}text.len();", prefix, prefix);
-    rust!(out, "let {}start_offset = self.consumed + {}whitespace;", prefix, prefix);
+    rust!(out, "let {}start_location = {}lalrpop_util::location::Location::advance(\
+                &self.location, &self.text[..{}whitespace]);",
+          prefix, prefix, prefix);
 
     // if nothing left, return None
     rust!(out, "if {}text.is_empty() {{", prefix);
     rust!(out, "self.text = {}text;", prefix);
-    rust!(out, "self.consumed = {}start_offset;", prefix);
+    rust!(out, "self.location = {}start_location;", prefix);
     rust!(out, "None");
     rust!(out, "}} else {{");
 
@@ -126,7 +163,7 @@ pub fn compile<W: Write>(
     // if nothing matched, return an error
     rust!(out, "if !{}matches.matched_any() {{", prefix);
     rust!(out, "Some(Err({}lalrpop_util::ParseError::InvalidToken {{", prefix);
-    rust!(out, "location: {}start_offset,", prefix);
+    rust!(out, "location: {}start_location,", prefix);
     rust!(out, "}}))");
     rust!(out, "}} else {{");
 
@@ -158,10 +195,12 @@ pub fn compile<W: Write>(
     // transform the result into the expected return value
     rust!(out, "let {}result = &{}text[..{}longest_match];", prefix, prefix, prefix);
     rust!(out, "let {}remaining = &{}text[{}longest_match..];", prefix, prefix, prefix);
-    rust!(out, "let {}end_offset = {}start_offset + {}longest_match;", prefix, prefix, prefix);
+    rust!(out, "let {}end_location = {}lalrpop_util::location::Location::advance(\
+                &{}start_location, {}result);",
+          prefix, prefix, prefix, prefix);
     rust!(out, "self.text = {}remaining;", prefix);
-    rust!(out, "self.consumed = {}end_offset;", prefix);
-    rust!(out, "Some(Ok(({}start_offset, ({}index, {}result), {}end_offset)))",
+    rust!(out, "self.location = {}end_location.clone();", prefix);
+    rust!(out, "Some(Ok(({}start_location, ({}index, {}result), {}end_location)))",
           prefix, prefix, prefix, prefix);
 
     rust!(out, "}}"); // else
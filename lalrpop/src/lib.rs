@@ -19,7 +19,13 @@ extern crate lalrpop_util;
 extern crate petgraph;
 extern crate regex;
 extern crate regex_syntax;
+extern crate rustc_serialize;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_json;
 extern crate term;
+extern crate unicode_width;
 extern crate unicode_xid;
 
 #[cfg(test)]
@@ -32,15 +38,20 @@ mod rust;
 mod log;
 
 mod api;
+mod backend;
 mod build;
 mod collections;
+mod diagnostics;
 mod file_text;
 mod grammar;
+pub mod ir;
 mod lexer;
+mod lint;
 mod lr1;
 mod message;
 mod normalize;
 mod parser;
+mod pass;
 mod kernel_set;
 mod session;
 mod tls;
@@ -51,6 +62,12 @@ mod util;
 #[cfg(test)] mod test_util;
 
 pub use api::Configuration;
+pub use api::{GrammarBuilder, NonterminalBuilder, SymbolBuilder};
 pub use api::process_root;
 pub use api::process_root_unconditionally;
+pub use backend::{Backend, BackendInput, OutputFile};
+pub use diagnostics::{Diagnostic, Diagnostics, Severity};
+pub use pass::Pass;
+pub use message::catalog::{CatalogEntry, CATALOG};
+pub use log::Level;
 pub use ascii_canvas::style;
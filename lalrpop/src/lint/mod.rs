@@ -0,0 +1,260 @@
+//! Checks over the normalized grammar for things that are legal, but
+//! which probably indicate a mistake on the user's part. Unlike
+//! normalization errors, these do not prevent us from generating a
+//! parser; they are just reported to the user as warnings.
+
+use collections::{set, Set};
+use grammar::consts::INLINE;
+use grammar::repr::*;
+use intern::intern;
+use lexer::dfa::sample;
+
+#[cfg(test)]
+mod test;
+
+#[derive(Clone, Debug)]
+pub struct Warning {
+    pub message: String,
+    pub span: Span,
+}
+
+pub fn lint(grammar: &Grammar) -> Vec<Warning> {
+    let mut warnings = vec![];
+    unused_conversions(grammar, &mut warnings);
+    unused_reserved_words(grammar, &mut warnings);
+    shadowed_terminals(grammar, &mut warnings);
+    unused_bindings(grammar, &mut warnings);
+    dead_nonterminals(grammar, &mut warnings);
+    warnings
+}
+
+fn used_terminals(grammar: &Grammar) -> Set<TerminalString> {
+    grammar.nonterminals
+           .values()
+           .flat_map(|data| data.productions.iter())
+           .flat_map(|production| production.symbols.iter())
+           .filter_map(|symbol| match *symbol {
+               Symbol::Terminal(term) => Some(term),
+               Symbol::Nonterminal(_) => None,
+           })
+           .collect()
+}
+
+/// If the user supplied an `extern { enum Tok { ... } }` section,
+/// check that every declared conversion is actually consumed by some
+/// production. An unused conversion usually means that the grammar
+/// and the external lexer have drifted apart.
+fn unused_conversions(grammar: &Grammar, warnings: &mut Vec<Warning>) {
+    // With the internal tokenizer, the conversions are synthesized
+    // from the terminals used in the grammar, so there is nothing to
+    // check.
+    if grammar.intern_token.is_some() {
+        return;
+    }
+
+    let used_terminals = used_terminals(grammar);
+
+    let mut reported = set();
+    for (&terminal, pattern) in &grammar.conversions {
+        if !used_terminals.contains(&terminal) && reported.insert(terminal) {
+            warnings.push(Warning {
+                message: format!("terminal `{}` is declared in the extern token \
+                                  enum, but never used in the grammar",
+                                 terminal),
+                span: pattern.span,
+            });
+        }
+    }
+}
+
+/// A reserved word that is never used as a keyword is still lexed as
+/// one, so it can only ever produce a parse error. That is sometimes
+/// intentional (e.g., to reserve a keyword for future use), but is
+/// worth pointing out.
+fn unused_reserved_words(grammar: &Grammar, warnings: &mut Vec<Warning>) {
+    let intern_token = match grammar.intern_token {
+        Some(ref intern_token) => intern_token,
+        None => return,
+    };
+
+    let used_terminals = used_terminals(grammar);
+    for reserved_word in &intern_token.reserved_words {
+        let literal = TerminalLiteral::Quoted(reserved_word.word);
+        let used = intern_token.match_entries
+                               .iter()
+                               .filter(|entry| entry.match_literal == literal)
+                               .any(|entry| used_terminals.contains(&entry.user_name));
+        if !used {
+            warnings.push(Warning {
+                message: format!("reserved word `{}` is never used as a terminal \
+                                  in the grammar",
+                                 literal),
+                span: reserved_word.span,
+            });
+        }
+    }
+}
+
+/// A terminal of the built-in lexer is never produced if a terminal
+/// of higher precedence (a quoted one, or one from an earlier `match`
+/// group) matches every string that it matches, which leaves the
+/// productions that use it dead. We point at the first of those.
+fn shadowed_terminals(grammar: &Grammar, warnings: &mut Vec<Warning>) {
+    let intern_token = match grammar.intern_token {
+        Some(ref intern_token) => intern_token,
+        None => return,
+    };
+
+    let entries = &intern_token.match_entries;
+    for shadowing in sample::shadowings(&intern_token.dfa, entries.len()) {
+        let terminal = entries[shadowing.shadowed.index()].user_name;
+        let span = grammar.nonterminals
+                          .values()
+                          .flat_map(|data| data.productions.iter())
+                          .find(|production| {
+                              production.symbols.contains(&Symbol::Terminal(terminal))
+                          })
+                          .map(|production| production.span);
+        if let Some(span) = span {
+            warnings.push(Warning {
+                message: format!("terminal `{}` is never produced by the lexer, since `{}` \
+                                  takes precedence over it on every input that it matches \
+                                  (e.g., {:?})",
+                                 terminal,
+                                 entries[shadowing.by.index()].user_name,
+                                 shadowing.example),
+                span: span,
+            });
+        }
+    }
+}
+
+/// A name like `x` in `<x:Foo>` that the action code never mentions
+/// was usually meant to be used, e.g. when an alternative was copied
+/// from another and its action was not updated. Names that start with
+/// `_` are exempt, as in Rust. Since macros are expanded by now, and
+/// each instantiation is spanned where it is used, we tell the copies
+/// of an alternative by their bindings and action code, and report
+/// each alternative once, however often it was instantiated.
+fn unused_bindings(grammar: &Grammar, warnings: &mut Vec<Warning>) {
+    let mut reported = set();
+    for production in grammar.nonterminals.values().flat_map(|data| data.productions.iter()) {
+        let defn = match grammar.action_fn_defns[production.action.index()].kind {
+            ActionFnDefnKind::User(ref defn) => defn,
+            ActionFnDefnKind::Inline(_) | ActionFnDefnKind::Lookaround(_) => continue,
+        };
+        let used: Set<&str> = defn.code
+                                  .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                                  .collect();
+        for &pattern in &defn.arg_patterns {
+            let name = pattern.to_string();
+            if name.starts_with('_') || used.contains(&name[..]) {
+                continue;
+            }
+            if reported.insert((&defn.arg_patterns, &defn.code, pattern)) {
+                warnings.push(Warning {
+                    message: format!("`{}` is bound in this alternative, but never used by its \
+                                      action code",
+                                     name),
+                    span: production.span,
+                });
+            }
+        }
+    }
+}
+
+/// Nonterminals that can never be parsed, since every alternative
+/// needs one that cannot be parsed either (e.g., `E = E "+" T;` without
+/// another alternative), and those that no public nonterminal uses.
+/// Only nonterminals that the user named are reported, not those made
+/// by macros or by LALRPOP itself, which are dead exactly when the one
+/// that uses them is.
+fn dead_nonterminals(grammar: &Grammar, warnings: &mut Vec<Warning>) {
+    let productive = productive_nonterminals(grammar);
+    let reachable = reachable_nonterminals(grammar);
+    for data in grammar.nonterminals.values() {
+        if !user_named(data.name) {
+            continue;
+        }
+        if !productive.contains(&data.name) {
+            warnings.push(Warning {
+                message: format!("`{}` can never be parsed, since each of its alternatives \
+                                  needs itself or another nonterminal that cannot be parsed",
+                                 data.name),
+                span: data.span,
+            });
+        } else if !grammar.start_nonterminals.is_empty() && !reachable.contains(&data.name) {
+            warnings.push(Warning {
+                message: format!("`{}` is not used by any public nonterminal, so it is never \
+                                  parsed",
+                                 data.name),
+                span: data.span,
+            });
+        }
+    }
+}
+
+/// The nonterminals that derive some string of terminals.
+fn productive_nonterminals(grammar: &Grammar) -> Set<NonterminalString> {
+    let mut productive = set();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for data in grammar.nonterminals.values() {
+            if productive.contains(&data.name) {
+                continue;
+            }
+            let derives = data.productions.iter().any(|production| {
+                production.symbols.iter().all(|symbol| match *symbol {
+                    Symbol::Terminal(_) => true,
+                    Symbol::Nonterminal(nt) => productive.contains(&nt),
+                })
+            });
+            if derives {
+                productive.insert(data.name);
+                changed = true;
+            }
+        }
+    }
+    productive
+}
+
+/// The nonterminals used, directly or not, by a public one or by an
+/// inline test. Inlined nonterminals are used through the productions
+/// they were inlined into, so they count as used.
+fn reachable_nonterminals(grammar: &Grammar) -> Set<NonterminalString> {
+    let inline = intern(INLINE);
+    let mut stack: Vec<NonterminalString> =
+        grammar.start_nonterminals
+               .values()
+               .chain(grammar.item_start_nonterminals.values())
+               .cloned()
+               .chain(grammar.tests.iter().map(|test| test.nonterminal))
+               .chain(grammar.nonterminals
+                             .values()
+                             .filter(|data| data.annotations.iter().any(|a| a.id == inline))
+                             .map(|data| data.name))
+               .collect();
+    let mut reachable = set();
+    while let Some(nt) = stack.pop() {
+        if !reachable.insert(nt) {
+            continue;
+        }
+        for production in grammar.productions_for(nt) {
+            for symbol in &production.symbols {
+                if let Symbol::Nonterminal(nt) = *symbol {
+                    stack.push(nt);
+                }
+            }
+        }
+    }
+    reachable
+}
+
+/// Whether `nt` is named in the grammar, rather than made by a macro,
+/// a `*` or an operator table, whose names are not identifiers, or by
+/// LALRPOP, whose names start with `__`.
+fn user_named(nt: NonterminalString) -> bool {
+    let name = nt.to_string();
+    !name.starts_with("__") && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
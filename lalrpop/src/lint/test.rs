@@ -0,0 +1,121 @@
+use test_util::normalized_grammar;
+
+use super::lint;
+
+#[test]
+fn unused_conversion() {
+    let grammar = normalized_grammar(r#"
+grammar;
+extern {
+    enum Tok {
+        "+" => Tok::Plus,
+        "-" => Tok::Minus,
+        "N" => Tok::Num(<i32>),
+    }
+}
+pub E: () = { E "+" "N", "N" };
+"#);
+    let warnings = lint(&grammar);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].message,
+               "terminal `\"-\"` is declared in the extern token enum, but never used in the grammar");
+}
+
+#[test]
+fn all_conversions_used() {
+    let grammar = normalized_grammar(r#"
+grammar;
+extern {
+    enum Tok {
+        "+" => Tok::Plus,
+        "N" => Tok::Num(<i32>),
+    }
+}
+pub E: () = { E "+" "N", "N" };
+"#);
+    assert!(lint(&grammar).is_empty());
+}
+
+#[test]
+fn intern_token_not_checked() {
+    let grammar = normalized_grammar(r#"
+grammar;
+match { "+", "-", r"[0-9]+" }
+pub E: () = { E "+" r"[0-9]+", r"[0-9]+" };
+"#);
+    assert!(lint(&grammar).is_empty());
+}
+
+#[test]
+fn unused_reserved_word() {
+    let grammar = normalized_grammar(r#"
+grammar;
+reserved { "if", "async" }
+pub E: () = { "if" r"[a-z]+" };
+"#);
+    let warnings = lint(&grammar);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].message,
+               "reserved word `\"async\"` is never used as a terminal in the grammar");
+}
+
+#[test]
+fn shadowed_terminal() {
+    let grammar = normalized_grammar(r#"
+grammar;
+match {
+    r"[a-z]+" => ID,
+} else {
+    "if",
+    r"[0-9]+",
+}
+pub S: () = { "if" ID, ID r"[0-9]+" };
+"#);
+    let warnings = lint(&grammar);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].message,
+               "terminal `\"if\"` is never produced by the lexer, since `ID` takes precedence \
+                over it on every input that it matches (e.g., \"if\")");
+}
+
+#[test]
+fn unused_binding() {
+    let grammar = normalized_grammar(r#"
+grammar;
+pub S: (i32, i32) = {
+    <a:N> "+" <b:N> => (a, a),
+    <a:N> "-" <_b:N> => (a, 0),
+    "(" <a:N> <b:N> ")" => (<>),
+    Pair<"x">,
+    Pair<"y">,
+};
+Pair<T>: (i32, i32) = <a:N> T <b:N> => (b, b);
+N: i32 = "N" => 0;
+"#);
+    let messages: Vec<_> = lint(&grammar).into_iter().map(|w| w.message).collect();
+    assert_eq!(messages, vec![
+        "`a` is bound in this alternative, but never used by its action code",
+        "`b` is bound in this alternative, but never used by its action code",
+    ]);
+}
+
+#[test]
+fn dead_nonterminal() {
+    let grammar = normalized_grammar(r#"
+grammar;
+pub S: () = { A, B };
+A: () = "a" => ();
+B: () = "b" B => ();
+C: () = "c" => ();
+D: () = "d"* => ();
+#[inline] I: () = "i" => ();
+"#);
+    let mut messages: Vec<_> = lint(&grammar).into_iter().map(|w| w.message).collect();
+    messages.sort();
+    assert_eq!(messages, vec![
+        "`B` can never be parsed, since each of its alternatives needs itself or another \
+         nonterminal that cannot be parsed",
+        "`C` is not used by any public nonterminal, so it is never parsed",
+        "`D` is not used by any public nonterminal, so it is never parsed",
+    ]);
+}
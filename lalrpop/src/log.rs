@@ -3,7 +3,7 @@ pub struct Log {
     level: Level,
 }
 
-#[derive(Clone, PartialOrd, Ord, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialOrd, Ord, PartialEq, Eq)]
 pub enum Level {
     /// No updates unless an error arises.
     Taciturn,
@@ -27,10 +27,15 @@ impl Log {
         self.level = level;
     }
 
+    /// Whether messages at `level` are logged.
+    pub fn enabled(&self, level: Level) -> bool {
+        self.level >= level
+    }
+
     pub fn log<M>(&self, level: Level, message: M)
         where M: FnOnce() -> String
     {
-        if self.level >= level {
+        if self.enabled(level) {
             println!("{}", message());
         }
     }
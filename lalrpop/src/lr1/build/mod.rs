@@ -18,16 +18,29 @@ fn build_lr1_states_legacy<'grammar>(grammar: &'grammar Grammar, start: Nontermi
 {
     let eof = TokenSet::eof();
     let mut lr1: LR<'grammar, TokenSet> = LR::new(grammar, start, eof);
-    lr1.set_permit_early_stop(true);
+    // conflicts may yet be resolved by precedence, with a second token
+    // of lookahead, by shifting, or at parse time, so we need all the
+    // states
+    let resolves_conflicts = grammar.uses_precedence() ||
+                             grammar.algorithm.lookahead2 ||
+                             grammar.prefers_shift() ||
+                             grammar.uses_conflict_hooks();
+    lr1.set_permit_early_stop(!resolves_conflicts);
+    lr1.set_max_states(Tls::session().max_states);
     lr1.build_states()
 }
 
 type ConstructionFunction<'grammar> = fn(&'grammar Grammar, NonterminalString) -> LR1Result<'grammar> ;
 
-fn use_lane_table() -> bool {
-    match env::var("LALRPOP_LANE_TABLE") {
-        Ok(ref s) => s == "enabled",
-        _ => false
+fn use_lane_table(grammar: &Grammar) -> bool {
+    // an annotation on the grammar takes precedence over the environment
+    match grammar.algorithm.construction {
+        Some(LrConstruction::Lr1) => false,
+        Some(LrConstruction::LaneTable) => true,
+        _ => match env::var("LALRPOP_LANE_TABLE") {
+            Ok(ref s) => s == "enabled",
+            _ => false
+        }
     }
 }
 
@@ -35,7 +48,7 @@ pub fn build_lr1_states<'grammar>(grammar: &'grammar Grammar,
                                   start: NonterminalString)
                                   -> LR1Result<'grammar>
 {
-    let (method_name, method_fn) = if use_lane_table() {
+    let (method_name, method_fn) = if use_lane_table(grammar) {
         ("lane", build_lane_table_states as ConstructionFunction)
     } else {
         ("legacy", build_lr1_states_legacy as ConstructionFunction)
@@ -65,6 +78,7 @@ pub struct LR<'grammar, L: LookaheadBuild> {
     start_nt: NonterminalString,
     start_lookahead: L,
     permit_early_stop: bool,
+    max_states: Option<usize>,
 }
 
 impl<'grammar, L: LookaheadBuild> LR<'grammar, L> {
@@ -78,6 +92,7 @@ impl<'grammar, L: LookaheadBuild> LR<'grammar, L> {
             start_nt: start_nt,
             start_lookahead: start_lookahead,
             permit_early_stop: false,
+            max_states: None,
         }
     }
 
@@ -85,6 +100,12 @@ impl<'grammar, L: LookaheadBuild> LR<'grammar, L> {
         self.permit_early_stop = v;
     }
 
+    /// Stop as soon as there are more than `max` states, which leaves
+    /// the automaton incomplete (see `lr1::exceeds_max_states`).
+    fn set_max_states(&mut self, max: Option<usize>) {
+        self.max_states = max;
+    }
+
     fn build_states(&self)
                     -> Result<Vec<State<'grammar, L>>,
                               TableConstructionError<'grammar, L>>
@@ -111,7 +132,9 @@ impl<'grammar, L: LookaheadBuild> LR<'grammar, L> {
                                          items: items.clone(),
                                          shifts: map(),
                                          reductions: vec!(),
-                                         gotos: map() };
+                                         gotos: map(),
+                                         lookahead2: map(),
+                                         conflict_hooks: map() };
 
             // group the items that we can transition into by shifting
             // over a term or nonterm
@@ -168,6 +191,12 @@ impl<'grammar, L: LookaheadBuild> LR<'grammar, L> {
                      "{} conflicts encountered, stopping.", conflicts.len());
                 break;
             }
+
+            if self.max_states.map_or(false, |max| states.len() > max) {
+                log!(session, Verbose,
+                     "more than {} states created, stopping.", states.len() - 1);
+                break;
+            }
         }
 
         if !conflicts.is_empty() {
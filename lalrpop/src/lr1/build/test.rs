@@ -7,7 +7,11 @@ use lr1::interpret::interpret;
 use lr1::lookahead::Token;
 use lr1::lookahead::Token::EOF;
 use lr1::lookahead::TokenSet;
+use lr1::exceeds_max_states;
 use lr1::tls::Lr1Tls;
+use file_text::FileText;
+use session::Session;
+use std::rc::Rc;
 use tls::Tls;
 
 use super::{LR, use_lane_table, build_lr0_states, build_lr1_states};
@@ -129,7 +133,7 @@ grammar;
     // and yields expected number of states.
     let states = build_lr1_states(&grammar, nt("S")).unwrap();
     println!("{:#?}", states);
-    assert_eq!(states.len(), if use_lane_table() { 9 } else { 16 });
+    assert_eq!(states.len(), if use_lane_table(&grammar) { 9 } else { 16 });
 
     // execute it on some sample inputs.
     let tree = interpret(&states, tokens!["N", "-", "(", "N", "-", "N", ")"]).unwrap();
@@ -157,6 +161,69 @@ grammar;
     random_test(&grammar, &states, nt("S"));
 }
 
+#[test]
+fn construction_annotation() {
+    // the annotation on the grammar overrides `LALRPOP_LANE_TABLE`
+    for &(annotation, expected) in &[("LR1", 16), ("lane_table", 9)] {
+        let _tls = Tls::test();
+
+        let grammar = normalized_grammar(&format!(r#"
+#[{}]
+grammar;
+    extern {{ enum Tok {{ "-" => .., "N" => .., "(" => .., ")" => .. }} }}
+
+    S: () =
+        E => ();
+
+    E: () = {{
+        E "-" T => (),
+        T => ()
+    }};
+
+    T: () = {{
+        "N" => (),
+        "(" E ")" => ()
+    }};
+"#, annotation));
+
+        let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+        let states = build_lr1_states(&grammar, nt("S")).unwrap();
+        assert_eq!(states.len(), expected);
+    }
+}
+
+#[test]
+fn max_states() {
+    let mut session = Session::test();
+    session.max_states = Some(5);
+    let _tls = Tls::install(Rc::new(session), Rc::new(FileText::test()));
+
+    let grammar = normalized_grammar(r#"
+#[LR1]
+grammar;
+    extern { enum Tok { "-" => .., "N" => .., "(" => .., ")" => .. } }
+
+    S: () =
+        E => ();
+
+    E: () = {
+        E "-" T => (),
+        T => ()
+    };
+
+    T: () = {
+        "N" => (),
+        "(" E ")" => ()
+    };
+"#);
+
+    // the construction gives up on the sixth of the 16 states
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let states = build_lr1_states(&grammar, nt("S")).unwrap();
+    assert_eq!(states.len(), 6);
+    assert!(exceeds_max_states(&states));
+}
+
 #[test]
 fn shift_reduce_conflict1() {
     let _tls = Tls::test();
@@ -0,0 +1,128 @@
+//! IELR(1)-style state construction: tables about as small as
+//! LALR(1), but with exactly the conflicts of canonical LR(1).
+//!
+//! Rather than computing the lookaheads that IELR(1) would need in
+//! order to split LALR(1) states, we start from the canonical LR(1)
+//! states and merge as many of those with the same LR(0) core as we
+//! can without introducing a reduce/reduce conflict (merging cannot
+//! introduce a shift/reduce conflict, since states with the same
+//! core shift the same terminals). The merged states must then be
+//! split until all the states in a group go to the same group on
+//! each symbol, as in DFA minimization. Splitting never introduces
+//! a conflict, so the result is conflict-free whenever the canonical
+//! LR(1) states are.
+
+use collections::{map, Map};
+use lr1::build;
+use lr1::build_lalr::{lr0_core, merge_states};
+use lr1::core::*;
+use lr1::lookahead::*;
+use grammar::repr::*;
+use tls::Tls;
+
+#[cfg(test)]
+mod test;
+
+pub fn build_ielr_states<'grammar>(grammar: &'grammar Grammar,
+                                   start: NonterminalString)
+                                   -> LR1Result<'grammar> {
+    let lr_states = try!(build::build_lr1_states(grammar, start));
+
+    profile! {
+        &Tls::session(),
+        "IELR(1) state merge",
+        merge_compatible_states(&lr_states)
+    }
+}
+
+// The reductions of a group of merged states, with their lookaheads
+// unioned per production.
+type Reductions<'grammar> = Vec<(&'grammar Production, TokenSet)>;
+
+pub fn merge_compatible_states<'grammar>(lr_states: &[LR1State<'grammar>])
+                                         -> LR1Result<'grammar> {
+    // First, put each state into the first group with the same core
+    // that it does not conflict with.
+    let mut group_of = Vec::with_capacity(lr_states.len());
+    let mut groups_by_core: Map<Vec<LR0Item>, Vec<(usize, Reductions)>> = map();
+    let mut num_groups = 0;
+    for lr_state in lr_states {
+        let groups = groups_by_core.entry(lr0_core(lr_state)).or_insert(vec![]);
+        let compatible = groups.iter()
+                               .position(|&(_, ref reductions)| {
+                                   compatible(reductions, &lr_state.reductions)
+                               });
+        let group = match compatible {
+            Some(position) => {
+                let &mut (group, ref mut reductions) = &mut groups[position];
+                add_reductions(reductions, &lr_state.reductions);
+                group
+            }
+            None => {
+                let mut reductions = vec![];
+                add_reductions(&mut reductions, &lr_state.reductions);
+                groups.push((num_groups, reductions));
+                num_groups += 1;
+                num_groups - 1
+            }
+        };
+        group_of.push(group);
+    }
+
+    // Then split the groups until the states in each group agree on
+    // the group they go to for every symbol. Since each state is
+    // visited in order, the groups stay numbered in order of first
+    // appearance, as `merge_states` requires.
+    loop {
+        let mut split: Map<(usize, Vec<(Symbol, usize)>), usize> = map();
+        let mut next_group_of = Vec::with_capacity(lr_states.len());
+        for (index, lr_state) in lr_states.iter().enumerate() {
+            let successors: Vec<_> =
+                lr_state.shifts
+                        .iter()
+                        .map(|(&terminal, &target)| (Symbol::Terminal(terminal), target))
+                        .chain(lr_state.gotos
+                                       .iter()
+                                       .map(|(&nt, &target)| (Symbol::Nonterminal(nt), target)))
+                        .map(|(symbol, target)| (symbol, group_of[target.0]))
+                        .collect();
+            let next_group = split.len();
+            next_group_of.push(*split.entry((group_of[index], successors))
+                                     .or_insert(next_group));
+        }
+
+        let done = split.len() == num_groups;
+        group_of = next_group_of;
+        num_groups = split.len();
+        if done {
+            break;
+        }
+    }
+
+    let remap: Vec<_> = group_of.into_iter().map(StateIndex).collect();
+    merge_states(lr_states, &remap)
+}
+
+/// Whether merging a state with `new` reductions into a group with
+/// `reductions` would lead to a reduce/reduce conflict.
+fn compatible<'grammar>(reductions: &Reductions<'grammar>,
+                        new: &[(TokenSet, &'grammar Production)])
+                        -> bool {
+    new.iter().all(|&(ref new_lookahead, new_production)| {
+        reductions.iter().all(|&(production, ref lookahead)| {
+            production == new_production || new_lookahead.is_disjoint(lookahead)
+        })
+    })
+}
+
+fn add_reductions<'grammar>(reductions: &mut Reductions<'grammar>,
+                            new: &[(TokenSet, &'grammar Production)]) {
+    for &(ref new_lookahead, new_production) in new {
+        match reductions.iter().position(|&(production, _)| production == new_production) {
+            Some(position) => {
+                reductions[position].1.union_with(new_lookahead);
+            }
+            None => reductions.push((new_production, new_lookahead.clone())),
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use intern::intern;
+use grammar::repr::*;
+use lr1::build::build_lr1_states;
+use lr1::build_lalr::build_lalr_states;
+use lr1::tls::Lr1Tls;
+use test_util::{normalized_grammar};
+use tls::Tls;
+use super::build_ielr_states;
+use super::super::interpret::interpret;
+
+fn nt(t: &str) -> NonterminalString {
+    NonterminalString(intern(t))
+}
+
+macro_rules! tokens {
+    ($($x:expr),*) => {
+        vec![$(TerminalString::quoted(intern($x))),*]
+    }
+}
+
+#[test]
+fn figure9_23() {
+    let _tls = Tls::test();
+
+    let grammar = normalized_grammar(r#"
+        grammar;
+        extern { enum Tok { "-" => .., "N" => .., "(" => .., ")" => .. } }
+        S: () = E       => ();
+        E: () = {
+            E "-" T     => (),
+            T           => ()
+        };
+        T: () = {
+            "N"         => (),
+            "(" E ")"   => ()
+        };
+   "#);
+
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+
+    // the grammar is LALR(1), so we should end up with just as many
+    // states as LALR(1) does, and fewer than canonical LR(1)
+    let lr1_states = build_lr1_states(&grammar, nt("S")).unwrap();
+    let lalr_states = build_lalr_states(&grammar, nt("S")).unwrap();
+    let states = build_ielr_states(&grammar, nt("S")).unwrap();
+    assert_eq!(states.len(), lalr_states.len());
+    assert!(states.len() < lr1_states.len());
+
+    let tree = interpret(&states, tokens!["N", "-", "(", "N", "-", "N", ")"])
+        .unwrap();
+    assert_eq!(
+        &format!("{:?}", tree)[..],
+        r#"[S: [E: [E: [T: "N"]], "-", [T: "(", [E: [E: [T: "N"]], "-", [T: "N"]], ")"]]]"#);
+}
+
+#[test]
+fn not_lalr() {
+    let _tls = Tls::test();
+
+    // merging the states after `"a" "e"` and `"b" "e"`, as LALR(1)
+    // does, leads to a reduce/reduce conflict between `E` and `F`
+    let grammar = normalized_grammar(r#"
+        grammar;
+        extern { enum Tok { "a" => .., "b" => .., "c" => .., "d" => .., "e" => .. } }
+        S: () = {
+            "a" E "c"   => (),
+            "a" F "d"   => (),
+            "b" F "c"   => (),
+            "b" E "d"   => ()
+        };
+        E: () = "e"     => ();
+        F: () = "e"     => ();
+   "#);
+
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+
+    assert!(build_lalr_states(&grammar, nt("S")).is_err());
+    let states = build_ielr_states(&grammar, nt("S")).unwrap();
+
+    let tree = interpret(&states, tokens!["b", "e", "c"]).unwrap();
+    assert_eq!(&format!("{:?}", tree)[..], r#"[S: "b", [F: "e"], "c"]"#);
+}
@@ -43,32 +43,46 @@ pub fn collapse_to_lalr_states<'grammar>(lr_states: &[LR1State<'grammar>])
     // LALR(1) state to which we will remap it.
     let mut remap: Vec<_> = (0..lr_states.len()).map(|_| StateIndex(0)).collect();
     let mut lalr1_map: Map<Vec<LR0Item>, StateIndex> = map();
-    let mut lalr1_states: Vec<LALR1State> = vec![];
 
     for (lr1_index, lr1_state) in lr_states.iter().enumerate() {
-        let lr0_kernel: Vec<_> = lr1_state.items
-                                          .vec
-                                          .iter()
-                                          .map(|item| item.to_lr0())
-                                          .dedup()
-                                          .collect();
-
-        let lalr1_index = *lalr1_map.entry(lr0_kernel)
-                                    .or_insert_with(|| {
-                                        let index = StateIndex(lalr1_states.len());
-                                        lalr1_states.push(LALR1State {
-                                            index: index,
-                                            items: vec![],
-                                            shifts: map(),
-                                            reductions: Multimap::new(),
-                                            gotos: map(),
-                                        });
-                                        index
-                                    });
+        let next_index = StateIndex(lalr1_map.len());
+        remap[lr1_index] = *lalr1_map.entry(lr0_core(lr1_state)).or_insert(next_index);
+    }
 
-        lalr1_states[lalr1_index.0].items.extend(lr1_state.items.vec.iter().cloned());
+    merge_states(lr_states, &remap)
+}
+
+/// The LR(0) items of a state, without their lookahead.
+pub fn lr0_core<'grammar>(lr1_state: &LR1State<'grammar>) -> Vec<LR0Item<'grammar>> {
+    lr1_state.items
+             .vec
+             .iter()
+             .map(|item| item.to_lr0())
+             .dedup()
+             .collect()
+}
 
-        remap[lr1_index] = lalr1_index;
+/// Merges each of `lr_states` into the state given by `remap`, which
+/// must number the merged states from zero in order of first
+/// appearance. The states that are merged must all have the same
+/// LR(0) core, and their successors must also be merged together.
+pub fn merge_states<'grammar>(lr_states: &[LR1State<'grammar>],
+                              remap: &[StateIndex])
+                              -> LR1Result<'grammar> {
+    let mut lalr1_states: Vec<LALR1State> = vec![];
+    for (lr1_index, lr1_state) in lr_states.iter().enumerate() {
+        let lalr1_index = remap[lr1_index];
+        if lalr1_index.0 == lalr1_states.len() {
+            lalr1_states.push(LALR1State {
+                index: lalr1_index,
+                items: vec![],
+                shifts: map(),
+                reductions: Multimap::new(),
+                gotos: map(),
+            });
+        }
+
+        lalr1_states[lalr1_index.0].items.extend(lr1_state.items.vec.iter().cloned());
     }
 
     // The reduction process can leave us with multiple
@@ -130,6 +144,8 @@ pub fn collapse_to_lalr_states<'grammar>(lr_states: &[LR1State<'grammar>])
                                                                .map(|(p, ts)| (ts, p))
                                                                .collect(),
                                                  gotos: lr.gotos,
+                                                 lookahead2: map(),
+                                                 conflict_hooks: map(),
                                              }
                                          })
                                          .collect();
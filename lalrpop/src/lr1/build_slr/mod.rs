@@ -0,0 +1,111 @@
+//! SLR(1) state construction: the LR(0) states, with the FOLLOW set
+//! of each nonterminal as the lookahead for its reductions. This is
+//! much quicker to build than LR(1), which makes it handy for
+//! iterating on big grammars, but the lookahead is coarser, so it can
+//! report conflicts that LR(1) (or even LALR(1)) would not.
+
+use collections::{map, Map};
+use lr1::build;
+use lr1::core::*;
+use lr1::first::FirstSets;
+use lr1::lookahead::*;
+use grammar::repr::*;
+use std::rc::Rc;
+use tls::Tls;
+
+#[cfg(test)]
+mod test;
+
+pub fn build_slr_states<'grammar>(grammar: &'grammar Grammar,
+                                  start: NonterminalString)
+                                  -> LR1Result<'grammar> {
+    // an LR(0) conflict is no reason to stop: the lookahead may well
+    // resolve it
+    let lr0_states = match build::build_lr0_states(grammar, start) {
+        Ok(states) => states,
+        Err(error) => error.states,
+    };
+
+    profile! {
+        &Tls::session(),
+        "SLR(1) lookahead",
+        add_follow_sets(grammar, start, lr0_states)
+    }
+}
+
+fn add_follow_sets<'grammar>(grammar: &'grammar Grammar,
+                             start: NonterminalString,
+                             lr0_states: Vec<LR0State<'grammar>>)
+                             -> LR1Result<'grammar> {
+    let follow_sets = follow_sets(grammar, start);
+    let follow = |production: &Production| {
+        follow_sets.get(&production.nonterminal).cloned().unwrap_or_else(TokenSet::new)
+    };
+
+    let states: Vec<LR1State> =
+        lr0_states.into_iter()
+                  .map(|state| {
+                      let items = state.items
+                                       .vec
+                                       .iter()
+                                       .map(|item| item.with_lookahead(follow(item.production)))
+                                       .collect();
+                      State {
+                          index: state.index,
+                          items: Items { vec: Rc::new(items) },
+                          shifts: state.shifts,
+                          reductions: state.reductions
+                                           .into_iter()
+                                           .map(|(_, production)| (follow(production), production))
+                                           .collect(),
+                          gotos: state.gotos,
+                          lookahead2: state.lookahead2,
+                          conflict_hooks: state.conflict_hooks,
+                      }
+                  })
+                  .collect();
+
+    let conflicts: Vec<_> = states.iter()
+                                  .flat_map(|s| TokenSet::conflicts(s))
+                                  .collect();
+
+    if !conflicts.is_empty() {
+        Err(TableConstructionError {
+            states: states,
+            conflicts: conflicts,
+        })
+    } else {
+        Ok(states)
+    }
+}
+
+/// The tokens that can follow each nonterminal, anywhere in the
+/// grammar; EOF follows `start`.
+pub fn follow_sets(grammar: &Grammar,
+                   start: NonterminalString)
+                   -> Map<NonterminalString, TokenSet> {
+    let first_sets = FirstSets::new(grammar);
+    let mut follow_sets = map();
+    follow_sets.insert(start, TokenSet::eof());
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for production in grammar.nonterminals.values().flat_map(|data| &data.productions) {
+            let lookahead = match follow_sets.get(&production.nonterminal) {
+                Some(set) => TokenSet::clone(set),
+                None => continue,
+            };
+            for (index, &symbol) in production.symbols.iter().enumerate() {
+                if let Symbol::Nonterminal(nt) = symbol {
+                    let set = first_sets.first1(&production.symbols[index + 1..], &lookahead);
+                    changed |= follow_sets.entry(nt)
+                                          .or_insert_with(TokenSet::new)
+                                          .union_with(&set);
+                }
+            }
+        }
+    }
+
+    follow_sets
+}
@@ -0,0 +1,76 @@
+use intern::intern;
+use grammar::repr::*;
+use lr1::build::{build_lr0_states, build_lr1_states};
+use lr1::tls::Lr1Tls;
+use test_util::{normalized_grammar};
+use tls::Tls;
+use super::build_slr_states;
+use super::super::interpret::interpret;
+
+fn nt(t: &str) -> NonterminalString {
+    NonterminalString(intern(t))
+}
+
+macro_rules! tokens {
+    ($($x:expr),*) => {
+        vec![$(TerminalString::quoted(intern($x))),*]
+    }
+}
+
+#[test]
+fn figure9_23() {
+    let _tls = Tls::test();
+
+    let grammar = normalized_grammar(r#"
+        grammar;
+        extern { enum Tok { "-" => .., "N" => .., "(" => .., ")" => .. } }
+        S: () = E       => ();
+        E: () = {
+            E "-" T     => (),
+            T           => ()
+        };
+        T: () = {
+            "N"         => (),
+            "(" E ")"   => ()
+        };
+   "#);
+
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+
+    // there is one state per LR(0) state
+    let states = build_slr_states(&grammar, nt("S")).unwrap();
+    assert_eq!(states.len(), build_lr0_states(&grammar, nt("S")).unwrap_err().states.len());
+
+    let tree = interpret(&states, tokens!["N", "-", "(", "N", "-", "N", ")"])
+        .unwrap();
+    assert_eq!(
+        &format!("{:?}", tree)[..],
+        r#"[S: [E: [E: [T: "N"]], "-", [T: "(", [E: [E: [T: "N"]], "-", [T: "N"]], ")"]]]"#);
+}
+
+#[test]
+fn not_slr() {
+    let _tls = Tls::test();
+
+    // the classic example from the dragon book: "=" is in FOLLOW(R),
+    // but it cannot follow the `R` that `L` reduces to after a "*"
+    let grammar = normalized_grammar(r#"
+        grammar;
+        extern { enum Tok { "=" => .., "*" => .., "id" => .. } }
+        S: () = {
+            L "=" R     => (),
+            R           => ()
+        };
+        L: () = {
+            "*" R       => (),
+            "id"        => ()
+        };
+        R: () = L       => ();
+   "#);
+
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+
+    assert!(build_lr1_states(&grammar, nt("S")).is_ok());
+    let error = build_slr_states(&grammar, nt("S")).unwrap_err();
+    assert_eq!(error.conflicts.len(), 1);
+}
@@ -786,6 +786,9 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent,
             args.push(format!("&{}start", self.prefix));
             args.push(format!("&{}end", self.prefix));
         }
+        if self.grammar.action_takes_lookahead_token(production.action) {
+            args.push(format!("{}lookahead.as_ref()", self.prefix));
+        }
 
         // invoke the action code
         let is_fallible = self.grammar.action_is_fallible(production.action);
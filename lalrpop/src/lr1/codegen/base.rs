@@ -4,6 +4,7 @@ use grammar::repr::*;
 use lr1::core::*;
 use rust::RustWrite;
 use std::io::{self, Write};
+use tls::Tls;
 use util::Sep;
 
 /// Base struct for various kinds of code generator. The flavor of
@@ -69,7 +70,7 @@ impl<'codegen, 'grammar, W: Write, C> CodeGenerator<'codegen, 'grammar, W, C> {
         where F: FnOnce(&mut Self) -> io::Result<()>
     {
         rust!(self.out, "");
-        rust!(self.out, "mod {}parse{} {{", self.prefix, self.start_symbol);
+        rust!(self.out, "pub mod {}parse{} {{", self.prefix, self.start_symbol);
 
         // these stylistic lints are annoying for the generated code,
         // which doesn't follow conventions:
@@ -86,19 +87,49 @@ impl<'codegen, 'grammar, W: Write, C> CodeGenerator<'codegen, 'grammar, W, C> {
         Ok(())
     }
 
+    /// The path of the module that holds the helper items shared by
+    /// the parsers of the grammar (the tokenizer, `ToTriple`, ...): it
+    /// is a private child of the action module (see
+    /// `build::emit_recursive_ascent`).
+    pub fn generated_module(&self) -> String {
+        format!("{}::{}generated", self.action_module, self.prefix)
+    }
+
     pub fn write_uses(&mut self) -> io::Result<()> {
         try!(self.out.write_uses(&format!("{}::", self.action_module), &self.grammar));
 
         if self.grammar.intern_token.is_none() {
-            rust!(self.out, "use {}::{}ToTriple;", self.action_module, self.prefix);
+            let generated_module = self.generated_module();
+            match Tls::session().support_module {
+                Some(ref module_path) =>
+                    rust!(self.out, "use ::{}::ToTriple as {}ToTriple;", module_path, self.prefix),
+                None =>
+                    rust!(self.out, "use {}::{}ToTriple;", generated_module, self.prefix),
+            }
+        }
+
+        if self.grammar.uses_conflict_hooks() {
+            rust!(self.out, "use {}::{}ConflictHook;", self.action_module, self.prefix);
         }
 
         Ok(())
     }
 
     pub fn start_parser_fn(&mut self) -> io::Result<()> {
+        let name = format!("parse_{}", self.user_start_symbol);
+        let return_type = format!("Result<{}, {}>",
+                                  self.types.nonterminal_type(self.start_symbol),
+                                  self.types.parse_error_type());
+        self.start_fn_over_input(name, return_type)
+    }
+
+    /// Writes the header of a public fn `name` that takes the same
+    /// parameters as the parser fn: the grammar's own, and the tokens
+    /// unless the grammar has the built-in lexer (whose input is one
+    /// of the grammar's parameters). `define_tokens` turns either
+    /// into an iterator.
+    pub fn start_fn_over_input(&mut self, name: String, return_type: String) -> io::Result<()> {
         let error_type = self.types.error_type();
-        let parse_error_type = self.types.parse_error_type();
 
         let (type_parameters, parameters, mut where_clauses);
 
@@ -115,11 +146,21 @@ impl<'codegen, 'grammar, W: Write, C> CodeGenerator<'codegen, 'grammar, W, C> {
             for type_parameter in &self.grammar.type_parameters {
                 user_type_parameters.push_str(&format!("{}, ", type_parameter));
             }
-            type_parameters = vec![format!("{}TOKEN: {}ToTriple<{}Error={}>",
-                                           self.prefix,
-                                           self.prefix,
-                                           user_type_parameters,
-                                           error_type),
+            // the shared `ToTriple` has no type parameters of the
+            // grammar, only the types of the triples and errors
+            let to_triple = if Tls::session().support_module.is_some() {
+                format!("{}ToTriple<{}, {}, {}>",
+                        self.prefix,
+                        self.types.terminal_loc_type(),
+                        self.types.terminal_token_type(),
+                        error_type)
+            } else {
+                format!("{}ToTriple<{}Error={}>",
+                        self.prefix,
+                        user_type_parameters,
+                        error_type)
+            };
+            type_parameters = vec![format!("{}TOKEN: {}", self.prefix, to_triple),
                                    format!("{}TOKENS: IntoIterator<Item={}TOKEN>",
                                            self.prefix,
                                            self.prefix)];
@@ -132,12 +173,10 @@ impl<'codegen, 'grammar, W: Write, C> CodeGenerator<'codegen, 'grammar, W, C> {
         }
 
         try!(self.out.write_pub_fn_header(self.grammar,
-                                          format!("parse_{}", self.user_start_symbol),
+                                          name,
                                           type_parameters,
                                           parameters,
-                                          format!("Result<{}, {}>",
-                                                  self.types.nonterminal_type(self.start_symbol),
-                                                  parse_error_type),
+                                          return_type,
                                           where_clauses));
         rust!(self.out, "{{");
 
@@ -147,10 +186,11 @@ impl<'codegen, 'grammar, W: Write, C> CodeGenerator<'codegen, 'grammar, W, C> {
     pub fn define_tokens(&mut self) -> io::Result<()> {
         if self.grammar.intern_token.is_some() {
             // if we are generating the tokenizer, create a matcher as our input iterator
+            let generated_module = self.generated_module();
             rust!(self.out,
                   "let mut {}tokens = {}::{}intern_token::{}Matcher::new(input);",
                   self.prefix,
-                  self.action_module,
+                  generated_module,
                   self.prefix,
                   self.prefix);
         } else {
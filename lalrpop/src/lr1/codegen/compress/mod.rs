@@ -0,0 +1,99 @@
+//! Compressing the `ACTION` and `GOTO` tables of table-driven parsers
+//! by row displacement, as yacc does. Most rows are nearly all the
+//! same entry: an error, or, in a state that reduces on most tokens,
+//! that reduction. That entry becomes the default of the row, and the
+//! others are packed into one shared array, each row starting at an
+//! offset where its entries do not collide with those of the rows
+//! placed before it. A parallel `check` array records which row each
+//! slot belongs to:
+//!
+//! ```ignore
+//! let slot = bases[row] + column;
+//! if slot < check.len() && check[slot] == row { entries[slot] } else { defaults[row] }
+//! ```
+//!
+//! Every lookup gives exactly the entry of the full table, errors
+//! included, so the parser detects errors and lists the expected
+//! tokens as it would with the full table.
+
+use collections::{map, Map};
+
+#[cfg(test)]
+mod test;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompressedTable {
+    /// The most common entry of each row, which is left out of
+    /// `entries`.
+    pub defaults: Vec<i32>,
+
+    /// Where each row starts in `check` and `entries`.
+    pub bases: Vec<usize>,
+
+    /// The row that each slot belongs to, or -1 if it is free.
+    pub check: Vec<i32>,
+
+    pub entries: Vec<i32>,
+}
+
+pub fn compress(rows: &[Vec<i32>]) -> CompressedTable {
+    let defaults: Vec<i32> = rows.iter().map(|row| most_common(row)).collect();
+    let columns: Vec<Vec<usize>> = rows.iter()
+                                       .zip(&defaults)
+                                       .map(|(row, &default)| {
+                                           (0..row.len()).filter(|&c| row[c] != default).collect()
+                                       })
+                                       .collect();
+
+    // placing the fullest rows first leaves the gaps between them for
+    // the sparse ones
+    let mut order: Vec<usize> = (0..rows.len()).collect();
+    order.sort_by(|&a, &b| (columns[b].len(), a).cmp(&(columns[a].len(), b)));
+
+    let mut table = CompressedTable {
+        defaults: defaults,
+        bases: vec![0; rows.len()],
+        check: vec![],
+        entries: vec![],
+    };
+    for row in order {
+        let columns = &columns[row];
+        if columns.is_empty() {
+            continue;
+        }
+
+        let mut base = 0;
+        while !columns.iter().all(|&c| table.check.get(base + c).map_or(true, |&r| r == -1)) {
+            base += 1;
+        }
+
+        let end = base + columns[columns.len() - 1] + 1;
+        if table.check.len() < end {
+            table.check.resize(end, -1);
+            table.entries.resize(end, 0);
+        }
+        for &c in columns {
+            table.check[base + c] = row as i32;
+            table.entries[base + c] = rows[row][c];
+        }
+        table.bases[row] = base;
+    }
+    table
+}
+
+/// The entry that occurs most often in `row`; of those that occur
+/// equally often, the least, so that the result does not depend on
+/// the order of the columns.
+fn most_common(row: &[i32]) -> i32 {
+    let mut counts: Map<i32, usize> = map();
+    for &entry in row {
+        *counts.entry(entry).or_insert(0) += 1;
+    }
+    let mut result = (0, 0);
+    for (&entry, &count) in &counts {
+        if count > result.1 {
+            result = (entry, count);
+        }
+    }
+    result.0
+}
@@ -0,0 +1,56 @@
+use super::{compress, CompressedTable};
+
+fn lookup(table: &CompressedTable, row: usize, column: usize) -> i32 {
+    let slot = table.bases[row] + column;
+    if slot < table.check.len() && table.check[slot] == row as i32 {
+        table.entries[slot]
+    } else {
+        table.defaults[row]
+    }
+}
+
+fn check_lossless(rows: &[Vec<i32>]) -> CompressedTable {
+    let table = compress(rows);
+    for (r, row) in rows.iter().enumerate() {
+        for (c, &entry) in row.iter().enumerate() {
+            assert_eq!(lookup(&table, r, c), entry, "row {} column {}", r, c);
+        }
+    }
+    table
+}
+
+#[test]
+fn defaults() {
+    let table = check_lossless(&[vec![0, 0, 3, 0],
+                                 vec![-2, -2, -2, 0],
+                                 vec![0, 0, 0, 0]]);
+    assert_eq!(table.defaults, vec![0, -2, 0]);
+
+    // the reduction is the default of the second row, which leaves
+    // one entry to store for each of the first two
+    assert_eq!(table.check.iter().filter(|&&r| r != -1).count(), 2);
+}
+
+#[test]
+fn rows_share_slots() {
+    let table = check_lossless(&[vec![1, 2, 0, 0, 0, 0],
+                                 vec![0, 0, 3, 4, 0, 0],
+                                 vec![0, 0, 0, 0, 5, 6],
+                                 vec![7, 0, 0, 0, 0, 8]]);
+
+    // the first three fit into each other without any gap
+    assert_eq!(&table.check[..6], &[0, 0, 1, 1, 2, 2]);
+    assert!(table.entries.len() < 4 * 6, "{:?}", table);
+}
+
+#[test]
+fn ties() {
+    let table = check_lossless(&[vec![5, -1, 5, -1]]);
+    assert_eq!(table.defaults, vec![-1]);
+}
+
+#[test]
+fn empty() {
+    let table = check_lossless(&[vec![], vec![0, 0]]);
+    assert!(table.entries.is_empty());
+}
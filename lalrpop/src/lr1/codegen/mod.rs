@@ -1,4 +1,5 @@
 pub mod ascent;
 mod base;
+mod compress;
 pub mod parse_table;
 pub mod test_all;
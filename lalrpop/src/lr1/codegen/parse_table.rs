@@ -1,24 +1,135 @@
 //! A compiler from an LR(1) table to a traditional table driven parser.
 
 use collections::{Map, Set};
+use grammar::consts::INPUT_PARAMETER;
 use grammar::repr::*;
+use intern::intern;
 use lr1::core::*;
 use lr1::lookahead::Token;
 use rust::RustWrite;
+use session::{Optimize, StackOverflow};
 use std::fmt;
 use std::io::{self, Write};
+use std::path::Path;
 use tls::Tls;
-use util::{Escape, Sep};
+use util::{Escape, Prefix, Sep};
 
 use super::base::CodeGenerator;
+use super::compress::compress;
 
 const DEBUG_PRINT: bool = false;
 
+/// The integer type of the `ACTION`, `EOF_ACTION` and `GOTO` tables:
+/// the narrowest one in which every state and production index fits,
+/// along with the two sentinel values below.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum TableType {
+    I8,
+    I16,
+    I32,
+}
+
+impl TableType {
+    fn for_automaton(num_states: usize, num_productions: usize) -> TableType {
+        // shifts and gotos store `state + 1`, reductions store
+        // `-(production + 1)`, and the two sentinels take the two
+        // smallest values of the type
+        let largest = ::std::cmp::max(num_states, num_productions);
+        if largest < ::std::i8::MAX as usize {
+            TableType::I8
+        } else if largest < ::std::i16::MAX as usize {
+            TableType::I16
+        } else {
+            TableType::I32
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            TableType::I8 => "i8",
+            TableType::I16 => "i16",
+            TableType::I32 => "i32",
+        }
+    }
+
+    fn min(self) -> i32 {
+        match self {
+            TableType::I8 => ::std::i8::MIN as i32,
+            TableType::I16 => ::std::i16::MIN as i32,
+            TableType::I32 => ::std::i32::MIN,
+        }
+    }
+
+    /// The value in the `ACTION` table for a terminal where the token
+    /// after it decides what to do (see `__action2`).
+    fn lookahead2(self) -> i32 {
+        self.min()
+    }
+
+    /// The value in the `ACTION` table for a terminal where the
+    /// grammar's conflict hook decides whether to shift or reduce (see
+    /// `__conflict_hook`).
+    fn conflict_hook(self) -> i32 {
+        self.min() + 1
+    }
+
+    /// What to append to a read from one of the tables to widen it to
+    /// the `i32` that the parser works with.
+    fn widen(self) -> &'static str {
+        match self {
+            TableType::I32 => "",
+            _ => " as i32",
+        }
+    }
+
+    /// How many bytes each entry takes in a table file.
+    fn width(self) -> usize {
+        match self {
+            TableType::I8 => 1,
+            TableType::I16 => 2,
+            TableType::I32 => 4,
+        }
+    }
+
+    /// The contents of a table file holding `entries`, each in
+    /// `width()` bytes, least significant first.
+    fn encode(self, entries: &[i32]) -> Vec<u8> {
+        let width = self.width();
+        entries.iter()
+               .flat_map(|&entry| (0..width).map(move |i| (entry >> (8 * i)) as u8))
+               .collect()
+    }
+}
+
+/// The binary files that the tables of table-driven parsers go in when
+/// `Session::binary_tables` is set; the caller writes them next to the
+/// generated `.rs` file, whose code embeds them with `include_bytes!`.
+pub struct TableFiles {
+    /// the name of the `.rs` file less its extension, which the names
+    /// of the table files start with
+    stem: String,
+
+    /// the name and the contents of each file
+    pub files: Vec<(String, Vec<u8>)>,
+}
+
+impl TableFiles {
+    pub fn new(rs_file: &Path) -> TableFiles {
+        TableFiles {
+            stem: rs_file.file_stem()
+                         .map(|stem| stem.to_string_lossy().into_owned())
+                         .unwrap_or_default(),
+            files: vec![],
+        }
+    }
+}
+
 pub fn compile<'grammar, W: Write>(grammar: &'grammar Grammar,
                                    user_start_symbol: NonterminalString,
                                    start_symbol: NonterminalString,
                                    states: &[LR1State<'grammar>],
                                    action_module: &str,
+                                   tables: &mut TableFiles,
                                    out: &mut RustWrite<W>)
                                    -> io::Result<()> {
     let mut table_driven = CodeGenerator::new_table_driven(grammar,
@@ -26,8 +137,35 @@ pub fn compile<'grammar, W: Write>(grammar: &'grammar Grammar,
                                                            start_symbol,
                                                            states,
                                                            action_module,
+                                                           tables,
                                                            out);
-    table_driven.write()
+    try!(table_driven.write());
+    table_driven.finish_table_file(tables);
+    Ok(())
+}
+
+/// Compiles a parser for the items of a start symbol like `File =
+/// Item*`: the generated `parse_File_items` function returns an
+/// iterator that parses one `Item` at a time, on demand.
+pub fn compile_items<'grammar, W: Write>(grammar: &'grammar Grammar,
+                                         user_start_symbol: NonterminalString,
+                                         start_symbol: NonterminalString,
+                                         states: &[LR1State<'grammar>],
+                                         action_module: &str,
+                                         tables: &mut TableFiles,
+                                         out: &mut RustWrite<W>)
+                                         -> io::Result<()> {
+    let mut table_driven = CodeGenerator::new_table_driven(grammar,
+                                                           user_start_symbol,
+                                                           start_symbol,
+                                                           states,
+                                                           action_module,
+                                                           tables,
+                                                           out);
+    table_driven.custom.partial = true;
+    try!(table_driven.write_items());
+    table_driven.finish_table_file(tables);
+    Ok(())
 }
 
 // We create three parse tables:
@@ -38,8 +176,13 @@ pub fn compile<'grammar, W: Write>(grammar: &'grammar Grammar,
 // - `GOTO[state * num_states + nonterminal]: i32`: index + 1 of state to jump to when given
 //   nonterminal is pushed (no error is possible)
 //
-// For the `ACTION` and `EOF_ACTION` tables, the value is an `i32` and
-// its interpretation varies depending on whether it is positive or
+// The values are stored as `i8`, `i16` or `i32`, whichever is the
+// narrowest type that fits the automaton (see `TableType`), so that
+// small grammars get small tables; the parser widens them to `i32` as
+// it reads them.
+//
+// For the `ACTION` and `EOF_ACTION` tables, the value's
+// interpretation varies depending on whether it is positive or
 // negative:
 //
 // - if zero, parse error.
@@ -103,7 +246,7 @@ pub fn compile<'grammar, W: Write>(grammar: &'grammar Grammar,
 //                symbols.push(symbol);
 //                continue 'shift;
 //            } else if action < 0 { // reduce
-//                try!(reduce(action, Some(&lookahead.0), &mut states, &mut symbols));
+//                try!(reduce(action, Some(&lookahead), &mut states, &mut symbols));
 //            } else {
 //                try_error_recovery(...)?;
 //            }
@@ -123,7 +266,7 @@ pub fn compile<'grammar, W: Write>(grammar: &'grammar Grammar,
 // }
 //
 // // generated by `emit_reduce_actions()`
-// fn reduce(action: i32, lookahead_start: Option<&L>,
+// fn reduce(action: i32, lookahead: Option<&(L, T, L)>,
 //           states: &mut Vec<i32>, symbols: &mut Vec<(L, Symbol, L))
 //           -> Option<Result<..>> {
 //     let nonterminal = match -action {
@@ -214,6 +357,8 @@ enum Comment<'a, T> {
     Goto(T, usize),
     Error(T),
     Reduce(T, &'a Production),
+    Lookahead2(T),
+    ConflictHook(T),
 }
 
 impl<'a, T: fmt::Display> fmt::Display for Comment<'a, T> {
@@ -224,7 +369,11 @@ impl<'a, T: fmt::Display> fmt::Display for Comment<'a, T> {
             Comment::Error(ref token) =>
                 write!(f, " // on {}, error", token),
             Comment::Reduce(ref token, production) =>
-                write!(f, " // on {}, reduce `{:?}`", token, production)
+                write!(f, " // on {}, reduce `{:?}`", token, production),
+            Comment::Lookahead2(ref token) =>
+                write!(f, " // on {}, look at the next token", token),
+            Comment::ConflictHook(ref token) =>
+                write!(f, " // on {}, ask the conflict hook", token),
         }
     }
 }
@@ -233,10 +382,38 @@ struct TableDriven<'grammar> {
     /// type parameters for the `Nonterminal` type
     symbol_type_params: Vec<TypeParameter>,
 
-    /// a list of each nonterminal in some specific order
+    /// a list of each nonterminal in some specific order; these are
+    /// the columns of the goto table. With `prune_tables`, only the
+    /// start symbol and those it can reach are listed.
     all_nonterminals: Vec<NonterminalString>,
 
     reduce_indices: Map<&'grammar Production, usize>,
+
+    /// if true, the parser fn stops at the first token that cannot be
+    /// shifted (or that the tokenizer fails to produce) and treats it
+    /// like EOF, leaving it in `__pending`
+    partial: bool,
+
+    /// if true, the parser fn resumes from the last of the
+    /// `__checkpoints` it is given, and records new ones as it goes
+    incremental: bool,
+
+    /// the integer type of the `ACTION`, `EOF_ACTION` and `GOTO` tables
+    table_type: TableType,
+
+    /// the name of the file that those tables go in, if they are not
+    /// written into the code (see `TableFiles`), and their entries so
+    /// far, in order
+    table_file: Option<String>,
+    table_entries: Vec<i32>,
+
+    /// where in `table_entries` the table being written starts
+    table_start: usize,
+
+    /// if true, the `ACTION` and `GOTO` tables are compressed (see
+    /// `compress`), and their rows so far, while one is being written
+    compress_tables: bool,
+    table_rows: Option<Vec<Vec<i32>>>,
 }
 
 impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDriven<'grammar>> {
@@ -245,6 +422,7 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
                         start_symbol: NonterminalString,
                         states: &'ascent [LR1State<'grammar>],
                         action_module: &str,
+                        tables: &TableFiles,
                         out: &'ascent mut RustWrite<W>)
                         -> Self {
         // The nonterminal type needs to be parameterized by all the
@@ -275,6 +453,34 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
                                                                       })
                                                                       .zip(0..)
                                                                       .collect();
+        let table_type = if Tls::session().optimize == Optimize::Speed {
+            TableType::I32
+        } else {
+            TableType::for_automaton(states.len(), reduce_indices.len())
+        };
+
+        let all_nonterminals = if Tls::session().prune_tables {
+            let reachable = grammar.reachable_from(start_symbol);
+            grammar.nonterminals
+                   .keys()
+                   .filter(|&&nt| nt == start_symbol || reachable.contains(&nt))
+                   .cloned()
+                   .collect()
+        } else {
+            grammar.nonterminals.keys().cloned().collect()
+        };
+
+        let table_file = if Tls::session().binary_tables {
+            Some(format!("{}.{}parse{}.tables", tables.stem, grammar.prefix, start_symbol))
+        } else {
+            None
+        };
+
+        // a compressed table takes a few more steps to read, and the
+        // tables in a file are left as they are
+        let compress_tables = !Tls::session().dense_tables &&
+                              Tls::session().optimize != Optimize::Speed &&
+                              table_file.is_none();
 
         CodeGenerator::new(grammar,
                            user_start_symbol,
@@ -285,864 +491,2682 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
                            action_module,
                            TableDriven {
                                symbol_type_params: symbol_type_params,
-                               all_nonterminals: grammar.nonterminals
-                                                        .keys()
-                                                        .cloned()
-                                                        .collect(),
+                               all_nonterminals: all_nonterminals,
                                reduce_indices: reduce_indices,
+                               partial: false,
+                               incremental: false,
+                               table_type: table_type,
+                               table_file: table_file,
+                               table_entries: vec![],
+                               table_start: 0,
+                               compress_tables: compress_tables,
+                               table_rows: None,
                            })
     }
 
+    /// Hands the tables over to `tables`, if they go in a file.
+    fn finish_table_file(&mut self, tables: &mut TableFiles) {
+        if let Some(name) = self.custom.table_file.take() {
+            let bytes = self.custom.table_type.encode(&self.custom.table_entries);
+            tables.files.push((name, bytes));
+        }
+    }
+
     fn write(&mut self) -> io::Result<()> {
         self.write_parse_mod(|this| {
             try!(this.write_value_type_defn());
             try!(this.write_parse_table());
             try!(this.write_parser_fn());
+            if this.grammar.generates_completion_fns() {
+                try!(this.write_completion_fn());
+            }
+            if this.grammar.algorithm.prefix {
+                try!(this.write_prefix_parser_fn());
+            }
+            if this.grammar.algorithm.incremental {
+                try!(this.write_checkpoints());
+                this.custom.incremental = true;
+                try!(this.write_parser_fn());
+                this.custom.incremental = false;
+            }
+            if this.grammar.algorithm.push {
+                try!(this.write_push_parser());
+            }
             try!(this.emit_reduce_actions());
             try!(this.emit_downcast_fns());
             Ok(())
         })
     }
 
-    fn write_value_type_defn(&mut self) -> io::Result<()> {
-        // sometimes some of the variants are not used, particularly
-        // if we are generating multiple parsers from the same file:
-        rust!(self.out, "#[allow(dead_code)]");
+    /// Writes `parse_Foo_prefix`, which parses the longest prefix of
+    /// the input that is a `Foo` and also returns the offset where
+    /// that prefix ends. It first runs through the tokens on the stack
+    /// of states alone, noting the end of the last token after which
+    /// the parser could have accepted, until a token cannot be shifted
+    /// or the tokenizer fails. Then it parses the input up to there,
+    /// or all of it if no prefix is a `Foo`, to report the error the
+    /// parser would.
+    fn write_prefix_parser_fn(&mut self) -> io::Result<()> {
+        let user_start_symbol = self.user_start_symbol;
+        try!(self.out.write_pub_fn_header(self.grammar,
+                                          format!("parse_{}_prefix", user_start_symbol),
+                                          vec![],
+                                          vec![],
+                                          format!("Result<({}, usize), {}>",
+                                                  self.types.nonterminal_type(self.start_symbol),
+                                                  self.types.parse_error_type()),
+                                          vec![]));
+        rust!(self.out, "{{");
+        try!(self.define_tokens());
+        rust!(self.out, "let mut {}states = vec![0_i32];", self.prefix);
+        rust!(self.out, "let mut {}integer;", self.prefix);
+        rust!(self.out, "let mut {}last_location = 0;", self.prefix);
+        rust!(self.out, "let mut {}end = None;", self.prefix);
+        rust!(self.out, "loop {{");
         rust!(self.out,
-              "pub enum {}Symbol<{}> {{",
+              "if {}simulate(&mut {}states.clone(), None, true, &mut vec![]) {{",
               self.prefix,
-              Sep(", ", &self.custom.symbol_type_params));
-
-        // make one variant per terminal
-        for &term in &self.grammar.terminals.all {
-            let name = self.variant_name_for_symbol(Symbol::Terminal(term));
-            let ty = self.types.terminal_type(term).clone();
-            rust!(self.out, "{}({}),", name, ty);
-        }
-
-        // make one variant per nonterminal
-        for &nt in self.grammar.nonterminals.keys() {
-            let name = self.variant_name_for_symbol(Symbol::Nonterminal(nt));
-            let ty = self.types.nonterminal_type(nt).clone();
-            rust!(self.out, "{}({}),", name, ty);
-        }
+              self.prefix);
+        rust!(self.out, "{}end = Some({}last_location);", self.prefix, self.prefix);
         rust!(self.out, "}}");
-        Ok(())
-    }
-
-    fn write_parse_table(&mut self) -> io::Result<()> {
-        // The table is a two-dimensional matrix indexed first by state
-        // and then by the terminal index. The value is described above.
-        rust!(self.out, "const {}ACTION: &'static [i32] = &[", self.prefix);
-
-        for (index, state) in self.states.iter().enumerate() {
-            rust!(self.out, "// State {}", index);
-
-            if Tls::session().emit_comments {
-                for item in state.items.vec.iter() {
-                    rust!(self.out, "//     {:?}", item);
-                }
-            }
-
-            // Write an action for each terminal (either shift, reduce, or error).
-            let custom = &self.custom;
-            let iterator = self.grammar.terminals.all.iter().map(|terminal| {
-                if let Some(new_state) = state.shifts.get(&terminal) {
-                    (new_state.0 as i32 + 1, Comment::Goto(Token::Terminal(*terminal), new_state.0))
-                } else {
-                    Self::write_reduction(custom, state, Token::Terminal(*terminal))
-                }
-            });
-            try!(self.out.write_table_row(iterator))
-        }
-
-        rust!(self.out, "];");
-
-        // Actions on EOF. Indexed just by state.
+        rust!(self.out, "let {}lookahead = match {}tokens.next() {{", self.prefix, self.prefix);
+        rust!(self.out, "Some(Ok(v)) => v,");
+        rust!(self.out, "_ => break,");
+        rust!(self.out, "}};");
+        try!(self.token_to_integer(false));
         rust!(self.out,
-              "const {}EOF_ACTION: &'static [i32] = &[",
+              "if !{}simulate(&mut {}states, Some({}integer), true, &mut vec![]) {{",
+              self.prefix,
+              self.prefix,
               self.prefix);
-        for state in self.states {
-            let reduction = Self::write_reduction(&self.custom, state, Token::EOF);
-            try!(self.out.write_table_row(Some(reduction)));
-        }
-        rust!(self.out, "];");
-
-        // The goto table is indexed by state and *nonterminal*.
-        rust!(self.out, "const {}GOTO: &'static [i32] = &[", self.prefix);
-        for (index, state) in self.states.iter().enumerate() {
-            rust!(self.out, "// State {}", index);
-            let iterator = self.grammar.nonterminals.keys().map(|nonterminal| {
-                if let Some(&new_state) = state.gotos.get(nonterminal) {
-                    (new_state.0 as i32 + 1, Comment::Goto(*nonterminal, new_state.0))
-                } else {
-                    (0, Comment::Error(*nonterminal))
-                }
-            });
-            try!(self.out.write_table_row(iterator));
-        }
-        rust!(self.out, "];");
+        rust!(self.out, "break;");
+        rust!(self.out, "}}");
 
-        try!(self.emit_expected_tokens_fn());
+        try!(self.shift_simulated_token());
+        rust!(self.out, "{}last_location = {}lookahead.2;", self.prefix, self.prefix);
+        rust!(self.out, "}}");
 
+        // the same arguments, but only the input up to the end
+        let input = intern(INPUT_PARAMETER);
+        let arguments: Vec<_> = self.grammar
+                                    .parameters
+                                    .iter()
+                                    .map(|parameter| {
+                                        if parameter.name == input {
+                                            format!("&input[..{}end]", self.prefix)
+                                        } else {
+                                            format!("{}", parameter.name)
+                                        }
+                                    })
+                                    .collect();
+        rust!(self.out, "match {}end {{", self.prefix);
+        rust!(self.out,
+              "Some({}end) => parse_{}({}).map(|v| (v, {}end)),",
+              self.prefix,
+              user_start_symbol,
+              Sep(", ", &arguments),
+              self.prefix);
+        rust!(self.out,
+              "None => parse_{}({}).map(|v| (v, input.len())),",
+              user_start_symbol,
+              self.grammar.user_parameter_refs());
+        rust!(self.out, "}}");
+        rust!(self.out, "}}");
         Ok(())
     }
 
-    fn write_reduction<'s>(custom: &TableDriven<'grammar>, state: &'s LR1State, token: Token) -> (i32, Comment<'s, Token>) {
-        let reduction = state.reductions
-                             .iter()
-                             .filter(|&&(ref t, _)| t.contains(token))
-                             .map(|&(_, p)| p)
-                             .next();
-        if let Some(production) = reduction {
-            let action = custom.reduce_indices[production];
-            (-(action as i32 + 1), Comment::Reduce(token, production))
-        } else {
-            // Otherwise, this is an error. Store 0.
-            (0, Comment::Error(token))
-        }
+    fn write_items(&mut self) -> io::Result<()> {
+        self.write_parse_mod(|this| {
+            try!(this.write_value_type_defn());
+            try!(this.write_parse_table());
+            try!(this.write_items_iterator());
+            try!(this.write_parser_fn());
+            try!(this.emit_reduce_actions());
+            try!(this.emit_downcast_fns());
+            Ok(())
+        })
     }
 
-    fn write_parser_fn(&mut self) -> io::Result<()> {
-        let phantom_data_expr = self.phantom_data_expr();
-
-        try!(self.start_parser_fn());
-
-        try!(self.define_tokens());
-
-        // State and data stack.
-        rust!(self.out, "let mut {}states = vec![0_i32];", self.prefix);
-        rust!(self.out, "let mut {}symbols = vec![];", self.prefix);
-
-        rust!(self.out, "let mut {}integer;", self.prefix);
-        rust!(self.out, "let mut {}lookahead;", self.prefix);
-        // The location of the last token is necessary for for error recovery at EOF (or they would not have
-        // a location)
-        rust!(self.out, "let mut {}last_location = Default::default();", self.prefix);
-
-        // Outer loop: each time we continue around this loop, we
-        // shift a new token from the input. We break from the loop
-        // when the end of the input is reached (we return early if an
-        // error occurs).
-        rust!(self.out, "'{}shift: loop {{", self.prefix);
-        if DEBUG_PRINT {
-            rust!(self.out, "println!(\"outer loop\");");
-        }
+    fn write_items_iterator(&mut self) -> io::Result<()> {
+        let item_type = self.types.nonterminal_type(self.start_symbol).clone();
+        let parse_error_type = self.types.parse_error_type().clone();
+        let triple_type = self.types.triple_type();
+        let type_parameters = Sep(", ", &self.grammar.type_parameters);
 
-        // Read next token from input; defines `integer` and `symbol`.
-        try!(self.next_token());
-        try!(self.token_to_integer());
-
-        // Loop.
-        rust!(self.out, "'{}inner: loop {{", self.prefix);
-        if DEBUG_PRINT {
-            rust!(self.out, "println!(\"inner loop\");");
+        rust!(self.out, "pub struct {}Items<{}> {{", self.prefix, type_parameters);
+        for parameter in &self.grammar.parameters {
+            rust!(self.out, "{}: {},", parameter.name, parameter.ty);
         }
+        let generated_module = self.generated_module();
         rust!(self.out,
-              "let {}state = *{}states.last().unwrap() as usize;",
+              "{}tokens: {}::{}intern_token::{}Matcher<'input>,",
+              self.prefix,
+              generated_module,
               self.prefix,
               self.prefix);
-
-        // Load the next action to take.
         rust!(self.out,
-              "let {}action = {}ACTION[{}state * {} + {}integer];",
+              "{}pending: Option<Result<{}, {}>>,",
               self.prefix,
+              triple_type,
+              parse_error_type);
+        rust!(self.out, "{}done: bool,", self.prefix);
+        rust!(self.out, "}}");
+        rust!(self.out, "");
+
+        rust!(self.out,
+              "impl<{}> Iterator for {}Items<{}> {{",
+              type_parameters,
               self.prefix,
+              type_parameters);
+        rust!(self.out, "type Item = Result<{}, {}>;", item_type, parse_error_type);
+        rust!(self.out, "");
+        rust!(self.out, "fn next(&mut self) -> Option<Self::Item> {{");
+        rust!(self.out, "if self.{}done {{", self.prefix);
+        rust!(self.out, "return None;");
+        rust!(self.out, "}}");
+
+        // peek at the next token to see if there is another item
+        rust!(self.out,
+              "match self.{}pending.take().or_else(|| self.{}tokens.next()) {{",
               self.prefix,
-              self.grammar.terminals.all.len(),
               self.prefix);
+        rust!(self.out, "Some(Ok(v)) => self.{}pending = Some(Ok(v)),", self.prefix);
+        rust!(self.out, "Some(Err(e)) => {{");
+        rust!(self.out, "self.{}done = true;", self.prefix);
+        rust!(self.out, "return Some(Err(e));");
+        rust!(self.out, "}}");
+        rust!(self.out, "None => {{");
+        rust!(self.out, "self.{}done = true;", self.prefix);
+        rust!(self.out, "return None;");
+        rust!(self.out, "}}");
+        rust!(self.out, "}}"); // match
 
-        if DEBUG_PRINT {
+        let mut arguments: Vec<_> = self.grammar
+                                        .parameters
+                                        .iter()
+                                        .map(|parameter| format!("self.{}", parameter.name))
+                                        .collect();
+        arguments.push(format!("&mut self.{}tokens", self.prefix));
+        arguments.push(format!("&mut self.{}pending", self.prefix));
+        rust!(self.out,
+              "let {}result = {}parse_partial({});",
+              self.prefix,
+              self.prefix,
+              Sep(", ", &arguments));
+        rust!(self.out, "if {}result.is_err() {{", self.prefix);
+        rust!(self.out, "self.{}done = true;", self.prefix);
+        rust!(self.out, "}}");
+        rust!(self.out, "Some({}result)", self.prefix);
+        rust!(self.out, "}}"); // fn next
+        rust!(self.out, "}}"); // impl
+        rust!(self.out, "");
+
+        // Speculative parsing: the items can be read again from the
+        // start of any of them (or any other offset).
+        if self.grammar.generates_item_checkpoints() {
             rust!(self.out,
-                  "println!(\"state: {{}} lookahead: {{}} action: {{}} stack-depth: {{}}\", \
-                   {}state, {}integer, {}action, {}symbols.len());",
-                  self.prefix,
-                  self.prefix,
+                  "impl<{}> {}Items<{}> {{",
+                  type_parameters,
                   self.prefix,
+                  type_parameters);
+            rust!(self.out, "pub fn checkpoint(&self) -> usize {{");
+            rust!(self.out, "match self.{}pending {{", self.prefix);
+            rust!(self.out, "Some(Ok((l, _, _))) => l,");
+            rust!(self.out,
+                  "Some(Err({}lalrpop_util::ParseError::InvalidToken {{ location }})) => location,",
                   self.prefix);
+            rust!(self.out, "_ => self.{}tokens.offset(),", self.prefix);
+            rust!(self.out, "}}");
+            rust!(self.out, "}}");
+            rust!(self.out, "");
+            rust!(self.out, "pub fn rewind(&mut self, checkpoint: usize) {{");
+            rust!(self.out, "self.{}tokens.seek(self.input, checkpoint);", self.prefix);
+            rust!(self.out, "self.{}pending = None;", self.prefix);
+            rust!(self.out, "self.{}done = false;", self.prefix);
+            rust!(self.out, "}}");
+            rust!(self.out, "}}"); // impl
+            rust!(self.out, "");
         }
 
-        // Shift.
-        rust!(self.out, "if {}action > 0 {{", self.prefix);
-        if DEBUG_PRINT {
-            rust!(self.out, "println!(\"--> shift\");");
+        let user_start_symbol = self.user_start_symbol;
+        try!(self.out.write_pub_fn_header(self.grammar,
+                                          format!("parse_{}_items", user_start_symbol),
+                                          vec![],
+                                          vec![],
+                                          format!("{}Items<{}>", self.prefix, type_parameters),
+                                          vec![]));
+        rust!(self.out, "{{");
+        rust!(self.out, "{}Items {{", self.prefix);
+        for parameter in &self.grammar.parameters {
+            rust!(self.out, "{}: {},", parameter.name, parameter.name);
         }
-        try!(self.token_to_symbol());
-        rust!(self.out,
-              "{}states.push({}action - 1);",
-              self.prefix,
-              self.prefix);
+        let generated_module = self.generated_module();
         rust!(self.out,
-              "{}symbols.push(({}lookahead.0, {}symbol, {}lookahead.2));",
-              self.prefix,
+              "{}tokens: {}::{}intern_token::{}Matcher::new(input),",
               self.prefix,
+              generated_module,
               self.prefix,
               self.prefix);
-        rust!(self.out, "continue '{}shift;", self.prefix);
-
-        // Reduce.
-        rust!(self.out, "}} else if {}action < 0 {{", self.prefix);
-        if DEBUG_PRINT {
-            rust!(self.out, "println!(\"--> reduce\");");
-        }
-        rust!(self.out,
-              "if let Some(r) = {}reduce({}{}action, Some(&{}lookahead.0), &mut {}states, &mut \
-               {}symbols, {}) {{",
-              self.prefix,
-              self.grammar.user_parameter_refs(),
-              self.prefix,
-              self.prefix,
-              self.prefix,
-              self.prefix,
-              phantom_data_expr);
-        rust!(self.out, "return r;");
+        rust!(self.out, "{}pending: None,", self.prefix);
+        rust!(self.out, "{}done: false,", self.prefix);
+        rust!(self.out, "}}");
         rust!(self.out, "}}");
+        rust!(self.out, "");
 
-        // Error.
-        rust!(self.out, "}} else {{");
+        Ok(())
+    }
 
-        if self.grammar.uses_error_recovery {
-            let prefix = self.prefix;
-            try!(self.unrecognized_token_error(&format!("Some({}lookahead.clone())", prefix)));
-            rust!(self.out, "let mut {}dropped_tokens = Vec::new();", self.prefix);
-            let lookahead_start = format!("Some(&{}lookahead.0)", self.prefix);
-            try!(self.error_recovery(&lookahead_start, ""));
-            rust!(self.out, "let {}start = {}lookahead.0.clone();", self.prefix, self.prefix);
-            rust!(self.out, "let {}end = {}lookahead.2.clone();", self.prefix, self.prefix);
-
-            if DEBUG_PRINT {
-                rust!(self.out, "println!(\"Attempting to recover on state: {{}}, error_state: {{}}, symbols: {{}}\", {}state, {}error_state - 1, {}symbols.len());",
-                    self.prefix,
-                    self.prefix,
-                    self.prefix);
-            }
-
-            // Loop which drops tokens until parsing can resume again
-            rust!(self.out, "loop {{");
-            rust!(self.out, "if {}ACTION[({}error_state as usize - 1) * {} + {}integer] != 0 {{",
-                self.prefix,
-                self.prefix,
-                self.grammar.terminals.all.len(),
-                self.prefix);
-            rust!(self.out, "let {}new_len = {}symbols.len() - ({}original_state_len - {}states.len());",
-                self.prefix,
-                self.prefix,
-                self.prefix,
-                self.prefix);
-            rust!(self.out, "{}symbols.truncate({}new_len);",
-                self.prefix,
-                self.prefix);
-            rust!(self.out, "{}states.push({}error_state - 1);",
-                self.prefix,
-                self.prefix);
-            rust!(self.out, "let {}recovery = {}lalrpop_util::ErrorRecovery {{",
-                self.prefix,
-                self.prefix);
-            rust!(self.out, "error: {}error,", self.prefix);
-            rust!(self.out, "dropped_tokens: {}dropped_tokens,", self.prefix);
-            rust!(self.out, "}};");
-            rust!(self.out,
-                "{}symbols.push(({}start, {}Symbol::Termerror({}recovery), {}end));",
-                self.prefix,
-                self.prefix,
-                self.prefix,
-                self.prefix,
-                self.prefix);
-
-            if DEBUG_PRINT {
-                rust!(self.out, "println!(\"Recovering on state: {{}}, lookahead: {{}}, symbols: {{}}\", {}error_state - 1, {}integer, {}symbols.len());",
-                    self.prefix,
-                    self.prefix,
-                    self.prefix);
-            }
+    /// Writes the header of the parser fn used by `write_items()`,
+    /// which reads its tokens from the iterator's matcher, starting
+    /// with the token left in `__pending` by the previous item.
+    fn start_partial_parser_fn(&mut self) -> io::Result<()> {
+        let parameters = vec![format!("{}tokens: &mut {}::{}intern_token::{}Matcher<'input>",
+                                      self.prefix,
+                                      self.generated_module(),
+                                      self.prefix,
+                                      self.prefix),
+                              format!("{}pending: &mut Option<Result<{}, {}>>",
+                                      self.prefix,
+                                      self.types.triple_type(),
+                                      self.types.parse_error_type())];
+        try!(self.out.write_fn_header(self.grammar,
+                                      format!("{}parse_partial", self.prefix),
+                                      vec![],
+                                      parameters,
+                                      format!("Result<{}, {}>",
+                                              self.types.nonterminal_type(self.start_symbol),
+                                              self.types.parse_error_type()),
+                                      vec![]));
+        rust!(self.out, "{{");
+        Ok(())
+    }
 
-            rust!(self.out, "continue '{}inner;", self.prefix);
-            rust!(self.out, "}}");// if ACTION
+    /// Writes the header of `parse_Foo_incremental`, which takes the
+    /// `__Checkpoints` to resume from and add to along with the input.
+    fn start_incremental_parser_fn(&mut self) -> io::Result<()> {
+        let user_start_symbol = self.user_start_symbol;
+        try!(self.out.write_pub_fn_header(self.grammar,
+                                          format!("parse_{}_incremental", user_start_symbol),
+                                          vec![],
+                                          vec![format!("{}checkpoints: &mut {}Checkpoints",
+                                                       self.prefix,
+                                                       self.prefix)],
+                                          format!("Result<{}, {}>",
+                                                  self.types.nonterminal_type(self.start_symbol),
+                                                  self.types.parse_error_type()),
+                                          vec![]));
+        rust!(self.out, "{{");
+        Ok(())
+    }
 
-            rust!(self.out, "{}dropped_tokens.push({}lookahead);",
-                self.prefix,
-                self.prefix);
-            try!(self.next_token());
-            try!(self.token_to_integer());
+    /// Writes `__Checkpoints`, the copies of the stacks taken by
+    /// `parse_Foo_incremental`. The terminals are kept as the span of
+    /// the text they matched, so that the checkpoints do not borrow
+    /// the input, and are sliced out of the new input on the way back.
+    fn write_checkpoints(&mut self) -> io::Result<()> {
+        let saved_symbol = format!("(usize, {}Symbol<'static>, usize)", self.prefix);
+        rust!(self.out, "pub struct {}Checkpoints {{", self.prefix);
+        rust!(self.out, "interval: usize,");
+        rust!(self.out, "saved: Vec<(usize, Vec<i32>, Vec<{}>)>,", saved_symbol);
+        rust!(self.out, "}}");
+        rust!(self.out, "");
+        rust!(self.out, "impl {}Checkpoints {{", self.prefix);
+
+        // the public interface
+        rust!(self.out, "pub fn new(interval: usize) -> {}Checkpoints {{", self.prefix);
+        rust!(self.out, "{}Checkpoints {{", self.prefix);
+        rust!(self.out, "interval: interval,");
+        rust!(self.out, "saved: vec![],");
+        rust!(self.out, "}}");
+        rust!(self.out, "}}");
+        rust!(self.out, "");
+        rust!(self.out, "pub fn edit(&mut self, start: usize) {{");
+        rust!(self.out, "self.saved.retain(|c| c.0 <= start);");
+        rust!(self.out, "}}");
+        rust!(self.out, "");
+        rust!(self.out, "pub fn offsets(&self) -> Vec<usize> {{");
+        rust!(self.out, "self.saved.iter().map(|c| c.0).collect()");
+        rust!(self.out, "}}");
+        rust!(self.out, "");
 
-            if DEBUG_PRINT {
-                rust!(self.out, "println!(\"Skipping token: {{}}\", {}integer);",
-                    self.prefix);
-            }
+        // and the parser's
+        rust!(self.out, "fn next_offset(&self) -> usize {{");
+        rust!(self.out, "match self.saved.last() {{");
+        rust!(self.out, "Some(c) => c.0 + self.interval,");
+        rust!(self.out, "None => self.interval,");
+        rust!(self.out, "}}");
+        rust!(self.out, "}}");
+        rust!(self.out, "");
+        rust!(self.out,
+              "fn record<'input>(&mut self, offset: usize, states: &[i32], \
+               symbols: &[(usize, {}Symbol<'input>, usize)]) {{",
+              self.prefix);
+        rust!(self.out,
+              "let symbols = symbols.iter().map(|&(l, ref s, r)| (l, match *s {{");
+        try!(self.copy_symbol_arms("\"\""));
+        rust!(self.out, "}}, r)).collect();");
+        rust!(self.out, "self.saved.push((offset, states.to_vec(), symbols));");
+        rust!(self.out, "}}");
+        rust!(self.out, "");
+        rust!(self.out,
+              "fn restore<'input>(&self, input: &'input str) \
+               -> (usize, Vec<i32>, Vec<(usize, {}Symbol<'input>, usize)>) {{",
+              self.prefix);
+        rust!(self.out, "match self.saved.last() {{");
+        rust!(self.out, "Some(&(offset, ref states, ref symbols)) => {{");
+        rust!(self.out,
+              "let symbols = symbols.iter().map(|&(l, ref s, r)| (l, match *s {{");
+        try!(self.copy_symbol_arms("&input[l..r]"));
+        rust!(self.out, "}}, r)).collect();");
+        rust!(self.out, "(offset, states.clone(), symbols)");
+        rust!(self.out, "}}");
+        rust!(self.out, "None => (0, vec![0], vec![]),");
+        rust!(self.out, "}}");
+        rust!(self.out, "}}");
+        rust!(self.out, "}}"); // impl
+        rust!(self.out, "");
+        Ok(())
+    }
 
-            rust!(self.out, "}}"); // loop
+    /// Writes `__PushParser`, which keeps the stacks of the parser fn
+    /// between calls to `push`, each of which carries out the actions
+    /// of one token: the reductions it leads to, then its shift. The
+    /// reductions at the end of the input are left to `finish`. The
+    /// grammar's parameters are kept along with the stacks.
+    fn write_push_parser(&mut self) -> io::Result<()> {
+        let type_parameters = Sep(", ", &self.grammar.type_parameters);
+        let where_clauses = if self.grammar.where_clauses.is_empty() {
+            String::new()
         } else {
-            let prefix = self.prefix;
-            try!(self.unrecognized_token_error(&format!("Some({}lookahead)", prefix)));
-            rust!(self.out, "return Err({}error)", self.prefix);
-        }
-
-        rust!(self.out, "}}"); // if-else-if-else
+            format!(" where {}", Sep(", ", &self.grammar.where_clauses))
+        };
+        let parse_error_type = self.types.parse_error_type().clone();
+        let phantom_data_expr = self.phantom_data_expr();
 
-        rust!(self.out, "}}"); // reduce loop
+        rust!(self.out,
+              "pub struct {}PushParser<{}>{} {{",
+              self.prefix,
+              type_parameters,
+              where_clauses);
+        for parameter in &self.grammar.parameters {
+            rust!(self.out, "{}: {},", parameter.name, parameter.ty);
+        }
+        rust!(self.out, "{}states: ::std::vec::Vec<i32>,", self.prefix);
+        rust!(self.out,
+              "{}symbols: ::std::vec::Vec<{}>,",
+              self.prefix,
+              self.spanned_symbol_type());
+        rust!(self.out, "{}phantom: {},", self.prefix, self.phantom_data_type());
+        rust!(self.out, "}}");
+        rust!(self.out, "");
 
-        rust!(self.out, "}}"); // shift loop
+        rust!(self.out,
+              "impl<{}> {}PushParser<{}>{} {{",
+              type_parameters,
+              self.prefix,
+              type_parameters,
+              where_clauses);
+
+        // `new`, which takes the grammar's parameters
+        let parameters: Vec<_> = self.grammar
+                                     .parameters
+                                     .iter()
+                                     .map(|p| format!("{}: {}", p.name, p.ty))
+                                     .collect();
+        rust!(self.out,
+              "pub fn new({}) -> {}PushParser<{}> {{",
+              Sep(", ", &parameters),
+              self.prefix,
+              type_parameters);
+        rust!(self.out, "{}PushParser {{", self.prefix);
+        for parameter in &self.grammar.parameters {
+            rust!(self.out, "{}: {},", parameter.name, parameter.name);
+        }
+        rust!(self.out, "{}states: vec![0],", self.prefix);
+        rust!(self.out, "{}symbols: vec![],", self.prefix);
+        rust!(self.out, "{}phantom: {},", self.prefix, phantom_data_expr);
+        rust!(self.out, "}}");
+        rust!(self.out, "}}");
+        rust!(self.out, "");
 
-        // EOF loop
+        // `push`, which acts on one token
+        rust!(self.out,
+              "pub fn push(&mut self, {}lookahead: {}) -> Result<(), {}> {{",
+              self.prefix,
+              self.types.triple_type(),
+              parse_error_type);
+        for parameter in &self.grammar.parameters {
+            rust!(self.out, "let {} = self.{};", parameter.name, parameter.name);
+        }
+        rust!(self.out, "let {}states = &mut self.{}states;", self.prefix, self.prefix);
+        rust!(self.out, "let {}symbols = &mut self.{}symbols;", self.prefix, self.prefix);
+        rust!(self.out, "let {}integer;", self.prefix);
+        try!(self.token_to_integer(true));
         rust!(self.out, "loop {{");
         rust!(self.out,
               "let {}state = *{}states.last().unwrap() as usize;",
               self.prefix,
               self.prefix);
-        if DEBUG_PRINT {
+        if self.grammar.uses_wildcard() {
             rust!(self.out,
-                  "println!(\"EOF loop state: {{}}\", {}state);",
+                  "let {}terminal = if {}ACTION[{}state * {} + {}integer] == 0 {{ {} }} \
+                   else {{ {}integer }};",
+                  self.prefix,
+                  self.prefix,
+                  self.prefix,
+                  self.grammar.terminals.all.len(),
+                  self.prefix,
+                  self.wildcard_integer(),
                   self.prefix);
         }
+        let terminal = self.terminal_variable();
         rust!(self.out,
-              "let {}action = {}EOF_ACTION[{}state];",
+              "let {}action = {}ACTION[{}state * {} + {}]{};",
               self.prefix,
               self.prefix,
-              self.prefix);
-        if DEBUG_PRINT {
+              self.prefix,
+              self.grammar.terminals.all.len(),
+              terminal,
+              self.custom.table_type.widen());
+        if Tls::session().emit_introspection {
             rust!(self.out,
-                  "println!(\"EOF in state {{}} takes action {{}}\", {}state, {}action);",
+                  "{}introspect(&{}states, Some({}));",
                   self.prefix,
-                  self.prefix);
+                  self.prefix,
+                  terminal);
         }
-        rust!(self.out, "if {}action < 0 {{", self.prefix);
+        if self.uses_conflict_hooks() {
+            try!(self.conflict_hook_action());
+        }
+        rust!(self.out, "if {}action > 0 {{", self.prefix);
+        try!(self.stack_overflow_check());
+        try!(self.token_to_symbol());
+        rust!(self.out, "{}states.push({}action - 1);", self.prefix, self.prefix);
+        try!(self.notify_observer(&format!("shift({}TERMINAL[{}integer], {}action - 1)",
+                                           self.prefix,
+                                           self.prefix,
+                                           self.prefix)));
         rust!(self.out,
-              "if let Some(r) = {}reduce({}{}action, None, &mut {}states, &mut {}symbols, {}) {{",
+              "{}symbols.push(({}lookahead.0, {}symbol, {}lookahead.2));",
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              self.prefix);
+        rust!(self.out, "return Ok(());");
+        rust!(self.out, "}} else if {}action < 0 {{", self.prefix);
+        try!(self.notify_reduce());
+        // only the end of the input completes the start symbol, so
+        // this can only be the error of a fallible action
+        rust!(self.out,
+              "if let Some(r) = {}reduce({}{}action, Some(&{}lookahead), {}states, {}symbols, \
+               {}) {{",
               self.prefix,
               self.grammar.user_parameter_refs(),
               self.prefix,
               self.prefix,
               self.prefix,
+              self.prefix,
               phantom_data_expr);
-        rust!(self.out, "return r;");
+        rust!(self.out, "return r.map(|_| ());");
         rust!(self.out, "}}");
         rust!(self.out, "}} else {{");
+        let prefix = self.prefix;
+        try!(self.unrecognized_token_error(&format!("Some({}lookahead)", prefix)));
+        try!(self.notify_error(&format!("Some({}TERMINAL[{}integer])", prefix, prefix)));
+        rust!(self.out, "return Err({}error);", self.prefix);
+        rust!(self.out, "}}");
+        rust!(self.out, "}}"); // loop
+        rust!(self.out, "}}"); // fn push
+        rust!(self.out, "");
 
-        // EOF error recovery
-        try!(self.unrecognized_token_error("None"));
-
-        if self.grammar.uses_error_recovery {
-            let extra_test = format!("&& {}EOF_ACTION[({}error_state as usize - 1)] != 0 ",
-                self.prefix,
-                self.prefix);
-            try!(self.error_recovery("None", &extra_test));
-            rust!(self.out, "let {}new_len = {}symbols.len() - ({}original_state_len - {}states.len());",
-                self.prefix,
-                self.prefix,
-                self.prefix,
-                self.prefix);
-            rust!(self.out, "{}symbols.truncate({}new_len);",
-                self.prefix,
-                self.prefix);
-            rust!(self.out, "{}states.push({}error_state - 1);",
-                self.prefix,
-                self.prefix);
-            rust!(self.out, "let {}recovery = {}lalrpop_util::ErrorRecovery {{",
-                self.prefix,
-                self.prefix);
-            rust!(self.out, "error: {}error,", self.prefix);
-            rust!(self.out, "dropped_tokens: Vec::new(),");
-            rust!(self.out, "}};");
-            rust!(self.out,
-                "{}symbols.push(({}last_location.clone(), {}Symbol::Termerror({}recovery), {}last_location.clone()));",
-                self.prefix,
-                self.prefix,
-                self.prefix,
-                self.prefix,
-                self.prefix);
-
-        } else {
-            rust!(self.out, "return Err({}error);", self.prefix)
+        // `finish`, which acts on the end of the input
+        rust!(self.out,
+              "pub fn finish(self) -> Result<{}, {}> {{",
+              self.types.nonterminal_type(self.start_symbol),
+              parse_error_type);
+        for parameter in &self.grammar.parameters {
+            rust!(self.out, "let {} = self.{};", parameter.name, parameter.name);
         }
-
-        rust!(self.out, "}}"); // else
-        
-        rust!(self.out, "}}"); // while let
-
-        self.end_parser_fn()
-    }
-
-    fn next_token(&mut self) -> io::Result<()> {
+        rust!(self.out, "let mut {}states = self.{}states;", self.prefix, self.prefix);
+        rust!(self.out, "let mut {}symbols = self.{}symbols;", self.prefix, self.prefix);
+        rust!(self.out, "loop {{");
         rust!(self.out,
-              "{}lookahead = match {}tokens.next() {{",
+              "let {}state = *{}states.last().unwrap() as usize;",
               self.prefix,
               self.prefix);
-        rust!(self.out, "Some(Ok(v)) => v,");
-        rust!(self.out, "None => break '{}shift,", self.prefix); // EOF: break out
-        if self.grammar.intern_token.is_some() {
-            // when we generate the tokenizer, the generated errors are `ParseError` values
-            rust!(self.out, "Some(Err(e)) => return Err(e),");
-        } else {
-            // otherwise, they are user errors
-            rust!(self.out,
-                  "Some(Err(e)) => return Err({}lalrpop_util::ParseError::User {{ error: e }}),",
-                  self.prefix);
+        if Tls::session().emit_introspection {
+            rust!(self.out, "{}introspect(&{}states, None);", self.prefix, self.prefix);
         }
-        rust!(self.out, "}};");
-        rust!(self.out, "{}last_location = {}lookahead.2.clone();",
+        rust!(self.out,
+              "let {}action = {}EOF_ACTION[{}state]{};",
               self.prefix,
-              self.prefix);
-        Ok(())
-    }
-
-    fn token_to_integer(&mut self) -> io::Result<()> {
+              self.prefix,
+              self.prefix,
+              self.custom.table_type.widen());
+        rust!(self.out, "if {}action < 0 {{", self.prefix);
+        try!(self.notify_reduce());
         rust!(self.out,
-              "{}integer = match {}lookahead.1 {{",
+              "if let Some(r) = {}reduce({}{}action, None, &mut {}states, &mut {}symbols, {}) {{",
               self.prefix,
-              self.prefix);
-        for (&terminal, index) in self.grammar.terminals.all.iter().zip(0..) {
-            if terminal == TerminalString::Error {
-                continue;
-            }
-            let pattern = self.grammar.pattern(terminal).map(&mut |_| "_");
-            rust!(self.out, "{} if true => {},", pattern, index);
-        }
-
-        rust!(self.out, "_ => {{");
-        let prefix = self.prefix;
-        try!(self.unrecognized_token_error(&format!("Some({}lookahead)", prefix)));
+              self.grammar.user_parameter_refs(),
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              phantom_data_expr);
+        rust!(self.out, "return r;");
+        rust!(self.out, "}}");
+        rust!(self.out, "}} else {{");
+        try!(self.unrecognized_token_error("None"));
+        try!(self.notify_error("None"));
         rust!(self.out, "return Err({}error);", self.prefix);
         rust!(self.out, "}}");
+        rust!(self.out, "}}"); // loop
+        rust!(self.out, "}}"); // fn finish
+        rust!(self.out, "");
 
-        rust!(self.out, "}};");
+        // the terminals that the next token may be, e.g. to tell
+        // whether the input so far is complete
+        rust!(self.out, "pub fn expected_tokens(&self) -> Vec<::std::string::String> {{");
+        rust!(self.out, "{}expected_tokens(&self.{}states)", self.prefix, self.prefix);
+        rust!(self.out, "}}");
+        rust!(self.out, "}}"); // impl
+        rust!(self.out, "");
         Ok(())
     }
 
-    fn token_to_symbol(&mut self) -> io::Result<()> {
-        rust!(self.out,
-              "let {}symbol = match {}integer {{",
-              self.prefix,
-              self.prefix);
-        for (&terminal, index) in self.grammar.terminals.all.iter().zip(0..) {
-            if terminal == TerminalString::Error {
-                continue;
-            }
-            rust!(self.out, "{} => match {}lookahead.1 {{", index, self.prefix);
-
-            let mut pattern_names = vec![];
-            let pattern = self.grammar.pattern(terminal).map(&mut |_| {
-                let index = pattern_names.len();
-                pattern_names.push(format!("{}tok{}", self.prefix, index));
-                pattern_names.last().cloned().unwrap()
-            });
+    /// Writes the arms of a match that copies the symbol `s`, with the
+    /// text of terminals replaced by `text`.
+    fn copy_symbol_arms(&mut self, text: &str) -> io::Result<()> {
+        for &term in &self.grammar.terminals.all {
+            let name = self.variant_name_for_symbol(Symbol::Terminal(term));
+            rust!(self.out,
+                  "{}Symbol::{}(_) => {}Symbol::{}({}),",
+                  self.prefix,
+                  name,
+                  self.prefix,
+                  name,
+                  text);
+        }
+        for &nt in self.grammar.nonterminals.keys() {
+            let name = self.variant_name_for_symbol(Symbol::Nonterminal(nt));
+            rust!(self.out,
+                  "{}Symbol::{}(ref v) => {}Symbol::{}(v.clone()),",
+                  self.prefix,
+                  name,
+                  self.prefix,
+                  name);
+        }
+        Ok(())
+    }
 
-            let mut pattern = format!("{}", pattern);
-            if pattern_names.is_empty() {
-                pattern_names.push(format!("{}tok", self.prefix));
-                pattern = format!("{}tok @ {}", self.prefix, pattern);
+    fn write_value_type_defn(&mut self) -> io::Result<()> {
+        // sometimes some of the variants are not used, particularly
+        // if we are generating multiple parsers from the same file:
+        rust!(self.out, "#[allow(dead_code)]");
+        rust!(self.out,
+              "pub enum {}Symbol<{}> {{",
+              self.prefix,
+              Sep(", ", &self.custom.symbol_type_params));
+
+        // make one variant per terminal
+        for &term in &self.grammar.terminals.all {
+            let name = self.variant_name_for_symbol(Symbol::Terminal(term));
+            let ty = self.types.terminal_type(term).clone();
+            rust!(self.out, "{}({}),", name, ty);
+        }
+
+        // make one variant per nonterminal
+        for &nt in self.grammar.nonterminals.keys() {
+            let name = self.variant_name_for_symbol(Symbol::Nonterminal(nt));
+            let ty = self.types.nonterminal_type(nt).clone();
+            rust!(self.out, "{}({}),", name, ty);
+        }
+        rust!(self.out, "}}");
+        Ok(())
+    }
+
+    fn write_parse_table(&mut self) -> io::Result<()> {
+        // The tables are `static`s rather than `const`s, so that every
+        // call to the parser (on any thread) reads the same copy, and
+        // so that rustc checks that they are `Sync`. They are plain
+        // data that the parser never writes to.
+        //
+        // The action table is a two-dimensional matrix indexed first by state
+        // and then by the terminal index. The value is described above.
+        if self.custom.compress_tables {
+            try!(self.emit_compressed_table_type());
+        }
+        let columns = self.grammar.terminals.all.len();
+        try!(self.begin_table("ACTION", Some(columns)));
+
+        for (index, state) in self.states.iter().enumerate() {
+            if self.writes_rows() {
+                rust!(self.out, "// State {}", index);
             }
 
-            let variant_name = self.variant_name_for_symbol(Symbol::Terminal(terminal));
+            if Tls::session().emit_comments && self.writes_rows() {
+                for item in state.items.vec.iter() {
+                    rust!(self.out, "//     {:?}", item);
+                }
+            }
+
+            // Write an action for each terminal (either shift, reduce, or error).
+            let row: Vec<_> = {
+                let custom = &self.custom;
+                self.grammar
+                    .terminals
+                    .all
+                    .iter()
+                    .map(|terminal| {
+                        if state.lookahead2.contains_key(&terminal) {
+                            (custom.table_type.lookahead2(),
+                             Comment::Lookahead2(Token::Terminal(*terminal)))
+                        } else if state.conflict_hooks.contains_key(&terminal) {
+                            (custom.table_type.conflict_hook(),
+                             Comment::ConflictHook(Token::Terminal(*terminal)))
+                        } else if let Some(new_state) = state.shifts.get(&terminal) {
+                            (new_state.0 as i32 + 1,
+                             Comment::Goto(Token::Terminal(*terminal), new_state.0))
+                        } else {
+                            Self::write_reduction(custom, state, Token::Terminal(*terminal))
+                        }
+                    })
+                    .collect()
+            };
+            try!(self.write_table_row(row));
+        }
+
+        try!(self.end_table("ACTION"));
+
+        // Actions on EOF. Indexed just by state.
+        try!(self.begin_table("EOF_ACTION", None));
+        for state in self.states {
+            let reduction = Self::write_reduction(&self.custom, state, Token::EOF);
+            try!(self.write_table_row(vec![reduction]));
+        }
+        try!(self.end_table("EOF_ACTION"));
+
+        // The goto table is indexed by state and *nonterminal*.
+        let columns = self.custom.all_nonterminals.len();
+        try!(self.begin_table("GOTO", Some(columns)));
+        for (index, state) in self.states.iter().enumerate() {
+            if self.writes_rows() {
+                rust!(self.out, "// State {}", index);
+            }
+            let row: Vec<_> = self.custom
+                                  .all_nonterminals
+                                  .iter()
+                                  .map(|nonterminal| {
+                                      if let Some(&new_state) = state.gotos.get(nonterminal) {
+                                          (new_state.0 as i32 + 1,
+                                           Comment::Goto(*nonterminal, new_state.0))
+                                      } else {
+                                          (0, Comment::Error(*nonterminal))
+                                      }
+                                  })
+                                  .collect();
+            try!(self.write_table_row(row));
+        }
+        try!(self.end_table("GOTO"));
+
+        if self.custom.table_file.is_some() {
+            try!(self.emit_table_file_decoder());
+        }
+
+        // For each production, in the order of the reduce actions: how
+        // many states it pops and the column of its nonterminal in the
+        // goto table. Only `__simulate` needs this; `__reduce` knows it.
+        // Productions that were pruned are never reduced, so their
+        // column does not matter.
+        rust!(self.out,
+              "static {}REDUCE: &'static [(usize, usize)] = &[",
+              self.prefix);
+        for production in self.grammar.nonterminals.values().flat_map(|nt| &nt.productions) {
+            let index = self.custom
+                            .all_nonterminals
+                            .iter()
+                            .position(|&x| x == production.nonterminal)
+                            .unwrap_or(0);
             rust!(self.out,
-                  "{} => {}Symbol::{}(({})),",
-                  pattern,
-                  self.prefix,
-                  variant_name,
-                  pattern_names.join(", "));
-            rust!(self.out, "_ => unreachable!(),");
-            rust!(self.out, "}},");
+                  "({}, {}), // {:?}",
+                  production.symbols.len(),
+                  index,
+                  production);
         }
+        rust!(self.out, "];");
 
-        rust!(self.out, "_ => unreachable!(),");
+        rust!(self.out, "static {}TERMINAL: &'static [&'static str] = &[", self.prefix);
+        for &terminal in &self.grammar.terminals.all {
+            // Three # should hopefully be enough to prevent any
+            // reasonable terminal from escaping the literal
+            rust!(self.out, "r###\"{}\"###,", terminal);
+        }
+        rust!(self.out, "];");
+
+        try!(self.emit_simulate_fn());
+        try!(self.emit_expected_tokens_fn());
+        // only `complete_Foo` needs this, which is not generated for
+        // the parsers of items
+        if self.grammar.generates_completion_fns() && !self.custom.partial {
+            try!(self.emit_expected_nonterminals_fn());
+        }
+        if Tls::session().emit_introspection || Tls::session().emit_observer {
+            try!(self.emit_productions_table());
+        }
+        if Tls::session().emit_introspection {
+            try!(self.emit_introspection());
+        }
+        if Tls::session().emit_observer {
+            try!(self.emit_observer());
+        }
+        try!(self.emit_token_to_integer_fn());
+
+        if self.uses_lookahead2() {
+            try!(self.emit_action2_fn());
+        }
+
+        if self.uses_conflict_hooks() {
+            try!(self.emit_conflict_hook_fn());
+        }
 
-        rust!(self.out, "}};");
         Ok(())
     }
 
-    fn emit_reduce_actions(&mut self) -> io::Result<()> {
-        let success_type = self.types.nonterminal_type(self.start_symbol);
-        let parse_error_type = self.types.parse_error_type();
-        let loc_type = self.types.terminal_loc_type();
-        let spanned_symbol_type = self.spanned_symbol_type();
+    /// Starts the table `name`: a `static` array, or, if the tables go
+    /// in a file, a `__Table` that picks its entries out of that file.
+    /// A table with `columns` columns is compressed, if the session
+    /// allows it; one without is always written out in full.
+    fn begin_table(&mut self, name: &str, columns: Option<usize>) -> io::Result<()> {
+        if let (true, Some(columns)) = (self.custom.compress_tables, columns) {
+            rust!(self.out,
+                  "static {}{}: {}CompressedTable = {}CompressedTable {{",
+                  self.prefix,
+                  name,
+                  self.prefix,
+                  self.prefix);
+            rust!(self.out, "columns: {},", columns);
+            self.custom.table_rows = Some(vec![]);
+        } else if self.custom.table_file.is_none() {
+            rust!(self.out,
+                  "static {}{}: &'static [{}] = &[",
+                  self.prefix,
+                  name,
+                  self.custom.table_type.name());
+        }
+        self.custom.table_start = self.custom.table_entries.len();
+        Ok(())
+    }
 
-        let parameters = vec![format!("{}action: i32", self.prefix),
-                              format!("{}lookahead_start: Option<&{}>", self.prefix, loc_type),
-                              format!("{}states: &mut ::std::vec::Vec<i32>", self.prefix),
-                              format!("{}symbols: &mut ::std::vec::Vec<{}>",
-                                      self.prefix,
-                                      spanned_symbol_type),
-                              format!("_: {}", self.phantom_data_type())];
+    fn write_table_row<C: fmt::Display>(&mut self, row: Vec<(i32, C)>) -> io::Result<()> {
+        if let Some(ref mut rows) = self.custom.table_rows {
+            rows.push(row.into_iter().map(|(entry, _)| entry).collect());
+            Ok(())
+        } else if self.custom.table_file.is_some() {
+            self.custom.table_entries.extend(row.into_iter().map(|(entry, _)| entry));
+            Ok(())
+        } else {
+            self.out.write_table_row(row)
+        }
+    }
 
-        try!(self.out.write_pub_fn_header(self.grammar,
-                                          format!("{}reduce", self.prefix),
-                                          vec![],
-                                          parameters,
-                                          format!("Option<Result<{},{}>>",
-                                                  success_type,
-                                                  parse_error_type),
-                                          vec![]));
-        rust!(self.out, "{{");
+    fn end_table(&mut self, name: &str) -> io::Result<()> {
+        if let Some(rows) = self.custom.table_rows.take() {
+            let table = compress(&rows);
+            let bases: Vec<i32> = table.bases.iter().map(|&base| base as i32).collect();
+            for &(field, ref entries) in &[("defaults", &table.defaults),
+                                           ("bases", &bases),
+                                           ("check", &table.check),
+                                           ("entries", &table.entries)] {
+                rust!(self.out, "{}: &[", field);
+                for line in entries.chunks(16) {
+                    let line: Vec<String> =
+                        line.iter().map(|entry| format!("{},", entry)).collect();
+                    rust!(self.out, "{}", line.join(" "));
+                }
+                rust!(self.out, "],");
+            }
+            rust!(self.out, "}};");
+        } else if self.custom.table_file.is_none() {
+            rust!(self.out, "];");
+        } else {
+            rust!(self.out,
+                  "static {}{}: {}Table = {}Table {{ start: {}, end: {} }};",
+                  self.prefix,
+                  name,
+                  self.prefix,
+                  self.prefix,
+                  self.custom.table_start,
+                  self.custom.table_entries.len());
+        }
+        Ok(())
+    }
 
+    /// True if the rows of the tables are written out as they are, so
+    /// that comments can go between them.
+    fn writes_rows(&self) -> bool {
+        self.custom.table_file.is_none() && !self.custom.compress_tables
+    }
+
+    /// Writes `__CompressedTable`, through which the parser indexes the
+    /// compressed `ACTION` and `GOTO` tables as it would the arrays,
+    /// with `state * columns + column`.
+    fn emit_compressed_table_type(&mut self) -> io::Result<()> {
+        let ty = self.custom.table_type.name();
+        rust!(self.out, "struct {}CompressedTable {{", self.prefix);
+        rust!(self.out, "columns: usize,");
+        rust!(self.out, "defaults: &'static [{}],", ty);
+        rust!(self.out, "bases: &'static [u32],");
+        rust!(self.out, "check: &'static [{}],", ty);
+        rust!(self.out, "entries: &'static [{}],", ty);
+        rust!(self.out, "}}");
+        rust!(self.out, "");
+        rust!(self.out, "impl ::std::ops::Index<usize> for {}CompressedTable {{", self.prefix);
+        rust!(self.out, "type Output = {};", ty);
+        rust!(self.out, "fn index(&self, index: usize) -> &{} {{", ty);
         rust!(self.out,
-              "let {}nonterminal = match -{}action {{",
+              "let ({}state, {}column) = (index / self.columns, index % self.columns);",
               self.prefix,
               self.prefix);
-        for (production, index) in self.grammar
-                                       .nonterminals
-                                       .values()
-                                       .flat_map(|nt| &nt.productions)
-                                       .zip(1..) {
-
-            rust!(self.out, "{} => {{", index);
-            try!(self.emit_reduce_action(production));
-            rust!(self.out, "}}");
-        }
         rust!(self.out,
-              "_ => panic!(\"invalid action code {{}}\", {}action)",
+              "let {}slot = self.bases[{}state] as usize + {}column;",
+              self.prefix,
+              self.prefix,
               self.prefix);
-        rust!(self.out, "}};");
         rust!(self.out,
-              "let {}state = *{}states.last().unwrap() as usize;",
+              "if {}slot < self.check.len() && self.check[{}slot] as usize == {}state {{",
+              self.prefix,
               self.prefix,
               self.prefix);
+        rust!(self.out, "&self.entries[{}slot]", self.prefix);
+        rust!(self.out, "}} else {{");
+        rust!(self.out, "&self.defaults[{}state]", self.prefix);
+        rust!(self.out, "}}");
+        rust!(self.out, "}}");
+        rust!(self.out, "}}");
+        rust!(self.out, "");
+        Ok(())
+    }
+
+    /// Writes `__Table`, through which the parser indexes the tables in
+    /// the file as it would the arrays, and `__tables`, which embeds the
+    /// file and decodes it the first time it is called. The decoded
+    /// entries are leaked: like the arrays, they live as long as the
+    /// program.
+    fn emit_table_file_decoder(&mut self) -> io::Result<()> {
+        let name = self.custom.table_file.clone().unwrap();
+        let table_type = self.custom.table_type;
+        let ty = table_type.name();
+
+        rust!(self.out, "struct {}Table {{", self.prefix);
+        rust!(self.out, "start: usize,");
+        rust!(self.out, "end: usize,");
+        rust!(self.out, "}}");
+        rust!(self.out, "");
+        rust!(self.out, "impl ::std::ops::Index<usize> for {}Table {{", self.prefix);
+        rust!(self.out, "type Output = {};", ty);
+        rust!(self.out, "fn index(&self, index: usize) -> &{} {{", ty);
+        rust!(self.out, "&{}tables()[self.start..self.end][index]", self.prefix);
+        rust!(self.out, "}}");
+        rust!(self.out, "}}");
+        rust!(self.out, "");
+        rust!(self.out, "fn {}tables() -> &'static [{}] {{", self.prefix, ty);
         rust!(self.out,
-              "let {}next_state = {}GOTO[{}state * {} + {}nonterminal] - 1;",
+              "static {}BYTES: &'static [u8] = include_bytes!({:?});",
               self.prefix,
+              name);
+        rust!(self.out,
+              "static {}ONCE: ::std::sync::Once = ::std::sync::ONCE_INIT;",
+              self.prefix);
+        rust!(self.out, "static mut {}TABLES: &'static [{}] = &[];", self.prefix, ty);
+        rust!(self.out, "unsafe {{");
+        rust!(self.out, "{}ONCE.call_once(|| {{", self.prefix);
+        rust!(self.out,
+              "let {}entries: Vec<{}> = {}BYTES.chunks({}).map(|{}bytes| {{",
               self.prefix,
+              ty,
               self.prefix,
-              self.grammar.nonterminals.len(),
+              table_type.width(),
               self.prefix);
-        if DEBUG_PRINT {
-            rust!(self.out,
-                  "println!(\"goto state {{}} from {{}} due to nonterminal {{}}\", {}next_state, \
-                   {}state, {}nonterminal);",
-                  self.prefix,
-                  self.prefix,
-                  self.prefix);
-        }
         rust!(self.out,
-              "{}states.push({}next_state);",
+              "{}bytes.iter().rev().fold(0u32, |{}entry, &{}byte| {}entry << 8 | {}byte as u32) \
+               as {}",
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              ty);
+        rust!(self.out, "}}).collect();");
+        rust!(self.out,
+              "{}TABLES = &*Box::into_raw({}entries.into_boxed_slice());",
               self.prefix,
               self.prefix);
-        rust!(self.out, "None");
+        rust!(self.out, "}});");
+        rust!(self.out, "{}TABLES", self.prefix);
+        rust!(self.out, "}}");
         rust!(self.out, "}}");
         Ok(())
     }
 
-    fn emit_reduce_action(&mut self, production: &Production) -> io::Result<()> {
-        rust!(self.out, "// {:?}", production);
+    /// Writes `__token_to_integer`, which maps a token to the index of
+    /// its terminal in the tables above, or `None` if it is not one of
+    /// the terminals of the grammar. The indices follow the order of
+    /// the terminals in the grammar, not the discriminants of the
+    /// token type, so the token type can be defined anywhere.
+    fn emit_token_to_integer_fn(&mut self) -> io::Result<()> {
+        let token_type = self.types.terminal_token_type().clone();
+        let referenced = token_type.referenced();
+        let type_parameters: Vec<_> = self.grammar
+                                          .type_parameters
+                                          .iter()
+                                          .filter(|t| referenced.contains(t))
+                                          .cloned()
+                                          .collect();
 
-        // Pop each of the symbols and their associated states.
-        for (index, &symbol) in production.symbols.iter().enumerate().rev() {
-            let name = self.variant_name_for_symbol(symbol);
-            rust!(self.out,
-                "let {}sym{} = {}pop_{}({}symbols);",
-                self.prefix,
-                index,
-                self.prefix,
-                name,
-                self.prefix);
+        rust!(self.out,
+              "fn {}token_to_integer<{}>({}token: &{}) -> Option<usize> {{",
+              self.prefix,
+              Sep(", ", &type_parameters),
+              self.prefix,
+              token_type);
+        rust!(self.out, "match *{}token {{", self.prefix);
+        for (&terminal, index) in self.grammar.terminals.all.iter().zip(0..) {
+            if terminal == TerminalString::Error {
+                continue;
+            }
+            let pattern = self.grammar.pattern(terminal).map(&mut |_| "_");
+            rust!(self.out, "{} if true => Some({}),", pattern, index);
         }
-        let transfer_syms: Vec<_> = (0..production.symbols.len())
-                                        .map(|i| format!("{}sym{}", self.prefix, i))
-                                        .collect();
+        rust!(self.out, "_ => None,");
+        rust!(self.out, "}}");
+        rust!(self.out, "}}");
+        Ok(())
+    }
 
-        // Execute the action fn
-        // identify the "start" location for this production; this
-        // is typically the start of the first symbol we are
-        // reducing; but in the case of an empty production, it
-        // will be the last symbol pushed, or at worst `default`.
-        if let Some(first_sym) = transfer_syms.first() {
-            rust!(self.out,
-                  "let {}start = {}.0.clone();",
-                  self.prefix,
-                  first_sym);
+    /// The variable that holds the column of the action table to
+    /// consult for the lookahead: `integer`, unless the grammar uses
+    /// `_`, in which case it may be the column of `_` instead.
+    fn terminal_variable(&self) -> String {
+        if self.grammar.uses_wildcard() {
+            format!("{}terminal", self.prefix)
         } else {
-            // we pop no symbols, so grab from the top of the stack
-            // (unless we are in the start state, in which case the
-            // stack will be empty)
-            rust!(self.out,
-                  "let {}start = {}symbols.last().map(|s| s.2.clone()).unwrap_or_default();",
-                  self.prefix,
-                  self.prefix);
+            format!("{}integer", self.prefix)
         }
+    }
 
-        // identify the "end" location for this production;
-        // this is typically the end of the last symbol we are reducing,
-        // but in the case of an empty production it will come from the
-        // lookahead
-        if let Some(last_sym) = transfer_syms.last() {
-            rust!(self.out, "let {}end = {}.2.clone();", self.prefix, last_sym);
-        } else {
-            rust!(self.out,
-                  "let {}end = {}lookahead_start.cloned().unwrap_or_else(|| \
-                   {}start.clone());",
-                  self.prefix,
-                  self.prefix,
-                  self.prefix);
-        }
+    fn wildcard_integer(&self) -> usize {
+        self.grammar.terminals.all.iter().position(|&t| t == TerminalString::Any).unwrap()
+    }
 
-        let transfered_syms = transfer_syms.len();
+    fn uses_lookahead2(&self) -> bool {
+        self.states.iter().any(|state| !state.lookahead2.is_empty())
+    }
 
-        let mut args = transfer_syms;
-        if transfered_syms == 0 {
-            args.push(format!("&{}start", self.prefix));
-            args.push(format!("&{}end", self.prefix));
-        }
+    fn uses_conflict_hooks(&self) -> bool {
+        self.states.iter().any(|state| !state.conflict_hooks.is_empty())
+    }
 
-        // invoke the action code
-        let is_fallible = self.grammar.action_is_fallible(production.action);
-        if is_fallible {
-            rust!(self.out,
-                  "let {}nt = match {}::{}action{}::<{}>({}{}) {{",
+    /// Writes `__conflict_hook`, which gives the two actions between
+    /// which the conflict hook decides in the states and on the
+    /// terminals marked `TableType::conflict_hook` in the action
+    /// table: the shift, the reduction, and the name of the
+    /// nonterminal it reduces to.
+    fn emit_conflict_hook_fn(&mut self) -> io::Result<()> {
+        rust!(self.out,
+              "fn {}conflict_hook({}state: usize, {}integer: usize) -> (i32, i32, &'static str) {{",
+              self.prefix,
+              self.prefix,
+              self.prefix);
+        rust!(self.out, "match ({}state, {}integer) {{", self.prefix, self.prefix);
+        for state in self.states {
+            for (terminal, &(next_state, production)) in &state.conflict_hooks {
+                let integer =
+                    self.grammar.terminals.all.iter().position(|t| t == terminal).unwrap();
+                rust!(self.out,
+                      "({}, {}) => ({}, {}, {:?}),",
+                      state.index.0,
+                      integer,
+                      next_state.0 as i32 + 1,
+                      -(self.custom.reduce_indices[production] as i32 + 1),
+                      production.nonterminal.to_string());
+            }
+        }
+        rust!(self.out, "_ => unreachable!(),");
+        rust!(self.out, "}}");
+        rust!(self.out, "}}");
+        Ok(())
+    }
+
+    /// Writes `__action2`, which gives the action to take in the
+    /// states and on the terminals marked `TableType::lookahead2` in
+    /// the action table, given the terminal after it (`None` for EOF).
+    fn emit_action2_fn(&mut self) -> io::Result<()> {
+        rust!(self.out,
+              "fn {}action2({}state: usize, {}integer: usize, {}next: Option<usize>) -> i32 {{",
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              self.prefix);
+        rust!(self.out,
+              "match ({}state, {}integer, {}next) {{",
+              self.prefix,
+              self.prefix,
+              self.prefix);
+        for state in self.states {
+            for (terminal, decisions) in &state.lookahead2 {
+                let integer =
+                    self.grammar.terminals.all.iter().position(|t| t == terminal).unwrap();
+                for &(ref after, action) in decisions {
+                    let patterns: Vec<_> =
+                        after.iter()
+                             .filter_map(|token| match token {
+                                 Token::Terminal(next) => {
+                                     let next = self.grammar
+                                                    .terminals
+                                                    .all
+                                                    .iter()
+                                                    .position(|&t| t == next)
+                                                    .unwrap();
+                                     Some(format!("Some({})", next))
+                                 }
+                                 Token::EOF => Some("None".to_string()),
+                                 Token::Error => None,
+                             })
+                             .map(|next| format!("({}, {}, {})", state.index.0, integer, next))
+                             .collect();
+                    if patterns.is_empty() {
+                        continue;
+                    }
+                    let value = match action {
+                        Action::Shift(_, next_state) => next_state.0 as i32 + 1,
+                        Action::Reduce(production) => {
+                            -(self.custom.reduce_indices[production] as i32 + 1)
+                        }
+                    };
+                    rust!(self.out, "{} => {},", patterns.join(" | "), value);
+                }
+            }
+        }
+        rust!(self.out, "_ => 0,");
+        rust!(self.out, "}}");
+        rust!(self.out, "}}");
+        Ok(())
+    }
+
+    fn write_reduction<'s>(custom: &TableDriven<'grammar>, state: &'s LR1State, token: Token) -> (i32, Comment<'s, Token>) {
+        let reduction = state.reductions
+                             .iter()
+                             .filter(|&&(ref t, _)| t.contains(token))
+                             .map(|&(_, p)| p)
+                             .next();
+        if let Some(production) = reduction {
+            let action = custom.reduce_indices[production];
+            (-(action as i32 + 1), Comment::Reduce(token, production))
+        } else {
+            // Otherwise, this is an error. Store 0.
+            (0, Comment::Error(token))
+        }
+    }
+
+    fn write_parser_fn(&mut self) -> io::Result<()> {
+        let phantom_data_expr = self.phantom_data_expr();
+
+        if self.custom.partial {
+            try!(self.start_partial_parser_fn());
+        } else if self.custom.incremental {
+            try!(self.start_incremental_parser_fn());
+        } else {
+            try!(self.start_parser_fn());
+            try!(self.define_tokens());
+        }
+
+        // State and data stack.
+        if self.custom.incremental {
+            rust!(self.out,
+                  "let ({}offset, mut {}states, mut {}symbols) = {}checkpoints.restore(input);",
                   self.prefix,
-                  self.action_module,
                   self.prefix,
-                  production.action.index(),
-                  Sep(", ", &self.grammar.non_lifetime_type_parameters()),
-                  self.grammar.user_parameter_refs(),
-                  Sep(", ", &args));
-            rust!(self.out, "Ok(v) => v,");
-            rust!(self.out, "Err(e) => return Some(Err(e)),");
-            rust!(self.out, "}};");
+                  self.prefix,
+                  self.prefix);
+            let generated_module = self.generated_module();
+            rust!(self.out,
+                  "let mut {}tokens = {}::{}intern_token::{}Matcher::resume(input, {}offset);",
+                  self.prefix,
+                  generated_module,
+                  self.prefix,
+                  self.prefix,
+                  self.prefix);
+        } else if let Some(capacity) = Tls::session().stack_capacity {
+            rust!(self.out,
+                  "let mut {}states = Vec::with_capacity({});",
+                  self.prefix,
+                  capacity + 1);
+            rust!(self.out, "{}states.push(0_i32);", self.prefix);
+            rust!(self.out,
+                  "let mut {}symbols = Vec::with_capacity({});",
+                  self.prefix,
+                  capacity);
+        } else {
+            rust!(self.out, "let mut {}states = vec![0_i32];", self.prefix);
+            rust!(self.out, "let mut {}symbols = vec![];", self.prefix);
+        }
+
+        rust!(self.out, "let mut {}integer;", self.prefix);
+        rust!(self.out, "let mut {}lookahead;", self.prefix);
+        if self.uses_lookahead2() {
+            // the token after `lookahead`, if we had to look at it
+            rust!(self.out, "let mut {}peeked = None;", self.prefix);
+        }
+        // The location of the last token is necessary for for error recovery at EOF (or they would not have
+        // a location)
+        if self.custom.incremental {
+            rust!(self.out, "let mut {}last_location = {}offset;", self.prefix, self.prefix);
         } else {
+            rust!(self.out, "let mut {}last_location = Default::default();", self.prefix);
+        }
+
+        // Outer loop: each time we continue around this loop, we
+        // shift a new token from the input. We break from the loop
+        // when the end of the input is reached (we return early if an
+        // error occurs).
+        rust!(self.out, "'{}shift: loop {{", self.prefix);
+        if DEBUG_PRINT {
+            rust!(self.out, "println!(\"outer loop\");");
+        }
+
+        // Read next token from input; defines `integer` and `symbol`.
+        if self.custom.incremental {
+            rust!(self.out, "let {}previous_end = {}last_location;", self.prefix, self.prefix);
+        }
+        try!(self.next_token());
+        if self.custom.incremental {
+            // The stacks so far only depend on the tokens before the
+            // lookahead, which cannot change if the input is edited
+            // after its start, provided there is some whitespace to
+            // keep it from running into the token before it.
             rust!(self.out,
-                  "let {}nt = {}::{}action{}::<{}>({}{});",
+                  "if {}lookahead.0 > {}previous_end && \
+                   {}lookahead.0 >= {}checkpoints.next_offset() {{",
                   self.prefix,
-                  self.action_module,
                   self.prefix,
-                  production.action.index(),
-                  Sep(", ", &self.grammar.non_lifetime_type_parameters()),
-                  self.grammar.user_parameter_refs(),
-                  Sep(", ", &args));
+                  self.prefix,
+                  self.prefix);
+            rust!(self.out,
+                  "{}checkpoints.record({}lookahead.0, &{}states, &{}symbols);",
+                  self.prefix,
+                  self.prefix,
+                  self.prefix,
+                  self.prefix);
+            rust!(self.out, "}}");
         }
+        try!(self.token_to_integer(true));
 
-        // if this is the final state, return it
-        if production.nonterminal == self.start_symbol {
-            rust!(self.out, "return Some(Ok({}nt));", self.prefix);
-            return Ok(());
+        // Loop.
+        rust!(self.out, "'{}inner: loop {{", self.prefix);
+        if DEBUG_PRINT {
+            rust!(self.out, "println!(\"inner loop\");");
         }
+        rust!(self.out,
+              "let {}state = *{}states.last().unwrap() as usize;",
+              self.prefix,
+              self.prefix);
 
-        // pop the consumed states from the stack
+        // Load the next action to take. A token that has no action of
+        // its own in this state is matched by `_`, if that has one.
+        if self.grammar.uses_wildcard() {
+            rust!(self.out,
+                  "let {}terminal = if {}ACTION[{}state * {} + {}integer] == 0 {{ {} }} \
+                   else {{ {}integer }};",
+                  self.prefix,
+                  self.prefix,
+                  self.prefix,
+                  self.grammar.terminals.all.len(),
+                  self.prefix,
+                  self.wildcard_integer(),
+                  self.prefix);
+        }
+        let terminal = self.terminal_variable();
+        rust!(self.out,
+              "let {}action = {}ACTION[{}state * {} + {}]{};",
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              self.grammar.terminals.all.len(),
+              terminal,
+              self.custom.table_type.widen());
+        if Tls::session().emit_introspection {
+            rust!(self.out,
+                  "{}introspect(&{}states, Some({}));",
+                  self.prefix,
+                  self.prefix,
+                  terminal);
+        }
+        if self.uses_lookahead2() {
+            try!(self.lookahead2_action());
+        }
+        if self.uses_conflict_hooks() {
+            try!(self.conflict_hook_action());
+        }
+
+        if DEBUG_PRINT {
+            rust!(self.out,
+                  "println!(\"state: {{}} lookahead: {{}} action: {{}} stack-depth: {{}}\", \
+                   {}state, {}, {}action, {}symbols.len());",
+                  self.prefix,
+                  terminal,
+                  self.prefix,
+                  self.prefix);
+        }
+
+        // Shift.
+        rust!(self.out, "if {}action > 0 {{", self.prefix);
+        if DEBUG_PRINT {
+            rust!(self.out, "println!(\"--> shift\");");
+        }
+        try!(self.stack_overflow_check());
+        try!(self.token_to_symbol());
+        rust!(self.out,
+              "{}states.push({}action - 1);",
+              self.prefix,
+              self.prefix);
+        try!(self.notify_observer(&format!("shift({}TERMINAL[{}integer], {}action - 1)",
+                                           self.prefix,
+                                           self.prefix,
+                                           self.prefix)));
+        rust!(self.out,
+              "{}symbols.push(({}lookahead.0, {}symbol, {}lookahead.2));",
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              self.prefix);
+        rust!(self.out, "continue '{}shift;", self.prefix);
+
+        // Reduce.
+        rust!(self.out, "}} else if {}action < 0 {{", self.prefix);
+        if DEBUG_PRINT {
+            rust!(self.out, "println!(\"--> reduce\");");
+        }
+        try!(self.notify_reduce());
+        rust!(self.out,
+              "if let Some(r) = {}reduce({}{}action, Some(&{}lookahead), &mut {}states, &mut \
+               {}symbols, {}) {{",
+              self.prefix,
+              self.grammar.user_parameter_refs(),
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              phantom_data_expr);
+        rust!(self.out, "return r;");
+        rust!(self.out, "}}");
+
+        // Error.
+        rust!(self.out, "}} else {{");
+
+        if self.grammar.uses_error_recovery {
+            let prefix = self.prefix;
+            try!(self.unrecognized_token_error(&format!("Some({}lookahead.clone())", prefix)));
+            try!(self.notify_error(&format!("Some({}TERMINAL[{}integer])", prefix, prefix)));
+            rust!(self.out, "let mut {}dropped_tokens = Vec::new();", self.prefix);
+            let lookahead = format!("Some(&{}lookahead)", self.prefix);
+            try!(self.error_recovery(&lookahead, ""));
+            rust!(self.out, "let {}start = {}lookahead.0.clone();", self.prefix, self.prefix);
+            rust!(self.out, "let {}end = {}lookahead.2.clone();", self.prefix, self.prefix);
+
+            if DEBUG_PRINT {
+                rust!(self.out, "println!(\"Attempting to recover on state: {{}}, error_state: {{}}, symbols: {{}}\", {}state, {}error_state - 1, {}symbols.len());",
+                    self.prefix,
+                    self.prefix,
+                    self.prefix);
+            }
+
+            // Loop which drops tokens until parsing can resume again
+            rust!(self.out, "loop {{");
+            rust!(self.out, "if {}ACTION[({}error_state as usize - 1) * {} + {}integer] != 0 {{",
+                self.prefix,
+                self.prefix,
+                self.grammar.terminals.all.len(),
+                self.prefix);
+            rust!(self.out, "let {}new_len = {}symbols.len() - ({}original_state_len - {}states.len());",
+                self.prefix,
+                self.prefix,
+                self.prefix,
+                self.prefix);
+            rust!(self.out, "{}symbols.truncate({}new_len);",
+                self.prefix,
+                self.prefix);
+            rust!(self.out, "{}states.push({}error_state - 1);",
+                self.prefix,
+                self.prefix);
+            rust!(self.out, "let {}recovery = {}lalrpop_util::ErrorRecovery {{",
+                self.prefix,
+                self.prefix);
+            rust!(self.out, "error: {}error,", self.prefix);
+            rust!(self.out, "dropped_tokens: {}dropped_tokens,", self.prefix);
+            rust!(self.out, "}};");
+            rust!(self.out,
+                "{}symbols.push(({}start, {}Symbol::Termerror({}recovery), {}end));",
+                self.prefix,
+                self.prefix,
+                self.prefix,
+                self.prefix,
+                self.prefix);
+
+            if DEBUG_PRINT {
+                rust!(self.out, "println!(\"Recovering on state: {{}}, lookahead: {{}}, symbols: {{}}\", {}error_state - 1, {}integer, {}symbols.len());",
+                    self.prefix,
+                    self.prefix,
+                    self.prefix);
+            }
+
+            rust!(self.out, "continue '{}inner;", self.prefix);
+            rust!(self.out, "}}");// if ACTION
+
+            rust!(self.out, "{}dropped_tokens.push({}lookahead);",
+                self.prefix,
+                self.prefix);
+            try!(self.next_token());
+            try!(self.token_to_integer(true));
+
+            if DEBUG_PRINT {
+                rust!(self.out, "println!(\"Skipping token: {{}}\", {}integer);",
+                    self.prefix);
+            }
+
+            rust!(self.out, "}}"); // loop
+        } else if self.custom.partial {
+            // stop here, leaving the token for the next item, and see
+            // whether what we have so far is complete
+            rust!(self.out, "*{}pending = Some(Ok({}lookahead));", self.prefix, self.prefix);
+            rust!(self.out, "break '{}shift;", self.prefix);
+        } else {
+            let prefix = self.prefix;
+            try!(self.unrecognized_token_error(&format!("Some({}lookahead)", prefix)));
+            try!(self.notify_error(&format!("Some({}TERMINAL[{}integer])", prefix, prefix)));
+            rust!(self.out, "return Err({}error)", self.prefix);
+        }
+
+        rust!(self.out, "}}"); // if-else-if-else
+
+        rust!(self.out, "}}"); // reduce loop
+
+        rust!(self.out, "}}"); // shift loop
+
+        // EOF loop
+        rust!(self.out, "loop {{");
+        rust!(self.out,
+              "let {}state = *{}states.last().unwrap() as usize;",
+              self.prefix,
+              self.prefix);
+        if DEBUG_PRINT {
+            rust!(self.out,
+                  "println!(\"EOF loop state: {{}}\", {}state);",
+                  self.prefix);
+        }
+        if Tls::session().emit_introspection {
+            rust!(self.out, "{}introspect(&{}states, None);", self.prefix, self.prefix);
+        }
+        rust!(self.out,
+              "let {}action = {}EOF_ACTION[{}state]{};",
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              self.custom.table_type.widen());
+        if DEBUG_PRINT {
+            rust!(self.out,
+                  "println!(\"EOF in state {{}} takes action {{}}\", {}state, {}action);",
+                  self.prefix,
+                  self.prefix);
+        }
+        rust!(self.out, "if {}action < 0 {{", self.prefix);
+        try!(self.notify_reduce());
+        rust!(self.out,
+              "if let Some(r) = {}reduce({}{}action, None, &mut {}states, &mut {}symbols, {}) {{",
+              self.prefix,
+              self.grammar.user_parameter_refs(),
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              phantom_data_expr);
+        rust!(self.out, "return r;");
+        rust!(self.out, "}}");
+        rust!(self.out, "}} else {{");
+
+        // EOF error recovery
+        if self.custom.partial {
+            // if the tokenizer failed, that is the more useful error
+            rust!(self.out,
+                  "let {}token = match {}pending.take() {{",
+                  self.prefix,
+                  self.prefix);
+            rust!(self.out, "Some(Ok(t)) => Some(t),");
+            rust!(self.out, "Some(Err(e)) => return Err(e),");
+            rust!(self.out, "None => None,");
+            rust!(self.out, "}};");
+            try!(self.notify_error(&format!("{}token.as_ref()\
+                                              .and_then(|t| {}token_to_integer(&t.1))\
+                                              .map(|i| {}TERMINAL[i])",
+                                             self.prefix,
+                                             self.prefix,
+                                             self.prefix)));
+            let token = format!("{}token", self.prefix);
+            try!(self.unrecognized_token_error(&token));
+        } else {
+            try!(self.unrecognized_token_error("None"));
+            try!(self.notify_error("None"));
+        }
+
+        if self.grammar.uses_error_recovery {
+            let extra_test = format!("&& {}EOF_ACTION[({}error_state as usize - 1)] != 0 ",
+                self.prefix,
+                self.prefix);
+            try!(self.error_recovery("None", &extra_test));
+            rust!(self.out, "let {}new_len = {}symbols.len() - ({}original_state_len - {}states.len());",
+                self.prefix,
+                self.prefix,
+                self.prefix,
+                self.prefix);
+            rust!(self.out, "{}symbols.truncate({}new_len);",
+                self.prefix,
+                self.prefix);
+            rust!(self.out, "{}states.push({}error_state - 1);",
+                self.prefix,
+                self.prefix);
+            rust!(self.out, "let {}recovery = {}lalrpop_util::ErrorRecovery {{",
+                self.prefix,
+                self.prefix);
+            rust!(self.out, "error: {}error,", self.prefix);
+            rust!(self.out, "dropped_tokens: Vec::new(),");
+            rust!(self.out, "}};");
+            rust!(self.out,
+                "{}symbols.push(({}last_location.clone(), {}Symbol::Termerror({}recovery), {}last_location.clone()));",
+                self.prefix,
+                self.prefix,
+                self.prefix,
+                self.prefix,
+                self.prefix);
+
+        } else {
+            rust!(self.out, "return Err({}error);", self.prefix)
+        }
+
+        rust!(self.out, "}}"); // else
+        
+        rust!(self.out, "}}"); // while let
+
+        self.end_parser_fn()
+    }
+
+    /// Replaces `action` with the action that the token after
+    /// `lookahead` selects, if the table says to look at it.
+    fn lookahead2_action(&mut self) -> io::Result<()> {
+        rust!(self.out,
+              "let {}action = if {}action == {} {{",
+              self.prefix,
+              self.prefix,
+              self.custom.table_type.lookahead2());
+        rust!(self.out, "if {}peeked.is_none() {{", self.prefix);
+        rust!(self.out, "{}peeked = Some({}tokens.next());", self.prefix, self.prefix);
+        rust!(self.out, "}}");
+
+        // a tokenizer error is reported right away, as it would be
+        // if we went on to read the token
+        rust!(self.out, "if let Some(Some(Err(_))) = {}peeked {{", self.prefix);
+        rust!(self.out, "match {}peeked.take() {{", self.prefix);
+        try!(self.tokenizer_error_arm());
+        rust!(self.out, "_ => unreachable!(),");
+        rust!(self.out, "}}");
+        rust!(self.out, "}}");
+
+        rust!(self.out, "let {}next = match {}peeked {{", self.prefix, self.prefix);
+        rust!(self.out,
+              "Some(Some(Ok(ref {}next))) => Some({}token_to_integer(&{}next.1).unwrap_or({})),",
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              self.grammar.terminals.all.len());
+        rust!(self.out, "_ => None,");
+        rust!(self.out, "}};");
+        rust!(self.out,
+              "{}action2({}state, {}, {}next)",
+              self.prefix,
+              self.prefix,
+              self.terminal_variable(),
+              self.prefix);
+        rust!(self.out, "}} else {{");
+        rust!(self.out, "{}action", self.prefix);
+        rust!(self.out, "}};");
+        Ok(())
+    }
+
+    /// Replaces `action` with the shift or the reduction, as the
+    /// grammar's first parameter says, if the table says to ask it.
+    fn conflict_hook_action(&mut self) -> io::Result<()> {
+        rust!(self.out,
+              "let {}action = if {}action == {} {{",
+              self.prefix,
+              self.prefix,
+              self.custom.table_type.conflict_hook());
+        rust!(self.out,
+              "let ({}shift, {}reduce, {}nonterminal) = {}conflict_hook({}state, {});",
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              self.terminal_variable());
+        rust!(self.out,
+              "if {}.should_shift({}nonterminal, &{}lookahead.1) {{",
+              self.grammar.parameters[0].name,
+              self.prefix,
+              self.prefix);
+        rust!(self.out, "{}shift", self.prefix);
+        rust!(self.out, "}} else {{");
+        rust!(self.out, "{}reduce", self.prefix);
+        rust!(self.out, "}}");
+        rust!(self.out, "}} else {{");
+        rust!(self.out, "{}action", self.prefix);
+        rust!(self.out, "}};");
+        Ok(())
+    }
+
+    /// Stops the parser if shifting the lookahead would take the
+    /// stacks past the limit, if the session sets one. Only shifts
+    /// can make the stacks much deeper: a reduce pops at least as
+    /// many states as it pushes, unless the production is empty.
+    fn stack_overflow_check(&mut self) -> io::Result<()> {
+        let session = Tls::session();
+        if let Some(limit) = session.stack_limit() {
+            rust!(self.out, "if {}symbols.len() >= {} {{", self.prefix, limit);
+            if session.stack_overflow == StackOverflow::Abort {
+                rust!(self.out, "::std::process::abort();");
+            } else {
+                rust!(self.out,
+                      "return Err({}lalrpop_util::ParseError::NestingTooDeep {{ \
+                       location: {}lookahead.0 }});",
+                      self.prefix,
+                      self.prefix);
+            }
+            rust!(self.out, "}}");
+        }
+        Ok(())
+    }
+
+    fn tokenizer_error_arm(&mut self) -> io::Result<()> {
+        if self.grammar.intern_token.is_some() {
+            // when we generate the tokenizer, the generated errors are `ParseError` values
+            rust!(self.out, "Some(Some(Err(e))) => return Err(e),");
+        } else {
+            // otherwise, they are user errors
+            rust!(self.out,
+                  "Some(Some(Err(e))) => \
+                   return Err({}lalrpop_util::ParseError::User {{ error: e }}),",
+                  self.prefix);
+        }
+        Ok(())
+    }
+
+    fn next_token(&mut self) -> io::Result<()> {
+        let mut next = if self.custom.partial {
+            format!("{}pending.take().or_else(|| {}tokens.next())", self.prefix, self.prefix)
+        } else {
+            format!("{}tokens.next()", self.prefix)
+        };
+        if self.uses_lookahead2() {
+            next = format!("{}peeked.take().unwrap_or_else(|| {})", self.prefix, next);
+        }
+        rust!(self.out, "{}lookahead = match {} {{", self.prefix, next);
+        rust!(self.out, "Some(Ok(v)) => v,");
+        rust!(self.out, "None => break '{}shift,", self.prefix); // EOF: break out
+        if self.custom.partial {
+            // stop, and report the error only if it is needed to
+            // complete what we have parsed so far
+            rust!(self.out, "Some(Err(e)) => {{");
+            rust!(self.out, "*{}pending = Some(Err(e));", self.prefix);
+            rust!(self.out, "break '{}shift;", self.prefix);
+            rust!(self.out, "}}");
+        } else if self.grammar.intern_token.is_some() {
+            // when we generate the tokenizer, the generated errors are `ParseError` values
+            rust!(self.out, "Some(Err(e)) => return Err(e),");
+        } else {
+            // otherwise, they are user errors
+            rust!(self.out,
+                  "Some(Err(e)) => return Err({}lalrpop_util::ParseError::User {{ error: e }}),",
+                  self.prefix);
+        }
+        rust!(self.out, "}};");
+        rust!(self.out, "{}last_location = {}lookahead.2.clone();",
+              self.prefix,
+              self.prefix);
+        Ok(())
+    }
+
+    /// Writes the conversion of the lookahead to the index of its
+    /// terminal, failing if it has none. The failure is only reported
+    /// to the observer if `observed`, which it is not in `complete_Foo`
+    /// and `parse_Foo_prefix`.
+    fn token_to_integer(&mut self, observed: bool) -> io::Result<()> {
+        rust!(self.out,
+              "{}integer = match {}token_to_integer(&{}lookahead.1) {{",
+              self.prefix,
+              self.prefix,
+              self.prefix);
+        rust!(self.out, "Some({}integer) => {}integer,", self.prefix, self.prefix);
+        rust!(self.out, "None => {{");
+        let prefix = self.prefix;
+        try!(self.unrecognized_token_error(&format!("Some({}lookahead)", prefix)));
+        if observed {
+            try!(self.notify_error("None"));
+        }
+        rust!(self.out, "return Err({}error);", self.prefix);
+        rust!(self.out, "}}");
+        rust!(self.out, "}};");
+        Ok(())
+    }
+
+    fn token_to_symbol(&mut self) -> io::Result<()> {
+        rust!(self.out,
+              "let {}symbol = match {} {{",
+              self.prefix,
+              self.terminal_variable());
+        for (&terminal, index) in self.grammar.terminals.all.iter().zip(0..) {
+            if terminal == TerminalString::Error {
+                continue;
+            }
+            rust!(self.out, "{} => match {}lookahead.1 {{", index, self.prefix);
+
+            let mut pattern_names = vec![];
+            let pattern = self.grammar.pattern(terminal).map(&mut |_| {
+                let index = pattern_names.len();
+                pattern_names.push(format!("{}tok{}", self.prefix, index));
+                pattern_names.last().cloned().unwrap()
+            });
+
+            let mut pattern = format!("{}", pattern);
+            if pattern_names.is_empty() {
+                pattern_names.push(format!("{}tok", self.prefix));
+                pattern = format!("{}tok @ {}", self.prefix, pattern);
+            }
+
+            let variant_name = self.variant_name_for_symbol(Symbol::Terminal(terminal));
+            rust!(self.out,
+                  "{} => {}Symbol::{}(({})),",
+                  pattern,
+                  self.prefix,
+                  variant_name,
+                  pattern_names.join(", "));
+            rust!(self.out, "_ => unreachable!(),");
+            rust!(self.out, "}},");
+        }
+
+        rust!(self.out, "_ => unreachable!(),");
+
+        rust!(self.out, "}};");
+        Ok(())
+    }
+
+    fn emit_reduce_actions(&mut self) -> io::Result<()> {
+        let success_type = self.types.nonterminal_type(self.start_symbol);
+        let parse_error_type = self.types.parse_error_type();
+        let parameters = self.reduce_parameters();
+
+        try!(self.inline_attribute());
+        try!(self.out.write_pub_fn_header(self.grammar,
+                                          format!("{}reduce", self.prefix),
+                                          vec![],
+                                          parameters.clone(),
+                                          format!("Option<Result<{},{}>>",
+                                                  success_type,
+                                                  parse_error_type),
+                                          vec![]));
+        rust!(self.out, "{{");
+
+        // rustc is slow to compile a single huge `match`, so in big
+        // grammars, the productions are reduced by several functions,
+        // each of which returns `Err` with what `__reduce` should.
+        let productions: Vec<_> = self.grammar
+                                      .nonterminals
+                                      .values()
+                                      .flat_map(|nt| &nt.productions)
+                                      .collect();
+        let per_fn = Tls::session().reductions_per_fn;
+        let split = productions.len() > per_fn;
+        if split {
+            rust!(self.out,
+                  "let {}nonterminal = match (-{}action - 1) as usize / {} {{",
+                  self.prefix,
+                  self.prefix,
+                  per_fn);
+            for index in 0..(productions.len() + per_fn - 1) / per_fn {
+                rust!(self.out,
+                      "{} => match {}reduce{}({}{}action, {}lookahead, {}states, {}symbols, \
+                       {}) {{",
+                      index,
+                      self.prefix,
+                      index,
+                      self.grammar.user_parameter_refs(),
+                      self.prefix,
+                      self.prefix,
+                      self.prefix,
+                      self.prefix,
+                      self.phantom_data_expr());
+                rust!(self.out, "Ok({}nonterminal) => {}nonterminal,", self.prefix, self.prefix);
+                rust!(self.out,
+                      "Err({}result) => return Some({}result),",
+                      self.prefix,
+                      self.prefix);
+                rust!(self.out, "}},");
+            }
+            rust!(self.out,
+                  "_ => panic!(\"invalid action code {{}}\", {}action)",
+                  self.prefix);
+            rust!(self.out, "}};");
+        } else {
+            try!(self.emit_reduce_match(&productions, 1, "Some"));
+        }
+
+        rust!(self.out,
+              "let {}state = *{}states.last().unwrap() as usize;",
+              self.prefix,
+              self.prefix);
+        rust!(self.out,
+              "let {}next_state = {}GOTO[{}state * {} + {}nonterminal]{} - 1;",
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              self.custom.all_nonterminals.len(),
+              self.prefix,
+              self.custom.table_type.widen());
+        if DEBUG_PRINT {
+            rust!(self.out,
+                  "println!(\"goto state {{}} from {{}} due to nonterminal {{}}\", {}next_state, \
+                   {}state, {}nonterminal);",
+                  self.prefix,
+                  self.prefix,
+                  self.prefix);
+        }
+        rust!(self.out,
+              "{}states.push({}next_state);",
+              self.prefix,
+              self.prefix);
+        rust!(self.out, "None");
+        rust!(self.out, "}}");
+
+        if split {
+            for (index, chunk) in productions.chunks(per_fn).enumerate() {
+                rust!(self.out, "");
+                try!(self.inline_attribute());
+                try!(self.out.write_fn_header(self.grammar,
+                                              format!("{}reduce{}", self.prefix, index),
+                                              vec![],
+                                              parameters.clone(),
+                                              format!("Result<usize, Result<{},{}>>",
+                                                      success_type,
+                                                      parse_error_type),
+                                              vec![]));
+                rust!(self.out, "{{");
+                try!(self.emit_reduce_match(chunk, index * per_fn + 1, "Err"));
+                rust!(self.out, "Ok({}nonterminal)", self.prefix);
+                rust!(self.out, "}}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes what `Configuration::optimize_for_size` and
+    /// `optimize_for_speed` say about inlining the functions that
+    /// carry out reductions.
+    fn inline_attribute(&mut self) -> io::Result<()> {
+        match Tls::session().optimize {
+            Optimize::Balanced => {}
+            Optimize::Size => rust!(self.out, "#[inline(never)]"),
+            Optimize::Speed => rust!(self.out, "#[inline]"),
+        }
+        Ok(())
+    }
+
+    fn reduce_parameters(&self) -> Vec<String> {
+        vec![format!("{}action: i32", self.prefix),
+             format!("{}lookahead: Option<&{}>", self.prefix, self.types.triple_type()),
+             format!("{}states: &mut ::std::vec::Vec<i32>", self.prefix),
+             format!("{}symbols: &mut ::std::vec::Vec<{}>",
+                     self.prefix,
+                     self.spanned_symbol_type()),
+             format!("_: {}", self.phantom_data_type())]
+    }
+
+    /// Writes the `match` that reduces one of `productions`, whose
+    /// action codes are numbered from `first`, and defines
+    /// `__nonterminal` as the index of the nonterminal it produced.
+    /// The parse ends with `return {ret}(result)`.
+    fn emit_reduce_match(&mut self,
+                         productions: &[&'grammar Production],
+                         first: usize,
+                         ret: &str)
+                         -> io::Result<()> {
+        rust!(self.out,
+              "let {}lookahead_start = {}lookahead.map(|l| &l.0);",
+              self.prefix,
+              self.prefix);
+        rust!(self.out,
+              "let {}nonterminal = match -{}action {{",
+              self.prefix,
+              self.prefix);
+        for (production, index) in productions.iter().zip(first..) {
+            if !self.custom.all_nonterminals.contains(&production.nonterminal) {
+                continue; // pruned: the `_` arm covers it
+            }
+            rust!(self.out, "{} => {{", index);
+            try!(self.emit_reduce_action(production, ret));
+            rust!(self.out, "}}");
+        }
+        rust!(self.out,
+              "_ => panic!(\"invalid action code {{}}\", {}action)",
+              self.prefix);
+        rust!(self.out, "}};");
+        Ok(())
+    }
+
+    fn emit_reduce_action(&mut self, production: &Production, ret: &str) -> io::Result<()> {
+        rust!(self.out, "// {:?}", production);
+
+        // Pop each of the symbols and their associated states.
+        for (index, &symbol) in production.symbols.iter().enumerate().rev() {
+            let name = self.variant_name_for_symbol(symbol);
+            rust!(self.out,
+                "let {}sym{} = {}pop_{}({}symbols);",
+                self.prefix,
+                index,
+                self.prefix,
+                name,
+                self.prefix);
+        }
+        let transfer_syms: Vec<_> = (0..production.symbols.len())
+                                        .map(|i| format!("{}sym{}", self.prefix, i))
+                                        .collect();
+
+        // Execute the action fn
+        // identify the "start" location for this production; this
+        // is typically the start of the first symbol we are
+        // reducing; but in the case of an empty production, it
+        // will be the last symbol pushed, or at worst `default`.
+        if let Some(first_sym) = transfer_syms.first() {
+            rust!(self.out,
+                  "let {}start = {}.0.clone();",
+                  self.prefix,
+                  first_sym);
+        } else {
+            // we pop no symbols, so grab from the top of the stack
+            // (unless we are in the start state, in which case the
+            // stack will be empty)
+            rust!(self.out,
+                  "let {}start = {}symbols.last().map(|s| s.2.clone()).unwrap_or_default();",
+                  self.prefix,
+                  self.prefix);
+        }
+
+        // identify the "end" location for this production;
+        // this is typically the end of the last symbol we are reducing,
+        // but in the case of an empty production it will come from the
+        // lookahead
+        if let Some(last_sym) = transfer_syms.last() {
+            rust!(self.out, "let {}end = {}.2.clone();", self.prefix, last_sym);
+        } else {
+            rust!(self.out,
+                  "let {}end = {}lookahead_start.cloned().unwrap_or_else(|| \
+                   {}start.clone());",
+                  self.prefix,
+                  self.prefix,
+                  self.prefix);
+        }
+
+        let transfered_syms = transfer_syms.len();
+
+        let mut args = transfer_syms;
+        if transfered_syms == 0 {
+            args.push(format!("&{}start", self.prefix));
+            args.push(format!("&{}end", self.prefix));
+        }
+        if self.grammar.action_takes_lookahead_token(production.action) {
+            args.push(format!("{}lookahead", self.prefix));
+        }
+
+        // invoke the action code
+        let is_fallible = self.grammar.action_is_fallible(production.action);
+        if is_fallible {
+            rust!(self.out,
+                  "let {}nt = match {}::{}action{}::<{}>({}{}) {{",
+                  self.prefix,
+                  self.action_module,
+                  self.prefix,
+                  production.action.index(),
+                  Sep(", ", &self.grammar.non_lifetime_type_parameters()),
+                  self.grammar.user_parameter_refs(),
+                  Sep(", ", &args));
+            rust!(self.out, "Ok(v) => v,");
+            rust!(self.out, "Err(e) => return {}(Err(e)),", ret);
+            rust!(self.out, "}};");
+        } else {
+            rust!(self.out,
+                  "let {}nt = {}::{}action{}::<{}>({}{});",
+                  self.prefix,
+                  self.action_module,
+                  self.prefix,
+                  production.action.index(),
+                  Sep(", ", &self.grammar.non_lifetime_type_parameters()),
+                  self.grammar.user_parameter_refs(),
+                  Sep(", ", &args));
+        }
+
+        // if this is the final state, return it
+        if production.nonterminal == self.start_symbol {
+            rust!(self.out, "return {}(Ok({}nt));", ret, self.prefix);
+            return Ok(());
+        }
+
+        // pop the consumed states from the stack
+        rust!(self.out,
+              "let {}states_len = {}states.len();",
+              self.prefix,
+              self.prefix);
+        rust!(self.out,
+              "{}states.truncate({}states_len - {});",
+              self.prefix,
+              self.prefix,
+              production.symbols.len());
+
+        // push the produced value on the stack
+        let name = self.variant_name_for_symbol(Symbol::Nonterminal(production.nonterminal));
+        rust!(self.out,
+              "{}symbols.push(({}start, {}Symbol::{}({}nt), {}end));",
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              name,
+              self.prefix,
+              self.prefix);
+
+        // produce the index that we will use to extract the next state
+        // from GOTO array
+        let index = self.custom
+                        .all_nonterminals
+                        .iter()
+                        .position(|&x| x == production.nonterminal)
+                        .unwrap();
+        rust!(self.out, "{}", index);
+
+        Ok(())
+    }
+
+    fn variant_name_for_symbol(&mut self, s: Symbol) -> String {
+        match s {
+            Symbol::Nonterminal(nt) => format!("Nt{}", Escape(nt)),
+            Symbol::Terminal(t) => format!("Term{}", Escape(t)),
+        }
+    }
+
+    fn emit_downcast_fns(&mut self) -> io::Result<()> {
+        // with `prune_tables`, skip the symbols that no remaining
+        // reduction pops, which would otherwise be dead code
+        let popped: Set<Symbol> = {
+            let all_nonterminals = &self.custom.all_nonterminals;
+            self.grammar
+                .nonterminals
+                .values()
+                .flat_map(|nt| &nt.productions)
+                .filter(|p| all_nonterminals.contains(&p.nonterminal))
+                .flat_map(|p| p.symbols.iter().cloned())
+                .collect()
+        };
+        let pruned = Tls::session().prune_tables;
+
+        for &term in &self.grammar.terminals.all {
+            if pruned && !popped.contains(&Symbol::Terminal(term)) {
+                continue;
+            }
+            let name = self.variant_name_for_symbol(Symbol::Terminal(term));
+            let ty = self.types.terminal_type(term).clone();
+            try!(self.emit_downcast_fn(&name, ty));
+        }
+
+        for nt in self.custom.all_nonterminals.clone() {
+            let name = self.variant_name_for_symbol(Symbol::Nonterminal(nt));
+            let ty = self.types.nonterminal_type(nt).clone();
+            try!(self.emit_downcast_fn(&name, ty));
+        }
+
+        Ok(())
+    }
+
+    fn emit_downcast_fn(&mut self, variant_name: &str, variant_ty: TypeRepr) -> io::Result<()> {
+        let spanned_symbol_type = self.spanned_symbol_type();
+
+        try!(self.inline_attribute());
+        rust!(self.out, "fn {}pop_{}<", self.prefix, variant_name);
+        for type_parameter in &self.custom.symbol_type_params {
+            rust!(self.out, "  {},", type_parameter);
+        }
+        rust!(self.out, ">(");
+        rust!(self.out,
+              "{}symbols: &mut ::std::vec::Vec<{}>",
+              self.prefix,
+              spanned_symbol_type);
+        rust!(self.out, ") -> {} {{", self.types.spanned_type(variant_ty));
+
+        if DEBUG_PRINT {
+            rust!(self.out, "println!(\"pop_{}\");", variant_name);
+        }
+        rust!(self.out, "match {}symbols.pop().unwrap() {{", self.prefix);
+        rust!(self.out,
+              "({}l, {}Symbol::{}({}v), {}r) => ({}l, {}v, {}r),",
+              self.prefix,
+              self.prefix,
+              variant_name,
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              self.prefix);
+        rust!(self.out, "_ => panic!(\"symbol type mismatch\")");
+        rust!(self.out, "}}");
+
+        rust!(self.out, "}}");
+
+        Ok(())
+    }
+
+    fn error_recovery(&mut self, lookahead: &str, extra_test: &str) -> io::Result<()> {
+        let phantom_data_expr = self.phantom_data_expr();
+
+        // First perform all reductions from the current state
+        rust!(self.out, "loop {{");
+        rust!(self.out, "let {}state = *{}states.last().unwrap() as usize;",
+              self.prefix,
+              self.prefix);
+
+        if DEBUG_PRINT {
+            rust!(self.out, r#"println!("Error recovery in state: {{}}", {}state);"#,
+                  self.prefix);
+        }
+
+        // Access the action with `error` as the lookahead; it is always final
+        // column in the row for this state
+        rust!(self.out, "let {}action = {}ACTION[({}state + 1) * {} - 1]{};",
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              self.grammar.terminals.all.len(),
+              self.custom.table_type.widen());
+        rust!(self.out, "if {}action >= 0 {{", self.prefix);
+        rust!(self.out, "break;");
+        rust!(self.out, "}}");
+
+        if DEBUG_PRINT {
+            rust!(self.out, r#"println!("Error recovery reduces on action: {{}}", {}action);"#,
+                  self.prefix);
+        }
+
+        rust!(self.out,
+              "if let Some(r) = {}reduce({}{}action, {}, &mut {}states, &mut {}symbols, {}) {{",
+              self.prefix,
+              self.grammar.user_parameter_refs(),
+              self.prefix,
+              lookahead,
+              self.prefix,
+              self.prefix,
+              phantom_data_expr);
+        rust!(self.out, "return r;");
+        rust!(self.out, "}}");
+        rust!(self.out, "}}");
+
+        rust!(self.out, "let {}original_state_len = {}states.len();",
+            self.prefix,
+            self.prefix);
+
+        // Used after breaking out of the loop
+        rust!(self.out, "let mut {}error_state;", self.prefix);
+        // Loop which pops states until a state that can be recovered from is found
+        rust!(self.out, "loop {{");
+        rust!(self.out, "match {}states.last().cloned() {{", self.prefix);
+        rust!(self.out, "Some({}state) => {{", self.prefix);
+        rust!(self.out, "{}error_state = {}ACTION[({}state as usize + 1) * {} - 1]{};",
+            self.prefix,
+            self.prefix,
+            self.prefix,
+            self.grammar.terminals.all.len(),
+            self.custom.table_type.widen());
+
+        rust!(self.out, "if {}error_state > 0 {} {{", self.prefix, extra_test);
+        rust!(self.out, "break;");
+        rust!(self.out, "}}");
+        rust!(self.out, "{}states.pop();", self.prefix);
+        rust!(self.out, "}}"); // Some
+        rust!(self.out, "None => {{");
+        rust!(self.out, "return Err({}error);", self.prefix);
+        rust!(self.out, "}}");
+        rust!(self.out, "}}"); // match
+
+        if DEBUG_PRINT {
+            rust!(self.out, "println!(\"Dropping state: {{}}\", {}state);",
+                self.prefix);
+        }
+
+        rust!(self.out, "}}"); // loop
+
+        Ok(())
+    }
+
+    fn symbol_type(&self) -> String {
+        format!("{}Symbol<{}>",
+                self.prefix,
+                Sep(", ", &self.custom.symbol_type_params))
+    }
+
+    fn spanned_symbol_type(&self) -> String {
+        let loc_type = self.types.terminal_loc_type();
+        format!("({},{},{})", loc_type, self.symbol_type(), loc_type)
+    }
+
+    fn unrecognized_token_error(&mut self, token: &str) -> io::Result<()> {
+        rust!(self.out, "let {}state = *{}states.last().unwrap() as usize;",
+            self.prefix,
+            self.prefix);
+        rust!(self.out,
+            "let {}error = {}lalrpop_util::ParseError::UnrecognizedToken {{",
+            self.prefix,
+            self.prefix);
+        rust!(self.out, "token: {},", token);
+        rust!(self.out, "expected: {}expected_tokens(&{}states),",
+            self.prefix,
+            self.prefix);
+        rust!(self.out, "}};");
+        Ok(())
+    }
+
+    /// Writes `observer.{call}` for the observer installed with
+    /// `__set_observer`, if the parsers are observed at all.
+    fn notify_observer(&mut self, call: &str) -> io::Result<()> {
+        if Tls::session().emit_observer {
+            rust!(self.out, "{}notify(|o| o.{});", self.prefix, call);
+        }
+        Ok(())
+    }
+
+    /// Tells the observer which production `action` reduces.
+    fn notify_reduce(&mut self) -> io::Result<()> {
+        let call = format!("reduce({}PRODUCTIONS[(-{}action - 1) as usize])",
+                           self.prefix,
+                           self.prefix);
+        self.notify_observer(&call)
+    }
+
+    /// Tells the observer about an error on `terminal`, an expression
+    /// of type `Option<&'static str>`, along with the terminals that
+    /// the top state of `__states` would have accepted.
+    fn notify_error(&mut self, terminal: &str) -> io::Result<()> {
+        let call = format!("error({}, &{}expected_tokens(&{}states))",
+                           terminal,
+                           self.prefix,
+                           self.prefix);
+        self.notify_observer(&call)
+    }
+
+    /// Writes `__simulate`, which tells whether the parser, with the
+    /// given stack of states, would accept the terminal with the given
+    /// index (or EOF, for `None`): it carries out the reductions that
+    /// the terminal leads to on the stack, recording them in
+    /// `reductions`, until the terminal is to be shifted or turns out
+    /// to be an error. Unlike the action table on its own, this does
+    /// not count terminals that are only reduced on in the top state
+    /// (as with `LALR(1)`), but are an error in the state reached by
+    /// the reduction. If `wildcard` is true, a terminal that has no
+    /// action in a state falls back to `_` there, as in the parser.
+    fn emit_simulate_fn(&mut self) -> io::Result<()> {
+        rust!(self.out,
+              "fn {}simulate({}states: &mut Vec<i32>, {}integer: Option<usize>, \
+               {}wildcard: bool, {}reductions: &mut Vec<usize>) -> bool {{",
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              self.prefix);
+        rust!(self.out, "loop {{");
+        rust!(self.out,
+              "let {}state = *{}states.last().unwrap() as usize;",
+              self.prefix,
+              self.prefix);
+        rust!(self.out, "let {}action = match {}integer {{", self.prefix, self.prefix);
+        if self.grammar.uses_wildcard() {
+            rust!(self.out,
+                  "Some({}integer) if {}wildcard && {}ACTION[{}state * {} + {}integer] == 0 =>",
+                  self.prefix,
+                  self.prefix,
+                  self.prefix,
+                  self.prefix,
+                  self.grammar.terminals.all.len(),
+                  self.prefix);
+            rust!(self.out,
+                  "{}ACTION[{}state * {} + {}]{},",
+                  self.prefix,
+                  self.prefix,
+                  self.grammar.terminals.all.len(),
+                  self.wildcard_integer(),
+                  self.custom.table_type.widen());
+        }
+        rust!(self.out,
+              "Some({}integer) => {}ACTION[{}state * {} + {}integer]{},",
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              self.grammar.terminals.all.len(),
+              self.prefix,
+              self.custom.table_type.widen());
+        rust!(self.out,
+              "None => {}EOF_ACTION[{}state]{},",
+              self.prefix,
+              self.prefix,
+              self.custom.table_type.widen());
+        rust!(self.out, "}};");
+        rust!(self.out, "if {}action == 0 {{", self.prefix);
+        rust!(self.out, "return false;");
+        rust!(self.out, "}}");
+        // which way the second token or the conflict hook decides, one
+        // of them accepts the terminal
+        rust!(self.out,
+              "if {}action > 0 || {}action == {} || {}action == {} {{",
+              self.prefix,
+              self.prefix,
+              self.custom.table_type.lookahead2(),
+              self.prefix,
+              self.custom.table_type.conflict_hook());
+        rust!(self.out, "return true;");
+        rust!(self.out, "}}");
+        rust!(self.out,
+              "{}reductions.push((-{}action - 1) as usize);",
+              self.prefix,
+              self.prefix);
+        rust!(self.out,
+              "let ({}pop, {}nonterminal) = {}REDUCE[(-{}action - 1) as usize];",
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              self.prefix);
+        let start_index = self.custom
+                              .all_nonterminals
+                              .iter()
+                              .position(|&x| x == self.start_symbol)
+                              .unwrap();
+        rust!(self.out, "if {}nonterminal == {} {{", self.prefix, start_index);
+        rust!(self.out, "return true;");
+        rust!(self.out, "}}");
+        rust!(self.out, "let {}states_len = {}states.len();", self.prefix, self.prefix);
+        rust!(self.out,
+              "{}states.truncate({}states_len - {}pop);",
+              self.prefix,
+              self.prefix,
+              self.prefix);
+        rust!(self.out,
+              "let {}state = *{}states.last().unwrap() as usize;",
+              self.prefix,
+              self.prefix);
+        rust!(self.out,
+              "{}states.push({}GOTO[{}state * {} + {}nonterminal]{} - 1);",
+              self.prefix,
+              self.prefix,
+              self.prefix,
+              self.custom.all_nonterminals.len(),
+              self.prefix,
+              self.custom.table_type.widen());
+        rust!(self.out, "}}");
+        rust!(self.out, "}}");
+        Ok(())
+    }
+
+    /// Writes `__expected_tokens`, which gives the terminals that the
+    /// parser would accept with the given stack of states, such as the
+    /// one where it failed. The `error` terminal is never included, and
+    /// nor are the terminals that only `_` would accept.
+    fn emit_expected_tokens_fn(&mut self) -> io::Result<()> {
+        rust!(self.out,
+              "pub fn {}expected_tokens({}states: &[i32]) -> Vec<::std::string::String> {{",
+              self.prefix,
+              self.prefix);
+        rust!(self.out,
+              "{}TERMINAL.iter().enumerate().filter_map(|({}integer, {}terminal)| {{",
+              self.prefix,
+              self.prefix,
+              self.prefix);
+        if self.grammar.uses_error_recovery {
+            let error_integer = self.grammar
+                                    .terminals
+                                    .all
+                                    .iter()
+                                    .position(|&t| t == TerminalString::Error)
+                                    .unwrap();
+            rust!(self.out, "if {}integer == {} {{", self.prefix, error_integer);
+            rust!(self.out, "return None;");
+            rust!(self.out, "}}");
+        }
+        rust!(self.out,
+              "if {}simulate(&mut {}states.to_vec(), Some({}integer), false, &mut vec![]) {{",
+              self.prefix,
+              self.prefix,
+              self.prefix);
+        rust!(self.out, "Some({}terminal.to_string())", self.prefix);
+        rust!(self.out, "}} else {{");
+        rust!(self.out, "None");
+        rust!(self.out, "}}");
+        rust!(self.out, "}}).collect()");
+        rust!(self.out, "}}");
+        Ok(())
+    }
+
+    /// Writes `__expected_nonterminals`, which gives the nonterminals
+    /// that the parser, with the given stack of states, is ready to
+    /// begin, once it has made the reductions for one of the terminals
+    /// it accepts. Only the nonterminals named in the grammar are
+    /// included, not those that LALRPOP makes up, e.g. for macros.
+    fn emit_expected_nonterminals_fn(&mut self) -> io::Result<()> {
+        rust!(self.out,
+              "static {}NONTERMINAL: &'static [(usize, &'static str)] = &[",
+              self.prefix);
+        for (index, &nonterminal) in self.custom.all_nonterminals.iter().enumerate() {
+            let name = nonterminal.to_string();
+            if !name.starts_with(self.prefix) &&
+                name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                rust!(self.out, "({}, \"{}\"),", index, name);
+            }
+        }
+        rust!(self.out, "];");
+
+        rust!(self.out,
+              "fn {}expected_nonterminals({}states: &[i32]) -> Vec<::std::string::String> {{",
+              self.prefix,
+              self.prefix);
+        rust!(self.out, "let mut {}nonterminals = vec![];", self.prefix);
+        rust!(self.out,
+              "for {}integer in 0..{} {{",
+              self.prefix,
+              self.grammar.terminals.all.len());
+        if self.grammar.uses_error_recovery {
+            let error_integer = self.grammar
+                                    .terminals
+                                    .all
+                                    .iter()
+                                    .position(|&t| t == TerminalString::Error)
+                                    .unwrap();
+            rust!(self.out, "if {}integer == {} {{", self.prefix, error_integer);
+            rust!(self.out, "continue;");
+            rust!(self.out, "}}");
+        }
+        rust!(self.out, "let mut {}next = {}states.to_vec();", self.prefix, self.prefix);
         rust!(self.out,
-              "let {}states_len = {}states.len();",
+              "if {}simulate(&mut {}next, Some({}integer), false, &mut vec![]) {{",
+              self.prefix,
               self.prefix,
               self.prefix);
         rust!(self.out,
-              "{}states.truncate({}states_len - {});",
+              "let {}state = *{}next.last().unwrap() as usize;",
               self.prefix,
-              self.prefix,
-              production.symbols.len());
-
-        // push the produced value on the stack
-        let name = self.variant_name_for_symbol(Symbol::Nonterminal(production.nonterminal));
+              self.prefix);
         rust!(self.out,
-              "{}symbols.push(({}start, {}Symbol::{}({}nt), {}end));",
+              "for &({}column, {}name) in {}NONTERMINAL {{",
               self.prefix,
               self.prefix,
+              self.prefix);
+        rust!(self.out,
+              "if {}GOTO[{}state * {} + {}column] != 0 {{",
               self.prefix,
-              name,
               self.prefix,
+              self.custom.all_nonterminals.len(),
               self.prefix);
-
-        // produce the index that we will use to extract the next state
-        // from GOTO array
-        let index = self.custom
-                        .all_nonterminals
-                        .iter()
-                        .position(|&x| x == production.nonterminal)
-                        .unwrap();
-        rust!(self.out, "{}", index);
-
+        rust!(self.out, "{}nonterminals.push({}name.to_string());", self.prefix, self.prefix);
+        rust!(self.out, "}}");
+        rust!(self.out, "}}");
+        rust!(self.out, "}}");
+        rust!(self.out, "}}");
+        rust!(self.out, "{}nonterminals.sort();", self.prefix);
+        rust!(self.out, "{}nonterminals.dedup();", self.prefix);
+        rust!(self.out, "{}nonterminals", self.prefix);
+        rust!(self.out, "}}");
         Ok(())
     }
 
-    fn variant_name_for_symbol(&mut self, s: Symbol) -> String {
-        match s {
-            Symbol::Nonterminal(nt) => format!("Nt{}", Escape(nt)),
-            Symbol::Terminal(t) => format!("Term{}", Escape(t)),
-        }
-    }
-
-    fn emit_downcast_fns(&mut self) -> io::Result<()> {
-        for &term in &self.grammar.terminals.all {
-            let name = self.variant_name_for_symbol(Symbol::Terminal(term));
-            let ty = self.types.terminal_type(term).clone();
-            try!(self.emit_downcast_fn(&name, ty));
+    /// Writes `complete_Foo`, which takes the same input as
+    /// `parse_Foo`, but only the part of it up to the point where code
+    /// is to be completed, and says what could come next there. It
+    /// only runs through the tables, without building any values, so
+    /// none of the action code is run.
+    fn write_completion_fn(&mut self) -> io::Result<()> {
+        let name = format!("complete_{}", self.user_start_symbol);
+        let return_type = format!("Result<{}lalrpop_util::Completion, {}>",
+                                  self.prefix,
+                                  self.types.parse_error_type());
+        try!(self.start_fn_over_input(name, return_type));
+        try!(self.define_tokens());
+        rust!(self.out, "let mut {}states = vec![0_i32];", self.prefix);
+        rust!(self.out, "let mut {}integer;", self.prefix);
+        rust!(self.out, "loop {{");
+        rust!(self.out, "let {}lookahead = match {}tokens.next() {{", self.prefix, self.prefix);
+        rust!(self.out, "Some(Ok(v)) => v,");
+        rust!(self.out, "None => break,");
+        if self.grammar.intern_token.is_some() {
+            rust!(self.out, "Some(Err(e)) => return Err(e),");
+        } else {
+            rust!(self.out,
+                  "Some(Err(e)) => return Err({}lalrpop_util::ParseError::User {{ error: e }}),",
+                  self.prefix);
         }
+        rust!(self.out, "}};");
+        try!(self.token_to_integer(false));
+        rust!(self.out,
+              "if !{}simulate(&mut {}states, Some({}integer), true, &mut vec![]) {{",
+              self.prefix,
+              self.prefix,
+              self.prefix);
+        let prefix = self.prefix;
+        try!(self.unrecognized_token_error(&format!("Some({}lookahead)", prefix)));
+        rust!(self.out, "return Err({}error);", self.prefix);
+        rust!(self.out, "}}");
 
-        for &nt in self.grammar.nonterminals.keys() {
-            let name = self.variant_name_for_symbol(Symbol::Nonterminal(nt));
-            let ty = self.types.nonterminal_type(nt).clone();
-            try!(self.emit_downcast_fn(&name, ty));
-        }
+        try!(self.shift_simulated_token());
+        rust!(self.out, "}}");
 
+        rust!(self.out, "Ok({}lalrpop_util::Completion {{", self.prefix);
+        rust!(self.out, "terminals: {}expected_tokens(&{}states),", self.prefix, self.prefix);
+        rust!(self.out,
+              "nonterminals: {}expected_nonterminals(&{}states),",
+              self.prefix,
+              self.prefix);
+        rust!(self.out,
+              "can_end: {}simulate(&mut {}states.clone(), None, true, &mut vec![]),",
+              self.prefix,
+              self.prefix);
+        rust!(self.out, "}})");
+        rust!(self.out, "}}");
         Ok(())
     }
 
-    fn emit_downcast_fn(&mut self, variant_name: &str, variant_ty: TypeRepr) -> io::Result<()> {
-        let spanned_symbol_type = self.spanned_symbol_type();
-
-        rust!(self.out, "fn {}pop_{}<", self.prefix, variant_name);
-        for type_parameter in &self.custom.symbol_type_params {
-            rust!(self.out, "  {},", type_parameter);
-        }
-        rust!(self.out, ">(");
+    /// Writes the shift of `__integer` onto `__states`, after
+    /// `__simulate` has carried out the reductions before it (falling
+    /// back to `_` as the parser does).
+    fn shift_simulated_token(&mut self) -> io::Result<()> {
         rust!(self.out,
-              "{}symbols: &mut ::std::vec::Vec<{}>",
+              "let {}state = *{}states.last().unwrap() as usize;",
               self.prefix,
-              spanned_symbol_type);
-        rust!(self.out, ") -> {} {{", self.types.spanned_type(variant_ty));
-
-        if DEBUG_PRINT {
-            rust!(self.out, "println!(\"pop_{}\");", variant_name);
+              self.prefix);
+        if self.grammar.uses_wildcard() {
+            rust!(self.out,
+                  "if {}ACTION[{}state * {} + {}integer] == 0 {{",
+                  self.prefix,
+                  self.prefix,
+                  self.grammar.terminals.all.len(),
+                  self.prefix);
+            rust!(self.out, "{}integer = {};", self.prefix, self.wildcard_integer());
+            rust!(self.out, "}}");
         }
-        rust!(self.out, "match {}symbols.pop().unwrap() {{", self.prefix);
         rust!(self.out,
-              "({}l, {}Symbol::{}({}v), {}r) => ({}l, {}v, {}r),",
-              self.prefix,
-              self.prefix,
-              variant_name,
+              "{}states.push({}ACTION[{}state * {} + {}integer]{} - 1);",
               self.prefix,
               self.prefix,
               self.prefix,
+              self.grammar.terminals.all.len(),
               self.prefix,
-              self.prefix);
-        rust!(self.out, "_ => panic!(\"symbol type mismatch\")");
-        rust!(self.out, "}}");
-
-        rust!(self.out, "}}");
+              self.custom.table_type.widen());
+        Ok(())
+    }
 
+    /// Writes `__PRODUCTIONS`, the text of each production, in the
+    /// order of `__reduce`.
+    fn emit_productions_table(&mut self) -> io::Result<()> {
+        rust!(self.out,
+              "static {}PRODUCTIONS: &'static [&'static str] = &[",
+              self.prefix);
+        for production in self.grammar.nonterminals.values().flat_map(|nt| &nt.productions) {
+            rust!(self.out,
+                  "r###\"{} ={}\"###,",
+                  production.nonterminal,
+                  Prefix(" ", &production.symbols[..]));
+        }
+        rust!(self.out, "];");
         Ok(())
     }
 
-    fn error_recovery(&mut self, lookahead_start: &str, extra_test: &str) -> io::Result<()> {
-        let phantom_data_expr = self.phantom_data_expr();
+    /// Writes what lets a callback installed with `__observe` watch
+    /// the parser (see `Configuration::emit_introspection`): at each
+    /// step, `__introspect` hands it an `__Introspection` with the
+    /// stack of states and the lookahead, which can also give the
+    /// items of the top state and the reductions pending before the
+    /// lookahead is shifted.
+    fn emit_introspection(&mut self) -> io::Result<()> {
+        rust!(self.out,
+              "static {}ITEMS: &'static [&'static [&'static str]] = &[",
+              self.prefix);
+        for (index, state) in self.states.iter().enumerate() {
+            rust!(self.out, "// State {}", index);
+            rust!(self.out, "&[");
+            for item in state.items.vec.iter() {
+                rust!(self.out, "r###\"{:?}\"###,", item);
+            }
+            rust!(self.out, "],");
+        }
+        rust!(self.out, "];");
 
-        // First perform all reductions from the current state
-        rust!(self.out, "loop {{");
-        rust!(self.out, "let {}state = *{}states.last().unwrap() as usize;",
-              self.prefix,
+        rust!(self.out, "pub struct {}Introspection<'s> {{", self.prefix);
+        rust!(self.out, "states: &'s [i32],");
+        rust!(self.out, "integer: Option<usize>,");
+        rust!(self.out, "}}");
+        rust!(self.out, "impl<'s> {}Introspection<'s> {{", self.prefix);
+        rust!(self.out, "pub fn states(&self) -> &'s [i32] {{");
+        rust!(self.out, "self.states");
+        rust!(self.out, "}}");
+        rust!(self.out, "pub fn items(&self) -> &'static [&'static str] {{");
+        rust!(self.out,
+              "{}ITEMS[*self.states.last().unwrap() as usize]",
               self.prefix);
+        rust!(self.out, "}}");
+        rust!(self.out, "pub fn lookahead(&self) -> Option<&'static str> {{");
+        rust!(self.out, "self.integer.map(|i| {}TERMINAL[i])", self.prefix);
+        rust!(self.out, "}}");
+        rust!(self.out, "pub fn pending_reductions(&self) -> Vec<&'static str> {{");
+        rust!(self.out, "let mut reductions = vec![];");
+        rust!(self.out,
+              "{}simulate(&mut self.states.to_vec(), self.integer, true, &mut reductions);",
+              self.prefix);
+        rust!(self.out,
+              "reductions.into_iter().map(|r| {}PRODUCTIONS[r]).collect()",
+              self.prefix);
+        rust!(self.out, "}}");
+        rust!(self.out, "}}");
 
-        if DEBUG_PRINT {
-            rust!(self.out, r#"println!("Error recovery in state: {{}}", {}state);"#,
-                  self.prefix);
-        }
+        rust!(self.out, "thread_local! {{");
+        rust!(self.out,
+              "static {}OBSERVER: ::std::cell::RefCell<Option<Box<FnMut(&{}Introspection)>>> =",
+              self.prefix,
+              self.prefix);
+        rust!(self.out, "::std::cell::RefCell::new(None)");
+        rust!(self.out, "}}");
 
-        // Access the action with `error` as the lookahead; it is always final
-        // column in the row for this state
-        rust!(self.out, "let {}action = {}ACTION[({}state + 1) * {} - 1];",
+        rust!(self.out,
+              "pub fn {}observe({}observer: Option<Box<FnMut(&{}Introspection)>>) {{",
               self.prefix,
               self.prefix,
+              self.prefix);
+        rust!(self.out,
+              "{}OBSERVER.with(|o| *o.borrow_mut() = {}observer);",
               self.prefix,
-              self.grammar.terminals.all.len());
-        rust!(self.out, "if {}action >= 0 {{", self.prefix);
-        rust!(self.out, "break;");
+              self.prefix);
         rust!(self.out, "}}");
 
-        if DEBUG_PRINT {
-            rust!(self.out, r#"println!("Error recovery reduces on action: {{}}", {}action);"#,
-                  self.prefix);
-        }
-
         rust!(self.out,
-              "if let Some(r) = {}reduce({}{}action, {}, &mut {}states, &mut {}symbols, {}) {{",
+              "fn {}introspect({}states: &[i32], {}integer: Option<usize>) {{",
               self.prefix,
-              self.grammar.user_parameter_refs(),
               self.prefix,
-              lookahead_start,
+              self.prefix);
+        rust!(self.out, "{}OBSERVER.with(|o| {{", self.prefix);
+        rust!(self.out, "if let Some(ref mut observer) = *o.borrow_mut() {{");
+        rust!(self.out,
+              "observer(&{}Introspection {{ states: {}states, integer: {}integer }});",
               self.prefix,
               self.prefix,
-              phantom_data_expr);
-        rust!(self.out, "return r;");
-        rust!(self.out, "}}");
-        rust!(self.out, "}}");
-
-        rust!(self.out, "let {}original_state_len = {}states.len();",
-            self.prefix,
-            self.prefix);
-
-        // Used after breaking out of the loop
-        rust!(self.out, "let mut {}error_state;", self.prefix);
-        // Loop which pops states until a state that can be recovered from is found
-        rust!(self.out, "loop {{");
-        rust!(self.out, "match {}states.last().cloned() {{", self.prefix);
-        rust!(self.out, "Some({}state) => {{", self.prefix);
-        rust!(self.out, "{}error_state = {}ACTION[({}state as usize + 1) * {} - 1];",
-            self.prefix,
-            self.prefix,
-            self.prefix,
-            self.grammar.terminals.all.len());
-
-        rust!(self.out, "if {}error_state > 0 {} {{", self.prefix, extra_test);
-        rust!(self.out, "break;");
+              self.prefix);
         rust!(self.out, "}}");
-        rust!(self.out, "{}states.pop();", self.prefix);
-        rust!(self.out, "}}"); // Some
-        rust!(self.out, "None => {{");
-        rust!(self.out, "return Err({}error);", self.prefix);
+        rust!(self.out, "}});");
         rust!(self.out, "}}");
-        rust!(self.out, "}}"); // match
-
-        if DEBUG_PRINT {
-            rust!(self.out, "println!(\"Dropping state: {{}}\", {}state);",
-                self.prefix);
-        }
-
-        rust!(self.out, "}}"); // loop
-
         Ok(())
     }
 
-    fn symbol_type(&self) -> String {
-        format!("{}Symbol<{}>",
-                self.prefix,
-                Sep(", ", &self.custom.symbol_type_params))
-    }
-
-    fn spanned_symbol_type(&self) -> String {
-        let loc_type = self.types.terminal_loc_type();
-        format!("({},{},{})", loc_type, self.symbol_type(), loc_type)
-    }
-
-    fn unrecognized_token_error(&mut self, token: &str) -> io::Result<()> {
-        rust!(self.out, "let {}state = *{}states.last().unwrap() as usize;",
-            self.prefix,
-            self.prefix);
+    /// Writes the `__Observer` trait, whose methods the parser calls
+    /// on each shift, reduction and error, and `__set_observer`, which
+    /// installs one for the current thread (see
+    /// `Configuration::emit_observer`). They are given only what is
+    /// `'static`: the names of the terminals and the productions, and
+    /// the states.
+    fn emit_observer(&mut self) -> io::Result<()> {
+        rust!(self.out, "#[allow(unused_variables)]");
+        rust!(self.out, "pub trait {}Observer {{", self.prefix);
+        rust!(self.out, "/// `terminal` was shifted, going to `state`.");
+        rust!(self.out, "fn shift(&mut self, terminal: &'static str, state: i32) {{ }}");
+        rust!(self.out, "/// `production` is about to be reduced.");
+        rust!(self.out, "fn reduce(&mut self, production: &'static str) {{ }}");
         rust!(self.out,
-            "let {}error = {}lalrpop_util::ParseError::UnrecognizedToken {{",
-            self.prefix,
-            self.prefix);
-        rust!(self.out, "token: {},", token);
-        rust!(self.out, "expected: {}expected_tokens({}state),",
-            self.prefix,
-            self.prefix);
-        rust!(self.out, "}};");
-        Ok(())
-    }
+              "/// `lookahead` was not expected, or the input ended if it is `None`: a \
+               token that");
+        rust!(self.out, "/// is not a terminal at all is `None` too.");
+        rust!(self.out,
+              "fn error(&mut self, lookahead: Option<&'static str>, \
+               expected: &[::std::string::String]) {{ }}");
+        rust!(self.out, "}}");
 
-    fn emit_expected_tokens_fn(&mut self) -> io::Result<()> {
-        rust!(self.out, "fn {}expected_tokens({}state: usize) -> Vec<::std::string::String> {{",
-            self.prefix,
-            self.prefix);
+        rust!(self.out, "thread_local! {{");
+        rust!(self.out,
+              "static {}PARSE_OBSERVER: ::std::cell::RefCell<Option<Box<{}Observer>>> =",
+              self.prefix,
+              self.prefix);
+        rust!(self.out, "::std::cell::RefCell::new(None)");
+        rust!(self.out, "}}");
 
-        rust!(self.out, "const {}TERMINAL: &'static [&'static str] = &[", self.prefix);
-        let all_terminals = if self.grammar.uses_error_recovery {
-            // Subtract one to exlude the error terminal
-            &self.grammar.terminals.all[..self.grammar.terminals.all.len() - 1]
-        } else {
-            &self.grammar.terminals.all
-        };
-        for &terminal in all_terminals {
-            // Three # should hopefully be enough to prevent any
-            // reasonable terminal from escaping the literal
-            rust!(self.out, "r###\"{}\"###,", terminal);
-        }
-        rust!(self.out, "];");
+        rust!(self.out,
+              "pub fn {}set_observer({}observer: Option<Box<{}Observer>>) {{",
+              self.prefix,
+              self.prefix,
+              self.prefix);
+        rust!(self.out,
+              "{}PARSE_OBSERVER.with(|o| *o.borrow_mut() = {}observer);",
+              self.prefix,
+              self.prefix);
+        rust!(self.out, "}}");
 
-        // Grab any terminals in the current state which would have resulted in a successful parse
-        rust!(self.out, "{}ACTION[({}state * {})..].iter().zip({}TERMINAL).filter_map(|(&state, terminal)| {{",
-            self.prefix,
-            self.prefix,
-            self.grammar.terminals.all.len(),
-            self.prefix);
-        rust!(self.out, "if state == 0 {{");
-        rust!(self.out, "None");
-        rust!(self.out, "}} else {{");
-        rust!(self.out, "Some(terminal.to_string())");
+        rust!(self.out,
+              "fn {}notify<F: FnOnce(&mut {}Observer)>({}f: F) {{",
+              self.prefix,
+              self.prefix,
+              self.prefix);
+        rust!(self.out, "{}PARSE_OBSERVER.with(|o| {{", self.prefix);
+        rust!(self.out, "if let Some(ref mut observer) = *o.borrow_mut() {{");
+        rust!(self.out, "{}f(&mut **observer);", self.prefix);
         rust!(self.out, "}}");
-        rust!(self.out, "}}).collect()");
+        rust!(self.out, "}});");
         rust!(self.out, "}}");
         Ok(())
     }
@@ -6,22 +6,27 @@ use grammar::repr::{Grammar, NonterminalString, TypeParameter};
 use lr1::core::*;
 use rust::RustWrite;
 use std::io::{self, Write};
+use tls::Tls;
 use util::Sep;
 
 use super::base::CodeGenerator;
+use super::parse_table::TableFiles;
 
 pub fn compile<'grammar, W: Write>(grammar: &'grammar Grammar,
                                    user_start_symbol: NonterminalString,
                                    start_symbol: NonterminalString,
                                    states: &[LR1State<'grammar>],
+                                   action_module: &str,
+                                   tables: &mut TableFiles,
                                    out: &mut RustWrite<W>)
                                    -> io::Result<()> {
     let mut ascent = CodeGenerator::new_test_all(grammar,
                                                  user_start_symbol,
                                                  start_symbol,
                                                  states,
+                                                 action_module,
                                                  out);
-    ascent.write()
+    ascent.write(tables)
 }
 
 struct TestAll;
@@ -31,6 +36,7 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TestAll> {
                     user_start_symbol: NonterminalString,
                     start_symbol: NonterminalString,
                     states: &'ascent [LR1State<'grammar>],
+                    action_module: &str,
                     out: &'ascent mut RustWrite<W>)
                     -> Self {
         CodeGenerator::new(grammar,
@@ -39,11 +45,13 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TestAll> {
                            states,
                            out,
                            true,
-                           "super",
+                           action_module,
                            TestAll)
     }
 
-    fn write(&mut self) -> io::Result<()> {
+    fn write(&mut self, tables: &mut TableFiles) -> io::Result<()> {
+        // the two parsers are two modules further down
+        let action_module = format!("super::super::{}", self.action_module);
         self.write_parse_mod(|this| {
             try!(this.write_parser_fn());
 
@@ -52,7 +60,7 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TestAll> {
                                         this.user_start_symbol,
                                         this.start_symbol,
                                         this.states,
-                                        "super::super::super",
+                                        &action_module,
                                         this.out));
             rust!(this.out,
                   "pub use self::{}parse{}::parse_{};",
@@ -66,15 +74,85 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TestAll> {
                                              this.user_start_symbol,
                                              this.start_symbol,
                                              this.states,
-                                             "super::super::super",
+                                             &action_module,
+                                             tables,
                                              this.out));
             rust!(this.out,
                   "pub use self::{}parse{}::parse_{};",
                   this.prefix,
                   this.start_symbol,
                   this.user_start_symbol);
+            if this.grammar.algorithm.prefix {
+                rust!(this.out,
+                      "pub use self::{}parse{}::parse_{}_prefix;",
+                      this.prefix,
+                      this.start_symbol,
+                      this.user_start_symbol);
+            }
+            if this.grammar.generates_completion_fns() {
+                rust!(this.out,
+                      "pub use self::{}parse{}::complete_{};",
+                      this.prefix,
+                      this.start_symbol,
+                      this.user_start_symbol);
+            }
+            rust!(this.out,
+                  "pub use self::{}parse{}::{}expected_tokens;",
+                  this.prefix,
+                  this.start_symbol,
+                  this.prefix);
+            if Tls::session().emit_introspection {
+                rust!(this.out,
+                      "pub use self::{}parse{}::{{{}observe, {}Introspection}};",
+                      this.prefix,
+                      this.start_symbol,
+                      this.prefix,
+                      this.prefix);
+            }
+            if Tls::session().emit_observer {
+                rust!(this.out,
+                      "pub use self::{}parse{}::{{{}Observer, {}set_observer}};",
+                      this.prefix,
+                      this.start_symbol,
+                      this.prefix,
+                      this.prefix);
+            }
             rust!(this.out, "}}");
 
+            // only the table-driven parser can parse a prefix, tell
+            // what could come next in the input, or which tokens a
+            // stack of its states accepts
+            if this.grammar.algorithm.prefix {
+                rust!(this.out,
+                      "pub use self::{}parse_table::parse_{}_prefix;",
+                      this.prefix,
+                      this.user_start_symbol);
+            }
+            if this.grammar.generates_completion_fns() {
+                rust!(this.out,
+                      "pub use self::{}parse_table::complete_{};",
+                      this.prefix,
+                      this.user_start_symbol);
+            }
+            rust!(this.out,
+                  "pub use self::{}parse_table::{}expected_tokens;",
+                  this.prefix,
+                  this.prefix);
+            if Tls::session().emit_introspection {
+                rust!(this.out,
+                      "pub use self::{}parse_table::{{{}observe, {}Introspection}};",
+                      this.prefix,
+                      this.prefix,
+                      this.prefix);
+            }
+            if Tls::session().emit_observer {
+                rust!(this.out,
+                      "pub use self::{}parse_table::{{{}Observer, {}set_observer}};",
+                      this.prefix,
+                      this.prefix,
+                      this.prefix);
+            }
+
             Ok(())
         })
     }
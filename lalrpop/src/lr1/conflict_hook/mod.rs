@@ -0,0 +1,74 @@
+//! Leaving shift/reduce conflicts to be decided at parse time, for
+//! the productions of nonterminals marked `#[conflict_hook]`. The
+//! classic example is C, where `T * x;` declares a pointer if `T`
+//! names a type and multiplies otherwise, which only the symbol table
+//! built up while parsing can tell.
+//!
+//! A conflict is left to the hook only if there is nothing else to
+//! decide: the state has one shift and exactly one reduction on the
+//! terminal, and that reduction is of a marked nonterminal.
+
+use collections::{set, Set};
+use grammar::repr::*;
+use lr1::core::*;
+use lr1::lookahead::{Token, TokenSet};
+
+#[cfg(test)]
+mod test;
+
+pub fn resolve_conflicts<'grammar>(grammar: &'grammar Grammar,
+                                   error: LR1TableConstructionError<'grammar>)
+                                   -> LR1Result<'grammar> {
+    let LR1TableConstructionError { mut states, conflicts } = error;
+
+    let mut resolved: Set<(StateIndex, Token)> = set();
+    for conflict in &conflicts {
+        let target = match conflict.action {
+            Action::Shift(_, target) => target,
+            Action::Reduce(_) => continue,
+        };
+        if !grammar.has_conflict_hook(conflict.production.nonterminal) {
+            continue;
+        }
+
+        let state = &mut states[conflict.state.0];
+        for token in conflict.lookahead.iter() {
+            let reductions = state.reductions
+                                  .iter()
+                                  .filter(|&&(ref lookahead, _)| lookahead.contains(token))
+                                  .count();
+            if reductions == 1 {
+                state.conflict_hooks
+                     .insert(token.unwrap_terminal(), (target, conflict.production));
+                resolved.insert((conflict.state, token));
+            }
+        }
+    }
+
+    let conflicts: Vec<_> =
+        conflicts.into_iter()
+                 .filter_map(|mut conflict| {
+                     let mut lookahead = TokenSet::new();
+                     for token in conflict.lookahead.iter() {
+                         if !resolved.contains(&(conflict.state, token)) {
+                             lookahead.insert(token);
+                         }
+                     }
+                     if lookahead.len() == 0 {
+                         None
+                     } else {
+                         conflict.lookahead = lookahead;
+                         Some(conflict)
+                     }
+                 })
+                 .collect();
+
+    if conflicts.is_empty() {
+        Ok(states)
+    } else {
+        Err(TableConstructionError {
+            states: states,
+            conflicts: conflicts,
+        })
+    }
+}
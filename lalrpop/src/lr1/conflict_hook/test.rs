@@ -0,0 +1,48 @@
+use intern::intern;
+use grammar::repr::*;
+use lr1::build_states;
+use lr1::tls::Lr1Tls;
+use test_util::normalized_grammar;
+use tls::Tls;
+
+fn nt(t: &str) -> NonterminalString {
+    NonterminalString(intern(t))
+}
+
+fn grammar(annotation: &str) -> Grammar {
+    // `x * y;` declares `y` if `x` names a type, and is an expression
+    // otherwise
+    normalized_grammar(&format!(r#"
+        grammar(types: &Types);
+        extern {{ enum Tok {{ "id" => .., "*" => .., ";" => .. }} }}
+        Stmt: () = {{
+            Type "*" "id" ";"   => (),
+            "id" "*" "id" ";"   => ()
+        }};
+        {}
+        Type: () = "id"         => ();
+    "#, annotation))
+}
+
+#[test]
+fn without_hook() {
+    let _tls = Tls::test();
+    let grammar = grammar("");
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    assert!(build_states(&grammar, nt("Stmt")).is_err());
+}
+
+#[test]
+fn with_hook() {
+    let _tls = Tls::test();
+    let grammar = grammar("#[conflict_hook]");
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let states = build_states(&grammar, nt("Stmt")).unwrap();
+
+    let hooked: Vec<_> = states.iter().filter(|state| !state.conflict_hooks.is_empty()).collect();
+    assert_eq!(hooked.len(), 1);
+    let star = TerminalString::quoted(intern("*"));
+    let (next_state, production) = hooked[0].conflict_hooks[&star];
+    assert_eq!(hooked[0].shifts[&star], next_state);
+    assert_eq!(production.nonterminal, nt("Type"));
+}
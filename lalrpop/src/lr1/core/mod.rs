@@ -125,6 +125,18 @@ pub struct State<'grammar, L: Lookahead> {
     pub shifts: Map<TerminalString, StateIndex>,
     pub reductions: Vec<(L, &'grammar Production)>,
     pub gotos: Map<NonterminalString, StateIndex>,
+
+    /// Terminals on which the actions above conflict, but where the
+    /// token after the terminal decides between them: each action is
+    /// taken if that token is in its set. Only ever filled in for
+    /// grammars marked `#[lookahead2]`.
+    pub lookahead2: Map<TerminalString, Vec<(TokenSet, Action<'grammar>)>>,
+
+    /// Terminals on which the state can either shift to the given
+    /// state or reduce the given production, where the grammar's
+    /// conflict hook decides between them at parse time. Only ever
+    /// filled in for productions of `#[conflict_hook]` nonterminals.
+    pub conflict_hooks: Map<TerminalString, (StateIndex, &'grammar Production)>,
 }
 
 pub type LR0State<'grammar> = State<'grammar, Nil>;
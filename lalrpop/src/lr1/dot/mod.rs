@@ -0,0 +1,67 @@
+//! The LR automaton as a Graphviz `.dot` file, for looking at the
+//! whole state machine while debugging conflicts. Each state is a box
+//! listing its items and reductions; shifts are solid edges labeled
+//! with the terminal, and gotos dashed ones labeled with the
+//! nonterminal. States with conflicts are drawn in red.
+
+use collections::{set, Set};
+use grammar::repr::*;
+use lr1::core::*;
+use std::io::{self, Write};
+use util::{DotEscape, Prefix};
+
+#[cfg(test)]
+mod test;
+
+pub fn generate_dot<'grammar, W: Write>(out: &mut W,
+                                        start: NonterminalString,
+                                        lr1result: &LR1Result<'grammar>)
+                                        -> io::Result<()> {
+    let (states, conflicted): (_, Set<StateIndex>) = match *lr1result {
+        Ok(ref states) => (states, set()),
+        Err(ref error) => (&error.states, error.conflicts.iter().map(|c| c.state).collect()),
+    };
+
+    try!(writeln!(out, "digraph \"{}\" {{", DotEscape(start)));
+    try!(writeln!(out, "    node [shape=box, fontname=\"monospace\"];"));
+    for state in states {
+        let mut label = format!("{:?}\n\n", state.index);
+        for item in state.items.vec.iter() {
+            label.push_str(&format!("{:?}\n", item));
+        }
+        if !state.reductions.is_empty() {
+            label.push('\n');
+        }
+        for &(ref lookahead, production) in &state.reductions {
+            label.push_str(&format!("reduce {} ={} on {:?}\n",
+                                    production.nonterminal,
+                                    Prefix(" ", &production.symbols[..]),
+                                    lookahead));
+        }
+        if conflicted.contains(&state.index) {
+            try!(writeln!(out,
+                          "    {:?} [label=\"{}\", color=red];",
+                          state.index,
+                          DotEscape(label)));
+        } else {
+            try!(writeln!(out, "    {:?} [label=\"{}\"];", state.index, DotEscape(label)));
+        }
+    }
+    for state in states {
+        for (terminal, &target) in &state.shifts {
+            try!(writeln!(out,
+                          "    {:?} -> {:?} [label=\"{}\"];",
+                          state.index,
+                          target,
+                          DotEscape(terminal)));
+        }
+        for (nonterminal, &target) in &state.gotos {
+            try!(writeln!(out,
+                          "    {:?} -> {:?} [label=\"{}\", style=dashed];",
+                          state.index,
+                          target,
+                          DotEscape(nonterminal)));
+        }
+    }
+    writeln!(out, "}}")
+}
@@ -0,0 +1,49 @@
+use intern::intern;
+use grammar::repr::*;
+use lr1::build_states;
+use lr1::tls::Lr1Tls;
+use test_util::normalized_grammar;
+use tls::Tls;
+
+use super::generate_dot;
+
+fn dot(grammar: &str) -> String {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(grammar);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let start = NonterminalString(intern("Ty"));
+    let lr1result = build_states(&grammar, start);
+    let mut out = vec![];
+    generate_dot(&mut out, start, &lr1result).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn dot_without_conflict() {
+    let dot = dot(r#"
+grammar;
+pub Ty: () = {
+    "int" => (),
+    "(" Ty ")" => (),
+};
+"#);
+    assert!(dot.starts_with("digraph \"Ty\" {\n"), "{}", dot);
+    assert!(dot.ends_with("}\n"));
+    assert!(dot.contains("    S0 [label=\"S0\\l\\l"), "{}", dot);
+    assert!(dot.contains("reduce Ty = \\\"int\\\" on ["), "{}", dot);
+    assert!(dot.contains(" [label=\"\\\"(\\\"\"];"), "{}", dot);
+    assert!(dot.contains(" [label=\"Ty\", style=dashed];"), "{}", dot);
+    assert!(!dot.contains("color=red"));
+}
+
+#[test]
+fn dot_with_conflict() {
+    let dot = dot(r#"
+grammar;
+pub Ty: () = {
+    "int" => (),
+    <t1:Ty> "->" <t2:Ty> => (),
+};
+"#);
+    assert!(dot.contains(", color=red];"), "{}", dot);
+}
@@ -6,12 +6,16 @@ use lr1::core::*;
 use lr1::example::{Example, ExampleStyles, ExampleSymbol};
 use lr1::first::FirstSets;
 use lr1::lookahead::{Token, TokenSet};
+use lr1::sentence::{sentence_text, sentence_to, terminal_text};
 use itertools::Itertools;
 use grammar::repr::*;
-use message::{Message};
-use message::builder::{Builder, BodyCharacter, Character, MessageBuilder};
+use message::{Content, Message};
+use message::catalog;
+use message::builder::{Builder, BodyCharacter, Character, InlineBuilder, MessageBuilder};
 use tls::Tls;
 
+use std::cmp::Ordering;
+
 #[cfg(test)] mod test;
 
 pub fn report_error(grammar: &Grammar,
@@ -22,6 +26,54 @@ pub fn report_error(grammar: &Grammar,
     cx.report_errors()
 }
 
+/// Warnings for the shift/reduce conflicts in `error`, for a grammar
+/// marked `#[prefer_shift]` that resolves them by shifting.
+pub fn report_shift_warnings(grammar: &Grammar,
+                             error: &LR1TableConstructionError)
+                             -> Vec<Message>
+{
+    let shift_reduce: Vec<_> = error.conflicts
+                                    .iter()
+                                    .filter(|conflict| match conflict.action {
+                                        Action::Shift(..) => true,
+                                        Action::Reduce(_) => false,
+                                    })
+                                    .cloned()
+                                    .collect();
+    let mut cx = ErrorReportingCx::new(grammar, &error.states, &shift_reduce);
+    cx.report_shift_warnings()
+}
+
+/// SVG pictures of the examples of each conflict in `error` that has
+/// them: first the example of the shift (or of the other reduction),
+/// then that of the reduction.
+pub fn conflict_svgs(grammar: &Grammar,
+                     error: &LR1TableConstructionError)
+                     -> Vec<(String, String)>
+{
+    conflict_pictures(grammar, error)
+        .into_iter()
+        .filter_map(|(_, svgs)| svgs)
+        .collect()
+}
+
+/// Each conflict in `error`, taking one lookahead at a time, with the
+/// SVG pictures of its examples if it has them (see `conflict_svgs`).
+pub fn conflict_pictures<'grammar>(grammar: &'grammar Grammar,
+                                   error: &LR1TableConstructionError<'grammar>)
+                                   -> Vec<(TokenConflict<'grammar>, Option<(String, String)>)>
+{
+    let mut cx = ErrorReportingCx::new(grammar, &error.states, &error.conflicts);
+    token_conflicts(&error.conflicts)
+        .into_iter()
+        .map(|conflict| {
+            let svgs = examples(cx.classify(&conflict))
+                .map(|(action, reduce)| (action.to_svg(), reduce.to_svg()));
+            (conflict, svgs)
+        })
+        .collect()
+}
+
 struct ErrorReportingCx<'cx, 'grammar: 'cx> {
     grammar: &'grammar Grammar,
     first_sets: FirstSets,
@@ -57,11 +109,12 @@ enum ConflictClassification {
     /// Can't say much beyond that a conflict occurred.
     InsufficientLookahead { action: Example, reduce: Example },
 
-    /// Really can't say *ANYTHING*.
-    Naive,
+    /// Only one of the two ways, if either, has an example, so we
+    /// show that one and just name the other.
+    Naive { action: Option<Example>, reduce: Option<Example> },
 }
 
-type TokenConflict<'grammar> = Conflict<'grammar, Token>;
+pub type TokenConflict<'grammar> = Conflict<'grammar, Token>;
 
 impl<'cx, 'grammar> ErrorReportingCx<'cx, 'grammar> {
     fn new(grammar: &'grammar Grammar,
@@ -76,13 +129,159 @@ impl<'cx, 'grammar> ErrorReportingCx<'cx, 'grammar> {
         }
     }
 
+    /// Reports the conflicts, grouped by the production whose
+    /// reduction is at the root of their backtraces: only the first
+    /// conflict of each group is reported in full, with a note
+    /// listing the others, which would only repeat it.
     fn report_errors(&mut self) -> Vec<Message> {
+        let slr = self.grammar.algorithm.construction == Some(LrConstruction::Slr);
+        let conflicts = token_conflicts(self.conflicts);
+        let mut groups: Vec<Vec<&TokenConflict<'grammar>>> = vec![];
+        for conflict in &conflicts {
+            let index = groups.iter().position(|g| g[0].production == conflict.production);
+            match index {
+                Some(index) => groups[index].push(conflict),
+                None => groups.push(vec![conflict]),
+            }
+        }
+
+        groups
+            .iter()
+            .map(|group| {
+                let mut lookaheads: Vec<Token> = group.iter().map(|c| c.lookahead).collect();
+                lookaheads.sort();
+                lookaheads.dedup();
+                let mut message = self.report_error(group[0])
+                                      .append_heading(lookahead_heading(&lookaheads));
+                if let Some(note) = self.sentence_note(group[0]) {
+                    message = message.append(note);
+                }
+                if group.len() > 1 {
+                    message = message.append(self.cluster_note(group));
+                }
+                if slr {
+                    message.append(slr_note())
+                } else {
+                    message
+                }
+            })
+            .collect()
+    }
+
+    /// A note with a short input that runs into `conflict`, if one can
+    /// be found.
+    fn sentence_note(&self, conflict: &TokenConflict<'grammar>) -> Option<Box<Content>> {
+        let sentence = match sentence_to(self.grammar, self.states, conflict.state,
+                                         conflict.lookahead) {
+            Some(sentence) => sentence,
+            None => return None,
+        };
+        let builder = InlineBuilder::new()
+            .begin_wrap()
+            .text("For example,");
+        let builder = if sentence.is_empty() {
+            builder.text("at the start of the input,")
+        } else {
+            builder.text("after the input")
+                   .text(sentence_text(&sentence))
+                   .verbatimed()
+                   .punctuated(",")
+        };
+        let builder = builder.text("the parser cannot decide what to do");
+        let builder = match conflict.lookahead {
+            Token::Terminal(terminal) => {
+                builder.text("if the next token is")
+                       .text(terminal_text(terminal))
+                       .verbatimed()
+                       .punctuated(".")
+            }
+            Token::EOF => builder.text("at the end of the input."),
+            Token::Error => return None,
+        };
+        Some(builder.end().end())
+    }
+
+    /// A note saying that all the conflicts of `group` stem from the
+    /// same alternative, and that only the first is shown.
+    fn cluster_note(&self, group: &[&TokenConflict<'grammar>]) -> Box<Content> {
+        let production = group[0].production;
+        let alternative = self.grammar
+                              .productions_for(production.nonterminal)
+                              .iter()
+                              .position(|p| p == production)
+                              .unwrap();
+        InlineBuilder::new()
+            .begin_wrap()
+            .text("Note:")
+            .styled(Tls::session().hint_text)
+            .text(group.len())
+            .text("conflicts all stem from rule")
+            .text(production.nonterminal)
+            .verbatimed()
+            .text("alternative")
+            .text(alternative + 1)
+            .punctuated(";")
+            .text("only the first is shown in full.")
+            .end()
+            .end()
+    }
+
+    fn report_shift_warnings(&mut self) -> Vec<Message> {
         token_conflicts(self.conflicts)
             .iter()
-            .map(|conflict| self.report_error(conflict))
+            .map(|conflict| {
+                self.report_shift_warning(conflict)
+                    .append_heading(lookahead_heading(&[conflict.lookahead]))
+            })
             .collect()
     }
 
+    fn report_shift_warning(&mut self, conflict: &TokenConflict<'grammar>) -> Message {
+        let styles = ExampleStyles::ambig();
+        let examples = examples(self.classify(conflict));
+        let builder =
+            MessageBuilder::new(conflict.production.span)
+            .warning()
+            .code(catalog::SHIFT_PREFERRED.code)
+            .label("not reduced when the next token can be shifted")
+            .heading()
+            .text(catalog::SHIFT_PREFERRED.format(&[]))
+            .end()
+            .body()
+
+            .begin_wrap()
+            .text("If the next token is a")
+            .push(conflict.lookahead.unwrap_terminal())
+            .verbatimed()
+            .styled(Tls::session().cursor_symbol)
+            .punctuated(",")
+            .text("the parser shifts it rather than executing the production at")
+            .push(conflict.production.span)
+            .punctuated(",")
+            .text("since the grammar is marked")
+            .text("#[prefer_shift]")
+            .verbatimed()
+            .punctuated(".")
+            .end();
+
+        let builder = match examples {
+            Some((shift, reduce)) => {
+                builder
+                    .begin_lines()
+                    .wrap_text("This yields parse trees like")
+                    .push(shift.into_picture(styles))
+                    .end()
+                    .begin_lines()
+                    .wrap_text("rather than")
+                    .push(reduce.into_picture(styles))
+                    .end()
+            }
+            None => builder,
+        };
+
+        builder.end().end()
+    }
+
     fn report_error(&mut self, conflict: &TokenConflict<'grammar>) -> Message {
         match self.classify(conflict) {
             ConflictClassification::Ambiguity { action, reduce } => {
@@ -105,8 +304,8 @@ impl<'cx, 'grammar> ErrorReportingCx<'cx, 'grammar> {
             ConflictClassification::InsufficientLookahead { action, reduce } => {
                 self.report_error_insufficient_lookahead(conflict, action, reduce)
             }
-            ConflictClassification::Naive => {
-                self.report_error_naive(conflict)
+            ConflictClassification::Naive { action, reduce } => {
+                self.report_error_naive(conflict, action, reduce)
             }
         }
     }
@@ -118,8 +317,10 @@ impl<'cx, 'grammar> ErrorReportingCx<'cx, 'grammar> {
                                    -> Builder<BodyCharacter> {
         let styles = ExampleStyles::ambig();
         MessageBuilder::new(conflict.production.span)
+            .code(catalog::AMBIGUOUS_GRAMMAR.code)
+            .label("can be reduced in more than one way")
             .heading()
-            .text("Ambiguous grammar detected")
+            .text(catalog::AMBIGUOUS_GRAMMAR.format(&[]))
             .end()
             .body()
 
@@ -166,22 +367,111 @@ impl<'cx, 'grammar> ErrorReportingCx<'cx, 'grammar> {
             .push(nonterminal)
             .verbatimed()
             .punctuated(".")
-            .text("See the LALRPOP manual for advice on encoding precedence.")
+            .text(self.suggest_precedence_intro(conflict))
             .end()
+            .push(self.suggest_precedence(conflict)
+                      .into_iter()
+                      .fold(InlineBuilder::new().begin_lines(), |builder, line| builder.text(line))
+                      .end()
+                      .end())
+            .indented()
+            .wrap_text("See the LALRPOP manual for advice on encoding precedence.")
             .end()
             .end()
     }
 
+    /// The two operators in a precedence conflict: the one in the
+    /// production being reduced, and the one that could be shifted.
+    fn precedence_operators(&self,
+                            conflict: &TokenConflict<'grammar>)
+                            -> (TerminalString, TerminalString) {
+        match conflict.action {
+            Action::Shift(term, _) => (binary_operator(conflict.production).unwrap(), term),
+            Action::Reduce(_) => panic!("precedence conflict without a shift"),
+        }
+    }
+
+    fn suggest_precedence_intro(&self, conflict: &TokenConflict<'grammar>) -> String {
+        let (reduce_op, shift_op) = self.precedence_operators(conflict);
+        if reduce_op == shift_op {
+            format!("Declaring the associativity of `{}` would resolve it, like so \
+                     (or with `right` if `{}` should group to the right, or `nonassoc` \
+                     if chaining it should be a parse error):",
+                    reduce_op,
+                    reduce_op)
+        } else {
+            format!("Putting `{}` and `{}` at different levels would resolve it, like so \
+                     (levels go from loosest to tightest, so swap them if `{}` should \
+                     bind more tightly):",
+                    reduce_op,
+                    shift_op,
+                    reduce_op)
+        }
+    }
+
+    /// An `operators` declaration that would resolve a precedence
+    /// conflict. The operand is the only other alternative of the
+    /// nonterminal, if that is a nonterminal, and otherwise a `Term`
+    /// that the user would have to introduce. If there are two
+    /// operators, the one that comes first in the grammar is taken to
+    /// bind more loosely.
+    fn suggest_precedence(&self, conflict: &TokenConflict<'grammar>) -> Vec<String> {
+        let nt = conflict.production.nonterminal;
+        let productions = self.grammar.productions_for(nt);
+        let operands: Vec<_> = productions.iter()
+                                          .filter(|p| binary_operator(p).is_none())
+                                          .collect();
+        let mut lines = vec![];
+        let operand = match operands.first() {
+            Some(p) if operands.len() == 1 && p.symbols.len() == 1 => {
+                match p.symbols[0] {
+                    Symbol::Nonterminal(operand) => Some(operand),
+                    Symbol::Terminal(_) => None,
+                }
+            }
+            _ => None,
+        };
+        let operand = match operand {
+            Some(operand) => operand.to_string(),
+            None => {
+                lines.push(format!("// where `Term` has the other alternatives of `{}`", nt));
+                "Term".to_string()
+            }
+        };
+
+        let (reduce_op, shift_op) = self.precedence_operators(conflict);
+        let mut operators = vec![reduce_op];
+        if shift_op != reduce_op {
+            operators.push(shift_op);
+        }
+        operators.sort_by_key(|&op| {
+            productions.iter().position(|p| binary_operator(p) == Some(op))
+        });
+
+        lines.push(format!("{} = operators({}) {{", nt, operand));
+        for op in operators {
+            lines.push(format!("    left {{ {} => ... }},", op));
+        }
+        lines.push("};".to_string());
+        lines
+    }
+
     fn report_error_not_lr1_core(&self,
                                  conflict: &TokenConflict<'grammar>,
                                  action: Example,
                                  reduce: Example)
                                  -> Builder<BodyCharacter> {
         let styles = ExampleStyles::new();
+        let entry = match conflict.action {
+            Action::Shift(..) => &catalog::LOCAL_AMBIGUITY,
+            Action::Reduce(_) => &catalog::REDUCE_REDUCE_CONFLICT,
+        };
         let builder =
             MessageBuilder::new(conflict.production.span)
+            .code(entry.code)
+            .label("not clear whether to reduce this production")
             .heading()
-            .text("Local ambiguity detected")
+            .text(entry.format(&[]))
             .end()
 
             .body();
@@ -221,7 +511,7 @@ impl<'cx, 'grammar> ErrorReportingCx<'cx, 'grammar> {
 
         let builder =
             self.describe_reduce(builder, styles, conflict.production,
-                                 reduce, "First");
+                                 conflict.lookahead, reduce, "First");
 
         match conflict.action {
             Action::Shift(lookahead, _) =>
@@ -229,7 +519,7 @@ impl<'cx, 'grammar> ErrorReportingCx<'cx, 'grammar> {
                                     action, "Alternatively"),
             Action::Reduce(production) =>
                 self.describe_reduce(builder, styles, production,
-                                     action, "Alternatively"),
+                                     conflict.lookahead, action, "Alternatively"),
         }
     }
 
@@ -275,11 +565,13 @@ impl<'cx, 'grammar> ErrorReportingCx<'cx, 'grammar> {
                                      builder: Builder<C>,
                                      styles: ExampleStyles,
                                      production: &Production,
+                                     lookahead: Token,
                                      example: Example,
                                      intro_word: &str)
                                      -> Builder<C>
     {
-        builder
+        let follow_path = self.follow_path(&example, lookahead);
+        let builder = builder
             .begin_lines()
             .begin_wrap()
             .text(intro_word)
@@ -297,7 +589,101 @@ impl<'cx, 'grammar> ErrorReportingCx<'cx, 'grammar> {
             .text("This might then yield a parse tree like")
             .end()
             .push(example.into_picture(styles))
-            .end()
+            .end();
+
+        match follow_path {
+            Some(follow_path) => builder.push(follow_path),
+            None => builder,
+        }
+    }
+
+    /// Explains why `lookahead` can follow the reduction in a reduce
+    /// example, which is not always obvious: walks out from the
+    /// reduction through the nonterminals that it ends, up to the one
+    /// that is followed by a symbol that can start with `lookahead`.
+    /// Returns `None` if the reduced nonterminal is itself followed
+    /// by such a symbol, as that needs no explaining.
+    fn follow_path(&self, example: &Example, lookahead: Token) -> Option<Box<Content>> {
+        // The symbol after the cursor that yields the lookahead,
+        // skipping those that can be empty. There is none if the
+        // lookahead is EOF, which follows the whole example.
+        let position = match lookahead {
+            Token::Terminal(_) => {
+                let position = (example.cursor..example.symbols.len()).find(|&i| {
+                    match example.symbols[i] {
+                        ExampleSymbol::Symbol(ref s) => {
+                            self.first_sets.first0(Some(s)).contains(lookahead)
+                        }
+                        ExampleSymbol::Epsilon => false,
+                    }
+                });
+                if position.is_none() {
+                    return None;
+                }
+                position
+            }
+            Token::EOF => None,
+            Token::Error => return None,
+        };
+
+        // The reductions that end before that symbol, innermost
+        // first, and the one that contains it.
+        let end = position.unwrap_or(example.symbols.len());
+        let path: Vec<NonterminalString> = example.reductions
+                                                  .iter()
+                                                  .take_while(|r| r.end <= end)
+                                                  .map(|r| r.nonterminal)
+                                                  .collect();
+        let parent = example.reductions.iter().find(|r| r.end > end);
+        if path.len() < 2 {
+            return None;
+        }
+
+        let mut builder = InlineBuilder::new().begin_wrap();
+        builder = match lookahead {
+            Token::Terminal(term) => builder.text("The token").push(term).verbatimed(),
+            _ => builder.text("The end of the input"),
+        };
+        builder = builder
+            .text("can follow a")
+            .push(path[0])
+            .verbatimed()
+            .text("here because");
+        for pair in path.windows(2) {
+            builder = builder
+                .text("a")
+                .push(pair[0])
+                .verbatimed()
+                .text("can end a")
+                .push(pair[1])
+                .verbatimed()
+                .punctuated(",");
+        }
+        builder = builder.text("and a").push(*path.last().unwrap()).verbatimed();
+        builder = match position {
+            Some(position) => {
+                let builder = match example.symbols[position] {
+                    ExampleSymbol::Symbol(Symbol::Nonterminal(nt)) => {
+                        builder
+                            .text("can be followed by a")
+                            .push(nt)
+                            .verbatimed()
+                            .punctuated(",")
+                            .text("which can start with it,")
+                    }
+                    ExampleSymbol::Symbol(symbol) => {
+                        builder.text("can be followed by").push(symbol).verbatimed()
+                    }
+                    ExampleSymbol::Epsilon => unreachable!(),
+                };
+                match parent {
+                    Some(parent) => builder.text("in a").push(parent.nonterminal).verbatimed(),
+                    None => builder,
+                }
+            }
+            None => builder.text("can end the input"),
+        };
+        Some(builder.punctuated(".").end().end())
     }
 
     fn report_error_suggest_inline(&self,
@@ -389,14 +775,83 @@ impl<'cx, 'grammar> ErrorReportingCx<'cx, 'grammar> {
     }
 
     /// Naive error reporting. This is a fallback path which (I think)
-    /// never actually executes.
+    /// never actually executes. Whichever of the two ways has an
+    /// example is drawn as in the other reports; if neither does, we
+    /// dump the items of the state.
     fn report_error_naive(&self,
-                          conflict: &TokenConflict<'grammar>)
+                          conflict: &TokenConflict<'grammar>,
+                          action: Option<Example>,
+                          reduce: Option<Example>)
                           -> Message {
+        if action.is_none() && reduce.is_none() {
+            return self.report_error_items(conflict);
+        }
+
+        let styles = ExampleStyles::new();
+        let builder =
+            MessageBuilder::new(conflict.production.span)
+            .code(catalog::CONFLICT.code)
+            .label("conflict when reducing this production")
+            .heading()
+            .text(catalog::CONFLICT.format(&[]))
+            .end()
+            .body()
+            .begin_lines()
+            .wrap_text("The parser can proceed in two different ways here.")
+            .end();
+
+        let builder = match reduce {
+            Some(reduce) =>
+                self.describe_reduce(builder, styles, conflict.production,
+                                     conflict.lookahead, reduce, "First"),
+            None =>
+                builder.begin_lines()
+                       .begin_wrap()
+                       .text("First, the parser could reduce to a")
+                       .push(conflict.production.nonterminal)
+                       .verbatimed()
+                       .punctuated(".")
+                       .end()
+                       .end(),
+        };
+
+        let builder = match (conflict.action, action) {
+            (Action::Shift(lookahead, _), Some(action)) =>
+                self.describe_shift(builder, styles, lookahead, action, "Alternatively"),
+            (Action::Reduce(production), Some(action)) =>
+                self.describe_reduce(builder, styles, production,
+                                     conflict.lookahead, action, "Alternatively"),
+            (Action::Shift(lookahead, _), None) =>
+                builder.begin_lines()
+                       .begin_wrap()
+                       .text("Alternatively, the parser could shift the")
+                       .push(lookahead)
+                       .verbatimed()
+                       .text("token.")
+                       .end()
+                       .end(),
+            (Action::Reduce(production), None) =>
+                builder.begin_lines()
+                       .begin_wrap()
+                       .text("Alternatively, the parser could reduce to a")
+                       .push(production.nonterminal)
+                       .verbatimed()
+                       .punctuated(".")
+                       .end()
+                       .end(),
+        };
+
+        builder.end().end()
+    }
+
+    /// The last resort, when there is no example at all.
+    fn report_error_items(&self, conflict: &TokenConflict<'grammar>) -> Message {
         let mut builder =
             MessageBuilder::new(conflict.production.span)
+            .code(catalog::CONFLICT.code)
+            .label("conflict when reducing this production")
             .heading()
-            .text("Conflict detected")
+            .text(catalog::CONFLICT.format(&[]))
             .end()
             .body()
             .begin_lines()
@@ -440,9 +895,12 @@ impl<'cx, 'grammar> ErrorReportingCx<'cx, 'grammar> {
                                                        conflict.production,
                                                        conflict.lookahead);
 
-        // Prefer shorter examples to longer ones.
-        action_examples.sort_by(|e, f| e.symbols.len().cmp(&f.symbols.len()));
-        reduce_examples.sort_by(|e, f| e.symbols.len().cmp(&f.symbols.len()));
+        // Prefer cheaper examples, then shorter ones. As the order is
+        // total, the examples picked below depend only on which
+        // examples there are, and not on the order in which the trace
+        // graph happened to yield them.
+        action_examples.sort_by(compare_examples);
+        reduce_examples.sort_by(compare_examples);
 
         if let Some(classification) = self.try_classify_ambiguity(conflict,
                                                                   &action_examples,
@@ -465,17 +923,20 @@ impl<'cx, 'grammar> ErrorReportingCx<'cx, 'grammar> {
         // Give up. Just grab an example from each and pair them up.
         // If there aren't even two examples, something's pretty
         // bogus, but we'll just call it naive.
-        action_examples
-            .into_iter()
-            .zip(reduce_examples)
-            .next()
-            .map(|(action, reduce)| {
+        match (action_examples.into_iter().next(), reduce_examples.into_iter().next()) {
+            (Some(action), Some(reduce)) => {
                 ConflictClassification::InsufficientLookahead {
                     action: action,
                     reduce: reduce,
                 }
-            })
-            .unwrap_or(ConflictClassification::Naive)
+            }
+            (action, reduce) => {
+                ConflictClassification::Naive {
+                    action: action,
+                    reduce: reduce,
+                }
+            }
+        }
     }
 
     fn try_classify_ambiguity(&self,
@@ -491,14 +952,21 @@ impl<'cx, 'grammar> ErrorReportingCx<'cx, 'grammar> {
             .map(|(action, reduce)| {
                 // Consider whether to call this a precedence
                 // error. We do this if we are stuck between reducing
-                // `T = T S T` and shifting `S`.
+                // `T = T S T` and shifting `S` (or some other `U` in
+                // `T = T (*) U T`).
                 if let Action::Shift(term, _) = conflict.action {
                     let nt = conflict.production.nonterminal;
-                    if conflict.production.symbols.len() == 3 &&
-                        conflict.production.symbols[0] == Symbol::Nonterminal(nt) &&
-                        conflict.production.symbols[1] == Symbol::Terminal(term) &&
-                        conflict.production.symbols[2] == Symbol::Nonterminal(nt)
-                    {
+                    let shifts_operator =
+                        self.states[conflict.state.0]
+                            .items
+                            .vec
+                            .iter()
+                            .any(|item| {
+                                item.index == 1 &&
+                                    item.production.nonterminal == nt &&
+                                    binary_operator(item.production) == Some(term)
+                            });
+                    if binary_operator(conflict.production).is_some() && shifts_operator {
                         return ConflictClassification::Precedence {
                             shift: action.clone(),
                             reduce: reduce.clone(),
@@ -747,6 +1215,77 @@ impl<'cx, 'grammar> ErrorReportingCx<'cx, 'grammar> {
     }
 }
 
+/// Orders examples cheapest first, by the `#[cost(N)]` of the
+/// productions they use, and then shortest first. Ties are broken by
+/// the symbols, cursor and reductions of the examples, so that which
+/// one gets reported depends only on the grammar.
+fn compare_examples(e: &Example, f: &Example) -> Ordering {
+    (e.cost, e.symbols.len(), &e.symbols, e.cursor, &e.reductions)
+        .cmp(&(f.cost, f.symbols.len(), &f.symbols, f.cursor, &f.reductions))
+}
+
+/// The lookaheads on which the actions of a conflict disagree, for its
+/// heading: `(lookaheads `+`, `*`)`.
+fn lookahead_heading(lookaheads: &[Token]) -> Box<Content> {
+    let mut builder =
+        InlineBuilder::new()
+        .begin_spaced()
+        .text(if lookaheads.len() == 1 { "(lookahead" } else { "(lookaheads" });
+    for (index, lookahead) in lookaheads.iter().enumerate() {
+        builder = builder.text(lookahead).verbatimed();
+        if index + 1 < lookaheads.len() {
+            builder = builder.punctuated(",");
+        }
+    }
+    builder.punctuated(")").end().end()
+}
+
+/// The operator in a production of the form `T = T op T`, if it is
+/// one.
+fn binary_operator(production: &Production) -> Option<TerminalString> {
+    let nt = Symbol::Nonterminal(production.nonterminal);
+    if production.symbols.len() != 3 || production.symbols[0] != nt ||
+        production.symbols[2] != nt {
+        return None;
+    }
+    match production.symbols[1] {
+        Symbol::Terminal(op) => Some(op),
+        Symbol::Nonterminal(_) => None,
+    }
+}
+
+/// SLR(1) lookahead is much coarser than LR(1), so many of the
+/// conflicts it reports are not really there.
+fn slr_note() -> Box<Content> {
+    InlineBuilder::new()
+        .begin_wrap()
+        .text("Note:")
+        .styled(Tls::session().hint_text)
+        .text("This grammar is marked")
+        .text("#[SLR]")
+        .verbatimed()
+        .punctuated(",")
+        .text("which can report conflicts that LR(1) would not.")
+        .text("This one may go away if you remove the annotation.")
+        .end()
+        .end()
+}
+
+/// The examples of the shift and of the reduction from a
+/// classification, if it has any.
+fn examples(classification: ConflictClassification) -> Option<(Example, Example)> {
+    match classification {
+        ConflictClassification::Ambiguity { action, reduce } |
+        ConflictClassification::InsufficientLookahead { action, reduce } => {
+            Some((action, reduce))
+        }
+        ConflictClassification::Precedence { shift, reduce, .. } |
+        ConflictClassification::SuggestInline { shift, reduce, .. } |
+        ConflictClassification::SuggestQuestion { shift, reduce, .. } => Some((shift, reduce)),
+        ConflictClassification::Naive { .. } => None,
+    }
+}
+
 fn token_conflicts<'grammar>(conflicts: &[Conflict<'grammar, TokenSet>])
                              -> Vec<TokenConflict<'grammar>> {
     conflicts
@@ -1,8 +1,16 @@
+use ascii_canvas::AsciiCanvas;
+use file_text::FileText;
 use intern::intern;
 use grammar::repr::*;
 use lr1::build_states;
+use lr1::core::Action;
+use lr1::example::{Example, ExampleSymbol, Reduction};
+use lr1::lookahead::Token;
 use lr1::tls::Lr1Tls;
-use test_util::normalized_grammar;
+use message::Content;
+use session::Session;
+use std::rc::Rc;
+use test_util::{expect_debug, normalized_grammar};
 use tls::Tls;
 
 use super::{ConflictClassification, ErrorReportingCx};
@@ -48,6 +56,48 @@ pub Ty: () = {
     }
 }
 
+#[test]
+fn precedence_suggestion() {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(r#"
+grammar;
+pub Expr: () = {
+    Expr "+" Expr => (),
+    Expr "*" Expr => (),
+    Atom => (),
+};
+Atom: () = "N" => ();
+"#);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let err = build_states(&grammar, nt("Expr")).unwrap_err();
+    let mut cx = ErrorReportingCx::new(&grammar, &err.states, &err.conflicts);
+    let conflicts = super::token_conflicts(&err.conflicts);
+
+    // find a conflict between two different operators
+    let conflict = conflicts.iter()
+                            .find(|conflict| match conflict.action {
+                                Action::Shift(term, _) => {
+                                    !conflict.production.symbols.contains(&Symbol::Terminal(term))
+                                }
+                                Action::Reduce(_) => false,
+                            })
+                            .unwrap();
+
+    match cx.classify(conflict) {
+        ConflictClassification::Precedence { nonterminal, .. } => {
+            assert_eq!(nonterminal, nt("Expr"));
+        }
+        r => panic!("wrong classification {:#?}", r)
+    }
+
+    assert_eq!(cx.suggest_precedence(conflict), vec![
+        r#"Expr = operators(Atom) {"#,
+        r#"    left { "+" => ... },"#,
+        r#"    left { "*" => ... },"#,
+        r#"};"#,
+    ]);
+}
+
 #[test]
 fn expr_braced_conflict() {
     let _tls = Tls::test();
@@ -148,3 +198,152 @@ Ident = r#"[a-zA-Z][a-zA-Z0-9]*"#;
         r => panic!("wrong classification {:#?}", r)
     }
 }
+
+#[test]
+fn canonical_example_order() {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(r#"
+grammar;
+pub Expr: () = {
+    Expr "+" Expr => (),
+    Expr "*" Expr => (),
+    "Int" => (),
+};
+"#);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let err = build_states(&grammar, nt("Expr")).unwrap_err();
+    let cx = ErrorReportingCx::new(&grammar, &err.states, &err.conflicts);
+    let conflicts = super::token_conflicts(&err.conflicts);
+    let conflict = &conflicts[0];
+
+    // whatever order the examples are found in, they are sorted the
+    // same way
+    let mut examples = cx.reduce_examples(conflict.state, conflict.production, conflict.lookahead);
+    assert!(!examples.is_empty());
+    let mut reversed: Vec<_> = examples.iter().cloned().rev().collect();
+    examples.sort_by(super::compare_examples);
+    reversed.sort_by(super::compare_examples);
+    assert_eq!(format!("{:?}", examples), format!("{:?}", reversed));
+}
+
+#[test]
+fn cheaper_examples_first() {
+    let _tls = Tls::test();
+    let symbols = |text: &str| -> Vec<ExampleSymbol> {
+        text.split(' ')
+            .map(|s| ExampleSymbol::Symbol(Symbol::Terminal(TerminalString::quoted(intern(s)))))
+            .collect()
+    };
+    let example = |text: &str, cost: u32| Example {
+        symbols: symbols(text),
+        cursor: 1,
+        reductions: vec![],
+        cost: cost,
+    };
+
+    // `( Int )` uses a costly production, so the longer `Int + Int +
+    // Int` comes before it
+    let mut examples = vec![example("( Int )", 3), example("Int + Int + Int", 0)];
+    examples.sort_by(super::compare_examples);
+    let lengths: Vec<_> = examples.iter().map(|e| e.symbols.len()).collect();
+    assert_eq!(lengths, vec![5, 3]);
+}
+
+#[test]
+fn conflicts_clustered_by_production() {
+    // keep going after the first conflict, so that both productions
+    // are seen
+    let mut session = Session::test();
+    session.max_errors = 0;
+    let _tls = Tls::install(Rc::new(session), Rc::new(FileText::test()));
+    let grammar = normalized_grammar(r#"
+grammar;
+pub Expr: () = {
+    Expr "+" Expr => (),
+    Expr "*" Expr => (),
+    "Int" => (),
+};
+"#);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let err = build_states(&grammar, nt("Expr")).unwrap_err();
+    let mut cx = ErrorReportingCx::new(&grammar, &err.states, &err.conflicts);
+
+    // each production conflicts on both `+` and `*`, but is reported
+    // once
+    assert!(super::token_conflicts(&err.conflicts).len() >= 4);
+    assert_eq!(cx.report_errors().len(), 2);
+}
+
+#[test]
+fn lookahead_heading() {
+    let _tls = Tls::test();
+    let lookaheads = vec![Token::Terminal(TerminalString::Bare(intern("PLUS"))),
+                          Token::Terminal(TerminalString::Bare(intern("STAR")))];
+    let heading = super::lookahead_heading(&lookaheads);
+    let mut canvas = AsciiCanvas::new(0, heading.min_width());
+    heading.emit(&mut canvas);
+    expect_debug(&canvas.to_strings(), r#"
+[
+    "(lookaheads `PLUS`, `STAR`)"
+]
+"#.trim());
+}
+
+#[test]
+fn follow_path() {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(r#"
+grammar;
+pub Stmt: () = {
+    Exprs ";",
+};
+Exprs: () = {
+    Expr,
+    Exprs "," Expr,
+};
+Expr: () = {
+    "Int",
+};
+"#);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let states = build_states(&grammar, nt("Stmt")).unwrap();
+    let cx = ErrorReportingCx::new(&grammar, &states, &[]);
+
+    // Exprs "," "Int"  ╷ ";"
+    // │         └─Expr─┤   │
+    // ├─Exprs──────────┘   │
+    // └─Stmt───────────────┘
+    let semi = TerminalString::quoted(intern(";"));
+    let symbol = |s: Symbol| ExampleSymbol::Symbol(s);
+    let example = Example {
+        symbols: vec![symbol(Symbol::Nonterminal(nt("Exprs"))),
+                      symbol(Symbol::Terminal(TerminalString::quoted(intern(",")))),
+                      symbol(Symbol::Terminal(TerminalString::quoted(intern("Int")))),
+                      symbol(Symbol::Terminal(semi))],
+        cursor: 3,
+        reductions: vec![Reduction { start: 2, end: 3, nonterminal: nt("Expr") },
+                         Reduction { start: 0, end: 3, nonterminal: nt("Exprs") },
+                         Reduction { start: 0, end: 4, nonterminal: nt("Stmt") }],
+        cost: 0,
+    };
+
+    let path = cx.follow_path(&example, Token::Terminal(semi)).unwrap();
+    let mut canvas = AsciiCanvas::new(0, 200);
+    path.emit(&mut canvas);
+    let rows: Vec<String> = canvas.to_strings().iter().map(|row| row.to_string()).collect();
+    assert_eq!(rows,
+               vec![concat!("The token `\";\"` can follow a `Expr` here because ",
+                            "a `Expr` can end a `Exprs`, ",
+                            "and a `Exprs` can be followed by `\";\"` in a `Stmt`.")]);
+
+    // with `Exprs` directly followed, there is nothing to explain
+    let example = Example {
+        symbols: example.symbols.clone(),
+        cursor: 1,
+        reductions: vec![Reduction { start: 0, end: 1, nonterminal: nt("Exprs") },
+                         Reduction { start: 0, end: 4, nonterminal: nt("Exprs") }],
+        cost: 0,
+    };
+    let comma = TerminalString::quoted(intern(","));
+    assert!(cx.follow_path(&example, Token::Terminal(comma)).is_none());
+}
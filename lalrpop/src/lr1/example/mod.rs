@@ -1,13 +1,15 @@
 //! Code to compute example inputs given a backtrace.
 
-use ascii_canvas::{AsciiView};
+use ascii_canvas::{AsciiCanvas, AsciiView};
 use message::Content;
 use message::builder::InlineBuilder;
 use grammar::repr::*;
 use std::fmt::{Debug, Formatter, Error};
 use style::Style;
 use tls::Tls;
+use unicode_width::UnicodeWidthStr;
 
+mod svg;
 #[cfg(test)] mod test;
 
 /// An "example" input and the way it was derived. This can be
@@ -46,9 +48,13 @@ pub struct Example {
     pub symbols: Vec<ExampleSymbol>,
     pub cursor: usize,
     pub reductions: Vec<Reduction>,
+
+    /// The total `#[cost(N)]` of the productions used to derive the
+    /// example; cheaper examples are reported first.
+    pub cost: u32,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ExampleSymbol {
     Symbol(Symbol),
     Epsilon,
@@ -61,7 +67,7 @@ pub struct ExampleStyles {
     pub after_cursor: Style,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Reduction {
     pub start: usize,
     pub end: usize,
@@ -70,13 +76,13 @@ pub struct Reduction {
 
 impl Example {
     /// Length of each symbol. Each will need *at least* that amount
-    /// of space. :) Measure in characters, under the assumption of a
-    /// mono-spaced font. Also add a final `0` marker which will serve
-    /// as the end position.
+    /// of space. :) Measure in columns of a mono-spaced font, where
+    /// CJK characters take two and combining marks none. Also add a
+    /// final `0` marker which will serve as the end position.
     fn lengths(&self) -> Vec<usize> {
         self.symbols.iter()
                     .map(|s| match *s {
-                        ExampleSymbol::Symbol(s) => format!("{}", s).chars().count(),
+                        ExampleSymbol::Symbol(s) => display_width(&format!("{}", s)),
                         ExampleSymbol::Epsilon => 1, // display as " "
                     })
                     .chain(Some(0))
@@ -114,18 +120,49 @@ impl Example {
     /// Render the example into a styled diagram suitable for
     /// embedding in an error message.
     pub fn into_picture(self, styles: ExampleStyles) -> Box<Content> {
-        let lengths = self.lengths();
-        let positions = self.positions(&lengths);
+        let (example, elision) = self.collapse(Tls::session().max_example_reductions);
+        let lengths = example.lengths();
+        let positions = example.positions(&lengths);
         InlineBuilder::new()
             .push(Box::new(ExamplePicture {
-                example: self,
+                example: example,
                 positions: positions,
+                elision: elision,
                 styles: styles,
             }))
             .indented()
             .end()
     }
 
+    /// If there are more than `max_reductions` reductions, leave out
+    /// those in the middle, keeping the innermost ones, next to the
+    /// symbols, and the outermost ones, which show where the conflict
+    /// sits in the grammar:
+    ///
+    /// ```
+    /// A B C D
+    /// | | | |
+    /// +-W-+ |
+    /// | … 7 more
+    /// |     |
+    /// +-Z---+
+    /// ```
+    fn collapse(mut self, max_reductions: Option<usize>) -> (Example, Option<Elision>) {
+        let max_reductions = match max_reductions {
+            Some(max) if self.reductions.len() > max => max,
+            _ => return (self, None),
+        };
+        let index = max_reductions / 2;
+        let count = self.reductions.len() - max_reductions;
+        let start = self.reductions[index..index + count]
+                        .iter()
+                        .map(|reduction| reduction.start)
+                        .min()
+                        .unwrap();
+        self.reductions.drain(index..index + count);
+        (self, Some(Elision { index: index, count: count, start: start }))
+    }
+
     fn starting_positions(&self, lengths: &[usize]) -> Vec<usize> {
         lengths.iter()
                .scan(0, |counter, &len| {
@@ -141,7 +178,7 @@ impl Example {
     }
 
     /// Start index where each symbol in the example should appear,
-    /// measured in characters. These are spaced to leave enough room
+    /// measured in columns. These are spaced to leave enough room
     /// for the reductions below.
     fn positions(&self, lengths: &[usize]) -> Vec<usize> {
         // Initially, position each symbol with one space in between,
@@ -166,7 +203,7 @@ impl Example {
         //    |             |
         //    +-LongLabel22-+
         for &Reduction { start, end, nonterminal } in &self.reductions {
-            let nt_len = format!("{}", nonterminal).chars().count();
+            let nt_len = display_width(&format!("{}", nonterminal));
 
             // Number of symbols we are reducing. This should always
             // be non-zero because even in the case of a \epsilon
@@ -269,18 +306,64 @@ impl Example {
         canvas.to_strings()
     }
 
+    /// The text in each row of the picture, as the column where each
+    /// piece starts and the piece itself: the symbols in the top row,
+    /// the label of each reduction in the row of its bracket, and the
+    /// note about the reductions left out, if any, in its own row.
+    fn row_texts(&self,
+                 positions: &[usize],
+                 elision: Option<Elision>)
+                 -> Vec<Vec<(usize, String)>> {
+        let symbols = self.symbols
+                          .iter()
+                          .zip(positions)
+                          .filter_map(|(symbol, &position)| match *symbol {
+                              ExampleSymbol::Symbol(s) => Some((position, s.to_string())),
+                              ExampleSymbol::Epsilon => None,
+                          })
+                          .collect();
+        let labels = self.reductions
+                         .iter()
+                         .map(|reduction| {
+                             vec![(positions[reduction.start] + 2,
+                                   reduction.nonterminal.to_string())]
+                         });
+        let mut rows: Vec<_> = Some(symbols).into_iter().chain(labels).collect();
+        if let Some(elision) = elision {
+            rows.insert(1 + elision.index,
+                        vec![(positions[elision.start] + 2, elision.note())]);
+        }
+        rows
+    }
+
     fn paint_on(&self,
                 styles: &ExampleStyles,
                 positions: &[usize],
+                elision: Option<Elision>,
                 view: &mut AsciiView) {
-        // Draw the brackets for each reduction:
+        let rows = self.row_texts(positions, elision);
+
+        // Draw the brackets for each reduction on a canvas of their
+        // own, laid out by columns of the terminal, then copy them a
+        // row at a time, since the text in each row moves them
+        // differently in the canvas:
+        let width = *positions.last().unwrap();
+        let brackets: &mut AsciiView = &mut AsciiCanvas::new(rows.len(), width);
         for (index, reduction) in self.reductions.iter().enumerate() {
             let start_column = positions[reduction.start];
             let end_column = positions[reduction.end] - 1;
-            let row = 1 + index;
-            view.draw_vertical_line(0 .. row + 1, start_column);
-            view.draw_vertical_line(0 .. row + 1, end_column - 1);
-            view.draw_horizontal_line(row, start_column .. end_column);
+            let row = row_of(index, elision);
+            brackets.draw_vertical_line(0 .. row + 1, start_column);
+            brackets.draw_vertical_line(0 .. row + 1, end_column - 1);
+            brackets.draw_horizontal_line(row, start_column .. end_column);
+        }
+        for (row, texts) in rows.iter().enumerate() {
+            for column in 0 .. width {
+                let ch = brackets.read_char(row, column);
+                if ch != ' ' {
+                    view.write_char(row, canvas_column(texts, column), ch, Style::new());
+                }
+            }
         }
 
         // Write the labels for each reduction. Do this after the
@@ -289,21 +372,27 @@ impl Example {
         let session = Tls::session();
         for (index, reduction) in self.reductions.iter().enumerate() {
             let column = positions[reduction.start] + 2;
-            let row = 1 + index;
+            let row = row_of(index, elision);
             view.write_chars(row,
                              column,
                              reduction.nonterminal.to_string().chars(),
                              session.nonterminal_symbol);
         }
+        if let Some(elision) = elision {
+            let row = 1 + elision.index;
+            let column = canvas_column(&rows[row], positions[elision.start] + 2);
+            view.write_chars(row, column, elision.note().chars(), session.nonterminal_symbol);
+        }
 
         // Write the labels on top:
         //    A1   B2  C3  D4 E5 F6
-        self.paint_symbols_on(&self.symbols, &positions, styles, view);
+        self.paint_symbols_on(&self.symbols, &positions, &rows[0], styles, view);
     }
 
     fn paint_symbols_on(&self,
                         symbols: &[ExampleSymbol],
                         positions: &[usize],
+                        texts: &[(usize, String)],
                         styles: &ExampleStyles,
                         view: &mut AsciiView) {
         let session = Tls::session();
@@ -323,7 +412,7 @@ impl Example {
                 styles.after_cursor
             };
 
-            let column = positions[index];
+            let column = canvas_column(texts, positions[index]);
             match *ex_symbol {
                 ExampleSymbol::Symbol(Symbol::Terminal(term)) => {
                     view.write_chars(0,
@@ -347,16 +436,60 @@ impl Example {
 struct ExamplePicture {
     example: Example,
     positions: Vec<usize>,
+    elision: Option<Elision>,
     styles: ExampleStyles,
 }
 
+/// The reductions that `Example::collapse` left out of a picture,
+/// which has a row noting them in their place.
+#[derive(Copy, Clone, Debug)]
+struct Elision {
+    /// The index of the first reduction kept after those left out;
+    /// the row of the note comes before its row.
+    index: usize,
+
+    /// How many reductions were left out.
+    count: usize,
+
+    /// The first symbol that any of them covers; the note goes under
+    /// it.
+    start: usize,
+}
+
+impl Elision {
+    fn note(&self) -> String {
+        format!("… {} more", self.count)
+    }
+}
+
+/// The row of the bracket of the reduction with the given index,
+/// below the symbols and the note about any reductions left out
+/// before it.
+fn row_of(index: usize, elision: Option<Elision>) -> usize {
+    match elision {
+        Some(elision) if index >= elision.index => 2 + index,
+        _ => 1 + index,
+    }
+}
+
 impl Content for ExamplePicture {
     fn min_width(&self) -> usize {
-        *self.positions.last().unwrap()
+        // the note about reductions left out may run past the end
+        let width = *self.positions.last().unwrap();
+        self.example
+            .row_texts(&self.positions, self.elision)
+            .iter()
+            .flat_map(|texts| {
+                let ends = texts.iter()
+                                .map(move |&(start, ref text)| start + display_width(text));
+                ends.chain(Some(width)).map(move |end| canvas_column(texts, end))
+            })
+            .max()
+            .unwrap_or(width)
     }
 
     fn emit(&self, view: &mut AsciiView) {
-        self.example.paint_on(&self.styles, &self.positions, view);
+        self.example.paint_on(&self.styles, &self.positions, self.elision, view);
     }
 
     fn into_wrap_items(self: Box<Self>, wrap_items: &mut Vec<Box<Content>>) {
@@ -370,6 +503,24 @@ impl Debug for ExamplePicture {
     }
 }
 
+/// The number of columns that `text` takes in a terminal.
+fn display_width(text: &str) -> usize {
+    UnicodeWidthStr::width(text)
+}
+
+/// The column of the canvas that shows up at `column` of the
+/// terminal, in a row with the pieces of text `texts` (see
+/// `row_texts`). The canvas holds one character per column, so each
+/// piece to the left that is wider or narrower than its number of
+/// characters moves everything after it.
+fn canvas_column(texts: &[(usize, String)], column: usize) -> usize {
+    texts.iter()
+         .filter(|&&(start, ref text)| start + display_width(text) <= column)
+         .fold(column, |column, &(_, ref text)| {
+             column + text.chars().count() - display_width(text)
+         })
+}
+
 fn shift(positions: &mut [usize], amount: usize) {
     for position in positions {
         *position += amount;
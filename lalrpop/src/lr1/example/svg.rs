@@ -0,0 +1,96 @@
+//! Rendering of examples as SVG, so that the derivation pictures of
+//! conflict reports can be embedded in documentation and bug reports.
+
+use grammar::repr::*;
+use util::XmlEscape;
+
+use super::{display_width, Example, ExampleSymbol};
+
+/// The width of one column (a character of a mono-spaced font) and
+/// the height of one row of the picture, in SVG user units.
+const COLUMN_WIDTH: usize = 10;
+const ROW_HEIGHT: usize = 24;
+
+const STYLE: &'static str = "text { font-family: monospace; font-size: 16px; } \
+                             .terminal { font-weight: bold; } \
+                             .cursor { fill: #c00000; } \
+                             path { fill: none; stroke: black; } \
+                             rect { fill: white; }";
+
+impl Example {
+    /// Render the example as a standalone SVG document, laid out as
+    /// `into_picture` lays it out in text: the symbols along the top,
+    /// and below them the bracket of each reduction in a row of its
+    /// own. Each piece of text is stretched to the columns it would
+    /// take in a terminal, so things line up whatever the font.
+    pub fn to_svg(&self) -> String {
+        let lengths = self.lengths();
+        let positions = self.positions(&lengths);
+        let width = *positions.last().unwrap() * COLUMN_WIDTH;
+        let height = (self.reductions.len() + 1) * ROW_HEIGHT;
+
+        let mut svg = String::new();
+        svg.push_str(&format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" \
+                               height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n",
+                              width,
+                              height));
+        svg.push_str(&format!("<style>{}</style>\n", STYLE));
+
+        // Draw the bracket for each reduction, and over it its label:
+        for (index, reduction) in self.reductions.iter().enumerate() {
+            let row = 1 + index;
+            let left = center(positions[reduction.start]);
+            let right = center(positions[reduction.end] - 2);
+            let middle = row * ROW_HEIGHT + ROW_HEIGHT / 2;
+            svg.push_str(&format!("<path d=\"M {} {} V {} H {} V {}\"/>\n",
+                                  left,
+                                  ROW_HEIGHT,
+                                  middle,
+                                  right,
+                                  ROW_HEIGHT));
+
+            let label = reduction.nonterminal.to_string();
+            let column = positions[reduction.start] + 2;
+            svg.push_str(&format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/>\n",
+                                  column * COLUMN_WIDTH,
+                                  row * ROW_HEIGHT,
+                                  display_width(&label) * COLUMN_WIDTH,
+                                  ROW_HEIGHT));
+            svg.push_str(&text(row, column, &label, "nonterminal"));
+        }
+
+        // Write the symbols along the top, picking out the terminal
+        // at the cursor as `paint_symbols_on` does:
+        for (index, symbol) in self.symbols.iter().enumerate() {
+            let class = match *symbol {
+                ExampleSymbol::Symbol(Symbol::Terminal(_)) if index == self.cursor => {
+                    "terminal cursor"
+                }
+                ExampleSymbol::Symbol(Symbol::Terminal(_)) => "terminal",
+                ExampleSymbol::Symbol(Symbol::Nonterminal(_)) => "nonterminal",
+                ExampleSymbol::Epsilon => continue,
+            };
+            if let ExampleSymbol::Symbol(s) = *symbol {
+                svg.push_str(&text(0, positions[index], &s.to_string(), class));
+            }
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// The middle of `column`, where vertical lines are drawn.
+fn center(column: usize) -> usize {
+    column * COLUMN_WIDTH + COLUMN_WIDTH / 2
+}
+
+fn text(row: usize, column: usize, text: &str, class: &str) -> String {
+    format!("<text x=\"{}\" y=\"{}\" textLength=\"{}\" lengthAdjust=\"spacingAndGlyphs\" \
+             class=\"{}\">{}</text>\n",
+            column * COLUMN_WIDTH,
+            row * ROW_HEIGHT + ROW_HEIGHT * 3 / 4,
+            display_width(text) * COLUMN_WIDTH,
+            class,
+            XmlEscape(text))
+}
@@ -1,5 +1,8 @@
+use file_text::FileText;
 use intern::intern;
 use grammar::repr::*;
+use session::Session;
+use std::rc::Rc;
 use test_util::expect_debug;
 use tls::Tls;
 
@@ -37,6 +40,7 @@ fn long_label_1_example() -> Example {
         reductions: vec![
             Reduction { start: 0, end: 4, nonterminal: nt("LongLabel22") },
             Reduction { start: 0, end: 6, nonterminal: nt("Label") }],
+        cost: 0,
     }
 }
 
@@ -85,6 +89,7 @@ fn empty_labels_example() -> Example {
             Reduction { start: 0, end: 4, nonterminal: nt("MegaLongLabel") },
             Reduction { start: 6, end: 7, nonterminal: nt("Y") },
             Reduction { start: 7, end: 8, nonterminal: nt("Z") }],
+        cost: 0,
     }
 }
 
@@ -113,6 +118,119 @@ fn empty_labels_strings() {
 "#.trim());
 }
 
+// The quoted terminal takes six columns but four characters, and the
+// label four columns but two characters.
+//
+//  "漢字" A B
+//  +-X----+ |
+//  +-表示---+
+fn wide_chars_example() -> Example {
+    Example {
+        symbols: vec![ExampleSymbol::Symbol(Symbol::Terminal(term("漢字"))), sym!(A), sym!(B)],
+        cursor: 0,
+        reductions: vec![
+            Reduction { start: 0, end: 2, nonterminal: nt("X") },
+            Reduction { start: 0, end: 3, nonterminal: nt("表示") }],
+        cost: 0,
+    }
+}
+
+#[test]
+fn wide_chars_positions() {
+    let _tls = Tls::test();
+    let example = wide_chars_example();
+    let lengths = example.lengths();
+    assert_eq!(lengths, vec![6, 1, 1, 0]);
+    let positions = example.positions(&lengths);
+    assert_eq!(positions, vec![0, 7, 9, 11]);
+}
+
+#[test]
+fn wide_chars_strings() {
+    let _tls = Tls::test();
+    let strings = wide_chars_example().paint_unstyled();
+    expect_debug(strings, r#"
+[
+    "  "漢字" A B",
+    "  ├─X────┘ │",
+    "  └─表示───┘"
+]
+"#.trim());
+}
+
+// With at most two reductions shown, `X` and `Y` are left out:
+//
+//  A   B C D
+//  +-W-+   |
+//  | … 2 more
+//  +-Z-----+
+fn deep_example() -> Example {
+    Example {
+        symbols: syms!(A,B,C,D),
+        cursor: 3,
+        reductions: vec![
+            Reduction { start: 0, end: 2, nonterminal: nt("W") },
+            Reduction { start: 0, end: 3, nonterminal: nt("X") },
+            Reduction { start: 0, end: 3, nonterminal: nt("Y") },
+            Reduction { start: 0, end: 4, nonterminal: nt("Z") }],
+        cost: 0,
+    }
+}
+
+#[test]
+fn deep_strings() {
+    let mut session = Session::test();
+    session.max_example_reductions = Some(2);
+    let _tls = Tls::install(Rc::new(session), Rc::new(FileText::test()));
+    let strings = deep_example().paint_unstyled();
+    expect_debug(strings, r#"
+[
+    "  A   B C D",
+    "  ├─W─┘   │",
+    "  │ … 2 more",
+    "  └─Z─────┘"
+]
+"#.trim());
+}
+
+#[test]
+fn deep_strings_expanded() {
+    let mut session = Session::test();
+    session.max_example_reductions = None;
+    let _tls = Tls::install(Rc::new(session), Rc::new(FileText::test()));
+    let strings = deep_example().paint_unstyled();
+    assert_eq!(strings.len(), 5);
+}
+
+#[test]
+fn long_label_1_svg() {
+    let _tls = Tls::test();
+    let svg = long_label_1_example().to_svg();
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"220\" \
+                             height=\"72\" viewBox=\"0 0 220 72\">\n"));
+    assert!(svg.ends_with("</svg>\n"));
+
+    // the bracket of `LongLabel22` runs from under `A1` to under `D4`,
+    // as `├─LongLabel22─┘` does in the text
+    assert!(svg.contains("<path d=\"M 5 24 V 36 H 145 V 24\"/>\n"));
+    assert!(svg.contains("<text x=\"20\" y=\"42\" textLength=\"110\" \
+                          lengthAdjust=\"spacingAndGlyphs\" \
+                          class=\"nonterminal\">LongLabel22</text>\n"));
+    assert!(svg.contains("<text x=\"50\" y=\"18\" textLength=\"20\" \
+                          lengthAdjust=\"spacingAndGlyphs\" class=\"nonterminal\">B2</text>\n"));
+}
+
+#[test]
+fn wide_chars_svg() {
+    let _tls = Tls::test();
+    let svg = wide_chars_example().to_svg();
+    assert!(svg.contains("<text x=\"0\" y=\"18\" textLength=\"60\" \
+                          lengthAdjust=\"spacingAndGlyphs\" \
+                          class=\"terminal cursor\">&quot;漢字&quot;</text>\n"));
+    assert!(svg.contains("<text x=\"20\" y=\"66\" textLength=\"40\" \
+                          lengthAdjust=\"spacingAndGlyphs\" class=\"nonterminal\">表示</text>\n"));
+}
+
 // _return_      _A_ Expression _B_
 // |            |                  |
 // +-ExprAtom---+                  |
@@ -129,7 +247,8 @@ fn single_token_example() -> Example {
             Reduction { start: 0, end: 1, nonterminal: nt("ExprAtom") },
             Reduction { start: 0, end: 1, nonterminal: nt("ExprSuffix") },
             Reduction { start: 0, end: 4, nonterminal: nt("ExprSuffix") },
-        ]
+        ],
+        cost: 0,
     }
 }
 
@@ -1,27 +1,125 @@
-//! LR(1) interpeter. Just builds up parse trees. Intended for testing.
+//! LR(1) interpeter. Just builds up parse trees. Used for testing,
+//! and to snapshot how a grammar parses a corpus of inputs.
 
 use lr1::core::*;
+use lr1::example::{Example, ExampleStyles, ExampleSymbol, Reduction};
 use lr1::lookahead::*;
-use generate::ParseTree;
 use grammar::repr::*;
+use message::Content;
 use std::iter::IntoIterator;
 use std::fmt::{Debug, Display, Formatter, Error};
 use util::Sep;
 
+#[derive(Clone, PartialEq, Eq)]
+pub enum ParseTree {
+    Nonterminal(NonterminalString, Vec<ParseTree>),
+    Terminal(TerminalString),
+}
+
 pub type InterpretError<'grammar, L> = (&'grammar State<'grammar, L>, Token);
 
+/// A move of the parser, made in the state that comes first.
+#[derive(Copy, Clone, Debug)]
+pub enum Step<'grammar> {
+    /// Shifted the terminal, going to the second state.
+    Shift(StateIndex, TerminalString, StateIndex),
+
+    /// Reduced the production, going to the state that the goto of
+    /// the state below gives -- or, if there is none, accepting the
+    /// input.
+    Reduce(StateIndex, &'grammar Production, Option<StateIndex>),
+}
+
+/// A move of the parser, with the states and the trees on its stacks
+/// once it was made.
+#[derive(Clone, Debug)]
+pub struct Frame<'grammar> {
+    pub step: Step<'grammar>,
+    pub states: Vec<StateIndex>,
+    pub trees: Vec<ParseTree>,
+}
+
 /// Feed in the given tokens and then EOF, returning the final parse tree that is reduced.
 pub fn interpret<'grammar, L>(states: &'grammar [State<'grammar, L>],
                               tokens: Vec<TerminalString>)
                               -> Result<ParseTree, InterpretError<'grammar, L>>
     where L: LookaheadInterpret
 {
-    println!("interpret(tokens={:?})", tokens);
     let mut m = Machine::new(states);
     m.execute(tokens.into_iter())
 }
 
+/// Like `interpret`, but also returns the moves that the parser made,
+/// up to the end of the input or to the error.
+pub fn interpret_steps<'grammar, L>(states: &'grammar [State<'grammar, L>],
+                                    tokens: Vec<TerminalString>)
+                                    -> (Result<ParseTree, InterpretError<'grammar, L>>,
+                                        Vec<Step<'grammar>>)
+    where L: LookaheadInterpret
+{
+    let mut m = Machine::new(states);
+    let result = m.execute(tokens.into_iter());
+    (result, m.steps)
+}
+
+/// Like `interpret_steps`, but with the stacks after each move.
+pub fn interpret_frames<'grammar, L>(states: &'grammar [State<'grammar, L>],
+                                     tokens: Vec<TerminalString>)
+                                     -> (Result<ParseTree, InterpretError<'grammar, L>>,
+                                         Vec<Frame<'grammar>>)
+    where L: LookaheadInterpret
+{
+    let mut m = Machine::new(states);
+    m.frames = Some(vec![]);
+    let result = m.execute(tokens.into_iter());
+    (result, m.frames.unwrap())
+}
+
+/// Draws the trees on the stack of the parser as the examples of
+/// conflict reports are drawn, followed by the tokens that it has yet
+/// to shift, the first of which is the lookahead.
+pub fn picture(trees: &[ParseTree], remaining: &[TerminalString]) -> Box<Content> {
+    fn walk(tree: &ParseTree, symbols: &mut Vec<ExampleSymbol>, reductions: &mut Vec<Reduction>) {
+        match *tree {
+            ParseTree::Terminal(terminal) => {
+                symbols.push(ExampleSymbol::Symbol(Symbol::Terminal(terminal)));
+            }
+            ParseTree::Nonterminal(nonterminal, ref children) => {
+                let start = symbols.len();
+                if children.is_empty() {
+                    symbols.push(ExampleSymbol::Epsilon);
+                }
+                for child in children {
+                    walk(child, symbols, reductions);
+                }
+                reductions.push(Reduction {
+                    start: start,
+                    end: symbols.len(),
+                    nonterminal: nonterminal,
+                });
+            }
+        }
+    }
+
+    let mut symbols = vec![];
+    let mut reductions = vec![];
+    for tree in trees {
+        walk(tree, &mut symbols, &mut reductions);
+    }
+    let cursor = symbols.len();
+    symbols.extend(remaining.iter().map(|&t| ExampleSymbol::Symbol(Symbol::Terminal(t))));
+
+    let example = Example {
+        symbols: symbols,
+        cursor: cursor,
+        reductions: reductions,
+        cost: 0,
+    };
+    example.into_picture(ExampleStyles::new())
+}
+
 /// Feed in the given tokens and returns the states on the stack.
+#[cfg(test)]
 pub fn interpret_partial<'grammar, TOKENS, L>
     (states: &'grammar [State<'grammar, L>],
      tokens: TOKENS)
@@ -38,6 +136,8 @@ struct Machine<'grammar, L: LookaheadInterpret + 'grammar> {
     states: &'grammar [State<'grammar, L>],
     state_stack: Vec<StateIndex>,
     data_stack: Vec<ParseTree>,
+    steps: Vec<Step<'grammar>>,
+    frames: Option<Vec<Frame<'grammar>>>,
 }
 
 impl<'grammar, L> Machine<'grammar, L>
@@ -49,6 +149,8 @@ impl<'grammar, L> Machine<'grammar, L>
             states: states,
             state_stack: vec![],
             data_stack: vec![],
+            steps: vec![],
+            frames: None,
         }
     }
 
@@ -58,7 +160,7 @@ impl<'grammar, L> Machine<'grammar, L>
     }
 
     fn execute_partial<TOKENS>(&mut self,
-                               mut tokens: TOKENS)
+                               tokens: TOKENS)
                                -> Result<(), InterpretError<'grammar, L>>
         where TOKENS: Iterator<Item = TerminalString>
     {
@@ -67,23 +169,43 @@ impl<'grammar, L> Machine<'grammar, L>
 
         self.state_stack.push(StateIndex(0));
 
+        let mut tokens = tokens.peekable();
         let mut token = tokens.next();
         while let Some(terminal) = token {
             let state = self.top_state();
 
-            println!("state={:?}", state);
-            println!("terminal={:?}", terminal);
-
-            // check whether we can shift this token
-            if let Some(&next_index) = state.shifts.get(&terminal) {
-                self.data_stack.push(ParseTree::Terminal(terminal));
-                self.state_stack.push(next_index);
-                token = tokens.next();
-            } else if let Some(production) = L::reduction(state, Token::Terminal(terminal)) {
-                let more = self.reduce(production);
-                assert!(more);
-            } else {
-                return Err((state, Token::Terminal(terminal)));
+            let action = match state.lookahead2.get(&terminal) {
+                // the token after this one decides what to do
+                Some(decisions) => {
+                    let next = tokens.peek().map_or(Token::EOF, |&t| Token::Terminal(t));
+                    decisions.iter()
+                             .find(|&&(ref after, _)| after.contains(next))
+                             .map(|&(_, action)| action)
+                }
+                None => {
+                    state.shifts
+                         .get(&terminal)
+                         .map(|&next_index| Action::Shift(terminal, next_index))
+                         .or_else(|| {
+                             L::reduction(state, Token::Terminal(terminal)).map(Action::Reduce)
+                         })
+                }
+            };
+
+            match action {
+                Some(Action::Shift(_, next_index)) => {
+                    self.data_stack.push(ParseTree::Terminal(terminal));
+                    self.state_stack.push(next_index);
+                    self.record(Step::Shift(state.index, terminal, next_index));
+                    token = tokens.next();
+                }
+                Some(Action::Reduce(production)) => {
+                    let more = self.reduce(production);
+                    assert!(more);
+                }
+                None => {
+                    return Err((state, Token::Terminal(terminal)));
+                }
             }
         }
 
@@ -112,10 +234,20 @@ impl<'grammar, L> Machine<'grammar, L>
         }
     }
 
-    fn reduce(&mut self, production: &Production) -> bool {
-        println!("reduce={:?}", production);
+    fn record(&mut self, step: Step<'grammar>) {
+        self.steps.push(step);
+        if let Some(ref mut frames) = self.frames {
+            frames.push(Frame {
+                step: step,
+                states: self.state_stack.clone(),
+                trees: self.data_stack.clone(),
+            });
+        }
+    }
 
+    fn reduce(&mut self, production: &'grammar Production) -> bool {
         let args = production.symbols.len();
+        let state_index = self.top_state().index;
 
         // remove the top N items from the data stack
         let mut popped = vec![];
@@ -138,9 +270,11 @@ impl<'grammar, L> Machine<'grammar, L>
         match receiving_state.gotos.get(&production.nonterminal) {
             Some(&goto_state) => {
                 self.state_stack.push(goto_state);
+                self.record(Step::Reduce(state_index, production, Some(goto_state)));
                 true // keep going
             }
             None => {
+                self.record(Step::Reduce(state_index, production, None));
                 false // all done
             }
         }
@@ -102,6 +102,8 @@ impl<'grammar> LaneTableConstruct<'grammar> {
                     shifts: s.shifts,
                     reductions: reductions,
                     gotos: s.gotos,
+                    lookahead2: s.lookahead2,
+                    conflict_hooks: s.conflict_hooks,
                 }
             })
             .collect()
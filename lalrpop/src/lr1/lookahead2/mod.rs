@@ -0,0 +1,229 @@
+//! Resolving conflicts with a second token of lookahead, for grammars
+//! that are LR(2) but not LR(1).
+//!
+//! After the states are built, we look at each terminal on which a
+//! state has more than one action, and work out which tokens can come
+//! *after* that terminal for each action. For a shift, the items being
+//! shifted tell us precisely. For a reduction, we fall back on the
+//! pairs of tokens that can follow the nonterminal anywhere in the
+//! grammar (its FOLLOW(2) set), which may be more than can actually
+//! follow it in this state. That only ever makes us give up on a
+//! conflict that could have been resolved, though: if the sets are
+//! disjoint, the second token always picks the right action.
+
+use collections::{map, set, Map, Set};
+use grammar::repr::*;
+use lr1::core::*;
+use lr1::first::FirstSets;
+use lr1::lookahead::{Token, TokenSet};
+
+#[cfg(test)]
+mod test;
+
+/// Tries to resolve each of `conflicts` by looking at a second token,
+/// recording the decisions in the states. Conflicts that cannot be
+/// resolved this way are reported as usual.
+pub fn resolve_conflicts<'grammar>(grammar: &'grammar Grammar,
+                                   start: NonterminalString,
+                                   mut states: Vec<LR1State<'grammar>>,
+                                   conflicts: Vec<LR1Conflict<'grammar>>)
+                                   -> LR1Result<'grammar> {
+    let follow = Follow2Sets::new(grammar, start);
+
+    let mut conflicted: Map<StateIndex, Set<Token>> = map();
+    for conflict in &conflicts {
+        conflicted.entry(conflict.state)
+                  .or_insert_with(set)
+                  .extend(conflict.lookahead.iter());
+    }
+
+    let mut unresolved = set();
+    for (&index, tokens) in &conflicted {
+        for &token in tokens {
+            let terminal = match token {
+                Token::Terminal(terminal) => terminal,
+                Token::EOF | Token::Error => {
+                    unresolved.insert((index, token));
+                    continue;
+                }
+            };
+
+            let decisions = decide(&follow, &states[index.0], terminal);
+            match decisions {
+                Some(decisions) => {
+                    states[index.0].lookahead2.insert(terminal, decisions);
+                }
+                None => {
+                    unresolved.insert((index, token));
+                }
+            }
+        }
+    }
+
+    let conflicts: Vec<_> =
+        conflicts.into_iter()
+                 .filter_map(|mut conflict| {
+                     let mut lookahead = TokenSet::new();
+                     for token in conflict.lookahead.iter() {
+                         if unresolved.contains(&(conflict.state, token)) {
+                             lookahead.insert(token);
+                         }
+                     }
+                     if lookahead.len() == 0 {
+                         None
+                     } else {
+                         conflict.lookahead = lookahead;
+                         Some(conflict)
+                     }
+                 })
+                 .collect();
+
+    if conflicts.is_empty() {
+        Ok(states)
+    } else {
+        Err(TableConstructionError {
+            states: states,
+            conflicts: conflicts,
+        })
+    }
+}
+
+/// The actions that `state` can take on `terminal`, each with the
+/// tokens after `terminal` that select it, or `None` if those sets
+/// overlap.
+fn decide<'grammar>(follow: &Follow2Sets,
+                    state: &LR1State<'grammar>,
+                    terminal: TerminalString)
+                    -> Option<Vec<(TokenSet, Action<'grammar>)>> {
+    let mut decisions = vec![];
+
+    if let Some(&next_index) = state.shifts.get(&terminal) {
+        let mut after = TokenSet::new();
+        for item in state.items.vec.iter().filter(|item| item.can_shift_terminal(terminal)) {
+            let rest = &item.production.symbols[item.index + 1..];
+            after.union_with(&follow.first_sets.first1(rest, &item.lookahead));
+        }
+        decisions.push((after, Action::Shift(terminal, next_index)));
+    }
+
+    for &(ref lookahead, production) in &state.reductions {
+        if lookahead.contains(Token::Terminal(terminal)) {
+            let after = follow.after(production.nonterminal, terminal);
+            decisions.push((after, Action::Reduce(production)));
+        }
+    }
+
+    for (index, &(ref after, _)) in decisions.iter().enumerate() {
+        if decisions[index + 1..].iter().any(|&(ref other, _)| after.is_intersecting(other)) {
+            return None;
+        }
+    }
+
+    Some(decisions)
+}
+
+/// A string of at most two tokens. It is shorter only if the string
+/// it is a prefix of was.
+type Prefix = Vec<Token>;
+
+pub struct Follow2Sets {
+    first_sets: FirstSets,
+    follow: Map<NonterminalString, Set<Prefix>>,
+}
+
+impl Follow2Sets {
+    pub fn new(grammar: &Grammar, start: NonterminalString) -> Follow2Sets {
+        let productions = || grammar.nonterminals.values().flat_map(|data| &data.productions);
+
+        let mut first = map();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for production in productions() {
+                let prefixes = first2(&first, &production.symbols);
+                let first_set = first.entry(production.nonterminal).or_insert_with(set);
+                for prefix in prefixes {
+                    changed |= first_set.insert(prefix);
+                }
+            }
+        }
+
+        let mut follow: Map<NonterminalString, Set<Prefix>> = map();
+        follow.entry(start).or_insert_with(set).insert(vec![Token::EOF]);
+        changed = true;
+        while changed {
+            changed = false;
+            for production in productions() {
+                let follow_set = follow.get(&production.nonterminal).cloned().unwrap_or(set());
+                for (index, &symbol) in production.symbols.iter().enumerate() {
+                    if let Symbol::Nonterminal(nt) = symbol {
+                        let rest = first2(&first, &production.symbols[index + 1..]);
+                        let prefixes = concat(&rest, &follow_set);
+                        let nt_set = follow.entry(nt).or_insert_with(set);
+                        for prefix in prefixes {
+                            changed |= nt_set.insert(prefix);
+                        }
+                    }
+                }
+            }
+        }
+
+        Follow2Sets {
+            first_sets: FirstSets::new(grammar),
+            follow: follow,
+        }
+    }
+
+    /// The tokens that can come right after `terminal` when it follows
+    /// `nt`.
+    pub fn after(&self, nt: NonterminalString, terminal: TerminalString) -> TokenSet {
+        let mut result = TokenSet::new();
+        if let Some(prefixes) = self.follow.get(&nt) {
+            for prefix in prefixes {
+                if prefix.len() == 2 && prefix[0] == Token::Terminal(terminal) {
+                    result.insert(prefix[1]);
+                }
+            }
+        }
+        result
+    }
+}
+
+/// The prefixes of at most two tokens of the strings that `symbols`
+/// derive, given those of each nonterminal found so far.
+fn first2(first: &Map<NonterminalString, Set<Prefix>>, symbols: &[Symbol]) -> Set<Prefix> {
+    let mut result = set();
+    result.insert(vec![]);
+    for &symbol in symbols {
+        if result.iter().all(|prefix| prefix.len() == 2) {
+            break;
+        }
+        let prefixes = match symbol {
+            Symbol::Terminal(terminal) => {
+                let mut prefixes = set();
+                prefixes.insert(vec![Token::Terminal(terminal)]);
+                prefixes
+            }
+            Symbol::Nonterminal(nt) => first.get(&nt).cloned().unwrap_or(set()),
+        };
+        result = concat(&result, &prefixes);
+    }
+    result
+}
+
+fn concat(prefixes: &Set<Prefix>, suffixes: &Set<Prefix>) -> Set<Prefix> {
+    let mut result = set();
+    for prefix in prefixes {
+        if prefix.len() == 2 {
+            result.insert(prefix.clone());
+            continue;
+        }
+        for suffix in suffixes {
+            let mut prefix = prefix.clone();
+            prefix.extend(suffix.iter().cloned());
+            prefix.truncate(2);
+            result.insert(prefix);
+        }
+    }
+    result
+}
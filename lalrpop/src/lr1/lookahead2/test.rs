@@ -0,0 +1,127 @@
+use intern::intern;
+use grammar::repr::*;
+use lr1::build_states;
+use lr1::interpret::interpret;
+use lr1::lookahead::{Token, TokenSet};
+use lr1::tls::Lr1Tls;
+use test_util::normalized_grammar;
+use tls::Tls;
+
+use super::Follow2Sets;
+
+fn nt(t: &str) -> NonterminalString {
+    NonterminalString(intern(t))
+}
+
+macro_rules! tokens {
+    ($($x:expr),*) => {
+        vec![$(TerminalString::quoted(intern($x))),*]
+    }
+}
+
+fn grammar(annotation: &str, body: &str) -> Grammar {
+    normalized_grammar(&format!(r#"
+        {}
+        grammar;
+        extern {{ enum Tok {{ "a" => .., "x" => .., "y" => .., "z" => .. }} }}
+        {}
+    "#, annotation, body))
+}
+
+// `A` and `B` can only be told apart by the token after the "x"
+const REDUCE_REDUCE: &'static str = r#"
+    S: () = {
+        A "x" "y"   => (),
+        B "x" "z"   => ()
+    };
+    A: () = "a"     => ();
+    B: () = "a"     => ();
+"#;
+
+#[test]
+fn reduce_reduce() {
+    let _tls = Tls::test();
+    let grammar = grammar("", REDUCE_REDUCE);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    assert!(build_states(&grammar, nt("S")).is_err());
+}
+
+#[test]
+fn reduce_reduce_lookahead2() {
+    let _tls = Tls::test();
+    let grammar = grammar("#[lookahead2]", REDUCE_REDUCE);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let states = build_states(&grammar, nt("S")).unwrap();
+    assert_eq!(states.iter().filter(|state| !state.lookahead2.is_empty()).count(), 1);
+
+    let tree = interpret(&states, tokens!["a", "x", "y"]).unwrap();
+    assert_eq!(&format!("{:?}", tree)[..], r#"[S: [A: "a"], "x", "y"]"#);
+    let tree = interpret(&states, tokens!["a", "x", "z"]).unwrap();
+    assert_eq!(&format!("{:?}", tree)[..], r#"[S: [B: "a"], "x", "z"]"#);
+    assert!(interpret(&states, tokens!["a", "x"]).is_err());
+}
+
+#[test]
+fn shift_reduce_lookahead2() {
+    let _tls = Tls::test();
+    let grammar = grammar("#[lookahead2]", r#"
+        S: () = {
+            A "x" "y"       => (),
+            "a" "x" "z"     => ()
+        };
+        A: () = "a"         => ();
+    "#);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let states = build_states(&grammar, nt("S")).unwrap();
+
+    let tree = interpret(&states, tokens!["a", "x", "y"]).unwrap();
+    assert_eq!(&format!("{:?}", tree)[..], r#"[S: [A: "a"], "x", "y"]"#);
+    let tree = interpret(&states, tokens!["a", "x", "z"]).unwrap();
+    assert_eq!(&format!("{:?}", tree)[..], r#"[S: "a", "x", "z"]"#);
+}
+
+#[test]
+fn not_lr2() {
+    let _tls = Tls::test();
+    let grammar = grammar("#[lookahead2]", r#"
+        S: () = {
+            A "x" "y"   => (),
+            B "x" "y"   => ()
+        };
+        A: () = "a"     => ();
+        B: () = "a"     => ();
+    "#);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let error = build_states(&grammar, nt("S")).unwrap_err();
+    assert_eq!(error.conflicts.len(), 1);
+    assert_eq!(error.conflicts[0].lookahead,
+               TokenSet::from(Token::Terminal(TerminalString::quoted(intern("x")))));
+}
+
+#[test]
+fn follow2() {
+    let _tls = Tls::test();
+    let grammar = grammar("", r#"
+        S: () = {
+            A "x" B     => (),
+            A "y"       => ()
+        };
+        A: () = "a"     => ();
+        B: () = {
+            "z"         => (),
+            => ()
+        };
+    "#);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let follow = Follow2Sets::new(&grammar, nt("S"));
+
+    let after = |nt_name: &str, terminal: &str| {
+        let set = follow.after(nt(nt_name), TerminalString::quoted(intern(terminal)));
+        let mut tokens: Vec<_> = set.iter().map(|token| format!("{}", token)).collect();
+        tokens.sort();
+        tokens
+    };
+    assert_eq!(after("A", "x"), vec![r#""z""#, "EOF"]);
+    assert_eq!(after("A", "y"), vec!["EOF"]);
+    assert!(after("A", "z").is_empty());
+}
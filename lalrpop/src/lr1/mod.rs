@@ -1,38 +1,95 @@
 //! Naive LR(1) generation algorithm.
 
 use grammar::repr::*;
+use tls::Tls;
 
 pub mod codegen;
 mod build;
+mod build_ielr;
 mod build_lalr;
+mod build_slr;
+mod conflict_hook;
 mod core;
+mod dot;
 mod error;
 mod example;
 mod first;
 mod lane_table;
 mod lookahead;
+mod lookahead2;
+mod precedence;
+mod prefer_shift;
+mod sentence;
 mod state_graph;
 mod tls;
 mod trace;
 mod report;
 use std::io::{self, Write};
 
-#[cfg(test)] mod interpret;
+pub mod interpret;
 
-pub use self::core::{LR1Result, LR1TableConstructionError};
-pub use self::error::report_error;
+pub use self::core::{Action, LR1Result, LR1State, LR1TableConstructionError, StateIndex};
+pub use self::error::{conflict_svgs, report_error, report_shift_warnings};
+pub use self::lookahead::Token;
 pub use self::tls::Lr1Tls;
 
 pub fn build_states<'grammar>(grammar: &'grammar Grammar,
                               start: NonterminalString)
                               -> LR1Result<'grammar> {
-    if !grammar.algorithm.lalr {
-        build::build_lr1_states(grammar, start)
+    let result = match grammar.algorithm.construction {
+        Some(LrConstruction::Lalr) => build_lalr::build_lalr_states(grammar, start),
+        Some(LrConstruction::Ielr) => build_ielr::build_ielr_states(grammar, start),
+        Some(LrConstruction::Slr) => build_slr::build_slr_states(grammar, start),
+        Some(LrConstruction::Lr1) |
+        Some(LrConstruction::LaneTable) |
+        None => build::build_lr1_states(grammar, start),
+    };
+
+    // an automaton cut short by `max_states` is incomplete, so there is
+    // no point in resolving its conflicts
+    let too_big = match result {
+        Ok(ref states) => exceeds_max_states(states),
+        Err(ref error) => exceeds_max_states(&error.states),
+    };
+    if too_big {
+        return result;
+    }
+
+    let result = if grammar.uses_precedence() {
+        result.or_else(precedence::resolve_conflicts)
+    } else {
+        result
+    };
+
+    let result = if grammar.algorithm.lookahead2 {
+        result.or_else(|error| {
+            lookahead2::resolve_conflicts(grammar, start, error.states, error.conflicts)
+        })
+    } else {
+        result
+    };
+
+    if grammar.uses_conflict_hooks() {
+        result.or_else(|error| conflict_hook::resolve_conflicts(grammar, error))
     } else {
-        build_lalr::build_lalr_states(grammar, start)
+        result
     }
 }
 
+/// Whether there are more `states` than `Session::max_states` allows.
+/// The canonical LR(1) construction stops as soon as there are, so the
+/// states are then only good for seeing where they come from.
+pub fn exceeds_max_states(states: &[LR1State]) -> bool {
+    Tls::session().max_states.map_or(false, |max| states.len() > max)
+}
+
+/// Resolves the shift/reduce conflicts in `error` by shifting, for
+/// grammars marked `#[prefer_shift]`.
+pub fn prefer_shift<'grammar>(error: LR1TableConstructionError<'grammar>)
+                              -> LR1Result<'grammar> {
+    prefer_shift::resolve_conflicts(error)
+}
+
 pub fn generate_report<'grammar, W : Write + 'grammar>
     ( out:          &'grammar mut W
     , lr1result:    &LR1Result<'grammar>
@@ -40,3 +97,22 @@ pub fn generate_report<'grammar, W : Write + 'grammar>
 {
     report::generate_report(out, lr1result)
 }
+
+/// Writes the automaton of `lr1result`, the parser for `start`, as a
+/// Graphviz `.dot` file.
+pub fn generate_dot<'grammar, W: Write>(out: &mut W,
+                                        start: NonterminalString,
+                                        lr1result: &LR1Result<'grammar>)
+                                        -> io::Result<()> {
+    dot::generate_dot(out, start, lr1result)
+}
+
+/// Writes the states of `lr1result`, and its conflicts with pictures
+/// of their examples, as a single HTML page titled `title`.
+pub fn generate_html_report<'grammar, W: Write>(out: &mut W,
+                                                grammar: &'grammar Grammar,
+                                                title: &str,
+                                                lr1result: &LR1Result<'grammar>)
+                                                -> io::Result<()> {
+    report::generate_html_report(out, grammar, title, lr1result)
+}
@@ -0,0 +1,141 @@
+//! Resolving shift/reduce conflicts with the `#[precedence(N)]` and
+//! `#[assoc(A)]` annotations on alternatives, as yacc does with
+//! `%left`, `%right` and `%nonassoc`. This lets `E "+" E` and
+//! `E "*" E` live in one nonterminal instead of a tier each:
+//!
+//! ```ignore
+//! Expr = {
+//!     #[precedence(1)] #[assoc(left)] Expr "+" Expr,
+//!     #[precedence(2)] #[assoc(left)] Expr "*" Expr,
+//!     Num,
+//! };
+//! ```
+//!
+//! When a state can reduce a production P or shift a terminal, we
+//! compare the precedence of P with that of the productions in the
+//! state that shift the terminal. If P's is greater than all of them,
+//! we reduce; if it is less, we shift; if they are all equal, P's
+//! associativity decides: left reduces, right shifts, and none makes
+//! the terminal a syntax error. Anything else, including a production
+//! without a precedence or another reduction on the same terminal, is
+//! left as a conflict.
+
+use collections::{set, Set};
+use grammar::repr::*;
+use lr1::core::*;
+use lr1::lookahead::{Token, TokenSet};
+use std::cmp::Ordering;
+
+#[cfg(test)]
+mod test;
+
+pub fn resolve_conflicts<'grammar>(error: LR1TableConstructionError<'grammar>)
+                                   -> LR1Result<'grammar> {
+    let LR1TableConstructionError { mut states, conflicts } = error;
+
+    let mut resolved: Set<(StateIndex, Token)> = set();
+    for conflict in &conflicts {
+        let terminal = match conflict.action {
+            Action::Shift(terminal, _) => terminal,
+            Action::Reduce(_) => continue,
+        };
+        let token = Token::Terminal(terminal);
+        let precedence = match conflict.production.precedence {
+            Some(precedence) => precedence,
+            None => continue,
+        };
+
+        let state = &mut states[conflict.state.0];
+        let reductions = state.reductions
+                              .iter()
+                              .filter(|&&(ref lookahead, _)| lookahead.contains(token))
+                              .count();
+        if reductions != 1 {
+            continue;
+        }
+
+        let (shift, reduce) = match compare(state, terminal, precedence) {
+            Some(Ordering::Less) => (true, false),
+            Some(Ordering::Greater) => (false, true),
+            Some(Ordering::Equal) => {
+                match precedence.assoc {
+                    Some(Assoc::Left) => (false, true),
+                    Some(Assoc::Right) => (true, false),
+                    Some(Assoc::NonAssoc) => (false, false),
+                    None => continue,
+                }
+            }
+            None => continue,
+        };
+
+        if !shift {
+            state.shifts.remove(&terminal);
+        }
+        if !reduce {
+            for reduction in &mut state.reductions {
+                if reduction.1 == conflict.production {
+                    reduction.0 = without(&reduction.0, token);
+                }
+            }
+        }
+        resolved.insert((conflict.state, token));
+    }
+
+    let conflicts: Vec<_> =
+        conflicts.into_iter()
+                 .filter_map(|mut conflict| {
+                     let mut lookahead = TokenSet::new();
+                     for token in conflict.lookahead.iter() {
+                         if !resolved.contains(&(conflict.state, token)) {
+                             lookahead.insert(token);
+                         }
+                     }
+                     if lookahead.len() == 0 {
+                         None
+                     } else {
+                         conflict.lookahead = lookahead;
+                         Some(conflict)
+                     }
+                 })
+                 .collect();
+
+    if conflicts.is_empty() {
+        Ok(states)
+    } else {
+        Err(TableConstructionError {
+            states: states,
+            conflicts: conflicts,
+        })
+    }
+}
+
+/// How `precedence`, that of a production to reduce, compares with
+/// the precedence of every item of `state` that shifts `terminal`, or
+/// `None` if it is not the same for them all or one has none.
+fn compare(state: &LR1State,
+           terminal: TerminalString,
+           precedence: Precedence)
+           -> Option<Ordering> {
+    let mut result = None;
+    for item in state.items.vec.iter().filter(|item| item.can_shift_terminal(terminal)) {
+        let ordering = match item.production.precedence {
+            Some(shifted) => precedence.level.cmp(&shifted.level),
+            None => return None,
+        };
+        match result {
+            Some(previous) if previous != ordering => return None,
+            _ => result = Some(ordering),
+        }
+    }
+    result
+}
+
+fn without(lookahead: &TokenSet, token: Token) -> TokenSet {
+    let mut result = TokenSet::new();
+    for t in lookahead.iter() {
+        if t != token {
+            result.insert(t);
+        }
+    }
+    result
+}
@@ -0,0 +1,75 @@
+use intern::intern;
+use grammar::repr::*;
+use lr1::build_states;
+use lr1::interpret::interpret;
+use lr1::tls::Lr1Tls;
+use test_util::normalized_grammar;
+use tls::Tls;
+
+fn nt(t: &str) -> NonterminalString {
+    NonterminalString(intern(t))
+}
+
+macro_rules! tokens {
+    ($($x:expr),*) => {
+        vec![$(TerminalString::quoted(intern($x))),*]
+    }
+}
+
+#[test]
+fn arithmetic() {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(r#"
+        grammar;
+        S: () = E => ();
+        E: () = {
+            #[precedence(1)] #[assoc(left)] E "+" E => (),
+            #[precedence(2)] #[assoc(left)] E "*" E => (),
+            #[precedence(3)] #[assoc(right)] E "^" E => (),
+            "x" => ()
+        };
+    "#);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let states = build_states(&grammar, nt("S")).unwrap();
+
+    let tree = interpret(&states, tokens!["x", "+", "x", "*", "x", "+", "x"]).unwrap();
+    assert_eq!(&format!("{:?}", tree)[..],
+               r#"[S: [E: [E: [E: "x"], "+", [E: [E: "x"], "*", [E: "x"]]], "+", [E: "x"]]]"#);
+
+    let tree = interpret(&states, tokens!["x", "^", "x", "^", "x"]).unwrap();
+    assert_eq!(&format!("{:?}", tree)[..],
+               r#"[S: [E: [E: "x"], "^", [E: [E: "x"], "^", [E: "x"]]]]"#);
+}
+
+#[test]
+fn nonassoc() {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(r#"
+        grammar;
+        S: () = E => ();
+        E: () = {
+            #[precedence(1)] #[assoc(none)] E "==" E => (),
+            "x" => ()
+        };
+    "#);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let states = build_states(&grammar, nt("S")).unwrap();
+
+    assert!(interpret(&states, tokens!["x", "==", "x"]).is_ok());
+    assert!(interpret(&states, tokens!["x", "==", "x", "==", "x"]).is_err());
+}
+
+#[test]
+fn without_assoc() {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(r#"
+        grammar;
+        E: () = {
+            #[precedence(1)] E "-" E => (),
+            "x" => ()
+        };
+    "#);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let error = build_states(&grammar, nt("E")).unwrap_err();
+    assert!(!error.conflicts.is_empty());
+}
@@ -0,0 +1,71 @@
+//! Resolving shift/reduce conflicts in favor of the shift, as yacc
+//! does by default, for grammars marked `#[prefer_shift]`. This is
+//! the classic way to handle a dangling `else`: it attaches to the
+//! innermost `if`.
+//!
+//! A terminal that a state can shift is simply removed from the
+//! lookahead of all its reductions, so reduce/reduce conflicts on that
+//! terminal go away too. Reduce/reduce conflicts on other terminals
+//! are still errors.
+
+use collections::{map, set, Map, Set};
+use lr1::core::*;
+use lr1::lookahead::{Token, TokenSet};
+
+#[cfg(test)]
+mod test;
+
+/// Shifts wherever `error` has a shift/reduce conflict. Reports any
+/// conflicts that remain.
+pub fn resolve_conflicts<'grammar>(error: LR1TableConstructionError<'grammar>)
+                                   -> LR1Result<'grammar> {
+    let LR1TableConstructionError { mut states, conflicts } = error;
+
+    let mut shifted: Map<StateIndex, Set<Token>> = map();
+    for conflict in &conflicts {
+        if let Action::Shift(..) = conflict.action {
+            shifted.entry(conflict.state)
+                   .or_insert_with(set)
+                   .extend(conflict.lookahead.iter());
+        }
+    }
+
+    for (&index, tokens) in &shifted {
+        for reduction in &mut states[index.0].reductions {
+            reduction.0 = without(&reduction.0, tokens);
+        }
+    }
+
+    let conflicts: Vec<_> =
+        conflicts.into_iter()
+                 .filter_map(|mut conflict| {
+                     if let Some(tokens) = shifted.get(&conflict.state) {
+                         conflict.lookahead = without(&conflict.lookahead, tokens);
+                     }
+                     if conflict.lookahead.len() == 0 {
+                         None
+                     } else {
+                         Some(conflict)
+                     }
+                 })
+                 .collect();
+
+    if conflicts.is_empty() {
+        Ok(states)
+    } else {
+        Err(TableConstructionError {
+            states: states,
+            conflicts: conflicts,
+        })
+    }
+}
+
+fn without(lookahead: &TokenSet, tokens: &Set<Token>) -> TokenSet {
+    let mut result = TokenSet::new();
+    for token in lookahead.iter() {
+        if !tokens.contains(&token) {
+            result.insert(token);
+        }
+    }
+    result
+}
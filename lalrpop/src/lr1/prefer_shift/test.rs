@@ -0,0 +1,63 @@
+use intern::intern;
+use grammar::repr::*;
+use lr1::build_states;
+use lr1::error::report_shift_warnings;
+use lr1::interpret::interpret;
+use lr1::tls::Lr1Tls;
+use test_util::normalized_grammar;
+use tls::Tls;
+
+use super::resolve_conflicts;
+
+fn nt(t: &str) -> NonterminalString {
+    NonterminalString(intern(t))
+}
+
+macro_rules! tokens {
+    ($($x:expr),*) => {
+        vec![$(TerminalString::quoted(intern($x))),*]
+    }
+}
+
+#[test]
+fn dangling_else() {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(r#"
+        #[prefer_shift]
+        grammar;
+        S: () = {
+            "if" "c" S              => (),
+            "if" "c" S "else" S     => (),
+            "x"                     => ()
+        };
+    "#);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let error = build_states(&grammar, nt("S")).unwrap_err();
+    assert_eq!(report_shift_warnings(&grammar, &error).len(), 1);
+    let states = resolve_conflicts(error).unwrap();
+
+    // the `else` goes with the innermost `if`
+    let tree = interpret(&states, tokens!["if", "c", "if", "c", "x", "else", "x"]).unwrap();
+    assert_eq!(&format!("{:?}", tree)[..],
+               r#"[S: "if", "c", [S: "if", "c", [S: "x"], "else", [S: "x"]]]"#);
+}
+
+#[test]
+fn reduce_reduce() {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(r#"
+        #[prefer_shift]
+        grammar;
+        S: () = {
+            A "x"   => (),
+            B "x"   => ()
+        };
+        A: () = "a" => ();
+        B: () = "a" => ();
+    "#);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let error = build_states(&grammar, nt("S")).unwrap_err();
+    assert!(report_shift_warnings(&grammar, &error).is_empty());
+    let error = resolve_conflicts(error).unwrap_err();
+    assert_eq!(error.conflicts.len(), 1);
+}
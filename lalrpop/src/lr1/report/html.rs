@@ -0,0 +1,185 @@
+//! The report on the states of a grammar as a single, self-contained
+//! HTML page, for sharing with people who do not run LALRPOP
+//! themselves: a summary, each conflict with the pictures of its
+//! examples, and the states, which can be searched, and whose items
+//! are folded away until asked for.
+
+use grammar::repr::*;
+use lr1::core::*;
+use lr1::error::{conflict_pictures, TokenConflict};
+use std::io::{self, Write};
+use util::{Prefix, XmlEscape};
+
+use super::{get_width_for_gotos, ReportGenerator};
+
+const STYLE: &'static str = "body { font-family: sans-serif; margin: 2em; } \
+                             pre { margin: 0.5em 0 0.5em 1em; } \
+                             .conflict, .state { border-top: 1px solid #ccc; } \
+                             .conflicted h3 { color: #c00000; } \
+                             .pictures svg { display: block; margin: 1em 0; } \
+                             #search { width: 30em; font-size: 100%; }";
+
+/// Hides the states that do not mention the text in the search box.
+const SCRIPT: &'static str = "document.getElementById('search').oninput = function () { \
+                              var query = this.value.toLowerCase(); \
+                              var states = document.getElementsByClassName('state'); \
+                              for (var i = 0; i < states.length; i++) { \
+                              var text = states[i].textContent.toLowerCase(); \
+                              states[i].style.display = text.indexOf(query) < 0 ? 'none' : ''; \
+                              } \
+                              };";
+
+pub fn generate_html_report<'grammar, W: Write>(out: &mut W,
+                                                grammar: &'grammar Grammar,
+                                                title: &str,
+                                                lr1result: &LR1Result<'grammar>)
+                                                -> io::Result<()> {
+    let (states, conflicts) = match *lr1result {
+        Ok(ref states) => (states, vec![]),
+        Err(ref error) => (&error.states, conflict_pictures(grammar, error)),
+    };
+
+    try!(writeln!(out, "<!DOCTYPE html>"));
+    try!(writeln!(out, "<html>"));
+    try!(writeln!(out, "<head>"));
+    try!(writeln!(out, "<meta charset=\"utf-8\">"));
+    try!(writeln!(out, "<title>{}</title>", XmlEscape(title)));
+    try!(writeln!(out, "<style>{}</style>", STYLE));
+    try!(writeln!(out, "</head>"));
+    try!(writeln!(out, "<body>"));
+    try!(writeln!(out, "<h1>{}</h1>", XmlEscape(title)));
+
+    let shift_reduce = conflicts.iter()
+                                .filter(|&&(ref conflict, _)| match conflict.action {
+                                    Action::Shift(..) => true,
+                                    Action::Reduce(_) => false,
+                                })
+                                .count();
+    try!(writeln!(out,
+                  "<p>{} states, {} shift/reduce and {} reduce/reduce conflicts.</p>",
+                  states.len(),
+                  shift_reduce,
+                  conflicts.len() - shift_reduce));
+
+    if !conflicts.is_empty() {
+        try!(writeln!(out, "<h2>Conflicts</h2>"));
+        for (index, &(ref conflict, ref svgs)) in conflicts.iter().enumerate() {
+            try!(write_conflict(out, index, conflict, svgs));
+        }
+    }
+
+    try!(writeln!(out, "<h2>States</h2>"));
+    try!(writeln!(out,
+                  "<p><input id=\"search\" type=\"search\" \
+                   placeholder=\"Show the states that mention...\"></p>"));
+    for state in states {
+        let count = conflicts.iter().filter(|&&(ref c, _)| c.state == state.index).count();
+        try!(write_state(out, state, count));
+    }
+
+    try!(writeln!(out, "<script>\n{}\n</script>", SCRIPT));
+    try!(writeln!(out, "</body>"));
+    try!(writeln!(out, "</html>"));
+    Ok(())
+}
+
+fn write_conflict<'grammar, W: Write>(out: &mut W,
+                                      index: usize,
+                                      conflict: &TokenConflict<'grammar>,
+                                      svgs: &Option<(String, String)>)
+                                      -> io::Result<()> {
+    let kind = match conflict.action {
+        Action::Shift(..) => "shift/reduce",
+        Action::Reduce(_) => "reduce/reduce",
+    };
+    try!(writeln!(out, "<div class=\"conflict\" id=\"conflict{}\">", index + 1));
+    try!(writeln!(out,
+                  "<h3>Conflict {}: {} in <a href=\"#{:?}\">state {}</a> \
+                   on <code>{}</code></h3>",
+                  index + 1,
+                  kind,
+                  conflict.state,
+                  conflict.state,
+                  XmlEscape(conflict.lookahead)));
+    try!(writeln!(out, "<ul>"));
+    try!(writeln!(out,
+                  "<li>reduce <code>{}</code></li>",
+                  XmlEscape(production_text(conflict.production))));
+    match conflict.action {
+        Action::Shift(terminal, next) => {
+            try!(writeln!(out,
+                          "<li>shift <code>{}</code> and go to \
+                           <a href=\"#{:?}\">state {}</a></li>",
+                          XmlEscape(terminal),
+                          next,
+                          next));
+        }
+        Action::Reduce(other) => {
+            try!(writeln!(out,
+                          "<li>reduce <code>{}</code></li>",
+                          XmlEscape(production_text(other))));
+        }
+    }
+    try!(writeln!(out, "</ul>"));
+    match *svgs {
+        Some((ref action, ref reduce)) => {
+            try!(writeln!(out, "<div class=\"pictures\">"));
+            try!(write!(out, "{}{}", action, reduce));
+            try!(writeln!(out, "</div>"));
+        }
+        None => {
+            try!(writeln!(out, "<p>No example inputs could be found.</p>"));
+        }
+    }
+    try!(writeln!(out, "</div>"));
+    Ok(())
+}
+
+fn write_state<'grammar, W: Write>(out: &mut W,
+                                   state: &LR1State<'grammar>,
+                                   conflicts: usize)
+                                   -> io::Result<()> {
+    // the items and the actions are laid out as in the text report
+    let mut items: Vec<u8> = vec![];
+    let mut actions: Vec<u8> = vec![];
+    {
+        let mut generator = ReportGenerator::new(&mut items);
+        try!(generator.write_items(&state.items));
+    }
+    {
+        let max_width = get_width_for_gotos(state);
+        let mut generator = ReportGenerator::new(&mut actions);
+        try!(generator.write_reductions(&state.reductions));
+        if !state.shifts.is_empty() {
+            try!(writeln!(generator.out, ""));
+            try!(generator.write_shifts(&state.shifts, max_width));
+        }
+        if !state.gotos.is_empty() {
+            try!(writeln!(generator.out, ""));
+            try!(generator.write_gotos(&state.gotos, max_width));
+        }
+    }
+
+    if conflicts > 0 {
+        try!(writeln!(out, "<div class=\"state conflicted\" id=\"{:?}\">", state.index));
+        try!(writeln!(out, "<h3>State {} ({} conflicts)</h3>", state.index, conflicts));
+    } else {
+        try!(writeln!(out, "<div class=\"state\" id=\"{:?}\">", state.index));
+        try!(writeln!(out, "<h3>State {}</h3>", state.index));
+    }
+    try!(writeln!(out, "<details>"));
+    try!(writeln!(out, "<summary>{} items</summary>", state.items.vec.len()));
+    try!(writeln!(out,
+                  "<pre>{}</pre>",
+                  XmlEscape(String::from_utf8_lossy(&items).trim())));
+    try!(writeln!(out, "</details>"));
+    try!(writeln!(out,
+                  "<pre>{}</pre>",
+                  XmlEscape(String::from_utf8_lossy(&actions).trim())));
+    try!(writeln!(out, "</div>"));
+    Ok(())
+}
+
+fn production_text(production: &Production) -> String {
+    format!("{} ->{}", production.nonterminal, Prefix(" ", &production.symbols[..]))
+}
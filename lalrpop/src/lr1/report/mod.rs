@@ -6,6 +6,12 @@ use collections::*;
 
 use super::lookahead::*;
 
+mod html;
+#[cfg(test)]
+mod test;
+
+pub use self::html::generate_html_report;
+
 pub fn generate_report<'grammar, W: Write + 'grammar>(out: &'grammar mut W,
                                                       lr1result: &LR1Result<'grammar>)
                                                       -> io::Result<()> {
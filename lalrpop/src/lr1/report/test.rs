@@ -0,0 +1,59 @@
+use intern::intern;
+use grammar::repr::*;
+use lr1::build_states;
+use lr1::tls::Lr1Tls;
+use test_util::normalized_grammar;
+use tls::Tls;
+
+use super::generate_html_report;
+
+fn html_report(grammar: &str) -> String {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(grammar);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let lr1result = build_states(&grammar, NonterminalString(intern("Ty")));
+    let mut out = vec![];
+    generate_html_report(&mut out, &grammar, "ty.lalrpop: Ty", &lr1result).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn html_report_with_conflict() {
+    let html = html_report(r#"
+grammar;
+pub Ty: () = {
+    "int" => (),
+    "bool" => (),
+    <t1:Ty> "->" <t2:Ty> => (),
+};
+"#);
+    assert!(html.contains("<title>ty.lalrpop: Ty</title>"));
+    assert!(html.contains(" shift/reduce and 0 reduce/reduce conflicts"), "{}", html);
+    assert!(html.contains("<h3>Conflict 1: shift/reduce in <a href=\"#S"), "{}", html);
+    assert!(html.contains("on <code>&quot;-&gt;&quot;</code>"), "{}", html);
+    assert!(html.contains("<li>reduce <code>Ty -&gt; Ty &quot;-&gt;&quot; Ty</code></li>"));
+
+    // both examples of each conflict are drawn inline
+    assert_eq!(html.matches("<svg ").count(), 2 * html.matches("<h3>Conflict ").count());
+
+    // every state can be searched and has its items folded away
+    assert!(html.contains("<div class=\"state\" id=\"S0\">"));
+    assert!(html.contains("<div class=\"state conflicted\" id=\"S"));
+    assert!(html.contains("<input id=\"search\""));
+    assert_eq!(html.matches("<details>").count(), html.matches("class=\"state").count());
+}
+
+#[test]
+fn html_report_without_conflict() {
+    let html = html_report(r#"
+grammar;
+pub Ty: () = {
+    "int" => (),
+    "(" Ty ")" => (),
+};
+"#);
+    assert!(html.contains("0 shift/reduce and 0 reduce/reduce conflicts"), "{}", html);
+    assert!(!html.contains("<h2>Conflicts</h2>"));
+    assert!(!html.contains("<svg "));
+    assert!(html.contains("<h2>States</h2>"));
+}
@@ -0,0 +1,144 @@
+//! Short inputs that run into a conflict. The examples of conflict
+//! reports are made of nonterminals, which say how the parser got
+//! there; a sentence of terminals like `x + y * z` says what the user
+//! would have to type, which is often easier to take in.
+//!
+//! We search breadth-first over the stacks of states that the parser
+//! can have, shifting one terminal at a time, so the first sentence
+//! found is a shortest one. Where the automaton has conflicts, every
+//! action is followed. The search gives up after `MAX_TERMINALS`
+//! terminals or `MAX_STACKS` distinct stacks.
+
+use collections::{set, Set};
+use grammar::repr::*;
+use lr1::core::*;
+use lr1::lookahead::Token;
+use std::collections::VecDeque;
+
+#[cfg(test)]
+mod test;
+
+const MAX_TERMINALS: usize = 10;
+const MAX_STACKS: usize = 10_000;
+
+/// A shortest sentence after which the parser, looking at
+/// `lookahead`, is in the state `target`, if there is one that the
+/// search can find.
+pub fn sentence_to(grammar: &Grammar,
+                   states: &[LR1State],
+                   target: StateIndex,
+                   lookahead: Token)
+                   -> Option<Vec<TerminalString>> {
+    // `!` and `_` are not tokens that anyone can type
+    let terminals: Vec<TerminalString> = grammar.terminals
+                                                .all
+                                                .iter()
+                                                .cloned()
+                                                .filter(|&t| match t {
+                                                    TerminalString::Error |
+                                                    TerminalString::Any => false,
+                                                    _ => true,
+                                                })
+                                                .collect();
+
+    let start = vec![StateIndex(0)];
+    let mut seen: Set<Vec<StateIndex>> = set();
+    seen.insert(start.clone());
+    let mut queue = VecDeque::new();
+    queue.push_back((start, vec![]));
+    while let Some((stack, sentence)) = queue.pop_front() {
+        if reduce_to(states, &stack, lookahead).0.contains(&target) {
+            return Some(sentence);
+        }
+        if sentence.len() == MAX_TERMINALS {
+            continue;
+        }
+        for &terminal in &terminals {
+            for next in reduce_to(states, &stack, Token::Terminal(terminal)).1 {
+                if seen.len() == MAX_STACKS {
+                    return None;
+                }
+                if seen.insert(next.clone()) {
+                    let mut sentence = sentence.clone();
+                    sentence.push(terminal);
+                    queue.push_back((next, sentence));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Makes the reductions that `lookahead` calls for, starting from
+/// `stack`. Returns the states that end up on top of the stack along
+/// the way, and the stacks after shifting `lookahead` from any of
+/// them.
+fn reduce_to(states: &[LR1State],
+             stack: &[StateIndex],
+             lookahead: Token)
+             -> (Set<StateIndex>, Vec<Vec<StateIndex>>) {
+    let mut tops = set();
+    let mut shifted = vec![];
+
+    // a stack can come back after reductions of cycles like `X = X`,
+    // and grow forever after those of empty productions in conflicted
+    // states, so remember the ones already seen and stop at some point
+    let mut seen: Set<Vec<StateIndex>> = set();
+    let mut stacks = vec![stack.to_vec()];
+    while let Some(stack) = stacks.pop() {
+        if seen.len() == MAX_STACKS {
+            break;
+        }
+        if !seen.insert(stack.clone()) {
+            continue;
+        }
+        // construction stops at the first conflict unless something
+        // might resolve it, so a shift or goto may lead to a state
+        // that was never built
+        let state = match states.get(stack[stack.len() - 1].0) {
+            Some(state) => state,
+            None => continue,
+        };
+        tops.insert(state.index);
+
+        if let Token::Terminal(terminal) = lookahead {
+            if let Some(&next) = state.shifts.get(&terminal) {
+                let mut stack = stack.clone();
+                stack.push(next);
+                shifted.push(stack);
+            }
+        }
+
+        for &(ref tokens, production) in &state.reductions {
+            if !tokens.contains(lookahead) {
+                continue;
+            }
+            let mut stack = stack.clone();
+            let len = stack.len() - production.symbols.len();
+            stack.truncate(len);
+            let below = &states[stack[len - 1].0];
+            if let Some(&next) = below.gotos.get(&production.nonterminal) {
+                stack.push(next);
+                stacks.push(stack);
+            }
+        }
+    }
+
+    (tops, shifted)
+}
+
+/// The sentence as the user would type it: quoted terminals without
+/// their quotes, the others by their names.
+pub fn sentence_text(sentence: &[TerminalString]) -> String {
+    sentence.iter()
+            .map(|&terminal| terminal_text(terminal))
+            .collect::<Vec<_>>()
+            .join(" ")
+}
+
+pub fn terminal_text(terminal: TerminalString) -> String {
+    match terminal {
+        TerminalString::Literal(TerminalLiteral::Quoted(s)) => s.to_string(),
+        _ => terminal.to_string(),
+    }
+}
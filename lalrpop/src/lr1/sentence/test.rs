@@ -0,0 +1,73 @@
+use intern::intern;
+use grammar::repr::*;
+use lr1::build_states;
+use lr1::core::StateIndex;
+use lr1::lookahead::Token;
+use lr1::tls::Lr1Tls;
+use test_util::normalized_grammar;
+use tls::Tls;
+
+use super::{sentence_text, sentence_to};
+
+fn nt(t: &str) -> NonterminalString {
+    NonterminalString(intern(t))
+}
+
+fn term(t: &str) -> Token {
+    Token::Terminal(TerminalString::quoted(intern(t)))
+}
+
+#[test]
+fn arithmetic() {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(r#"
+grammar;
+pub E: () = {
+    E "+" E => (),
+    E "*" E => (),
+    "x" => (),
+};
+"#);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let err = build_states(&grammar, nt("E")).unwrap_err();
+
+    // reducing `E "*" E` or shifting `"+"`
+    let conflict = err.conflicts
+                      .iter()
+                      .find(|c| c.production.symbols.len() == 3 &&
+                                c.production.symbols[1] ==
+                                Symbol::Terminal(TerminalString::quoted(intern("*"))) &&
+                                c.lookahead.contains(term("+")))
+                      .unwrap();
+    let sentence = sentence_to(&grammar, &err.states, conflict.state, term("+")).unwrap();
+    assert_eq!(sentence_text(&sentence), "x * x");
+
+    // every conflict has a sentence
+    for conflict in &err.conflicts {
+        for lookahead in conflict.lookahead.iter() {
+            assert!(sentence_to(&grammar, &err.states, conflict.state, lookahead).is_some());
+        }
+    }
+}
+
+#[test]
+fn at_the_start() {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(r#"
+grammar;
+pub S: () = {
+    A "x" => (),
+    B "x" "y" => (),
+};
+A: () = { => () };
+B: () = { => () };
+"#);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let err = build_states(&grammar, nt("S")).unwrap_err();
+
+    // reducing `A` or `B` before the first token
+    let conflict = &err.conflicts[0];
+    assert_eq!(conflict.state, StateIndex(0));
+    let sentence = sentence_to(&grammar, &err.states, conflict.state, term("x")).unwrap();
+    assert!(sentence.is_empty());
+}
@@ -382,10 +382,20 @@ impl<'graph, 'grammar> PathEnumerator<'graph, 'grammar> {
                 .collect();
         reductions.reverse();
 
+        let cost =
+            self.stack[1..]
+                .iter()
+                .map(|state| match self.graph.graph[state.index] {
+                    TraceGraphNode::Nonterminal(_) => 0,
+                    TraceGraphNode::Item(item) => item.production.cost,
+                })
+                .fold(0, |total: u32, cost| total.saturating_add(cost));
+
         Example {
             symbols: symbols,
             cursor: cursor,
-            reductions: reductions
+            reductions: reductions,
+            cost: cost,
         }
     }
 }
@@ -37,19 +37,249 @@ fn main1() -> io::Result<()> {
         config.always_use_colors();
     }
 
+    match args.flag_error_format.unwrap_or(ErrorFormatFlag::Human) {
+        ErrorFormatFlag::Human => { }
+        ErrorFormatFlag::Rustc => { config.use_rustc_error_format(); }
+    }
+
     if args.flag_comments {
         config.emit_comments(true);
     }
 
+    if args.flag_introspection {
+        config.emit_introspection(true);
+    }
+
+    if args.flag_observer {
+        config.emit_observer(true);
+    }
+
     if args.flag_report {
         config.emit_report(true);
     }
 
+    if args.flag_emit_dot {
+        config.emit_dot(true);
+    }
+
+    if args.flag_trivia {
+        config.snapshot_trivia(true);
+    }
+
+    if let Some(ref dir) = args.flag_html_report {
+        config.write_html_reports(dir);
+    }
+
+    if args.flag_prune_tables {
+        config.prune_tables(true);
+    }
+
+    if let Some(count) = args.flag_max_states {
+        config.max_states(count);
+    }
+
+    if let Some(count) = args.flag_max_table_entries {
+        config.max_table_entries(count);
+    }
+
+    if args.flag_binary_tables {
+        config.binary_tables(true);
+    }
+
+    if args.flag_dense_tables {
+        config.dense_tables(true);
+    }
+
+    if let Some(capacity) = args.flag_stack_capacity {
+        config.stack_capacity(capacity);
+    }
+
+    match args.flag_optimize {
+        None => { }
+        Some(OptimizeFlag::Size) => { config.optimize_for_size(); }
+        Some(OptimizeFlag::Speed) => { config.optimize_for_speed(); }
+    }
+
+    match args.flag_construction {
+        None | Some(ConstructionFlag::Lr1) => { }
+        Some(ConstructionFlag::Lane) => { config.use_lane_table(); }
+        Some(ConstructionFlag::Lalr) => { config.use_lalr(); }
+        Some(ConstructionFlag::Ielr) => { config.use_ielr(); }
+        Some(ConstructionFlag::Slr) => { config.use_slr(); }
+    }
+
+    match args.flag_stack_overflow.unwrap_or(StackOverflowFlag::Grow) {
+        StackOverflowFlag::Grow => { }
+        StackOverflowFlag::Error => { config.error_on_stack_overflow(); }
+        StackOverflowFlag::Abort => { config.abort_on_stack_overflow(); }
+    }
+
+    if args.cmd_snapshot {
+        let start = args.flag_start.as_ref().map(|s| &s[..]);
+        let result = if args.flag_verify {
+            config.verify_snapshots(&args.arg_grammar, &args.arg_corpus, start)
+        } else {
+            config.write_snapshots(&args.arg_grammar, &args.arg_corpus, start)
+        };
+        if let Err(err) = result {
+            try!(writeln!(stderr, "Error encountered snapshotting `{}`: {}",
+                          args.arg_corpus, err));
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if args.cmd_coverage {
+        let start = args.flag_start.as_ref().map(|s| &s[..]);
+        if let Err(err) = config.measure_coverage(&args.arg_grammar, &args.arg_corpus, start) {
+            try!(writeln!(stderr, "Error encountered measuring coverage of `{}`: {}",
+                          args.arg_grammar, err));
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if args.cmd_fuzz {
+        let fuzz_dir = args.flag_out.as_ref().map(|s| &s[..]).unwrap_or("fuzz");
+        let result = config.generate_fuzz_target(&args.arg_grammar, &args.arg_module, fuzz_dir);
+        if let Err(err) = result {
+            try!(writeln!(stderr, "Error encountered generating a fuzz target for `{}`: {}",
+                          args.arg_grammar, err));
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if args.cmd_ambiguity {
+        let start = args.flag_start.as_ref().map(|s| &s[..]);
+        let max_length = args.flag_max_length.unwrap_or(6);
+        if let Err(err) = config.check_ambiguity(&args.arg_grammar, start, max_length) {
+            try!(writeln!(stderr, "Error encountered checking `{}` for ambiguity: {}",
+                          args.arg_grammar, err));
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if args.cmd_metrics {
+        if let Err(err) = config.report_metrics(&args.arg_grammar) {
+            try!(writeln!(stderr, "Error encountered measuring `{}`: {}",
+                          args.arg_grammar, err));
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if args.cmd_diff {
+        if let Err(err) = config.diff_grammars(&args.arg_old, &args.arg_new) {
+            try!(writeln!(stderr, "Error encountered comparing `{}` with `{}`: {}",
+                          args.arg_old, args.arg_new, err));
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if args.cmd_docs {
+        if let Err(err) = config.document_grammar(&args.arg_grammar) {
+            try!(writeln!(stderr, "Error encountered documenting `{}`: {}",
+                          args.arg_grammar, err));
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if args.cmd_tokens {
+        if let Err(err) = config.list_tokens(&args.arg_grammar) {
+            try!(writeln!(stderr, "Error encountered listing the tokens of `{}`: {}",
+                          args.arg_grammar, err));
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if args.cmd_ir {
+        if let Err(err) = config.print_normalized_grammar(&args.arg_grammar) {
+            try!(writeln!(stderr, "Error encountered normalizing `{}`: {}",
+                          args.arg_grammar, err));
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if args.cmd_antlr {
+        if let Err(err) = config.export_antlr(&args.arg_grammar) {
+            try!(writeln!(stderr, "Error encountered exporting `{}` to ANTLR: {}",
+                          args.arg_grammar, err));
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if args.cmd_abnf {
+        if let Err(err) = config.import_abnf(&args.arg_abnf) {
+            try!(writeln!(stderr, "Error encountered translating `{}`: {}",
+                          args.arg_abnf, err));
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if args.cmd_parse {
+        let start = args.flag_start.as_ref().map(|s| &s[..]);
+        if let Err(err) = config.parse_samples(&args.arg_grammar,
+                                               &args.arg_samples,
+                                               start,
+                                               args.flag_tokens) {
+            try!(writeln!(stderr, "Error encountered parsing samples with `{}`: {}",
+                          args.arg_grammar, err));
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if args.cmd_explain {
+        let start = args.flag_start.as_ref().map(|s| &s[..]);
+        if let Err(err) = config.explain_input(&args.arg_grammar,
+                                               &args.arg_input,
+                                               start,
+                                               args.flag_tokens,
+                                               args.flag_steps) {
+            try!(writeln!(stderr, "Error encountered explaining `{}`: {}",
+                          args.arg_input, err));
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if args.cmd_simulate {
+        let start = args.flag_start.as_ref().map(|s| &s[..]);
+        if let Err(err) = config.simulate_input(&args.arg_grammar,
+                                                &args.arg_input,
+                                                start,
+                                                args.flag_tokens) {
+            try!(writeln!(stderr, "Error encountered simulating `{}`: {}",
+                          args.arg_input, err));
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
     if args.arg_inputs.len() == 0 {
         try!(writeln!(stderr, "Error: no input files specified! Try --help for help."));
         process::exit(1);
     }
 
+    if args.cmd_check {
+        for arg in args.arg_inputs {
+            if let Err(err) = config.check_file(&arg) {
+                try!(writeln!(stderr, "Error encountered checking `{}`: {}",
+                              arg, err));
+                process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
     for arg in args.arg_inputs {
         match config.process_file(&arg) {
             Ok(()) => { }
@@ -65,7 +295,22 @@ fn main1() -> io::Result<()> {
 }
 
 const USAGE: &'static str = "
-Usage: lalrpop [options] <inputs>...
+Usage: lalrpop snapshot [options] [--verify] [--trivia] [--start NT] <grammar> <corpus>
+       lalrpop coverage [options] [--start NT] <grammar> <corpus>
+       lalrpop fuzz [options] [--out DIR] <grammar> <module>
+       lalrpop ambiguity [options] [--start NT] [--max-length N] <grammar>
+       lalrpop metrics [options] <grammar>
+       lalrpop diff [options] <old> <new>
+       lalrpop docs [options] <grammar>
+       lalrpop tokens [options] <grammar>
+       lalrpop ir [options] <grammar>
+       lalrpop antlr [options] <grammar>
+       lalrpop abnf [options] <abnf>
+       lalrpop explain [options] [--start NT] [--tokens] [--steps] <grammar> <input>
+       lalrpop parse [options] [--start NT] [--tokens] <grammar> <samples>...
+       lalrpop simulate [options] [--start NT] [--tokens] <grammar> <input>
+       lalrpop check [options] <inputs>...
+       lalrpop [options] <inputs>...
        lalrpop --help
 
 Options:
@@ -73,18 +318,109 @@ Options:
                          Valid values: quiet, info, verbose, debug.
     -f, --force          Force execution, even if the .lalrpop file is older than the .rs file.
     -c, --color          Force colorful output, even if this is not a TTY.
+    --error-format FMT   Set the format of error messages. (Default: human)
+                         Valid values: human, rustc.
     --comments           Enable comments in the generated code.
-    --report             Generate report files.
+    --introspection      Let table-driven parsers be watched as they parse.
+    --observer           Let an observer follow the shifts, reductions and errors of
+                         table-driven parsers.
+    --report             Generate report files, listing the states and the conflicts
+                         with their examples.
+    --emit-dot           Write the LR automaton of each public nonterminal as a Graphviz
+                         .dot file next to the generated parser.
+    --html-report DIR    Write a report on the states and conflicts of each public
+                         nonterminal into DIR, as an HTML page.
+    --prune-tables       Leave out of the tables of each table-driven parser what its
+                         public nonterminal cannot reach.
+    --max-states N       Give up if the parser for a public nonterminal needs more than
+                         N states, listing the nonterminals in the most of them.
+    --max-table-entries N
+                         Give up if the tables of the parser for a public nonterminal
+                         would have more than N entries.
+    --binary-tables      Write the tables of table-driven parsers to binary files that
+                         the generated code embeds with `include_bytes!`.
+    --dense-tables       Write the tables of table-driven parsers in full rather than
+                         compressed, which is easier to read when debugging.
+    -O, --optimize GOAL  Favor the size of the generated code or the speed of the
+                         parsers, in how they dispatch on states, how wide their
+                         tables are, and what they inline. Valid values: size, speed.
+    --construction ALGO  How to build the states of grammars that do not choose with an
+                         annotation like `#[LALR]`. (Default: lr1)
+                         Valid values: lr1, lane, lalr, ielr, slr.
+    --stack-capacity N   Make room for N entries in the stacks of table-driven parsers
+                         when they start; with --stack-overflow error or abort, also
+                         their limit. (Default: 10000 with a limit, otherwise none)
+    --stack-overflow WHAT
+                         What table-driven parsers do when their stacks are full.
+                         (Default: grow) Valid values: grow, error, abort.
+    --verify             With `snapshot`, check the parse of each file in <corpus>
+                         against its saved snapshot instead of overwriting it.
+    --trivia             With `snapshot`, also record the whitespace around each terminal.
+    --start NT           With `snapshot`, `coverage`, `ambiguity`, `explain`, `parse` or
+                         `simulate`, the public nonterminal to start from. (Default: the
+                         only public nonterminal)
+    --out DIR            With `fuzz`, the `cargo fuzz` directory to write the target
+                         into. (Default: fuzz)
+    --max-length N       With `ambiguity`, the longest input, in tokens, to search
+                         for. (Default: 6)
+    --tokens             With `explain`, `parse` or `simulate`, take the input to be the
+                         names of terminals separated by spaces, such as `\"(\" Num \")\"`,
+                         rather than text to lex.
+    --steps              With `explain`, also print the shifts and reductions of the parser.
 ";
 
 #[derive(Debug, RustcDecodable)]
 struct Args {
+    cmd_snapshot: bool,
+    cmd_coverage: bool,
+    cmd_fuzz: bool,
+    cmd_ambiguity: bool,
+    cmd_metrics: bool,
+    cmd_diff: bool,
+    cmd_docs: bool,
+    cmd_tokens: bool,
+    cmd_ir: bool,
+    cmd_antlr: bool,
+    cmd_abnf: bool,
+    cmd_explain: bool,
+    cmd_parse: bool,
+    cmd_simulate: bool,
+    cmd_check: bool,
     arg_inputs: Vec<String>,
+    arg_grammar: String,
+    arg_abnf: String,
+    arg_corpus: String,
+    arg_module: String,
+    arg_old: String,
+    arg_new: String,
+    arg_input: String,
+    arg_samples: Vec<String>,
     flag_level: Option<LevelFlag>,
     flag_force: bool,
     flag_color: bool,
+    flag_error_format: Option<ErrorFormatFlag>,
     flag_comments: bool,
-    flag_report: bool
+    flag_introspection: bool,
+    flag_observer: bool,
+    flag_report: bool,
+    flag_emit_dot: bool,
+    flag_html_report: Option<String>,
+    flag_prune_tables: bool,
+    flag_max_states: Option<usize>,
+    flag_max_table_entries: Option<usize>,
+    flag_binary_tables: bool,
+    flag_dense_tables: bool,
+    flag_stack_capacity: Option<usize>,
+    flag_stack_overflow: Option<StackOverflowFlag>,
+    flag_optimize: Option<OptimizeFlag>,
+    flag_construction: Option<ConstructionFlag>,
+    flag_verify: bool,
+    flag_trivia: bool,
+    flag_start: Option<String>,
+    flag_out: Option<String>,
+    flag_max_length: Option<usize>,
+    flag_tokens: bool,
+    flag_steps: bool,
 }
 
 #[derive(Debug, RustcDecodable)]
@@ -92,6 +428,26 @@ enum LevelFlag {
     Quiet, Info, Verbose, Debug
 }
 
+#[derive(Debug, RustcDecodable)]
+enum ErrorFormatFlag {
+    Human, Rustc
+}
+
+#[derive(Debug, RustcDecodable)]
+enum StackOverflowFlag {
+    Grow, Error, Abort
+}
+
+#[derive(Debug, RustcDecodable)]
+enum OptimizeFlag {
+    Size, Speed
+}
+
+#[derive(Debug, RustcDecodable)]
+enum ConstructionFlag {
+    Lr1, Lane, Lalr, Ielr, Slr
+}
+
 #[cfg(test)]
 mod test {
     use docopt::Docopt;
@@ -121,4 +477,322 @@ mod test {
             .and_then(|d| d.argv(argv().into_iter()).decode())
             .unwrap();
     }
+
+    #[test]
+    fn test_usage_error_format() {
+        let argv = || vec!["lalrpop", "--error-format", "rustc", "file.lalrpop"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        match args.flag_error_format {
+            Some(super::ErrorFormatFlag::Rustc) => { }
+            other => panic!("unexpected error format: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_usage_stack_overflow() {
+        let argv = || vec!["lalrpop", "--stack-capacity", "500", "--stack-overflow", "error",
+                           "file.lalrpop"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        assert_eq!(args.flag_stack_capacity, Some(500));
+        match args.flag_stack_overflow {
+            Some(super::StackOverflowFlag::Error) => { }
+            other => panic!("unexpected stack overflow behavior: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_usage_optimize() {
+        let argv = || vec!["lalrpop", "-O", "speed", "file.lalrpop"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        match args.flag_optimize {
+            Some(super::OptimizeFlag::Speed) => { }
+            other => panic!("unexpected optimization goal: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_usage_construction() {
+        let argv = || vec!["lalrpop", "--construction", "lalr", "file.lalrpop"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        match args.flag_construction {
+            Some(super::ConstructionFlag::Lalr) => { }
+            other => panic!("unexpected construction: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_usage_snapshot() {
+        let argv = || vec!["lalrpop", "snapshot", "--verify", "--start", "Expr",
+                           "file.lalrpop", "corpus"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        assert!(args.cmd_snapshot);
+        assert!(args.flag_verify);
+        assert_eq!(args.flag_start, Some("Expr".to_string()));
+        assert_eq!(args.arg_grammar, "file.lalrpop");
+        assert_eq!(args.arg_corpus, "corpus");
+    }
+
+    #[test]
+    fn test_usage_snapshot_trivia() {
+        let argv = || vec!["lalrpop", "snapshot", "--trivia", "file.lalrpop", "corpus"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        assert!(args.cmd_snapshot);
+        assert!(args.flag_trivia);
+        assert!(!args.flag_verify);
+    }
+
+    #[test]
+    fn test_usage_prune_tables() {
+        let argv = || vec!["lalrpop", "--prune-tables", "file.lalrpop"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        assert!(args.flag_prune_tables);
+        assert_eq!(args.arg_inputs, vec!["file.lalrpop"]);
+    }
+
+    #[test]
+    fn test_usage_size_limits() {
+        let argv = || vec!["lalrpop", "--max-states", "2000", "--max-table-entries", "100000",
+                           "file.lalrpop"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        assert_eq!(args.flag_max_states, Some(2000));
+        assert_eq!(args.flag_max_table_entries, Some(100000));
+        assert_eq!(args.arg_inputs, vec!["file.lalrpop"]);
+    }
+
+    #[test]
+    fn test_usage_binary_tables() {
+        let argv = || vec!["lalrpop", "--binary-tables", "-O", "size", "file.lalrpop"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        assert!(args.flag_binary_tables);
+        assert_eq!(args.arg_inputs, vec!["file.lalrpop"]);
+    }
+
+    #[test]
+    fn test_usage_emit_dot() {
+        let argv = || vec!["lalrpop", "--emit-dot", "file.lalrpop"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        assert!(args.flag_emit_dot);
+        assert!(!args.flag_report);
+        assert_eq!(args.arg_inputs, vec!["file.lalrpop"]);
+    }
+
+    #[test]
+    fn test_usage_dense_tables() {
+        let argv = || vec!["lalrpop", "--dense-tables", "file.lalrpop"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        assert!(args.flag_dense_tables);
+        assert!(!args.flag_binary_tables);
+        assert_eq!(args.arg_inputs, vec!["file.lalrpop"]);
+    }
+
+    #[test]
+    fn test_usage_observer() {
+        let argv = || vec!["lalrpop", "--observer", "file.lalrpop"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        assert!(args.flag_observer);
+        assert!(!args.flag_introspection);
+        assert_eq!(args.arg_inputs, vec!["file.lalrpop"]);
+    }
+
+    #[test]
+    fn test_usage_coverage() {
+        let argv = || vec!["lalrpop", "coverage", "file.lalrpop", "corpus"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        assert!(args.cmd_coverage);
+        assert!(!args.cmd_snapshot);
+        assert_eq!(args.flag_start, None);
+        assert_eq!(args.arg_grammar, "file.lalrpop");
+        assert_eq!(args.arg_corpus, "corpus");
+    }
+
+    #[test]
+    fn test_usage_fuzz() {
+        let argv = || vec!["lalrpop", "fuzz", "--out", "fuzz", "src/parser.lalrpop",
+                           "calculator::parser"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        assert!(args.cmd_fuzz);
+        assert_eq!(args.flag_out, Some("fuzz".to_string()));
+        assert_eq!(args.arg_grammar, "src/parser.lalrpop");
+        assert_eq!(args.arg_module, "calculator::parser");
+    }
+
+    #[test]
+    fn test_usage_ambiguity() {
+        let argv = || vec!["lalrpop", "ambiguity", "--max-length", "8", "file.lalrpop"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        assert!(args.cmd_ambiguity);
+        assert_eq!(args.flag_max_length, Some(8));
+        assert_eq!(args.flag_start, None);
+        assert_eq!(args.arg_grammar, "file.lalrpop");
+    }
+
+    #[test]
+    fn test_usage_metrics() {
+        let argv = || vec!["lalrpop", "metrics", "file.lalrpop"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        assert!(args.cmd_metrics);
+        assert!(!args.cmd_ambiguity);
+        assert_eq!(args.arg_grammar, "file.lalrpop");
+    }
+
+    #[test]
+    fn test_usage_diff() {
+        let argv = || vec!["lalrpop", "diff", "old.lalrpop", "new.lalrpop"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        assert!(args.cmd_diff);
+        assert_eq!(args.arg_old, "old.lalrpop");
+        assert_eq!(args.arg_new, "new.lalrpop");
+        assert!(args.arg_inputs.is_empty());
+    }
+
+    #[test]
+    fn test_usage_docs() {
+        let argv = || vec!["lalrpop", "docs", "file.lalrpop"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        assert!(args.cmd_docs);
+        assert!(!args.cmd_diff);
+        assert_eq!(args.arg_grammar, "file.lalrpop");
+        assert!(args.arg_inputs.is_empty());
+    }
+
+    #[test]
+    fn test_usage_tokens() {
+        let argv = || vec!["lalrpop", "tokens", "file.lalrpop"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        assert!(args.cmd_tokens);
+        assert_eq!(args.arg_grammar, "file.lalrpop");
+        assert!(args.arg_inputs.is_empty());
+    }
+
+    #[test]
+    fn test_usage_ir() {
+        let argv = || vec!["lalrpop", "ir", "file.lalrpop"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        assert!(args.cmd_ir);
+        assert_eq!(args.arg_grammar, "file.lalrpop");
+        assert!(args.arg_inputs.is_empty());
+    }
+
+    #[test]
+    fn test_usage_antlr() {
+        let argv = || vec!["lalrpop", "antlr", "file.lalrpop"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        assert!(args.cmd_antlr);
+        assert_eq!(args.arg_grammar, "file.lalrpop");
+        assert!(args.arg_inputs.is_empty());
+    }
+
+    #[test]
+    fn test_usage_abnf() {
+        let argv = || vec!["lalrpop", "abnf", "rfc3986.abnf"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        assert!(args.cmd_abnf);
+        assert_eq!(args.arg_abnf, "rfc3986.abnf");
+        assert!(args.arg_inputs.is_empty());
+    }
+
+    #[test]
+    fn test_usage_explain() {
+        let argv = || vec!["lalrpop", "explain", "--tokens", "--steps", "file.lalrpop",
+                           "\"(\" Num \")\""];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        assert!(args.cmd_explain);
+        assert!(args.flag_tokens);
+        assert!(args.flag_steps);
+        assert_eq!(args.arg_grammar, "file.lalrpop");
+        assert_eq!(args.arg_input, "\"(\" Num \")\"");
+    }
+
+    #[test]
+    fn test_usage_parse() {
+        let argv = || vec!["lalrpop", "parse", "--start", "Expr", "file.lalrpop", "a.txt", "-"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        assert!(args.cmd_parse);
+        assert!(!args.flag_tokens);
+        assert_eq!(args.flag_start, Some("Expr".to_string()));
+        assert_eq!(args.arg_grammar, "file.lalrpop");
+        assert_eq!(args.arg_samples, vec!["a.txt", "-"]);
+    }
+
+    #[test]
+    fn test_usage_simulate() {
+        let argv = || vec!["lalrpop", "simulate", "--tokens", "file.lalrpop", "Num \"+\" Num"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        assert!(args.cmd_simulate);
+        assert!(!args.cmd_explain);
+        assert!(args.flag_tokens);
+        assert_eq!(args.arg_grammar, "file.lalrpop");
+        assert_eq!(args.arg_input, "Num \"+\" Num");
+    }
+
+    #[test]
+    fn test_usage_html_report() {
+        let argv = || vec!["lalrpop", "check", "--html-report", "reports", "file.lalrpop"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        assert!(args.cmd_check);
+        assert_eq!(args.flag_html_report, Some("reports".to_string()));
+        assert_eq!(args.arg_inputs, vec!["file.lalrpop"]);
+    }
+
+    #[test]
+    fn test_usage_check() {
+        let argv = || vec!["lalrpop", "check", "file.lalrpop", "../file2.lalrpop"];
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv().into_iter()).decode())
+            .unwrap();
+        assert!(args.cmd_check);
+        assert_eq!(args.arg_inputs, vec!["file.lalrpop", "../file2.lalrpop"]);
+    }
 }
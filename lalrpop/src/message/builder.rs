@@ -10,6 +10,9 @@ use style::Style;
 
 pub struct MessageBuilder {
     span: Span,
+    code: Option<&'static str>,
+    label: &'static str,
+    warning: bool,
     heading: Option<Box<Content>>,
     body: Option<Box<Content>>,
 }
@@ -24,10 +27,28 @@ pub struct BodyCharacter {
 
 impl MessageBuilder {
     pub fn new(span: Span) -> Self {
-        MessageBuilder { span: span,
+        MessageBuilder { span: span, code: None, label: "", warning: false,
                          heading: None, body: None }
     }
 
+    /// Sets the error code, shown in `rustc`-style output.
+    pub fn code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// Sets the text shown next to the span in `rustc`-style output.
+    pub fn label(mut self, label: &'static str) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// Marks the message as a warning rather than an error.
+    pub fn warning(mut self) -> Self {
+        self.warning = true;
+        self
+    }
+
     pub fn heading(self) -> Builder<HeadingCharacter> {
         Builder::new(HeadingCharacter { message: self })
     }
@@ -38,6 +59,9 @@ impl MessageBuilder {
 
     pub fn end(self) -> Message {
         Message::new(self.span,
+                     self.code,
+                     self.label,
+                     self.warning,
                      self.heading.expect("never defined a heading"),
                      self.body.expect("never defined a body"))
     }
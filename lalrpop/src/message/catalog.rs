@@ -0,0 +1,159 @@
+//! The catalog of LALRPOP's diagnostics. Each kind has a stable code,
+//! which appears in `rustc`-style output as in `error[E0001]: ...`,
+//! and which `Configuration::set_message` uses to replace the text
+//! (e.g., to translate it).
+
+use std::fmt::Display;
+use tls::Tls;
+
+/// A kind of diagnostic.
+#[derive(Debug)]
+pub struct CatalogEntry {
+    /// The stable code, such as `E0012`.
+    pub code: &'static str,
+
+    /// A short name, for documentation: `E0012: reduce/reduce
+    /// conflict`.
+    pub name: &'static str,
+
+    /// The text of the message, in which `{0}`, `{1}` and so on stand
+    /// for the details of each occurrence (the offending token, say).
+    pub default_text: &'static str,
+}
+
+impl CatalogEntry {
+    /// The text of the message, as given to `Configuration::set_message`
+    /// if it has been, with `args` in place of `{0}`, `{1}` and so on.
+    pub fn format(&self, args: &[&Display]) -> String {
+        let session = Tls::session();
+        let mut rest = match session.messages.get(self.code) {
+            Some(text) => &text[..],
+            None => self.default_text,
+        };
+
+        // anything in braces that is not the index of an argument is
+        // left alone
+        let mut result = String::new();
+        while let Some(open) = rest.find('{') {
+            result.push_str(&rest[..open]);
+            rest = &rest[open..];
+            let arg = rest.find('}').and_then(|close| {
+                rest[1..close].parse::<usize>()
+                              .ok()
+                              .and_then(|index| args.get(index))
+                              .map(|arg| (arg, close))
+            });
+            match arg {
+                Some((arg, close)) => {
+                    result.push_str(&arg.to_string());
+                    rest = &rest[close + 1..];
+                }
+                None => {
+                    result.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        result.push_str(rest);
+        result
+    }
+}
+
+macro_rules! catalog {
+    ($($(#[$attr:meta])* $id:ident = $code:expr, $name:expr, $text:expr;)*) => {
+        $(
+            $(#[$attr])*
+            pub static $id: CatalogEntry = CatalogEntry {
+                code: $code,
+                name: $name,
+                default_text: $text,
+            };
+        )*
+
+        /// Every kind of diagnostic, in order of code.
+        pub static CATALOG: &'static [&'static CatalogEntry] = &[$(&$id),*];
+    }
+}
+
+catalog! {
+    /// The grammar file could not be parsed at some token.
+    UNEXPECTED_TOKEN = "E0001", "unexpected token", "unexpected token: `{0}`";
+
+    /// The grammar was rejected during normalization.
+    INVALID_GRAMMAR = "E0002", "invalid grammar", "{0}";
+
+    /// The grammar is ambiguous.
+    AMBIGUOUS_GRAMMAR = "E0003", "ambiguous grammar", "Ambiguous grammar detected";
+
+    /// The grammar needs more than one token of lookahead at some
+    /// point to choose between shifting and reducing.
+    LOCAL_AMBIGUITY = "E0004", "local ambiguity", "Local ambiguity detected";
+
+    /// Some other LR(1) conflict.
+    CONFLICT = "E0005", "conflict", "Conflict detected";
+
+    INVALID_CHARACTER = "E0006", "invalid character", "invalid character `{0}`";
+
+    UNEXPECTED_EOF = "E0007", "unexpected end of file", "unexpected end of file";
+
+    EXTRA_TOKEN = "E0008", "extra token", "extra token at end of input: `{0}`";
+
+    NESTING_TOO_DEEP = "E0009", "nesting too deep", "grammar nested too deeply";
+
+    /// A `${NAME}` in the grammar names no variable given to
+    /// `Configuration::set_variable`.
+    UNDEFINED_VARIABLE = "E0010", "undefined variable",
+        "no value for the variable `{0}`; set one with `Configuration::set_variable`";
+
+    /// A warning about a shift/reduce conflict in a grammar marked
+    /// `#[prefer_shift]`.
+    SHIFT_PREFERRED = "E0011", "shift/reduce conflict resolved by shifting",
+        "Shift/reduce conflict resolved by shifting";
+
+    /// The grammar needs more than one token of lookahead at some
+    /// point to choose between two reductions.
+    REDUCE_REDUCE_CONFLICT = "E0012", "reduce/reduce conflict",
+        "Reduce/reduce conflict detected";
+
+    UNRECOGNIZED_TOKEN = "E0013", "unrecognized token", "unrecognized token";
+
+    UNTERMINATED_ESCAPE = "E0014", "unterminated escape", "unterminated escape; missing '`'?";
+
+    UNTERMINATED_STRING_LITERAL = "E0015", "unterminated string literal",
+        "unterminated string literal; missing `\"`?";
+
+    UNTERMINATED_CHARACTER_LITERAL = "E0016", "unterminated character literal",
+        "unterminated character literal; missing `'`?";
+
+    EXPECTED_STRING_LITERAL = "E0017", "expected string literal",
+        "expected string literal; missing `\"`?";
+
+    EXPECTED_SINGLE_CHARACTER = "E0018", "expected single character",
+        "expected a single character in character range";
+
+    UNTERMINATED_CODE = "E0019", "unterminated code",
+        "unterminated code block; perhaps a missing `;`, `)`, `]` or `}`?";
+
+    UNTERMINATED_COMMENT = "E0020", "unterminated comment",
+        "unterminated block comment; missing `*/`?";
+
+    /// The parser for a public nonterminal needs more states than
+    /// `Configuration::max_states` allows.
+    TOO_MANY_STATES = "E0021", "too many states",
+        "the parser for `{0}` needs more than {1} states; \
+         the nonterminals in the most states are:{2}";
+
+    /// The tables of the parser for a public nonterminal have more
+    /// entries than `Configuration::max_table_entries` allows.
+    TABLES_TOO_LARGE = "E0022", "tables too large",
+        "the tables of the parser for `{0}` would have {1} entries, more than {2}; \
+         the nonterminals in the most states are:{3}";
+
+    /// A file named in `use "file.lalrpop";` could not be read.
+    UNREADABLE_IMPORT = "E0023", "unreadable import", "cannot read `{0}`: {1}";
+
+    /// A grammar given to `generate`, which has no file of its own,
+    /// imports another file.
+    IMPORT_WITHOUT_FILE = "E0024", "import without a grammar file",
+        "`use \"{0}\";` is only allowed in a grammar file";
+}
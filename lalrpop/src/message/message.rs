@@ -1,6 +1,9 @@
 use ascii_canvas::AsciiView;
 use grammar::parse_tree::Span;
 use message::Content;
+use message::horiz::Horiz;
+use message::vert::Vert;
+use session::ErrorFormat;
 use std::cmp;
 use std::fmt::{Debug, Formatter, Error};
 use style::Style;
@@ -28,37 +31,122 @@ use tls::Tls;
 ///     <body>
 /// }
 /// ```
+///
+/// With `ErrorFormat::Rustc`, the span is instead shown below the
+/// heading, the way `rustc` does it (with `warning` in place of
+/// `error` for a warning):
+///
+/// ```
+/// error[<code>]: <heading>
+///  --> <file>:<line>:<column>
+///   |
+/// 3 | <source line>
+///   |   ^^^^^^^^^^^ <label>
+///
+/// <body>
+/// ```
 pub struct Message {
     span: Span,
+    code: Option<&'static str>,
+    label: &'static str,
+    warning: bool,
     heading: Box<Content>,
     body: Box<Content>,
 }
 
 impl Message {
     pub fn new(span: Span,
+               code: Option<&'static str>,
+               label: &'static str,
+               warning: bool,
                heading: Box<Content>,
                body: Box<Content>) -> Self {
         Message {
             span: span,
+            code: code,
+            label: label,
+            warning: warning,
             heading: heading,
             body: body,
         }
     }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn code(&self) -> Option<&'static str> {
+        self.code
+    }
+
+    pub fn is_warning(&self) -> bool {
+        self.warning
+    }
+
+    /// Adds `content` below the body.
+    pub fn append(mut self, content: Box<Content>) -> Self {
+        self.body = Box::new(Vert::new(vec![self.body, content], 2));
+        self
+    }
+
+    /// Adds `content` at the end of the heading, after a space.
+    pub fn append_heading(mut self, content: Box<Content>) -> Self {
+        self.heading = Box::new(Horiz::new(vec![self.heading, content], 2));
+        self
+    }
+
+    fn rustc_prefix(&self) -> String {
+        let severity = if self.warning { "warning" } else { "error" };
+        match self.code {
+            Some(code) => format!("{}[{}]: ", severity, code),
+            None => format!("{}: ", severity),
+        }
+    }
 }
 
 impl Content for Message {
     fn min_width(&self) -> usize {
+        let session = Tls::session();
         let file_text = Tls::file_text();
-        let span = file_text.span_str(self.span).chars().count();
         let heading = self.heading.min_width();
         let body = self.body.min_width();
-        cmp::max(span + heading + 2, body + 2)
+        match session.error_format {
+            ErrorFormat::Human => {
+                let span = file_text.span_str(self.span).chars().count();
+                cmp::max(span + heading + 2, body + 2)
+            }
+            ErrorFormat::Rustc => {
+                let prefix = self.rustc_prefix().chars().count();
+                let snippet = file_text.rustc_snippet(self.span, self.label)
+                                       .iter()
+                                       .map(|line| line.chars().count())
+                                       .max()
+                                       .unwrap();
+                cmp::max(cmp::max(prefix + heading, snippet), body + 2)
+            }
+        }
     }
 
     fn emit(&self, view: &mut AsciiView) {
         let session = Tls::session();
         let file_text = Tls::file_text();
 
+        if let ErrorFormat::Rustc = session.error_format {
+            let prefix = self.rustc_prefix();
+            view.write_chars(0, 0, prefix.chars(), Style::new());
+            let (row, _) = self.heading.emit_at(&mut view.styled(session.heading),
+                                                0,
+                                                prefix.chars().count());
+
+            let snippet = file_text.rustc_snippet(self.span, self.label);
+            for (index, line) in snippet.iter().enumerate() {
+                view.write_chars(row + 1 + index, 0, line.chars(), Style::new());
+            }
+
+            self.body.emit_at(view, row + snippet.len() + 2, 2);
+            return;
+        }
+
         let span = file_text.span_str(self.span);
         view.write_chars(0, 0, span.chars(), Style::new());
         let count = span.chars().count();
@@ -80,6 +168,9 @@ impl Debug for Message {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
         fmt.debug_struct("Message")
            .field("span", &self.span)
+           .field("code", &self.code)
+           .field("label", &self.label)
+           .field("warning", &self.warning)
            .field("heading", &self.heading)
            .field("body", &self.body)
            .finish()
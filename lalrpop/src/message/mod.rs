@@ -4,6 +4,7 @@ use std::cmp;
 use std::fmt::Debug;
 
 pub mod builder;
+pub mod catalog;
 pub mod horiz;
 pub mod message;
 pub mod indent;
@@ -1,6 +1,10 @@
 use ascii_canvas::AsciiCanvas;
+use file_text::FileText;
 use grammar::parse_tree::Span;
 use message::builder::MessageBuilder;
+use session::{ErrorFormat, Session};
+use std::path::PathBuf;
+use std::rc::Rc;
 use test_util::expect_debug;
 use tls::Tls;
 
@@ -128,3 +132,90 @@ fn paragraphs() {
 ]
 "#.trim());
 }
+
+#[test]
+fn rustc_format() {
+    let mut session = Session::test();
+    session.error_format = ErrorFormat::Rustc;
+    let _tls = Tls::install(Rc::new(session),
+                            Rc::new(FileText::new(PathBuf::from("tmp.txt"),
+                                                  String::from("foo\nbar\nbaz\n"))));
+    let msg =
+        MessageBuilder::new(Span(4, 7))
+        .code("E0003")
+        .label("this is bar")
+        .heading()
+        .text("Hello, world!")
+        .end()
+        .body()
+        .text("Some more details.")
+        .end()
+        .end();
+    let min_width = msg.min_width();
+    let mut canvas = AsciiCanvas::new(0, min_width);
+    msg.emit(&mut canvas);
+    expect_debug(&canvas.to_strings(), r#"
+[
+    "error[E0003]: Hello, world!",
+    " --> tmp.txt:2:1",
+    "  |",
+    "2 | bar",
+    "  | ^^^ this is bar",
+    "",
+    "  Some more details."
+]
+"#.trim());
+}
+
+#[test]
+fn rustc_format_warning() {
+    let mut session = Session::test();
+    session.error_format = ErrorFormat::Rustc;
+    let _tls = Tls::install(Rc::new(session),
+                            Rc::new(FileText::new(PathBuf::from("tmp.txt"),
+                                                  String::from("foo\nbar\nbaz\n"))));
+    let msg =
+        MessageBuilder::new(Span(4, 7))
+        .warning()
+        .code("E0005")
+        .label("this is bar")
+        .heading()
+        .text("Hello, world!")
+        .end()
+        .body()
+        .text("Some more details.")
+        .end()
+        .end();
+    let min_width = msg.min_width();
+    let mut canvas = AsciiCanvas::new(0, min_width);
+    msg.emit(&mut canvas);
+    expect_debug(&canvas.to_strings(), r#"
+[
+    "warning[E0005]: Hello, world!",
+    " --> tmp.txt:2:1",
+    "  |",
+    "2 | bar",
+    "  | ^^^ this is bar",
+    "",
+    "  Some more details."
+]
+"#.trim());
+}
+
+#[test]
+fn catalog_default_text() {
+    let _tls = install_tls();
+    assert_eq!(catalog::EXTRA_TOKEN.format(&[&"=>"]),
+               "extra token at end of input: `=>`");
+}
+
+#[test]
+fn catalog_replaced_text() {
+    let mut session = Session::test();
+    session.messages.insert("E0008".to_string(),
+                            "{0}: jeton en trop ({1} {0})".to_string());
+    let _tls = Tls::install(Rc::new(session),
+                            Rc::new(FileText::new(PathBuf::from("tmp.txt"), String::new())));
+    assert_eq!(catalog::EXTRA_TOKEN.format(&[&"=>"]),
+               "=>: jeton en trop ({1} =>)");
+}
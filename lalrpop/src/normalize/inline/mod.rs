@@ -35,6 +35,7 @@ fn inline_nt(grammar: &mut Grammar, inline_nt: NonterminalString) {
                 inline_nonterminal: inline_nt,
                 into_production: into_production,
                 inline_fallible: 0,
+                inline_cost: 0,
                 inline_productions: &inline_productions,
                 new_symbols: vec![],
                 new_productions: &mut new_productions,
@@ -67,6 +68,10 @@ struct Inliner<'a> {
     /// be 1.
     inline_fallible: u32,
 
+    /// Total cost of the productions for `A` that we have inlined so
+    /// far, which is added to the cost of the new production.
+    inline_cost: u32,
+
     /// The `X = Y A Z` being inlined into
     into_production: &'a Production,
 
@@ -113,6 +118,8 @@ impl<'a> Inliner<'a> {
                 span: self.into_production.span,
                 symbols: prod_symbols,
                 action: action_fn,
+                cost: self.into_production.cost.saturating_add(self.inline_cost),
+                precedence: self.into_production.precedence,
             });
         } else {
             let next_symbol = into_symbols[0];
@@ -126,6 +133,8 @@ impl<'a> Inliner<'a> {
                         let inline_action = inline_production.action;
                         let fallible = self.action_fn_defns[inline_action.index()].fallible;
                         self.inline_fallible += fallible as u32;
+                        let cost = self.inline_cost;
+                        self.inline_cost = cost.saturating_add(inline_production.cost);
 
                         // Push the symbols of the production inline.
                         self.new_symbols.push(
@@ -139,6 +148,7 @@ impl<'a> Inliner<'a> {
                         // Reset state after we have inlined remaining symbols:
                         self.new_symbols.pop();
                         self.inline_fallible -= fallible as u32;
+                        self.inline_cost = cost;
                     }
                 }
                 _ => {
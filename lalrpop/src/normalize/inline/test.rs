@@ -93,3 +93,24 @@ ET: () = {
     // AT+ ET AT+ }` but not `E = X { AT+ ET }` or `E = X { ET AT+ }`.
     assert!(grammar.productions_for(nt).len() == 4);
 }
+
+#[test]
+fn inlined_costs_add_up() {
+    let grammar = inlined_grammar(r#"
+        grammar;
+
+        E: () = {
+            "L",
+            #[cost(2)] "&" OPT_L E
+        };
+
+        #[inline] OPT_L: () = {
+            (),
+            #[cost(3)] "L"
+        };
+    "#).unwrap();
+
+    let nt = NonterminalString(intern("E"));
+    let costs: Vec<_> = grammar.productions_for(nt).iter().map(|p| p.cost).collect();
+    assert_eq!(costs, vec![0, 2, 5]);
+}
@@ -2,16 +2,19 @@
 //!
 
 use intern::{self, intern, InternedString};
-use normalize::NormResult;
+use normalize::{NormError, NormResult};
 use normalize::norm_util::{self, Symbols};
 use grammar::consts::*;
 use grammar::pattern::{Pattern, PatternKind};
 use grammar::parse_tree as pt;
 use grammar::parse_tree::{InternToken, NonterminalString, TerminalString, read_algorithm};
 use grammar::repr as r;
-use session::Session;
+use session::{Optimize, Session};
 use collections::{map, Map};
 
+#[cfg(test)]
+mod test;
+
 pub fn lower(session: &Session, grammar: pt::Grammar, types: r::Types) -> NormResult<r::Grammar> {
     let state = LowerState::new(session, types, &grammar);
     state.lower(grammar)
@@ -26,6 +29,8 @@ struct LowerState<'s> {
     intern_token: Option<InternToken>,
     types: r::Types,
     uses_error_recovery: bool,
+    uses_wildcard: bool,
+    reduce_hooks: bool,
 }
 
 impl<'s> LowerState<'s> {
@@ -39,13 +44,18 @@ impl<'s> LowerState<'s> {
             types: types,
             intern_token: None,
             uses_error_recovery: false,
+            uses_wildcard: false,
+            reduce_hooks: grammar.annotations.iter().any(|a| a.id == intern(REDUCE_HOOKS)),
         }
     }
 
     fn lower(mut self, grammar: pt::Grammar) -> NormResult<r::Grammar> {
         let start_symbols = self.synthesize_start_symbols(&grammar);
+        let repeated_symbols = self.repeated_start_symbols(&grammar);
 
         let mut uses = vec![];
+        let mut rust_items = vec![];
+        let mut tests = vec![];
         let mut token_span = None;
 
         for item in grammar.items {
@@ -95,6 +105,38 @@ impl<'s> LowerState<'s> {
                     self.intern_token = Some(data);
                 }
 
+                pt::GrammarItem::InlineTest(data) => {
+                    tests.push(r::InlineTest {
+                        nonterminal: data.nonterminal,
+                        input: data.input,
+                        should_parse: data.expectation == intern(PARSES),
+                    });
+                }
+
+                pt::GrammarItem::Operators(..) => panic!("operator table not expanded"),
+
+                pt::GrammarItem::Reserved(_) => {
+                    // Like the match token, reserved words are
+                    // folded into the `InternToken` by `token_check`.
+                }
+
+                pt::GrammarItem::RustItem(data) => {
+                    rust_items.push(data);
+                }
+
+                pt::GrammarItem::TypeAlias(_) => {
+                    // Type aliases were expanded by `type_alias`.
+                }
+
+                pt::GrammarItem::TerminalConst(_) => {
+                    // Uses of the constants were replaced by `resolve`.
+                }
+
+                pt::GrammarItem::Import(_) => {
+                    // The items of the imported file were added to
+                    // those of the grammar when it was loaded.
+                }
+
                 pt::GrammarItem::ExternToken(data) => {
                     if let Some(enum_token) = data.enum_token {
                         token_span = Some(enum_token.type_span);
@@ -113,20 +155,26 @@ impl<'s> LowerState<'s> {
                     let nt_name = nt.name;
                     let productions: Vec<_> = nt.alternatives
                                                 .into_iter()
-                                                .map(|alt| {
+                                                .enumerate()
+                                                .map(|(index, alt)| {
                                                     let nt_type = self.types
                                                                       .nonterminal_type(nt_name)
                                                                       .clone();
                                                     let symbols = self.symbols(&alt.expr.symbols);
+                                                    let cost = alt.cost();
+                                                    let precedence = alt.precedence();
                                                     let action = self.action_kind(nt_type,
                                                                                   &alt.expr,
                                                                                   &symbols,
                                                                                   alt.action);
+                                                    self.name_reduce_hook(action, nt_name, index);
                                                     r::Production {
                                                         nonterminal: nt_name,
                                                         span: alt.span,
                                                         symbols: symbols,
                                                         action: action,
+                                                        cost: cost,
+                                                        precedence: precedence,
                                                     }
                                                 })
                                                 .collect();
@@ -152,6 +200,7 @@ impl<'s> LowerState<'s> {
                                 .collect();
 
         let mut algorithm = r::Algorithm::default();
+        algorithm.construction = self.session.construction;
 
         // FIXME Error recovery only works for parse tables so temporarily only generate parse tables for
         // testing
@@ -161,6 +210,77 @@ impl<'s> LowerState<'s> {
 
         read_algorithm(&grammar.annotations, &mut algorithm);
 
+        // Only the table-driven backend can look at a second token,
+        // ask a conflict hook what to do, or fall back to `_`.
+        let conflict_hooks =
+            self.nonterminals
+                .values()
+                .any(|data| data.annotations.iter().any(|a| a.id == intern(CONFLICT_HOOK)));
+        if (algorithm.lookahead2 || conflict_hooks || self.uses_wildcard) &&
+            algorithm.codegen == r::LrCodeGeneration::TestAll {
+            algorithm.codegen = r::LrCodeGeneration::TableDriven;
+        }
+
+        // Likewise, only the table-driven parser keeps its stacks in
+        // vectors that it can checkpoint.
+        if algorithm.incremental {
+            let only_input = grammar.parameters.len() == 1 &&
+                grammar.type_parameters.len() == 1 &&
+                grammar.where_clauses.is_empty();
+            try!(self.validate_incremental(&grammar.annotations, only_input));
+            if algorithm.codegen == r::LrCodeGeneration::TestAll {
+                algorithm.codegen = r::LrCodeGeneration::TableDriven;
+            }
+        }
+        if algorithm.push {
+            try!(self.validate_push(&grammar.annotations));
+            if algorithm.codegen == r::LrCodeGeneration::TestAll {
+                algorithm.codegen = r::LrCodeGeneration::TableDriven;
+            }
+        }
+
+        // Optimizing for speed means recursive ascent, unless the
+        // grammar chose otherwise or needs the tables.
+        let codegens = vec![intern(TABLE_DRIVEN), intern(RECURSIVE_ASCENT), intern(TEST_ALL)];
+        let chose_codegen = grammar.annotations.iter().any(|a| codegens.contains(&a.id));
+        if self.session.optimize == Optimize::Speed &&
+            !chose_codegen &&
+            algorithm.codegen == r::LrCodeGeneration::TableDriven &&
+            !algorithm.lookahead2 &&
+            !algorithm.incremental &&
+            !algorithm.items &&
+            !algorithm.prefix &&
+            !algorithm.push &&
+            !conflict_hooks &&
+            !self.uses_wildcard &&
+            !self.uses_error_recovery {
+            algorithm.codegen = r::LrCodeGeneration::RecursiveAscent;
+        }
+
+        let item_start_symbols = if algorithm.items {
+            let only_input = grammar.parameters.len() == 1 &&
+                grammar.type_parameters.len() == 1 &&
+                grammar.where_clauses.is_empty();
+            try!(self.validate_items(&grammar.annotations, only_input, &repeated_symbols));
+            self.synthesize_item_start_symbols(repeated_symbols)
+        } else {
+            map()
+        };
+
+        if algorithm.prefix {
+            try!(self.validate_prefix(&grammar.annotations, conflict_hooks));
+        }
+
+        // `_` matches any token, but is only consulted after the
+        // other terminals (see `TerminalString::Any`).
+        if self.uses_wildcard {
+            self.conversions.push((TerminalString::Any,
+                                   Pattern {
+                                       span: grammar.span,
+                                       kind: PatternKind::Underscore,
+                                   }));
+        }
+
         let mut all_terminals: Vec<_> = self.conversions
                                             .iter()
                                             .map(|c| c.0)
@@ -181,7 +301,10 @@ impl<'s> LowerState<'s> {
             uses_error_recovery: self.uses_error_recovery,
             prefix: self.prefix,
             start_nonterminals: start_symbols,
+            item_start_nonterminals: item_start_symbols,
             uses: uses,
+            rust_items: rust_items,
+            tests: tests,
             action_fn_defns: self.action_fn_defns,
             nonterminals: self.nonterminals,
             conversions: self.conversions.into_iter().collect(),
@@ -227,6 +350,8 @@ impl<'s> LowerState<'s> {
                        symbols: symbols,
                        action: action_fn,
                        span: nt.span,
+                       cost: 0,
+                       precedence: None,
                    };
                    self.nonterminals.insert(fake_name,
                                             r::NonterminalData {
@@ -240,6 +365,207 @@ impl<'s> LowerState<'s> {
                .collect()
     }
 
+    /// Finds the public nonterminals of the form `File = Item*` (or
+    /// `Item+`), returning each along with the repeated symbol `Item`.
+    fn repeated_start_symbols(&self, grammar: &pt::Grammar) -> Vec<(NonterminalString, pt::Symbol)> {
+        let nonterminals: Map<_, _> = grammar.items
+                                             .iter()
+                                             .filter_map(|item| item.as_nonterminal())
+                                             .map(|nt| (nt.name, nt))
+                                             .collect();
+
+        nonterminals.values()
+                    .filter(|nt| nt.public && nt.alternatives.len() == 1)
+                    .filter_map(|nt| {
+                        let alternative = &nt.alternatives[0];
+                        if alternative.action.is_some() || alternative.expr.symbols.len() != 1 {
+                            return None;
+                        }
+                        let repeat_name = match alternative.expr.symbols[0].kind {
+                            pt::SymbolKind::Nonterminal(name) => name.to_string(),
+                            _ => return None,
+                        };
+
+                        // `macro_expand` names the nonterminals it
+                        // creates for `Item*` and `Item+` after the
+                        // repetition itself (user names cannot contain
+                        // `*` or `+`), and `Item+` always begins with
+                        // the alternative `Item+ = Item`.
+                        let plus_name = if repeat_name.ends_with('*') {
+                            format!("{}+", &repeat_name[..repeat_name.len() - 1])
+                        } else if repeat_name.ends_with('+') {
+                            repeat_name
+                        } else {
+                            return None;
+                        };
+                        nonterminals.get(&NonterminalString(intern(&plus_name)))
+                                    .map(|plus| (nt.name, plus.alternatives[0].expr.symbols[0].clone()))
+                    })
+                    .collect()
+    }
+
+    /// `parse_Foo_incremental` re-lexes the input from a byte offset,
+    /// and keeps the values of the nonterminals in its checkpoints
+    /// from one version of the input to the next, so they cannot
+    /// borrow from it.
+    fn validate_incremental(&self,
+                            annotations: &[pt::Annotation],
+                            only_input: bool)
+                            -> NormResult<()> {
+        let span = annotations.iter()
+                              .find(|a| a.id == intern(INCREMENTAL))
+                              .unwrap()
+                              .id_span;
+        if self.intern_token.is_none() || self.types.terminal_loc_type() != r::TypeRepr::usize() {
+            return_err!(span,
+                        "`#[{}]` requires the built-in lexer, with byte offsets as locations",
+                        INCREMENTAL);
+        }
+        if !only_input {
+            return_err!(span,
+                        "`#[{}]` grammars cannot declare parameters of their own",
+                        INCREMENTAL);
+        }
+        if self.uses_error_recovery {
+            return_err!(span, "`#[{}]` does not support error recovery", INCREMENTAL);
+        }
+        if self.uses_wildcard {
+            return_err!(span, "`#[{}]` does not support the wildcard terminal `_`", INCREMENTAL);
+        }
+
+        let input = r::TypeParameter::Lifetime(intern(INPUT_LIFETIME));
+        for data in self.nonterminals.values() {
+            if self.types.nonterminal_type(data.name).referenced().contains(&input) {
+                return_err!(data.span,
+                            "the type of `{}` borrows from the input, so `#[{}]` cannot keep \
+                             it in a checkpoint",
+                            data.name,
+                            INCREMENTAL);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// `parse_Foo_prefix` reports how much of the input it parsed as
+    /// a byte offset, and runs the parser over the tables to find out
+    /// where the longest prefix ends, which a conflict hook would have
+    /// to be asked about and error recovery would make the whole input.
+    fn validate_prefix(&self,
+                       annotations: &[pt::Annotation],
+                       conflict_hooks: bool)
+                       -> NormResult<()> {
+        let span = annotations.iter()
+                              .find(|a| a.id == intern(PREFIX))
+                              .unwrap()
+                              .id_span;
+        if self.intern_token.is_none() {
+            return_err!(span, "`#[{}]` requires the built-in lexer", PREFIX);
+        }
+        // the end of the prefix is the byte offset at which we slice the input
+        if self.types.terminal_loc_type() != r::TypeRepr::usize() {
+            return_err!(span, "`#[{}]` requires byte offsets as locations", PREFIX);
+        }
+        if conflict_hooks {
+            return_err!(span, "`#[{}]` cannot be combined with `#[{}]`", PREFIX, CONFLICT_HOOK);
+        }
+        if self.uses_error_recovery {
+            return_err!(span, "`#[{}]` does not support error recovery", PREFIX);
+        }
+        Ok(())
+    }
+
+    /// The `parse_Foo_items` iterators are generated only for
+    /// grammars whose sole parameter is the implicit `input`, since
+    /// they have to carry the parameters around between items.
+    fn validate_items(&self,
+                      annotations: &[pt::Annotation],
+                      only_input: bool,
+                      repeated_symbols: &[(NonterminalString, pt::Symbol)])
+                      -> NormResult<()> {
+        let span = annotations.iter()
+                              .find(|a| a.id == intern(ITEMS))
+                              .unwrap()
+                              .id_span;
+        if self.intern_token.is_none() {
+            return_err!(span, "`#[{}]` requires the built-in lexer", ITEMS);
+        }
+        if !only_input {
+            return_err!(span, "`#[{}]` grammars cannot declare parameters of their own", ITEMS);
+        }
+        if self.uses_error_recovery {
+            return_err!(span, "`#[{}]` does not support error recovery", ITEMS);
+        }
+        if repeated_symbols.is_empty() {
+            return_err!(span,
+                        "`#[{}]` needs a public nonterminal of the form `File = Item*`",
+                        ITEMS);
+        }
+        Ok(())
+    }
+
+    /// `PushParser_Foo` is handed the tokens of an external
+    /// tokenizer, and cannot go back to the tokens before the current
+    /// one, or on to the next, to recover from an error.
+    fn validate_push(&self, annotations: &[pt::Annotation]) -> NormResult<()> {
+        let span = annotations.iter()
+                              .find(|a| a.id == intern(PUSH))
+                              .unwrap()
+                              .id_span;
+        if self.intern_token.is_some() {
+            return_err!(span, "`#[{}]` requires an external tokenizer", PUSH);
+        }
+        if self.uses_error_recovery {
+            return_err!(span, "`#[{}]` does not support error recovery", PUSH);
+        }
+        Ok(())
+    }
+
+    fn synthesize_item_start_symbols(&mut self,
+                                     repeated_symbols: Vec<(NonterminalString, pt::Symbol)>)
+                                     -> Map<NonterminalString, NonterminalString> {
+        repeated_symbols.into_iter()
+                        .map(|(nt_name, symbol)| {
+                            // create a synthetic start symbol `__File__item`
+                            // for each public symbol `File = Item*`, with a
+                            // rule like:
+                            //
+                            //     __File__item = Item;
+                            //
+                            // (the prefix never appears in user names, so
+                            // this cannot collide with another `__Foo`)
+                            let fake_name = NonterminalString(intern(&format!("{}{}{}item",
+                                                                              self.prefix,
+                                                                              nt_name,
+                                                                              self.prefix)));
+                            let symbols = vec![self.symbol(&symbol)];
+                            let item_type = match symbols[0] {
+                                r::Symbol::Nonterminal(id) => self.types.nonterminal_type(id).clone(),
+                                r::Symbol::Terminal(id) => self.types.terminal_type(id).clone(),
+                            };
+                            self.types.add_type(fake_name, item_type.clone());
+                            let expr = pt::ExprSymbol { symbols: vec![symbol.clone()] };
+                            let action_fn = self.action_fn(item_type, false, &expr, &symbols, None);
+                            let production = r::Production {
+                                nonterminal: fake_name,
+                                symbols: symbols,
+                                action: action_fn,
+                                span: symbol.span,
+                                cost: 0,
+                                precedence: None,
+                            };
+                            self.nonterminals.insert(fake_name,
+                                                     r::NonterminalData {
+                                                         name: fake_name,
+                                                         annotations: vec![],
+                                                         span: symbol.span,
+                                                         productions: vec![production],
+                                                     });
+                            (nt_name, fake_name)
+                        })
+                        .collect()
+    }
+
     fn action_kind(&mut self,
                    nt_type: r::TypeRepr,
                    expr: &pt::ExprSymbol,
@@ -249,6 +575,7 @@ impl<'s> LowerState<'s> {
         match action {
             Some(pt::ActionKind::Lookahead) => self.lookahead_action_fn(),
             Some(pt::ActionKind::Lookbehind) => self.lookbehind_action_fn(),
+            Some(pt::ActionKind::LookaheadToken) => self.lookahead_token_action_fn(),
             Some(pt::ActionKind::User(string)) => {
                 self.action_fn(nt_type, false, &expr, &symbols, Some(string))
             }
@@ -279,6 +606,16 @@ impl<'s> LowerState<'s> {
         self.add_action_fn(action_fn_defn)
     }
 
+    fn lookahead_token_action_fn(&mut self) -> r::ActionFn {
+        let action_fn_defn = r::ActionFnDefn {
+            fallible: false,
+            ret_type: self.types.opt_triple_type(),
+            kind: r::ActionFnDefnKind::Lookaround(r::LookaroundActionFnDefn::LookaheadToken),
+        };
+
+        self.add_action_fn(action_fn_defn)
+    }
+
     fn action_fn(&mut self,
                  nt_type: r::TypeRepr,
                  fallible: bool,
@@ -349,6 +686,7 @@ impl<'s> LowerState<'s> {
                         arg_patterns: arg_patterns,
                         arg_types: arg_types,
                         code: action,
+                        hook: None,
                     }),
                 }
             }
@@ -370,6 +708,7 @@ impl<'s> LowerState<'s> {
                         arg_patterns: arg_patterns,
                         arg_types: arg_types,
                         code: action,
+                        hook: None,
                     }),
                 }
             }
@@ -378,6 +717,20 @@ impl<'s> LowerState<'s> {
         self.add_action_fn(action_fn_defn)
     }
 
+    /// In a `#[reduce_hooks]` grammar, names the method that will
+    /// hold the code of `action`, if the user wrote it for the
+    /// alternative `index` of a nonterminal with a plain name: the
+    /// nonterminals that macros and `?`, `*` and `+` make are left out.
+    fn name_reduce_hook(&mut self, action: r::ActionFn, nt_name: NonterminalString, index: usize) {
+        let plain = nt_name.0.to_string().chars().all(|c| c.is_alphanumeric() || c == '_');
+        if !self.reduce_hooks || !plain {
+            return;
+        }
+        if let r::ActionFnDefnKind::User(ref mut data) = self.action_fn_defns[action.index()].kind {
+            data.hook = Some(intern(&format!("reduce_{}_{}", nt_name, index)));
+        }
+    }
+
     fn add_action_fn(&mut self, action_fn_defn: r::ActionFnDefn) -> r::ActionFn {
         let index = r::ActionFn::new(self.action_fn_defns.len());
         self.action_fn_defns.push(action_fn_defn);
@@ -390,6 +743,10 @@ impl<'s> LowerState<'s> {
 
     fn symbol(&mut self, symbol: &pt::Symbol) -> r::Symbol {
         match symbol.kind {
+            pt::SymbolKind::Terminal(TerminalString::Any) => {
+                self.uses_wildcard = true;
+                r::Symbol::Terminal(TerminalString::Any)
+            }
             pt::SymbolKind::Terminal(id) => r::Symbol::Terminal(id),
             pt::SymbolKind::Nonterminal(id) => r::Symbol::Nonterminal(id),
             pt::SymbolKind::Choose(ref s) | pt::SymbolKind::Name(_, ref s) => self.symbol(s),
@@ -402,8 +759,10 @@ impl<'s> LowerState<'s> {
             pt::SymbolKind::Repeat(..) |
             pt::SymbolKind::Expr(..) |
             pt::SymbolKind::AmbiguousId(_) |
+            pt::SymbolKind::Variant(_) |
             pt::SymbolKind::Lookahead |
-            pt::SymbolKind::Lookbehind => {
+            pt::SymbolKind::Lookbehind |
+            pt::SymbolKind::LookaheadToken => {
                 unreachable!("symbol `{}` should have been normalized away by now",
                              symbol)
             }
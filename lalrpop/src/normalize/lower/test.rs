@@ -0,0 +1,53 @@
+use grammar::repr::{LrCodeGeneration, LrConstruction};
+use normalize::lower_helper;
+use parser;
+use session::{Optimize, Session};
+
+fn codegen(optimize: Optimize, annotation: &str, extra: &str) -> LrCodeGeneration {
+    let mut session = Session::test();
+    session.unit_test = false;
+    session.optimize = optimize;
+    let text = format!(r#"{} grammar; pub Expr: () = {{ "a" Expr => (), "b" => (), {} }};"#,
+                       annotation,
+                       extra);
+    let grammar = parser::parse_grammar(&text).unwrap();
+    lower_helper(&session, grammar, true).unwrap().algorithm.codegen
+}
+
+#[test]
+fn optimize_for_speed() {
+    assert_eq!(codegen(Optimize::Balanced, "", ""), LrCodeGeneration::TableDriven);
+    assert_eq!(codegen(Optimize::Size, "", ""), LrCodeGeneration::TableDriven);
+    assert_eq!(codegen(Optimize::Speed, "", ""), LrCodeGeneration::RecursiveAscent);
+
+    // the grammar's own choice, or what only the tables can do, wins
+    assert_eq!(codegen(Optimize::Speed, "#[table_driven]", ""), LrCodeGeneration::TableDriven);
+    assert_eq!(codegen(Optimize::Speed, "#[lookahead2]", ""), LrCodeGeneration::TableDriven);
+    assert_eq!(codegen(Optimize::Speed, "", "! => ()"), LrCodeGeneration::TableDriven);
+}
+
+#[test]
+fn items_on_request() {
+    let items = |annotation: &str| {
+        let text = format!(r#"{} grammar; pub File = Item*; Item: () = "a";"#, annotation);
+        let grammar = parser::parse_grammar(&text).unwrap();
+        lower_helper(&Session::test(), grammar, true).unwrap().item_start_nonterminals.len()
+    };
+    assert_eq!(items(""), 0);
+    assert_eq!(items("#[items]"), 1);
+}
+
+#[test]
+fn construction_from_session() {
+    let construction = |annotation: &str| {
+        let mut session = Session::test();
+        session.construction = Some(LrConstruction::Lalr);
+        let text = format!(r#"{} grammar; pub Expr: () = "a";"#, annotation);
+        let grammar = parser::parse_grammar(&text).unwrap();
+        lower_helper(&session, grammar, true).unwrap().algorithm.construction
+    };
+    assert_eq!(construction(""), Some(LrConstruction::Lalr));
+
+    // the grammar's own choice wins
+    assert_eq!(construction("#[LR1]"), Some(LrConstruction::Lr1));
+}
@@ -36,7 +36,8 @@ pub fn expand_macros(input: Grammar) -> NormResult<Grammar> {
                   })
                   .collect();
 
-    let mut expander = MacroExpander::new(macro_defs);
+    let literal_params = literal_params(&macro_defs);
+    let mut expander = MacroExpander::new(macro_defs, literal_params);
     try!(expander.expand(&mut items));
 
     Ok(Grammar { items: items, ..input})
@@ -44,14 +45,18 @@ pub fn expand_macros(input: Grammar) -> NormResult<Grammar> {
 
 struct MacroExpander {
     macro_defs: HashMap<NonterminalString, NonterminalData>,
+    literal_params: HashMap<NonterminalString, Vec<bool>>,
     expansion_set: HashSet<NonterminalString>,
     expansion_stack: Vec<Symbol>,
 }
 
 impl MacroExpander {
-    fn new(macro_defs: HashMap<NonterminalString, NonterminalData>) -> MacroExpander {
+    fn new(macro_defs: HashMap<NonterminalString, NonterminalData>,
+           literal_params: HashMap<NonterminalString, Vec<bool>>)
+           -> MacroExpander {
         MacroExpander {
             macro_defs: macro_defs,
+            literal_params: literal_params,
             expansion_stack: Vec::new(),
             expansion_set: HashSet::new()
         }
@@ -87,6 +92,9 @@ impl MacroExpander {
                     SymbolKind::Lookbehind =>
                         items.push(try!(self.expand_lookaround_symbol(
                             sym.span, "@R", ActionKind::Lookbehind))),
+                    SymbolKind::LookaheadToken =>
+                        items.push(try!(self.expand_lookaround_symbol(
+                            sym.span, "@T", ActionKind::LookaheadToken))),
                     _ =>
                         assert!(false, "don't know how to expand `{:?}`", sym)
                 }
@@ -100,6 +108,13 @@ impl MacroExpander {
             GrammarItem::ExternToken(..) => { }
             GrammarItem::InternToken(..) => { }
             GrammarItem::Use(..) => { }
+            GrammarItem::InlineTest(..) => { }
+            GrammarItem::Operators(..) => panic!("operator table not expanded"),
+            GrammarItem::Reserved(..) => { }
+            GrammarItem::TypeAlias(..) => { }
+            GrammarItem::TerminalConst(..) => { }
+            GrammarItem::Import(..) => { }
+            GrammarItem::RustItem(..) => { }
             GrammarItem::Nonterminal(ref mut data) => {
                 // Should not encounter macro definitions here,
                 // they've already been siphoned off.
@@ -123,6 +138,9 @@ impl MacroExpander {
             SymbolKind::AmbiguousId(id) => {
                 panic!("ambiguous id `{}` encountered after name resolution", id)
             }
+            SymbolKind::Variant(ref v) => {
+                panic!("variant `{}` encountered after name resolution", v)
+            }
             SymbolKind::Macro(ref mut m) => {
                 for sym in &mut m.args {
                     self.replace_symbol(sym);
@@ -144,7 +162,7 @@ impl MacroExpander {
                 self.replace_symbol(sym);
                 return;
             }
-            SymbolKind::Lookahead | SymbolKind::Lookbehind => {
+            SymbolKind::Lookahead | SymbolKind::Lookbehind | SymbolKind::LookaheadToken => {
             }
         }
 
@@ -166,7 +184,10 @@ impl MacroExpander {
 
         let mdef = match self.macro_defs.get(&msym.name) {
             Some(v) => v,
-            None => return_err!(span, "no macro definition found for `{}`", msym.name)
+            None => match msym.builtin_wrapper() {
+                Some(path) => return self.expand_wrapper_symbol(span, msym, path),
+                None => return_err!(span, "no macro definition found for `{}`", msym.name)
+            }
         };
 
         if mdef.args.len() != msym.args.len() {
@@ -174,6 +195,45 @@ impl MacroExpander {
                         mdef.args.len(), msym.name, msym.args.len());
         }
 
+        // check the arguments here, rather than when evaluating the
+        // conditions, so that the error points at this use of the macro
+        let literal_params = &self.literal_params[&msym.name];
+        for ((&param, arg), &literal) in mdef.args.iter().zip(&msym.args).zip(literal_params) {
+            match arg.kind {
+                SymbolKind::Terminal(TerminalString::Literal(TerminalLiteral::Quoted(_))) => { }
+                _ if !literal => { }
+                ref kind => {
+                    return_err!(arg.span,
+                                "`{}` compares its parameter `{}` with a string in an `if` \
+                                 condition, so the argument for it must be a quoted terminal \
+                                 like `\"x\"`, not `{}`",
+                                msym.name, param, kind);
+                }
+            }
+        }
+
+        // a parameter used as a macro, like `F` in `F<E>`, needs the name
+        // of one, and the name of a macro can only be passed to such a
+        // parameter, or on to another macro
+        for (&param, arg) in mdef.args.iter().zip(&msym.args) {
+            let passed_macro = match arg.kind {
+                SymbolKind::Nonterminal(id) => self.is_macro(id),
+                _ => false,
+            };
+            let (as_macro, as_arg) = param_uses(mdef, param);
+            if as_macro && !passed_macro {
+                return_err!(arg.span,
+                            "`{}` uses its parameter `{}` as a macro, so the argument for it \
+                             must be the name of a macro, not `{}`",
+                            msym.name, param, arg.kind);
+            }
+            if passed_macro && !as_macro && !as_arg {
+                return_err!(arg.span,
+                            "`{}` is a macro, but `{}` does not use its parameter `{}` as one",
+                            arg.kind, msym.name, param);
+            }
+        }
+
         let args: HashMap<NonterminalString, SymbolKind> =
             mdef.args.iter()
                      .cloned()
@@ -191,6 +251,7 @@ impl MacroExpander {
             }
             alternatives.push(Alternative {
                 span: span,
+                annotations: alternative.annotations.clone(),
                 expr: self.macro_expand_expr_symbol(&args, &alternative.expr),
                 condition: None,
                 action: alternative.action.clone(),
@@ -208,6 +269,42 @@ impl MacroExpander {
         }))
     }
 
+    fn is_macro(&self, id: NonterminalString) -> bool {
+        self.macro_defs.contains_key(&id) || Path::builtin_wrapper(id).is_some()
+    }
+
+    /// Expands a use of a built-in wrapper macro like `Box<X>` into:
+    ///
+    /// ```
+    /// #[inline]
+    /// Box<X>: ::std::boxed::Box<X> = X => ::std::boxed::Box::new(<>);
+    /// ```
+    fn expand_wrapper_symbol(&mut self, span: Span, msym: MacroSymbol, path: Path)
+                             -> NormResult<GrammarItem> {
+        let name = NonterminalString(intern(&msym.canonical_form()));
+        let symbol = msym.args.into_iter().next().unwrap();
+        let ty_ref = TypeRef::Nominal {
+            path: path.clone(),
+            types: vec![TypeRef::OfSymbol(symbol.kind.clone())]
+        };
+
+        Ok(GrammarItem::Nonterminal(NonterminalData {
+            public: false,
+            span: span,
+            name: name,
+            annotations: inline(span),
+            args: vec![],
+            type_decl: Some(ty_ref),
+            alternatives: vec![Alternative {
+                span: span,
+                annotations: vec![],
+                expr: ExprSymbol { symbols: vec![symbol] },
+                condition: None,
+                action: action(&format!("{}::new(<>)", path)),
+            }],
+        }))
+    }
+
     fn macro_expand_type_refs(&self,
                               args: &HashMap<NonterminalString, SymbolKind>,
                               type_refs: &[TypeRef])
@@ -315,7 +412,12 @@ impl MacroExpander {
                 },
             SymbolKind::Macro(ref msym) =>
                 SymbolKind::Macro(MacroSymbol {
-                    name: msym.name,
+                    // a parameter used as a macro stands for the macro
+                    // passed for it
+                    name: match args.get(&msym.name) {
+                        Some(&SymbolKind::Nonterminal(id)) => id,
+                        _ => msym.name,
+                    },
                     args: self.macro_expand_symbols(args, &msym.args),
                 }),
             SymbolKind::Repeat(ref r) =>
@@ -331,10 +433,14 @@ impl MacroExpander {
                 SymbolKind::Lookahead,
             SymbolKind::Lookbehind =>
                 SymbolKind::Lookbehind,
+            SymbolKind::LookaheadToken =>
+                SymbolKind::LookaheadToken,
             SymbolKind::Error =>
                 SymbolKind::Error,
             SymbolKind::AmbiguousId(id) =>
                 panic!("ambiguous id `{}` encountered after name resolution", id),
+            SymbolKind::Variant(ref v) =>
+                panic!("variant `{}` encountered after name resolution", v),
         };
 
         Symbol { span: symbol.span, kind: kind }
@@ -346,13 +452,15 @@ impl MacroExpander {
     fn expand_expr_symbol(&mut self, span: Span, expr: ExprSymbol) -> NormResult<GrammarItem> {
         let name = NonterminalString(intern(&expr.canonical_form()));
 
+        // Named symbols within a group like `(<e:Expr> ",")*` select
+        // the values to keep, just as `<>` does; the default action
+        // `(<>)` then expands to a tuple of the names.
         let ty_ref = match norm_util::analyze_expr(&expr) {
             Symbols::Named(names) => {
-                let (_, ex_id, ex_sym) = names[0];
-                return_err!(
-                    span,
-                    "named symbols like `{}:{}` are only allowed at the top-level of a nonterminal",
-                    ex_id, ex_sym)
+                maybe_tuple(
+                    names.into_iter()
+                         .map(|(_, _, s)| TypeRef::OfSymbol(s.kind.clone()))
+                         .collect())
             }
             Symbols::Anon(syms) => {
                 maybe_tuple(
@@ -370,6 +478,7 @@ impl MacroExpander {
             args: vec![],
             type_decl: Some(ty_ref),
             alternatives: vec![Alternative { span: span,
+                                             annotations: vec![],
                                              expr: expr,
                                              condition: None,
                                              action: action("(<>)") }]
@@ -407,6 +516,7 @@ impl MacroExpander {
                         // X* =
                         Alternative {
                             span: span,
+                            annotations: vec![],
                             expr: ExprSymbol { symbols: vec![] },
                             condition: None,
                             action: action("vec![]")
@@ -415,6 +525,7 @@ impl MacroExpander {
                         // X* = <v:X+>
                         Alternative {
                             span: span,
+                            annotations: vec![],
                             expr: ExprSymbol {
                                 symbols: vec![
                                     Symbol::new(
@@ -446,6 +557,7 @@ impl MacroExpander {
                         // X+ = X
                         Alternative {
                             span: span,
+                            annotations: vec![],
                             expr: ExprSymbol {
                                 symbols: vec![repeat.symbol.clone()]
                             },
@@ -456,6 +568,7 @@ impl MacroExpander {
                         // X+ = <v:X+> <e:X>
                         Alternative {
                             span: span,
+                            annotations: vec![],
                             expr: ExprSymbol {
                                 symbols: vec![
                                     Symbol::new(span, SymbolKind::Name(
@@ -484,6 +597,7 @@ impl MacroExpander {
                     alternatives: vec![
                         // X? = X => Some(<>)
                         Alternative { span: span,
+                                      annotations: vec![],
                                       expr: ExprSymbol {
                                           symbols: vec![repeat.symbol.clone()]
                                       },
@@ -492,6 +606,7 @@ impl MacroExpander {
 
                         // X? = { => None; }
                         Alternative { span: span,
+                                      annotations: vec![],
                                       expr: ExprSymbol {
                                           symbols: vec![]
                                       },
@@ -514,6 +629,7 @@ impl MacroExpander {
             type_decl: None,
             alternatives: vec![
                 Alternative { span: span,
+                              annotations: vec![],
                               expr: ExprSymbol { symbols: vec![] },
                               condition: None,
                               action: Some(action) }]
@@ -521,6 +637,116 @@ impl MacroExpander {
     }
 }
 
+/// For each macro, which of its parameters must be given quoted
+/// terminals as arguments: those that one of its conditions compares
+/// with a string, and those that it passes on to another macro in
+/// such a position. (`resolve` has checked that conditions only use
+/// the parameters of their macro.)
+fn literal_params(macro_defs: &HashMap<NonterminalString, NonterminalData>)
+                  -> HashMap<NonterminalString, Vec<bool>>
+{
+    let mut literal: HashMap<NonterminalString, Vec<bool>> =
+        macro_defs.iter()
+                  .map(|(&name, data)| (name, vec![false; data.args.len()]))
+                  .collect();
+
+    // each `(macro, index, callee, callee_index)` says that parameter
+    // `index` of `macro` is the argument `callee_index` of `callee`
+    let mut passes = vec![];
+    for (&name, data) in macro_defs {
+        for alternative in &data.alternatives {
+            if let Some(ref c) = alternative.condition {
+                let index = data.args.iter().position(|&arg| arg == c.lhs).unwrap();
+                literal.get_mut(&name).unwrap()[index] = true;
+            }
+            for symbol in &alternative.expr.symbols {
+                passed_params(name, &data.args, symbol, &mut passes);
+            }
+        }
+    }
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &(name, index, callee, callee_index) in &passes {
+            let needed = literal.get(&callee).and_then(|l| l.get(callee_index)) == Some(&true);
+            if needed && !literal[&name][index] {
+                literal.get_mut(&name).unwrap()[index] = true;
+                changed = true;
+            }
+        }
+    }
+
+    literal
+}
+
+/// Whether the macro `data` uses its parameter `param` as a macro,
+/// like `F` in `F<E>`, and whether it passes it to another macro, like
+/// `F` in `Apply<F, E>`.
+fn param_uses(data: &NonterminalData, param: NonterminalString) -> (bool, bool) {
+    let mut uses = (false, false);
+    for alternative in &data.alternatives {
+        for symbol in &alternative.expr.symbols {
+            symbol_param_uses(param, symbol, &mut uses);
+        }
+    }
+    uses
+}
+
+fn symbol_param_uses(param: NonterminalString, symbol: &Symbol, uses: &mut (bool, bool)) {
+    match symbol.kind {
+        SymbolKind::Macro(ref msym) => {
+            if msym.name == param {
+                uses.0 = true;
+            }
+            for arg in &msym.args {
+                if let SymbolKind::Nonterminal(id) = arg.kind {
+                    if id == param {
+                        uses.1 = true;
+                    }
+                }
+                symbol_param_uses(param, arg, uses);
+            }
+        }
+        SymbolKind::Expr(ref expr) => {
+            for symbol in &expr.symbols {
+                symbol_param_uses(param, symbol, uses);
+            }
+        }
+        SymbolKind::Repeat(ref repeat) => symbol_param_uses(param, &repeat.symbol, uses),
+        SymbolKind::Choose(ref symbol) |
+        SymbolKind::Name(_, ref symbol) => symbol_param_uses(param, symbol, uses),
+        _ => { }
+    }
+}
+
+fn passed_params(name: NonterminalString,
+                 params: &[NonterminalString],
+                 symbol: &Symbol,
+                 passes: &mut Vec<(NonterminalString, usize, NonterminalString, usize)>) {
+    match symbol.kind {
+        SymbolKind::Macro(ref msym) => {
+            for (callee_index, arg) in msym.args.iter().enumerate() {
+                if let SymbolKind::Nonterminal(id) = arg.kind {
+                    if let Some(index) = params.iter().position(|&p| p == id) {
+                        passes.push((name, index, msym.name, callee_index));
+                    }
+                }
+                passed_params(name, params, arg, passes);
+            }
+        }
+        SymbolKind::Expr(ref expr) => {
+            for symbol in &expr.symbols {
+                passed_params(name, params, symbol, passes);
+            }
+        }
+        SymbolKind::Repeat(ref repeat) => passed_params(name, params, &repeat.symbol, passes),
+        SymbolKind::Choose(ref symbol) |
+        SymbolKind::Name(_, ref symbol) => passed_params(name, params, symbol, passes),
+        _ => { }
+    }
+}
+
 fn maybe_tuple(v: Vec<TypeRef>) -> TypeRef {
     if v.len() == 1 {
         v.into_iter().next().unwrap()
@@ -537,5 +763,6 @@ fn inline(span: Span) -> Vec<Annotation> {
     vec![Annotation {
                 id_span: span,
                 id: intern(INLINE),
+                arg: None,
     }]
 }
@@ -1,5 +1,5 @@
 use parser;
-use test_util::compare;
+use test_util::{check_norm_err, compare};
 
 use super::expand_macros;
 
@@ -49,6 +49,55 @@ grammar;
     compare(actual, expected);
 }
 
+#[test]
+fn test_plus_with_named_group() {
+    let grammar = parser::parse_grammar(r#"
+grammar;
+    Pairs = (<k:"Id"> "=" <v:"Num"> ",")+;
+"#).unwrap();
+
+    let actual = expand_macros(grammar).unwrap();
+
+    let expected = parser::parse_grammar(r##"
+grammar;
+    Pairs = `(k:"Id" "=" v:"Num" ",")+`;
+
+    `(k:"Id" "=" v:"Num" ",")+`: ::std::vec::Vec<#`(k:"Id" "=" v:"Num" ",")`#> = {
+        `(k:"Id" "=" v:"Num" ",")` => vec![<>],
+        <v:`(k:"Id" "=" v:"Num" ",")+`> <e:`(k:"Id" "=" v:"Num" ",")`> => { let mut v = v; v.push(e); v },
+    };
+
+    #[inline]
+    `(k:"Id" "=" v:"Num" ",")`: (#"Id"#, #"Num"#) = {
+        <k:"Id"> "=" <v:"Num"> "," => (<>),
+    };
+"##).unwrap();
+
+    compare(actual, expected);
+}
+
+#[test]
+fn test_builtin_box() {
+    let grammar = parser::parse_grammar(r#"
+grammar;
+    Ids = Box<"Id">;
+"#).unwrap();
+
+    let actual = expand_macros(grammar).unwrap();
+
+    let expected = parser::parse_grammar(r##"
+grammar;
+    Ids = `Box<"Id">`;
+
+    #[inline]
+    `Box<"Id">`: ::std::boxed::Box<#"Id"#> = {
+        "Id" => ::std::boxed::Box::new(<>),
+    };
+"##).unwrap();
+
+    compare(actual, expected);
+}
+
 #[test]
 fn test_if_match() {
     let grammar = parser::parse_grammar(r#"
@@ -98,3 +147,77 @@ fn test_lookahead() {
 
     compare(actual, expected);
 }
+
+#[test]
+fn test_if_with_nonterminal_argument() {
+    let grammar = r#"grammar; Expr<E> = { "A" if E == "A" }; Outer<F> = Expr<F>; X = Outer<X>;"#;
+    let span = r#"                                                                      ~  "#;
+    let err = expand_macros(parser::parse_grammar(grammar).unwrap()).unwrap_err();
+    check_norm_err(r#"`Outer` compares its parameter `F` with a string"#, span, err);
+}
+
+#[test]
+fn test_macro_argument() {
+    let grammar = parser::parse_grammar(r#"
+grammar;
+    Apply<F, E> = F<E>;
+    Twice<F, E> = Apply<F, E> Apply<F, E>;
+
+    Ids = Twice<Box, "Id">;
+"#).unwrap();
+
+    let actual = expand_macros(grammar).unwrap();
+
+    let expected = parser::parse_grammar(r##"
+grammar;
+    Ids = `Twice<Box, "Id">`;
+
+    `Twice<Box, "Id">` = `Apply<Box, "Id">` `Apply<Box, "Id">`;
+
+    `Apply<Box, "Id">` = `Box<"Id">`;
+
+    #[inline]
+    `Box<"Id">`: ::std::boxed::Box<#"Id"#> = {
+        "Id" => ::std::boxed::Box::new(<>),
+    };
+"##).unwrap();
+
+    compare(actual, expected);
+}
+
+#[test]
+fn test_equal_expansions_shared() {
+    let grammar = parser::parse_grammar(r#"
+grammar;
+    Apply<F, E> = F<E>;
+    Wrap<E> = "(" E ")";
+
+    A = Apply<Wrap, "Id">;
+    B = Wrap<"Id">;
+"#).unwrap();
+
+    let actual = expand_macros(grammar).unwrap();
+    let names: Vec<String> = actual.items
+                                   .iter()
+                                   .filter_map(|item| item.as_nonterminal())
+                                   .map(|data| data.name.to_string())
+                                   .collect();
+    assert_eq!(names, vec!["A", "B", r#"Wrap<"Id">"#, r#"Apply<Wrap, "Id">"#]);
+}
+
+#[test]
+fn test_macro_parameter_without_macro() {
+    let grammar = r#"grammar; Apply<F, E> = F<E>; X = Apply<"x", "y">;"#;
+    let span = r#"                                       ~~~       "#;
+    let err = expand_macros(parser::parse_grammar(grammar).unwrap()).unwrap_err();
+    check_norm_err(r#"`Apply` uses its parameter `F` as a macro"#, span, err);
+}
+
+#[test]
+fn test_macro_for_symbol_parameter() {
+    let grammar = r#"grammar; Wrap<E> = "(" E ")"; X = Wrap<Wrap>;"#;
+    let span = r#"                                       ~~~~  "#;
+    let err = expand_macros(parser::parse_grammar(grammar).unwrap()).unwrap_err();
+    check_norm_err(r#"`Wrap` is a macro, but `Wrap` does not use its parameter `E` as one"#,
+                   span, err);
+}
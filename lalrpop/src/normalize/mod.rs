@@ -45,9 +45,13 @@ fn normalize_helper(session: &Session,
 }
 
 fn lower_helper(session: &Session, grammar: pt::Grammar, validate: bool) -> NormResult<r::Grammar> {
+    let grammar = profile!(session, "Operator expansion", try!(operators::expand_operators(grammar)));
     profile!(session, "Grammar validation", if validate { try!(prevalidate::validate(&grammar)); });
+    let grammar = profile!(session, "Type alias expansion", try!(type_alias::expand_type_aliases(grammar)));
+    let grammar = profile!(session, "Prelude", prelude::add_prelude(grammar));
     let grammar = profile!(session, "Grammar resolution", try!(resolve::resolve(grammar)));
     let grammar = profile!(session, "Macro expansion", try!(macro_expand::expand_macros(grammar)));
+    let grammar = profile!(session, "Scannerless terminals", try!(scannerless::split_terminals(grammar)));
     let grammar = profile!(session, "Token check", try!(token_check::validate(grammar)));
     let types = profile!(session, "Infer types", try!(tyinfer::infer_types(&grammar)));
     let grammar = profile!(session, "Lowering", try!(lower::lower(session, grammar, types)));
@@ -56,9 +60,25 @@ fn lower_helper(session: &Session, grammar: pt::Grammar, validate: bool) -> Norm
 
 // These are executed *IN ORDER*:
 
+// Expands the `operators(Atom) { ... }` tables into one nonterminal
+// per precedence level.
+//
+// AFTER THIS POINT: No more operator tables.
+mod operators;
+
 // Check most safety conditions.
 mod prevalidate;
 
+// Expands the `type` aliases declared in the grammar within the type
+// annotations of nonterminals.
+//
+// AFTER THIS POINT: No more type aliases.
+mod type_alias;
+
+// Adds the macros of the prelude, like `Sep<E, S>`, that the grammar
+// uses without defining them.
+mod prelude;
+
 // Resolve identifiers into terminals/nonterminals etc.
 mod resolve;
 
@@ -77,6 +97,13 @@ mod resolve;
 // may occur.
 mod macro_expand;
 
+// In a `#[scannerless]` grammar, rewrites the terminals so that each
+// matches a single character, and no two match the same one.
+//
+// AFTER THIS POINT: In a `#[scannerless]` grammar, no multi-character
+// or overlapping terminals.
+mod scannerless;
+
 // Check if there is an extern token and all terminals have have a
 // conversion; if no extern token, synthesize an intern token.
 mod token_check;
@@ -0,0 +1,213 @@
+//! Expands the `operators(Atom) { ... }` tables declared in the
+//! grammar into a tower of ordinary nonterminals, one per precedence
+//! level. For example,
+//!
+//! ```
+//! Expr: i32 = operators(Term) {
+//!     left { "+" => l + r },
+//!     right { "^" => l.pow(r as u32) },
+//!     prefix { "-" => -e },
+//! };
+//! ```
+//!
+//! becomes
+//!
+//! ```
+//! Expr: i32 = { <l:Expr> "+" <r:`Expr[2]`> => l + r, `Expr[2]` };
+//! `Expr[2]`: i32 = { <l:`Expr[3]`> "^" <r:`Expr[2]`> => l.pow(r as u32), `Expr[3]` };
+//! `Expr[3]`: i32 = { "-" <e:`Expr[3]`> => -e, Term };
+//! ```
+//!
+//! Mixfix operators like `<c:_> "?" <t:_> ":" <f:_>` spell out where
+//! their operands go instead; see `expand` for how the holes are
+//! filled in.
+
+use super::{NormError, NormResult};
+
+use grammar::parse_tree::*;
+use intern::{intern, InternedString};
+use util::Sep;
+
+#[cfg(test)]
+mod test;
+
+pub fn expand_operators(mut grammar: Grammar) -> NormResult<Grammar> {
+    let mut items = Vec::with_capacity(grammar.items.len());
+    for item in grammar.items {
+        match item {
+            GrammarItem::Operators(operators) => items.extend(try!(expand(operators))),
+            item => items.push(item),
+        }
+    }
+    grammar.items = items;
+    Ok(grammar)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Associativity {
+    Left,
+    Right,
+    NonAssoc,
+    Prefix,
+    Postfix,
+}
+
+const ASSOCIATIVITIES: &'static [(&'static str, Associativity)] = &[
+    ("left", Associativity::Left),
+    ("right", Associativity::Right),
+    ("nonassoc", Associativity::NonAssoc),
+    ("prefix", Associativity::Prefix),
+    ("postfix", Associativity::Postfix),
+];
+
+fn associativity(level: &OperatorLevel) -> NormResult<Associativity> {
+    match ASSOCIATIVITIES.iter().find(|&&(name, _)| intern(name) == level.associativity) {
+        Some(&(_, associativity)) => Ok(associativity),
+        None => {
+            let names: Vec<_> = ASSOCIATIVITIES.iter().map(|&(name, _)| name).collect();
+            return_err!(level.span,
+                        "unrecognized associativity `{}`, try one of the following: {}",
+                        level.associativity,
+                        Sep(", ", &names))
+        }
+    }
+}
+
+fn expand(operators: Operators) -> NormResult<Vec<GrammarItem>> {
+    if !operators.args.is_empty() {
+        return_err!(operators.span,
+                    "macros like `{}` cannot be defined with an operator table",
+                    operators.name);
+    }
+
+    if operators.levels.is_empty() {
+        let alternative = fallthrough(operators.span, operators.atom.clone());
+        return Ok(vec![nonterminal(&operators, 0, operators.name, vec![alternative])]);
+    }
+
+    // The nonterminal for the loosest level is the one being
+    // defined; each tighter level gets a name like `Expr[2]`, which
+    // cannot clash with anything the user writes.
+    let names: Vec<NonterminalString> =
+        (0..operators.levels.len())
+            .map(|index| if index == 0 {
+                operators.name
+            } else {
+                NonterminalString(intern(&format!("{}[{}]", operators.name, index + 1)))
+            })
+            .collect();
+
+    let mut items = vec![];
+    for (index, level) in operators.levels.iter().enumerate() {
+        let associativity = try!(associativity(level));
+        let top = || Symbol::new(level.span, SymbolKind::Nonterminal(names[0]));
+        let this = || Symbol::new(level.span, SymbolKind::Nonterminal(names[index]));
+        let next = || match names.get(index + 1) {
+            Some(&name) => Symbol::new(level.span, SymbolKind::Nonterminal(name)),
+            None => operators.atom.clone(),
+        };
+
+        let mut alternatives = vec![];
+        for operator in &level.operators {
+            if operator.parts.iter().all(|part| part.is_hole()) {
+                return_err!(operator.span, "operators must contain at least one symbol");
+            }
+
+            let symbols = if operator.parts.iter().any(|part| part.is_hole()) {
+                // A mixfix operator, like `_ "?" _ ":" _`: the holes
+                // at either end are governed by the associativity of
+                // the level, whereas those in the middle are
+                // delimited on both sides and so can take any
+                // expression at all.
+                let last = operator.parts.len() - 1;
+                operator.parts
+                        .iter()
+                        .enumerate()
+                        .map(|(position, part)| match part.as_hole() {
+                            None => part.clone(),
+                            Some(name) => {
+                                let operand = if position == 0 {
+                                    match associativity {
+                                        Associativity::Left | Associativity::Postfix => this(),
+                                        _ => next(),
+                                    }
+                                } else if position == last {
+                                    match associativity {
+                                        Associativity::Right | Associativity::Prefix => this(),
+                                        _ => next(),
+                                    }
+                                } else {
+                                    top()
+                                };
+                                let operand = Symbol::new(part.span, operand.kind);
+                                match name {
+                                    Some(name) => named(name, operand),
+                                    None => operand,
+                                }
+                            }
+                        })
+                        .collect()
+            } else {
+                let op = operator.parts.clone();
+                let (l, r, e) = (intern("l"), intern("r"), intern("e"));
+                let (before, after) = match associativity {
+                    Associativity::Left =>
+                        (Some(named(l, this())), Some(named(r, next()))),
+                    Associativity::Right =>
+                        (Some(named(l, next())), Some(named(r, this()))),
+                    Associativity::NonAssoc =>
+                        (Some(named(l, next())), Some(named(r, next()))),
+                    Associativity::Prefix =>
+                        (None, Some(named(e, this()))),
+                    Associativity::Postfix =>
+                        (Some(named(e, this())), None),
+                };
+                before.into_iter().chain(op).chain(after).collect()
+            };
+
+            alternatives.push(Alternative {
+                span: operator.span,
+                annotations: vec![],
+                expr: ExprSymbol { symbols: symbols },
+                condition: None,
+                action: Some(operator.action.clone()),
+            });
+        }
+        alternatives.push(fallthrough(level.span, next()));
+
+        items.push(nonterminal(&operators, index, names[index], alternatives));
+    }
+
+    Ok(items)
+}
+
+fn nonterminal(operators: &Operators,
+               index: usize,
+               name: NonterminalString,
+               alternatives: Vec<Alternative>)
+               -> GrammarItem {
+    let loosest = index == 0;
+    GrammarItem::Nonterminal(NonterminalData {
+        public: loosest && operators.public,
+        name: name,
+        annotations: if loosest { operators.annotations.clone() } else { vec![] },
+        span: if loosest { operators.span } else { operators.levels[index].span },
+        args: vec![],
+        type_decl: operators.type_decl.clone(),
+        alternatives: alternatives,
+    })
+}
+
+fn named(name: InternedString, symbol: Symbol) -> Symbol {
+    Symbol::new(symbol.span, SymbolKind::Name(name, Box::new(symbol)))
+}
+
+fn fallthrough(span: Span, symbol: Symbol) -> Alternative {
+    Alternative {
+        span: span,
+        annotations: vec![],
+        expr: ExprSymbol { symbols: vec![symbol] },
+        condition: None,
+        action: None,
+    }
+}
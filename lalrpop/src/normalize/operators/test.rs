@@ -0,0 +1,133 @@
+use parser;
+use test_util::{self, compare};
+
+use super::expand_operators;
+
+fn check_err(expected_err: &str, grammar: &str, span: &str) {
+    let parsed_grammar = parser::parse_grammar(&grammar).unwrap();
+    let err = expand_operators(parsed_grammar).unwrap_err();
+    test_util::check_norm_err(expected_err, span, err);
+}
+
+#[test]
+fn test_levels() {
+    let grammar = parser::parse_grammar(r#"
+grammar;
+    pub Expr: i32 = operators(Term) {
+        left { "+" => l + r, "-" => l - r },
+        right { "^" => l.pow(r as u32) },
+        nonassoc { "==" => (l == r) as i32 },
+        prefix { "-" => -e },
+        postfix { "!" => fact(e) },
+    };
+"#).unwrap();
+
+    let actual = expand_operators(grammar).unwrap();
+
+    let expected = parser::parse_grammar(r#"
+grammar;
+    pub Expr: i32 = {
+        <l:`Expr`> "+" <r:`Expr[2]`> => l + r,
+        <l:`Expr`> "-" <r:`Expr[2]`> => l - r,
+        `Expr[2]`,
+    };
+
+    `Expr[2]`: i32 = {
+        <l:`Expr[3]`> "^" <r:`Expr[2]`> => l.pow(r as u32),
+        `Expr[3]`,
+    };
+
+    `Expr[3]`: i32 = {
+        <l:`Expr[4]`> "==" <r:`Expr[4]`> => (l == r) as i32,
+        `Expr[4]`,
+    };
+
+    `Expr[4]`: i32 = {
+        "-" <e:`Expr[4]`> => -e,
+        `Expr[5]`,
+    };
+
+    `Expr[5]`: i32 = {
+        <e:`Expr[5]`> "!" => fact(e),
+        Term,
+    };
+"#).unwrap();
+
+    compare(actual, expected);
+}
+
+#[test]
+fn test_no_levels() {
+    let grammar = parser::parse_grammar(r#"
+grammar;
+    Expr = operators(Term) { };
+"#).unwrap();
+
+    let actual = expand_operators(grammar).unwrap();
+
+    let expected = parser::parse_grammar(r#"
+grammar;
+    Expr = Term;
+"#).unwrap();
+
+    compare(actual, expected);
+}
+
+#[test]
+fn test_mixfix() {
+    let grammar = parser::parse_grammar(r#"
+grammar;
+    pub Expr: i32 = operators(Term) {
+        right { <c:_> "?" <t:_> ":" <f:_> => if c != 0 { t } else { f } },
+        prefix { "if" _ "then" _ "else" _ => 0, "|" <e:_> "|" => e.abs() },
+        left { _ "[" <i:_> "]" => i },
+    };
+"#).unwrap();
+
+    let actual = expand_operators(grammar).unwrap();
+
+    let expected = parser::parse_grammar(r#"
+grammar;
+    pub Expr: i32 = {
+        <c:`Expr[2]`> "?" <t:`Expr`> ":" <f:`Expr`> => if c != 0 { t } else { f },
+        `Expr[2]`,
+    };
+
+    `Expr[2]`: i32 = {
+        "if" `Expr` "then" `Expr` "else" `Expr[2]` => 0,
+        "|" <e:`Expr`> "|" => e.abs(),
+        `Expr[3]`,
+    };
+
+    `Expr[3]`: i32 = {
+        `Expr[3]` "[" <i:`Expr`> "]" => i,
+        Term,
+    };
+"#).unwrap();
+
+    compare(actual, expected);
+}
+
+#[test]
+fn unknown_associativity() {
+    check_err(
+        r#"unrecognized associativity `lft`, try one of the following: left, right"#,
+        r#"grammar; Expr = operators(Term) { lft { "+" => l + r } };"#,
+        r#"                                  ~~~                    "#);
+}
+
+#[test]
+fn operators_macro() {
+    check_err(
+        r#"macros like `Expr` cannot be defined with an operator table"#,
+        r#"grammar; Expr<T> = operators(T) { };"#,
+        r#"         ~~~~~~~                    "#);
+}
+
+#[test]
+fn operator_without_symbols() {
+    check_err(
+        r#"operators must contain at least one symbol"#,
+        r#"grammar; Expr = operators(Term) { left { _ _ => 0 } };"#,
+        r#"                                         ~~~~~~~~~    "#);
+}
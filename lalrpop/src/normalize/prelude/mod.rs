@@ -0,0 +1,139 @@
+//! The prelude: macros like `Sep<E, S>` that any grammar can use
+//! without defining them. Those that a grammar uses are added to it,
+//! unless it defines a nonterminal of the same name, which then takes
+//! their place. They get the span of their first use, so that an error
+//! in them points at the grammar rather than at a text the user has
+//! never seen.
+
+use collections::{map, set, Map, Set};
+use grammar::parse_tree::*;
+use parser;
+
+#[cfg(test)]
+mod test;
+
+const PRELUDE: &'static str = r#"
+    // `E`s separated by `S`s, with an optional `S` at the end
+    Sep<E, S>: ::std::vec::Vec<E> =
+        <v:(<E> S)*> <e:E?> => match e {
+            None => v,
+            Some(e) => {
+                let mut v = v;
+                v.push(e);
+                v
+            }
+        };
+
+    // `E`s separated by commas
+    Comma<E>: ::std::vec::Vec<E> = Sep<E, ",">;
+"#;
+
+pub fn add_prelude(mut grammar: Grammar) -> Grammar {
+    let mut defined: Set<NonterminalString> = grammar.items
+                                                     .iter()
+                                                     .filter_map(|item| item.as_nonterminal())
+                                                     .map(|data| data.name)
+                                                     .collect();
+
+    let mut uses = vec![];
+    for data in grammar.items.iter().filter_map(|item| item.as_nonterminal()) {
+        macro_uses(data, &mut uses);
+    }
+    if uses.iter().all(|&(_, name)| defined.contains(&name)) {
+        return grammar;
+    }
+
+    let mut prelude = prelude_macros();
+    uses.reverse();
+    while let Some((span, name)) = uses.pop() {
+        if !defined.insert(name) {
+            continue;
+        }
+        if let Some(mut data) = prelude.remove(&name) {
+            respan(&mut data, span);
+            let start = uses.len();
+            macro_uses(&data, &mut uses);
+            uses[start..].reverse();
+            grammar.items.push(GrammarItem::Nonterminal(data));
+        }
+    }
+    grammar
+}
+
+fn prelude_macros() -> Map<NonterminalString, NonterminalData> {
+    let items = parser::parse_grammar_items(PRELUDE, 0).unwrap();
+    let mut macros = map();
+    for item in items {
+        if let GrammarItem::Nonterminal(data) = item {
+            macros.insert(data.name, data);
+        }
+    }
+    macros
+}
+
+/// Adds the macros that `data` uses, with the span of each use, in
+/// the order in which they appear.
+fn macro_uses(data: &NonterminalData, uses: &mut Vec<(Span, NonterminalString)>) {
+    for alternative in &data.alternatives {
+        for symbol in &alternative.expr.symbols {
+            symbol_macro_uses(symbol, uses);
+        }
+    }
+}
+
+fn symbol_macro_uses(symbol: &Symbol, uses: &mut Vec<(Span, NonterminalString)>) {
+    match symbol.kind {
+        SymbolKind::Macro(ref msym) => {
+            uses.push((symbol.span, msym.name));
+            for arg in &msym.args {
+                // a bare name may be a macro, passed to one that takes macros
+                if let SymbolKind::AmbiguousId(name) = arg.kind {
+                    uses.push((arg.span, NonterminalString(name)));
+                }
+                symbol_macro_uses(arg, uses);
+            }
+        }
+        SymbolKind::Expr(ref expr) => {
+            for symbol in &expr.symbols {
+                symbol_macro_uses(symbol, uses);
+            }
+        }
+        SymbolKind::Repeat(ref repeat) => symbol_macro_uses(&repeat.symbol, uses),
+        SymbolKind::Choose(ref symbol) |
+        SymbolKind::Name(_, ref symbol) => symbol_macro_uses(symbol, uses),
+        _ => { }
+    }
+}
+
+fn respan(data: &mut NonterminalData, span: Span) {
+    data.span = span;
+    for alternative in &mut data.alternatives {
+        alternative.span = span;
+        if let Some(ref mut condition) = alternative.condition {
+            condition.span = span;
+        }
+        for symbol in &mut alternative.expr.symbols {
+            respan_symbol(symbol, span);
+        }
+    }
+}
+
+fn respan_symbol(symbol: &mut Symbol, span: Span) {
+    symbol.span = span;
+    match symbol.kind {
+        SymbolKind::Macro(ref mut msym) => {
+            for arg in &mut msym.args {
+                respan_symbol(arg, span);
+            }
+        }
+        SymbolKind::Expr(ref mut expr) => {
+            for symbol in &mut expr.symbols {
+                respan_symbol(symbol, span);
+            }
+        }
+        SymbolKind::Repeat(ref mut repeat) => respan_symbol(&mut repeat.symbol, span),
+        SymbolKind::Choose(ref mut symbol) |
+        SymbolKind::Name(_, ref mut symbol) => respan_symbol(symbol, span),
+        _ => { }
+    }
+}
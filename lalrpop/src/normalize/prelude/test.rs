@@ -0,0 +1,72 @@
+use parser;
+use grammar::parse_tree::{Grammar, NonterminalData, Span};
+use normalize::macro_expand::expand_macros;
+
+use super::{add_prelude, prelude_macros};
+
+fn names(grammar: &Grammar) -> Vec<String> {
+    grammar.items
+           .iter()
+           .filter_map(|item| item.as_nonterminal())
+           .map(|data| data.name.to_string())
+           .collect()
+}
+
+fn find<'g>(grammar: &'g Grammar, name: &str) -> &'g NonterminalData {
+    grammar.items
+           .iter()
+           .filter_map(|item| item.as_nonterminal())
+           .find(|data| data.name.to_string() == name)
+           .unwrap()
+}
+
+#[test]
+fn prelude_parses() {
+    let macros = prelude_macros();
+    assert!(macros.values().all(|data| data.is_macro_def()));
+}
+
+#[test]
+fn used_macros_added() {
+    let text = r#"grammar; Ids = Comma<"Id">;"#;
+    let grammar = add_prelude(parser::parse_grammar(text).unwrap());
+
+    // `Comma` brings `Sep` along, both with the span of the use
+    assert_eq!(names(&grammar), vec!["Ids", "Comma", "Sep"]);
+    let start = text.find("Comma").unwrap();
+    let span = Span(start, start + r#"Comma<"Id">"#.len());
+    assert_eq!(find(&grammar, "Comma").span, span);
+    assert_eq!(find(&grammar, "Sep").alternatives[0].expr.symbols[0].span, span);
+
+    let grammar = expand_macros(grammar).unwrap();
+    assert!(names(&grammar).contains(&r#"Sep<"Id", ",">"#.to_string()));
+}
+
+#[test]
+fn definitions_take_precedence() {
+    let grammar = add_prelude(parser::parse_grammar(r#"
+grammar;
+    Comma<E> = E ",";
+    Ids = Comma<"Id">;
+"#).unwrap());
+    assert_eq!(names(&grammar), vec!["Comma", "Ids"]);
+}
+
+#[test]
+fn unused_macros_left_out() {
+    let grammar = add_prelude(parser::parse_grammar(r#"grammar; Ids = "Id"+;"#).unwrap());
+    assert_eq!(names(&grammar), vec!["Ids"]);
+}
+
+#[test]
+fn macros_passed_as_arguments_added() {
+    let grammar = add_prelude(parser::parse_grammar(r#"
+grammar;
+    Apply<F, E> = F<E>;
+    Ids = Apply<Comma, "Id">;
+"#).unwrap());
+    assert_eq!(names(&grammar), vec!["Apply", "Ids", "Comma", "Sep"]);
+
+    let grammar = expand_macros(grammar).unwrap();
+    assert!(names(&grammar).contains(&r#"Comma<"Id">"#.to_string()));
+}
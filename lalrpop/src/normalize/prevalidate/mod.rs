@@ -43,10 +43,22 @@ struct Validator<'grammar> {
 
 impl<'grammar> Validator<'grammar> {
     fn validate(&self) -> NormResult<()> {
-        let allowed_names = vec![intern(LALR),
+        let allowed_names = vec![intern(LR1),
+                                 intern(LANE_TABLE),
+                                 intern(LALR),
+                                 intern(IELR),
+                                 intern(SLR),
+                                 intern(LOOKAHEAD2),
+                                 intern(PREFER_SHIFT),
+                                 intern(INCREMENTAL),
+                                 intern(PUSH),
+                                 intern(SCANNERLESS),
+                                 intern(REDUCE_HOOKS),
                                  intern(TABLE_DRIVEN),
                                  intern(RECURSIVE_ASCENT),
-                                 intern(TEST_ALL)];
+                                 intern(TEST_ALL),
+                                 intern(ITEMS),
+                                 intern(PREFIX)];
         for annotation in &self.grammar.annotations {
             if !allowed_names.contains(&annotation.id) {
                 return_err!(annotation.id_span,
@@ -55,6 +67,131 @@ impl<'grammar> Validator<'grammar> {
             }
         }
 
+        let constructions =
+            vec![intern(LR1), intern(LANE_TABLE), intern(LALR), intern(IELR), intern(SLR)];
+        let algorithms: Vec<_> = self.grammar
+                                     .annotations
+                                     .iter()
+                                     .filter(|a| constructions.contains(&a.id))
+                                     .collect();
+        if algorithms.len() > 1 {
+            return_err!(algorithms[1].id_span,
+                        "`#[{}]` cannot be combined with `#[{}]`",
+                        algorithms[1].id,
+                        algorithms[0].id);
+        }
+
+        // only the table-driven parsers know how to peek at the token
+        // after the lookahead
+        if self.grammar.annotations.iter().any(|a| a.id == intern(LOOKAHEAD2)) {
+            for annotation in &self.grammar.annotations {
+                if annotation.id == intern(RECURSIVE_ASCENT) || annotation.id == intern(TEST_ALL) {
+                    return_err!(annotation.id_span,
+                                "`#[{}]` cannot be combined with `#[{}]`",
+                                annotation.id,
+                                LOOKAHEAD2);
+                }
+            }
+        }
+
+        // the checkpoints are taken by the table-driven parser, in
+        // between tokens
+        if self.grammar.annotations.iter().any(|a| a.id == intern(INCREMENTAL)) {
+            for annotation in &self.grammar.annotations {
+                if annotation.id == intern(RECURSIVE_ASCENT) ||
+                    annotation.id == intern(TEST_ALL) ||
+                    annotation.id == intern(LOOKAHEAD2) {
+                    return_err!(annotation.id_span,
+                                "`#[{}]` cannot be combined with `#[{}]`",
+                                annotation.id,
+                                INCREMENTAL);
+                }
+            }
+        }
+
+        // likewise, the push parser keeps the table-driven parser's
+        // stacks in between tokens, and cannot wait for a second one
+        if self.grammar.annotations.iter().any(|a| a.id == intern(PUSH)) {
+            for annotation in &self.grammar.annotations {
+                if annotation.id == intern(RECURSIVE_ASCENT) ||
+                    annotation.id == intern(TEST_ALL) ||
+                    annotation.id == intern(LOOKAHEAD2) {
+                    return_err!(annotation.id_span,
+                                "`#[{}]` cannot be combined with `#[{}]`",
+                                annotation.id,
+                                PUSH);
+                }
+            }
+        }
+
+        // the iterator parses each item with the table-driven parser,
+        // and cannot leave a second token of lookahead for the next
+        if self.grammar.annotations.iter().any(|a| a.id == intern(ITEMS)) {
+            for annotation in &self.grammar.annotations {
+                if annotation.id == intern(RECURSIVE_ASCENT) ||
+                    annotation.id == intern(LOOKAHEAD2) {
+                    return_err!(annotation.id_span,
+                                "`#[{}]` cannot be combined with `#[{}]`",
+                                annotation.id,
+                                ITEMS);
+                }
+            }
+        }
+
+        // the prefix is found with the tables alone, which cannot say
+        // which way a second token of lookahead would go
+        if self.grammar.annotations.iter().any(|a| a.id == intern(PREFIX)) {
+            for annotation in &self.grammar.annotations {
+                if annotation.id == intern(RECURSIVE_ASCENT) ||
+                    annotation.id == intern(LOOKAHEAD2) {
+                    return_err!(annotation.id_span,
+                                "`#[{}]` cannot be combined with `#[{}]`",
+                                annotation.id,
+                                PREFIX);
+                }
+            }
+        }
+
+        // without a lexer, there are no tokens to match or declare
+        if let Some(scannerless) = self.grammar
+                                       .annotations
+                                       .iter()
+                                       .find(|a| a.id == intern(SCANNERLESS)) {
+            if self.extern_token.map_or(false, |d| d.enum_token.is_some()) {
+                return_err!(scannerless.id_span,
+                            "`#[{}]` cannot be combined with an external tokenizer",
+                            SCANNERLESS);
+            }
+            if let Some(match_token) = self.match_token {
+                return_err!(match_token.span,
+                            "a `#[{}]` grammar cannot have a `match` block",
+                            SCANNERLESS);
+            }
+            let reserved = self.grammar.items.iter().filter_map(|item| item.as_reserved()).next();
+            if let Some(reserved) = reserved {
+                return_err!(reserved.span,
+                            "a `#[{}]` grammar cannot have a `reserved` block",
+                            SCANNERLESS);
+            }
+        }
+
+        // the hooks are methods of the first parameter, which the
+        // action code can go on using under its own name
+        if let Some(hooks) = self.grammar
+                                 .annotations
+                                 .iter()
+                                 .find(|a| a.id == intern(REDUCE_HOOKS)) {
+            match self.grammar.parameters.first().map(|p| &p.ty) {
+                Some(&TypeRef::Ref { mutable: false, .. }) => {}
+                _ => {
+                    return_err!(hooks.id_span,
+                                "`#[{}]` needs a grammar parameter that is a shared reference, \
+                                 like `grammar(hooks: &MyHooks);`",
+                                REDUCE_HOOKS);
+                }
+            }
+        }
+
         for item in &self.grammar.items {
             match *item {
                 GrammarItem::Use(..) => { }
@@ -66,11 +203,14 @@ impl<'grammar> Validator<'grammar> {
                             "multiple match definitions are not permitted");
                     }
 
-                    // We may want to allow a limited extern to coexist with match in the future
+                    // An extern that only declares associated types, like
+                    // the `Location` of the tokens, can coexist with match
                     if let Some(d) = self.extern_token {
-                        return_err!(
-                            d.span,
-                            "extern and match definitions are mutually exclusive");
+                        if d.enum_token.is_some() {
+                            return_err!(
+                                d.span,
+                                "extern and match definitions are mutually exclusive");
+                        }
                     }
 
                     // Ensure that the catch all is final item of final block
@@ -94,11 +234,14 @@ impl<'grammar> Validator<'grammar> {
                             "multiple extern definitions are not permitted");
                     }
 
-                    // We may want to allow a limited extern to coexist with match in the future
+                    // An extern that only declares associated types, like
+                    // the `Location` of the tokens, can coexist with match
                     if let Some(d) = self.match_token {
-                        return_err!(
-                            d.span,
-                            "match and extern definitions are mutually exclusive");
+                        if data.enum_token.is_some() {
+                            return_err!(
+                                d.span,
+                                "match and extern definitions are mutually exclusive");
+                        }
                     }
 
                     let allowed_names = vec![intern(LOCATION), intern(ERROR)];
@@ -121,7 +264,8 @@ impl<'grammar> Validator<'grammar> {
                 }
                 GrammarItem::Nonterminal(ref data) => {
                     let inline_annotation = intern(INLINE);
-                    let known_annotations = vec![inline_annotation];
+                    let hook_annotation = intern(CONFLICT_HOOK);
+                    let known_annotations = vec![inline_annotation, hook_annotation];
                     let mut found_annotations = set();
                     for annotation in &data.annotations {
                         if !known_annotations.contains(&annotation.id) {
@@ -135,22 +279,184 @@ impl<'grammar> Validator<'grammar> {
                         } else if annotation.id == inline_annotation && data.public {
                             return_err!(annotation.id_span,
                                         "public items cannot be marked #[inline]");
+                        } else if annotation.id == hook_annotation {
+                            try!(self.validate_conflict_hook(annotation));
                         }
                     }
+                    if found_annotations.contains(&inline_annotation) &&
+                        found_annotations.contains(&hook_annotation) {
+                        return_err!(data.span,
+                                    "`#[{}]` cannot be combined with `#[{}]`",
+                                    CONFLICT_HOOK,
+                                    INLINE);
+                    }
 
                     for alternative in &data.alternatives {
                         try!(self.validate_alternative(alternative));
                     }
                 }
                 GrammarItem::InternToken(..) => { }
+                GrammarItem::TypeAlias(..) => { }
+                GrammarItem::TerminalConst(..) => { }
+                GrammarItem::Import(..) => { }
+                GrammarItem::RustItem(ref data) => {
+                    if let Some(annotation) = data.annotations.first() {
+                        return_err!(annotation.id_span,
+                                    "annotations are not permitted on Rust items like `{}`",
+                                    data.name);
+                    }
+                }
+                GrammarItem::InlineTest(ref data) => {
+                    try!(self.validate_inline_test(data));
+                }
+                GrammarItem::Operators(..) => panic!("operator table not expanded"),
+                GrammarItem::Reserved(ref data) => {
+                    if let Some(d) = self.extern_token {
+                        if d.enum_token.is_some() {
+                            return_err!(
+                                data.span,
+                                "reserved words can only be declared when using \
+                                 the built-in lexer, not with an extern token enum");
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_inline_test(&self, test: &InlineTest) -> NormResult<()> {
+        let test_annotation = intern(TEST);
+        match test.annotations.iter().find(|a| a.id != test_annotation) {
+            Some(annotation) => {
+                return_err!(annotation.id_span,
+                            "unrecognized annotation `{}`",
+                            annotation.id);
+            }
+            None if test.annotations.len() != 1 => {
+                return_err!(test.span,
+                            "inline tests must be marked with a single `#[{}]` annotation",
+                            TEST);
             }
+            None => { }
         }
+
+        if test.public {
+            return_err!(test.span, "inline tests cannot be declared `pub`");
+        }
+
+        let allowed_names = vec![intern(PARSES), intern(REJECTS)];
+        if !allowed_names.contains(&test.expectation) {
+            return_err!(test.expectation_span,
+                        "unrecognized expectation `{}`, try one of the following: {}",
+                        test.expectation,
+                        Sep(", ", &allowed_names));
+        }
+
+        if self.extern_token.map_or(false, |d| d.enum_token.is_some()) {
+            return_err!(test.span,
+                        "inline tests can only be used with the built-in lexer, \
+                         not with an extern token enum");
+        }
+
+        if !self.grammar.type_parameters.is_empty() || !self.grammar.parameters.is_empty() {
+            return_err!(test.span,
+                        "inline tests cannot be used in grammars with parameters");
+        }
+
+        let public = self.grammar
+                         .items
+                         .iter()
+                         .filter_map(|item| item.as_nonterminal())
+                         .any(|nt| nt.name == test.nonterminal && nt.public);
+        if !public {
+            return_err!(test.span,
+                        "inline tests can only exercise public nonterminals, \
+                         and `{}` is not one",
+                        test.nonterminal);
+        }
+
+        Ok(())
+    }
+
+    /// The generated parser asks the grammar's first parameter about
+    /// the conflicts of a `#[conflict_hook]` nonterminal, so there has
+    /// to be one, and only the table-driven parser knows how to ask.
+    fn validate_conflict_hook(&self, annotation: &Annotation) -> NormResult<()> {
+        if self.grammar.parameters.is_empty() {
+            return_err!(annotation.id_span,
+                        "`#[{}]` needs a grammar parameter to decide the conflicts, \
+                         like `grammar(hook: &MyHook);`",
+                        CONFLICT_HOOK);
+        }
+
+        for grammar_annotation in &self.grammar.annotations {
+            if grammar_annotation.id == intern(RECURSIVE_ASCENT) ||
+                grammar_annotation.id == intern(TEST_ALL) {
+                return_err!(grammar_annotation.id_span,
+                            "`#[{}]` cannot be combined with `#[{}]`",
+                            grammar_annotation.id,
+                            CONFLICT_HOOK);
+            }
+        }
+
         Ok(())
     }
 
     fn validate_alternative(&self,
                             alternative: &Alternative)
                             -> NormResult<()> {
+        let mut found_annotations = set();
+        for annotation in &alternative.annotations {
+            if annotation.id != intern(COST) && annotation.id != intern(PRECEDENCE) &&
+                annotation.id != intern(ASSOC) {
+                return_err!(annotation.id_span,
+                            "unrecognized annotation `{}`",
+                            annotation.id);
+            } else if !found_annotations.insert(annotation.id) {
+                return_err!(annotation.id_span,
+                            "duplicate annotation `{}`",
+                            annotation.id);
+            }
+            if annotation.id == intern(ASSOC) {
+                match annotation.arg {
+                    Some((_, a)) if ["left", "right", "none"].iter().any(|&s| a == intern(s)) => {}
+                    Some((span, _)) => {
+                        return_err!(span, "`#[{}]` must be `left`, `right` or `none`", ASSOC);
+                    }
+                    None => {
+                        return_err!(annotation.id_span,
+                                    "`#[{}]` requires `left`, `right` or `none`, like \
+                                     `#[{}(left)]`",
+                                    ASSOC,
+                                    ASSOC);
+                    }
+                }
+                continue;
+            }
+            match annotation.arg {
+                Some((span, n)) if n.to_string().chars().all(|c| c.is_digit(10)) => {
+                    if n.to_string().parse::<u32>().is_err() {
+                        return_err!(span, "`{}` is too large a {}", n, annotation.id);
+                    }
+                }
+                _ => {
+                    return_err!(annotation.id_span,
+                                "`#[{}]` requires a number, like `#[{}(2)]`",
+                                annotation.id,
+                                annotation.id);
+                }
+            }
+        }
+        if let Some(annotation) = alternative.annotations.iter().find(|a| a.id == intern(ASSOC)) {
+            if !found_annotations.contains(&intern(PRECEDENCE)) {
+                return_err!(annotation.id_span,
+                            "`#[{}]` requires a `#[{}]` on the same alternative",
+                            ASSOC,
+                            PRECEDENCE);
+            }
+        }
+
         try!(self.validate_expr(&alternative.expr));
 
         match norm_util::analyze_expr(&alternative.expr) {
@@ -235,9 +541,18 @@ impl<'grammar> Validator<'grammar> {
             SymbolKind::Expr(ref expr) => {
                 try!(self.validate_expr(expr));
             }
-            SymbolKind::AmbiguousId(_) => {
+            SymbolKind::AmbiguousId(_) | SymbolKind::Variant(_) => {
                 /* see resolve */
             }
+            SymbolKind::Terminal(TerminalString::Any) => {
+                let mut algorithm = r::Algorithm::default();
+                read_algorithm(&self.grammar.annotations, &mut algorithm);
+                if algorithm.codegen == r::LrCodeGeneration::RecursiveAscent {
+                    return_err!(symbol.span,
+                                "the wildcard terminal `_` is not yet supported by recursive \
+                                 ascent parsers");
+                }
+            }
             SymbolKind::Terminal(_) => {
                 /* see postvalidate! */
             }
@@ -263,7 +578,7 @@ impl<'grammar> Validator<'grammar> {
             SymbolKind::Choose(ref sym) | SymbolKind::Name(_, ref sym) => {
                 try!(self.validate_symbol(sym));
             }
-            SymbolKind::Lookahead | SymbolKind::Lookbehind => {
+            SymbolKind::Lookahead | SymbolKind::Lookbehind | SymbolKind::LookaheadToken => {
                 // if using an internal tokenizer, lookahead/lookbehind are ok.
                 if let Some(extern_token) = self.extern_token {
                     if extern_token.enum_token.is_some() {
@@ -55,6 +55,42 @@ fn unrecognized_annotation() {
         r#"           ~~~            "#);
 }
 
+#[test]
+fn conflicting_algorithms() {
+    check_err(
+        r#"`#\[IELR\]` cannot be combined with `#\[LALR\]`"#,
+        r#"#[LALR] #[IELR] grammar; Term = ();"#,
+        r#"          ~~~~                     "#);
+    check_err(
+        r#"`#\[LALR\]` cannot be combined with `#\[lane_table\]`"#,
+        r#"#[lane_table] #[LALR] grammar; Term = ();"#,
+        r#"                ~~~~                     "#);
+}
+
+#[test]
+fn lookahead2_recursive_ascent() {
+    check_err(
+        r#"`#\[recursive_ascent\]` cannot be combined with `#\[lookahead2\]`"#,
+        r#"#[lookahead2] #[recursive_ascent] grammar; Term = ();"#,
+        r#"                ~~~~~~~~~~~~~~~~                     "#);
+}
+
+#[test]
+fn incremental_recursive_ascent() {
+    check_err(
+        r#"`#\[recursive_ascent\]` cannot be combined with `#\[incremental\]`"#,
+        r#"#[incremental] #[recursive_ascent] grammar; Term = ();"#,
+        r#"                 ~~~~~~~~~~~~~~~~                     "#);
+}
+
+#[test]
+fn push_lookahead2() {
+    check_err(
+        r#"`#\[lookahead2\]` cannot be combined with `#\[push\]`"#,
+        r#"#[push] #[lookahead2] grammar; Term = ();"#,
+        r#"          ~~~~~~~~~~                     "#);
+}
+
 #[test]
 fn duplicate_annotation() {
     check_err(
@@ -95,6 +131,22 @@ fn extern_after_match_token() {
         r#"                     ~~~~~~                 "#);
 }
 
+#[test]
+fn reserved_with_extern_token() {
+    check_err(
+        r#"reserved words can only be declared when using the built-in lexer"#,
+        r#"grammar; extern { enum Tok { } } reserved { "async" }"#,
+        r#"                                 ~~~~~~~~           "#);
+}
+
+#[test]
+fn annotated_rust_item() {
+    check_err(
+        r#"annotations are not permitted on Rust items like `foo`"#,
+        r#"grammar; #[inline] fn foo() { }"#,
+        r#"           ~~~~~~              "#);
+}
+
 #[test]
 fn match_catch_all_first_of_last() {
     check_err(
@@ -125,4 +177,128 @@ fn mixing_names_and_anonymous_values() {
         r#"anonymous symbols like this one cannot be combined with named symbols like `b:B`"#,
         r#"grammar; Term = { <A> <b:B> => Alien: Eighth passanger of Nostromo};"#,
         r#"                  ~~~                                               "#);
-}
\ No newline at end of file
+}
+
+#[test]
+fn items_recursive_ascent() {
+    check_err(
+        r#"`#\[recursive_ascent\]` cannot be combined with `#\[items\]`"#,
+        r#"#[items] #[recursive_ascent] grammar; Term = ();"#,
+        r#"           ~~~~~~~~~~~~~~~~                     "#);
+}
+
+#[test]
+fn prefix_recursive_ascent() {
+    check_err(
+        r#"`#\[recursive_ascent\]` cannot be combined with `#\[prefix\]`"#,
+        r#"#[prefix] #[recursive_ascent] grammar; Term = ();"#,
+        r#"            ~~~~~~~~~~~~~~~~                     "#);
+}
+
+#[test]
+fn inline_test_unknown_expectation() {
+    check_err(
+        r#"unrecognized expectation `parse`, try one of the following: parses, rejects"#,
+        r#"grammar; pub A = "a"; #[test] A parse "a";"#,
+        r#"                                ~~~~~     "#);
+}
+
+#[test]
+fn inline_test_private_nonterminal() {
+    check_err(
+        r#"inline tests can only exercise public nonterminals, and `A` is not one"#,
+        r#"grammar; A = "a"; #[test] A parses "a";"#,
+        r#"                          ~            "#);
+}
+
+#[test]
+fn inline_test_with_parameters() {
+    check_err(
+        r#"inline tests cannot be used in grammars with parameters"#,
+        r#"grammar(x: u32); pub A = "a"; #[test] A parses "a";"#,
+        r#"                                      ~            "#);
+}
+
+#[test]
+fn prefix_lookahead2() {
+    check_err(
+        r#"`#\[lookahead2\]` cannot be combined with `#\[prefix\]`"#,
+        r#"#[prefix] #[lookahead2] grammar; Term = ();"#,
+        r#"            ~~~~~~~~~~                     "#);
+}
+
+#[test]
+fn conflict_hook_without_parameter() {
+    check_err(
+        r#"`#\[conflict_hook\]` needs a grammar parameter to decide the conflicts"#,
+        r#"grammar; #[conflict_hook] Term = ();"#,
+        r#"           ~~~~~~~~~~~~~            "#);
+}
+
+#[test]
+fn conflict_hook_recursive_ascent() {
+    check_err(
+        r#"`#\[recursive_ascent\]` cannot be combined with `#\[conflict_hook\]`"#,
+        r#"#[recursive_ascent] grammar(h: &H); #[conflict_hook] Term = ();"#,
+        r#"  ~~~~~~~~~~~~~~~~                                             "#);
+}
+
+#[test]
+fn reduce_hooks_without_shared_reference() {
+    check_err(
+        r#"`#\[reduce_hooks\]` needs a grammar parameter that is a shared reference"#,
+        r#"#[reduce_hooks] grammar(h: &mut H); Term = ();"#,
+        r#"  ~~~~~~~~~~~~                                "#);
+}
+
+#[test]
+fn wildcard_recursive_ascent() {
+    check_err(
+        r#"the wildcard terminal `_` is not yet supported by recursive ascent parsers"#,
+        r#"#[recursive_ascent] grammar; Term = "a" _;"#,
+        r#"                                        ~ "#);
+}
+
+#[test]
+fn scannerless_with_tokens() {
+    check_err(
+        r#"`#\[scannerless\]` cannot be combined with an external tokenizer"#,
+        r#"#[scannerless] grammar; extern { enum Tok { } } Term = ();"#,
+        r#"  ~~~~~~~~~~~                                             "#);
+    check_err(
+        r#"a `#\[scannerless\]` grammar cannot have a `match` block"#,
+        r#"#[scannerless] grammar; match { "a" } Term = "a";"#,
+        r#"                        ~~~~~                    "#);
+}
+
+#[test]
+fn alternative_annotations() {
+    check_err(
+        r#"unrecognized annotation `inline`"#,
+        r#"grammar; Term = { #[inline] "a" };"#,
+        r#"                    ~~~~~~        "#);
+    check_err(
+        r#"`#\[cost\]` requires a number, like `#\[cost\(2\)\]`"#,
+        r#"grammar; Term = { #[cost] "a" };"#,
+        r#"                    ~~~~        "#);
+    check_err(
+        r#"duplicate annotation `cost`"#,
+        r#"grammar; Term = { #[cost(1)] #[cost(2)] "a" };"#,
+        r#"                               ~~~~           "#);
+    check_err(
+        r#"`99999999999` is too large a cost"#,
+        r#"grammar; Term = { #[cost(99999999999)] "a" };"#,
+        r#"                         ~~~~~~~~~~~          "#);
+    check_err(
+        r#"`#\[precedence\]` requires a number, like `#\[precedence\(2\)\]`"#,
+        r#"grammar; Term = { #[precedence(left)] "a" };"#,
+        r#"                    ~~~~~~~~~~              "#);
+    check_err(
+        r#"`#\[assoc\]` must be `left`, `right` or `none`"#,
+        r#"grammar; Term = { #[precedence(1)] #[assoc(up)] "a" };"#,
+        r#"                                           ~~         "#);
+    check_err(
+        r#"`#\[assoc\]` requires a `#\[precedence\]` on the same alternative"#,
+        r#"grammar; Term = { #[assoc(left)] "a" };"#,
+        r#"                    ~~~~~               "#);
+}
@@ -4,8 +4,10 @@
 use super::{NormResult, NormError};
 
 use grammar::parse_tree::*;
+use grammar::pattern::{Pattern, PatternKind};
 use intern::{InternedString};
 use collections::{map, Map};
+use util::Sep;
 
 #[cfg(test)]
 mod test;
@@ -30,7 +32,8 @@ fn resolve_in_place(grammar: &mut Grammar) -> NormResult<()> {
                    .flat_map(|extern_token| extern_token.enum_token.as_ref())
                    .flat_map(|enum_token| &enum_token.conversions)
                    .filter_map(|conversion| match conversion.from {
-                       TerminalString::Literal(..) | TerminalString::Error => None,
+                       TerminalString::Literal(..) | TerminalString::Error |
+                       TerminalString::Any => None,
                        TerminalString::Bare(id) => Some((conversion.span, id, Def::Terminal)),
                    });
 
@@ -53,8 +56,17 @@ fn resolve_in_place(grammar: &mut Grammar) -> NormResult<()> {
                        _ => None
                    });
 
+        // `const LBRACE = "{";` names a terminal literal
+        let const_identifiers =
+            grammar.items
+                   .iter()
+                   .filter_map(|item| item.as_terminal_const())
+                   .map(|c| (c.span, c.name, Def::Const(c.literal)));
+
         let all_identifiers =
-            nonterminal_identifiers.chain(terminal_identifiers).chain(match_identifiers);
+            nonterminal_identifiers.chain(terminal_identifiers)
+                                   .chain(match_identifiers)
+                                   .chain(const_identifiers);
 
         let mut identifiers = map();
         for (span, id, def) in all_identifiers {
@@ -78,8 +90,17 @@ fn resolve_in_place(grammar: &mut Grammar) -> NormResult<()> {
         ScopeChain { previous: None, identifiers: identifiers }
     };
 
+    let conversions =
+        grammar.items
+               .iter()
+               .filter_map(|item| item.as_extern_token())
+               .flat_map(|extern_token| extern_token.enum_token.as_ref())
+               .flat_map(|enum_token| enum_token.conversions.iter().cloned())
+               .collect();
+
     let validator = Validator {
         globals: globals,
+        conversions: conversions,
     };
 
     validator.validate(grammar)
@@ -87,6 +108,10 @@ fn resolve_in_place(grammar: &mut Grammar) -> NormResult<()> {
 
 struct Validator {
     globals: ScopeChain<'static>,
+
+    /// the conversions of the extern token, if any, which resolve
+    /// terminals written like `Tok::Num(n)`
+    conversions: Vec<Conversion>,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -94,6 +119,7 @@ enum Def {
     Terminal,
     Nonterminal(usize), // argument is the number of macro arguments
     MacroArg,
+    Const(TerminalLiteral), // argument is the literal that the constant names
 }
 
 #[derive(Debug)]
@@ -109,6 +135,7 @@ impl Def {
             Def::Nonterminal(0) => "nonterminal",
             Def::Nonterminal(_) => "macro",
             Def::MacroArg => "macro argument",
+            Def::Const(_) => "terminal constant",
         }
     }
 }
@@ -121,6 +148,13 @@ impl Validator {
                 GrammarItem::MatchToken(..) => {}
                 GrammarItem::InternToken(..) => {}
                 GrammarItem::ExternToken(..) => {}
+                GrammarItem::InlineTest(..) => {}
+                GrammarItem::Operators(..) => panic!("operator table not expanded"),
+                GrammarItem::Reserved(..) => {}
+                GrammarItem::TypeAlias(..) => {}
+                GrammarItem::TerminalConst(..) => {}
+                GrammarItem::Import(..) => {}
+                GrammarItem::RustItem(..) => {}
                 GrammarItem::Nonterminal(ref mut data) => {
                     let identifiers = try!(self.validate_macro_args(data.span, &data.args));
                     let locals = ScopeChain {
@@ -192,6 +226,9 @@ impl Validator {
             SymbolKind::AmbiguousId(name) => {
                 try!(self.rewrite_ambiguous_id(scope, name, symbol));
             }
+            SymbolKind::Variant(_) => {
+                try!(self.rewrite_variant(symbol));
+            }
             SymbolKind::Terminal(_) => {
                 /* see postvalidate! */
             }
@@ -205,21 +242,30 @@ impl Validator {
                         // OK
                     }
                     Def::Terminal |
+                    Def::Const(_) |
                     Def::Nonterminal(_) => {
                         return_err!(symbol.span, "`{}` is a {}, not a nonterminal",
-                                    def.description(), id);
+                                    id, def.description());
                     }
                 }
             }
             SymbolKind::Macro(ref mut msym) => {
                 debug_assert!(msym.args.len() > 0);
-                let def = try!(self.validate_id(scope, symbol.span, msym.name.0));
+                let def = match scope.def(msym.name.0) {
+                    Some(def) => def,
+                    None if msym.builtin_wrapper().is_some() => Def::Nonterminal(1),
+                    None => return_err!(symbol.span, "no definition found for `{}`", msym.name),
+                };
                 match def {
+                    Def::MacroArg => {
+                        // the argument must be a macro, which is checked
+                        // when the macro is expanded
+                    }
                     Def::Nonterminal(0) |
                     Def::Terminal |
-                    Def::MacroArg => {
+                    Def::Const(_) => {
                         return_err!(symbol.span, "`{}` is a {}, not a macro",
-                                    def.description(), msym.name)
+                                    msym.name, def.description())
                     }
                     Def::Nonterminal(arity) => {
                         if arity != msym.args.len() {
@@ -232,7 +278,7 @@ impl Validator {
                 }
 
                 for arg in &mut msym.args {
-                    try!(self.validate_symbol(scope, arg));
+                    try!(self.validate_macro_arg(scope, arg));
                 }
             }
             SymbolKind::Repeat(ref mut repeat) => {
@@ -241,13 +287,39 @@ impl Validator {
             SymbolKind::Choose(ref mut sym) | SymbolKind::Name(_, ref mut sym) => {
                 try!(self.validate_symbol(scope, sym));
             }
-            SymbolKind::Lookahead | SymbolKind::Lookbehind | SymbolKind::Error => {
+            SymbolKind::Lookahead | SymbolKind::Lookbehind | SymbolKind::LookaheadToken |
+            SymbolKind::Error => {
             }
         }
 
         Ok(())
     }
 
+    /// Unlike other symbols, an argument of a macro may name a macro,
+    /// to be passed to a parameter used as one, like `F` in
+    /// `Apply<F, E> = F<E>;`.
+    fn validate_macro_arg(&self,
+                          scope: &ScopeChain,
+                          arg: &mut Symbol)
+                          -> NormResult<()> {
+        let id = match arg.kind {
+            SymbolKind::AmbiguousId(id) => id,
+            SymbolKind::Nonterminal(id) => id.0,
+            _ => return self.validate_symbol(scope, arg),
+        };
+        let is_macro = match scope.def(id) {
+            Some(Def::Nonterminal(arity)) => arity > 0,
+            Some(_) => false,
+            None => Path::builtin_wrapper(NonterminalString(id)).is_some(),
+        };
+        if is_macro {
+            arg.kind = SymbolKind::Nonterminal(NonterminalString(id));
+            Ok(())
+        } else {
+            self.validate_symbol(scope, arg)
+        }
+    }
+
     fn rewrite_ambiguous_id(&self,
                             scope: &ScopeChain,
                             id: InternedString,
@@ -257,11 +329,67 @@ impl Validator {
             Def::MacroArg |
             Def::Nonterminal(0) => SymbolKind::Nonterminal(NonterminalString(id)),
             Def::Terminal => SymbolKind::Terminal(TerminalString::Bare(id)),
+            Def::Const(literal) => SymbolKind::Terminal(TerminalString::Literal(literal)),
             Def::Nonterminal(_) => return_err!(symbol.span, "`{}` is a macro", id),
         };
         Ok(())
     }
 
+    fn rewrite_variant(&self, symbol: &mut Symbol) -> NormResult<()> {
+        let terminal = match symbol.kind {
+            SymbolKind::Variant(ref variant) => try!(self.resolve_variant(symbol.span, variant)),
+            _ => unreachable!(),
+        };
+        symbol.kind = SymbolKind::Terminal(terminal);
+        Ok(())
+    }
+
+    /// Finds the terminal whose conversion is the pattern `variant`
+    /// abbreviates. The fields it names, if any, must be exactly those
+    /// that the conversion marks with `<T>`, since those make up the
+    /// value of the terminal.
+    fn resolve_variant(&self, span: Span, variant: &VariantSymbol) -> NormResult<TerminalString> {
+        let candidates: Vec<&Conversion> =
+            self.conversions
+                .iter()
+                .filter(|conversion| same_shape(variant, &conversion.to))
+                .collect();
+
+        let conversion = match candidates.len() {
+            0 => return_err!(span, "no terminal is declared with a pattern like `{}`", variant),
+            1 => candidates[0],
+            _ => return_err!(span,
+                             "`{}` could be any of the terminals {}",
+                             variant,
+                             Sep(", ", &candidates.iter()
+                                                  .map(|c| format!("`{}`", c.from))
+                                                  .collect::<Vec<_>>())),
+        };
+
+        if variant.binds_fields() {
+            let fields = match conversion.to.kind {
+                PatternKind::Enum(_, ref fields) => fields,
+                _ => unreachable!(),
+            };
+            let consistent = fields.iter().zip(&variant.fields).all(|(pattern, field)| {
+                match pattern.kind {
+                    PatternKind::Choose(_) => field.is_some(),
+                    _ => field.is_none(),
+                }
+            });
+            if !consistent {
+                return_err!(span,
+                            "`{}` must name exactly the fields that the pattern of `{}`, \
+                             `{}`, marks with `<T>`",
+                            variant,
+                            conversion.from,
+                            conversion.to);
+            }
+        }
+
+        Ok(conversion.from)
+    }
+
     fn validate_id(&self,
                    scope: &ScopeChain,
                    span: Span,
@@ -274,6 +402,15 @@ impl Validator {
     }
 }
 
+fn same_shape(variant: &VariantSymbol, pattern: &Pattern<TypeRef>) -> bool {
+    match pattern.kind {
+        PatternKind::Enum(ref path, ref fields) => {
+            *path == variant.path && fields.len() == variant.fields.len()
+        }
+        _ => false,
+    }
+}
+
 impl<'scope> ScopeChain<'scope> {
     fn def(&self, id: InternedString) -> Option<Def> {
         self.identifiers.get(&id)
@@ -75,3 +75,62 @@ fn overlapping_terminal_and_nonterminal() {
         "terminal and nonterminal both declared with the name `A`",
         r#"grammar; A = "Foo"; extern { enum Foo { >>>A => Foo::A(..) <<<} }"#);
 }
+
+#[test]
+fn builtin_wrapper_wrong_arguments() {
+    check_err(
+        "wrong number of arguments to `Box`: expected 1, found 2",
+        r#"grammar; X = >>>Box<"a", "b"><<<;"#);
+}
+
+#[test]
+fn unknown_variant() {
+    check_err(
+        r"no terminal is declared with a pattern like `Tok::Num\(n\)`",
+        r#"grammar; X = >>>Tok::Num(n)<<< => n; extern { enum Tok { "+" => Tok::Plus } }"#);
+}
+
+#[test]
+fn ambiguous_variant() {
+    check_err(
+        r#"`Tok::Op\(_\)` could be any of the terminals `"\+"`, `"-"`"#,
+        r#"grammar; X = >>>Tok::Op(_)<<<;
+           extern { enum Tok { "+" => Tok::Op('+'), "-" => Tok::Op('-') } }"#);
+}
+
+#[test]
+fn variant_names_wrong_fields() {
+    check_err(
+        r#"`Tok::Pair\(_, b\)` must name exactly the fields that the pattern of `Pair`"#,
+        r#"grammar; X = >>>Tok::Pair(_, b)<<< => b;
+           extern { enum Tok { Pair => Tok::Pair(<i32>, _) } }"#);
+}
+
+#[test]
+fn terminal_const() {
+    let grammar = parser::parse_grammar(r#"
+grammar;
+const LBRACE = "{";
+X = LBRACE Id<LBRACE> "}";
+Id<T> = T;
+"#).unwrap();
+    let grammar = super::resolve(grammar).unwrap();
+    let data = grammar.items.iter().filter_map(|item| item.as_nonterminal()).next().unwrap();
+    let symbols: Vec<String> =
+        data.alternatives[0].expr.symbols.iter().map(|s| s.to_string()).collect();
+    assert_eq!(symbols, vec![r#""{""#, r#"Id<"{">"#, r#""}""#]);
+}
+
+#[test]
+fn terminal_const_and_nonterminal() {
+    check_err(
+        "terminal constant and nonterminal both declared with the name `A`",
+        r#"grammar; A = "Foo"; const >>>A<<< = "a";"#);
+}
+
+#[test]
+fn terminal_const_as_macro() {
+    check_err(
+        "`A` is a terminal constant, not a macro",
+        r#"grammar; const A = "a"; X = >>>A<"b"><<<;"#);
+}
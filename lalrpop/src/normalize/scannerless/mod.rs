@@ -0,0 +1,374 @@
+//! In a `#[scannerless]` grammar, each character of the input is a
+//! token of its own. This pass rewrites the terminals of such a
+//! grammar so that each matches exactly one character, and no two
+//! match the same one, which leaves the lexer that `token_check`
+//! generates with nothing to decide:
+//!
+//! - the characters that the terminals can match are split into
+//!   disjoint sets, each of which becomes a terminal of its own;
+//! - a character class like `r"[a-z]"`, if it overlaps with another
+//!   terminal, becomes a nonterminal with one alternative for each of
+//!   the sets that it covers;
+//! - a quoted literal like `"if"` becomes a nonterminal that matches
+//!   its characters in sequence, and yields the literal itself.
+//!
+//! Which alternative wins is then decided by the parser rather than by
+//! the lexer; shift/reduce conflicts are resolved by shifting, so that
+//! a repetition matches as much of the input as it can.
+
+use super::{NormResult, NormError};
+
+use collections::{map, Map};
+use grammar::consts::{INPUT_LIFETIME, SCANNERLESS};
+use grammar::parse_tree::*;
+use intern::intern;
+use lexer::re;
+use regex_syntax::Expr;
+use std::char;
+
+#[cfg(test)]
+mod test;
+
+/// An inclusive range of code points.
+type Range = (u32, u32);
+
+pub fn split_terminals(mut grammar: Grammar) -> NormResult<Grammar> {
+    if !grammar.annotations.iter().any(|a| a.id == intern(SCANNERLESS)) {
+        return Ok(grammar);
+    }
+
+    let mut literals = vec![];
+    for data in grammar.items.iter().filter_map(|item| item.as_nonterminal()) {
+        for alternative in &data.alternatives {
+            for symbol in &alternative.expr.symbols {
+                collect_literals(symbol, &mut literals);
+            }
+        }
+    }
+
+    // the characters matched by each position of each literal: one for
+    // each character of a quoted literal, one for a regex
+    let mut positions: Vec<(TerminalLiteral, Span, Vec<Vec<Range>>)> = vec![];
+    for (literal, span) in literals {
+        match literal {
+            TerminalLiteral::Quoted(s) => {
+                let chars = s.to_string().chars().map(|c| vec![(c as u32, c as u32)]).collect();
+                positions.push((literal, span, chars));
+            }
+            TerminalLiteral::Regex(s) => {
+                // an invalid regex is reported by `token_check`
+                let regex = match re::parse_regex(&s.to_string()) {
+                    Ok(regex) => regex,
+                    Err(_) => continue,
+                };
+                match regex_chars(&regex) {
+                    Some(chars) => positions.push((literal, span, vec![normalize_ranges(chars)])),
+                    None => {
+                        return_err!(span,
+                                    "in a `#[{}]` grammar, a regular expression must match a \
+                                     single character",
+                                    SCANNERLESS);
+                    }
+                }
+            }
+        }
+    }
+
+    let sets: Vec<&Vec<Range>> = positions.iter().flat_map(|p| p.2.iter()).collect();
+    let atoms = atoms(&sets);
+
+    // name each atom after a literal that matches just its characters,
+    // if there is one, preferring quoted literals, or else after a
+    // regex of its own
+    let atom_terminals: Vec<TerminalLiteral> =
+        atoms.iter()
+             .map(|atom| {
+                 let exact: Vec<TerminalLiteral> =
+                     positions.iter()
+                              .filter(|&&(_, _, ref chars)| chars.len() == 1 && chars[0] == *atom)
+                              .map(|&(literal, _, _)| literal)
+                              .collect();
+                 exact.iter()
+                      .find(|literal| match **literal {
+                          TerminalLiteral::Quoted(_) => true,
+                          TerminalLiteral::Regex(_) => false,
+                      })
+                      .or(exact.first())
+                      .cloned()
+                      .unwrap_or_else(|| atom_literal(atom))
+             })
+             .collect();
+    let atoms_of = |chars: &Vec<Range>| -> Vec<TerminalLiteral> {
+        atoms.iter()
+             .zip(&atom_terminals)
+             .filter(|&(atom, _)| chars.iter().any(|&range| contains(range, atom[0].0)))
+             .map(|(_, &terminal)| terminal)
+             .collect()
+    };
+
+    let mut replacements: Map<TerminalLiteral, SymbolKind> = map();
+    let mut new_nonterminals = vec![];
+    for &(literal, span, ref chars) in &positions {
+        let name = NonterminalString(intern(&literal.to_string()));
+        let terminal_symbol = |terminal| {
+            Symbol::new(span, SymbolKind::Terminal(TerminalString::Literal(terminal)))
+        };
+        match literal {
+            TerminalLiteral::Regex(_) => {
+                let terminals = atoms_of(&chars[0]);
+                if terminals.len() == 1 {
+                    let kind = SymbolKind::Terminal(TerminalString::Literal(terminals[0]));
+                    replacements.insert(literal, kind);
+                    continue;
+                }
+                new_nonterminals.push(NonterminalData {
+                    public: false,
+                    span: span,
+                    name: name,
+                    annotations: vec![],
+                    args: vec![],
+                    type_decl: None,
+                    alternatives: terminals.into_iter()
+                                           .map(|terminal| {
+                                               Alternative {
+                                                   span: span,
+                                                   annotations: vec![],
+                                                   expr: ExprSymbol {
+                                                       symbols: vec![terminal_symbol(terminal)],
+                                                   },
+                                                   condition: None,
+                                                   action: None,
+                                               }
+                                           })
+                                           .collect(),
+                });
+            }
+            TerminalLiteral::Quoted(s) => {
+                if chars.len() == 1 {
+                    let terminal = atoms_of(&chars[0])[0];
+                    replacements.insert(literal,
+                                        SymbolKind::Terminal(TerminalString::Literal(terminal)));
+                    continue;
+                }
+                let symbols = chars.iter().map(|c| terminal_symbol(atoms_of(c)[0])).collect();
+                new_nonterminals.push(NonterminalData {
+                    public: false,
+                    span: span,
+                    name: name,
+                    annotations: vec![],
+                    args: vec![],
+                    type_decl: Some(TypeRef::Ref {
+                        lifetime: Some(intern(INPUT_LIFETIME)),
+                        mutable: false,
+                        referent: Box::new(TypeRef::Id(intern("str"))),
+                    }),
+                    alternatives: vec![Alternative {
+                                           span: span,
+                                           annotations: vec![],
+                                           expr: ExprSymbol { symbols: symbols },
+                                           condition: None,
+                                           action: Some(ActionKind::User(format!("{:?}",
+                                                                                 s.to_string()))),
+                                       }],
+                });
+            }
+        }
+        replacements.insert(literal, SymbolKind::Nonterminal(name));
+    }
+
+    for item in &mut grammar.items {
+        if let GrammarItem::Nonterminal(ref mut data) = *item {
+            // macro expansion gave `Foo+` and the like the type of
+            // their symbols, which may be among those replaced
+            if let Some(ref mut type_decl) = data.type_decl {
+                replace_literals_in_type(type_decl, &replacements);
+            }
+            for alternative in &mut data.alternatives {
+                for symbol in &mut alternative.expr.symbols {
+                    replace_literals(&mut symbol.kind, &replacements);
+                }
+            }
+        }
+    }
+    grammar.items.extend(new_nonterminals.into_iter().map(GrammarItem::Nonterminal));
+
+    Ok(grammar)
+}
+
+/// Adds the terminal literals in `symbol` to `literals`, unless they
+/// are already there, along with where they first appear.
+fn collect_literals(symbol: &Symbol, literals: &mut Vec<(TerminalLiteral, Span)>) {
+    match symbol.kind {
+        SymbolKind::Terminal(TerminalString::Literal(literal)) => {
+            if !literals.iter().any(|&(l, _)| l == literal) {
+                literals.push((literal, symbol.span));
+            }
+        }
+        SymbolKind::Expr(ref expr) => {
+            for symbol in &expr.symbols {
+                collect_literals(symbol, literals);
+            }
+        }
+        SymbolKind::Repeat(ref repeat) => collect_literals(&repeat.symbol, literals),
+        SymbolKind::Choose(ref symbol) |
+        SymbolKind::Name(_, ref symbol) => collect_literals(symbol, literals),
+        _ => {}
+    }
+}
+
+fn replace_literals(kind: &mut SymbolKind, replacements: &Map<TerminalLiteral, SymbolKind>) {
+    let replacement = match *kind {
+        SymbolKind::Terminal(TerminalString::Literal(literal)) => {
+            replacements.get(&literal).cloned()
+        }
+        SymbolKind::Expr(ref mut expr) => {
+            for symbol in &mut expr.symbols {
+                replace_literals(&mut symbol.kind, replacements);
+            }
+            None
+        }
+        SymbolKind::Repeat(ref mut repeat) => {
+            replace_literals(&mut repeat.symbol.kind, replacements);
+            None
+        }
+        SymbolKind::Choose(ref mut symbol) |
+        SymbolKind::Name(_, ref mut symbol) => {
+            replace_literals(&mut symbol.kind, replacements);
+            None
+        }
+        _ => None,
+    };
+    if let Some(replacement) = replacement {
+        *kind = replacement;
+    }
+}
+
+fn replace_literals_in_type(type_ref: &mut TypeRef,
+                            replacements: &Map<TerminalLiteral, SymbolKind>) {
+    match *type_ref {
+        TypeRef::Tuple(ref mut types) |
+        TypeRef::Nominal { ref mut types, .. } => {
+            for type_ref in types {
+                replace_literals_in_type(type_ref, replacements);
+            }
+        }
+        TypeRef::Ref { ref mut referent, .. } => replace_literals_in_type(referent, replacements),
+        TypeRef::OfSymbol(ref mut kind) => replace_literals(kind, replacements),
+        TypeRef::Lifetime(_) |
+        TypeRef::Id(_) => {}
+    }
+}
+
+/// The characters matched by `regex`, or `None` if it does not always
+/// match exactly one character.
+fn regex_chars(regex: &Expr) -> Option<Vec<Range>> {
+    match *regex {
+        Expr::Literal { ref chars, casei } if chars.len() == 1 => {
+            let c = chars[0];
+            if casei {
+                Some(c.to_lowercase()
+                      .chain(c.to_uppercase())
+                      .map(|c| (c as u32, c as u32))
+                      .collect())
+            } else {
+                Some(vec![(c as u32, c as u32)])
+            }
+        }
+        Expr::Class(ref class) => {
+            let mut ranges = vec![];
+            for &range in class {
+                ranges.push((range.start as u32, range.end as u32));
+            }
+            Some(ranges)
+        }
+        // leaving out the surrogates, which are not characters
+        Expr::AnyChar => Some(vec![(0, 0xD7FF), (0xE000, 0x10FFFF)]),
+        Expr::AnyCharNoNL => Some(vec![(0, 0x9), (0xB, 0xC), (0xE, 0xD7FF), (0xE000, 0x10FFFF)]),
+        Expr::Group { ref e, .. } => regex_chars(e),
+        Expr::Alternate(ref regexes) => {
+            let mut ranges = vec![];
+            for regex in regexes {
+                match regex_chars(regex) {
+                    Some(r) => ranges.extend(r),
+                    None => return None,
+                }
+            }
+            Some(ranges)
+        }
+        _ => None,
+    }
+}
+
+/// Sorts `ranges` and merges those that overlap or are adjacent.
+fn normalize_ranges(mut ranges: Vec<Range>) -> Vec<Range> {
+    ranges.sort();
+    let mut result: Vec<Range> = vec![];
+    for (start, end) in ranges {
+        match result.last_mut() {
+            Some(last) if start <= last.1 + 1 => {
+                if end > last.1 {
+                    last.1 = end;
+                }
+                continue;
+            }
+            _ => {}
+        }
+        result.push((start, end));
+    }
+    result
+}
+
+fn contains(range: Range, c: u32) -> bool {
+    range.0 <= c && c <= range.1
+}
+
+/// Splits the characters in `sets` into the coarsest partition such
+/// that each set is a union of its parts, which we call atoms.
+fn atoms(sets: &[&Vec<Range>]) -> Vec<Vec<Range>> {
+    let mut bounds: Vec<u32> = sets.iter()
+                                   .flat_map(|set| set.iter())
+                                   .flat_map(|&(start, end)| vec![start, end + 1])
+                                   .collect();
+    bounds.sort();
+    bounds.dedup();
+
+    // group the intervals between consecutive bounds by the sets that
+    // contain them
+    let mut atoms: Map<Vec<usize>, Vec<Range>> = map();
+    for window in bounds.windows(2) {
+        let (start, end) = (window[0], window[1] - 1);
+        let members: Vec<usize> =
+            (0..sets.len()).filter(|&i| sets[i].iter().any(|&r| contains(r, start))).collect();
+        if !members.is_empty() {
+            atoms.entry(members).or_insert_with(Vec::new).push((start, end));
+        }
+    }
+    atoms.into_iter().map(|(_, ranges)| normalize_ranges(ranges)).collect()
+}
+
+/// A terminal for the characters in `atom`, which no literal in the
+/// grammar matches exactly.
+fn atom_literal(atom: &[Range]) -> TerminalLiteral {
+    if atom.len() == 1 && atom[0].0 == atom[0].1 {
+        if let Some(c) = char::from_u32(atom[0].0) {
+            return TerminalLiteral::Quoted(intern(&c.to_string()));
+        }
+    }
+    let mut class = String::from("[");
+    for &(start, end) in atom {
+        class.push_str(&class_char(start));
+        if end > start {
+            class.push('-');
+            class.push_str(&class_char(end));
+        }
+    }
+    class.push(']');
+    TerminalLiteral::Regex(intern(&class))
+}
+
+fn class_char(c: u32) -> String {
+    match char::from_u32(c) {
+        Some(c @ 'a'...'z') | Some(c @ 'A'...'Z') | Some(c @ '0'...'9') => c.to_string(),
+        _ => format!("\\x{{{:X}}}", c),
+    }
+}
@@ -0,0 +1,56 @@
+use parser;
+use normalize::macro_expand::expand_macros;
+use normalize::resolve::resolve;
+use test_util::{check_norm_err, compare};
+
+use super::split_terminals;
+
+#[test]
+fn split_overlapping_terminals() {
+    let grammar = parser::parse_grammar(r#"
+#[scannerless]
+grammar;
+    pub Item = { "if" Space Letter, Letter, Digit };
+    Letter = r"[a-z]";
+    Digit = r"[0-9]";
+    Space = " ";
+"#).unwrap();
+
+    let actual = split_terminals(expand_macros(grammar).unwrap()).unwrap();
+
+    let expected = parser::parse_grammar(r##"
+#[scannerless]
+grammar;
+    pub Item = { `"if"` Space Letter, Letter, Digit };
+    Letter = `r#"[a-z]"#`;
+    Digit = r"[0-9]";
+    Space = " ";
+
+    `"if"`: &'input str = "i" "f" => "if";
+
+    `r#"[a-z]"#` = { "i", "f", r"[a-eg-hj-z]" };
+"##).unwrap();
+
+    compare(actual, resolve(expected).unwrap());
+}
+
+#[test]
+fn not_scannerless() {
+    let grammar = r#"
+grammar;
+    pub Item = { "if" Letter, Letter };
+    Letter = r"[a-z]+";
+"#;
+    let actual = split_terminals(expand_macros(parser::parse_grammar(grammar).unwrap()).unwrap());
+    let expected = expand_macros(parser::parse_grammar(grammar).unwrap()).unwrap();
+    compare(actual.unwrap(), expected);
+}
+
+#[test]
+fn regex_of_several_characters() {
+    let grammar = r#"#[scannerless] grammar; Term = r"[a-z]+";"#;
+    let span = r#"                               ~~~~~~~~~  "#;
+    let err = split_terminals(expand_macros(parser::parse_grammar(grammar).unwrap()).unwrap())
+        .unwrap_err();
+    check_norm_err(r#"a regular expression must match a single character"#, span, err);
+}
@@ -13,6 +13,7 @@ use lexer::dfa::{self, DFAConstructionError, Precedence};
 use lexer::nfa::NFAConstructionError::*;
 use grammar::consts::*;
 use grammar::parse_tree::*;
+use grammar::pattern::PatternKind;
 use collections::{Map, Set};
 
 #[cfg(test)]
@@ -24,6 +25,8 @@ pub fn validate(mut grammar: Grammar) -> NormResult<Grammar> {
             assert!(grammar.match_token().is_none(),
                     "validator permitted both an extern/match section");
 
+            try!(check_conversions(enum_token));
+
             TokenMode::Extern {
                 conversions: enum_token.conversions
                                        .iter()
@@ -50,15 +53,53 @@ pub fn validate(mut grammar: Grammar) -> NormResult<Grammar> {
         TokenMode::Extern { .. } => {
             // If using an external tokenizer, we're all done at this point.
         }
-        TokenMode::Internal { match_block } => {
-            // Otherwise, construct the `InternToken` item.
-            construct(&mut grammar, match_block)?;
+        TokenMode::Internal { mut match_block } => {
+            // Otherwise, construct the `InternToken` item. Reserved
+            // words must win over everything else, even the regular
+            // expressions in the highest-priority match group.
+            let reserved_words: Vec<ReservedWord> =
+                grammar.items
+                       .iter()
+                       .filter_map(|item| item.as_reserved())
+                       .flat_map(|reserved| reserved.words.iter().cloned())
+                       .collect();
+            for reserved_word in &reserved_words {
+                match_block.reserve(reserved_word);
+            }
+
+            construct(&mut grammar, match_block, reserved_words)?;
         }
     }
 
     Ok(grammar)
 }
 
+/// Checks that no two terminals are converted to the same pattern.
+/// The parser maps each token to the first terminal whose pattern it
+/// matches, so the second such terminal could never be produced.
+/// Terminals whose pattern is just `..` or `_` are left alone: that is
+/// how grammars that are analyzed, but never compiled, say that the
+/// pattern does not matter.
+fn check_conversions(enum_token: &EnumToken) -> NormResult<()> {
+    let mut patterns: Map<String, TerminalString> = Map::new();
+    for conversion in &enum_token.conversions {
+        match conversion.to.kind {
+            PatternKind::DotDot | PatternKind::Underscore => continue,
+            _ => { }
+        }
+        let pattern = format!("{}", conversion.to);
+        if let Some(&previous) = patterns.get(&pattern) {
+            return_err!(conversion.span,
+                        "terminals `{}` and `{}` both have the pattern `{}`",
+                        previous,
+                        conversion.from,
+                        pattern);
+        }
+        patterns.insert(pattern, conversion.from);
+    }
+    Ok(())
+}
+
 ///////////////////////////////////////////////////////////////////////////
 // Validation phase -- this phase walks the grammar and visits all
 // terminals. If using an external set of tokens, it checks that all
@@ -108,6 +149,10 @@ struct MatchBlock {
 
     /// True if we should permit unrecognized literals to be used.
     catch_all: bool,
+
+    /// Precedence given to reserved words; this is higher than that
+    /// of any entry in the `match` block.
+    reserved_precedence: usize,
 }
 
 impl MatchBlock {
@@ -139,6 +184,8 @@ impl MatchBlock {
             // no match block is equivalent to `match { _ }`
             match_block.catch_all = true;
         }
+        let num_groups = opt_match_token.map(|m| m.contents.len()).unwrap_or(0);
+        match_block.reserved_precedence = (num_groups + 1) * 2 + 1;
         Ok(match_block)
     }
 
@@ -191,6 +238,34 @@ impl MatchBlock {
 
         Ok(())
     }
+
+    /// Ensures that `reserved_word` is always lexed as a keyword. If
+    /// the word is already a terminal (because it appears in the
+    /// grammar or the `match` block), we just raise its precedence;
+    /// otherwise, we add an entry that the parser will never accept.
+    fn reserve(&mut self, reserved_word: &ReservedWord) {
+        let sym = TerminalLiteral::Quoted(reserved_word.word);
+        let precedence = self.reserved_precedence;
+
+        let mut found = false;
+        for match_entry in &mut self.match_entries {
+            if match_entry.match_literal == sym {
+                match_entry.precedence = precedence;
+                found = true;
+            }
+        }
+
+        if !found {
+            self.match_user_names.insert(TerminalString::Literal(sym));
+            self.match_entries
+                .push(MatchEntry {
+                          precedence: precedence,
+                          match_literal: sym,
+                          user_name: TerminalString::Literal(sym),
+                      });
+            self.spans.insert(sym, reserved_word.span);
+        }
+    }
 }
 
 impl<'grammar> Validator<'grammar> {
@@ -201,6 +276,13 @@ impl<'grammar> Validator<'grammar> {
                 GrammarItem::MatchToken(..) => {}
                 GrammarItem::ExternToken(_) => {}
                 GrammarItem::InternToken(_) => {}
+                GrammarItem::InlineTest(_) => {}
+                GrammarItem::Operators(..) => panic!("operator table not expanded"),
+                GrammarItem::Reserved(_) => {}
+                GrammarItem::TypeAlias(_) => {}
+                GrammarItem::TerminalConst(_) => {}
+                GrammarItem::Import(_) => {}
+                GrammarItem::RustItem(_) => {}
                 GrammarItem::Nonterminal(ref data) => {
                     for alternative in &data.alternatives {
                         try!(self.validate_alternative(alternative));
@@ -240,10 +322,14 @@ impl<'grammar> Validator<'grammar> {
             SymbolKind::Name(_, ref sym) => {
                 try!(self.validate_symbol(sym));
             }
-            SymbolKind::Lookahead | SymbolKind::Lookbehind | SymbolKind::Error => {}
+            SymbolKind::Lookahead | SymbolKind::Lookbehind | SymbolKind::LookaheadToken |
+            SymbolKind::Error => {}
             SymbolKind::AmbiguousId(id) => {
                 panic!("ambiguous id `{}` encountered after name resolution", id)
             }
+            SymbolKind::Variant(ref v) => {
+                panic!("variant `{}` encountered after name resolution", v)
+            }
             SymbolKind::Macro(..) => {
                 panic!("macro not removed: {:?}", symbol);
             }
@@ -257,7 +343,7 @@ impl<'grammar> Validator<'grammar> {
             // If there is an extern token definition, validate that
             // this terminal has a defined conversion.
             TokenMode::Extern { ref conversions } => {
-                if !conversions.contains(&term) {
+                if term != TerminalString::Any && !conversions.contains(&term) {
                     return_err!(span,
                                 "terminal `{}` does not have a pattern defined for it",
                                 term);
@@ -280,6 +366,9 @@ impl<'grammar> Validator<'grammar> {
 
                     // Error is a builtin terminal that always exists
                     TerminalString::Error => (),
+
+                    // and `_` matches whatever tokens the others do not
+                    TerminalString::Any => (),
                 }
             }
         }
@@ -292,7 +381,10 @@ impl<'grammar> Validator<'grammar> {
 // Construction phase -- if we are constructing a tokenizer, this
 // phase builds up an internal token DFA.
 
-fn construct(grammar: &mut Grammar, match_block: MatchBlock) -> NormResult<()> {
+fn construct(grammar: &mut Grammar,
+             match_block: MatchBlock,
+             reserved_words: Vec<ReservedWord>)
+             -> NormResult<()> {
     let MatchBlock {
         mut match_entries,
         spans,
@@ -349,11 +441,22 @@ fn construct(grammar: &mut Grammar, match_block: MatchBlock) -> NormResult<()> {
         Err(DFAConstructionError::Ambiguity { match0, match1 }) => {
             let literal0 = match_entries[match0.index()].match_literal;
             let literal1 = match_entries[match1.index()].match_literal;
+
+            // Overlaps are only permitted if the user declared which
+            // terminal wins, so point them at the way to do that.
+            let hint = if grammar.match_token().is_some() {
+                "list them in different sections of the `match` block \
+                 (separated by `else`) to declare which one takes precedence"
+            } else {
+                "add a `match` block to declare which one takes precedence"
+            };
+
             // FIXME(#88) -- it'd be nice to give an example here
             return_err!(spans[&literal0],
-                        "ambiguity detected between the terminal `{}` and the terminal `{}`",
+                        "ambiguity detected between the terminal `{}` and the terminal `{}`; {}",
                         literal0,
-                        literal1)
+                        literal1,
+                        hint)
         }
     };
 
@@ -362,6 +465,7 @@ fn construct(grammar: &mut Grammar, match_block: MatchBlock) -> NormResult<()> {
         .push(GrammarItem::InternToken(InternToken {
                                            match_entries: match_entries,
                                            dfa: dfa,
+                                           reserved_words: reserved_words,
                                        }));
 
     // we need to inject a `'input` lifetime and `input: &'input str` parameter as well:
@@ -49,6 +49,20 @@ fn unknown_terminal() {
         r#"                                        ~~~ "#);
 }
 
+#[test]
+fn duplicate_pattern() {
+    check_err(
+        r#"terminals `"\+"` and `"plus"` both have the pattern `Tok::Plus`"#,
+        r#"grammar; extern { enum Tok { "+" => Tok::Plus, "plus" => Tok::Plus } } X = "+";"#,
+        r#"                                               ~~~~~~~~~~~~~~~~~~~~            "#);
+}
+
+#[test]
+fn duplicate_dot_dot_pattern() {
+    assert!(validate_grammar(r#"grammar; extern { enum Tok { "+" => .., "-" => .. } } X = "+";"#)
+        .is_ok());
+}
+
 #[test]
 fn unknown_id_terminal() {
     check_err(
@@ -105,6 +119,26 @@ fn regex_literals() {
                 ]);
 }
 
+#[test]
+fn char_range_literals() {
+    check_intern_token(
+        r#"grammar; X = X ['a'-'z' '_'] ['0'-'9'];"#,
+        vec![
+            ("a", r##"Some((r#"[a-z_]"#, "a"))"##),
+            ("_", r##"Some((r#"[a-z_]"#, "_"))"##),
+            ("1", r##"Some((r#"[0-9]"#, "1"))"##),
+            ("A", r#"None"#),
+                ]);
+}
+
+#[test]
+fn invalid_char_range() {
+    check_err(
+        r#"invalid regular expression.*"#,
+        r#"grammar; X = X ['z'-'a'];"#,
+        r#"               ~~~~~~~~~ "#);
+}
+
 /// Basic test for match mappings.
 #[test]
 fn match_mappings() {
@@ -130,6 +164,21 @@ fn match_precedence() {
                 ]);
 }
 
+/// Reserved words take precedence even over the regular expressions
+/// in the highest-priority match group, and are lexed as keywords
+/// even when the grammar does not use them.
+#[test]
+fn reserved_words() {
+    check_intern_token(
+        r#"grammar; match { r"[a-z]+" => ID } else { _ } reserved { "if", "async" } X = "if" ID;"#,
+        vec![
+            ("if", r#"Some(("if", "if"))"#),
+            ("async", r#"Some(("async", "async"))"#),
+            ("asyncs", r#"Some((ID, "asyncs"))"#),
+            ("abc", r#"Some((ID, "abc"))"#),
+                ]);
+}
+
 /// Test that, without a `catch-all`, using unrecognized literals is an error.
 #[test]
 fn invalid_match_literal() {
@@ -176,11 +225,21 @@ fn complex_match() {
 #[test]
 fn ambiguity_within_match() {
     check_err(
-        r##"ambiguity detected between the terminal `r#"b"#` and the terminal `r#"\(\?i\)b"#`"##,
+        r##"ambiguity detected between the terminal `r#"b"#` and the terminal `r#"\(\?i\)b"#`; list them in different sections"##,
         r#"grammar; match { r"(?i)b" => "B", r"b" => "b" }"#,
         r#"                                  ~~~~~~~~~~~~ "#);
 }
 
+/// Test that overlapping regular expressions outside of any match
+/// declaration point the user at the `match` block.
+#[test]
+fn ambiguity_without_match() {
+    check_err(
+        r##"ambiguity detected between .*; add a `match` block"##,
+        r#"grammar; X = X r"(?i)b" r"b";"#,
+        r#"                        ~~~~ "#);
+}
+
 /// Test that using the **exact same regular expression** twice is
 /// forbidden, even across multiple levels of the match expression.
 /// No good reason to do that.
@@ -9,9 +9,10 @@ use grammar::parse_tree::{ActionKind, Alternative,
                           Path,
                           Span,
                           SymbolKind,
+                          TypeParameter,
                           TypeRef};
 use grammar::repr::{NominalTypeRepr, Types, TypeRepr};
-use intern::intern;
+use intern::{intern, InternedString};
 
 #[cfg(test)]
 mod test;
@@ -25,6 +26,10 @@ struct TypeInferencer<'grammar> {
     stack: Vec<NonterminalString>,
     nonterminals: HashMap<NonterminalString, NT<'grammar>>,
     types: Types,
+
+    // the `'input` lifetime, if the grammar has one, which references
+    // in the declared types borrow from unless they say otherwise
+    input_lifetime: Option<InternedString>,
 }
 
 #[derive(Copy, Clone)]
@@ -48,9 +53,18 @@ impl<'grammar> TypeInferencer<'grammar> {
                    })
                    .collect();
 
+        let input_lifetime = intern(INPUT_LIFETIME);
+        let input_lifetime =
+            if grammar.type_parameters.contains(&TypeParameter::Lifetime(input_lifetime)) {
+                Some(input_lifetime)
+            } else {
+                None
+            };
+
         Ok(TypeInferencer { stack: vec![],
                             nonterminals: nonterminals,
-                            types: types })
+                            types: types,
+                            input_lifetime: input_lifetime })
     }
 
     fn make_types(grammar: &Grammar) -> Types {
@@ -64,10 +78,15 @@ impl<'grammar> TypeInferencer<'grammar> {
             });
 
         // Determine location type and enum type. If using an internal
-        // token, that's specified by us, not user.
+        // token, that's specified by us, not user, except that the user
+        // may pick another type of location than the byte offset.
         if let Some(intern_token) = grammar.intern_token() {
-            let loc_type = // usize
-                TypeRepr::usize();
+            let loc_type = // usize, unless `type Location = ..` says otherwise
+                opt_extern_token.and_then(|extern_token| {
+                    extern_token.associated_type(intern(LOCATION))
+                                .map(|tr| tr.type_ref.type_repr())
+                })
+                .unwrap_or_else(TypeRepr::usize);
             let input_str = // &'input str
                 TypeRepr::Ref {
                     lifetime: Some(intern(INPUT_LIFETIME)),
@@ -174,6 +193,17 @@ impl<'grammar> TypeInferencer<'grammar> {
                 }
             }
 
+            // A purely structural nonterminal -- one without a type
+            // annotation or any action code, like `Sep = { ",", ";" Newline }` --
+            // whose alternatives disagree about their value is not
+            // meant to produce a value at all, so default its type
+            // to `()`; lowering will then generate actions that
+            // discard the matched values.
+            let structural = nt.alternatives.iter().all(|alt| alt.action.is_none());
+            if structural && alternative_types.iter().any(|ty| ty != &alternative_types[0]) {
+                return Ok(TypeRepr::Tuple(vec![]));
+            }
+
             // otherwise, check that all the cases where we had success agree
             for ((ty, alt), i) in
                 alternative_types[1..].iter().zip(&nt.alternatives[1..]).zip(1..)
@@ -226,7 +256,10 @@ impl<'grammar> TypeInferencer<'grammar> {
                                                        types: vec![] }))
             }
             TypeRef::Ref { lifetime, mutable, ref referent } => {
-                Ok(TypeRepr::Ref { lifetime: lifetime,
+                // a reference like `&str` in the type of a nonterminal
+                // can only borrow from the input, so spare the user
+                // from writing `&'input str` everywhere
+                Ok(TypeRepr::Ref { lifetime: lifetime.or(self.input_lifetime),
                                    mutable: mutable,
                                    referent: Box::new(try!(self.type_ref(referent))) })
             }
@@ -248,6 +281,10 @@ impl<'grammar> TypeInferencer<'grammar> {
                 Ok(self.types.opt_terminal_loc_type().unwrap().clone())
             }
 
+            AlternativeAction::User(&ActionKind::LookaheadToken) => {
+                Ok(self.types.opt_triple_type())
+            }
+
             AlternativeAction::Default(Symbols::Named(ref syms)) => {
                 return_err!(alt.span,
                             "cannot infer types in the presence of named symbols like `{}:{}`",
@@ -274,7 +311,8 @@ impl<'grammar> TypeInferencer<'grammar> {
             SymbolKind::Error => Ok(self.types.parse_error_type().clone()),
 
             SymbolKind::Repeat(..) | SymbolKind::Expr(..) | SymbolKind::Macro(..) |
-            SymbolKind::AmbiguousId(..) | SymbolKind::Lookahead | SymbolKind::Lookbehind => {
+            SymbolKind::AmbiguousId(..) | SymbolKind::Variant(..) | SymbolKind::Lookahead |
+            SymbolKind::Lookbehind | SymbolKind::LookaheadToken => {
                 unreachable!("symbol `{:?}` should have been expanded away", symbol)
             }
         }
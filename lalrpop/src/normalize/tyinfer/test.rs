@@ -42,6 +42,19 @@ grammar;
         ])
 }
 
+#[test]
+fn test_unit_default_for_structural_nonterminal() {
+    compare(r#"
+grammar;
+    extern { enum Tok { "Hi" => Hi(..), "Ho" => Ho(..) } }
+    X = { "Hi", Y };
+    Y = "Hi" "Ho";
+"#, vec![
+    ("X", "()"),
+    ("Y", "(Tok, Tok)"),
+        ])
+}
+
 #[test]
 fn test_cycle_direct() {
     let grammar = parser::parse_grammar(r#"
@@ -140,6 +153,17 @@ grammar;
         ])
 }
 
+#[test]
+fn test_lookahead_token() {
+    compare(r#"
+grammar;
+    extern { type Location = usize; enum Tok { } }
+    A = @T;
+"#, vec![
+    ("A", "::std::option::Option<(usize, Tok, usize)>"),
+        ])
+}
+
 #[test]
 fn test_spanned_macro() {
     compare(r#"
@@ -223,3 +247,30 @@ grammar;
     ("Z", "__lalrpop_util::ParseError<usize, (usize, &'input str), ()>")
         ])
 }
+
+#[test]
+fn input_lifetime() {
+    compare(r#"
+grammar;
+    Names: Vec<&str> = Name*;
+    Pair = Name Name;
+    Name: &str = r"[a-z]+";
+    Keyword: &'static str = "if" => "if";
+"#, vec![
+    ("Names", "Vec<&'input str>"),
+    ("Pair", "(&'input str, &'input str)"),
+    ("Name", "&'input str"),
+    ("Keyword", "&'static str"),
+        ])
+}
+
+#[test]
+fn input_lifetime_custom_token() {
+    compare(r#"
+grammar<'input>;
+    extern { enum Tok<'input> { N => N(<&'input str>) } }
+    A: &str = N;
+"#, vec![
+    ("A", "&'input str")
+        ])
+}
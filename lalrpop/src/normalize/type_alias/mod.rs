@@ -0,0 +1,158 @@
+//! Expands the `type Foo<T> = ...;` aliases declared in the grammar
+//! within the type annotations of nonterminals. Aliases are local to
+//! the grammar file and do not appear in the generated code.
+
+use super::{NormResult, NormError};
+
+use grammar::parse_tree::*;
+use intern::InternedString;
+use collections::{map, Map};
+
+#[cfg(test)]
+mod test;
+
+pub fn expand_type_aliases(mut grammar: Grammar) -> NormResult<Grammar> {
+    let mut aliases: Map<InternedString, TypeAlias> = map();
+    for alias in grammar.items.iter().filter_map(|item| item.as_type_alias()) {
+        if aliases.insert(alias.name, alias.clone()).is_some() {
+            return_err!(alias.span, "multiple definitions of type alias `{}`", alias.name);
+        }
+    }
+
+    if aliases.is_empty() {
+        return Ok(grammar);
+    }
+
+    let expander = Expander { aliases: aliases };
+    for item in &mut grammar.items {
+        if let GrammarItem::Nonterminal(ref mut data) = *item {
+            // the arguments of a macro shadow any alias of the same name
+            let shadowed: Vec<InternedString> = data.args.iter().map(|a| a.0).collect();
+            if let Some(ref mut type_decl) = data.type_decl {
+                *type_decl = try!(expander.expand(data.span, &shadowed, type_decl, &mut vec![]));
+            }
+        }
+    }
+
+    Ok(grammar)
+}
+
+struct Expander {
+    aliases: Map<InternedString, TypeAlias>,
+}
+
+impl Expander {
+    fn alias(&self, shadowed: &[InternedString], id: InternedString) -> Option<&TypeAlias> {
+        if shadowed.contains(&id) {
+            None
+        } else {
+            self.aliases.get(&id)
+        }
+    }
+
+    fn expand_all(&self,
+                  span: Span,
+                  shadowed: &[InternedString],
+                  type_refs: &[TypeRef],
+                  stack: &mut Vec<InternedString>)
+                  -> NormResult<Vec<TypeRef>>
+    {
+        type_refs.iter().map(|tr| self.expand(span, shadowed, tr, stack)).collect()
+    }
+
+    /// Expands the aliases appearing in `type_ref`. `span` is used
+    /// for reporting errors; `stack` holds the aliases currently
+    /// being expanded, so that we can detect recursive definitions.
+    fn expand(&self,
+              span: Span,
+              shadowed: &[InternedString],
+              type_ref: &TypeRef,
+              stack: &mut Vec<InternedString>)
+              -> NormResult<TypeRef>
+    {
+        Ok(match *type_ref {
+            TypeRef::Tuple(ref trs) =>
+                TypeRef::Tuple(try!(self.expand_all(span, shadowed, trs, stack))),
+            TypeRef::Nominal { ref path, ref types } => {
+                let types = try!(self.expand_all(span, shadowed, types, stack));
+                match path.as_id().and_then(|id| self.alias(shadowed, id)) {
+                    Some(alias) => try!(self.expand_alias(span, alias, types, stack)),
+                    None => TypeRef::Nominal { path: path.clone(), types: types },
+                }
+            }
+            TypeRef::Ref { lifetime, mutable, ref referent } =>
+                TypeRef::Ref { lifetime: lifetime,
+                               mutable: mutable,
+                               referent: Box::new(try!(self.expand(span, shadowed, referent, stack))) },
+            TypeRef::Lifetime(id) =>
+                TypeRef::Lifetime(id),
+            TypeRef::OfSymbol(ref sym) =>
+                TypeRef::OfSymbol(sym.clone()),
+            TypeRef::Id(id) => {
+                match self.alias(shadowed, id) {
+                    Some(alias) => try!(self.expand_alias(span, alias, vec![], stack)),
+                    None => TypeRef::Id(id),
+                }
+            }
+        })
+    }
+
+    fn expand_alias(&self,
+                    span: Span,
+                    alias: &TypeAlias,
+                    args: Vec<TypeRef>,
+                    stack: &mut Vec<InternedString>)
+                    -> NormResult<TypeRef>
+    {
+        if stack.contains(&alias.name) {
+            return_err!(alias.span, "type alias `{}` is defined in terms of itself", alias.name);
+        }
+
+        if alias.type_parameters.len() != args.len() {
+            return_err!(span,
+                        "type alias `{}` expects {} type argument(s), but {} were supplied",
+                        alias.name, alias.type_parameters.len(), args.len());
+        }
+
+        // Expand the body of the alias first, treating its parameters
+        // as opaque, and only then substitute the arguments, which
+        // have already been expanded by our caller.
+        let parameters: Vec<InternedString> =
+            alias.type_parameters.iter()
+                                 .map(|tp| match *tp {
+                                     TypeParameter::Lifetime(l) => l,
+                                     TypeParameter::Id(id) => id,
+                                 })
+                                 .collect();
+        stack.push(alias.name);
+        let body = try!(self.expand(span, &parameters, &alias.type_ref, stack));
+        stack.pop();
+
+        let substitutions: Map<InternedString, TypeRef> =
+            parameters.into_iter().zip(args).collect();
+        Ok(substitute(&substitutions, &body))
+    }
+}
+
+fn substitute(substitutions: &Map<InternedString, TypeRef>, type_ref: &TypeRef) -> TypeRef {
+    match *type_ref {
+        TypeRef::Tuple(ref trs) =>
+            TypeRef::Tuple(trs.iter().map(|tr| substitute(substitutions, tr)).collect()),
+        TypeRef::Nominal { ref path, ref types } =>
+            TypeRef::Nominal { path: path.clone(),
+                               types: types.iter().map(|tr| substitute(substitutions, tr)).collect() },
+        TypeRef::Ref { lifetime, mutable, ref referent } => {
+            let lifetime = lifetime.map(|l| match substitutions.get(&l) {
+                Some(&TypeRef::Lifetime(arg)) => arg,
+                _ => l,
+            });
+            TypeRef::Ref { lifetime: lifetime,
+                           mutable: mutable,
+                           referent: Box::new(substitute(substitutions, referent)) }
+        }
+        TypeRef::OfSymbol(ref sym) =>
+            TypeRef::OfSymbol(sym.clone()),
+        TypeRef::Lifetime(id) | TypeRef::Id(id) =>
+            substitutions.get(&id).cloned().unwrap_or_else(|| type_ref.clone()),
+    }
+}
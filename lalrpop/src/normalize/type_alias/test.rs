@@ -0,0 +1,86 @@
+use parser;
+use grammar::parse_tree::{Grammar, NonterminalString, TypeRef};
+use intern::intern;
+use test_util;
+
+fn expand(grammar: &str) -> Grammar {
+    let parsed_grammar = parser::parse_grammar(&grammar).unwrap();
+    super::expand_type_aliases(parsed_grammar).unwrap()
+}
+
+fn type_decl(grammar: &Grammar, name: &str) -> TypeRef {
+    grammar.items
+           .iter()
+           .filter_map(|item| item.as_nonterminal())
+           .find(|nt| nt.name == NonterminalString(intern(name)))
+           .and_then(|nt| nt.type_decl.clone())
+           .unwrap()
+}
+
+fn check_type(grammar: &str, name: &str, expected: &str) {
+    let grammar = expand(grammar);
+    let expected = parser::parse_type_ref(expected).unwrap();
+    assert_eq!(type_decl(&grammar, name), expected);
+}
+
+fn check_err(expected_err: &str, grammar: &str, span: &str) {
+    let parsed_grammar = parser::parse_grammar(&grammar).unwrap();
+    let err = super::expand_type_aliases(parsed_grammar).unwrap_err();
+    test_util::check_norm_err(expected_err, span, err);
+}
+
+#[test]
+fn simple_alias() {
+    check_type(
+        r#"grammar; type Num = i32; A: Num = "a" => 0;"#,
+        "A",
+        "i32");
+}
+
+#[test]
+fn generic_alias() {
+    check_type(
+        r#"grammar; type Spanned<T> = (usize, T, usize); A: Vec<Spanned<String>> = "a" => vec![];"#,
+        "A",
+        "Vec<(usize, String, usize)>");
+}
+
+#[test]
+fn nested_aliases() {
+    check_type(
+        r#"grammar; type Spanned<T> = (usize, T, usize); type Expr<'a> = Spanned<&'a str>; A: Expr<'input> = "a" => (0, "", 0);"#,
+        "A",
+        "(usize, &'input str, usize)");
+}
+
+#[test]
+fn macro_argument_shadows_alias() {
+    check_type(
+        r#"grammar; type T = i32; Comma<T>: Vec<T> = T => vec![<>];"#,
+        "Comma",
+        "Vec<T>");
+}
+
+#[test]
+fn duplicate_alias() {
+    check_err(
+        r#"multiple definitions of type alias `Num`"#,
+        r#"grammar; type Num = i32; type Num = u32;"#,
+        r#"                              ~~~       "#);
+}
+
+#[test]
+fn wrong_number_of_arguments() {
+    check_err(
+        r#"type alias `Spanned` expects 1 type argument\(s\), but 2 were supplied"#,
+        r#"grammar; type Spanned<T> = (usize, T, usize); A: Spanned<u32, u32> = "a" => (0, 0, 0);"#,
+        r#"                                              ~                                       "#);
+}
+
+#[test]
+fn recursive_alias() {
+    check_err(
+        r#"type alias `List` is defined in terms of itself"#,
+        r#"grammar; type List = Vec<List>; A: List = "a" => vec![];"#,
+        r#"              ~~~~                                      "#);
+}
@@ -1,6 +1,7 @@
 use grammar::parse_tree::*;
 use grammar::pattern::*;
 use lalrpop_util;
+use regex_syntax;
 use tok;
 
 #[allow(dead_code)]
@@ -25,6 +26,15 @@ pub fn parse_grammar<'input>(input: &'input str)
     Ok(grammar)
 }
 
+/// Parses the items of a file imported with `use "file.lalrpop";`,
+/// whose text begins at `offset` in the text of the grammar.
+pub fn parse_grammar_items<'input>(input: &'input str, offset: usize)
+                                   -> Result<Vec<GrammarItem>, ParseError<'input>>
+{
+    let tokenizer = tok::Tokenizer::new(input, offset);
+    lrgrammar::parse_GrammarItems(input, tokenizer)
+}
+
 fn parse_pattern<'input>(input: &'input str, offset: usize)
                          -> Result<Pattern<TypeRef>, ParseError<'input>>
 {
@@ -39,10 +49,46 @@ fn parse_match_mapping<'input>(input: &'input str, offset: usize)
     lrgrammar::parse_MatchMapping(input, tokenizer)
 }
 
+/// Converts the text of a character literal appearing in a character
+/// range like `['a'-'z']` into the equivalent fragment of a regex
+/// character class. `location` is the start of the literal, used for
+/// error reporting.
+fn parse_char_range_char<'input>(text: &'input str, location: usize)
+                                 -> Result<String, ParseError<'input>>
+{
+    let result = match text {
+        r"\\" | r"\n" | r"\r" | r"\t" => Some(text.to_string()),
+        r"\'" => Some(format!("'")),
+        r#"\""# => Some(format!("\"")),
+        _ if text.chars().count() == 1 => Some(regex_syntax::escape(text)),
+        _ => None,
+    };
+
+    result.ok_or(lalrpop_util::ParseError::User {
+        error: tok::Error { location: location, code: tok::ErrorCode::ExpectedSingleCharacter }
+    })
+}
+
+/// Extracts the name of a Rust item like `fn foo() { ... }` or
+/// `struct Foo;`: this is the identifier following the keyword.
+fn rust_item_name(text: &str) -> &str {
+    let rest = text.trim_left_matches(|c: char| c.is_alphabetic()).trim_left();
+    let end = rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len());
+    &rest[..end]
+}
+
 #[cfg(test)]
 pub fn parse_type_ref<'input>(input: &'input str)
                               -> Result<TypeRef, ParseError<'input>>
 {
-    let tokenizer = tok::Tokenizer::new(input, 0);
+    parse_type_ref_at(input, 0)
+}
+
+/// Parses a type that begins at `offset` in some larger text, so that
+/// the spans in it are offsets into that text.
+pub fn parse_type_ref_at<'input>(input: &'input str, offset: usize)
+                                 -> Result<TypeRef, ParseError<'input>>
+{
+    let tokenizer = tok::Tokenizer::new(input, offset);
     lrgrammar::parse_TypeRef(input, tokenizer)
 }
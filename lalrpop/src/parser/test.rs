@@ -1,5 +1,5 @@
 use parser;
-use grammar::parse_tree::{GrammarItem, MatchItem};
+use grammar::parse_tree::{GrammarItem, MatchItem, Span};
 
 #[test]
 fn match_block() {
@@ -94,3 +94,219 @@ fn match_complex() {
         _ => panic!("expected MatchToken, but was: {:?}", first_item)
     }
 }
+
+#[test]
+fn char_range() {
+    let parsed = parser::parse_grammar(r#"
+        grammar;
+        match {
+            ['a'-'z' 'A'-'Z' '_'],
+            ['0'-'9' '-' '\''] => DIGIT,
+        }
+"#).unwrap();
+
+    let first_item = parsed.items.first().expect("has item");
+    match *first_item {
+        GrammarItem::MatchToken(ref data) => {
+            let contents0 = data.contents.get(0).unwrap();
+            let item00 = contents0.items.get(0).unwrap();
+            match *item00 {
+                MatchItem::Unmapped(ref sym, _) => {
+                    assert_eq!(format!("{:?}", sym), "r#\"[a-zA-Z_]\"#");
+                },
+                _ => panic!("expected MatchItem::Unmapped, but was: {:?}", item00)
+            };
+            let item01 = contents0.items.get(1).unwrap();
+            match *item01 {
+                MatchItem::Mapped(ref sym, ref mapping, _) => {
+                    assert_eq!(format!("{:?}", sym), r##"r#"[0-9\\-']"#"##);
+                    assert_eq!(format!("{}", mapping), "DIGIT");
+                },
+                _ => panic!("expected MatchItem::Mapped, but was: {:?}", item01)
+            };
+        }
+        _ => panic!("expected MatchToken, but was: {:?}", first_item)
+    }
+}
+
+#[test]
+fn char_range_multiple_characters() {
+    assert!(parser::parse_grammar(r#"grammar; X = ['ab'-'z'];"#).is_err());
+}
+
+#[test]
+fn reserved_words() {
+    let parsed = parser::parse_grammar(r#"grammar; reserved { "async", "await" }"#).unwrap();
+    let reserved = parsed.items.first().and_then(|item| item.as_reserved()).expect("reserved");
+    let words: Vec<_> = reserved.words.iter().map(|w| w.word.to_string()).collect();
+    assert_eq!(words, vec!["async", "await"]);
+}
+
+#[test]
+fn import() {
+    let text = r#"grammar; use "tokens.lalrpop"; use std::str;"#;
+    let parsed = parser::parse_grammar(text).unwrap();
+    match parsed.items[0] {
+        GrammarItem::Import(ref import) => {
+            assert_eq!(import.path.to_string(), "tokens.lalrpop");
+            assert_eq!(&text[import.span.0..import.span.1], r#"use "tokens.lalrpop""#);
+        }
+        ref item => panic!("expected an import, got {:?}", item),
+    }
+    assert_eq!(parsed.items[1], GrammarItem::Use(format!("std::str")));
+}
+
+#[test]
+fn grammar_items() {
+    // the spans are offsets into the text of the importing grammar
+    let items = parser::parse_grammar_items(r#"X = "x"; Y = X;"#, 100).unwrap();
+    assert_eq!(items.len(), 2);
+    let x = items[0].as_nonterminal().expect("nonterminal");
+    assert_eq!(x.name.to_string(), "X");
+    assert_eq!(x.span, Span(100, 101));
+}
+
+#[test]
+fn type_alias() {
+    let parsed = parser::parse_grammar(r#"grammar; type Spanned<T> = (usize, T, usize);"#).unwrap();
+    let alias = parsed.items.first().and_then(|item| item.as_type_alias()).expect("type alias");
+    assert_eq!(alias.name.to_string(), "Spanned");
+    assert_eq!(alias.type_parameters.len(), 1);
+    assert_eq!(format!("{}", alias.type_ref), "(usize, T, usize)");
+}
+
+#[test]
+fn rust_items() {
+    let parsed = parser::parse_grammar(r#"
+grammar;
+fn twice(x: u32) -> u32 { x * 2 }
+pub struct Pair(u32, u32);
+"#).unwrap();
+    let items: Vec<_> =
+        parsed.items.iter()
+                    .filter_map(|item| item.as_rust_item())
+                    .map(|item| (item.name.to_string(), item.code.clone()))
+                    .collect();
+    assert_eq!(items,
+               vec![("twice".to_string(), "fn twice(x: u32) -> u32 { x * 2 }".to_string()),
+                    ("Pair".to_string(), "pub struct Pair(u32, u32);".to_string())]);
+}
+
+#[test]
+fn operators() {
+    let parsed = parser::parse_grammar(r#"
+grammar;
+pub Expr: i32 = operators(Term) {
+    left { "+" => l + r, "-" => l - r },
+    prefix { "-" => -e },
+};
+"#).unwrap();
+    let operators = parsed.items.first().and_then(|item| item.as_operators()).expect("operators");
+    assert_eq!(operators.name.to_string(), "Expr");
+    assert!(operators.public);
+    let levels: Vec<_> =
+        operators.levels.iter()
+                        .map(|level| (level.associativity.to_string(), level.operators.len()))
+                        .collect();
+    assert_eq!(levels, vec![("left".to_string(), 2), ("prefix".to_string(), 1)]);
+}
+
+#[test]
+fn inline_test() {
+    let parsed = parser::parse_grammar(r#"grammar; #[test] Expr parses "1+2*3";"#).unwrap();
+    let test = parsed.items.first().and_then(|item| item.as_inline_test()).expect("inline test");
+    assert_eq!(test.nonterminal.to_string(), "Expr");
+    assert_eq!(test.expectation.to_string(), "parses");
+    assert_eq!(test.input.to_string(), "1+2*3");
+}
+
+#[test]
+fn pipe_separated_alternatives() {
+    let alternatives = |text: &str| -> Vec<String> {
+        let parsed = parser::parse_grammar(text).unwrap();
+        let data = parsed.items[0].as_nonterminal().expect("nonterminal");
+        data.alternatives
+            .iter()
+            .map(|a| format!("{} => {:?}", a.expr, a.action))
+            .collect()
+    };
+    let pipes = alternatives(r#"grammar;
+        Expr: i32 = {
+            | <l:Expr> "+" <r:Term> => l | r
+            | <v:Term> => { v.iter().map(|x| x + 1) }
+            | Term
+        };
+    "#);
+    let commas = alternatives(r#"grammar;
+        Expr: i32 = {
+            <l:Expr> "+" <r:Term> => l | r,
+            <v:Term> => { v.iter().map(|x| x + 1) },
+            Term
+        };
+    "#);
+    assert_eq!(pipes.len(), 3);
+    assert_eq!(pipes, commas);
+}
+
+#[test]
+fn pipe_continues_code_outside_pipe_alternatives() {
+    let parsed = parser::parse_grammar(r#"grammar;
+        Flag: bool = {
+            <a:A> <b:B> => a
+                || b,
+            C => false,
+        };
+    "#).unwrap();
+    let data = parsed.items[0].as_nonterminal().expect("nonterminal");
+    let actions: Vec<_> = data.alternatives.iter().map(|a| format!("{:?}", a.action)).collect();
+    assert_eq!(actions,
+               vec![r#"Some(User("a\n                || b"))"#.to_string(),
+                    r#"Some(User("false"))"#.to_string()]);
+}
+
+#[test]
+fn pipes_in_code_of_pipe_alternatives() {
+    let alternatives = |text: &str| -> Vec<String> {
+        let parsed = parser::parse_grammar(text).unwrap();
+        let data = parsed.items[0].as_nonterminal().expect("nonterminal");
+        data.alternatives
+            .iter()
+            .map(|a| format!("{} => {:?}", a.expr, a.action))
+            .collect()
+    };
+    let pipes = alternatives(r#"grammar;
+        Flag: bool = {
+            | <a:A> <b:B> => a || b
+            | <v:C> => v.map(|x| x)
+            | D
+        };
+    "#);
+    let commas = alternatives(r#"grammar;
+        Flag: bool = {
+            <a:A> <b:B> => a || b,
+            <v:C> => v.map(|x| x),
+            D
+        };
+    "#);
+    assert_eq!(pipes.len(), 3);
+    assert_eq!(pipes, commas);
+}
+
+#[test]
+fn alternative_cost() {
+    let parsed = parser::parse_grammar(r#"grammar;
+        Term = { Num, #[cost(4)] "(" <Term> ")", #[cost(0)] => 0 };
+    "#).unwrap();
+    let data = parsed.items[0].as_nonterminal().expect("nonterminal");
+    let costs: Vec<_> = data.alternatives.iter().map(|a| a.cost()).collect();
+    assert_eq!(costs, vec![0, 4, 0]);
+}
+
+#[test]
+fn type_parameter_bounds() {
+    let grammar = parser::parse_grammar(r#"grammar<'a, T: Clone + 'a, U> where U: Copy;"#).unwrap();
+    let type_parameters: Vec<String> =
+        grammar.type_parameters.iter().map(|tp| tp.to_string()).collect();
+    assert_eq!(type_parameters, vec!["'a", "T", "U"]);
+    assert_eq!(grammar.where_clauses, vec!["T: Clone + 'a", "U: Copy"]);
+}
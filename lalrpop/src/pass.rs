@@ -0,0 +1,19 @@
+//! A way for embedders to rewrite the grammar before the parsers are
+//! built from it -- to wrap the actions in tracing code, say, or to
+//! give the nonterminals other names.
+
+use ir;
+
+/// A pass over the normalized grammar, once given to
+/// `Configuration::add_pass`. The passes run in the order that they
+/// were added, after macros are expanded and `#[inline]` nonterminals
+/// inlined, and before the LR(1) tables are built.
+pub trait Pass {
+    /// Rewrites `grammar`. A pass can rename nonterminals by changing
+    /// their `name`, and the new name then replaces the old one wherever
+    /// else it appears, and it can change the code of the actions
+    /// written in the grammar. Any other change is an error. So is
+    /// returning `Err`, whose message is then reported against the
+    /// grammar.
+    fn run(&self, grammar: &mut ir::Grammar) -> Result<(), String>;
+}
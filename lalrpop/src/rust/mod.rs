@@ -191,6 +191,14 @@ impl<W:Write> RustWrite<W> {
             }
         }
 
+        // items the user defined in the grammar itself, which live
+        // in the action module
+        if !super_prefix.is_empty() {
+            for item in &grammar.rust_items {
+                rust!(self, "use {}{};", super_prefix, item.name);
+            }
+        }
+
         self.write_standard_uses(&grammar.prefix)
     }
 
@@ -2,10 +2,16 @@
 //! to `configuration::Configuration`, but it is not exported outside the
 //! crate. Note that all fields are public and so forth for convenience.
 
+use backend::Backend;
+use collections::{map, Map};
+use diagnostics::Diagnostics;
+use grammar::repr::LrConstruction;
 use std::default::Default;
 use std::path;
+use std::rc::Rc;
 use style::{self, Style};
 use log::{Log, Level};
+use pass::Pass;
 
 // These two, ubiquitous types are defined here so that their fields can be private
 // across crate, but visible within the crate:
@@ -22,6 +28,82 @@ pub enum ColorConfig {
     IfTty,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// LALRPOP's own format, which puts as much of the explanation
+    /// as possible in the message itself (default).
+    Human,
+
+    /// The `error[E0001]: ... --> file:line:col` format used by
+    /// `rustc`, which editors and CI tools know how to parse.
+    Rustc,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StackOverflow {
+    /// Let the stacks grow for as long as there is memory (default).
+    Grow,
+
+    /// Fail with `ParseError::NestingTooDeep`.
+    Error,
+
+    /// Abort the process.
+    Abort,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Optimize {
+    /// Table-driven parsers, with the tables and functions as the
+    /// other options say (default).
+    Balanced,
+
+    /// Table-driven parsers with pruned tables of the narrowest
+    /// integers, and reductions kept out of line.
+    Size,
+
+    /// Recursive ascent where the grammar allows it, and otherwise
+    /// uncompressed tables of `i32`s, with the reductions inlined.
+    Speed,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Visibility {
+    /// `pub` (default).
+    Pub,
+
+    /// `pub(crate)`.
+    PubCrate,
+
+    /// No visibility qualifier: the items can only be used by the
+    /// module into which the generated code is `include!`d.
+    Private,
+}
+
+impl Visibility {
+    /// The qualifier to write before an item, including the
+    /// trailing space if there is one.
+    pub fn qualifier(self) -> &'static str {
+        match self {
+            Visibility::Pub => "pub ",
+            Visibility::PubCrate => "pub(crate) ",
+            Visibility::Private => "",
+        }
+    }
+}
+
+/// How deep the stacks of the table-driven parsers may get before
+/// `StackOverflow::Error` or `StackOverflow::Abort` applies, unless
+/// `stack_capacity` says otherwise.
+pub const DEFAULT_STACK_LIMIT: usize = 10_000;
+
+/// The most productions that one function of a table-driven parser
+/// reduces, unless `reductions_per_fn` says otherwise.
+pub const DEFAULT_REDUCTIONS_PER_FN: usize = 500;
+
+/// The most reductions shown in each example of a conflict report,
+/// unless `max_example_reductions` says otherwise.
+pub const DEFAULT_MAX_EXAMPLE_REDUCTIONS: usize = 12;
+
 /// Various options to control debug output. Although this struct is
 /// technically part of LALRPOP's exported interface, it is not
 /// considered part of the semver guarantees as end-users are not
@@ -32,6 +114,11 @@ pub struct Session {
 
     pub force_build: bool,
 
+    /// Print a `cargo:rerun-if-changed` line for each grammar
+    /// processed, so that cargo only runs the build script again when
+    /// one of them changes.
+    pub emit_rerun_directives: bool,
+
     pub in_dir: Option<path::PathBuf>,
 
     pub out_dir: Option<path::PathBuf>,
@@ -40,11 +127,117 @@ pub struct Session {
     /// forth.
     pub emit_comments: bool,
 
+    /// Emit the functions through which a callback can watch the
+    /// table-driven parsers as they parse
+    pub emit_introspection: bool,
+
+    /// Emit the trait through which an observer can follow the shifts,
+    /// reductions and errors of the table-driven parsers
+    pub emit_observer: bool,
+
     /// Emit report file about generated code
     pub emit_report: bool,
 
+    /// Emit a Graphviz `.dot` file of the LR automaton of each public
+    /// nonterminal, next to the generated parser
+    pub emit_dot: bool,
+
+    /// Record the whitespace that the lexer skips in snapshots, as
+    /// trivia on the terminals next to it
+    pub snapshot_trivia: bool,
+
+    /// Where to write SVG pictures of the examples in conflict
+    /// reports, if anywhere.
+    pub conflict_svg_dir: Option<path::PathBuf>,
+
+    /// Where to write an HTML report on the states and conflicts of
+    /// each public nonterminal, if anywhere.
+    pub html_report_dir: Option<path::PathBuf>,
+
+    /// The most reductions shown in each example of a conflict report;
+    /// the ones in the middle of deeper derivations are collapsed into
+    /// a single `…` row. `None` shows them all.
+    pub max_example_reductions: Option<usize>,
+
+    /// Where errors, warnings, conflict reports and log messages go;
+    /// `None` prints them.
+    pub diagnostics: Option<Rc<Diagnostics>>,
+
+    /// What to generate from each grammar; `None` generates the Rust
+    /// code of its parsers.
+    pub backend: Option<Rc<Backend>>,
+
+    /// The passes to run on each grammar once it is normalized, in
+    /// order.
+    pub passes: Vec<Rc<Pass>>,
+
+    /// How many entries to make room for in the stacks of the
+    /// table-driven parsers when they start; `None` starts them empty.
+    pub stack_capacity: Option<usize>,
+
+    /// What the table-driven parsers do when a token would be shifted
+    /// onto stacks that are `stack_capacity` deep.
+    pub stack_overflow: StackOverflow,
+
+    /// The most productions that one function of a table-driven
+    /// parser reduces; those of bigger grammars are split among
+    /// several, since rustc is slow to compile one huge `match`.
+    pub reductions_per_fn: usize,
+
+    /// Leave out of the tables of each table-driven parser the
+    /// nonterminals, and the reductions of productions, that its
+    /// public nonterminal cannot reach.
+    pub prune_tables: bool,
+
+    /// The most LR states that the parser for one public nonterminal
+    /// may have; the construction gives up as soon as it needs more.
+    /// `None` sets no limit.
+    pub max_states: Option<usize>,
+
+    /// The most entries that the action and goto tables of the parser
+    /// for one public nonterminal may have together. `None` sets no
+    /// limit.
+    pub max_table_entries: Option<usize>,
+
+    /// Write the action and goto tables of the table-driven parsers
+    /// to binary files next to the generated code, which embeds them
+    /// with `include_bytes!`, rather than into the code itself.
+    pub binary_tables: bool,
+
+    /// Write the action and goto tables of the table-driven parsers in
+    /// full, with an entry for each state and symbol, rather than
+    /// compressed
+    pub dense_tables: bool,
+
+    /// Whether the generated code favors its size or the speed of
+    /// the parsers (see `Configuration::optimize_for_size`)
+    pub optimize: Optimize,
+
+    /// How to construct the states of grammars that do not choose,
+    /// with an annotation like `#[LALR]`; `None` is canonical LR(1)
+    /// (see `Configuration::use_lalr`).
+    pub construction: Option<LrConstruction>,
+
+    /// The visibility of the entry points of the generated parsers
+    /// (`parse_Foo` and so forth) and of `display_error`.
+    pub visibility: Visibility,
+
+    /// The path, from the root of the crate, of the module in which
+    /// the parsers find the scaffolding that does not depend on their
+    /// grammar; `None` gives each parser its own copy.
+    pub support_module: Option<String>,
+
+    /// The values to substitute for `${NAME}` in grammars.
+    pub variables: Map<String, String>,
+
+    /// Texts to use instead of the default ones of the message
+    /// catalog, by code (see `Configuration::set_message`).
+    pub messages: Map<String, String>,
+
     pub color_config: ColorConfig,
 
+    pub error_format: ErrorFormat,
+
     /// Stop after you find `max_errors` errors. If this value is 0,
     /// report *all* errors. Note that we MAY always report more than
     /// this value if we so choose.
@@ -88,9 +281,35 @@ impl Session {
             in_dir: None,
             out_dir: None,
             force_build: false,
+            emit_rerun_directives: false,
             emit_comments: false,
+            emit_introspection: false,
+            emit_observer: false,
             emit_report: false,
+            emit_dot: false,
+            snapshot_trivia: false,
+            conflict_svg_dir: None,
+            html_report_dir: None,
+            max_example_reductions: Some(DEFAULT_MAX_EXAMPLE_REDUCTIONS),
+            diagnostics: None,
+            backend: None,
+            passes: vec![],
+            stack_capacity: None,
+            stack_overflow: StackOverflow::default(),
+            reductions_per_fn: DEFAULT_REDUCTIONS_PER_FN,
+            prune_tables: false,
+            max_states: None,
+            max_table_entries: None,
+            binary_tables: false,
+            dense_tables: false,
+            optimize: Optimize::Balanced,
+            construction: None,
+            visibility: Visibility::default(),
+            support_module: None,
+            variables: map(),
+            messages: map(),
             color_config: ColorConfig::default(),
+            error_format: ErrorFormat::default(),
             max_errors: 1,
             heading: style::FG_WHITE.with(style::BOLD),
             ambig_symbols: style::FG_WHITE,
@@ -112,9 +331,35 @@ impl Session {
             in_dir: None,
             out_dir: None,
             force_build: false,
+            emit_rerun_directives: false,
             emit_comments: false,
+            emit_introspection: false,
+            emit_observer: false,
             emit_report: false,
+            emit_dot: false,
+            snapshot_trivia: false,
+            conflict_svg_dir: None,
+            html_report_dir: None,
+            max_example_reductions: Some(DEFAULT_MAX_EXAMPLE_REDUCTIONS),
+            diagnostics: None,
+            backend: None,
+            passes: vec![],
+            stack_capacity: None,
+            stack_overflow: StackOverflow::default(),
+            reductions_per_fn: DEFAULT_REDUCTIONS_PER_FN,
+            prune_tables: false,
+            max_states: None,
+            max_table_entries: None,
+            binary_tables: false,
+            dense_tables: false,
+            optimize: Optimize::Balanced,
+            construction: None,
+            visibility: Visibility::default(),
+            support_module: None,
+            variables: map(),
+            messages: map(),
             color_config: ColorConfig::IfTty,
+            error_format: ErrorFormat::Human,
             max_errors: 1,
             heading: Style::new(),
             ambig_symbols: Style::new(),
@@ -128,6 +373,16 @@ impl Session {
         }
     }
 
+    /// How deep the stacks of the table-driven parsers may get, if
+    /// they are not to grow for as long as there is memory.
+    pub fn stack_limit(&self) -> Option<usize> {
+        match self.stack_overflow {
+            StackOverflow::Grow => None,
+            StackOverflow::Error | StackOverflow::Abort =>
+                Some(self.stack_capacity.unwrap_or(DEFAULT_STACK_LIMIT)),
+        }
+    }
+
     /// Indicates whether we should stop after `actual_errors` number
     /// of errors have been reported.
     pub fn stop_after(&self, actual_errors: usize) -> bool {
@@ -137,7 +392,14 @@ impl Session {
     pub fn log<M>(&self, level: Level, message: M)
         where M: FnOnce() -> String
     {
-        self.log.log(level, message)
+        match self.diagnostics {
+            Some(ref diagnostics) => {
+                if self.log.enabled(level) {
+                    diagnostics.log(level, &message());
+                }
+            }
+            None => self.log.log(level, message),
+        }
     }
 }
 
@@ -152,3 +414,21 @@ impl Default for ColorConfig {
         ColorConfig::IfTty
     }
 }
+
+impl Default for ErrorFormat {
+    fn default() -> Self {
+        ErrorFormat::Human
+    }
+}
+
+impl Default for StackOverflow {
+    fn default() -> Self {
+        StackOverflow::Grow
+    }
+}
+
+impl Default for Visibility {
+    fn default() -> Self {
+        Visibility::Pub
+    }
+}
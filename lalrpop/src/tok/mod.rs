@@ -22,7 +22,9 @@ pub enum ErrorCode {
     UnterminatedStringLiteral,
     UnterminatedCharacterLiteral,
     UnterminatedCode,
+    UnterminatedComment,
     ExpectedStringLiteral,
+    ExpectedSingleCharacter,
 }
 
 fn error<T>(c: ErrorCode, l: usize) -> Result<T,Error> {
@@ -32,20 +34,25 @@ fn error<T>(c: ErrorCode, l: usize) -> Result<T,Error> {
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Tok<'input> {
     // Keywords;
+    Const, // only in `const NAME = "...";`, see `terminal_const_follows`
     Enum,
     Extern,
     Grammar,
+    Import, // `use` in `use "file.lalrpop";`, see `import_follows`
     Match,
     Else,
     If,
     Mut,
+    Operators, // only in `= operators(...)`, see `operator_table_follows`
     Pub,
+    Reserved, // only in `reserved { ... }`, see `reserved_words_follow`
     Type,
 
     // Special keywords: these are accompanied by a series of
     // uninterpreted strings representing imports and stuff.
     Use(&'input str),
     Where(Vec<&'input str>),
+    RustItem(&'input str), // includes the keyword and trailing `;` or `}`
 
     // Identifiers of various kinds:
     Escape(&'input str),
@@ -55,6 +62,7 @@ pub enum Tok<'input> {
     StringLiteral(&'input str), // excludes the `"`
     CharLiteral(&'input str), // excludes the `'`
     RegexLiteral(&'input str), // excludes the `r"` and `"`
+    Num(&'input str), // only in annotation arguments, like `#[cost(2)]`
 
     // Symbols:
     Ampersand,
@@ -78,6 +86,9 @@ pub enum Tok<'input> {
     LessThan,
     Lookahead, // @L
     Lookbehind, // @R
+    LookaheadToken, // @T
+    Minus,
+    Pipe,
     Plus,
     Question,
     RightBrace,
@@ -95,6 +106,14 @@ pub struct Tokenizer<'input> {
     chars: CharIndices<'input>,
     lookahead: Option<(usize, char)>,
     shift: usize,
+
+    // the last token produced, which decides whether `operators` and
+    // `reserved` are keywords
+    previous: Option<Tok<'input>>,
+
+    // inside a `{ | A => ... | B => ... }` body, where a `|` that
+    // begins a line ends the action code before it
+    pipe_alternatives: bool,
 }
 
 macro_rules! eof {
@@ -125,6 +144,8 @@ impl<'input> Tokenizer<'input> {
             chars: text.char_indices(),
             lookahead: None,
             shift: shift,
+            previous: None,
+            pipe_alternatives: false,
         };
         t.bump();
         t
@@ -203,6 +224,7 @@ impl<'input> Tokenizer<'input> {
                 }
                 Some((idx0, '{')) => {
                     self.bump();
+                    self.pipe_alternatives = self.text[idx0+1..].trim_left().starts_with('|');
                     Some(Ok((idx0, LeftBrace, idx0+1)))
                 }
                 Some((idx0, '[')) => {
@@ -227,21 +249,34 @@ impl<'input> Tokenizer<'input> {
                             self.bump();
                             Some(Ok((idx0, Lookbehind, idx1+1)))
                         }
+                        Some((idx1, 'T')) => {
+                            self.bump();
+                            Some(Ok((idx0, LookaheadToken, idx1+1)))
+                        }
                         _ => {
                             Some(error(UnrecognizedToken, idx0))
                         }
                     }
                 }
+                Some((idx0, '-')) => {
+                    self.bump();
+                    Some(Ok((idx0, Minus, idx0+1)))
+                }
                 Some((idx0, '+')) => {
                     self.bump();
                     Some(Ok((idx0, Plus, idx0+1)))
                 }
+                Some((idx0, '|')) => {
+                    self.bump();
+                    Some(Ok((idx0, Pipe, idx0+1)))
+                }
                 Some((idx0, '?')) => {
                     self.bump();
                     Some(Ok((idx0, Question, idx0+1)))
                 }
                 Some((idx0, '}')) => {
                     self.bump();
+                    self.pipe_alternatives = false;
                     Some(Ok((idx0, RightBrace, idx0+1)))
                 }
                 Some((idx0, ']')) => {
@@ -314,6 +349,10 @@ impl<'input> Tokenizer<'input> {
                         Some(self.identifierish(idx0))
                     }
                 }
+                Some((idx0, c)) if c.is_digit(10) => {
+                    let end = self.take_while(|c| c.is_digit(10)).unwrap_or(self.text.len());
+                    Some(Ok((idx0, Num(&self.text[idx0..end]), end)))
+                }
                 Some((_, c)) if c.is_whitespace() => {
                     self.bump();
                     continue;
@@ -380,33 +419,15 @@ impl<'input> Tokenizer<'input> {
     fn code(&mut self, idx0: usize, open_delims: &str, close_delims: &str) -> Result<usize, Error> {
         // This is the interesting case. To find the end of the code,
         // we have to scan ahead, matching (), [], and {}, and looking
-        // for a suitable terminator: `,`, `;`, `]`, `}`, or `)`.
+        // for a suitable terminator: `,`, `;`, `]`, `}`, or `)`, or,
+        // in the `{ | A => ... | B => ... }` form, a `|` that begins
+        // a line, which starts the next alternative.
         // Additionaly we had to take into account that we can encounter an character literal
-        // equal to one of delimeters.
+        // equal to one of delimeters, and string literals or comments containing them.
         let mut balance = 0; // number of unclosed `(` etc
         loop {
             if let Some((idx, c)) = self.lookahead {
-                if c == '"' {
-                    self.bump();
-                    try!(self.string_literal(idx)); // discard the produced token
-                    continue;
-                } else if c == '\'' {
-                    self.bump();
-                    if self.take_lifetime_or_character_literal().is_none() {
-                        return error(UnterminatedCharacterLiteral, idx);
-                    }
-                    continue;
-                } else if c == 'r' {
-                    self.bump();
-                    if let Some((idx, '#')) = self.lookahead {
-                        try!(self.regex_literal(idx));
-                    }
-                    continue;
-                } else if c == '/' {
-                    self.bump();
-                    if let Some((_, '/')) = self.lookahead {
-                        self.take_until(|c| c == '\n');
-                    }
+                if try!(self.skip_literal_or_comment(idx, c)) {
                     continue;
                 } else if open_delims.find(c).is_some() {
                     balance += 1;
@@ -417,7 +438,8 @@ impl<'input> Tokenizer<'input> {
                 } else {
                     debug_assert!(balance == 0);
 
-                    if c == ',' || c == ';' || close_delims.find(c).is_some() {
+                    if c == ',' || c == ';' || close_delims.find(c).is_some() ||
+                        (c == '|' && self.pipe_alternatives && self.begins_line(idx)) {
                         // Note: we do not consume the
                         // terminator. The code is everything *up
                         // to but not including* the terminating
@@ -439,6 +461,116 @@ impl<'input> Tokenizer<'input> {
         }
     }
 
+    /// True if only whitespace precedes `idx` on its line.
+    fn begins_line(&self, idx: usize) -> bool {
+        self.text[..idx].chars().rev().take_while(|&c| c != '\n').all(|c| c.is_whitespace())
+    }
+
+    /// Skips over a string, character or regex literal, or a comment,
+    /// starting with the character `c` at `idx`. Returns true if
+    /// anything was consumed, in which case the caller should examine
+    /// the new lookahead.
+    fn skip_literal_or_comment(&mut self, idx: usize, c: char) -> Result<bool, Error> {
+        if c == '"' {
+            self.bump();
+            try!(self.string_literal(idx)); // discard the produced token
+        } else if c == '\'' {
+            self.bump();
+            if self.take_lifetime_or_character_literal().is_none() {
+                return error(UnterminatedCharacterLiteral, idx);
+            }
+        } else if c == 'r' {
+            self.bump();
+            match self.lookahead {
+                Some((_, '#')) | Some((_, '"')) => {
+                    // a raw string like `r"..."` or `r#"..."#`
+                    try!(self.regex_literal(idx));
+                }
+                _ => { }
+            }
+        } else if c == '/' {
+            self.bump();
+            match self.lookahead {
+                Some((_, '/')) => {
+                    self.take_until(|c| c == '\n');
+                }
+                Some((_, '*')) => {
+                    self.bump();
+                    try!(self.block_comment(idx));
+                }
+                _ => { }
+            }
+        } else {
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    // skips a (possibly nested) `/* ... */` comment; starts after the
+    // `/*` has been consumed; idx0 points at the `/`
+    fn block_comment(&mut self, idx0: usize) -> Result<(), Error> {
+        let mut depth = 1;
+        loop {
+            match self.lookahead {
+                Some((_, '*')) => {
+                    if let Some((_, '/')) = self.bump() {
+                        self.bump();
+                        depth -= 1;
+                        if depth == 0 {
+                            return Ok(());
+                        }
+                    }
+                }
+                Some((_, '/')) => {
+                    if let Some((_, '*')) = self.bump() {
+                        self.bump();
+                        depth += 1;
+                    }
+                }
+                Some(_) => {
+                    self.bump();
+                }
+                None => {
+                    return error(UnterminatedComment, idx0);
+                }
+            }
+        }
+    }
+
+    fn rust_item(&mut self, idx0: usize) -> Result<usize, Error> {
+        // Scan ahead to the end of an item like `fn foo() { ... }`,
+        // `struct Foo(u32);` or `const FOO: u32 = 22;`: that is, to
+        // the first `;` outside of any delimiters, or to the `}`
+        // closing the body of the item, whichever comes first. Unlike
+        // `code`, the terminator is included in the item.
+        let mut balance = 0; // number of unclosed `(` etc
+        loop {
+            if let Some((idx, c)) = self.lookahead {
+                if try!(self.skip_literal_or_comment(idx, c)) {
+                    continue;
+                } else if "([{".find(c).is_some() {
+                    balance += 1;
+                } else if "}])".find(c).is_some() {
+                    if balance == 0 {
+                        return error(UnterminatedCode, idx0);
+                    }
+                    balance -= 1;
+                    if balance == 0 && c == '}' {
+                        self.bump();
+                        return Ok(idx + 1);
+                    }
+                } else if c == ';' && balance == 0 {
+                    self.bump();
+                    return Ok(idx + 1);
+                }
+            } else {
+                return error(UnterminatedCode, idx0);
+            }
+
+            self.bump();
+        }
+    }
+
     fn escape(&mut self, idx0: usize) -> Result<Spanned<Tok<'input>>, Error> {
         match self.take_until(|c| c == '`') {
             Some(idx1) => {
@@ -561,6 +693,40 @@ impl<'input> Tokenizer<'input> {
         }
     }
 
+    /// True if the `use` that ends at `idx` imports another grammar
+    /// file, as in `use "tokens.lalrpop";`, rather than being a Rust
+    /// `use` item, which starts with a path.
+    fn import_follows(&self, idx: usize) -> bool {
+        self.text[idx..].trim_left().starts_with('"')
+    }
+
+    /// True if the `const` that ends at `idx` declares a terminal
+    /// constant like `const LBRACE = "{";`, rather than starting a
+    /// Rust item, which would have to give the type of the constant.
+    fn terminal_const_follows(&self, idx: usize) -> bool {
+        let rest = self.text[idx..].trim_left();
+        let rest = rest.trim_left_matches(is_identifier_continue);
+        rest.trim_left().starts_with('=')
+    }
+
+    /// True if the `operators` that ends at `idx` starts an operator
+    /// table, as in `Expr = operators(Term) { ... }`, rather than
+    /// naming a nonterminal or a binding.
+    fn operator_table_follows(&self, idx: usize) -> bool {
+        self.previous == Some(Equals) && self.text[idx..].trim_left().starts_with('(')
+    }
+
+    /// True if the `reserved` that ends at `idx` starts a grammar item
+    /// like `reserved { "async" }`, rather than naming a nonterminal or
+    /// a binding.
+    fn reserved_words_follow(&self, idx: usize) -> bool {
+        let item_start = match self.previous {
+            None | Some(Semi) | Some(RightBrace) | Some(RightBracket) | Some(RustItem(_)) => true,
+            _ => false,
+        };
+        item_start && self.text[idx..].trim_left().starts_with('{')
+    }
+
     // Saw a `'`, could either be: `'a` or `'a'`.
     fn lifetimeish(&mut self, idx0: usize) -> Result<Spanned<Tok<'input>>, Error> {
         match self.lookahead {
@@ -598,12 +764,26 @@ impl<'input> Tokenizer<'input> {
             return Ok((idx0, Underscore, idx0+1));
         }
 
+        if word == "use" && self.import_follows(end) {
+            return Ok((start, Import, end));
+        }
+
         if word == "use" {
             let code_end = try!(self.code(idx0, "([{", "}])"));
             let code = &self.text[end..code_end];
             return Ok((start, Tok::Use(code), code_end));
         }
 
+        if word == "const" && self.terminal_const_follows(end) {
+            return Ok((start, Const, end));
+        }
+
+        if word == "fn" || word == "const" || word == "struct" {
+            let item_end = try!(self.rust_item(idx0));
+            let item = &self.text[start..item_end];
+            return Ok((start, Tok::RustItem(item), item_end));
+        }
+
         if word == "where" {
             let mut wcs = vec![];
             let mut wc_start = end;
@@ -629,6 +809,14 @@ impl<'input> Tokenizer<'input> {
             return Ok((start, Tok::Where(wcs), wc_end));
         }
 
+        if word == "operators" && self.operator_table_follows(end) {
+            return Ok((start, Operators, end));
+        }
+
+        if word == "reserved" && self.reserved_words_follow(end) {
+            return Ok((start, Reserved, end));
+        }
+
         let tok =
             // search for a keyword first; if none are found, this is
             // either a MacroId or an Id, depending on whether there
@@ -695,8 +883,10 @@ impl<'input> Iterator for Tokenizer<'input> {
         match self.next_unshifted() {
             None =>
                 None,
-            Some(Ok((l, t, r))) =>
-                Some(Ok((l+self.shift, t, r+self.shift))),
+            Some(Ok((l, t, r))) => {
+                self.previous = Some(t.clone());
+                Some(Ok((l+self.shift, t, r+self.shift)))
+            }
             Some(Err(Error { location, code })) =>
                 Some(Err(Error { location: location+self.shift, code: code })),
         }
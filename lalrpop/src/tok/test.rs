@@ -54,6 +54,15 @@ fn basic() {
     ]);
 }
 
+#[test]
+fn lookaround() {
+    test("@L @R @T", vec![
+        ("~~      ", Lookahead),
+        ("   ~~   ", Lookbehind),
+        ("      ~~", LookaheadToken),
+    ]);
+}
+
 #[test]
 fn eol_comment() {
     test("extern // This is a comment$ foo", vec![
@@ -307,6 +316,59 @@ fn code_comment_eol() {
 )~", Comma)]);
 }
 
+#[test]
+fn code_block_comment() {
+    test(r#"=> a(/* ) /* nested ) */ , */ b),"#, vec![
+        (r#"~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~ "#, EqualsGreaterThanCode(" a(/* ) /* nested ) */ , */ b)")),
+        (r#"                                ~"#, Comma),
+    ]);
+}
+
+#[test]
+fn code_raw_string() {
+    test(r#"=> a(r"\", '}'),"#, vec![
+        (r#"~~~~~~~~~~~~~~~ "#, EqualsGreaterThanCode(r#" a(r"\", '}')"#)),
+        (r#"               ~"#, Comma),
+    ]);
+}
+
+#[test]
+fn code_pipe_begins_line() {
+    test("{ | x => a | b$    | c }", vec![
+        ("~                       ", LeftBrace),
+        ("  ~                     ", Pipe),
+        ("    ~                   ", Id("x")),
+        ("      ~~~~~~~~~~~~~     ", EqualsGreaterThanCode(" a | b\n    ")),
+        ("                   ~    ", Pipe),
+        ("                     ~  ", Id("c")),
+        ("                       ~", RightBrace),
+    ]);
+}
+
+#[test]
+fn code_pipe_begins_line_without_pipe_alternatives() {
+    test("=> a$    || b,", vec![
+        ("~~~~~~~~~~~~~ ", EqualsGreaterThanCode(" a\n    || b")),
+        ("             ~", Comma),
+    ]);
+}
+
+#[test]
+fn code_multi_statement_block() {
+    test(r#"=> { let x = "}"; /* { */ if x.is_empty() { 0 } else { 1 } },"#, vec![
+        (r#"~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~ "#,
+         EqualsGreaterThanCode(r#" { let x = "}"; /* { */ if x.is_empty() { 0 } else { 1 } }"#)),
+        (r#"                                                            ~"#, Comma),
+    ]);
+}
+
+#[test]
+fn code_error_unterminated_block_comment() {
+    test_err(r#"=> a(/* b),"#,
+            (r#"     ~     "#, ErrorCode::UnterminatedComment)
+    )
+}
+
 #[test]
 fn code2() {
     test("=>? a(b, c),", vec![
@@ -324,6 +386,21 @@ fn code_forgot_comma() {
     ]);
 }
 
+#[test]
+fn unicode_ids() {
+    // spans are byte offsets, and `Σ` and `ä` take two bytes each
+    test("Σ = <ä:Zahl>;", vec![
+        ("~~              ", Id("Σ")),
+        ("   ~            ", Equals),
+        ("     ~          ", LessThan),
+        ("      ~~        ", Id("ä")),
+        ("        ~       ", Colon),
+        ("         ~~~~   ", Id("Zahl")),
+        ("             ~  ", GreaterThan),
+        ("              ~ ", Semi),
+    ]);
+}
+
 #[test]
 fn various_kinds_of_ids() {
     test("foo<T<'a,U,`Z*{}`>>", vec![
@@ -368,6 +445,42 @@ fn use2() {
     ]);
 }
 
+#[test]
+fn import() {
+    test(r#"use "tokens.lalrpop";"#, vec![
+        (r#"~~~                  "#, Import),
+        (r#"    ~~~~~~~~~~~~~~~~ "#, StringLiteral("tokens.lalrpop")),
+        (r#"                    ~"#, Semi),
+    ]);
+}
+
+#[test]
+fn rust_item_fn() {
+    test(r#"fn foo() { if x { "}" } } X"#, vec![
+        (r#"~~~~~~~~~~~~~~~~~~~~~~~~~  "#, RustItem(r#"fn foo() { if x { "}" } }"#)),
+        (r#"                          ~"#, Id("X")),
+    ]);
+}
+
+#[test]
+fn rust_item_struct_and_const() {
+    test(r#"struct Foo(u32, u32); const X: [u8; 2] = [1, 2];"#, vec![
+        (r#"~~~~~~~~~~~~~~~~~~~~~                           "#, RustItem("struct Foo(u32, u32);")),
+        (r#"                      ~~~~~~~~~~~~~~~~~~~~~~~~~~"#, RustItem("const X: [u8; 2] = [1, 2];")),
+    ]);
+}
+
+#[test]
+fn terminal_const() {
+    test(r#"const LBRACE = "{";"#, vec![
+        (r#"~~~~~              "#, Const),
+        (r#"      ~~~~~~       "#, Id("LBRACE")),
+        (r#"             ~     "#, Equals),
+        (r#"               ~~~ "#, StringLiteral("{")),
+        (r#"                  ~"#, Semi),
+    ]);
+}
+
 #[test]
 fn where1() {
     test(r#"where <foo,bar>,baz;"#, vec![
@@ -406,3 +519,87 @@ fn char_literals() {
 }
 
 
+
+#[test]
+fn char_range() {
+    test(r#"['a'-'z' '_']"#, vec![
+        (r#"~            "#, LeftBracket),
+        (r#" ~~~         "#, CharLiteral("a")),
+        (r#"    ~        "#, Minus),
+        (r#"     ~~~     "#, CharLiteral("z")),
+        (r#"         ~~~ "#, CharLiteral("_")),
+        (r#"            ~"#, RightBracket),
+    ]);
+}
+
+#[test]
+fn reserved() {
+    test(r#"reserved { "async" }"#, vec![
+        (r#"~~~~~~~~            "#, Reserved),
+        (r#"         ~          "#, LeftBrace),
+        (r#"           ~~~~~~~  "#, StringLiteral("async")),
+        (r#"                   ~"#, RightBrace),
+    ]);
+}
+
+#[test]
+fn reserved_as_identifier() {
+    test(r#"grammar; reserved: X = <reserved:Id>; }reserved {"#, vec![
+        (r#"~~~~~~~                                          "#, Grammar),
+        (r#"       ~                                         "#, Semi),
+        (r#"         ~~~~~~~~                                "#, Id("reserved")),
+        (r#"                 ~                               "#, Colon),
+        (r#"                   ~                             "#, Id("X")),
+        (r#"                     ~                           "#, Equals),
+        (r#"                       ~                         "#, LessThan),
+        (r#"                        ~~~~~~~~                 "#, Id("reserved")),
+        (r#"                                ~                "#, Colon),
+        (r#"                                 ~~              "#, Id("Id")),
+        (r#"                                   ~             "#, GreaterThan),
+        (r#"                                    ~            "#, Semi),
+        (r#"                                      ~          "#, RightBrace),
+        (r#"                                       ~~~~~~~~  "#, Reserved),
+        (r#"                                                ~"#, LeftBrace),
+    ]);
+}
+
+#[test]
+fn operators() {
+    test(r#"= operators(Term) {"#, vec![
+        (r#"~                  "#, Equals),
+        (r#"  ~~~~~~~~~        "#, Operators),
+        (r#"           ~       "#, LeftParen),
+        (r#"            ~~~~   "#, Id("Term")),
+        (r#"                ~  "#, RightParen),
+        (r#"                  ~"#, LeftBrace),
+    ]);
+}
+
+#[test]
+fn operators_as_identifier() {
+    test(r#"<operators:Op*> operators(X)"#, vec![
+        (r#"~                           "#, LessThan),
+        (r#" ~~~~~~~~~                  "#, Id("operators")),
+        (r#"          ~                 "#, Colon),
+        (r#"           ~~               "#, Id("Op")),
+        (r#"             ~              "#, Star),
+        (r#"              ~             "#, GreaterThan),
+        (r#"                ~~~~~~~~~   "#, Id("operators")),
+        (r#"                         ~  "#, LeftParen),
+        (r#"                          ~ "#, Id("X")),
+        (r#"                           ~"#, RightParen),
+    ]);
+}
+
+#[test]
+fn annotation_argument() {
+    test(r#"#[cost(12)]"#, vec![
+        (r#"~          "#, Hash),
+        (r#" ~         "#, LeftBracket),
+        (r#"  ~~~~     "#, Id("cost")),
+        (r#"      ~    "#, LeftParen),
+        (r#"       ~~  "#, Num("12")),
+        (r#"         ~ "#, RightParen),
+        (r#"          ~"#, RightBracket),
+    ]);
+}
@@ -17,6 +17,12 @@ impl<'a,S:Display> Display for Sep<&'a Vec<S>> {
     }
 }
 
+/// Makes a Rust identifier out of a name from the grammar (a
+/// nonterminal, which may use any XID characters, or a terminal),
+/// keeping ASCII letters and digits and writing everything else as
+/// `_` followed by its code point in hex and another `_`. Since `_`
+/// itself becomes `__`, distinct names never give the same
+/// identifier: without the closing `_`, `éa` and `\u{e9a}` would.
 pub struct Escape<S>(pub S);
 
 impl<S:Display> Display for Escape<S> {
@@ -26,7 +32,46 @@ impl<S:Display> Display for Escape<S> {
             match c {
                 'a' ... 'z' | '0' ... '9' | 'A' ... 'Z' => try!(write!(fmt, "{}", c)),
                 '_' => try!(write!(fmt, "__")),
-                _ => try!(write!(fmt, "_{:x}", c as usize)),
+                _ => try!(write!(fmt, "_{:x}_", c as usize)),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes text for inclusion in XML or HTML, replacing the characters
+/// that would be taken for markup with entities.
+pub struct XmlEscape<S>(pub S);
+
+impl<S:Display> Display for XmlEscape<S> {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        let tmp = format!("{}", self.0);
+        for c in tmp.chars() {
+            match c {
+                '&' => try!(write!(fmt, "&amp;")),
+                '<' => try!(write!(fmt, "&lt;")),
+                '>' => try!(write!(fmt, "&gt;")),
+                '"' => try!(write!(fmt, "&quot;")),
+                _ => try!(write!(fmt, "{}", c)),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Escapes text for a quoted string in a Graphviz `.dot` file, where
+/// each line ends in `\l` so that it is aligned to the left.
+pub struct DotEscape<S>(pub S);
+
+impl<S:Display> Display for DotEscape<S> {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        let tmp = format!("{}", self.0);
+        for c in tmp.chars() {
+            match c {
+                '\\' => try!(write!(fmt, "\\\\")),
+                '"' => try!(write!(fmt, "\\\"")),
+                '\n' => try!(write!(fmt, "\\l")),
+                _ => try!(write!(fmt, "{}", c)),
             }
         }
         Ok(())